@@ -11,6 +11,8 @@ fn run_command(bin: &str, args: &[&str]) -> Result<String, Box<dyn Error>> {
 }
 
 fn main() {
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/oracle_core.proto").unwrap();
     println!("cargo:rustc-env=GIT_COMMIT_INFO=");
     if let Ok(git_path) = run_command("git", &["rev-parse", "--show-cdup"]) {
         // Check whether .git repository belongs to oracle-core, since GitHub releases do not include .git
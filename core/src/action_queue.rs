@@ -0,0 +1,351 @@
+//! Persistent queue of the pool actions of the daemon (datapoint publication, refresh and reward
+//! extraction). The action decided by the main loop is queued in the store before its tx is built,
+//! a failed attempt is retried after the backoff of the retry policy of its kind, and the action is
+//! complete only once its tx is confirmed. An evicted or failed tx puts the action back in the
+//! queue, it is built again from the current state. The queue survives a restart of the daemon, so
+//! the attempts and backoffs of an action carry over. The queued actions of an epoch the pool left
+//! are abandoned.
+
+use serde::Serialize;
+
+use crate::downtime::unix_now;
+use crate::node_interface::TxId;
+use crate::pool_commands::PoolCommand;
+use crate::store::{self, Store, StoreError, TxStatus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    PublishDatapoint,
+    Refresh,
+    RewardExtraction,
+}
+
+impl ActionKind {
+    /// Also the action of its txs in the tx history
+    pub fn name(&self) -> &'static str {
+        match self {
+            ActionKind::PublishDatapoint => "publish_datapoint",
+            ActionKind::Refresh => "refresh",
+            ActionKind::RewardExtraction => "reward_extraction",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, StoreError> {
+        [
+            ActionKind::PublishDatapoint,
+            ActionKind::Refresh,
+            ActionKind::RewardExtraction,
+        ]
+        .iter()
+        .copied()
+        .find(|kind| kind.name() == name)
+        .ok_or_else(|| StoreError::UnknownValue(name.to_string()))
+    }
+
+    pub fn of_command(command: &PoolCommand) -> Self {
+        match command {
+            PoolCommand::Refresh => ActionKind::Refresh,
+            PoolCommand::PublishFirstDataPoint | PoolCommand::PublishSubsequentDataPoint { .. } => {
+                ActionKind::PublishDatapoint
+            }
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        match self {
+            ActionKind::PublishDatapoint => RetryPolicy {
+                max_attempts: 5,
+                backoff_secs: 30,
+            },
+            // The pool stalls without a refresh
+            ActionKind::Refresh => RetryPolicy {
+                max_attempts: 10,
+                backoff_secs: 30,
+            },
+            ActionKind::RewardExtraction => RetryPolicy {
+                max_attempts: 3,
+                backoff_secs: 600,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionStatus {
+    /// Waiting for its (next) attempt
+    Queued,
+    /// Its tx is in the mempool
+    Submitted,
+    Confirmed,
+    /// All its attempts failed
+    Failed,
+    /// No longer applies
+    Abandoned,
+}
+
+impl ActionStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ActionStatus::Queued => "queued",
+            ActionStatus::Submitted => "submitted",
+            ActionStatus::Confirmed => "confirmed",
+            ActionStatus::Failed => "failed",
+            ActionStatus::Abandoned => "abandoned",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, StoreError> {
+        [
+            ActionStatus::Queued,
+            ActionStatus::Submitted,
+            ActionStatus::Confirmed,
+            ActionStatus::Failed,
+            ActionStatus::Abandoned,
+        ]
+        .iter()
+        .copied()
+        .find(|status| status.name() == name)
+        .ok_or_else(|| StoreError::UnknownValue(name.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    /// Delay after the first failed attempt, doubled after every further one
+    pub backoff_secs: u64,
+}
+
+/// Longest delay between two attempts
+const MAX_BACKOFF_SECS: u64 = 3600;
+
+impl RetryPolicy {
+    /// Delay after `attempts` failed attempts
+    pub fn backoff(&self, attempts: u32) -> u64 {
+        let doublings = attempts.saturating_sub(1).min(16);
+        (self.backoff_secs << doublings).min(MAX_BACKOFF_SECS)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueuedAction {
+    pub id: i64,
+    pub kind: ActionKind,
+    /// Epoch of the pool the action was decided in, `None` for the actions not bound to an epoch
+    pub epoch: Option<u32>,
+    pub status: ActionStatus,
+    pub attempts: u32,
+    /// Tx of the last attempt
+    pub tx_id: Option<TxId>,
+    pub last_error: Option<String>,
+    /// Unix time in seconds
+    pub next_attempt_at: u64,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl QueuedAction {
+    pub fn is_due(&self, now: u64) -> bool {
+        self.status == ActionStatus::Queued && self.next_attempt_at <= now
+    }
+
+    fn set_status(&mut self, status: ActionStatus, now: u64) {
+        self.status = status;
+        self.updated_at = now;
+    }
+
+    /// Record the outcome of an attempt, the id of the submitted tx or the error
+    pub fn record_attempt(&mut self, res: Result<TxId, String>, now: u64) {
+        self.attempts += 1;
+        match res {
+            Ok(tx_id) => {
+                self.tx_id = Some(tx_id);
+                self.set_status(ActionStatus::Submitted, now);
+            }
+            Err(e) => {
+                self.last_error = Some(e);
+                self.retry_or_fail(now);
+            }
+        }
+    }
+
+    /// The tx of the action was settled with `status`
+    pub fn tx_settled(&mut self, status: TxStatus, now: u64) {
+        match status {
+            TxStatus::Submitted => (),
+            TxStatus::Confirmed => self.set_status(ActionStatus::Confirmed, now),
+            TxStatus::Evicted | TxStatus::Failed => {
+                self.requeue(format!("tx {}", status.name()), now)
+            }
+        }
+    }
+
+    /// Build the tx of the action again after the backoff, its last attempt failed for `reason`
+    pub fn requeue(&mut self, reason: String, now: u64) {
+        self.last_error = Some(reason);
+        self.retry_or_fail(now);
+    }
+
+    fn retry_or_fail(&mut self, now: u64) {
+        let policy = self.kind.retry_policy();
+        if self.attempts >= policy.max_attempts {
+            self.set_status(ActionStatus::Failed, now);
+        } else {
+            self.next_attempt_at = now + policy.backoff(self.attempts);
+            self.set_status(ActionStatus::Queued, now);
+        }
+    }
+
+    pub fn abandon(&mut self, reason: String, now: u64) {
+        self.last_error = Some(reason);
+        self.set_status(ActionStatus::Abandoned, now);
+    }
+}
+
+/// The open action of `kind` in `epoch`, queued now if there is none
+pub fn enqueue(
+    store: &Store,
+    kind: ActionKind,
+    epoch: Option<u32>,
+) -> Result<QueuedAction, StoreError> {
+    match store.open_action(kind, epoch)? {
+        Some(action) => Ok(action),
+        None => {
+            let action = store.insert_action(kind, epoch, unix_now())?;
+            log::debug!("Queued the {} action {}", kind.name(), action.id);
+            Ok(action)
+        }
+    }
+}
+
+/// Record the outcome of an attempt of `action`, see [`QueuedAction::record_attempt`]
+pub fn record_attempt(
+    store: &Store,
+    mut action: QueuedAction,
+    res: Result<TxId, String>,
+) -> Result<(), StoreError> {
+    action.record_attempt(res, unix_now());
+    match action.status {
+        ActionStatus::Failed => log::error!(
+            "The {} action {} failed after {} attempts: {}",
+            action.kind.name(),
+            action.id,
+            action.attempts,
+            action.last_error.as_deref().unwrap_or_default()
+        ),
+        ActionStatus::Queued => log::warn!(
+            "Attempt {} of the {} action {} failed, retrying in {}s: {}",
+            action.attempts,
+            action.kind.name(),
+            action.id,
+            action.next_attempt_at.saturating_sub(action.updated_at),
+            action.last_error.as_deref().unwrap_or_default()
+        ),
+        ActionStatus::Submitted | ActionStatus::Confirmed | ActionStatus::Abandoned => (),
+    }
+    store.update_action(&action)
+}
+
+/// Abandon `action`, it no longer applies
+pub fn abandon(store: &Store, mut action: QueuedAction, reason: &str) -> Result<(), StoreError> {
+    log::info!(
+        "Abandoning the {} action {}: {}",
+        action.kind.name(),
+        action.id,
+        reason
+    );
+    action.abandon(reason.to_string(), unix_now());
+    store.update_action(&action)
+}
+
+/// Complete or requeue the action of the settled tx `tx_id`
+pub fn tx_settled(store: &Store, tx_id: &str, status: TxStatus) -> Result<(), StoreError> {
+    if let Some(mut action) = store.action_of_tx(tx_id)? {
+        action.tx_settled(status, unix_now());
+        if action.status != ActionStatus::Confirmed {
+            log::warn!(
+                "The tx of the {} action {} was {}, the action is {}",
+                action.kind.name(),
+                action.id,
+                status.name(),
+                action.status.name()
+            );
+        }
+        store.update_action(&action)?;
+    }
+    Ok(())
+}
+
+/// Abandon the queued actions bound to an epoch before `epoch`
+pub fn abandon_stale(epoch: u32) -> Result<(), StoreError> {
+    let store = store::get()?;
+    for mut action in store.open_actions()? {
+        if let (ActionStatus::Queued, Some(action_epoch)) = (action.status, action.epoch) {
+            if action_epoch < epoch {
+                log::info!(
+                    "Abandoning the {} action {} of epoch {}, the pool is in epoch {}",
+                    action.kind.name(),
+                    action.id,
+                    action_epoch,
+                    epoch
+                );
+                action.abandon(format!("the pool left epoch {}", action_epoch), unix_now());
+                store.update_action(&action)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_action_retries() {
+        let store = Store::open(Path::new(":memory:")).unwrap();
+        let action = enqueue(&store, ActionKind::PublishDatapoint, Some(7)).unwrap();
+        assert!(action.is_due(action.created_at));
+        record_attempt(&store, action, Err("node down".to_string())).unwrap();
+        // The open action is reused
+        let mut action = enqueue(&store, ActionKind::PublishDatapoint, Some(7)).unwrap();
+        assert_eq!(action.attempts, 1);
+        assert!(!action.is_due(action.updated_at));
+        assert!(action.is_due(action.updated_at + 30));
+
+        let now = action.updated_at;
+        action.record_attempt(Err("node down".to_string()), now);
+        assert_eq!(action.next_attempt_at, now + 60);
+        action.record_attempt(Ok("tx".to_string()), now);
+        assert_eq!(action.status, ActionStatus::Submitted);
+        store.update_action(&action).unwrap();
+        // Evicted, built again
+        tx_settled(&store, "tx", TxStatus::Evicted).unwrap();
+        let mut action = enqueue(&store, ActionKind::PublishDatapoint, Some(7)).unwrap();
+        assert_eq!(action.status, ActionStatus::Queued);
+        assert_eq!(action.last_error.as_deref(), Some("tx evicted"));
+        action.record_attempt(Err("node down".to_string()), now);
+        assert_eq!(action.status, ActionStatus::Queued);
+        action.record_attempt(Err("node down".to_string()), now);
+        assert_eq!(action.status, ActionStatus::Failed);
+        store.update_action(&action).unwrap();
+        assert!(store.open_actions().unwrap().is_empty());
+
+        let action = enqueue(&store, ActionKind::Refresh, Some(7)).unwrap();
+        record_attempt(&store, action, Ok("refresh_tx".to_string())).unwrap();
+        tx_settled(&store, "refresh_tx", TxStatus::Confirmed).unwrap();
+        assert!(store.open_actions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = ActionKind::RewardExtraction.retry_policy();
+        assert_eq!(policy.backoff(1), 600);
+        assert_eq!(policy.backoff(2), 1200);
+        assert_eq!(policy.backoff(10), 3600);
+    }
+}
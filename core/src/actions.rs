@@ -1,8 +1,14 @@
 /// This file holds all the actions which can be performed
 /// by an oracle part of the oracle pool. These actions
 /// are implemented on the `OraclePool` struct.
-use crate::node_interface::sign_and_submit_transaction;
+use crate::events::{PoolEvent, EVENTS};
+use crate::metrics::METRICS;
+use crate::node_interface::sign_and_submit_transaction_with_outputs;
+use crate::node_interface::SubmitTxError;
+use crate::node_interface::TxId;
 use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::Transaction;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
 
 use derive_more::From;
 use ergo_node_interface::node_interface::NodeError;
@@ -17,6 +23,17 @@ pub enum PoolAction {
     PublishDatapoint(PublishDataPointAction),
 }
 
+impl PoolAction {
+    /// Ids of the boxes spent by the action tx
+    pub fn input_box_ids(&self) -> Vec<BoxId> {
+        let tx = match self {
+            PoolAction::Refresh(action) => &action.tx,
+            PoolAction::PublishDatapoint(action) => &action.tx,
+        };
+        tx.inputs.iter().map(|i| i.box_id.clone()).collect()
+    }
+}
+
 #[derive(Debug)]
 pub struct RefreshAction {
     pub tx: UnsignedTransaction,
@@ -25,40 +42,62 @@ pub struct RefreshAction {
 #[derive(Debug)]
 pub struct PublishDataPointAction {
     pub tx: UnsignedTransaction,
+    /// The datapoint of the new oracle box
+    pub datapoint: i64,
 }
 
 #[derive(Error, Debug, From)]
 pub enum ActionExecError {
-    #[error("node error: {0}")]
-    NodeError(NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
 }
 
-pub fn execute_action(action: PoolAction) -> Result<(), ActionExecError> {
+/// Returns the id of the submitted tx and the signed tx, `None` if the node already has it in the
+/// mempool
+pub fn execute_action(action: PoolAction) -> Result<Option<(TxId, Transaction)>, ActionExecError> {
     let exec_res = match action {
-        PoolAction::Refresh(action) => execute_refresh_action(action),
-        PoolAction::PublishDatapoint(action) => execute_publish_datapoint_action(action),
+        PoolAction::Refresh(action) => {
+            let _span = tracing::info_span!("submit_tx", action = "refresh").entered();
+            execute_refresh_action(action)
+        }
+        PoolAction::PublishDatapoint(action) => {
+            let _span = tracing::info_span!("submit_tx", action = "publish_datapoint").entered();
+            execute_publish_datapoint_action(action)
+        }
     };
     match exec_res {
-        Ok(_) => Ok(()),
-        Err(ActionExecError::NodeError(NodeError::BadRequest(msg)))
+        Ok(submitted) => Ok(Some(submitted)),
+        Err(ActionExecError::SubmitTx(SubmitTxError::Node(NodeError::BadRequest(msg))))
             if msg.as_str() == "Double spending attempt"
                 || msg.contains("it is invalidated earlier or the pool is full") =>
         {
             log::info!("Node rejected tx, probably, due to this tx is already in the mempool)");
-            Ok(())
+            Ok(None)
         }
         Err(e) => Err(e),
     }
 }
 
-fn execute_refresh_action(action: RefreshAction) -> Result<(), ActionExecError> {
-    let tx_id = sign_and_submit_transaction(&action.tx)?;
+fn execute_refresh_action(action: RefreshAction) -> Result<(TxId, Transaction), ActionExecError> {
+    let (tx_id, tx) = sign_and_submit_transaction_with_outputs(&action.tx, "refresh")?;
     log::info!("Refresh tx published successfully, tx id: {}", tx_id);
-    Ok(())
+    METRICS.record_tx_submission("refresh");
+    EVENTS.publish(PoolEvent::TxSubmitted {
+        tx_id: tx_id.clone(),
+        action: "refresh".to_string(),
+    });
+    Ok((tx_id, tx))
 }
 
-fn execute_publish_datapoint_action(action: PublishDataPointAction) -> Result<(), ActionExecError> {
-    let tx_id = sign_and_submit_transaction(&action.tx)?;
+fn execute_publish_datapoint_action(
+    action: PublishDataPointAction,
+) -> Result<(TxId, Transaction), ActionExecError> {
+    let (tx_id, tx) = sign_and_submit_transaction_with_outputs(&action.tx, "publish_datapoint")?;
     log::info!("Datapoint published successfully, tx id: {}", tx_id);
-    Ok(())
+    METRICS.record_tx_submission("publish_datapoint");
+    EVENTS.publish(PoolEvent::TxSubmitted {
+        tx_id: tx_id.clone(),
+        action: "publish_datapoint".to_string(),
+    });
+    Ok((tx_id, tx))
 }
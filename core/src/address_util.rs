@@ -10,7 +10,7 @@ use thiserror::Error;
 #[derive(Error, From, Debug)]
 pub enum AddressUtilError {
     #[error("address encoder error: {0}")]
-    AddressEncoderError(AddressEncoderError),
+    AddressEncoderError(#[source] AddressEncoderError),
     #[error("expected P2PK address")]
     ExpectedP2PK,
     #[error("expected P2S address")]
@@ -0,0 +1,175 @@
+//! Requests of the admin interfaces (the admin routes of the REST API and the gRPC service) to the
+//! main loop. The requests are checked by the main loop on its next iteration, so an admin call
+//! never races with the tx the daemon is building.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::datapoint_source::PredefinedDataPointSource;
+
+pub static ADMIN_REQUESTS: Lazy<AdminRequests> = Lazy::new(AdminRequests::new);
+
+/// New data source of the oracle, replaces both `data_point_source` and
+/// `data_point_source_custom_script` of the running config
+#[derive(Debug, Clone, PartialEq, Deserialize, ToSchema)]
+pub struct DataSourceChange {
+    /// `NanoErgUsd`, `NanoErgXau` or `NanoAdaUsd`
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub data_point_source: Option<PredefinedDataPointSource>,
+    #[serde(default)]
+    pub data_point_source_custom_script: Option<String>,
+}
+
+impl DataSourceChange {
+    /// Check that exactly one data source is set and that a custom script is one of
+    /// `allowed_scripts`, a remote caller must not run any command on the host
+    pub fn check(&self, allowed_scripts: Option<&[String]>) -> Result<(), String> {
+        if self.data_point_source.is_some() == self.data_point_source_custom_script.is_some() {
+            return Err(
+                "exactly one of data_point_source and data_point_source_custom_script must be set"
+                    .to_string(),
+            );
+        }
+        match &self.data_point_source_custom_script {
+            Some(script) if !allowed_scripts.map_or(false, |s| s.contains(script)) => Err(format!(
+                "{} is not in core_api_admin_allowed_scripts of the config",
+                script
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub struct AdminRequests {
+    posting_paused: AtomicBool,
+    extraction_requested: AtomicBool,
+    reload_requested: AtomicBool,
+    data_source_change: Mutex<Option<DataSourceChange>>,
+}
+
+impl AdminRequests {
+    pub fn new() -> Self {
+        AdminRequests {
+            posting_paused: AtomicBool::new(false),
+            extraction_requested: AtomicBool::new(false),
+            reload_requested: AtomicBool::new(false),
+            data_source_change: Mutex::new(None),
+        }
+    }
+
+    /// While paused the daemon doesn't publish datapoints, it still refreshes the pool box
+    pub fn set_posting_paused(&self, paused: bool) {
+        self.posting_paused.store(paused, Ordering::SeqCst);
+    }
+
+    pub fn posting_paused(&self) -> bool {
+        self.posting_paused.load(Ordering::SeqCst)
+    }
+
+    /// Extract the reward tokens on the next idle iteration, regardless of the thresholds of the
+    /// `reward_extraction` config
+    pub fn request_extraction(&self) {
+        self.extraction_requested.store(true, Ordering::SeqCst);
+    }
+
+    pub fn take_extraction_request(&self) -> bool {
+        self.extraction_requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Reload the config file on the next iteration even if it wasn't modified
+    pub fn request_reload(&self) {
+        self.reload_requested.store(true, Ordering::SeqCst);
+    }
+
+    pub fn take_reload_request(&self) -> bool {
+        self.reload_requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Switch the data source of the datapoints from the next iteration on. A later change of the
+    /// data source in the config file takes over again.
+    pub fn request_data_source_change(&self, change: DataSourceChange) {
+        *self.data_source_change.lock().unwrap() = Some(change);
+    }
+
+    pub fn take_data_source_change(&self) -> Option<DataSourceChange> {
+        self.data_source_change.lock().unwrap().take()
+    }
+}
+
+impl Default for AdminRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read the auth token of an admin interface from `path`, surrounding whitespace is ignored
+pub fn read_token_file(path: &Path) -> Result<String, anyhow::Error> {
+    let token = std::fs::read_to_string(path)?.trim().to_string();
+    if token.is_empty() {
+        return Err(anyhow!("auth token file {} is empty", path.display()));
+    }
+    Ok(token)
+}
+
+/// Compare the token of a request with the configured one in constant time
+pub fn token_matches(token: &str, expected: &str) -> bool {
+    token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_requests() {
+        let requests = AdminRequests::new();
+        assert!(!requests.take_extraction_request());
+        requests.request_extraction();
+        assert!(requests.take_extraction_request());
+        assert!(!requests.take_extraction_request());
+        requests.set_posting_paused(true);
+        assert!(requests.posting_paused());
+        let change = DataSourceChange {
+            data_point_source: Some(PredefinedDataPointSource::NanoErgXau),
+            data_point_source_custom_script: None,
+        };
+        requests.request_data_source_change(change.clone());
+        assert_eq!(requests.take_data_source_change(), Some(change));
+        assert_eq!(requests.take_data_source_change(), None);
+
+        let allowed_scripts = vec!["./datapoint.sh".to_string()];
+        assert!(change.check(None).is_ok());
+        let script_change = DataSourceChange {
+            data_point_source: None,
+            data_point_source_custom_script: Some("./datapoint.sh".to_string()),
+        };
+        assert!(script_change.check(Some(&allowed_scripts)).is_ok());
+        assert!(script_change.check(None).is_err());
+        let other_script_change = DataSourceChange {
+            data_point_source: None,
+            data_point_source_custom_script: Some("rm -rf ~".to_string()),
+        };
+        assert!(other_script_change.check(Some(&allowed_scripts)).is_err());
+        let both = DataSourceChange {
+            data_point_source: Some(PredefinedDataPointSource::NanoErgXau),
+            data_point_source_custom_script: Some("./datapoint.sh".to_string()),
+        };
+        assert!(both.check(Some(&allowed_scripts)).is_err());
+
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret"));
+        assert!(!token_matches("secret1", "secret"));
+    }
+}
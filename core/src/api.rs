@@ -1,88 +1,187 @@
 use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
 
-use crate::box_kind::OracleBox;
+use crate::admin::{token_matches, DataSourceChange, ADMIN_REQUESTS};
+use crate::box_kind::{OracleBox, OracleBoxWrapper, PoolBox};
+use crate::cli_commands::ballot_status::get_ballot_status;
+use crate::contracts::oracle::OracleContract;
+use crate::downtime::{
+    unix_now, CauseDowntime, DowntimeCause, DowntimeInterval, DowntimeReport, DOWNTIME,
+};
+use crate::epoch_stats::{EpochStats, EPOCH_STATS};
+use crate::events::EVENTS;
+use crate::fees::{FeeLedger, FeeTotal};
+use crate::health::{HealthCheck, Readiness, HEALTH};
+use crate::metrics::METRICS;
 use crate::node_interface::current_block_height;
-use crate::oracle_config::{get_core_api_port, get_node_ip, get_node_port, ORACLE_CONFIG};
-use crate::oracle_state::{OraclePool, StageDataSource};
+use crate::notifications::NotificationKind;
+use crate::oracle_config::{get_node_ip, get_node_port, ORACLE_CONFIG};
+use crate::oracle_state::{DatapointBoxesSource, OraclePool, PoolBoxSource, StageScan};
+use crate::peer_monitor::{PeerStatus, PEER_REPORT};
+use crate::pool_archive::{
+    HistoryInterval, HistoryPage, HistoryPoint, PoolArchive, POOL_ARCHIVE_FILE,
+};
 use crate::state::PoolState;
-use axum::response::IntoResponse;
-use axum::routing::get;
+use crate::store::{self, Alert, ObservedPoolState, PostedDatapoint, TxOutput, TxRecord, TxStatus};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::Query;
+use axum::http::{header, HeaderMap, HeaderValue, Request, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
 use axum::{Json, Router};
 use crossbeam::channel::Receiver;
-use serde_json::json;
-use tower_http::cors::CorsLayer;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
 
 /// Basic welcome endpoint
+#[utoipa::path(get, path = "/", responses((status = 200, description = "Welcome text", body = String)))]
 async fn root() -> &'static str {
     "This is an Oracle Core. Please use one of the endpoints to interact with it.\n"
 }
 
 /// Basic oracle information
+#[utoipa::path(get, path = "/oracleInfo", responses((status = 200, description = "Address of the oracle")))]
 async fn oracle_info() -> impl IntoResponse {
     Json(json! ( {
             "oracle_address": &ORACLE_CONFIG.oracle_address.to_base58(),
         } ))
 }
 
-/// Status of the oracle
-async fn oracle_status() -> impl IntoResponse {
-    let op = OraclePool::new().unwrap();
+/// Latest datapoint box of the local oracle, served on `/oracleStatus` and by the gRPC service
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OracleStatus {
+    /// `posted`, `collected` or `no datapoint box`
+    #[schema(value_type = String)]
+    pub state: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_datapoint: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_datapoint_epoch: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_datapoint_creation_height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reward_tokens: Option<u64>,
+}
 
-    // Get latest datapoint the local oracle produced/submit
-    let latest_oracle_box = op
+pub fn get_oracle_status() -> Result<OracleStatus, anyhow::Error> {
+    let op = OraclePool::new()?;
+    let local_box = op
         .get_local_datapoint_box_source()
-        .get_local_oracle_datapoint_box();
-    // let self_datapoint = match latest_oracle_box {
-    //     Ok(Some(ref d)) => d.rate().unwrap_or(0),
-    //     Ok(None) | Err(_) => 0,
-    // };
-    // // Get latest datapoint submit epoch
-    // let datapoint_epoch = match latest_oracle_box {
-    //     Ok(Some(ref d)) => d.epoch_counter().unwrap_or(0),
-    //     Ok(None) | Err(_) => 0,
-    // };
-    // Get latest datapoint submit epoch
-    let datapoint_creation = match latest_oracle_box {
-        Ok(Some(ref d)) => d.get_box().creation_height,
-        Ok(None) | Err(_) => 0,
-    };
+        .get_local_oracle_datapoint_box()?;
+    Ok(match local_box {
+        Some(OracleBoxWrapper::Posted(ref posted_box)) => OracleStatus {
+            state: "posted",
+            latest_datapoint: Some(posted_box.rate()),
+            latest_datapoint_epoch: Some(posted_box.epoch_counter()),
+            latest_datapoint_creation_height: Some(posted_box.get_box().creation_height),
+            reward_tokens: Some(u64::from(posted_box.reward_token().amount)),
+        },
+        Some(ref collected_box @ OracleBoxWrapper::Collected(_)) => OracleStatus {
+            state: "collected",
+            latest_datapoint: None,
+            latest_datapoint_epoch: None,
+            latest_datapoint_creation_height: Some(collected_box.get_box().creation_height),
+            reward_tokens: Some(u64::from(collected_box.reward_token().amount)),
+        },
+        None => OracleStatus {
+            state: "no datapoint box",
+            latest_datapoint: None,
+            latest_datapoint_epoch: None,
+            latest_datapoint_creation_height: None,
+            reward_tokens: None,
+        },
+    })
+}
 
-    Json(json! ({
-        // "latest_datapoint": self_datapoint,
-        // "latest_datapoint_epoch": datapoint_epoch,
-        "latest_datapoint_creation_height": datapoint_creation,
-    }))
+#[utoipa::path(
+    get,
+    path = "/oracleStatus",
+    responses((status = 200, body = OracleStatus), (status = 500, description = "Node error"))
+)]
+async fn oracle_status() -> impl IntoResponse {
+    blocking_json(|| Ok(json!(get_oracle_status()?))).await
 }
 
-// Basic information about the oracle pool
-async fn pool_info() -> impl IntoResponse {
-    let parameters = &ORACLE_CONFIG;
-    let op = OraclePool::new().unwrap();
-    let datapoint_stage = op.datapoint_stage;
-    let num_of_oracles = datapoint_stage.stage.number_of_boxes().unwrap_or(10);
+/// Parameters and tokens of the pool, served on `/poolInfo` and by the gRPC service
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolInfo {
+    pub number_of_oracles: u64,
+    pub datapoint_address: Option<String>,
+    pub live_epoch_length: i32,
+    pub deviation_range: i32,
+    pub consensus_num: i32,
+    #[serde(serialize_with = "crate::serde::token_id_as_base64_string")]
+    #[schema(value_type = String, format = Byte)]
+    pub oracle_pool_nft_id: TokenId,
+    #[serde(serialize_with = "crate::serde::token_id_as_base64_string")]
+    #[schema(value_type = String, format = Byte)]
+    pub oracle_pool_participant_token_id: TokenId,
+}
 
-    Json(json!({
-        "number_of_oracles": num_of_oracles,
-        "datapoint_address": datapoint_stage.stage.contract_address,
-        "live_epoch_length": parameters.refresh_box_wrapper_inputs.contract_inputs.contract_parameters().epoch_length(),
-        "deviation_range": parameters.refresh_box_wrapper_inputs.contract_inputs.contract_parameters().max_deviation_percent(),
-        "consensus_num": parameters.refresh_box_wrapper_inputs.contract_inputs.contract_parameters().min_data_points(),
-        "oracle_pool_nft_id": parameters.token_ids.pool_nft_token_id,
-        "oracle_pool_participant_token_id": parameters.token_ids.oracle_token_id,
+pub fn get_pool_info() -> Result<PoolInfo, anyhow::Error> {
+    let parameters = &ORACLE_CONFIG;
+    let op = OraclePool::new()?;
+    let num_of_oracles = op.datapoint_boxes_scan.number_of_boxes().unwrap_or(10);
+    let datapoint_address =
+        OracleContract::checked_load(&parameters.oracle_box_wrapper_inputs.contract_inputs)
+            .ok()
+            .and_then(|contract| contract.ergo_tree().to_base16_bytes().ok());
+    let refresh_parameters = parameters
+        .refresh_box_wrapper_inputs
+        .contract_inputs
+        .contract_parameters();
+    Ok(PoolInfo {
+        number_of_oracles: num_of_oracles,
+        datapoint_address,
+        live_epoch_length: refresh_parameters.epoch_length(),
+        deviation_range: refresh_parameters.max_deviation_percent(),
+        consensus_num: refresh_parameters.min_data_points(),
+        oracle_pool_nft_id: parameters.token_ids.pool_nft_token_id.clone(),
+        oracle_pool_participant_token_id: parameters.token_ids.oracle_token_id.clone(),
+    })
+}
 
-    }))
+// Basic information about the oracle pool
+#[utoipa::path(
+    get,
+    path = "/poolInfo",
+    responses((status = 200, body = PoolInfo), (status = 500, description = "Node error"))
+)]
+async fn pool_info() -> impl IntoResponse {
+    blocking_json(|| Ok(json!(get_pool_info()?))).await
 }
 
 /// Basic information about node the oracle core is using
+#[utoipa::path(get, path = "/nodeInfo", responses((status = 200, description = "URL of the node")))]
 async fn node_info() -> impl IntoResponse {
     Json(json!({
         "node_url": "http://".to_string() + &get_node_ip() + ":" + &get_node_port(),
     }))
 }
 
-/// Status of the oracle pool
-async fn pool_status() -> impl IntoResponse {
-    let op = OraclePool::new().unwrap();
+/// Status of the oracle pool, served on `/poolStatus` and by the gRPC service
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolStatus {
+    #[schema(value_type = String)]
+    pub current_pool_stage: &'static str,
+    pub latest_datapoint: u64,
+    pub current_epoch_id: String,
+    pub pool_box_height: u32,
+    pub blocks_since_pool_update: u32,
+    /// Datapoints posted in the current epoch
+    pub epoch_datapoints: usize,
+    pub pool_reward_tokens: u64,
+}
+
+pub fn get_pool_status() -> Result<PoolStatus, anyhow::Error> {
+    let op = OraclePool::new()?;
 
     // Current stage of the oracle pool box
     let current_stage = match op.check_oracle_pool_stage() {
@@ -96,21 +195,560 @@ async fn pool_status() -> impl IntoResponse {
         latest_datapoint = l.latest_pool_datapoint;
         current_epoch_id = l.pool_box_epoch_id.to_string();
     }
-    Json(json!({
-            "current_pool_stage": current_stage,
-            "latest_datapoint": latest_datapoint,
-            "current_epoch_id" : current_epoch_id,
-    }))
+    let pool_box = op.get_pool_box_source().get_pool_box()?;
+    let height = current_block_height()? as u32;
+    let pool_box_height = pool_box.get_box().creation_height;
+    let epoch_datapoints = op
+        .get_datapoint_boxes_source()
+        .get_oracle_datapoint_boxes()?
+        .iter()
+        .filter(|b| b.epoch_counter() == pool_box.epoch_counter())
+        .count();
+    Ok(PoolStatus {
+        current_pool_stage: current_stage,
+        latest_datapoint,
+        current_epoch_id,
+        pool_box_height,
+        blocks_since_pool_update: height.saturating_sub(pool_box_height),
+        epoch_datapoints,
+        pool_reward_tokens: u64::from(pool_box.reward_token().amount),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/poolStatus",
+    responses((status = 200, body = PoolStatus), (status = 500, description = "Node error"))
+)]
+async fn pool_status() -> impl IntoResponse {
+    blocking_json(|| Ok(json!(get_pool_status()?))).await
+}
+
+/// Clients may reuse a `/rate` response for this long, the rate changes at most once per block
+const RATE_CACHE_CONTROL: &str = "public, max-age=30";
+
+/// Latest pool rate confirmed on chain, served on `/rate` for dApps
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolRate {
+    pub rate: i64,
+    pub epoch: u32,
+    pub pool_box_id: String,
+    /// Height of the pool box
+    pub height: u32,
+    pub current_height: u32,
+    pub blocks_since_update: u32,
+    /// The pool box wasn't refreshed half an epoch after the end of its epoch
+    pub stale: bool,
+}
+
+impl PoolRate {
+    /// Changes with the pool box and with every block (`blocks_since_update`)
+    fn etag(&self) -> String {
+        format!("\"{}-{}\"", self.pool_box_id, self.current_height)
+    }
+}
+
+pub fn get_pool_rate() -> Result<PoolRate, anyhow::Error> {
+    let op = OraclePool::new()?;
+    let pool_box = op.get_confirmed_pool_box()?;
+    let current_height = current_block_height()? as u32;
+    let height = pool_box.get_box().creation_height;
+    let blocks_since_update = current_height.saturating_sub(height);
+    let epoch_length = ORACLE_CONFIG
+        .refresh_box_wrapper_inputs
+        .contract_inputs
+        .contract_parameters()
+        .epoch_length() as u32;
+    Ok(PoolRate {
+        rate: pool_box.rate(),
+        epoch: pool_box.epoch_counter(),
+        pool_box_id: String::from(pool_box.get_box().box_id()),
+        height,
+        current_height,
+        blocks_since_update,
+        stale: blocks_since_update >= epoch_length + epoch_length / 2,
+    })
+}
+
+/// Answers a conditional request with `304 Not Modified` if the rate didn't change
+#[utoipa::path(
+    get,
+    path = "/rate",
+    responses(
+        (status = 200, body = PoolRate),
+        (status = 304, description = "Matches the `If-None-Match` header"),
+        (status = 500, description = "Node error")
+    )
+)]
+async fn rate(headers: HeaderMap) -> Response {
+    let pool_rate = match tokio::task::spawn_blocking(get_pool_rate).await {
+        Ok(Ok(pool_rate)) => pool_rate,
+        Ok(Err(e)) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": e.to_string() })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": e.to_string() })),
+            )
+                .into_response()
+        }
+    };
+    let etag = pool_rate.etag();
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| {
+            value
+                .split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+    let cache_headers = [
+        (header::ETAG, etag),
+        (header::CACHE_CONTROL, RATE_CACHE_CONTROL.to_string()),
+    ];
+    if not_modified {
+        (StatusCode::NOT_MODIFIED, cache_headers).into_response()
+    } else {
+        (cache_headers, Json(pool_rate)).into_response()
+    }
+}
+
+/// Votes cast in the ballot boxes and the operators that haven't voted yet
+#[utoipa::path(
+    get,
+    path = "/ballotStatus",
+    responses((status = 200, description = "Ballot status"), (status = 500, description = "Node error"))
+)]
+async fn ballot_status() -> impl IntoResponse {
+    blocking_json(|| {
+        let op = OraclePool::new()?;
+        let status = get_ballot_status(
+            op.get_ballot_boxes_source(),
+            op.get_update_box_source(),
+            op.get_datapoint_boxes_source(),
+            ORACLE_CONFIG.oracle_address.network(),
+        )?;
+        Ok(json!(status))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EpochStatsQuery {
+    /// Only the last `last` epochs
+    last: Option<usize>,
+}
+
+/// Statistics of the epochs finished since the daemon started, oldest first
+#[utoipa::path(
+    get,
+    path = "/epochStats",
+    params(EpochStatsQuery),
+    responses((status = 200, body = [EpochStats]))
+)]
+async fn epoch_stats(Query(query): Query<EpochStatsQuery>) -> impl IntoResponse {
+    Json(EPOCH_STATS.last(query.last))
+}
+
+/// The peer oracles observed by the `peer_monitor` of the daemon, the flagged ones first. Empty
+/// without a `peer_monitor` in the config.
+#[utoipa::path(get, path = "/peers", responses((status = 200, body = [PeerStatus])))]
+async fn peers() -> impl IntoResponse {
+    Json(PEER_REPORT.lock().unwrap().clone())
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DowntimeQuery {
+    /// Start of the report (unix time in seconds), the first observation of the daemon by default
+    from: Option<u64>,
+}
+
+/// Intervals in which the node was down, the data source down or the wallet locked, and the
+/// downtime and uptime by cause
+#[utoipa::path(
+    get,
+    path = "/downtime",
+    params(DowntimeQuery),
+    responses((status = 200, body = DowntimeReport))
+)]
+async fn downtime(Query(query): Query<DowntimeQuery>) -> impl IntoResponse {
+    Json(DOWNTIME.lock().unwrap().report(query.from, unix_now()))
+}
+
+/// Tx fees paid by the oracle wallet, in total and by action, UTC day and epoch
+#[utoipa::path(
+    get,
+    path = "/fees",
+    responses((status = 200, body = FeeLedger), (status = 500, description = "Store error"))
+)]
+async fn fees() -> impl IntoResponse {
+    blocking_json(|| Ok(json!(store::get()?.fee_ledger()?))).await
+}
+
+/// Rows of the store history routes without `limit`
+const STORE_HISTORY_LIMIT: u32 = 100;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StoreHistoryQuery {
+    /// Only the last `limit` rows, 100 by default
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TxHistoryQuery {
+    /// Only the txs with this status (`submitted`, `confirmed`, `evicted` or `failed`)
+    #[param(value_type = Option<String>)]
+    status: Option<TxStatus>,
+    /// Only the last `limit` txs, 100 by default
+    limit: Option<u32>,
+}
+
+/// The txs signed by the node wallet with their status, newest first
+#[utoipa::path(
+    get,
+    path = "/txs",
+    params(TxHistoryQuery),
+    responses((status = 200, body = [TxRecord]), (status = 500, description = "Store error"))
+)]
+async fn txs(Query(query): Query<TxHistoryQuery>) -> impl IntoResponse {
+    blocking_json(move || {
+        Ok(json!(store::get()?.txs(
+            query.status,
+            query.limit.unwrap_or(STORE_HISTORY_LIMIT)
+        )?))
+    })
+    .await
+}
+
+/// The datapoints posted by the daemon, newest first
+#[utoipa::path(
+    get,
+    path = "/datapoints",
+    params(StoreHistoryQuery),
+    responses((status = 200, body = [PostedDatapoint]), (status = 500, description = "Store error"))
+)]
+async fn datapoints(Query(query): Query<StoreHistoryQuery>) -> impl IntoResponse {
+    blocking_json(move || {
+        Ok(json!(
+            store::get()?.datapoints(query.limit.unwrap_or(STORE_HISTORY_LIMIT))?
+        ))
+    })
+    .await
+}
+
+/// The pool boxes observed by the daemon, one per epoch, newest first
+#[utoipa::path(
+    get,
+    path = "/poolStates",
+    params(StoreHistoryQuery),
+    responses((status = 200, body = [ObservedPoolState]), (status = 500, description = "Store error"))
+)]
+async fn pool_states(Query(query): Query<StoreHistoryQuery>) -> impl IntoResponse {
+    blocking_json(move || {
+        Ok(json!(
+            store::get()?.pool_states(query.limit.unwrap_or(STORE_HISTORY_LIMIT))?
+        ))
+    })
+    .await
+}
+
+/// The alerts raised by the daemon, newest first, sent to the notification targets or not
+#[utoipa::path(
+    get,
+    path = "/alerts",
+    params(StoreHistoryQuery),
+    responses((status = 200, body = [Alert]), (status = 500, description = "Store error"))
+)]
+async fn alerts(Query(query): Query<StoreHistoryQuery>) -> impl IntoResponse {
+    blocking_json(move || {
+        Ok(json!(
+            store::get()?.alerts(query.limit.unwrap_or(STORE_HISTORY_LIMIT))?
+        ))
+    })
+    .await
+}
+
+/// Archived pool boxes (height, epoch counter, rate), see `pool-history --backfill`
+#[utoipa::path(get, path = "/poolHistory", responses((status = 200, description = "Archived pool boxes"), (status = 500, description = "The pool archive can't be loaded")))]
+async fn pool_history() -> impl IntoResponse {
+    blocking_json(|| {
+        let archive = PoolArchive::load(
+            Path::new(POOL_ARCHIVE_FILE),
+            &ORACLE_CONFIG.token_ids.pool_nft_token_id,
+        )?;
+        Ok(json!(archive.pool_boxes))
+    })
+    .await
+}
+
+/// Points per `/history` page without `limit`, and the maximum `limit`
+const HISTORY_PAGE_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HistoryQuery {
+    /// First height (inclusive)
+    from: Option<u32>,
+    /// Last height (inclusive)
+    to: Option<u32>,
+    /// Points per page, at most 1000
+    limit: Option<usize>,
+    /// `hourly` or `daily` aggregates instead of every pool box
+    #[param(value_type = Option<String>)]
+    interval: Option<HistoryInterval>,
+}
+
+/// Archived rate history, a page at a time. The next page starts at `next_from`.
+#[utoipa::path(
+    get,
+    path = "/history",
+    params(HistoryQuery),
+    responses((status = 200, body = HistoryPage), (status = 500, description = "Archive error"))
+)]
+async fn history(Query(query): Query<HistoryQuery>) -> impl IntoResponse {
+    blocking_json(move || {
+        let archive = PoolArchive::load(
+            Path::new(POOL_ARCHIVE_FILE),
+            &ORACLE_CONFIG.token_ids.pool_nft_token_id,
+        )?;
+        let limit = query
+            .limit
+            .unwrap_or(HISTORY_PAGE_LIMIT)
+            .min(HISTORY_PAGE_LIMIT);
+        Ok(json!(archive.history(
+            query.from,
+            query.to,
+            limit,
+            query.interval
+        )))
+    })
+    .await
 }
 
 /// Block height of the Ergo blockchain
+#[utoipa::path(
+    get,
+    path = "/blockHeight",
+    responses((status = 200, body = String), (status = 502, description = "Node error"))
+)]
 async fn block_height() -> impl IntoResponse {
-    let current_height =
-        current_block_height().expect("Please ensure that the Ergo node is running.");
-    format!("{}", current_height)
+    match tokio::task::spawn_blocking(current_block_height).await {
+        Ok(Ok(height)) => (StatusCode::OK, height.to_string()),
+        Ok(Err(e)) => (StatusCode::BAD_GATEWAY, e.to_string()),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Pool events pushed as JSON text messages, see [`PoolEvent`](crate::events::PoolEvent)
+#[utoipa::path(get, path = "/events", responses((status = 101, description = "WebSocket upgrade")))]
+async fn events(ws: WebSocketUpgrade) -> impl IntoResponse {
+    ws.on_upgrade(stream_events)
+}
+
+async fn stream_events(mut socket: WebSocket) {
+    let mut receiver = EVENTS.subscribe();
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                log::debug!("/events client lagged behind, {} events missed", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        let text = match serde_json::to_string(&event) {
+            Ok(text) => text,
+            Err(e) => {
+                log::warn!("Failed to serialize event {:?}: {}", event, e);
+                continue;
+            }
+        };
+        if socket.send(Message::Text(text)).await.is_err() {
+            // The client disconnected
+            return;
+        }
+    }
+}
+
+/// `200` while the main loop runs, `503` if it is stuck
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    responses((status = 200, body = String), (status = 503, body = String))
+)]
+async fn health_live() -> impl IntoResponse {
+    if HEALTH.live() {
+        (StatusCode::OK, "live")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "main loop stalled")
+    }
+}
+
+/// `200` if all the checks pass, `503` otherwise, with the result of every check
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    responses((status = 200, body = Readiness), (status = 503, body = Readiness))
+)]
+async fn health_ready() -> impl IntoResponse {
+    match tokio::task::spawn_blocking(|| HEALTH.readiness()).await {
+        Ok(readiness) if readiness.ready => (StatusCode::OK, Json(json!(readiness))),
+        Ok(readiness) => (StatusCode::SERVICE_UNAVAILABLE, Json(json!(readiness))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Metrics in the Prometheus text format
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses((status = 200, description = "Prometheus text format", body = String))
+)]
+async fn metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        METRICS.render(),
+    )
+}
+
+/// Run a handler querying the node on a thread where blocking is allowed, its error is returned as
+/// `{"error": ...}`
+async fn blocking_json(
+    handler: impl FnOnce() -> Result<Value, anyhow::Error> + Send + 'static,
+) -> (StatusCode, Json<Value>) {
+    match tokio::task::spawn_blocking(handler).await {
+        Ok(Ok(value)) => (StatusCode::OK, Json(value)),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Stop publishing datapoints, the pool box is still refreshed
+#[utoipa::path(
+    post,
+    path = "/admin/pausePosting",
+    responses((status = 200), (status = 401, description = "Missing or wrong token")),
+    security(("admin_token" = []))
+)]
+async fn pause_posting() -> impl IntoResponse {
+    log::info!("Admin API: datapoint posting paused");
+    ADMIN_REQUESTS.set_posting_paused(true);
+    Json(json!({ "posting_paused": true }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/resumePosting",
+    responses((status = 200), (status = 401, description = "Missing or wrong token")),
+    security(("admin_token" = []))
+)]
+async fn resume_posting() -> impl IntoResponse {
+    log::info!("Admin API: datapoint posting resumed");
+    ADMIN_REQUESTS.set_posting_paused(false);
+    Json(json!({ "posting_paused": false }))
+}
+
+/// Extract the reward tokens on the next idle iteration of the main loop
+#[utoipa::path(
+    post,
+    path = "/admin/triggerExtraction",
+    responses((status = 202), (status = 401, description = "Missing or wrong token")),
+    security(("admin_token" = []))
+)]
+async fn trigger_extraction() -> impl IntoResponse {
+    log::info!("Admin API: reward extraction requested");
+    ADMIN_REQUESTS.request_extraction();
+    StatusCode::ACCEPTED
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reloadConfig",
+    responses((status = 202), (status = 401, description = "Missing or wrong token")),
+    security(("admin_token" = []))
+)]
+async fn reload_config() -> impl IntoResponse {
+    log::info!("Admin API: config reload requested");
+    ADMIN_REQUESTS.request_reload();
+    StatusCode::ACCEPTED
+}
+
+/// Switch the data source of the datapoints, e.g. `{"data_point_source": "NanoErgXau"}` or
+/// `{"data_point_source_custom_script": "./datapoint.sh"}`. Custom scripts must be listed in
+/// `core_api_admin_allowed_scripts` of the config.
+#[utoipa::path(
+    post,
+    path = "/admin/dataSource",
+    request_body = DataSourceChange,
+    responses(
+        (status = 202),
+        (status = 400, description = "Not exactly one data source, or a custom script not allowed by the config"),
+        (status = 401, description = "Missing or wrong token")
+    ),
+    security(("admin_token" = []))
+)]
+async fn rotate_data_source(Json(change): Json<DataSourceChange>) -> impl IntoResponse {
+    if let Err(e) = change.check(ORACLE_CONFIG.core_api_admin_allowed_scripts.as_deref()) {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": e })));
+    }
+    log::info!("Admin API: data source change requested: {:?}", change);
+    ADMIN_REQUESTS.request_data_source_change(change);
+    (StatusCode::ACCEPTED, Json(json!({})))
+}
+
+/// Rejects the requests without the `Authorization: Bearer <token>` header
+async fn require_token<B>(
+    token: Arc<String>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, StatusCode> {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map_or(false, |given| token_matches(given, &token));
+    if authorized {
+        Ok(next.run(request).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Routes changing the behaviour of the daemon, nested under `/admin`
+fn admin_routes(token: String) -> Router {
+    let token = Arc::new(token);
+    Router::new()
+        .route("/pausePosting", post(pause_posting))
+        .route("/resumePosting", post(resume_posting))
+        .route("/triggerExtraction", post(trigger_extraction))
+        .route("/reloadConfig", post(reload_config))
+        .route("/dataSource", post(rotate_data_source))
+        .route_layer(middleware::from_fn(move |request, next| {
+            require_token(token.clone(), request, next)
+        }))
 }
 
 /// Whether the Core requires the Connector to repost a new Datapoint
+#[utoipa::path(get, path = "/requireDatapointRepost", responses((status = 200, body = String)))]
 async fn require_datapoint_repost(repost_receiver: Receiver<bool>) -> impl IntoResponse {
     let mut response_text = "false".to_string();
     if let Ok(b) = repost_receiver.try_recv() {
@@ -119,27 +757,166 @@ async fn require_datapoint_repost(repost_receiver: Receiver<bool>) -> impl IntoR
     response_text
 }
 
-pub async fn start_rest_server(repost_receiver: Receiver<bool>) {
-    let app = Router::new()
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        root,
+        oracle_info,
+        oracle_status,
+        pool_info,
+        node_info,
+        pool_status,
+        rate,
+        ballot_status,
+        pool_history,
+        epoch_stats,
+        peers,
+        fees,
+        txs,
+        datapoints,
+        pool_states,
+        alerts,
+        downtime,
+        history,
+        block_height,
+        events,
+        health_live,
+        health_ready,
+        metrics,
+        require_datapoint_repost,
+        pause_posting,
+        resume_posting,
+        trigger_extraction,
+        reload_config,
+        rotate_data_source
+    ),
+    components(schemas(
+        OracleStatus,
+        PoolInfo,
+        PoolStatus,
+        PoolRate,
+        EpochStats,
+        PeerStatus,
+        FeeLedger,
+        FeeTotal,
+        TxRecord,
+        TxOutput,
+        TxStatus,
+        PostedDatapoint,
+        ObservedPoolState,
+        Alert,
+        NotificationKind,
+        DowntimeReport,
+        CauseDowntime,
+        DowntimeInterval,
+        DowntimeCause,
+        HistoryPage,
+        HistoryPoint,
+        Readiness,
+        HealthCheck,
+        DataSourceChange
+    )),
+    modifiers(&AdminTokenScheme)
+)]
+struct ApiDoc;
+
+/// The bearer token of the admin routes
+struct AdminTokenScheme;
+
+impl Modify for AdminTokenScheme {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+/// OpenAPI document of the REST API
+async fn openapi() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+/// Browsers may read the API (GET only) from the pages of `origin`
+fn cors_layer(origin: AllowOrigin) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(origin)
+        .allow_methods([axum::http::Method::GET])
+        .allow_headers([header::IF_NONE_MATCH])
+        .expose_headers([header::ETAG])
+}
+
+/// Serve the REST API on `address` until the process exits. The admin routes are served only with
+/// an `admin_token`.
+pub async fn start_rest_server(
+    repost_receiver: Receiver<bool>,
+    address: SocketAddr,
+    admin_token: Option<String>,
+) -> Result<(), anyhow::Error> {
+    let mut app = Router::new()
         .route("/", get(root))
         .route("/oracleInfo", get(oracle_info))
         .route("/oracleStatus", get(oracle_status))
         .route("/poolInfo", get(pool_info))
         .route("/nodeInfo", get(node_info))
         .route("/poolStatus", get(pool_status))
+        .route("/rate", get(rate))
+        .route("/ballotStatus", get(ballot_status))
+        .route("/poolHistory", get(pool_history))
+        .route("/epochStats", get(epoch_stats))
+        .route("/peers", get(peers))
+        .route("/fees", get(fees))
+        .route("/txs", get(txs))
+        .route("/datapoints", get(datapoints))
+        .route("/poolStates", get(pool_states))
+        .route("/alerts", get(alerts))
+        .route("/downtime", get(downtime))
+        .route("/history", get(history))
         .route("/blockHeight", get(block_height))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready))
+        .route("/metrics", get(metrics))
+        .route("/openapi.json", get(openapi))
+        .route("/events", get(events))
         .route(
             "/requireDatapointRepost",
             get(|| require_datapoint_repost(repost_receiver)),
-        )
-        .layer(
-            CorsLayer::new()
-                .allow_origin(tower_http::cors::Any)
-                .allow_methods([axum::http::Method::GET]),
         );
-    let addr = SocketAddr::from(([0, 0, 0, 0], get_core_api_port().parse().unwrap()));
-    axum::Server::bind(&addr)
+    if let Some(admin_token) = admin_token {
+        app = app.nest("/admin", admin_routes(admin_token));
+    } else {
+        log::info!("No core_api_admin_token_file in the config, the admin routes are disabled");
+    }
+    let app = match &ORACLE_CONFIG.core_api_cors_allowed_origins {
+        None => app.layer(cors_layer(AllowOrigin::any())),
+        Some(origins) if origins.is_empty() => app,
+        Some(origins) => {
+            let origins = origins
+                .iter()
+                .map(|origin| HeaderValue::from_str(origin))
+                .collect::<Result<Vec<_>, _>>()?;
+            app.layer(cors_layer(AllowOrigin::list(origins)))
+        }
+    };
+    log::info!("REST API listening on {}", address);
+    axum::Server::try_bind(&address)?
         .serve(app.into_make_service())
-        .await
-        .unwrap();
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        assert!(doc["paths"]["/rate"]["get"].is_object());
+        assert!(doc["paths"]["/admin/dataSource"]["post"]["security"].is_array());
+        assert!(doc["components"]["schemas"]["PoolStatus"].is_object());
+        assert!(doc["components"]["securitySchemes"]["admin_token"].is_object());
+    }
 }
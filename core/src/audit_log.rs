@@ -0,0 +1,196 @@
+//! Append-only audit log (`audit_log.jsonl`) of the txs built by the daemon and the commands, for
+//! the pool admins and auditors reconstructing what an oracle did. Every built tx is logged as one
+//! JSON line with its action, the answer to the confirmation prompt of the command (`not_prompted`
+//! for the daemon), the outcome (submitted, declined or failed with the error), the inputs, the
+//! data inputs, the outputs with their tokens and registers, and the fee. The log is never
+//! rewritten, failures to write it are only logged.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::Transaction;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::downtime::unix_now;
+use crate::fees::unsigned_tx_fee;
+
+pub const AUDIT_LOG_FILE: &str = "audit_log.jsonl";
+
+/// Answer to the confirmation prompt of the command, taken by the next logged tx
+static PROMPT_RESULT: Lazy<Mutex<Option<PromptResult>>> = Lazy::new(|| Mutex::new(None));
+
+/// Serializes the appends of the daemon threads
+static WRITE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptResult {
+    /// Built by the daemon
+    NotPrompted,
+    Confirmed,
+    /// Confirmed by `--yes`
+    AssumedYes,
+    Declined,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxOutcome {
+    Submitted,
+    Declined,
+    /// Signing or submitting failed
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix time in seconds
+    pub time: u64,
+    pub action: String,
+    pub prompt: PromptResult,
+    pub outcome: TxOutcome,
+    /// Id of the signed tx
+    pub tx_id: Option<String>,
+    pub error: Option<String>,
+    pub fee: u64,
+    /// Ids of the spent boxes
+    pub inputs: serde_json::Value,
+    pub data_inputs: serde_json::Value,
+    /// Output candidates as the node API encodes them (value, ergo tree, tokens, registers)
+    pub outputs: serde_json::Value,
+}
+
+impl AuditEntry {
+    pub fn new(
+        action: &str,
+        unsigned_tx: &UnsignedTransaction,
+        prompt: PromptResult,
+        submitted: Result<&Transaction, String>,
+    ) -> Self {
+        let mut tx_json = serde_json::to_value(unsigned_tx).unwrap_or_default();
+        let mut take = |field: &str| {
+            tx_json
+                .get_mut(field)
+                .map(serde_json::Value::take)
+                .unwrap_or_else(|| serde_json::Value::Array(Vec::new()))
+        };
+        let (outcome, tx_id, error) = match submitted {
+            Ok(tx) => (TxOutcome::Submitted, Some(String::from(tx.id().0)), None),
+            Err(_) if prompt == PromptResult::Declined => (TxOutcome::Declined, None, None),
+            Err(e) => (TxOutcome::Failed, None, Some(e)),
+        };
+        AuditEntry {
+            time: unix_now(),
+            action: action.to_string(),
+            prompt,
+            outcome,
+            tx_id,
+            error,
+            fee: unsigned_tx_fee(unsigned_tx),
+            inputs: take("inputs"),
+            data_inputs: take("dataInputs"),
+            outputs: take("outputs"),
+        }
+    }
+}
+
+/// Remember the answer to the confirmation prompt for the tx signed next
+pub fn set_prompt_result(prompt: PromptResult) {
+    *PROMPT_RESULT.lock().unwrap() = Some(prompt);
+}
+
+fn take_prompt_result() -> PromptResult {
+    PROMPT_RESULT
+        .lock()
+        .unwrap()
+        .take()
+        .unwrap_or(PromptResult::NotPrompted)
+}
+
+/// Log the tx built for `action`, signed and submitted or failed with the error
+pub fn record_tx(
+    action: &str,
+    unsigned_tx: &UnsignedTransaction,
+    submitted: Result<&Transaction, String>,
+) {
+    let entry = AuditEntry::new(action, unsigned_tx, take_prompt_result(), submitted);
+    append(Path::new(AUDIT_LOG_FILE), &entry);
+}
+
+/// Log the tx built for `action` the operator declined at the confirmation prompt
+pub fn record_declined(action: &str, unsigned_tx: &UnsignedTransaction) {
+    take_prompt_result();
+    let entry = AuditEntry::new(
+        action,
+        unsigned_tx,
+        PromptResult::Declined,
+        Err("declined".to_string()),
+    );
+    append(Path::new(AUDIT_LOG_FILE), &entry);
+}
+
+fn append(path: &Path, entry: &AuditEntry) {
+    if let Err(e) = append_entry(path, entry) {
+        log::warn!(
+            "Failed to write the {} audit log entry: {}",
+            entry.action,
+            e
+        );
+    }
+}
+
+fn append_entry(path: &Path, entry: &AuditEntry) -> Result<(), std::io::Error> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    let _lock = WRITE_LOCK.lock().unwrap();
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    // One write per line, a reader never sees a partial entry
+    file.write_all(line.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    #[test]
+    fn test_append_entries() {
+        let path = std::env::temp_dir().join("oracle_core_test_audit_log.jsonl");
+        let _ = std::fs::remove_file(&path);
+        let unsigned_tx = force_any_val::<UnsignedTransaction>();
+        let failed = AuditEntry::new(
+            "refresh",
+            &unsigned_tx,
+            PromptResult::NotPrompted,
+            Err("node error".to_string()),
+        );
+        let declined = AuditEntry::new(
+            "exit_pool",
+            &unsigned_tx,
+            PromptResult::Declined,
+            Err("declined".to_string()),
+        );
+        append_entry(&path, &failed).unwrap();
+        append_entry(&path, &declined).unwrap();
+
+        let entries: Vec<AuditEntry> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries, vec![failed, declined]);
+        assert_eq!(entries[0].outcome, TxOutcome::Failed);
+        assert_eq!(entries[0].error.as_deref(), Some("node error"));
+        assert_eq!(entries[1].outcome, TxOutcome::Declined);
+        assert_eq!(
+            entries[0].inputs.as_array().map(Vec::len),
+            Some(unsigned_tx.inputs.len())
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+}
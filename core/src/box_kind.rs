@@ -1,11 +1,15 @@
 mod ballot_box;
+mod buyback_box;
 mod oracle_box;
 mod pool_box;
 mod refresh_box;
+mod spec_box;
 mod update_box;
 
 pub use ballot_box::*;
+pub use buyback_box::*;
 pub use oracle_box::*;
 pub use pool_box::*;
 pub use refresh_box::*;
+pub use spec_box::*;
 pub use update_box::*;
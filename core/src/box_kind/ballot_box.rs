@@ -1,3 +1,4 @@
+use super::spec_box::{impl_box_wrapper, layout_register, layout_token, SpecBox};
 use crate::{
     contracts::ballot::{
         BallotContract, BallotContractError, BallotContractInputs, BallotContractParameters,
@@ -13,7 +14,7 @@ use ergo_lib::{
             ergo_box::{box_value::BoxValue, ErgoBox, ErgoBoxCandidate, NonMandatoryRegisterId},
             token::{Token, TokenId},
         },
-        mir::constant::{TryExtractFromError, TryExtractInto},
+        mir::constant::TryExtractFromError,
         serialization::SigmaSerializationError,
         sigma_protocol::sigma_boolean::ProveDlog,
     },
@@ -24,8 +25,8 @@ use thiserror::Error;
 pub enum BallotBoxError {
     #[error("ballot box: no ballot token found")]
     NoBallotToken,
-    #[error("ballot box: unknown ballot token id in `TOKENS(0)`")]
-    UnknownBallotTokenId,
+    #[error("ballot box: unknown ballot token id {0:?} in `TOKENS(0)`")]
+    UnknownBallotTokenId(TokenId),
     #[error("ballot box: no reward token id in R7 register")]
     NoRewardTokenIdInR7,
     #[error("ballot box: no reward token quantity in R8 register")]
@@ -62,27 +63,22 @@ pub struct BallotBoxWrapper {
     contract: BallotContract,
 }
 
+impl_box_wrapper!(BallotBoxWrapper {
+    error: BallotBoxError,
+    no_tokens: BallotBoxError::NoBallotToken,
+    incorrect_token: BallotBoxError::UnknownBallotTokenId,
+    tokens: {},
+    registers: { R4: EcPoint => BallotBoxError::NoGroupElementInR4 },
+});
+
 impl BallotBoxWrapper {
     pub fn new(
         ergo_box: ErgoBox,
         inputs: &BallotBoxWrapperInputs,
         ballot_token_owner_address: &Address,
     ) -> Result<Self, BallotBoxError> {
-        let ballot_token_id = &ergo_box
-            .tokens
-            .as_ref()
-            .ok_or(BallotBoxError::NoBallotToken)?
-            .get(0)
-            .ok_or(BallotBoxError::NoBallotToken)?
-            .token_id;
-        if *ballot_token_id != inputs.ballot_token_id {
-            return Err(BallotBoxError::UnknownBallotTokenId);
-        }
-
-        let ec = ergo_box
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .ok_or(BallotBoxError::NoGroupElementInR4)?
-            .try_extract_into::<EcPoint>()?;
+        Self::check_layout(&ergo_box, &inputs.ballot_token_id)?;
+        let ec = layout_register::<EcPoint>(&ergo_box, NonMandatoryRegisterId::R4);
         if ballot_token_owner_address != &Address::P2Pk(ProveDlog::from(ec)) {
             return Err(BallotBoxError::UnexpectedGroupElementInR4);
         }
@@ -136,45 +132,29 @@ pub struct VoteBallotBoxWrapper {
     contract: BallotContract,
 }
 
+impl_box_wrapper!(VoteBallotBoxWrapper {
+    error: BallotBoxError,
+    no_tokens: BallotBoxError::NoBallotToken,
+    incorrect_token: BallotBoxError::UnknownBallotTokenId,
+    tokens: {},
+    registers: {
+        R4: EcPoint => BallotBoxError::NoGroupElementInR4,
+        R5: i32 => BallotBoxError::NoUpdateBoxCreationHeightInR5,
+        R6: Digest32 => BallotBoxError::NoPoolBoxAddressInR6,
+        R7: TokenId => BallotBoxError::NoRewardTokenIdInR7,
+        R8: i64 => BallotBoxError::NoRewardTokenQuantityInR8,
+    },
+});
+
 impl VoteBallotBoxWrapper {
     pub fn new(ergo_box: ErgoBox, inputs: &BallotBoxWrapperInputs) -> Result<Self, BallotBoxError> {
-        let ballot_token_id = &ergo_box
-            .tokens
-            .as_ref()
-            .ok_or(BallotBoxError::NoBallotToken)?
-            .get(0)
-            .ok_or(BallotBoxError::NoBallotToken)?
-            .token_id;
-        if *ballot_token_id != inputs.ballot_token_id {
-            return Err(BallotBoxError::UnknownBallotTokenId);
-        }
-
-        if ergo_box
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .ok_or(BallotBoxError::NoGroupElementInR4)?
-            .try_extract_into::<EcPoint>()
-            .is_err()
-        {
-            return Err(BallotBoxError::NoGroupElementInR4);
-        }
-        let update_box_creation_height = ergo_box
-            .get_register(NonMandatoryRegisterId::R5.into())
-            .ok_or(BallotBoxError::NoUpdateBoxCreationHeightInR5)?
-            .try_extract_into::<i32>()?;
-
-        let pool_box_address_hash = ergo_box
-            .get_register(NonMandatoryRegisterId::R6.into())
-            .ok_or(BallotBoxError::NoPoolBoxAddressInR6)?
-            .try_extract_into::<Digest32>()?;
-
-        let reward_token_id = ergo_box
-            .get_register(NonMandatoryRegisterId::R7.into())
-            .ok_or(BallotBoxError::NoRewardTokenIdInR7)?
-            .try_extract_into::<TokenId>()?;
-        let reward_token_quantity = ergo_box
-            .get_register(NonMandatoryRegisterId::R8.into())
-            .ok_or(BallotBoxError::NoRewardTokenQuantityInR8)?
-            .try_extract_into::<i64>()? as u64;
+        Self::check_layout(&ergo_box, &inputs.ballot_token_id)?;
+        let update_box_creation_height =
+            layout_register::<i32>(&ergo_box, NonMandatoryRegisterId::R5);
+        let pool_box_address_hash = layout_register(&ergo_box, NonMandatoryRegisterId::R6);
+        let reward_token_id = layout_register(&ergo_box, NonMandatoryRegisterId::R7);
+        let reward_token_quantity =
+            layout_register::<i64>(&ergo_box, NonMandatoryRegisterId::R8) as u64;
 
         let contract =
             BallotContract::from_ergo_tree(ergo_box.ergo_tree.clone(), &inputs.contract_inputs)?;
@@ -202,13 +182,7 @@ impl BallotBox for BallotBoxWrapper {
     }
 
     fn ballot_token(&self) -> Token {
-        self.ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone()
+        layout_token(&self.ergo_box, 0)
     }
 
     fn min_storage_rent(&self) -> BoxValue {
@@ -216,12 +190,7 @@ impl BallotBox for BallotBoxWrapper {
     }
 
     fn ballot_token_owner(&self) -> ProveDlog {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .unwrap()
-            .try_extract_into::<EcPoint>()
-            .unwrap()
-            .into()
+        layout_register::<EcPoint>(&self.ergo_box, NonMandatoryRegisterId::R4).into()
     }
 
     fn get_box(&self) -> &ErgoBox {
@@ -235,13 +204,7 @@ impl BallotBox for VoteBallotBoxWrapper {
     }
 
     fn ballot_token(&self) -> Token {
-        self.ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone()
+        layout_token(&self.ergo_box, 0)
     }
 
     fn min_storage_rent(&self) -> BoxValue {
@@ -249,12 +212,7 @@ impl BallotBox for VoteBallotBoxWrapper {
     }
 
     fn ballot_token_owner(&self) -> ProveDlog {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .unwrap()
-            .try_extract_into::<EcPoint>()
-            .unwrap()
-            .into()
+        layout_register::<EcPoint>(&self.ergo_box, NonMandatoryRegisterId::R4).into()
     }
 
     fn get_box(&self) -> &ErgoBox {
@@ -294,3 +252,21 @@ pub fn make_local_ballot_box_candidate(
     builder.add_token(ballot_token);
     builder.build()
 }
+
+/// Make a ballot box candidate without a vote (no R5-R8 registers), as created when the ballot
+/// box is spent in the update pool transaction.
+pub fn make_ballot_box_candidate(
+    contract: &BallotContract,
+    ballot_token_owner: ProveDlog,
+    ballot_token: Token,
+    value: BoxValue,
+    creation_height: u32,
+) -> Result<ErgoBoxCandidate, ErgoBoxCandidateBuilderError> {
+    let mut builder = ErgoBoxCandidateBuilder::new(value, contract.ergo_tree(), creation_height);
+    builder.set_register_value(
+        NonMandatoryRegisterId::R4,
+        (*ballot_token_owner.h).clone().into(),
+    );
+    builder.add_token(ballot_token);
+    builder.build()
+}
@@ -0,0 +1,81 @@
+use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilderError;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBoxCandidate;
+use ergo_lib::ergotree_ir::chain::token::Token;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use thiserror::Error;
+
+use super::spec_box::impl_box_wrapper;
+use super::spec_box::layout_token;
+use super::spec_box::token_at;
+use super::spec_box::SpecBox;
+use crate::contracts::buyback::BuybackContract;
+use crate::contracts::buyback::BuybackContractError;
+
+#[derive(Debug, Error)]
+pub enum BuybackBoxError {
+    #[error("buyback box: no tokens found")]
+    NoTokens,
+    #[error("buyback box: incorrect buyback token id: {0:?}")]
+    IncorrectBuybackTokenId(TokenId),
+    #[error("buyback box: buyback contract error: {0:?}")]
+    BuybackContractError(#[from] BuybackContractError),
+}
+
+/// Box holding the buyback NFT in `tokens(0)` and the bought back reward tokens (if any) in
+/// `tokens(1)`. The reward tokens are moved to the pool box on refresh.
+#[derive(Clone, Debug)]
+pub struct BuybackBoxWrapper {
+    ergo_box: ErgoBox,
+    contract: BuybackContract,
+}
+
+impl_box_wrapper!(BuybackBoxWrapper {
+    error: BuybackBoxError,
+    no_tokens: BuybackBoxError::NoTokens,
+    incorrect_token: BuybackBoxError::IncorrectBuybackTokenId,
+    tokens: {},
+    registers: {},
+});
+
+impl BuybackBoxWrapper {
+    pub fn new(b: ErgoBox, buyback_nft_token_id: &TokenId) -> Result<Self, BuybackBoxError> {
+        Self::check_layout(&b, buyback_nft_token_id)?;
+        let contract = BuybackContract::from_ergo_tree(b.ergo_tree.clone())?;
+        Ok(Self {
+            ergo_box: b,
+            contract,
+        })
+    }
+
+    pub fn contract(&self) -> &BuybackContract {
+        &self.contract
+    }
+
+    pub fn buyback_nft_token(&self) -> Token {
+        layout_token(&self.ergo_box, 0)
+    }
+
+    /// Reward tokens held by the box, `None` if nothing was bought back yet
+    pub fn reward_token(&self) -> Option<Token> {
+        token_at(&self.ergo_box, 1)
+    }
+
+    pub fn get_box(&self) -> &ErgoBox {
+        &self.ergo_box
+    }
+}
+
+/// The buyback box recreated without its reward tokens
+pub fn make_buyback_box_candidate(
+    contract: &BuybackContract,
+    buyback_nft: Token,
+    value: BoxValue,
+    creation_height: u32,
+) -> Result<ErgoBoxCandidate, ErgoBoxCandidateBuilderError> {
+    let mut builder = ErgoBoxCandidateBuilder::new(value, contract.ergo_tree(), creation_height);
+    builder.add_token(buyback_nft);
+    builder.build()
+}
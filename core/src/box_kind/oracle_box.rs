@@ -8,10 +8,14 @@ use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisterId;
 use ergo_lib::ergotree_ir::chain::token::Token;
 use ergo_lib::ergotree_ir::chain::token::TokenId;
 use ergo_lib::ergotree_ir::mir::constant::TryExtractFromError;
-use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
 use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
 use thiserror::Error;
 
+use super::spec_box::impl_box_wrapper;
+use super::spec_box::layout_register;
+use super::spec_box::layout_token;
+use super::spec_box::register_value;
+use super::spec_box::SpecBox;
 use crate::contracts::oracle::OracleContract;
 use crate::contracts::oracle::OracleContractError;
 use crate::contracts::oracle::OracleContractInputs;
@@ -31,12 +35,10 @@ pub enum OracleBoxError {
     NoTokens,
     #[error("oracle box: no oracle token found")]
     NoOracleToken,
-    #[error("oracle box: unknown oracle token id in `TOKENS(0)`")]
-    UnknownOracleTokenId,
+    #[error("oracle box: unknown oracle token id {0:?} in `TOKENS(0)`")]
+    UnknownOracleTokenId(TokenId),
     #[error("oracle box: no reward token found")]
     NoRewardToken,
-    #[error("oracle box: unknown reward token id in `TOKENS(1)`")]
-    UnknownRewardTokenId,
     #[error("oracle box: no public key in R4")]
     NoPublicKeyInR4,
     #[error("oracle box: no epoch counter in R5")]
@@ -71,47 +73,26 @@ pub enum OracleBoxWrapper {
     Collected(CollectedOracleBox),
 }
 
+impl_box_wrapper!(OracleBoxWrapper {
+    error: OracleBoxError,
+    no_tokens: OracleBoxError::NoTokens,
+    incorrect_token: OracleBoxError::UnknownOracleTokenId,
+    // The reward token id is not checked against the config. After a reward token update the oracle
+    // boxes may hold the old reward token until their next datapoint is published, the refresh
+    // action only collects the oracle boxes with the reward token of the pool box.
+    tokens: { 1 => OracleBoxError::NoRewardToken },
+    // We won't be analysing the actual address since there exists multiple oracle boxes that will
+    // be inputs for the 'refresh pool' operation.
+    registers: { R4: EcPoint => OracleBoxError::NoPublicKeyInR4 },
+});
+
 impl OracleBoxWrapper {
     pub fn new(b: ErgoBox, inputs: &OracleBoxWrapperInputs) -> Result<Self, OracleBoxError> {
-        let oracle_token_id = b
-            .tokens
-            .as_ref()
-            .ok_or(OracleBoxError::NoTokens)?
-            .first()
-            .token_id
-            .clone();
-
-        if oracle_token_id != inputs.oracle_token_id {
-            return Err(OracleBoxError::UnknownOracleTokenId);
-        }
-
-        let reward_token_id = b
-            .tokens
-            .as_ref()
-            .ok_or(OracleBoxError::NoTokens)?
-            .get(1)
-            .ok_or(OracleBoxError::NoRewardToken)?
-            .token_id
-            .clone();
-
-        if reward_token_id != inputs.reward_token_id {
-            return Err(OracleBoxError::UnknownRewardTokenId);
-        }
-
-        // We won't be analysing the actual address since there exists multiple oracle boxes that
-        // will be inputs for the 'refresh pool' operation.
-        let _ = b
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .ok_or(OracleBoxError::NoPublicKeyInR4)?
-            .try_extract_into::<EcPoint>()?;
-
-        let epoch_counter_opt = b
-            .get_register(NonMandatoryRegisterId::R5.into())
-            .and_then(|r| r.try_extract_into::<i32>().ok());
+        Self::check_layout(&b, &inputs.oracle_token_id)?;
 
-        let rate_opt = b
-            .get_register(NonMandatoryRegisterId::R6.into())
-            .and_then(|r| r.try_extract_into::<i64>().ok());
+        // The epoch counter and the data point are only set on a posted oracle box
+        let epoch_counter_opt = register_value::<i32>(&b, NonMandatoryRegisterId::R5);
+        let rate_opt = register_value::<i64>(&b, NonMandatoryRegisterId::R6);
 
         let contract =
             OracleContract::from_ergo_tree(b.ergo_tree.clone(), &inputs.contract_inputs)?;
@@ -137,32 +118,15 @@ impl OracleBoxWrapper {
 
 impl OracleBox for OracleBoxWrapper {
     fn oracle_token(&self) -> Token {
-        self.get_box()
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone()
+        layout_token(self.get_box(), 0)
     }
 
     fn reward_token(&self) -> Token {
-        self.get_box()
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .clone()
+        layout_token(self.get_box(), 1)
     }
 
     fn public_key(&self) -> ProveDlog {
-        self.get_box()
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .unwrap()
-            .try_extract_into::<EcPoint>()
-            .unwrap()
-            .into()
+        layout_register::<EcPoint>(self.get_box(), NonMandatoryRegisterId::R4).into()
     }
 
     fn get_box(&self) -> &ErgoBox {
@@ -189,32 +153,15 @@ impl PostedOracleBox {
     }
 
     pub fn oracle_token(&self) -> Token {
-        self.ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone()
+        layout_token(&self.ergo_box, 0)
     }
 
     pub fn reward_token(&self) -> Token {
-        self.ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .clone()
+        layout_token(&self.ergo_box, 1)
     }
 
     pub fn public_key(&self) -> ProveDlog {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .unwrap()
-            .try_extract_into::<EcPoint>()
-            .unwrap()
-            .into()
+        layout_register::<EcPoint>(&self.ergo_box, NonMandatoryRegisterId::R4).into()
     }
 
     pub fn contract(&self) -> &OracleContract {
@@ -226,19 +173,11 @@ impl PostedOracleBox {
     }
 
     pub fn epoch_counter(&self) -> u32 {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R5.into())
-            .unwrap()
-            .try_extract_into::<i32>()
-            .unwrap() as u32
+        layout_register::<i32>(&self.ergo_box, NonMandatoryRegisterId::R5) as u32
     }
 
     pub fn rate(&self) -> u64 {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R6.into())
-            .unwrap()
-            .try_extract_into::<i64>()
-            .unwrap() as u64
+        layout_register::<i64>(&self.ergo_box, NonMandatoryRegisterId::R6) as u64
     }
 }
 
@@ -247,7 +186,8 @@ pub struct OracleBoxWrapperInputs {
     pub contract_inputs: OracleContractInputs,
     /// Ballot token is expected to reside in `tokens(0)` of the oracle box.
     pub oracle_token_id: TokenId,
-    /// Reward token is expected to reside in `tokens(1)` of the oracle box.
+    /// Reward token id at the time the config was created. The reward token resides in `tokens(1)`
+    /// of the oracle box, the current reward token id is read from the pool box.
     pub reward_token_id: TokenId,
 }
 
@@ -6,9 +6,12 @@ use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBoxCandidate;
 use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisterId;
 use ergo_lib::ergotree_ir::chain::token::Token;
 use ergo_lib::ergotree_ir::chain::token::TokenId;
-use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
 use thiserror::Error;
 
+use super::spec_box::impl_box_wrapper;
+use super::spec_box::layout_register;
+use super::spec_box::layout_token;
+use super::spec_box::SpecBox;
 use crate::contracts::pool::PoolContract;
 use crate::contracts::pool::PoolContractError;
 use crate::contracts::pool::PoolContractInputs;
@@ -35,10 +38,8 @@ pub enum PoolBoxError {
     NoRewardToken,
     #[error("pool box: {0:?}")]
     PoolContractError(#[from] PoolContractError),
-    #[error("pool box: unknown pool NFT token id in box")]
-    UnknownPoolNftId,
-    #[error("pool box: unknown reward token id in box")]
-    UnknownRewardTokenId,
+    #[error("pool box: unknown pool NFT token id {0:?} in box")]
+    UnknownPoolNftId(TokenId),
 }
 
 #[derive(Clone, Debug)]
@@ -47,42 +48,24 @@ pub struct PoolBoxWrapper {
     contract: PoolContract,
 }
 
+impl_box_wrapper!(PoolBoxWrapper {
+    error: PoolBoxError,
+    no_tokens: PoolBoxError::NoTokens,
+    incorrect_token: PoolBoxError::UnknownPoolNftId,
+    // The reward token id is not checked against the config, it can be changed in the update pool
+    // transaction. The pool box is the authority on the current reward token id.
+    tokens: { 1 => PoolBoxError::NoRewardToken },
+    // No need to analyse the data point and the epoch counter as their validity is checked within
+    // the refresh, pool and oracle contracts.
+    registers: {
+        R4: i64 => PoolBoxError::NoDataPoint,
+        R5: i32 => PoolBoxError::NoEpochCounter,
+    },
+});
+
 impl PoolBoxWrapper {
     pub fn new(b: ErgoBox, inputs: &PoolBoxWrapperInputs) -> Result<Self, PoolBoxError> {
-        if let Some(token) = b.tokens.as_ref().ok_or(PoolBoxError::NoTokens)?.get(0) {
-            if token.token_id != inputs.pool_nft_token_id {
-                return Err(PoolBoxError::UnknownPoolNftId);
-            }
-        } else {
-            return Err(PoolBoxError::NoTokens);
-        }
-
-        // No need to analyse the data point as its validity is checked within the refresh contract.
-        if b.get_register(NonMandatoryRegisterId::R4.into())
-            .ok_or(PoolBoxError::NoDataPoint)?
-            .try_extract_into::<i64>()
-            .is_err()
-        {
-            return Err(PoolBoxError::NoDataPoint);
-        }
-
-        // No need to analyse the epoch counter as its validity is checked within the pool and
-        // oracle contracts.
-        if b.get_register(NonMandatoryRegisterId::R5.into())
-            .ok_or(PoolBoxError::NoEpochCounter)?
-            .try_extract_into::<i32>()
-            .is_err()
-        {
-            return Err(PoolBoxError::NoEpochCounter);
-        }
-
-        if let Some(reward_token) = b.tokens.as_ref().ok_or(PoolBoxError::NoTokens)?.get(1) {
-            if reward_token.token_id != inputs.reward_token_id {
-                return Err(PoolBoxError::UnknownRewardTokenId);
-            }
-        } else {
-            return Err(PoolBoxError::NoRewardToken);
-        }
+        Self::check_layout(&b, &inputs.pool_nft_token_id)?;
         let contract = PoolContract::from_ergo_tree(b.ergo_tree.clone(), &inputs.contract_inputs)?;
         Ok(Self {
             ergo_box: b,
@@ -93,39 +76,19 @@ impl PoolBoxWrapper {
 
 impl PoolBox for PoolBoxWrapper {
     fn pool_nft_token(&self) -> Token {
-        self.ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone()
+        layout_token(&self.ergo_box, 0)
     }
 
     fn epoch_counter(&self) -> u32 {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R5.into())
-            .unwrap()
-            .try_extract_into::<i32>()
-            .unwrap() as u32
+        layout_register::<i32>(&self.ergo_box, NonMandatoryRegisterId::R5) as u32
     }
 
     fn rate(&self) -> i64 {
-        self.ergo_box
-            .get_register(NonMandatoryRegisterId::R4.into())
-            .unwrap()
-            .try_extract_into::<i64>()
-            .unwrap()
+        layout_register(&self.ergo_box, NonMandatoryRegisterId::R4)
     }
 
     fn reward_token(&self) -> Token {
-        self.ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .clone()
+        layout_token(&self.ergo_box, 1)
     }
 
     fn get_box(&self) -> &ErgoBox {
@@ -142,7 +105,8 @@ pub struct PoolBoxWrapperInputs {
     pub contract_inputs: PoolContractInputs,
     /// Pool NFT token is expected to reside in `tokens(0)` of the pool box.
     pub pool_nft_token_id: TokenId,
-    /// Reward token is expected to reside in `tokens(1)` of the pool box.
+    /// Reward token id at the time the config was created. The reward token resides in `tokens(1)`
+    /// of the pool box, which holds a different reward token after a reward token update.
     pub reward_token_id: TokenId,
 }
 
@@ -7,6 +7,9 @@ use ergo_lib::ergotree_ir::chain::token::Token;
 use ergo_lib::ergotree_ir::chain::token::TokenId;
 use thiserror::Error;
 
+use super::spec_box::impl_box_wrapper;
+use super::spec_box::layout_token;
+use super::spec_box::SpecBox;
 use crate::contracts::refresh::RefreshContract;
 use crate::contracts::refresh::RefreshContractError;
 use crate::contracts::refresh::RefreshContractInputs;
@@ -81,20 +84,17 @@ impl RefreshBoxWrapperInputs {
     }
 }
 
+impl_box_wrapper!(RefreshBoxWrapper {
+    error: RefreshBoxError,
+    no_tokens: RefreshBoxError::NoTokens,
+    incorrect_token: RefreshBoxError::IncorrectRefreshTokenId,
+    tokens: {},
+    registers: {},
+});
+
 impl RefreshBoxWrapper {
     pub fn new(b: ErgoBox, inputs: &RefreshBoxWrapperInputs) -> Result<Self, RefreshBoxError> {
-        let refresh_token_id = b
-            .tokens
-            .as_ref()
-            .ok_or(RefreshBoxError::NoTokens)?
-            .get(0)
-            .ok_or(RefreshBoxError::NoTokens)?
-            .token_id
-            .clone();
-        if refresh_token_id != inputs.refresh_nft_token_id {
-            return Err(RefreshBoxError::IncorrectRefreshTokenId(refresh_token_id));
-        }
-
+        Self::check_layout(&b, &inputs.refresh_nft_token_id)?;
         let contract =
             RefreshContract::from_ergo_tree(b.ergo_tree.clone(), &inputs.contract_inputs)?;
         Ok(Self {
@@ -106,13 +106,7 @@ impl RefreshBoxWrapper {
 
 impl RefreshBox for RefreshBoxWrapper {
     fn refresh_nft_token(&self) -> Token {
-        self.ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone()
+        layout_token(&self.ergo_box, 0)
     }
 
     fn get_box(&self) -> &ErgoBox {
@@ -0,0 +1,136 @@
+//! Token and register layout shared by the boxes of the protocol. A box is identified by the token
+//! in `tokens(0)` (the NFT of the pool, refresh, update or buyback box, the oracle or ballot token)
+//! and holds the other tokens and registers expected by its contract. [`impl_box_wrapper!`]
+//! implements [`SpecBox`] for a wrapper from the layout of its box, the wrapper then parses its
+//! contract and checks what is specific to it. The accessors of the wrappers read the tokens and
+//! registers with [`layout_token`] and [`layout_register`], which can't fail once the layout was
+//! checked.
+
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisterId;
+use ergo_lib::ergotree_ir::chain::token::Token;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use ergo_lib::ergotree_ir::mir::constant::Constant;
+use ergo_lib::ergotree_ir::mir::constant::TryExtractFrom;
+use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
+
+/// Box of the protocol with the layout of its tokens and registers, guarded by its contract
+pub trait SpecBox {
+    type Error;
+
+    /// Check that `tokens(0)` of `b` holds `spec_token_id` and that the other tokens and the
+    /// registers of the layout are set
+    fn check_layout(b: &ErgoBox, spec_token_id: &TokenId) -> Result<(), Self::Error>;
+}
+
+/// Token at `index` of `b`
+pub fn token_at(b: &ErgoBox, index: usize) -> Option<Token> {
+    b.tokens.as_ref()?.get(index).cloned()
+}
+
+/// Value of the register `id` of `b`, `None` if the register is empty or holds another type
+pub fn register_value<T: TryExtractFrom<Constant>>(
+    b: &ErgoBox,
+    id: NonMandatoryRegisterId,
+) -> Option<T> {
+    b.get_register(id.into())?.try_extract_into::<T>().ok()
+}
+
+/// Token at `index` of a box of the layout checked by [`SpecBox::check_layout`]
+pub fn layout_token(b: &ErgoBox, index: usize) -> Token {
+    token_at(b, index).unwrap()
+}
+
+/// Register `id` of a box of the layout checked by [`SpecBox::check_layout`]
+pub fn layout_register<T: TryExtractFrom<Constant>>(b: &ErgoBox, id: NonMandatoryRegisterId) -> T {
+    register_value(b, id).unwrap()
+}
+
+/// Implement [`SpecBox`] for a wrapper: the error of a box without tokens and the error variant
+/// taking the token id found in `tokens(0)` when it isn't the expected one, then the other tokens
+/// and the registers (with their type) of the layout, each with the error of a box lacking it.
+macro_rules! impl_box_wrapper {
+    ($wrapper:ty {
+        error: $error:ty,
+        no_tokens: $no_tokens:expr,
+        incorrect_token: $incorrect_token:path,
+        tokens: { $($index:literal => $token_error:expr),* $(,)? },
+        registers: { $($register:ident: $register_type:ty => $register_error:expr),* $(,)? } $(,)?
+    }) => {
+        impl $crate::box_kind::SpecBox for $wrapper {
+            type Error = $error;
+
+            fn check_layout(
+                b: &ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox,
+                spec_token_id: &ergo_lib::ergotree_ir::chain::token::TokenId,
+            ) -> std::result::Result<(), Self::Error> {
+                let token_id = $crate::box_kind::token_at(b, 0)
+                    .ok_or($no_tokens)?
+                    .token_id;
+                if token_id != *spec_token_id {
+                    return Err($incorrect_token(token_id));
+                }
+                $(
+                    if $crate::box_kind::token_at(b, $index).is_none() {
+                        return Err($token_error);
+                    }
+                )*
+                $(
+                    if $crate::box_kind::register_value::<$register_type>(
+                        b,
+                        ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisterId::$register,
+                    )
+                    .is_none()
+                    {
+                        return Err($register_error);
+                    }
+                )*
+                Ok(())
+            }
+        }
+    };
+}
+
+pub(crate) use impl_box_wrapper;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::box_kind::{PoolBox, PoolBoxError, PoolBoxWrapper};
+    use crate::contracts::pool::PoolContractParameters;
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_commands::test_utils::{generate_token_ids, make_pool_box};
+
+    #[test]
+    fn test_check_layout() {
+        let token_ids = generate_token_ids();
+        let pool_box = make_pool_box(
+            200,
+            1,
+            *BASE_FEE,
+            100,
+            &PoolContractParameters::default(),
+            &token_ids,
+        )
+        .get_box()
+        .clone();
+        assert!(PoolBoxWrapper::check_layout(&pool_box, &token_ids.pool_nft_token_id).is_ok());
+        assert_eq!(
+            layout_register::<i64>(&pool_box, NonMandatoryRegisterId::R4),
+            200
+        );
+        assert_eq!(
+            layout_token(&pool_box, 0).token_id,
+            token_ids.pool_nft_token_id
+        );
+        assert_eq!(
+            register_value::<i32>(&pool_box, NonMandatoryRegisterId::R4),
+            None
+        );
+        assert!(token_at(&pool_box, 2).is_none());
+        assert!(matches!(
+            PoolBoxWrapper::check_layout(&pool_box, &token_ids.oracle_token_id),
+            Err(PoolBoxError::UnknownPoolNftId(id)) if id == token_ids.pool_nft_token_id
+        ));
+    }
+}
@@ -4,6 +4,9 @@ use ergo_lib::ergotree_ir::chain::token::TokenId;
 use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
 use thiserror::Error;
 
+use super::spec_box::impl_box_wrapper;
+use super::spec_box::layout_token;
+use super::spec_box::SpecBox;
 use crate::contracts::update::UpdateContract;
 use crate::contracts::update::UpdateContractError;
 use crate::contracts::update::UpdateContractInputs;
@@ -11,33 +14,31 @@ use crate::contracts::update::UpdateContractParameters;
 
 #[derive(Debug, Error)]
 pub enum UpdateBoxError {
-    #[error("oracle box: no tokens found")]
+    #[error("update box: no tokens found")]
     NoTokens,
     #[error("update contract: {0:?}")]
     UpdateContractError(#[from] UpdateContractError),
-    #[error("update contract: {0:?}")]
+    #[error("update box: incorrect update NFT id {0:?}")]
     IncorrectUpdateTokenId(TokenId),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct UpdateBoxWrapper {
     ergo_box: ErgoBox,
     contract: UpdateContract,
 }
 
+impl_box_wrapper!(UpdateBoxWrapper {
+    error: UpdateBoxError,
+    no_tokens: UpdateBoxError::NoTokens,
+    incorrect_token: UpdateBoxError::IncorrectUpdateTokenId,
+    tokens: {},
+    registers: {},
+});
+
 impl UpdateBoxWrapper {
     pub fn new(b: ErgoBox, inputs: &UpdateBoxWrapperInputs) -> Result<Self, UpdateBoxError> {
-        let update_token_id = b
-            .tokens
-            .as_ref()
-            .ok_or(UpdateBoxError::NoTokens)?
-            .get(0)
-            .ok_or(UpdateBoxError::NoTokens)?
-            .token_id
-            .clone();
-        if update_token_id != inputs.update_nft_token_id {
-            return Err(UpdateBoxError::IncorrectUpdateTokenId(update_token_id));
-        }
+        Self::check_layout(&b, &inputs.update_nft_token_id)?;
         let contract =
             UpdateContract::from_ergo_tree(b.ergo_tree.clone(), &inputs.contract_inputs)?;
 
@@ -50,13 +51,7 @@ impl UpdateBoxWrapper {
         self.contract.ergo_tree()
     }
     pub fn update_nft(&self) -> Token {
-        self.ergo_box
-            .tokens
-            .as_ref()
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .clone()
+        layout_token(&self.ergo_box, 0)
     }
     pub fn ballot_token_id(&self) -> TokenId {
         self.contract.ballot_token_id().clone()
@@ -1,21 +1,241 @@
-use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::TxId;
+use ergo_lib::ergotree_ir::chain::address::{AddressEncoderError, NetworkPrefix};
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_node_interface::node_interface::NodeError;
+use once_cell::sync;
+use serde::Serialize;
 
+use crate::audit_log::{self, PromptResult};
+use crate::oracle_config::OracleConfigError;
+use crate::prompt::{AssumeYesPrompt, Prompt, StdinPrompt};
+use crate::serde::SerdeConversionError;
+use crate::tx_preview::tx_preview;
+
+pub mod backup;
+pub mod ballot_status;
 pub mod bootstrap;
+pub mod consolidate_utxos;
+pub mod datapoint_history;
+pub mod diff_pool;
+pub mod earnings_report;
+pub mod encrypt_secret;
+pub mod exit_pool;
+pub mod export_history;
 pub mod extract_reward_tokens;
+pub mod generate_config;
+pub mod import_pool;
+pub mod init;
+pub mod maintain_refresh_box;
+pub mod migrate_pool;
+pub mod peer_report;
+pub mod pool_history;
 pub mod prepare_update;
+pub mod print_contract_addresses;
 pub mod print_reward_tokens;
+pub mod print_status;
+pub mod propose_update;
+pub mod publish_datapoint;
+pub mod renew_storage_rent;
+pub mod repair;
+pub mod rescan;
+pub mod run_pools;
+pub mod simulate;
+pub mod snapshot;
+pub mod split_config;
+pub mod top_up_pool_box;
 pub mod transfer_oracle_token;
+pub mod tx_history;
 pub mod update_pool;
+pub mod validate_config;
+pub mod verify_contracts;
+pub mod verify_pool;
+pub mod verify_proposal;
 pub mod vote_update_pool;
 
-pub(crate) fn ergo_explorer_transaction_link(tx_id_str: String, prefix: NetworkPrefix) -> String {
+/// Environment variable which, when set to `1`/`true`/`yes`, answers all confirmation prompts
+/// with 'YES'.
+pub const ASSUME_YES_ENV_VAR: &str = "ORACLE_CORE_ASSUME_YES";
+
+/// Set from the `--yes` command line flag.
+pub static ASSUME_YES: sync::OnceCell<bool> = sync::OnceCell::new();
+
+/// Set from the `--json` command line flag.
+pub static JSON_OUTPUT: sync::OnceCell<bool> = sync::OnceCell::new();
+
+/// Returns `true` if the command results should be printed as JSON.
+pub(crate) fn json_output() -> bool {
+    JSON_OUTPUT.get().copied().unwrap_or(false)
+}
+
+/// Result of a command that submits a transaction, printed in `--json` mode.
+#[derive(Debug, Serialize)]
+struct TransactionOutput {
+    status: &'static str,
+    tx_id: Option<TxId>,
+    explorer_link: Option<String>,
+    output_box_ids: Vec<String>,
+}
+
+/// Error of a failed command, printed in `--json` mode.
+#[derive(Debug, Serialize)]
+struct ErrorOutput<'a> {
+    status: &'static str,
+    command: &'a str,
+    code: i32,
+    error: String,
+}
+
+fn assume_yes() -> bool {
+    ASSUME_YES.get().copied().unwrap_or(false)
+        || std::env::var(ASSUME_YES_ENV_VAR)
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false)
+}
+
+/// Prompt of the CLI: the terminal, or confirming every tx if the `--yes` flag or the
+/// `ORACLE_CORE_ASSUME_YES` env var is set.
+pub(crate) fn cli_prompt() -> &'static dyn Prompt {
+    if assume_yes() {
+        &AssumeYesPrompt
+    } else {
+        &StdinPrompt
+    }
+}
+
+/// Ask the `prompt` to confirm the tx described by `message`, the answer is recorded in the audit
+/// log with the tx.
+pub(crate) fn confirm_transaction(
+    prompt: &dyn Prompt,
+    message: &str,
+) -> Result<bool, std::io::Error> {
+    let answer = prompt.confirm(message)?;
+    audit_log::set_prompt_result(answer);
+    Ok(matches!(
+        answer,
+        PromptResult::Confirmed | PromptResult::AssumedYes
+    ))
+}
+
+/// Ask the `prompt` to confirm `unsigned_tx`, shown with its preview (inputs, outputs and fee)
+/// before the `message`.
+pub(crate) fn confirm_transaction_preview(
+    prompt: &dyn Prompt,
+    unsigned_tx: &UnsignedTransaction,
+    prefix: NetworkPrefix,
+    message: &str,
+) -> Result<bool, std::io::Error> {
+    confirm_transaction(
+        prompt,
+        &format!("{}\n{}", tx_preview(unsigned_tx, prefix), message),
+    )
+}
+
+pub(crate) fn ergo_explorer_transaction_link(tx_id: &TxId, prefix: NetworkPrefix) -> String {
     let prefix_str = match prefix {
         NetworkPrefix::Mainnet => "explorer",
         NetworkPrefix::Testnet => "testnet",
     };
-    let tx_id_str = tx_id_str.replace('"', ""); // Node interface returns Tx Id as a JSON string "TxId"
     format!(
         "https://{}.ergoplatform.com/en/transactions/{}",
-        prefix_str, tx_id_str
+        prefix_str,
+        String::from(tx_id.0.clone())
     )
 }
+
+/// Print the link to the submitted `unsigned_tx`, its id is the id of the signed tx
+pub(crate) fn print_transaction_submitted(
+    unsigned_tx: &UnsignedTransaction,
+    prefix: NetworkPrefix,
+) {
+    let tx_id = unsigned_tx.id();
+    let explorer_link = ergo_explorer_transaction_link(&tx_id, prefix);
+    if json_output() {
+        let output_box_ids = unsigned_tx
+            .output_candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, candidate)| {
+                ErgoBox::from_box_candidate(candidate, tx_id.clone(), idx as u16).ok()
+            })
+            .map(|b| String::from(b.box_id()))
+            .collect();
+        print_json(&TransactionOutput {
+            status: "submitted",
+            tx_id: Some(tx_id),
+            explorer_link: Some(explorer_link),
+            output_box_ids,
+        });
+    } else {
+        println!("Transaction made. Check status here: {}", explorer_link);
+    }
+}
+
+pub(crate) fn print_transaction_aborted() {
+    if json_output() {
+        print_json(&TransactionOutput {
+            status: "aborted",
+            tx_id: None,
+            explorer_link: None,
+            output_box_ids: vec![],
+        });
+    } else {
+        println!("Aborting the transaction.")
+    }
+}
+
+/// Exit code of a failed command, from the first error of the source chain telling why: the node
+/// is unavailable, a file can't be read or written, the config or the input data is invalid.
+/// `SOFTWARE` otherwise.
+pub(crate) fn exit_code(e: &anyhow::Error) -> i32 {
+    e.chain()
+        .find_map(|e| {
+            if e.is::<NodeError>() {
+                Some(exitcode::UNAVAILABLE)
+            } else if e.is::<std::io::Error>() {
+                Some(exitcode::IOERR)
+            } else if e.is::<OracleConfigError>()
+                || e.is::<SerdeConversionError>()
+                || e.is::<serde_yaml::Error>()
+            {
+                Some(exitcode::CONFIG)
+            } else if e.is::<AddressEncoderError>() || e.is::<serde_json::Error>() {
+                Some(exitcode::DATAERR)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(exitcode::SOFTWARE)
+}
+
+/// Print the error of the failed command as JSON (in `--json` mode only).
+pub(crate) fn print_error_json(command: &str, code: i32, error: String) {
+    if json_output() {
+        print_json(&ErrorOutput {
+            status: "error",
+            command,
+            code,
+            error,
+        });
+    }
+}
+
+pub(crate) fn print_json<T: Serialize>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        let e = anyhow::Error::from(std::io::Error::new(std::io::ErrorKind::NotFound, "no file"));
+        assert_eq!(exit_code(&e), exitcode::IOERR);
+        assert_eq!(exit_code(&e.context("reading the backup")), exitcode::IOERR);
+        assert_eq!(
+            exit_code(&anyhow::anyhow!("oracle address must be a P2PK address")),
+            exitcode::SOFTWARE
+        );
+    }
+}
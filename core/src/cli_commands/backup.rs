@@ -0,0 +1,366 @@
+//! Backup of the state of an oracle to a `.tar.gz` archive and its restore, to move an oracle to
+//! another server. The archive holds the config without its secrets, the registered scans, a
+//! consistent copy of the state database (tx history, action queue) and the state files of the
+//! daemon. With the tx history and the action queue restored, the daemon on the new server knows
+//! the txs submitted on the old one and doesn't post them again.
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use derive_more::From;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use thiserror::Error;
+
+use crate::audit_log::AUDIT_LOG_FILE;
+use crate::config_format::{ConfigFormat, ConfigFormatError};
+use crate::downtime::{unix_now, DOWNTIME_FILE};
+use crate::peer_monitor::PEER_REPORT_FILE;
+use crate::pool_archive::POOL_ARCHIVE_FILE;
+use crate::scans::{LEGACY_SCAN_IDS_FILE, SCAN_STATE_FILE};
+use crate::store::{Store, StoreError, STORE_FILE};
+
+/// Version of the archive layout
+const BACKUP_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Name of the config in the archive, restored to the config path of the command. The config is
+/// archived as YAML and restored as TOML to a `.toml` config path.
+const CONFIG_ENTRY: &str = "oracle_config.yaml";
+
+/// State files of the daemon, backed up if they exist
+const STATE_FILES: [&str; 6] = [
+    SCAN_STATE_FILE,
+    LEGACY_SCAN_IDS_FILE,
+    DOWNTIME_FILE,
+    POOL_ARCHIVE_FILE,
+    PEER_REPORT_FILE,
+    AUDIT_LOG_FILE,
+];
+
+/// Config keys holding secrets, replaced by [`REDACTED`] in the archive
+const SECRET_KEYS: [&str; 4] = [
+    "node_api_key",
+    "node_wallet_password",
+    "bot_token",
+    "webhook_url",
+];
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, From, Error)]
+pub enum BackupError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("config error: {0}")]
+    Config(serde_yaml::Error),
+    #[error("config error: {0}")]
+    ConfigFormat(ConfigFormatError),
+    #[error("manifest error: {0}")]
+    Manifest(serde_json::Error),
+    #[error("store error: {0}")]
+    Store(StoreError),
+    #[error("not an oracle-core backup, {} is missing", MANIFEST_FILE)]
+    MissingManifest,
+    #[error("backup version {0} is not supported")]
+    UnsupportedVersion(u32),
+    #[error("unexpected file {0} in the backup")]
+    UnexpectedFile(String),
+    #[error("{} exist already, pass --force to overwrite them", .0.join(", "))]
+    #[from(ignore)]
+    ExistingFiles(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    /// Unix time in seconds
+    pub created_at: u64,
+    pub oracle_core_version: String,
+    pub files: Vec<String>,
+    /// Config keys to fill in after a restore
+    pub redacted_keys: Vec<String>,
+}
+
+/// Replace the values of the secret keys of `config`, returns their paths (e.g.
+/// `notifications.targets.0.bot_token`)
+fn redact_secrets(config: &mut Value, path: &str, redacted: &mut Vec<String>) {
+    let child_path = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    match config {
+        Value::Mapping(mapping) => {
+            // The url of a generic webhook target
+            let is_webhook = mapping.get(&Value::from("type")) == Some(&Value::from("webhook"));
+            let keys: Vec<Value> = mapping.iter().map(|(key, _)| key.clone()).collect();
+            for key_value in keys {
+                let (key, value) = match (key_value.as_str(), mapping.get_mut(&key_value)) {
+                    (Some(key), Some(value)) => (key, value),
+                    _ => continue,
+                };
+                if SECRET_KEYS.contains(&key) || (is_webhook && key == "url") {
+                    *value = Value::from(REDACTED);
+                    redacted.push(child_path(key));
+                } else {
+                    redact_secrets(value, &child_path(key), redacted);
+                }
+            }
+        }
+        Value::Sequence(sequence) => {
+            for (i, value) in sequence.iter_mut().enumerate() {
+                redact_secrets(value, &child_path(&i.to_string()), redacted);
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => (),
+    }
+}
+
+fn append_file(
+    builder: &mut tar::Builder<GzEncoder<File>>,
+    name: &str,
+    data: &[u8],
+) -> Result<(), std::io::Error> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(unix_now());
+    header.set_cksum();
+    builder.append_data(&mut header, name, data)
+}
+
+/// Write the backup of the oracle with its state files in `dir` and its config at `config_path`
+/// to `archive_path`
+pub fn write_backup(
+    dir: &Path,
+    config_path: &Path,
+    archive_path: &Path,
+) -> Result<BackupManifest, BackupError> {
+    let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    let mut config =
+        ConfigFormat::of_path(config_path).parse(&std::fs::read_to_string(config_path)?)?;
+    let mut redacted_keys = Vec::new();
+    redact_secrets(&mut config, "", &mut redacted_keys);
+    files.insert(
+        CONFIG_ENTRY.to_string(),
+        serde_yaml::to_string(&config)?.into_bytes(),
+    );
+    for name in STATE_FILES.iter() {
+        let path = dir.join(name);
+        if path.exists() {
+            files.insert(name.to_string(), std::fs::read(path)?);
+        }
+    }
+    if dir.join(STORE_FILE).exists() {
+        let copy_path =
+            std::env::temp_dir().join(format!("oracle_core_backup_{}.sqlite", std::process::id()));
+        if copy_path.exists() {
+            std::fs::remove_file(&copy_path)?;
+        }
+        Store::open(&dir.join(STORE_FILE))?.backup_to(&copy_path)?;
+        let data = std::fs::read(&copy_path);
+        std::fs::remove_file(&copy_path)?;
+        files.insert(STORE_FILE.to_string(), data?);
+    }
+
+    let manifest = BackupManifest {
+        version: BACKUP_VERSION,
+        created_at: unix_now(),
+        oracle_core_version: env!("CARGO_PKG_VERSION").to_string(),
+        files: files.keys().cloned().collect(),
+        redacted_keys,
+    };
+    let mut builder = tar::Builder::new(GzEncoder::new(
+        File::create(archive_path)?,
+        Compression::default(),
+    ));
+    append_file(
+        &mut builder,
+        MANIFEST_FILE,
+        serde_json::to_string_pretty(&manifest)?.as_bytes(),
+    )?;
+    for (name, data) in &files {
+        append_file(&mut builder, name, data)?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(manifest)
+}
+
+/// Restore the backup at `archive_path`, the state files to `dir` and the config to
+/// `config_path`. Existing files are only overwritten with `force`.
+pub fn restore_backup(
+    archive_path: &Path,
+    dir: &Path,
+    config_path: &Path,
+    force: bool,
+) -> Result<BackupManifest, BackupError> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(archive_path)?));
+    let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        files.insert(name, data);
+    }
+    let manifest: BackupManifest = match files.remove(MANIFEST_FILE) {
+        Some(data) => serde_json::from_slice(&data)?,
+        None => return Err(BackupError::MissingManifest),
+    };
+    if manifest.version != BACKUP_VERSION {
+        return Err(BackupError::UnsupportedVersion(manifest.version));
+    }
+    let destination = |name: &str| -> Result<std::path::PathBuf, BackupError> {
+        if name == CONFIG_ENTRY {
+            Ok(config_path.to_path_buf())
+        } else if name == STORE_FILE || STATE_FILES.contains(&name) {
+            Ok(dir.join(name))
+        } else {
+            Err(BackupError::UnexpectedFile(name.to_string()))
+        }
+    };
+    let mut existing = Vec::new();
+    for name in files.keys() {
+        let path = destination(name)?;
+        if path.exists() {
+            existing.push(path.display().to_string());
+        }
+    }
+    if !existing.is_empty() && !force {
+        return Err(BackupError::ExistingFiles(existing));
+    }
+    for (name, data) in &files {
+        if name == STORE_FILE {
+            // The log of the replaced database would be applied to the restored one
+            for suffix in ["-wal", "-shm"].iter() {
+                let path = dir.join(format!("{}{}", STORE_FILE, suffix));
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+        if name == CONFIG_ENTRY && ConfigFormat::of_path(config_path) == ConfigFormat::Toml {
+            let config = ConfigFormat::Yaml.parse(&String::from_utf8_lossy(data))?;
+            std::fs::write(config_path, ConfigFormat::Toml.to_string(&config)?)?;
+            continue;
+        }
+        std::fs::write(destination(name)?, data)?;
+    }
+    Ok(manifest)
+}
+
+pub fn backup(config_path: &Path, archive_path: &Path) -> Result<(), BackupError> {
+    let manifest = write_backup(Path::new("."), config_path, archive_path)?;
+    println!(
+        "Backed up {} to {}",
+        manifest.files.join(", "),
+        archive_path.display()
+    );
+    if !manifest.redacted_keys.is_empty() {
+        println!(
+            "Left out of the config: {}",
+            manifest.redacted_keys.join(", ")
+        );
+    }
+    Ok(())
+}
+
+pub fn restore(archive_path: &Path, config_path: &Path, force: bool) -> Result<(), BackupError> {
+    let manifest = restore_backup(archive_path, Path::new("."), config_path, force)?;
+    println!(
+        "Restored {} from the backup of {} (oracle-core {})",
+        manifest.files.join(", "),
+        manifest.created_at,
+        manifest.oracle_core_version
+    );
+    if !manifest.redacted_keys.is_empty() {
+        println!(
+            "Fill in {} in {} before starting the oracle",
+            manifest.redacted_keys.join(", "),
+            config_path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_secrets() {
+        let mut config: Value = serde_yaml::from_str(
+            "
+node_api_key: hello
+node_port: 9053
+notifications:
+  targets:
+    - type: telegram
+      bot_token: '123:abc'
+      chat_id: '42'
+    - type: webhook
+      url: https://example.com/hook?key=secret
+",
+        )
+        .unwrap();
+        let mut redacted = Vec::new();
+        redact_secrets(&mut config, "", &mut redacted);
+        assert_eq!(
+            redacted,
+            vec![
+                "node_api_key",
+                "notifications.targets.0.bot_token",
+                "notifications.targets.1.url"
+            ]
+        );
+        assert_eq!(config["node_port"].as_u64(), Some(9053));
+        assert_eq!(
+            config["notifications"]["targets"][0]["chat_id"].as_str(),
+            Some("42")
+        );
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let dir = std::env::temp_dir().join("oracle_core_test_backup");
+        let restore_dir = dir.join("restored");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&restore_dir).unwrap();
+        let config_path = dir.join("oracle_config.yaml");
+        std::fs::write(&config_path, "node_api_key: hello\nnode_port: 9053\n").unwrap();
+        std::fs::write(dir.join(SCAN_STATE_FILE), "{}").unwrap();
+        Store::open(&dir.join(STORE_FILE))
+            .unwrap()
+            .set_epoch(7)
+            .unwrap();
+
+        let archive_path = dir.join("backup.tar.gz");
+        let manifest = write_backup(&dir, &config_path, &archive_path).unwrap();
+        assert_eq!(
+            manifest.files,
+            vec![CONFIG_ENTRY, SCAN_STATE_FILE, STORE_FILE]
+        );
+        let restored_config_path = restore_dir.join("oracle_config.yaml");
+        restore_backup(&archive_path, &restore_dir, &restored_config_path, false).unwrap();
+        assert!(std::fs::read_to_string(&restored_config_path)
+            .unwrap()
+            .contains(REDACTED));
+        let store = Store::open(&restore_dir.join(STORE_FILE)).unwrap();
+        assert_eq!(store.epoch().unwrap(), Some(7));
+        drop(store);
+        // Nothing is overwritten without --force
+        assert!(matches!(
+            restore_backup(&archive_path, &restore_dir, &restored_config_path, false),
+            Err(BackupError::ExistingFiles(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,321 @@
+//! Overview of the ballot boxes of the pool: what each of them votes for, which proposals reached
+//! the quorum and which operators haven't voted yet.
+use ergo_lib::ergotree_ir::{
+    chain::address::{Address, AddressEncoder, NetworkPrefix},
+    sigma_protocol::sigma_boolean::ProveDlog,
+};
+use serde::Serialize;
+
+use crate::{
+    box_kind::{BallotBox, OracleBox},
+    cli_commands::{json_output, print_json},
+    oracle_config::CastBallotBoxVoteParameters,
+    oracle_state::{DatapointBoxesSource, StageError, UpdateBoxSource, VoteBallotBoxesSource},
+};
+
+#[derive(Debug, Serialize)]
+pub struct BallotStatus {
+    /// Number of ballot tokens needed to update the pool box
+    pub min_votes: u32,
+    /// Creation height of the current update box. Votes for other heights are stale.
+    pub update_box_creation_height: u32,
+    pub proposals: Vec<ProposalStatus>,
+    pub ballots: Vec<BallotVote>,
+    /// Addresses of the oracles holding an oracle box (posted or collected) without a (non-stale)
+    /// vote
+    pub operators_not_voted: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProposalStatus {
+    pub pool_box_address_hash: String,
+    pub reward_token_id: String,
+    pub reward_token_quantity: u64,
+    pub update_box_creation_height: i32,
+    pub votes: u64,
+    pub stale: bool,
+    pub quorum_reached: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BallotVote {
+    pub box_id: String,
+    pub owner_address: String,
+    pub votes: u64,
+    pub pool_box_address_hash: String,
+}
+
+pub fn print_ballot_status(
+    ballot_boxes_source: &dyn VoteBallotBoxesSource,
+    update_box_source: &dyn UpdateBoxSource,
+    datapoint_boxes_source: &dyn DatapointBoxesSource,
+    network_prefix: NetworkPrefix,
+) -> Result<(), StageError> {
+    let status = get_ballot_status(
+        ballot_boxes_source,
+        update_box_source,
+        datapoint_boxes_source,
+        network_prefix,
+    )?;
+    if json_output() {
+        print_json(&status);
+        return Ok(());
+    }
+    println!(
+        "Update box creation height: {}, votes needed: {}",
+        status.update_box_creation_height, status.min_votes
+    );
+    if status.proposals.is_empty() {
+        println!("No votes cast");
+    }
+    for p in &status.proposals {
+        println!(
+            "Pool box address hash {} (reward token {} x {}): {} vote(s){}",
+            p.pool_box_address_hash,
+            p.reward_token_id,
+            p.reward_token_quantity,
+            p.votes,
+            if p.stale {
+                format!(
+                    ", STALE (cast for update box creation height {})",
+                    p.update_box_creation_height
+                )
+            } else if p.quorum_reached {
+                ", QUORUM REACHED".to_string()
+            } else {
+                String::new()
+            }
+        );
+    }
+    for b in &status.ballots {
+        println!(
+            "  ballot box {} of {}: {} vote(s) for {}",
+            b.box_id, b.owner_address, b.votes, b.pool_box_address_hash
+        );
+    }
+    if !status.operators_not_voted.is_empty() {
+        println!("Operators that haven't voted:");
+        for address in &status.operators_not_voted {
+            println!("  {}", address);
+        }
+    }
+    Ok(())
+}
+
+pub fn get_ballot_status(
+    ballot_boxes_source: &dyn VoteBallotBoxesSource,
+    update_box_source: &dyn UpdateBoxSource,
+    datapoint_boxes_source: &dyn DatapointBoxesSource,
+    network_prefix: NetworkPrefix,
+) -> Result<BallotStatus, StageError> {
+    let update_box = update_box_source.get_update_box()?;
+    let min_votes = update_box.min_votes();
+    let update_box_creation_height = update_box.get_box().creation_height;
+    let encoder = AddressEncoder::new(network_prefix);
+    let address_str = |pk: ProveDlog| encoder.address_to_str(&Address::P2Pk(pk));
+
+    let ballot_boxes = ballot_boxes_source.get_ballot_boxes()?;
+    let mut votes: Vec<(&CastBallotBoxVoteParameters, u64)> = Vec::new();
+    let mut voters = Vec::new();
+    let mut ballots = Vec::new();
+    for ballot_box in &ballot_boxes {
+        let vote_parameters = ballot_box.vote_parameters();
+        let amount = *ballot_box.ballot_token().amount.as_u64();
+        match votes.iter_mut().find(|(v, _)| *v == vote_parameters) {
+            Some((_, n)) => *n += amount,
+            None => votes.push((vote_parameters, amount)),
+        }
+        if vote_parameters.update_box_creation_height == update_box_creation_height as i32 {
+            voters.push(ballot_box.ballot_token_owner());
+        }
+        ballots.push(BallotVote {
+            box_id: String::from(ballot_box.get_box().box_id()),
+            owner_address: address_str(ballot_box.ballot_token_owner()),
+            votes: amount,
+            pool_box_address_hash: String::from(vote_parameters.pool_box_address_hash.clone()),
+        });
+    }
+    // Most voted proposals first
+    votes.sort_by(|(_, n1), (_, n2)| n2.cmp(n1));
+    let proposals = votes
+        .into_iter()
+        .map(|(v, n)| {
+            let stale = v.update_box_creation_height != update_box_creation_height as i32;
+            ProposalStatus {
+                pool_box_address_hash: String::from(v.pool_box_address_hash.clone()),
+                reward_token_id: String::from(v.reward_token_id.clone()),
+                reward_token_quantity: v.reward_token_quantity,
+                update_box_creation_height: v.update_box_creation_height,
+                votes: n,
+                stale,
+                quorum_reached: !stale && n >= min_votes as u64,
+            }
+        })
+        .collect();
+
+    let mut operators_not_voted: Vec<String> = datapoint_boxes_source
+        .get_oracle_boxes()?
+        .into_iter()
+        .map(|b| b.public_key())
+        .filter(|pk| !voters.contains(pk))
+        .map(address_str)
+        .collect();
+    operators_not_voted.sort();
+    operators_not_voted.dedup();
+
+    Ok(BallotStatus {
+        min_votes,
+        update_box_creation_height,
+        proposals,
+        ballots,
+        operators_not_voted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::{TryFrom, TryInto};
+
+    use super::*;
+    use crate::box_kind::{
+        make_local_ballot_box_candidate, BallotBoxWrapperInputs, OracleBoxWrapper,
+        OracleBoxWrapperInputs, UpdateBoxWrapper, UpdateBoxWrapperInputs, VoteBallotBoxWrapper,
+    };
+    use crate::contracts::ballot::{BallotContract, BallotContractParameters};
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::contracts::update::{UpdateContract, UpdateContractParameters};
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_commands::test_utils::{
+        generate_token_ids, make_collected_datapoint_box, make_datapoint_box, BallotBoxesMock,
+        OracleBoxesMock, UpdateBoxMock,
+    };
+    use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+    use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergo_chain_types::Digest32;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+    use ergo_lib::ergotree_ir::chain::token::Token;
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_get_ballot_status() {
+        let height = 1000;
+        let token_ids = generate_token_ids();
+        let update_box_wrapper_inputs = UpdateBoxWrapperInputs::build_with(
+            UpdateContractParameters::default(),
+            token_ids.pool_nft_token_id.clone(),
+            token_ids.ballot_token_id.clone(),
+            token_ids.update_nft_token_id.clone(),
+        )
+        .unwrap();
+        let update_contract =
+            UpdateContract::checked_load(&update_box_wrapper_inputs.contract_inputs).unwrap();
+        let min_votes = update_contract.min_votes() as u32;
+        let mut update_box_candidate =
+            ErgoBoxCandidateBuilder::new(*BASE_FEE, update_contract.ergo_tree(), height);
+        update_box_candidate.add_token(Token {
+            token_id: token_ids.update_nft_token_id.clone(),
+            amount: 1.try_into().unwrap(),
+        });
+        let update_box = UpdateBoxWrapper::new(
+            ErgoBox::from_box_candidate(
+                &update_box_candidate.build().unwrap(),
+                force_any_val::<TxId>(),
+                0,
+            )
+            .unwrap(),
+            &update_box_wrapper_inputs,
+        )
+        .unwrap();
+
+        let ballot_box_wrapper_inputs = BallotBoxWrapperInputs::build_with(
+            BallotContractParameters::default(),
+            token_ids.ballot_token_id.clone(),
+            token_ids.update_nft_token_id.clone(),
+        )
+        .unwrap();
+        let ballot_contract =
+            BallotContract::checked_load(&ballot_box_wrapper_inputs.contract_inputs).unwrap();
+        let pool_box_hash = force_any_val::<Digest32>();
+        let reward_tokens = Token {
+            token_id: token_ids.reward_token_id.clone(),
+            amount: 1000.try_into().unwrap(),
+        };
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let mut ballot_boxes = vec![];
+        let mut oracle_boxes = vec![];
+        for i in 0..(min_votes + 1) {
+            let secret = DlogProverInput::random();
+            // The last operator doesn't vote
+            if i < min_votes {
+                let ballot_box_candidate = make_local_ballot_box_candidate(
+                    &ballot_contract,
+                    secret.public_image(),
+                    height,
+                    Token {
+                        token_id: token_ids.ballot_token_id.clone(),
+                        amount: 1.try_into().unwrap(),
+                    },
+                    pool_box_hash.clone(),
+                    reward_tokens.clone(),
+                    ballot_contract.min_storage_rent(),
+                    height,
+                )
+                .unwrap();
+                ballot_boxes.push(
+                    VoteBallotBoxWrapper::new(
+                        ErgoBox::from_box_candidate(
+                            &ballot_box_candidate,
+                            force_any_val::<TxId>(),
+                            0,
+                        )
+                        .unwrap(),
+                        &ballot_box_wrapper_inputs,
+                    )
+                    .unwrap(),
+                );
+            }
+            // The datapoint of the last operator was collected
+            let oracle_box = if i < min_votes {
+                make_datapoint_box(
+                    *secret.public_image().h,
+                    200,
+                    1,
+                    &token_ids,
+                    BASE_FEE.checked_mul_u32(100).unwrap(),
+                    height,
+                )
+            } else {
+                make_collected_datapoint_box(
+                    *secret.public_image().h,
+                    &token_ids,
+                    BASE_FEE.checked_mul_u32(100).unwrap(),
+                    height,
+                )
+            };
+            oracle_boxes
+                .push(OracleBoxWrapper::new(oracle_box, &oracle_box_wrapper_inputs).unwrap());
+        }
+        let non_voter = oracle_boxes.last().unwrap().public_key();
+
+        let status = get_ballot_status(
+            &BallotBoxesMock { ballot_boxes },
+            &UpdateBoxMock { update_box },
+            &OracleBoxesMock { oracle_boxes },
+            NetworkPrefix::Mainnet,
+        )
+        .unwrap();
+        assert_eq!(status.proposals.len(), 1);
+        assert_eq!(status.proposals[0].votes, min_votes as u64);
+        assert!(!status.proposals[0].stale);
+        assert!(status.proposals[0].quorum_reached);
+        assert_eq!(status.ballots.len(), min_votes as usize);
+        assert_eq!(
+            status.operators_not_voted,
+            vec![AddressEncoder::new(NetworkPrefix::Mainnet)
+                .address_to_str(&Address::P2Pk(non_voter))]
+        );
+    }
+}
@@ -1,5 +1,9 @@
 //! Bootstrap a new oracle pool
-use std::{convert::TryInto, io::Write, path::Path};
+use std::{
+    convert::{TryFrom, TryInto},
+    io::Write,
+    path::Path,
+};
 
 use derive_more::From;
 use ergo_lib::{
@@ -44,18 +48,33 @@ use crate::{
     },
     datapoint_source::PredefinedDataPointSource,
     node_interface::{assert_wallet_unlocked, SignTransaction, SubmitTransaction},
+    oracle_config::OracleConfigError,
     oracle_config::{OracleConfig, TokenIds},
-    oracle_config::{OracleConfigError, BASE_FEE},
     serde::BootstrapConfigSerde,
     wallet::{WalletDataError, WalletDataSource},
 };
 
 /// Loads bootstrap configuration file and performs the chain-transactions for minting of tokens and
-/// box creations. An oracle configuration file is then created which contains the `TokenId`s of the
-/// minted tokens.
-pub fn bootstrap(config_file_name: String) -> Result<(), BootstrapError> {
+/// box creations. An oracle configuration file (at the path given with `--config-file`) is then
+/// created which contains the `TokenId`s of the minted tokens. If `contract_sources_dir` is given,
+/// the contracts are compiled from the ErgoScript sources found there (`compile-contracts`
+/// feature).
+pub fn bootstrap(
+    config_file_name: String,
+    contract_sources_dir: Option<String>,
+) -> Result<(), BootstrapError> {
+    let oracle_config_file_name = crate::oracle_config::CONFIG_FILE_PATH
+        .get()
+        .cloned()
+        .unwrap_or_else(|| crate::oracle_config::DEFAULT_CONFIG_FILE_NAME.to_string());
+    // Check before minting anything, the minted token ids must not be lost.
+    if Path::new(&oracle_config_file_name).exists() {
+        return Err(BootstrapError::OracleConfigFileAlreadyExists(
+            oracle_config_file_name,
+        ));
+    }
     let s = std::fs::read_to_string(config_file_name)?;
-    let config: BootstrapConfig = serde_yaml::from_str(&s)?;
+    let config = with_compiled_contracts(serde_yaml::from_str(&s)?, contract_sources_dir)?;
 
     // We can't call any functions from the `crate::node_interface` module because we don't have an
     // `oracle_config.yaml` file to work from here.
@@ -73,12 +92,14 @@ pub fn bootstrap(config_file_name: String) -> Result<(), BootstrapError> {
 
     let change_address = AddressEncoder::unchecked_parse_address_from_str(&change_address_str)?;
     let erg_value_per_box = config.oracle_contract_parameters.min_storage_rent;
+    // There is no oracle config yet
+    let tx_fee = BoxValue::try_from(config.base_fee)?;
     let input = BootstrapInput {
         config,
         wallet: &node as &dyn WalletDataSource,
         tx_signer: &node as &dyn SignTransaction,
         submit_tx: &node as &dyn SubmitTransaction,
-        tx_fee: *BASE_FEE,
+        tx_fee,
         erg_value_per_box,
         change_address,
         height: node.current_block_height()? as u32,
@@ -86,15 +107,45 @@ pub fn bootstrap(config_file_name: String) -> Result<(), BootstrapError> {
     let oracle_config = perform_bootstrap_chained_transaction(input)?;
     info!("Bootstrap chain-transaction complete");
     let s = serde_yaml::to_string(&oracle_config)?;
-    let mut file = std::fs::File::create(crate::oracle_config::DEFAULT_CONFIG_FILE_NAME)?;
+    let mut file = std::fs::File::create(&oracle_config_file_name)?;
     file.write_all(s.as_bytes())?;
     info!(
         "Oracle configuration file created: {}",
-        crate::oracle_config::DEFAULT_CONFIG_FILE_NAME
+        oracle_config_file_name
     );
+    if crate::cli_commands::json_output() {
+        crate::cli_commands::print_json(&serde_json::json!({
+            "status": "ok",
+            "config_file": oracle_config_file_name,
+            "token_ids": oracle_config.token_ids,
+        }));
+    }
     Ok(())
 }
 
+/// `config` with its contracts compiled from the sources in `contract_sources_dir`, if given
+#[cfg(feature = "compile-contracts")]
+fn with_compiled_contracts(
+    mut config: BootstrapConfig,
+    contract_sources_dir: Option<String>,
+) -> Result<BootstrapConfig, BootstrapError> {
+    if let Some(dir) = contract_sources_dir {
+        crate::contracts::compile::compile_bootstrap_contracts(&mut config, Path::new(&dir))?;
+    }
+    Ok(config)
+}
+
+#[cfg(not(feature = "compile-contracts"))]
+fn with_compiled_contracts(
+    config: BootstrapConfig,
+    contract_sources_dir: Option<String>,
+) -> Result<BootstrapConfig, BootstrapError> {
+    match contract_sources_dir {
+        Some(_) => Err(BootstrapError::CompileContractsFeatureDisabled),
+        None => Ok(config),
+    }
+}
+
 pub fn generate_bootstrap_config_template(config_file_name: String) -> Result<(), BootstrapError> {
     if Path::new(&config_file_name).exists() {
         return Err(BootstrapError::ConfigFilenameAlreadyExists);
@@ -629,19 +680,19 @@ pub enum BootstrapError {
     #[error("box builder error: {0}")]
     ErgoBoxCandidateBuilder(ErgoBoxCandidateBuilderError),
     #[error("node error: {0}")]
-    Node(NodeError),
+    Node(#[source] NodeError),
     #[error("box selector error: {0}")]
     BoxSelector(BoxSelectorError),
     #[error("box value error: {0}")]
     BoxValue(BoxValueError),
     #[error("IO error: {0}")]
-    Io(std::io::Error),
+    Io(#[source] std::io::Error),
     #[error("serde-yaml error: {0}")]
     SerdeYaml(serde_yaml::Error),
     #[error("yaml-rust error: {0}")]
     YamlRust(String),
     #[error("AddressEncoder error: {0}")]
-    AddressEncoder(AddressEncoderError),
+    AddressEncoder(#[source] AddressEncoderError),
     #[error("SigmaParsing error: {0}")]
     SigmaParse(SigmaParsingError),
     #[error("Node doesn't have a change address set")]
@@ -652,6 +703,8 @@ pub enum BootstrapError {
     UpdateContract(UpdateContractError),
     #[error("Bootstrap config file already exists")]
     ConfigFilenameAlreadyExists,
+    #[error("Oracle config file {0} already exists")]
+    OracleConfigFileAlreadyExists(String),
     #[error("Ballot contract error: {0}")]
     BallotContractError(BallotContractError),
     #[error("Oracle config error: {0}")]
@@ -659,7 +712,14 @@ pub enum BootstrapError {
     #[error("Pool contract error: {0}")]
     PoolContractError(PoolContractError),
     #[error("WalletData error: {0}")]
-    WalletData(WalletDataError),
+    WalletData(#[source] WalletDataError),
+    #[cfg(feature = "compile-contracts")]
+    #[error("Compile contract error: {0}")]
+    CompileContract(crate::contracts::compile::CompileContractError),
+    #[error(
+        "Compiling contracts requires oracle-core to be built with the `compile-contracts` feature"
+    )]
+    CompileContractsFeatureDisabled,
 }
 
 #[cfg(test)]
@@ -677,6 +737,7 @@ pub(crate) mod tests {
     use sigma_test_util::force_any_val;
 
     use super::*;
+    use crate::oracle_config::BASE_FEE;
     use crate::pool_commands::test_utils::{LocalTxSigner, WalletDataMock};
     use std::cell::RefCell;
     #[derive(Default)]
@@ -0,0 +1,278 @@
+use std::convert::TryFrom;
+use std::convert::TryInto;
+
+use derive_more::From;
+use ergo_lib::{
+    chain::{
+        ergo_box::box_builder::{ErgoBoxCandidateBuilder, ErgoBoxCandidateBuilderError},
+        transaction::unsigned::UnsignedTransaction,
+    },
+    ergotree_ir::{
+        chain::{
+            address::{Address, AddressEncoder, AddressEncoderError},
+            ergo_box::{
+                box_value::{BoxValue, BoxValueError},
+                ErgoBox,
+            },
+            token::{Token, TokenAmountError},
+        },
+        serialization::SigmaParsingError,
+    },
+    wallet::{
+        box_selector::BoxSelection,
+        tx_builder::{TxBuilder, TxBuilderError},
+    },
+};
+use ergo_node_interface::node_interface::NodeError;
+use thiserror::Error;
+
+use crate::{
+    audit_log,
+    cli_commands::{
+        confirm_transaction_preview, print_transaction_aborted, print_transaction_submitted,
+    },
+    fee_estimation::apply_estimated_fee,
+    min_box_value::{check_min_box_values, MinBoxValueError},
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_config::{tx_fee, TxFeeAction},
+    prompt::Prompt,
+    wallet::{WalletDataError, WalletDataSource},
+};
+
+/// Maximum number of dust boxes swept in a single transaction, to keep the tx size reasonable.
+/// Run the command again to sweep the rest.
+const MAX_INPUTS_PER_TX: usize = 100;
+
+#[derive(Debug, Error, From)]
+pub enum ConsolidateUtxosActionError {
+    #[error("Not enough dust boxes to consolidate. Found {0}, need at least 2")]
+    NotEnoughDustBoxes(usize),
+    #[error("Total value of dust boxes ({0} nanoErgs) is too low to cover the tx fee")]
+    InsufficientValueForFee(u64),
+    #[error("box builder error: {0}")]
+    ErgoBoxCandidateBuilder(ErgoBoxCandidateBuilderError),
+    #[error("box value error: {0}")]
+    BoxValue(BoxValueError),
+    #[error("{0}")]
+    MinBoxValue(MinBoxValueError),
+    #[error("token amount error: {0}")]
+    TokenAmount(TokenAmountError),
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
+    #[error("Sigma parsing error: {0}")]
+    SigmaParse(SigmaParsingError),
+    #[error("tx builder error: {0}")]
+    TxBuilder(TxBuilderError),
+    #[error("AddressEncoder error: {0}")]
+    AddressEncoder(#[source] AddressEncoderError),
+    #[error("Node doesn't have a change address set and change_address isn't set in the config")]
+    NoChangeAddressSetInNode,
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("WalletData error: {0}")]
+    WalletData(#[source] WalletDataError),
+}
+
+pub fn consolidate_utxos(
+    wallet: &dyn WalletDataSource,
+    dust_threshold: u64,
+    prompt: &dyn Prompt,
+) -> Result<(), ConsolidateUtxosActionError> {
+    let change_address_str =
+        get_change_address()?.ok_or(ConsolidateUtxosActionError::NoChangeAddressSetInNode)?;
+
+    let (change_address, network_prefix) = {
+        let a = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
+        (a.address(), a.network())
+    };
+    let (unsigned_tx, num_swept_boxes) = build_consolidate_utxos_tx(
+        wallet,
+        BoxValue::try_from(dust_threshold)?,
+        current_block_height()? as u32,
+        change_address,
+    )?;
+
+    if confirm_transaction_preview(
+        prompt,
+        &unsigned_tx,
+        network_prefix,
+        &format!(
+            "YOU WILL BE CONSOLIDATING {} WALLET BOXES INTO A SINGLE BOX AT {}.",
+            num_swept_boxes, change_address_str
+        ),
+    )? {
+        sign_and_submit_transaction(&unsigned_tx, "consolidate_utxos")?;
+        print_transaction_submitted(&unsigned_tx, network_prefix);
+    } else {
+        audit_log::record_declined("consolidate_utxos", &unsigned_tx);
+        print_transaction_aborted();
+    }
+    Ok(())
+}
+
+/// Builds a transaction spending all wallet boxes with a value below `dust_threshold` into a
+/// single box at `change_address`. All tokens held in the swept boxes are preserved. Returns the
+/// transaction and the number of swept boxes.
+fn build_consolidate_utxos_tx(
+    wallet: &dyn WalletDataSource,
+    dust_threshold: BoxValue,
+    height: u32,
+    change_address: Address,
+) -> Result<(UnsignedTransaction, usize), ConsolidateUtxosActionError> {
+    let dust_boxes: Vec<ErgoBox> = wallet
+        .get_unspent_wallet_boxes()?
+        .into_iter()
+        .filter(|b| b.value < dust_threshold)
+        .take(MAX_INPUTS_PER_TX)
+        .collect();
+    if dust_boxes.len() < 2 {
+        return Err(ConsolidateUtxosActionError::NotEnoughDustBoxes(
+            dust_boxes.len(),
+        ));
+    }
+
+    let total_value: u64 = dust_boxes.iter().map(|b| *b.value.as_u64()).sum();
+    let fee = tx_fee(TxFeeAction::ConsolidateUtxos);
+    let out_value = total_value.checked_sub(*fee.as_u64()).ok_or(
+        ConsolidateUtxosActionError::InsufficientValueForFee(total_value),
+    )?;
+
+    let mut tokens: Vec<Token> = Vec::new();
+    for token in dust_boxes
+        .iter()
+        .flat_map(|b| b.tokens.iter().flat_map(|ts| ts.iter()))
+    {
+        if let Some(existing) = tokens.iter_mut().find(|t| t.token_id == token.token_id) {
+            existing.amount = existing.amount.checked_add(&token.amount)?;
+        } else {
+            tokens.push(token.clone());
+        }
+    }
+
+    let mut builder =
+        ErgoBoxCandidateBuilder::new(out_value.try_into()?, change_address.script()?, height);
+    for token in tokens {
+        builder.add_token(token);
+    }
+    let consolidated_box_candidate = builder.build()?;
+    // The dust left after the fee may not cover the consolidated box with all the tokens
+    check_min_box_values(&[consolidated_box_candidate.clone()])?;
+
+    let num_swept_boxes = dust_boxes.len();
+    let box_selection = BoxSelection {
+        boxes: dust_boxes.try_into().unwrap(),
+        change_boxes: vec![],
+    };
+    let tx_builder = TxBuilder::new(
+        box_selection,
+        vec![consolidated_box_candidate],
+        height,
+        fee,
+        change_address.clone(),
+    );
+    let tx = apply_estimated_fee(
+        tx_builder.build()?,
+        TxFeeAction::ConsolidateUtxos,
+        &change_address,
+    );
+    Ok((tx, num_swept_boxes))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_commands::test_utils::{
+        find_input_boxes, generate_token_ids, make_wallet_unspent_box, WalletDataMock,
+    };
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+    use ergo_lib::wallet::signing::TransactionContext;
+    use ergo_lib::wallet::Wallet;
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_consolidate_utxos() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = ctx.pre_header.height;
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
+
+        let dust_token = Token::from((token_ids.reward_token_id.clone(), 5u64.try_into().unwrap()));
+        let mut unspent_boxes: Vec<ErgoBox> = (0..5)
+            .map(|_| {
+                make_wallet_unspent_box(
+                    secret.public_image(),
+                    BASE_FEE.checked_mul_u32(2).unwrap(),
+                    Some(vec![dust_token.clone()].try_into().unwrap()),
+                )
+            })
+            .collect();
+        let large_box = make_wallet_unspent_box(
+            secret.public_image(),
+            BASE_FEE.checked_mul_u32(10000).unwrap(),
+            None,
+        );
+        unspent_boxes.push(large_box.clone());
+        let wallet_mock = WalletDataMock { unspent_boxes };
+
+        let change_address = AddressEncoder::new(NetworkPrefix::Mainnet)
+            .parse_address_from_str("9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r")
+            .unwrap();
+
+        let (tx, num_swept_boxes) = build_consolidate_utxos_tx(
+            &wallet_mock,
+            BASE_FEE.checked_mul_u32(10).unwrap(),
+            height,
+            change_address,
+        )
+        .unwrap();
+
+        assert_eq!(num_swept_boxes, 5);
+        assert!(tx.inputs.iter().all(|i| i.box_id != large_box.box_id()));
+        let consolidated_tokens = tx.output_candidates.first().tokens.clone().unwrap();
+        assert_eq!(consolidated_tokens.len(), 1);
+        assert_eq!(*consolidated_tokens.first().amount.as_u64(), 25);
+
+        let tx_context = TransactionContext::new(
+            tx.clone(),
+            find_input_boxes(tx, wallet_mock.get_unspent_wallet_boxes().unwrap()),
+            Vec::new(),
+        )
+        .unwrap();
+
+        let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
+    }
+
+    #[test]
+    fn test_consolidate_utxos_not_enough_dust() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet_mock = WalletDataMock {
+            unspent_boxes: vec![make_wallet_unspent_box(
+                secret.public_image(),
+                BASE_FEE.checked_mul_u32(2).unwrap(),
+                None,
+            )],
+        };
+        let change_address = AddressEncoder::new(NetworkPrefix::Mainnet)
+            .parse_address_from_str("9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r")
+            .unwrap();
+        assert!(matches!(
+            build_consolidate_utxos_tx(
+                &wallet_mock,
+                BASE_FEE.checked_mul_u32(10).unwrap(),
+                ctx.pre_header.height,
+                change_address,
+            ),
+            Err(ConsolidateUtxosActionError::NotEnoughDustBoxes(1))
+        ));
+    }
+}
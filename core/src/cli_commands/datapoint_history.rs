@@ -0,0 +1,196 @@
+use std::path::Path;
+
+use derive_more::From;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    box_kind::{OracleBox, OracleBoxWrapper},
+    cli_commands::{json_output, print_json},
+    oracle_config::ORACLE_CONFIG,
+    oracle_state::{LocalDatapointBoxSource, StageError},
+    pool_archive::{PoolArchive, PoolArchiveError, POOL_ARCHIVE_FILE},
+};
+
+#[derive(Debug, Error, From)]
+pub enum DatapointHistoryError {
+    #[error("stage error: {0}")]
+    StageError(#[source] StageError),
+    #[error("pool archive error: {0}")]
+    PoolArchive(PoolArchiveError),
+}
+
+/// A datapoint posted by the local oracle and the pool rate the epoch was finalized with
+#[derive(Debug, Serialize)]
+pub struct PostedDatapoint {
+    pub box_id: String,
+    pub height: u32,
+    pub epoch_counter: u32,
+    pub datapoint: i64,
+    /// `None` if the refresh of the epoch is not in the pool archive
+    pub pool_rate: Option<i64>,
+    pub deviation_percent: Option<f64>,
+}
+
+pub fn print_datapoint_history(
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    max_deviation_percent: u32,
+) -> Result<(), DatapointHistoryError> {
+    let archive = PoolArchive::load(
+        Path::new(POOL_ARCHIVE_FILE),
+        &ORACLE_CONFIG.token_ids.pool_nft_token_id,
+    )?;
+    let history = get_datapoint_history(local_datapoint_box_source, &archive)?;
+    if json_output() {
+        print_json(&history);
+        return Ok(());
+    }
+    if archive.pool_boxes.is_empty() {
+        println!("The pool archive is empty, run `pool-history --backfill` to get the pool rates");
+    }
+    println!(
+        "{:>10} {:>8} {:>20} {:>20} {:>10}",
+        "height", "epoch", "datapoint", "pool rate", "deviation"
+    );
+    for posted in &history {
+        let deviation = posted
+            .deviation_percent
+            .map_or("-".to_string(), |d| format!("{:.2}%", d));
+        // Datapoints outside of the deviation range are left out of the refresh
+        let outlier = posted
+            .deviation_percent
+            .map_or(false, |d| d.abs() > max_deviation_percent as f64);
+        println!(
+            "{:>10} {:>8} {:>20} {:>20} {:>10}{}",
+            posted.height,
+            posted.epoch_counter,
+            posted.datapoint,
+            posted
+                .pool_rate
+                .map_or("-".to_string(), |rate| rate.to_string()),
+            deviation,
+            if outlier {
+                " (outside deviation range)"
+            } else {
+                ""
+            }
+        );
+    }
+    Ok(())
+}
+
+/// The datapoints posted by the local oracle (spent and current datapoint boxes), oldest first.
+/// The datapoint of epoch `n` is compared with the rate of the pool box of epoch `n + 1` created
+/// by the refresh.
+pub fn get_datapoint_history(
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    archive: &PoolArchive,
+) -> Result<Vec<PostedDatapoint>, StageError> {
+    let mut oracle_boxes = local_datapoint_box_source.get_spent_local_oracle_datapoint_boxes()?;
+    oracle_boxes.extend(local_datapoint_box_source.get_local_oracle_datapoint_box()?);
+    Ok(oracle_boxes
+        .iter()
+        .filter_map(|oracle_box| match oracle_box {
+            OracleBoxWrapper::Posted(posted) => Some(posted),
+            OracleBoxWrapper::Collected(_) => None,
+        })
+        .map(|posted| {
+            let epoch_counter = posted.epoch_counter();
+            let datapoint = posted.rate() as i64;
+            let pool_rate = archive
+                .pool_boxes
+                .iter()
+                .find(|b| b.epoch_counter == epoch_counter + 1)
+                .map(|b| b.rate);
+            PostedDatapoint {
+                box_id: String::from(posted.get_box().box_id()),
+                height: posted.get_box().creation_height,
+                epoch_counter,
+                datapoint,
+                pool_rate,
+                deviation_percent: pool_rate
+                    .filter(|rate| *rate != 0)
+                    .map(|rate| (datapoint - rate) as f64 / rate as f64 * 100.0),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::box_kind::OracleBoxWrapperInputs;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_archive::ArchivedPoolBox;
+    use crate::pool_commands::test_utils::{generate_token_ids, make_datapoint_box};
+
+    struct OracleBoxHistoryMock {
+        oracle_boxes: Vec<OracleBoxWrapper>,
+    }
+
+    impl LocalDatapointBoxSource for OracleBoxHistoryMock {
+        fn get_local_oracle_datapoint_box(&self) -> Result<Option<OracleBoxWrapper>, StageError> {
+            Ok(self.oracle_boxes.last().cloned())
+        }
+
+        fn get_local_oracle_datapoint_boxes(&self) -> Result<Vec<OracleBoxWrapper>, StageError> {
+            Ok(self.oracle_boxes.last().cloned().into_iter().collect())
+        }
+
+        fn get_spent_local_oracle_datapoint_boxes(
+            &self,
+        ) -> Result<Vec<OracleBoxWrapper>, StageError> {
+            Ok(self.oracle_boxes[..self.oracle_boxes.len() - 1].to_vec())
+        }
+    }
+
+    #[test]
+    fn test_get_datapoint_history() {
+        let height = force_any_val::<ErgoStateContext>().pre_header.height;
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let oracle_boxes = vec![(1, 210, height - 20), (2, 300, height - 10)]
+            .into_iter()
+            .map(|(epoch_counter, datapoint, creation_height)| {
+                OracleBoxWrapper::new(
+                    make_datapoint_box(
+                        *secret.public_image().h,
+                        datapoint,
+                        epoch_counter,
+                        &token_ids,
+                        *BASE_FEE,
+                        creation_height,
+                    ),
+                    &inputs,
+                )
+                .unwrap()
+            })
+            .collect();
+        let mut archive = PoolArchive::new(&token_ids.pool_nft_token_id);
+        archive.merge(vec![ArchivedPoolBox {
+            box_id: "pool".to_string(),
+            height: height - 15,
+            rate: 200,
+            epoch_counter: 2,
+        }]);
+
+        let history =
+            get_datapoint_history(&OracleBoxHistoryMock { oracle_boxes }, &archive).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].pool_rate, Some(200));
+        assert_eq!(history[0].deviation_percent, Some(5.0));
+        // The epoch of the current datapoint is not refreshed yet
+        assert_eq!(history[1].datapoint, 300);
+        assert_eq!(history[1].pool_rate, None);
+    }
+}
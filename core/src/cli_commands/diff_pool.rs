@@ -0,0 +1,250 @@
+//! Comparison of the constants of the on-chain pool, refresh and update contracts with the ones
+//! built from the config, to find out whether the config matches the chain before posting.
+use derive_more::From;
+use ergo_lib::ergotree_ir::{ergo_tree::ErgoTree, serialization::SigmaSerializable};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    cli_commands::{json_output, print_json, verify_contracts::template_hash},
+    contracts::{
+        constants::render_constant,
+        pool::{PoolContract, PoolContractError},
+        refresh::{RefreshContract, RefreshContractError},
+        update::{UpdateContract, UpdateContractError},
+    },
+    oracle_config::OracleConfig,
+    oracle_state::{OraclePool, StageError},
+};
+
+#[derive(Debug, Error, From)]
+pub enum DiffPoolError {
+    #[error("diff pool: stage error {0}")]
+    Stage(#[source] StageError),
+    #[error("diff pool: pool contract error {0:?}")]
+    PoolContract(PoolContractError),
+    #[error("diff pool: refresh contract error {0:?}")]
+    RefreshContract(RefreshContractError),
+    #[error("diff pool: update contract error {0:?}")]
+    UpdateContract(UpdateContractError),
+    #[error("diff pool: the config differs from the on-chain contracts: {}", .0.join(", "))]
+    #[from(ignore)]
+    Drift(Vec<String>),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContractDiff {
+    pub contract: &'static str,
+    pub box_id: String,
+    /// `false` if the scripts differ (not only their constants)
+    pub template_matches: bool,
+    pub constants: Vec<ConstantDiff>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConstantDiff {
+    pub index: usize,
+    /// Name of the contract parameter at this index, if any
+    pub name: Option<&'static str>,
+    /// `None` if the tree has no constant at this index
+    pub local: Option<String>,
+    pub on_chain: Option<String>,
+}
+
+impl ContractDiff {
+    pub fn has_drift(&self) -> bool {
+        !self.template_matches || !self.constants.is_empty()
+    }
+}
+
+/// Diff the live pool, refresh and update boxes against the contracts built from the config.
+pub fn diff_pool(config: &OracleConfig, op: &OraclePool) -> Result<(), DiffPoolError> {
+    let pool_box = op.get_unchecked_pool_box()?;
+    let refresh_box = op.get_unchecked_refresh_box()?;
+    let update_box = op.get_unchecked_update_box()?;
+
+    let pool_parameters = config
+        .pool_box_wrapper_inputs
+        .contract_inputs
+        .contract_parameters();
+    let refresh_parameters = config
+        .refresh_box_wrapper_inputs
+        .contract_inputs
+        .contract_parameters();
+    let update_parameters = config
+        .update_box_wrapper_inputs
+        .contract_inputs
+        .contract_parameters();
+    let diffs = vec![
+        diff_contract(
+            "pool",
+            &[
+                (pool_parameters.refresh_nft_index(), "refresh NFT"),
+                (pool_parameters.update_nft_index(), "update NFT"),
+            ],
+            &PoolContract::checked_load(&config.pool_box_wrapper_inputs.contract_inputs)?
+                .ergo_tree(),
+            &pool_box.ergo_tree,
+            String::from(pool_box.box_id()),
+        ),
+        diff_contract(
+            "refresh",
+            &[
+                (refresh_parameters.pool_nft_index(), "pool NFT"),
+                (refresh_parameters.oracle_token_id_index(), "oracle token"),
+                (
+                    refresh_parameters.min_data_points_index(),
+                    "min data points",
+                ),
+                (refresh_parameters.buffer_length_index(), "buffer length"),
+                (
+                    refresh_parameters.max_deviation_percent_index(),
+                    "max deviation percent",
+                ),
+                (refresh_parameters.epoch_length_index(), "epoch length"),
+            ],
+            &RefreshContract::checked_load(&config.refresh_box_wrapper_inputs.contract_inputs)?
+                .ergo_tree(),
+            &refresh_box.ergo_tree,
+            String::from(refresh_box.box_id()),
+        ),
+        diff_contract(
+            "update",
+            &[
+                (update_parameters.pool_nft_index(), "pool NFT"),
+                (update_parameters.ballot_token_index(), "ballot token"),
+                (update_parameters.min_votes_index(), "min votes"),
+            ],
+            &UpdateContract::checked_load(&config.update_box_wrapper_inputs.contract_inputs)?
+                .ergo_tree(),
+            &update_box.ergo_tree,
+            String::from(update_box.box_id()),
+        ),
+    ];
+
+    if json_output() {
+        print_json(&diffs);
+    } else {
+        for diff in &diffs {
+            print_contract_diff(diff);
+        }
+    }
+    let drifted: Vec<String> = diffs
+        .iter()
+        .filter(|d| d.has_drift())
+        .map(|d| d.contract.to_string())
+        .collect();
+    if drifted.is_empty() {
+        Ok(())
+    } else {
+        Err(DiffPoolError::Drift(drifted))
+    }
+}
+
+fn print_contract_diff(diff: &ContractDiff) {
+    if !diff.has_drift() {
+        println!("{} contract (box {}): matches", diff.contract, diff.box_id);
+        return;
+    }
+    println!("{} contract (box {}): DIFFERS", diff.contract, diff.box_id);
+    if !diff.template_matches {
+        println!("  the script differs, not only its constants");
+    }
+    for c in &diff.constants {
+        println!(
+            "  constant {}{}: local {}, on-chain {}",
+            c.index,
+            c.name.map(|n| format!(" ({})", n)).unwrap_or_default(),
+            c.local.as_deref().unwrap_or("<none>"),
+            c.on_chain.as_deref().unwrap_or("<none>"),
+        );
+    }
+}
+
+/// Compare the constants of the local and on-chain trees. `names` gives the name of the contract
+/// parameters at their constant index.
+fn diff_contract(
+    contract: &'static str,
+    names: &[(usize, &'static str)],
+    local: &ErgoTree,
+    on_chain: &ErgoTree,
+    box_id: String,
+) -> ContractDiff {
+    let local_constants = local.get_constants().unwrap_or_default();
+    let on_chain_constants = on_chain.get_constants().unwrap_or_default();
+    let constants = (0..local_constants.len().max(on_chain_constants.len()))
+        .filter_map(|index| {
+            let local = local_constants.get(index).map(render_constant);
+            let on_chain = on_chain_constants.get(index).map(render_constant);
+            if local == on_chain {
+                return None;
+            }
+            Some(ConstantDiff {
+                index,
+                name: names.iter().find(|(i, _)| *i == index).map(|(_, n)| *n),
+                local,
+                on_chain,
+            })
+        })
+        .collect();
+    let tree_template_hash =
+        |tree: &ErgoTree| template_hash(&tree.sigma_serialize_bytes().unwrap_or_default());
+    ContractDiff {
+        contract,
+        box_id,
+        template_matches: tree_template_hash(local) == tree_template_hash(on_chain),
+        constants,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contracts::pool::{PoolContractInputs, PoolContractParameters};
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    #[test]
+    fn test_diff_contract() {
+        let token_ids = generate_token_ids();
+        let parameters = PoolContractParameters::default();
+        let local = PoolContract::build_with(
+            &PoolContractInputs::build_with(
+                parameters.clone(),
+                token_ids.refresh_nft_token_id.clone(),
+                token_ids.update_nft_token_id.clone(),
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .ergo_tree();
+        let names = [
+            (parameters.refresh_nft_index(), "refresh NFT"),
+            (parameters.update_nft_index(), "update NFT"),
+        ];
+        let same = diff_contract("pool", &names, &local, &local, String::new());
+        assert!(!same.has_drift());
+
+        // The on-chain pool has another update NFT
+        let other_token_ids = generate_token_ids();
+        let on_chain = PoolContract::build_with(
+            &PoolContractInputs::build_with(
+                parameters.clone(),
+                token_ids.refresh_nft_token_id.clone(),
+                other_token_ids.update_nft_token_id.clone(),
+            )
+            .unwrap(),
+        )
+        .unwrap()
+        .ergo_tree();
+        let diff = diff_contract("pool", &names, &local, &on_chain, String::new());
+        assert!(diff.has_drift());
+        assert!(diff.template_matches);
+        assert_eq!(diff.constants.len(), 1);
+        assert_eq!(diff.constants[0].index, parameters.update_nft_index());
+        assert_eq!(diff.constants[0].name, Some("update NFT"));
+        assert_eq!(
+            diff.constants[0].on_chain,
+            Some(String::from(other_token_ids.update_nft_token_id))
+        );
+    }
+}
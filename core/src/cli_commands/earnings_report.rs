@@ -0,0 +1,330 @@
+//! Report of the epochs the local oracle participated in, the reward tokens it earned and the fees
+//! it paid, for accounting. The reward tokens of an epoch are the ones added to the oracle box by
+//! the refresh collecting its datapoint, the fees are taken from the fee ledger. With `--fiat` the
+//! amounts are valued at the pool rate the epoch was finalized with (the aggregated value of the
+//! data source), which must be a nanoERG price of the fiat unit.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use derive_more::From;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    box_kind::{OracleBox, OracleBoxWrapper},
+    cli_commands::json_output,
+    datapoint_source::PredefinedDataPointSource,
+    fees::FeeLedger,
+    oracle_config::ORACLE_CONFIG,
+    oracle_state::{LocalDatapointBoxSource, StageError},
+    pool_archive::{PoolArchive, PoolArchiveError, POOL_ARCHIVE_FILE},
+    store::{self, StoreError},
+};
+
+#[derive(Debug, Error, From)]
+pub enum EarningsReportError {
+    #[error("stage error: {0}")]
+    StageError(#[source] StageError),
+    #[error("pool archive error: {0}")]
+    PoolArchive(PoolArchiveError),
+    #[error("store error: {0}")]
+    Store(StoreError),
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error(
+        "the rate of the NanoAdaUsd data source is not a nanoERG price, --fiat is not supported"
+    )]
+    FiatNotSupported,
+}
+
+/// An oracle box of the local oracle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OracleBoxRecord {
+    pub height: u32,
+    /// Epoch counter and datapoint, `None` for a box created by a refresh
+    pub posted: Option<(u32, i64)>,
+    pub reward_tokens: u64,
+}
+
+impl From<&OracleBoxWrapper> for OracleBoxRecord {
+    fn from(oracle_box: &OracleBoxWrapper) -> Self {
+        OracleBoxRecord {
+            height: oracle_box.get_box().creation_height,
+            posted: match oracle_box {
+                OracleBoxWrapper::Posted(posted) => {
+                    Some((posted.epoch_counter(), posted.rate() as i64))
+                }
+                OracleBoxWrapper::Collected(_) => None,
+            },
+            reward_tokens: *oracle_box.reward_token().amount.as_u64(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EpochEarnings {
+    pub epoch_counter: u32,
+    pub datapoint: i64,
+    /// The datapoint was collected by the refresh of the epoch
+    pub collected: bool,
+    /// Height of the refresh collecting the datapoint
+    pub collected_height: Option<u32>,
+    pub reward_tokens: u64,
+    pub fees_nanoergs: u64,
+    /// Rate the epoch was finalized with, `None` if its refresh is not in the pool archive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_rate: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reward_fiat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fees_fiat: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EarningsReport {
+    pub epochs: Vec<EpochEarnings>,
+    pub participated_epochs: u32,
+    pub reward_tokens: u64,
+    pub fees_nanoergs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reward_fiat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fees_fiat: Option<f64>,
+}
+
+/// Valuation of the earnings in the fiat unit of the pool rate
+#[derive(Debug, Clone, Copy)]
+pub struct FiatValuation {
+    /// nanoERG value of a reward token, the reward tokens are not valued without it
+    pub reward_token_price: Option<u64>,
+}
+
+pub fn print_earnings_report(
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    fiat: bool,
+    reward_token_price: Option<u64>,
+    output: Option<String>,
+) -> Result<(), EarningsReportError> {
+    if fiat && ORACLE_CONFIG.data_point_source == Some(PredefinedDataPointSource::NanoAdaUsd) {
+        return Err(EarningsReportError::FiatNotSupported);
+    }
+    let archive = PoolArchive::load(
+        Path::new(POOL_ARCHIVE_FILE),
+        &ORACLE_CONFIG.token_ids.pool_nft_token_id,
+    )?;
+    let fee_ledger = store::get()?.fee_ledger()?;
+    let mut oracle_boxes = local_datapoint_box_source.get_spent_local_oracle_datapoint_boxes()?;
+    oracle_boxes.extend(local_datapoint_box_source.get_local_oracle_datapoint_box()?);
+    let records: Vec<OracleBoxRecord> = oracle_boxes.iter().map(OracleBoxRecord::from).collect();
+    let report = get_earnings_report(
+        records,
+        &archive,
+        &fee_ledger,
+        fiat.then(|| FiatValuation { reward_token_price }),
+    );
+    if fiat && archive.pool_boxes.is_empty() {
+        eprintln!("The pool archive is empty, run `pool-history --backfill` to get the pool rates");
+    }
+    let text = if json_output() {
+        serde_json::to_string_pretty(&report).unwrap()
+    } else {
+        to_csv(&report, fiat)
+    };
+    match output {
+        Some(path) => std::fs::write(path, text)?,
+        None => println!("{}", text),
+    }
+    Ok(())
+}
+
+/// The earnings of every epoch the oracle posted a datapoint for, oldest first. The datapoint of
+/// the last box is left out if no refresh collected it yet.
+pub fn get_earnings_report(
+    mut oracle_boxes: Vec<OracleBoxRecord>,
+    archive: &PoolArchive,
+    fee_ledger: &FeeLedger,
+    fiat: Option<FiatValuation>,
+) -> EarningsReport {
+    oracle_boxes.sort_by_key(|b| b.height);
+    let mut epochs: BTreeMap<u32, EpochEarnings> = BTreeMap::new();
+    for pair in oracle_boxes.windows(2) {
+        let (posted, next) = (&pair[0], &pair[1]);
+        let (epoch_counter, datapoint) = match posted.posted {
+            Some(posted) => posted,
+            None => continue,
+        };
+        // The refresh replaces the posted box with a box without datapoint, a republication or
+        // the datapoint of the next epoch replaces a datapoint left out by the refresh
+        let collected = next.posted.is_none() && next.reward_tokens > posted.reward_tokens;
+        let pool_rate = archive
+            .pool_boxes
+            .iter()
+            .find(|b| b.epoch_counter == epoch_counter + 1)
+            .map(|b| b.rate)
+            .filter(|rate| *rate > 0);
+        let entry = epochs
+            .entry(epoch_counter)
+            .or_insert_with(|| EpochEarnings {
+                epoch_counter,
+                datapoint,
+                collected: false,
+                collected_height: None,
+                reward_tokens: 0,
+                fees_nanoergs: fee_ledger
+                    .by_epoch
+                    .get(&epoch_counter)
+                    .map_or(0, |f| f.nanoergs),
+                pool_rate: fiat.and(pool_rate),
+                reward_fiat: None,
+                fees_fiat: None,
+            });
+        entry.datapoint = datapoint;
+        if collected {
+            entry.collected = true;
+            entry.collected_height = Some(next.height);
+            entry.reward_tokens = next.reward_tokens - posted.reward_tokens;
+        }
+    }
+    let mut report = EarningsReport {
+        epochs: epochs.into_values().collect(),
+        participated_epochs: 0,
+        reward_tokens: 0,
+        fees_nanoergs: 0,
+        reward_fiat: fiat.and_then(|f| f.reward_token_price).map(|_| 0.0),
+        fees_fiat: fiat.map(|_| 0.0),
+    };
+    for epoch in report.epochs.iter_mut() {
+        if let (Some(fiat), Some(rate)) = (fiat, epoch.pool_rate) {
+            epoch.fees_fiat = Some(epoch.fees_nanoergs as f64 / rate as f64);
+            epoch.reward_fiat = fiat
+                .reward_token_price
+                .map(|price| epoch.reward_tokens as f64 * price as f64 / rate as f64);
+        }
+        report.participated_epochs += epoch.collected as u32;
+        report.reward_tokens += epoch.reward_tokens;
+        report.fees_nanoergs += epoch.fees_nanoergs;
+        report.reward_fiat = report
+            .reward_fiat
+            .map(|total| total + epoch.reward_fiat.unwrap_or_default());
+        report.fees_fiat = report
+            .fees_fiat
+            .map(|total| total + epoch.fees_fiat.unwrap_or_default());
+    }
+    report
+}
+
+fn to_csv(report: &EarningsReport, fiat: bool) -> String {
+    let mut csv =
+        String::from("epoch,datapoint,collected,collected_height,reward_tokens,fees_nanoergs");
+    if fiat {
+        csv.push_str(",pool_rate,reward_fiat,fees_fiat");
+    }
+    let optional = |v: Option<String>| v.unwrap_or_default();
+    for epoch in &report.epochs {
+        let _ = write!(
+            csv,
+            "\n{},{},{},{},{},{}",
+            epoch.epoch_counter,
+            epoch.datapoint,
+            epoch.collected,
+            optional(epoch.collected_height.map(|h| h.to_string())),
+            epoch.reward_tokens,
+            epoch.fees_nanoergs
+        );
+        if fiat {
+            let _ = write!(
+                csv,
+                ",{},{},{}",
+                optional(epoch.pool_rate.map(|r| r.to_string())),
+                optional(epoch.reward_fiat.map(|v| format!("{:.6}", v))),
+                optional(epoch.fees_fiat.map(|v| format!("{:.6}", v)))
+            );
+        }
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergotree_ir::chain::token::TokenId;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::fees::FeeTotal;
+    use crate::pool_archive::ArchivedPoolBox;
+
+    #[test]
+    fn test_get_earnings_report() {
+        let record = |height, posted, reward_tokens| OracleBoxRecord {
+            height,
+            posted,
+            reward_tokens,
+        };
+        let oracle_boxes = vec![
+            record(130, Some((4, 2100)), 11),
+            record(100, Some((2, 2000)), 10),
+            record(110, None, 11),
+            // Left out by the refresh of epoch 3, republished in epoch 4
+            record(120, Some((3, 9000)), 11),
+            record(140, None, 15),
+            // Not collected yet
+            record(150, Some((5, 2200)), 15),
+        ];
+        let mut archive = PoolArchive::new(&force_any_val::<TokenId>());
+        archive.merge(vec![
+            ArchivedPoolBox {
+                box_id: "pool3".to_string(),
+                height: 110,
+                rate: 2_000_000,
+                epoch_counter: 3,
+            },
+            ArchivedPoolBox {
+                box_id: "pool5".to_string(),
+                height: 140,
+                rate: 1_000_000,
+                epoch_counter: 5,
+            },
+        ]);
+        let mut fee_ledger = FeeLedger::default();
+        fee_ledger.by_epoch.insert(
+            4,
+            FeeTotal {
+                txs: 2,
+                nanoergs: 3_000_000,
+            },
+        );
+
+        let report = get_earnings_report(oracle_boxes.clone(), &archive, &fee_ledger, None);
+        assert_eq!(report.epochs.len(), 3);
+        assert!(report.epochs[0].collected);
+        assert_eq!(report.epochs[0].reward_tokens, 1);
+        assert_eq!(report.epochs[0].collected_height, Some(110));
+        assert!(!report.epochs[1].collected);
+        assert_eq!(report.epochs[2].reward_tokens, 4);
+        assert_eq!(report.participated_epochs, 2);
+        assert_eq!(report.reward_tokens, 5);
+        assert_eq!(report.fees_nanoergs, 3_000_000);
+        assert_eq!(report.epochs[0].pool_rate, None);
+        assert_eq!(report.fees_fiat, None);
+
+        let report = get_earnings_report(
+            oracle_boxes,
+            &archive,
+            &fee_ledger,
+            Some(FiatValuation {
+                reward_token_price: Some(500_000),
+            }),
+        );
+        assert_eq!(report.epochs[0].reward_fiat, Some(0.25));
+        assert_eq!(report.epochs[2].fees_fiat, Some(3.0));
+        assert_eq!(report.epochs[2].reward_fiat, Some(2.0));
+        assert_eq!(report.reward_fiat, Some(2.25));
+        assert_eq!(report.fees_fiat, Some(3.0));
+        let csv = to_csv(&report, true);
+        assert!(csv.starts_with("epoch,datapoint,collected,collected_height,reward_tokens,fees_nanoergs,pool_rate,reward_fiat,fees_fiat\n"));
+        assert!(csv.contains("\n3,9000,false,,0,0,,,\n"));
+        assert!(csv.ends_with("\n4,2100,true,140,4,3000000,1000000,2.000000,3.000000"));
+    }
+}
@@ -0,0 +1,54 @@
+use derive_more::From;
+use serde_json::json;
+use thiserror::Error;
+
+use crate::cli_commands::{json_output, print_json};
+use crate::secrets::{self, SecretsError, SECRETS_PASSPHRASE_ENV_VAR};
+
+#[derive(Debug, Error, From)]
+pub enum EncryptSecretError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("{0}")]
+    Secrets(SecretsError),
+    #[error("the passphrases don't match")]
+    PassphraseMismatch,
+    #[error("empty secret")]
+    EmptySecret,
+}
+
+/// Ask for a secret and print the config value replacing it, the secret encrypted with the
+/// passphrase of the config secrets or stored in the OS keyring under `keyring_name`
+pub fn encrypt_secret(keyring_name: Option<String>) -> Result<(), EncryptSecretError> {
+    let secret = rpassword::prompt_password("Secret: ")?;
+    if secret.is_empty() {
+        return Err(EncryptSecretError::EmptySecret);
+    }
+    let value = match keyring_name {
+        Some(name) => secrets::store_in_keyring(&name, &secret)?,
+        None => {
+            let passphrase = match std::env::var(SECRETS_PASSPHRASE_ENV_VAR) {
+                Ok(passphrase) => passphrase,
+                Err(_) => {
+                    let passphrase =
+                        rpassword::prompt_password("Passphrase of the config secrets: ")?;
+                    if rpassword::prompt_password("Repeat the passphrase: ")? != passphrase {
+                        return Err(EncryptSecretError::PassphraseMismatch);
+                    }
+                    passphrase
+                }
+            };
+            if passphrase.is_empty() {
+                return Err(SecretsError::EmptyPassphrase.into());
+            }
+            secrets::encrypt(&secret, &passphrase)?
+        }
+    };
+    if json_output() {
+        print_json(&json!({ "value": value }));
+    } else {
+        println!("Set the field of the secret in the config to");
+        println!("{}", value);
+    }
+    Ok(())
+}
@@ -0,0 +1,309 @@
+//! Leaving the pool: all reward tokens but one are extracted and the oracle token is handed over
+//! to the pool admin in a single transaction.
+//!
+//! Burning the oracle token is not possible, the oracle contract requires the oracle token to stay
+//! in a box guarded by the oracle contract on every spend.
+use std::convert::TryInto;
+
+use derive_more::From;
+use ergo_lib::{
+    chain::{
+        ergo_box::box_builder::{ErgoBoxCandidateBuilder, ErgoBoxCandidateBuilderError},
+        transaction::unsigned::UnsignedTransaction,
+    },
+    ergotree_interpreter::sigma_protocol::prover::ContextExtension,
+    ergotree_ir::{
+        chain::{
+            address::{Address, AddressEncoder, AddressEncoderError},
+            token::Token,
+        },
+        serialization::SigmaParsingError,
+    },
+    wallet::{
+        box_selector::{BoxSelection, BoxSelector, BoxSelectorError, SimpleBoxSelector},
+        tx_builder::{TxBuilder, TxBuilderError},
+    },
+};
+use ergo_node_interface::node_interface::NodeError;
+use thiserror::Error;
+
+use crate::{
+    audit_log,
+    box_kind::{
+        make_collected_oracle_box_candidate, make_oracle_box_candidate, OracleBox, OracleBoxWrapper,
+    },
+    cli_commands::{
+        confirm_transaction_preview, print_transaction_aborted, print_transaction_submitted,
+    },
+    fee_estimation::apply_estimated_fee,
+    min_box_value::{check_min_box_values, MinBoxValueError},
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_config::{tx_fee, TxFeeAction, BASE_FEE},
+    oracle_state::{LocalDatapointBoxSource, StageError},
+    prompt::Prompt,
+    wallet::{WalletDataError, WalletDataSource},
+};
+
+#[derive(Debug, Error, From)]
+pub enum ExitPoolActionError {
+    #[error("Pool admin address not P2PK")]
+    IncorrectPoolAdminAddress,
+    #[error("Rewards address not P2PK")]
+    IncorrectRewardsAddress,
+    #[error("box builder error: {0}")]
+    ErgoBoxCandidateBuilder(ErgoBoxCandidateBuilderError),
+    #[error("stage error: {0}")]
+    StageError(#[source] StageError),
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
+    #[error("box selector error: {0}")]
+    BoxSelector(BoxSelectorError),
+    #[error("Sigma parsing error: {0}")]
+    SigmaParse(SigmaParsingError),
+    #[error("tx builder error: {0}")]
+    TxBuilder(TxBuilderError),
+    #[error("Node doesn't have a change address set and change_address isn't set in the config")]
+    NoChangeAddressSetInNode,
+    #[error("No local datapoint box")]
+    NoLocalDatapointBox,
+    #[error("AddressEncoder error: {0}")]
+    AddressEncoder(#[source] AddressEncoderError),
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("WalletData error: {0}")]
+    WalletData(#[source] WalletDataError),
+    #[error("{0}")]
+    MinBoxValue(MinBoxValueError),
+}
+
+pub fn exit_pool(
+    wallet: &dyn WalletDataSource,
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    pool_admin_address_str: String,
+    rewards_address_str: String,
+    prompt: &dyn Prompt,
+) -> Result<(), ExitPoolActionError> {
+    let pool_admin_address =
+        AddressEncoder::unchecked_parse_network_address_from_str(&pool_admin_address_str)?;
+    let rewards_address =
+        AddressEncoder::unchecked_parse_network_address_from_str(&rewards_address_str)?;
+
+    let change_address_str =
+        get_change_address()?.ok_or(ExitPoolActionError::NoChangeAddressSetInNode)?;
+
+    let (change_address, network_prefix) = {
+        let a = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
+        (a.address(), a.network())
+    };
+    let (unsigned_tx, num_extracted_reward_tokens) = build_exit_pool_tx(
+        local_datapoint_box_source,
+        wallet,
+        pool_admin_address.address(),
+        rewards_address.address(),
+        current_block_height()? as u32,
+        change_address,
+    )?;
+
+    if confirm_transaction_preview(prompt, &unsigned_tx, network_prefix, &format!(
+        "YOU WILL BE LEAVING THE POOL: {} REWARD TOKENS WILL BE SENT TO {} AND YOUR ORACLE TOKEN TO {}.",
+        num_extracted_reward_tokens, rewards_address_str, pool_admin_address_str
+    ))? {
+        sign_and_submit_transaction(&unsigned_tx, "exit_pool")?;
+        print_transaction_submitted(&unsigned_tx, network_prefix);
+    } else {
+        audit_log::record_declined("exit_pool", &unsigned_tx);
+        print_transaction_aborted();
+    }
+    Ok(())
+}
+
+/// Builds a transaction that hands the oracle box over to `pool_admin_address` (keeping the single
+/// reward token required by the contract) and sends the rest of the reward tokens to
+/// `rewards_address`. Returns the transaction and the number of extracted reward tokens.
+fn build_exit_pool_tx(
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    wallet: &dyn WalletDataSource,
+    pool_admin_address: Address,
+    rewards_address: Address,
+    height: u32,
+    change_address: Address,
+) -> Result<(UnsignedTransaction, u64), ExitPoolActionError> {
+    let in_oracle_box = local_datapoint_box_source
+        .get_local_oracle_datapoint_box()?
+        .ok_or(ExitPoolActionError::NoLocalDatapointBox)?;
+    let pool_admin_pk = if let Address::P2Pk(pk) = &pool_admin_address {
+        pk.clone()
+    } else {
+        return Err(ExitPoolActionError::IncorrectPoolAdminAddress);
+    };
+    if !matches!(rewards_address, Address::P2Pk(_)) {
+        return Err(ExitPoolActionError::IncorrectRewardsAddress);
+    }
+    let num_reward_tokens = *in_oracle_box.reward_token().amount.as_u64();
+    // The oracle box must always keep at least 1 reward token.
+    let num_extracted_reward_tokens = num_reward_tokens.saturating_sub(1);
+    let remaining_reward_token = Token {
+        token_id: in_oracle_box.reward_token().token_id.clone(),
+        amount: 1u64.try_into().unwrap(),
+    };
+    let oracle_box_candidate =
+        if let OracleBoxWrapper::Posted(ref posted_oracle_box) = in_oracle_box {
+            make_oracle_box_candidate(
+                posted_oracle_box.contract(),
+                pool_admin_pk,
+                posted_oracle_box.rate() as i64,
+                posted_oracle_box.epoch_counter(),
+                posted_oracle_box.oracle_token(),
+                remaining_reward_token,
+                posted_oracle_box.get_box().value,
+                height,
+            )?
+        } else {
+            make_collected_oracle_box_candidate(
+                in_oracle_box.contract(),
+                pool_admin_pk,
+                in_oracle_box.oracle_token(),
+                remaining_reward_token,
+                in_oracle_box.get_box().value,
+                height,
+            )?
+        };
+    let mut output_candidates = vec![oracle_box_candidate];
+    let tx_fee = tx_fee(TxFeeAction::ExitPool);
+    let mut target_balance = tx_fee;
+    if num_extracted_reward_tokens > 0 {
+        let mut builder =
+            ErgoBoxCandidateBuilder::new(*BASE_FEE, rewards_address.script()?, height);
+        builder.add_token(Token {
+            token_id: in_oracle_box.reward_token().token_id.clone(),
+            amount: num_extracted_reward_tokens.try_into().unwrap(),
+        });
+        output_candidates.push(builder.build()?);
+        // The fee and `BASE_FEE` for the box holding the extracted reward tokens.
+        target_balance = tx_fee.checked_add(&BASE_FEE).unwrap();
+    }
+
+    let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
+    let box_selector = SimpleBoxSelector::new();
+    let selection = box_selector.select(unspent_boxes, target_balance, &[])?;
+    let mut input_boxes = vec![in_oracle_box.get_box().clone()];
+    input_boxes.append(selection.boxes.as_vec().clone().as_mut());
+    let box_selection = BoxSelection {
+        boxes: input_boxes.try_into().unwrap(),
+        change_boxes: selection.change_boxes,
+    };
+    check_min_box_values(&output_candidates)?;
+    let mut tx_builder = TxBuilder::new(
+        box_selection,
+        output_candidates,
+        height,
+        tx_fee,
+        change_address.clone(),
+    );
+    // The following context value ensures that `outIndex` in the oracle contract is properly set.
+    let ctx_ext = ContextExtension {
+        values: vec![(0, 0i32.into())].into_iter().collect(),
+    };
+    tx_builder.set_context_extension(in_oracle_box.get_box().box_id(), ctx_ext);
+    let tx = apply_estimated_fee(tx_builder.build()?, TxFeeAction::ExitPool, &change_address);
+    Ok((tx, num_extracted_reward_tokens))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::box_kind::OracleBoxWrapperInputs;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::pool_commands::test_utils::{
+        find_input_boxes, generate_token_ids, make_datapoint_box, make_wallet_unspent_box,
+        OracleBoxMock, WalletDataMock,
+    };
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+    use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+    use ergo_lib::wallet::signing::TransactionContext;
+    use ergo_lib::wallet::Wallet;
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_exit_pool() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = ctx.pre_header.height;
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let oracle_box = OracleBoxWrapper::new(
+            make_datapoint_box(
+                *secret.public_image().h,
+                200,
+                1,
+                &token_ids,
+                BASE_FEE.checked_mul_u32(100).unwrap(),
+                height - 9,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        let local_datapoint_box_source = OracleBoxMock { oracle_box };
+        let wallet_mock = WalletDataMock {
+            unspent_boxes: vec![make_wallet_unspent_box(
+                secret.public_image(),
+                BASE_FEE.checked_mul_u32(10000).unwrap(),
+                None,
+            )],
+        };
+        let pool_admin_address = Address::P2Pk(force_any_val::<DlogProverInput>().public_image());
+        let change_address = AddressEncoder::new(NetworkPrefix::Mainnet)
+            .parse_address_from_str("9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r")
+            .unwrap();
+
+        let (tx, num_extracted_reward_tokens) = build_exit_pool_tx(
+            &local_datapoint_box_source,
+            &wallet_mock,
+            pool_admin_address.clone(),
+            change_address.clone(),
+            height,
+            change_address,
+        )
+        .unwrap();
+        assert_eq!(num_extracted_reward_tokens, 99);
+        let out_oracle_box = OracleBoxWrapper::new(
+            ErgoBox::from_box_candidate(tx.output_candidates.first(), force_any_val::<TxId>(), 0)
+                .unwrap(),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        assert_eq!(
+            Address::P2Pk(out_oracle_box.public_key()),
+            pool_admin_address
+        );
+        assert_eq!(*out_oracle_box.reward_token().amount.as_u64(), 1);
+
+        let mut possible_input_boxes = vec![local_datapoint_box_source
+            .get_local_oracle_datapoint_box()
+            .unwrap()
+            .unwrap()
+            .get_box()
+            .clone()];
+        possible_input_boxes.append(&mut wallet_mock.get_unspent_wallet_boxes().unwrap());
+        let tx_context = TransactionContext::new(
+            tx.clone(),
+            find_input_boxes(tx, possible_input_boxes),
+            Vec::new(),
+        )
+        .unwrap();
+        let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
+    }
+}
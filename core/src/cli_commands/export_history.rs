@@ -0,0 +1,193 @@
+//! Export of the pool rates and the datapoints posted by the local oracle over a height range, as
+//! a CSV or JSON time series for analysis. The pool rates come from the pool archive (see
+//! `pool-history`), backfilled from the explorer with `--backfill`, the datapoints from the current
+//! and spent oracle boxes of the local oracle.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use derive_more::From;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    cli_commands::{
+        datapoint_history::{get_datapoint_history, PostedDatapoint},
+        json_output,
+    },
+    oracle_config::OracleConfig,
+    oracle_state::{LocalDatapointBoxSource, StageError},
+    pool_archive::{
+        backfill, default_explorer_url, PoolArchive, PoolArchiveError, POOL_ARCHIVE_FILE,
+    },
+};
+
+#[derive(Debug, Error, From)]
+pub enum ExportHistoryError {
+    #[error("stage error: {0}")]
+    StageError(#[source] StageError),
+    #[error("pool archive error: {0}")]
+    PoolArchive(PoolArchiveError),
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Series {
+    /// Rate of a pool box
+    PoolRate,
+    /// Datapoint posted by the local oracle
+    Datapoint,
+}
+
+impl Series {
+    fn name(&self) -> &'static str {
+        match self {
+            Series::PoolRate => "pool_rate",
+            Series::Datapoint => "datapoint",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SeriesPoint {
+    pub height: u32,
+    pub epoch_counter: u32,
+    pub series: Series,
+    pub value: i64,
+    pub box_id: String,
+}
+
+/// The pool rates of `archive` and the `datapoints` created between the heights `from` and `to`
+/// (inclusive), by height
+pub fn history_series(
+    archive: &PoolArchive,
+    datapoints: &[PostedDatapoint],
+    from: Option<u32>,
+    to: Option<u32>,
+) -> Vec<SeriesPoint> {
+    let pool_rates = archive.pool_boxes.iter().map(|b| SeriesPoint {
+        height: b.height,
+        epoch_counter: b.epoch_counter,
+        series: Series::PoolRate,
+        value: b.rate,
+        box_id: b.box_id.clone(),
+    });
+    let posted = datapoints.iter().map(|d| SeriesPoint {
+        height: d.height,
+        epoch_counter: d.epoch_counter,
+        series: Series::Datapoint,
+        value: d.datapoint,
+        box_id: d.box_id.clone(),
+    });
+    let mut points: Vec<SeriesPoint> = pool_rates
+        .chain(posted)
+        .filter(|p| {
+            from.map_or(true, |from| p.height >= from) && to.map_or(true, |to| p.height <= to)
+        })
+        .collect();
+    // Stable, the pool rate first at a height
+    points.sort_by_key(|p| p.height);
+    points
+}
+
+fn to_csv(points: &[SeriesPoint]) -> String {
+    let mut csv = String::from("height,epoch,series,value,box_id");
+    for point in points {
+        let _ = write!(
+            csv,
+            "\n{},{},{},{},{}",
+            point.height,
+            point.epoch_counter,
+            point.series.name(),
+            point.value,
+            point.box_id
+        );
+    }
+    csv
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn export_history(
+    config: &OracleConfig,
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    from_height: Option<u32>,
+    to_height: Option<u32>,
+    backfill_archive: bool,
+    explorer_url: Option<String>,
+    output: Option<String>,
+) -> Result<(), ExportHistoryError> {
+    let path = Path::new(POOL_ARCHIVE_FILE);
+    let pool_nft_token_id = &config.token_ids.pool_nft_token_id;
+    let archive = if backfill_archive {
+        let explorer_url = explorer_url
+            .unwrap_or_else(|| default_explorer_url(config.oracle_address.network()).to_string());
+        let (archive, added) = backfill(path, &explorer_url, pool_nft_token_id)?;
+        log::info!("Archived {} new pool boxes from {}", added, explorer_url);
+        archive
+    } else {
+        PoolArchive::load(path, pool_nft_token_id)?
+    };
+    if archive.pool_boxes.is_empty() {
+        eprintln!("The pool archive is empty, run with --backfill to fetch the pool rates");
+    }
+    let datapoints = get_datapoint_history(local_datapoint_box_source, &archive)?;
+    let points = history_series(&archive, &datapoints, from_height, to_height);
+    let text = if json_output() {
+        serde_json::to_string_pretty(&points).unwrap()
+    } else {
+        to_csv(&points)
+    };
+    match output {
+        Some(path) => std::fs::write(path, text)?,
+        None => println!("{}", text),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergotree_ir::chain::token::TokenId;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::pool_archive::ArchivedPoolBox;
+
+    #[test]
+    fn test_history_series() {
+        let mut archive = PoolArchive::new(&force_any_val::<TokenId>());
+        archive.merge(
+            vec![(100, 1, 200), (130, 2, 210), (160, 3, 205)]
+                .into_iter()
+                .map(|(height, epoch_counter, rate)| ArchivedPoolBox {
+                    box_id: format!("pool{}", epoch_counter),
+                    height,
+                    rate,
+                    epoch_counter,
+                })
+                .collect(),
+        );
+        let datapoints = vec![(125, 1, 211), (155, 2, 204)]
+            .into_iter()
+            .map(|(height, epoch_counter, datapoint)| PostedDatapoint {
+                box_id: format!("oracle{}", epoch_counter),
+                height,
+                epoch_counter,
+                datapoint,
+                pool_rate: None,
+                deviation_percent: None,
+            })
+            .collect::<Vec<_>>();
+
+        let points = history_series(&archive, &datapoints, Some(120), Some(155));
+        assert_eq!(
+            to_csv(&points),
+            "height,epoch,series,value,box_id\n\
+            125,1,datapoint,211,oracle1\n\
+            130,2,pool_rate,210,pool2\n\
+            155,2,datapoint,204,oracle2"
+        );
+        assert_eq!(history_series(&archive, &datapoints, None, None).len(), 5);
+    }
+}
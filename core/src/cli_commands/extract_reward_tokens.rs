@@ -23,13 +23,21 @@ use ergo_node_interface::node_interface::NodeError;
 use thiserror::Error;
 
 use crate::{
+    audit_log,
     box_kind::{
         make_collected_oracle_box_candidate, make_oracle_box_candidate, OracleBox, OracleBoxWrapper,
     },
-    cli_commands::ergo_explorer_transaction_link,
-    node_interface::{current_block_height, get_wallet_status, sign_and_submit_transaction},
-    oracle_config::BASE_FEE,
+    cli_commands::{
+        confirm_transaction_preview, print_transaction_aborted, print_transaction_submitted,
+    },
+    fee_estimation::apply_estimated_fee,
+    min_box_value::{check_min_box_values, MinBoxValueError},
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_config::{tx_fee, TxFeeAction, BASE_FEE},
     oracle_state::{LocalDatapointBoxSource, StageError},
+    prompt::Prompt,
     wallet::{WalletDataError, WalletDataSource},
 };
 
@@ -37,14 +45,22 @@ use crate::{
 pub enum ExtractRewardTokensActionError {
     #[error("Oracle box must contain at least 2 reward tokens. It contains {0} tokens")]
     InsufficientRewardTokensInOracleBox(usize),
+    #[error(
+        "Requested to extract {requested} reward tokens, but only {available} can be extracted"
+    )]
+    RequestedAmountExceedsAvailable { requested: u64, available: u64 },
+    #[error("Requested amount of reward tokens to extract must be greater than zero")]
+    ZeroAmountRequested,
     #[error("Destination address not P2PK")]
     IncorrectDestinationAddress,
     #[error("box builder error: {0}")]
     ErgoBoxCandidateBuilder(ErgoBoxCandidateBuilderError),
     #[error("stage error: {0}")]
-    StageError(StageError),
+    StageError(#[source] StageError),
     #[error("node error: {0}")]
-    Node(NodeError),
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
     #[error("box selector error: {0}")]
     BoxSelector(BoxSelectorError),
     #[error("Sigma parsing error: {0}")]
@@ -54,27 +70,30 @@ pub enum ExtractRewardTokensActionError {
     #[error("No local datapoint box")]
     NoLocalDatapointBox,
     #[error("AddressEncoder error: {0}")]
-    AddressEncoder(AddressEncoderError),
-    #[error("Node doesn't have a change address set")]
+    AddressEncoder(#[source] AddressEncoderError),
+    #[error("Node doesn't have a change address set and change_address isn't set in the config")]
     NoChangeAddressSetInNode,
     #[error("IO error: {0}")]
-    Io(std::io::Error),
+    Io(#[source] std::io::Error),
     #[error("WalletData error: {0}")]
-    WalletData(WalletDataError),
+    WalletData(#[source] WalletDataError),
+    #[error("{0}")]
+    MinBoxValue(MinBoxValueError),
 }
 
 pub fn extract_reward_tokens(
     wallet: &dyn WalletDataSource,
     local_datapoint_box_source: &dyn LocalDatapointBoxSource,
     rewards_destination_str: String,
+    amount: Option<u64>,
+    prompt: &dyn Prompt,
 ) -> Result<(), ExtractRewardTokensActionError> {
     let rewards_destination =
         AddressEncoder::unchecked_parse_network_address_from_str(&rewards_destination_str)?;
     let network_prefix = rewards_destination.network();
 
-    let change_address_str = get_wallet_status()?
-        .change_address
-        .ok_or(ExtractRewardTokensActionError::NoChangeAddressSetInNode)?;
+    let change_address_str =
+        get_change_address()?.ok_or(ExtractRewardTokensActionError::NoChangeAddressSetInNode)?;
 
     let change_address =
         AddressEncoder::new(network_prefix).parse_address_from_str(&change_address_str)?;
@@ -82,32 +101,34 @@ pub fn extract_reward_tokens(
         local_datapoint_box_source,
         wallet,
         rewards_destination.address(),
+        amount,
         current_block_height()? as u32,
         change_address,
     )?;
 
-    println!(
-        "YOU WILL BE TRANSFERRING {} REWARD TOKENS TO {}. TYPE 'YES' TO INITIATE THE TRANSACTION.",
-        num_reward_tokens, rewards_destination_str
-    );
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-    if input.trim() == "YES" {
-        let tx_id_str = sign_and_submit_transaction(&unsigned_tx)?;
-        println!(
-            "Transaction made. Check status here: {}",
-            ergo_explorer_transaction_link(tx_id_str, network_prefix)
-        );
+    if confirm_transaction_preview(
+        prompt,
+        &unsigned_tx,
+        network_prefix,
+        &format!(
+            "YOU WILL BE TRANSFERRING {} REWARD TOKENS TO {}.",
+            num_reward_tokens, rewards_destination_str
+        ),
+    )? {
+        sign_and_submit_transaction(&unsigned_tx, "extract_reward_tokens")?;
+        print_transaction_submitted(&unsigned_tx, network_prefix);
     } else {
-        println!("Aborting the transaction.")
+        audit_log::record_declined("extract_reward_tokens", &unsigned_tx);
+        print_transaction_aborted();
     }
     Ok(())
 }
 
-fn build_extract_reward_tokens_tx(
+pub(crate) fn build_extract_reward_tokens_tx(
     local_datapoint_box_source: &dyn LocalDatapointBoxSource,
     wallet: &dyn WalletDataSource,
     rewards_destination: Address,
+    amount: Option<u64>,
     height: u32,
     change_address: Address,
 ) -> Result<(UnsignedTransaction, u64), ExtractRewardTokensActionError> {
@@ -122,10 +143,25 @@ fn build_extract_reward_tokens_tx(
             ),
         );
     }
+    // The oracle box must always keep at least 1 reward token.
+    let available_amount = num_reward_tokens - 1;
+    let extract_amount = match amount {
+        Some(0) => return Err(ExtractRewardTokensActionError::ZeroAmountRequested),
+        Some(requested) if requested > available_amount => {
+            return Err(
+                ExtractRewardTokensActionError::RequestedAmountExceedsAvailable {
+                    requested,
+                    available: available_amount,
+                },
+            )
+        }
+        Some(requested) => requested,
+        None => available_amount,
+    };
     if let Address::P2Pk(_) = &rewards_destination {
-        let single_reward_token = Token {
+        let remaining_reward_tokens = Token {
             token_id: in_oracle_box.reward_token().token_id.clone(),
-            amount: 1.try_into().unwrap(),
+            amount: (num_reward_tokens - extract_amount).try_into().unwrap(),
         };
         let oracle_box_candidate =
             if let OracleBoxWrapper::Posted(ref posted_oracle_box) = in_oracle_box {
@@ -135,7 +171,7 @@ fn build_extract_reward_tokens_tx(
                     posted_oracle_box.rate() as i64,
                     posted_oracle_box.epoch_counter(),
                     posted_oracle_box.oracle_token(),
-                    single_reward_token,
+                    remaining_reward_tokens,
                     posted_oracle_box.get_box().value,
                     height,
                 )?
@@ -144,7 +180,7 @@ fn build_extract_reward_tokens_tx(
                     in_oracle_box.contract(),
                     in_oracle_box.public_key(),
                     in_oracle_box.oracle_token(),
-                    single_reward_token,
+                    remaining_reward_tokens,
                     in_oracle_box.get_box().value,
                     height,
                 )?
@@ -156,7 +192,7 @@ fn build_extract_reward_tokens_tx(
 
         let extracted_reward_tokens = Token {
             token_id: in_oracle_box.reward_token().token_id.clone(),
-            amount: (num_reward_tokens - 1).try_into().unwrap(),
+            amount: extract_amount.try_into().unwrap(),
         };
 
         builder.add_token(extracted_reward_tokens);
@@ -164,8 +200,9 @@ fn build_extract_reward_tokens_tx(
 
         let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
 
-        // `BASE_FEE` each for the fee and the box holding the extracted reward tokens.
-        let target_balance = BASE_FEE.checked_mul_u32(2).unwrap();
+        // The fee and `BASE_FEE` for the box holding the extracted reward tokens.
+        let tx_fee = tx_fee(TxFeeAction::ExtractRewardTokens);
+        let target_balance = tx_fee.checked_add(&BASE_FEE).unwrap();
 
         let box_selector = SimpleBoxSelector::new();
         let selection = box_selector.select(unspent_boxes, target_balance, &[])?;
@@ -175,20 +212,26 @@ fn build_extract_reward_tokens_tx(
             boxes: input_boxes.try_into().unwrap(),
             change_boxes: selection.change_boxes,
         };
+        let output_candidates = vec![oracle_box_candidate, reward_box_candidate];
+        check_min_box_values(&output_candidates)?;
         let mut tx_builder = TxBuilder::new(
             box_selection,
-            vec![oracle_box_candidate, reward_box_candidate],
+            output_candidates,
             height,
-            *BASE_FEE,
-            change_address,
+            tx_fee,
+            change_address.clone(),
         );
         // The following context value ensures that `outIndex` in the oracle contract is properly set.
         let ctx_ext = ContextExtension {
             values: vec![(0, 0i32.into())].into_iter().collect(),
         };
         tx_builder.set_context_extension(in_oracle_box.get_box().box_id(), ctx_ext);
-        let tx = tx_builder.build()?;
-        Ok((tx, num_reward_tokens - 1))
+        let tx = apply_estimated_fee(
+            tx_builder.build()?,
+            TxFeeAction::ExtractRewardTokens,
+            &change_address,
+        );
+        Ok((tx, extract_amount))
     } else {
         Err(ExtractRewardTokensActionError::IncorrectDestinationAddress)
     }
@@ -258,6 +301,7 @@ mod tests {
             &local_datapoint_box_source,
             &wallet_mock,
             change_address.clone(),
+            None,
             height,
             change_address,
         )
@@ -281,4 +325,103 @@ mod tests {
 
         let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
     }
+
+    #[test]
+    fn test_extract_part_of_reward_tokens() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = ctx.pre_header.height;
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
+        let oracle_pub_key = secret.public_image().h;
+
+        let parameters = OracleContractParameters::default();
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((parameters, &token_ids)).unwrap();
+        let oracle_box = OracleBoxWrapper::new(
+            make_datapoint_box(
+                *oracle_pub_key,
+                200,
+                1,
+                &token_ids,
+                BASE_FEE.checked_mul_u32(100).unwrap(),
+                height - 9,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        let local_datapoint_box_source = OracleBoxMock { oracle_box };
+
+        let change_address =
+            AddressEncoder::new(ergo_lib::ergotree_ir::chain::address::NetworkPrefix::Mainnet)
+                .parse_address_from_str("9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r")
+                .unwrap();
+
+        let wallet_unspent_box = make_wallet_unspent_box(
+            secret.public_image(),
+            BASE_FEE.checked_mul_u32(10000).unwrap(),
+            None,
+        );
+        let wallet_mock = WalletDataMock {
+            unspent_boxes: vec![wallet_unspent_box],
+        };
+
+        // Can't extract the last reward token
+        assert!(matches!(
+            build_extract_reward_tokens_tx(
+                &local_datapoint_box_source,
+                &wallet_mock,
+                change_address.clone(),
+                Some(100),
+                height,
+                change_address.clone(),
+            ),
+            Err(
+                ExtractRewardTokensActionError::RequestedAmountExceedsAvailable {
+                    requested: 100,
+                    available: 99
+                }
+            )
+        ));
+
+        let (tx, num_reward_tokens) = build_extract_reward_tokens_tx(
+            &local_datapoint_box_source,
+            &wallet_mock,
+            change_address.clone(),
+            Some(40),
+            height,
+            change_address,
+        )
+        .unwrap();
+        assert_eq!(num_reward_tokens, 40);
+        let out_oracle_box = tx.output_candidates.first();
+        assert_eq!(
+            *out_oracle_box
+                .tokens
+                .as_ref()
+                .unwrap()
+                .get(1)
+                .unwrap()
+                .amount
+                .as_u64(),
+            60
+        );
+
+        let mut possible_input_boxes = vec![local_datapoint_box_source
+            .get_local_oracle_datapoint_box()
+            .unwrap()
+            .unwrap()
+            .get_box()
+            .clone()];
+        possible_input_boxes.append(&mut wallet_mock.get_unspent_wallet_boxes().unwrap());
+
+        let tx_context = TransactionContext::new(
+            tx.clone(),
+            find_input_boxes(tx, possible_input_boxes),
+            Vec::new(),
+        )
+        .unwrap();
+
+        let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
+    }
 }
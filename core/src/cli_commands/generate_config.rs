@@ -0,0 +1,242 @@
+//! Commented template of the operator config for a network and a pool preset. The values to fill
+//! in are placeholders starting with [`PLACEHOLDER_PREFIX`], reported by `validate-config` until
+//! they are replaced. The pool definition isn't part of the template, it is imported from the
+//! chain with `import-pool` (see `pool_config`).
+
+use std::io::Write;
+use std::path::Path;
+
+use derive_more::From;
+use ergo_lib::wallet::tx_builder::SUGGESTED_TX_FEE;
+use serde_json::json;
+use thiserror::Error;
+
+use crate::cli_commands::{json_output, print_json};
+use crate::config_format::ConfigFormat;
+use crate::config_schema::PLACEHOLDER_PREFIX;
+
+#[derive(Debug, Error, From)]
+pub enum GenerateConfigError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("unknown preset {0}, expected erg-usd-mainnet or testnet-demo")]
+    #[from(ignore)]
+    UnknownPreset(String),
+    #[error("{0} already exists")]
+    #[from(ignore)]
+    FileExists(String),
+    #[error("the template is YAML, {0} is a TOML file")]
+    #[from(ignore)]
+    NotYaml(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigPreset {
+    /// An operator of the ERG/USD pool on the mainnet
+    ErgUsdMainnet,
+    /// An operator of a demo pool on the testnet
+    TestnetDemo,
+}
+
+impl ConfigPreset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigPreset::ErgUsdMainnet => "erg-usd-mainnet",
+            ConfigPreset::TestnetDemo => "testnet-demo",
+        }
+    }
+}
+
+impl std::str::FromStr for ConfigPreset {
+    type Err = GenerateConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [ConfigPreset::ErgUsdMainnet, ConfigPreset::TestnetDemo]
+            .iter()
+            .copied()
+            .find(|preset| preset.name() == s)
+            .ok_or_else(|| GenerateConfigError::UnknownPreset(s.to_string()))
+    }
+}
+
+fn placeholder(description: &str) -> String {
+    format!("\"{}: {}>\"", PLACEHOLDER_PREFIX, description)
+}
+
+/// Commented operator config of the `preset`
+pub fn config_template(preset: ConfigPreset) -> String {
+    let (network, node_port, address_start, import_pool, log_level) = match preset {
+        ConfigPreset::ErgUsdMainnet => (
+            "mainnet",
+            9053,
+            "9",
+            "oracle-core import-pool --pool-nft <pool NFT id of the ERG/USD pool>",
+            "Info",
+        ),
+        ConfigPreset::TestnetDemo => (
+            "testnet",
+            9052,
+            "3",
+            "oracle-core import-pool --testnet --pool-nft <pool NFT id of the demo pool>",
+            "Debug",
+        ),
+    };
+    format!(
+        "\
+# oracle-core config of an operator ({preset} preset), generated by `oracle-core generate-config`.
+# The values starting with {marker} must be filled in, `oracle-core validate-config` lists
+# the ones left.
+
+# Node of the {network} with the wallet holding the oracle token (its wallet must be unlocked). The
+# secrets can be kept encrypted or in the OS keyring, see `oracle-core encrypt-secret`.
+node_ip: 127.0.0.1
+node_port: {node_port}
+node_api_key: {api_key}
+# node_wallet_password: <password the daemon unlocks the node wallet with>
+
+# Address of the node wallet holding the oracle token of the pool
+oracle_address: {oracle_address}
+# change_address: <address receiving the change, the change address of the node if not set>
+
+# Height to start the scans of the pool boxes from, e.g. the height the oracle token was received
+rescan_height: {rescan_height}
+
+# Fee of the txs in nanoERGs, raised per action in tx_fees
+base_fee: {base_fee}
+# tx_fees:
+#   refresh: 5000000
+# Or the fee estimated from the size of the txs
+# fee_estimation:
+#   nanoergs_per_byte: 1000
+
+# Source of the datapoints, one of NanoErgUsd, NanoErgXau, NanoAdaUsd, or a script printing the
+# datapoint set in data_point_source_custom_script (data_point_source left empty)
+data_point_source: NanoErgUsd
+data_point_source_custom_script: ~
+
+# Pool definition (contract parameters and token ids), relative to this file. Import it from the
+# chain with
+#   {import_pool}
+# and compare the printed hash with the one of the pool admins before pinning it.
+pool_config_file: pool_config.yaml
+# pool_config_hash: <hash printed by import-pool>
+
+# One of Off, Error, Warn, Info, Debug, Trace
+log_level: {log_level}
+# log_filter: oracle_core::scans=debug,reqwest=warn
+# log_format: json
+
+# REST API
+core_api_port: 9010
+# core_api_bind_address: 127.0.0.1
+# core_api_admin_token_file: admin_token
+# core_api_admin_allowed_scripts: [./datapoint.sh]
+# core_api_cors_allowed_origins: []
+
+# When to publish and refresh: standard, aggressive_collector, passive_poster or watch_only
+# epoch_strategy: standard
+
+# Automatic extraction of the reward tokens of the oracle box
+# reward_extraction:
+#   payout_address: <address receiving the reward tokens>
+#   min_reward_tokens: 100
+#   dry_run: true
+
+# Notifications of the operator
+# notifications:
+#   targets:
+#     - type: telegram
+#       bot_token: <bot token>
+#       chat_id: <chat id>
+# missed_epochs_alert_threshold: 3
+# low_balance_alert:
+#   min_nanoergs: 100000000
+",
+        preset = preset.name(),
+        marker = PLACEHOLDER_PREFIX,
+        network = network,
+        node_port = node_port,
+        api_key = placeholder("API key of the node"),
+        oracle_address = placeholder(&format!(
+            "{} address of the wallet, starting with {}",
+            network, address_start
+        )),
+        rescan_height = placeholder("start height of the scans"),
+        base_fee = SUGGESTED_TX_FEE().as_u64(),
+        import_pool = import_pool,
+        log_level = log_level,
+    )
+}
+
+/// Write the config template of the `preset` to `config_path`
+pub fn generate_config(
+    config_path: &Path,
+    preset: ConfigPreset,
+) -> Result<(), GenerateConfigError> {
+    if ConfigFormat::of_path(config_path) != ConfigFormat::Yaml {
+        return Err(GenerateConfigError::NotYaml(
+            config_path.display().to_string(),
+        ));
+    }
+    if config_path.exists() {
+        return Err(GenerateConfigError::FileExists(
+            config_path.display().to_string(),
+        ));
+    }
+    let mut file = std::fs::File::create(config_path)?;
+    file.write_all(config_template(preset).as_bytes())?;
+    if json_output() {
+        print_json(&json!({
+            "config_file": config_path.display().to_string(),
+            "preset": preset.name(),
+        }));
+    } else {
+        println!(
+            "{} written, fill in the values starting with {} and import the pool definition",
+            config_path.display(),
+            PLACEHOLDER_PREFIX
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli_commands::bootstrap::BootstrapConfig;
+    use crate::config_schema::validate_config_value;
+    use crate::oracle_config::OracleConfig;
+    use crate::pool_commands::test_utils::generate_token_ids;
+    use crate::pool_config::{POOL_CONFIG_FILE_KEY, POOL_CONFIG_KEYS};
+
+    #[test]
+    fn test_config_template() {
+        let config =
+            OracleConfig::create(BootstrapConfig::default(), generate_token_ids(), 0).unwrap();
+        let full_config = serde_json::to_value(&config).unwrap();
+        for preset in [ConfigPreset::ErgUsdMainnet, ConfigPreset::TestnetDemo] {
+            assert_eq!(preset.name().parse::<ConfigPreset>().unwrap(), preset);
+            let template: serde_json::Value =
+                serde_yaml::from_str(&config_template(preset)).unwrap();
+            let mut template = template.as_object().unwrap().clone();
+            template.remove(POOL_CONFIG_FILE_KEY);
+            let mut filled = serde_json::Value::Object(template);
+            for key in POOL_CONFIG_KEYS {
+                filled[key] = full_config[key].clone();
+            }
+            let mut issues = validate_config_value(&filled)
+                .into_iter()
+                .map(|issue| issue.location)
+                .collect::<Vec<_>>();
+            issues.sort();
+            assert_eq!(
+                issues,
+                vec!["/node_api_key", "/oracle_address", "/rescan_height"]
+            );
+            filled["node_api_key"] = "hello".into();
+            filled["oracle_address"] = full_config["oracle_address"].clone();
+            filled["rescan_height"] = 0.into();
+            assert_eq!(validate_config_value(&filled), vec![]);
+        }
+    }
+}
@@ -0,0 +1,477 @@
+//! Import of the pool definition (see `pool_config`) of a live pool from its pool NFT. The pool,
+//! refresh and update boxes are fetched from the explorer, along with an oracle box and a ballot
+//! box, and the contract parameters and token ids are read from their contracts. The contracts
+//! are expected to follow the EIP-23 templates (constants at the EIP-23 indices).
+
+use std::convert::{TryFrom, TryInto};
+use std::path::Path;
+
+use derive_more::From;
+use ergo_lib::ergo_chain_types::{Digest32, DigestNError};
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValueError;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
+use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
+use ergo_lib::ergotree_ir::serialization::{SigmaParsingError, SigmaSerializable};
+use serde_json::json;
+use thiserror::Error;
+
+use crate::{
+    cli_commands::{json_output, print_json},
+    config_format::{ConfigFormat, ConfigFormatError},
+    contracts::{
+        ballot::{BallotContractParameters, BallotContractParametersError},
+        oracle::{OracleContractParameters, OracleContractParametersError},
+        pool::{PoolContractParameters, PoolContractParametersError},
+        refresh::{
+            RefreshContractParameters, RefreshContractParametersError,
+            RefreshContractParametersInputs,
+        },
+        update::{UpdateContractParameters, UpdateContractParametersError},
+    },
+    oracle_config::TokenIds,
+    pool_config::{pool_config_hash, resolve_path},
+    serde::PoolDefinitionSerde,
+};
+
+/// Number of boxes holding a token requested from the explorer
+const EXPLORER_BOX_LIMIT: usize = 100;
+
+#[derive(Debug, Error, From)]
+pub enum ImportPoolError {
+    #[error("Reqwest error: {0}")]
+    Reqwest(reqwest::Error),
+    #[error("JSON parse error: {0}")]
+    JsonParse(json::Error),
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("YAML error: {0}")]
+    Yaml(serde_yaml::Error),
+    #[error("{0}")]
+    Format(ConfigFormatError),
+    #[error("Base16 decode error: {0}")]
+    Decode(base16::DecodeError),
+    #[error("invalid token id: {0}")]
+    Digest(DigestNError),
+    #[error("sigma parsing error: {0}")]
+    SigmaParsing(SigmaParsingError),
+    #[error("BoxValue error: {0}")]
+    BoxValue(BoxValueError),
+    #[error("unexpected explorer response: {0}")]
+    #[from(ignore)]
+    UnexpectedExplorerResponse(String),
+    #[error("no unspent {0} box found")]
+    #[from(ignore)]
+    BoxNotFound(&'static str),
+    #[error("the {0} contract doesn't follow the EIP-23 template: no {1} at index {2}")]
+    #[from(ignore)]
+    NotEip23(&'static str, &'static str, usize),
+    #[error("the {0} contract belongs to another pool")]
+    #[from(ignore)]
+    OtherPool(&'static str),
+    #[error("{0} exists already")]
+    #[from(ignore)]
+    FileExists(String),
+    #[error("Oracle contract parameter error: {0}")]
+    OracleContractParameters(OracleContractParametersError),
+    #[error("Pool contract parameter error: {0}")]
+    PoolContractParameters(PoolContractParametersError),
+    #[error("Refresh contract parameter error: {0}")]
+    RefreshContractParameters(RefreshContractParametersError),
+    #[error("Update contract parameter error: {0}")]
+    UpdateContractParameters(UpdateContractParametersError),
+    #[error("Ballot contract parameter error: {0}")]
+    BallotContractParameters(BallotContractParametersError),
+}
+
+/// Contract parameters and token ids of a live pool
+#[derive(Debug, Clone)]
+pub struct PoolDefinition {
+    pub oracle_contract_parameters: OracleContractParameters,
+    pub pool_contract_parameters: PoolContractParameters,
+    pub refresh_contract_parameters: RefreshContractParameters,
+    pub update_contract_parameters: UpdateContractParameters,
+    pub ballot_contract_parameters: BallotContractParameters,
+    pub token_ids: TokenIds,
+}
+
+/// An unspent box as the explorer returns it
+struct ExplorerBox {
+    ergo_tree: ErgoTree,
+    token_ids: Vec<TokenId>,
+}
+
+impl ExplorerBox {
+    fn ergo_tree_bytes(&self) -> Vec<u8> {
+        self.ergo_tree.sigma_serialize_bytes().unwrap()
+    }
+}
+
+pub fn parse_token_id(token_id: &str) -> Result<TokenId, ImportPoolError> {
+    Ok(TokenId::from(Digest32::try_from(token_id.to_string())?))
+}
+
+/// Fetch the pool holding `pool_nft_token_id` from the explorer and write its definition to
+/// `pool_config_file` (relative to the directory of the config at `config_path`), as TOML if its
+/// name ends with `.toml`
+pub fn import_pool(
+    pool_nft_token_id: &TokenId,
+    explorer_url: &str,
+    config_path: &Path,
+    pool_config_file: &str,
+) -> Result<(), ImportPoolError> {
+    let output = resolve_path(config_path, pool_config_file);
+    if output.exists() {
+        return Err(ImportPoolError::FileExists(output.display().to_string()));
+    }
+    let pool_definition = fetch_pool_definition(explorer_url, pool_nft_token_id)?;
+    let refresh = pool_definition.refresh_contract_parameters.clone();
+    let min_votes = pool_definition.update_contract_parameters.min_votes();
+    let pool_config_str = ConfigFormat::of_path(&output).to_string(&serde_yaml::to_value(
+        PoolDefinitionSerde::from(pool_definition),
+    )?)?;
+    let hash = pool_config_hash(pool_config_str.as_bytes());
+    std::fs::write(&output, pool_config_str)?;
+    if json_output() {
+        print_json(&json!({
+            "pool_config_file": output.display().to_string(),
+            "pool_config_hash": hash,
+            "epoch_length": refresh.epoch_length(),
+            "min_data_points": refresh.min_data_points(),
+            "max_deviation_percent": refresh.max_deviation_percent(),
+            "min_votes": min_votes,
+        }));
+    } else {
+        println!(
+            "Pool definition written to {}, its hash is {}. Compare the hash with the one of the \
+            pool admin, then set `pool_config_file` and `pool_config_hash` in the oracle config.",
+            output.display(),
+            hash
+        );
+        println!(
+            "Epoch length: {}, min data points: {}, max deviation: {}%, min votes: {}",
+            refresh.epoch_length(),
+            refresh.min_data_points(),
+            refresh.max_deviation_percent(),
+            min_votes
+        );
+    }
+    Ok(())
+}
+
+/// Read the contract parameters and the token ids of the pool from its live boxes
+pub fn fetch_pool_definition(
+    explorer_url: &str,
+    pool_nft_token_id: &TokenId,
+) -> Result<PoolDefinition, ImportPoolError> {
+    let pool_box = fetch_unspent_boxes(explorer_url, pool_nft_token_id)?
+        .into_iter()
+        .next()
+        .ok_or(ImportPoolError::BoxNotFound("pool"))?;
+    let reward_token_id = pool_box
+        .token_ids
+        .get(1)
+        .cloned()
+        .ok_or(ImportPoolError::BoxNotFound("pool"))?;
+    let default_pool = PoolContractParameters::default();
+    let refresh_nft_token_id = token_id_constant(
+        &pool_box.ergo_tree,
+        default_pool.refresh_nft_index(),
+        "pool",
+        "refresh NFT",
+    )?;
+    let update_nft_token_id = token_id_constant(
+        &pool_box.ergo_tree,
+        default_pool.update_nft_index(),
+        "pool",
+        "update NFT",
+    )?;
+    let pool_contract_parameters = PoolContractParameters::checked_load(
+        pool_box.ergo_tree_bytes(),
+        default_pool.refresh_nft_index(),
+        default_pool.update_nft_index(),
+    )?;
+
+    let refresh_box = fetch_unspent_boxes(explorer_url, &refresh_nft_token_id)?
+        .into_iter()
+        .next()
+        .ok_or(ImportPoolError::BoxNotFound("refresh"))?;
+    let (refresh_contract_parameters, oracle_token_id) =
+        refresh_parameters(&refresh_box, pool_nft_token_id)?;
+
+    let update_box = fetch_unspent_boxes(explorer_url, &update_nft_token_id)?
+        .into_iter()
+        .next()
+        .ok_or(ImportPoolError::BoxNotFound("update"))?;
+    let (update_contract_parameters, ballot_token_id) =
+        update_parameters(&update_box, pool_nft_token_id)?;
+
+    // The tokens are also held by the wallets of the operators, take the first contract box
+    let oracle_contract_parameters = fetch_unspent_boxes(explorer_url, &oracle_token_id)?
+        .iter()
+        .find_map(|b| oracle_parameters(b, pool_nft_token_id).ok())
+        .ok_or(ImportPoolError::BoxNotFound("oracle"))?;
+    let ballot_contract_parameters = fetch_unspent_boxes(explorer_url, &ballot_token_id)?
+        .iter()
+        .find_map(|b| ballot_parameters(b, &update_nft_token_id).ok())
+        .ok_or(ImportPoolError::BoxNotFound("ballot"))?;
+
+    Ok(PoolDefinition {
+        oracle_contract_parameters,
+        pool_contract_parameters,
+        refresh_contract_parameters,
+        update_contract_parameters,
+        ballot_contract_parameters,
+        token_ids: TokenIds {
+            pool_nft_token_id: pool_nft_token_id.clone(),
+            refresh_nft_token_id,
+            update_nft_token_id,
+            oracle_token_id,
+            reward_token_id,
+            ballot_token_id,
+        },
+    })
+}
+
+fn refresh_parameters(
+    refresh_box: &ExplorerBox,
+    pool_nft_token_id: &TokenId,
+) -> Result<(RefreshContractParameters, TokenId), ImportPoolError> {
+    let default = RefreshContractParameters::default();
+    let tree = &refresh_box.ergo_tree;
+    if &token_id_constant(tree, default.pool_nft_index(), "refresh", "pool NFT")?
+        != pool_nft_token_id
+    {
+        return Err(ImportPoolError::OtherPool("refresh"));
+    }
+    let oracle_token_id = token_id_constant(
+        tree,
+        default.oracle_token_id_index(),
+        "refresh",
+        "oracle token",
+    )?;
+    let parameters = RefreshContractParameters::checked_load(RefreshContractParametersInputs {
+        ergo_tree_bytes: refresh_box.ergo_tree_bytes(),
+        pool_nft_index: default.pool_nft_index(),
+        oracle_token_id_index: default.oracle_token_id_index(),
+        min_data_points_index: default.min_data_points_index(),
+        min_data_points: int_constant(
+            tree,
+            default.min_data_points_index(),
+            "refresh",
+            "min data points",
+        )?,
+        buffer_length_index: default.buffer_length_index(),
+        buffer_length: int_constant(
+            tree,
+            default.buffer_length_index(),
+            "refresh",
+            "buffer length",
+        )?,
+        max_deviation_percent_index: default.max_deviation_percent_index(),
+        max_deviation_percent: int_constant(
+            tree,
+            default.max_deviation_percent_index(),
+            "refresh",
+            "max deviation percent",
+        )?,
+        epoch_length_index: default.epoch_length_index(),
+        epoch_length: int_constant(
+            tree,
+            default.epoch_length_index(),
+            "refresh",
+            "epoch length",
+        )?,
+    })?;
+    Ok((parameters, oracle_token_id))
+}
+
+fn update_parameters(
+    update_box: &ExplorerBox,
+    pool_nft_token_id: &TokenId,
+) -> Result<(UpdateContractParameters, TokenId), ImportPoolError> {
+    let default = UpdateContractParameters::default();
+    let tree = &update_box.ergo_tree;
+    if &token_id_constant(tree, default.pool_nft_index(), "update", "pool NFT")?
+        != pool_nft_token_id
+    {
+        return Err(ImportPoolError::OtherPool("update"));
+    }
+    let ballot_token_id =
+        token_id_constant(tree, default.ballot_token_index(), "update", "ballot token")?;
+    let min_votes = int_constant(tree, default.min_votes_index(), "update", "min votes")?;
+    let parameters = UpdateContractParameters::checked_load(
+        update_box.ergo_tree_bytes(),
+        default.pool_nft_index(),
+        default.ballot_token_index(),
+        default.min_votes_index(),
+        min_votes as u64,
+    )?;
+    Ok((parameters, ballot_token_id))
+}
+
+fn oracle_parameters(
+    oracle_box: &ExplorerBox,
+    pool_nft_token_id: &TokenId,
+) -> Result<OracleContractParameters, ImportPoolError> {
+    let default = OracleContractParameters::default();
+    let tree = &oracle_box.ergo_tree;
+    if &token_id_constant(tree, default.pool_nft_index, "oracle", "pool NFT")? != pool_nft_token_id
+    {
+        return Err(ImportPoolError::OtherPool("oracle"));
+    }
+    let min_storage_rent = long_constant(
+        tree,
+        default.min_storage_rent_index,
+        "oracle",
+        "min storage rent",
+    )?;
+    Ok(OracleContractParameters::checked_load(
+        oracle_box.ergo_tree_bytes(),
+        default.pool_nft_index,
+        default.min_storage_rent_index,
+        min_storage_rent.try_into()?,
+    )?)
+}
+
+fn ballot_parameters(
+    ballot_box: &ExplorerBox,
+    update_nft_token_id: &TokenId,
+) -> Result<BallotContractParameters, ImportPoolError> {
+    let default = BallotContractParameters::default();
+    let tree = &ballot_box.ergo_tree;
+    if &token_id_constant(tree, default.update_nft_index(), "ballot", "update NFT")?
+        != update_nft_token_id
+    {
+        return Err(ImportPoolError::OtherPool("ballot"));
+    }
+    let min_storage_rent = long_constant(
+        tree,
+        default.min_storage_rent_index(),
+        "ballot",
+        "min storage rent",
+    )?;
+    Ok(BallotContractParameters::checked_load(
+        ballot_box.ergo_tree_bytes(),
+        min_storage_rent.try_into()?,
+        default.min_storage_rent_index(),
+        default.update_nft_index(),
+    )?)
+}
+
+fn token_id_constant(
+    tree: &ErgoTree,
+    index: usize,
+    contract: &'static str,
+    name: &'static str,
+) -> Result<TokenId, ImportPoolError> {
+    tree.get_constant(index)
+        .ok()
+        .flatten()
+        .and_then(|c| c.try_extract_into::<TokenId>().ok())
+        .ok_or(ImportPoolError::NotEip23(contract, name, index))
+}
+
+fn int_constant(
+    tree: &ErgoTree,
+    index: usize,
+    contract: &'static str,
+    name: &'static str,
+) -> Result<i32, ImportPoolError> {
+    tree.get_constant(index)
+        .ok()
+        .flatten()
+        .and_then(|c| c.try_extract_into::<i32>().ok())
+        .ok_or(ImportPoolError::NotEip23(contract, name, index))
+}
+
+fn long_constant(
+    tree: &ErgoTree,
+    index: usize,
+    contract: &'static str,
+    name: &'static str,
+) -> Result<i64, ImportPoolError> {
+    tree.get_constant(index)
+        .ok()
+        .flatten()
+        .and_then(|c| c.try_extract_into::<i64>().ok())
+        .ok_or(ImportPoolError::NotEip23(contract, name, index))
+}
+
+/// Unspent boxes holding `token_id`, from the explorer
+fn fetch_unspent_boxes(
+    explorer_url: &str,
+    token_id: &TokenId,
+) -> Result<Vec<ExplorerBox>, ImportPoolError> {
+    let url = format!(
+        "{}/api/v1/boxes/unspent/byTokenId/{}?limit={}",
+        explorer_url.trim_end_matches('/'),
+        String::from(token_id.clone()),
+        EXPLORER_BOX_LIMIT
+    );
+    let page = json::parse(&reqwest::blocking::get(&url)?.text()?)?;
+    let items = &page["items"];
+    if !items.is_array() {
+        return Err(ImportPoolError::UnexpectedExplorerResponse(page.dump()));
+    }
+    parse_explorer_boxes(items)
+}
+
+fn parse_explorer_boxes(items: &json::JsonValue) -> Result<Vec<ExplorerBox>, ImportPoolError> {
+    items
+        .members()
+        .map(|item| {
+            let ergo_tree_hex = item["ergoTree"]
+                .as_str()
+                .ok_or_else(|| ImportPoolError::UnexpectedExplorerResponse(item.dump()))?;
+            let ergo_tree = ErgoTree::sigma_parse_bytes(&base16::decode(ergo_tree_hex)?)?;
+            let token_ids = item["assets"]
+                .members()
+                .map(|asset| parse_token_id(asset["tokenId"].as_str().unwrap_or_default()))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(ExplorerBox {
+                ergo_tree,
+                token_ids,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contracts::refresh::{RefreshContract, RefreshContractInputs};
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    fn explorer_box(ergo_tree: &ErgoTree) -> ExplorerBox {
+        let items = json::parse(&format!(
+            r#"[{{"ergoTree": "{}", "assets": []}}]"#,
+            ergo_tree.to_base16_bytes().unwrap()
+        ))
+        .unwrap();
+        parse_explorer_boxes(&items).unwrap().pop().unwrap()
+    }
+
+    #[test]
+    fn test_read_parameters_from_contracts() {
+        let token_ids = generate_token_ids();
+        let refresh = RefreshContractInputs::build_with(
+            RefreshContractParameters::default(),
+            token_ids.oracle_token_id.clone(),
+            token_ids.pool_nft_token_id.clone(),
+        )
+        .unwrap();
+        let refresh_tree = RefreshContract::checked_load(&refresh).unwrap().ergo_tree();
+        let (parameters, oracle_token_id) =
+            refresh_parameters(&explorer_box(&refresh_tree), &token_ids.pool_nft_token_id).unwrap();
+        assert_eq!(oracle_token_id, token_ids.oracle_token_id);
+        assert_eq!(
+            parameters.epoch_length(),
+            RefreshContractParameters::default().epoch_length()
+        );
+        assert!(matches!(
+            refresh_parameters(&explorer_box(&refresh_tree), &token_ids.reward_token_id),
+            Err(ImportPoolError::OtherPool("refresh"))
+        ));
+    }
+}
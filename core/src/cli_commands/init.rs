@@ -0,0 +1,261 @@
+//! Interactive setup wizard creating the oracle config file (to join an existing pool) or the
+//! bootstrap config file (to bootstrap a new pool).
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use derive_more::From;
+use ergo_lib::ergotree_ir::chain::{
+    address::{Address, AddressEncoder, AddressEncoderError, NetworkAddress, NetworkPrefix},
+    token::TokenId,
+};
+use thiserror::Error;
+
+use crate::{
+    cli_commands::bootstrap::BootstrapConfig,
+    datapoint_source::PredefinedDataPointSource,
+    oracle_config::{OracleConfig, RewardExtractionConfig},
+    serde::BootstrapConfigSerde,
+};
+
+/// Name of the bootstrap config file created when the bootstrap of a new pool is chosen.
+const BOOTSTRAP_CONFIG_FILE_NAME: &str = "bootstrap.yaml";
+
+#[derive(Debug, Error, From)]
+pub enum InitError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("yaml error: {0}")]
+    Yaml(serde_yaml::Error),
+    #[error("AddressEncoder error: {0}")]
+    AddressEncoder(#[source] AddressEncoderError),
+    #[error("Address must be P2PK: {0}")]
+    NotP2PKAddress(String),
+    #[error("Invalid answer: {0}")]
+    InvalidAnswer(String),
+    #[error("Pool NFT id {0} doesn't match the pool NFT id in the pool config")]
+    PoolNftMismatch(String),
+    #[error("File {0} already exists")]
+    FileAlreadyExists(String),
+}
+
+/// What the wizard has created
+pub enum InitOutcome {
+    /// Oracle config file for an existing pool was written, scans can be registered
+    OracleConfigCreated,
+    /// Bootstrap config file was written, the pool has to be bootstrapped with it
+    BootstrapConfigCreated(String),
+}
+
+pub fn init(config_file_path: &str) -> Result<InitOutcome, InitError> {
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    run_wizard(&mut input, config_file_path)
+}
+
+fn run_wizard(input: &mut dyn BufRead, config_file_path: &str) -> Result<InitOutcome, InitError> {
+    let node_ip = ask(input, "Node IP", Some("127.0.0.1"))?;
+    let node_port = ask(input, "Node port", Some("9053"))?
+        .parse::<u16>()
+        .map_err(|e| InitError::InvalidAnswer(e.to_string()))?;
+    let node_api_key = ask(input, "Node API key", None)?;
+    let network = parse_network(&ask(input, "Network (mainnet/testnet)", Some("mainnet"))?)?;
+    let oracle_address = parse_p2pk_address(
+        &ask(
+            input,
+            "Oracle address (node wallet address used to post datapoints and pay tx fees)",
+            None,
+        )?,
+        network,
+    )?;
+    let (data_point_source, data_point_source_custom_script) = parse_data_point_source(&ask(
+        input,
+        "Datapoint source (NanoErgUsd/NanoErgXau/NanoAdaUsd or path to a custom script)",
+        Some("NanoErgUsd"),
+    )?);
+
+    match ask(
+        input,
+        "Join an existing pool or bootstrap a new one? (join/bootstrap)",
+        Some("join"),
+    )?
+    .as_str()
+    {
+        "join" => {
+            check_file_does_not_exist(config_file_path)?;
+            let pool_config_path = ask(
+                input,
+                "Path to the pool config file received from the pool operator",
+                None,
+            )?;
+            let pool_config: OracleConfig =
+                serde_yaml::from_str(&std::fs::read_to_string(pool_config_path)?)?;
+            let pool_nft_id_str = ask(input, "Pool NFT id (base64)", None)?;
+            let pool_nft_id = TokenId::from_base64(&pool_nft_id_str)
+                .map_err(|e| InitError::InvalidAnswer(e.to_string()))?;
+            if pool_nft_id != pool_config.token_ids.pool_nft_token_id {
+                return Err(InitError::PoolNftMismatch(pool_nft_id_str));
+            }
+            let payout_address_str = ask(
+                input,
+                "Payout address for automatic reward token extraction (leave empty to disable)",
+                Some(""),
+            )?;
+            let reward_extraction = if payout_address_str.is_empty() {
+                None
+            } else {
+                let payout_address = parse_address(&payout_address_str, network)?;
+                let min_reward_tokens = ask(
+                    input,
+                    "Extract once at least this many reward tokens are accumulated",
+                    Some("100"),
+                )?
+                .parse::<u64>()
+                .map_err(|e| InitError::InvalidAnswer(e.to_string()))?;
+                Some(RewardExtractionConfig {
+                    payout_address,
+                    min_reward_tokens: Some(min_reward_tokens),
+                    every_n_epochs: None,
+                    max_tokens_per_day: None,
+                    dry_run: false,
+                })
+            };
+            let config = OracleConfig {
+                node_ip,
+                node_port,
+                node_api_key,
+                oracle_address,
+                data_point_source,
+                data_point_source_custom_script,
+                reward_extraction,
+                ..pool_config
+            };
+            write_yaml_file(config_file_path, &serde_yaml::to_string(&config)?)?;
+            println!("Oracle config file created: {}", config_file_path);
+            Ok(InitOutcome::OracleConfigCreated)
+        }
+        "bootstrap" => {
+            check_file_does_not_exist(BOOTSTRAP_CONFIG_FILE_NAME)?;
+            let config = BootstrapConfig {
+                node_ip,
+                node_port,
+                node_api_key,
+                oracle_address,
+                data_point_source,
+                data_point_source_custom_script,
+                ..BootstrapConfig::default()
+            };
+            write_yaml_file(
+                BOOTSTRAP_CONFIG_FILE_NAME,
+                &serde_yaml::to_string(&BootstrapConfigSerde::from(config))?,
+            )?;
+            println!(
+                "Bootstrap config file created: {}. Review the pool parameters in it and run `oracle-core bootstrap {}`",
+                BOOTSTRAP_CONFIG_FILE_NAME, BOOTSTRAP_CONFIG_FILE_NAME
+            );
+            Ok(InitOutcome::BootstrapConfigCreated(
+                BOOTSTRAP_CONFIG_FILE_NAME.to_string(),
+            ))
+        }
+        other => Err(InitError::InvalidAnswer(other.to_string())),
+    }
+}
+
+/// Print the question and read the answer. Empty answer returns the default (if given), otherwise
+/// the question is asked again.
+fn ask(
+    input: &mut dyn BufRead,
+    question: &str,
+    default: Option<&str>,
+) -> Result<String, InitError> {
+    loop {
+        match default {
+            Some(d) if !d.is_empty() => print!("{} [{}]: ", question, d),
+            _ => print!("{}: ", question),
+        }
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Err(InitError::InvalidAnswer(format!(
+                "no answer for '{}'",
+                question
+            )));
+        }
+        let answer = answer.trim();
+        if !answer.is_empty() {
+            return Ok(answer.to_string());
+        }
+        if let Some(d) = default {
+            return Ok(d.to_string());
+        }
+    }
+}
+
+fn parse_network(answer: &str) -> Result<NetworkPrefix, InitError> {
+    match answer.to_lowercase().as_str() {
+        "mainnet" => Ok(NetworkPrefix::Mainnet),
+        "testnet" => Ok(NetworkPrefix::Testnet),
+        other => Err(InitError::InvalidAnswer(other.to_string())),
+    }
+}
+
+fn parse_address(answer: &str, network: NetworkPrefix) -> Result<NetworkAddress, InitError> {
+    let address = AddressEncoder::new(network).parse_address_from_str(answer)?;
+    Ok(NetworkAddress::new(network, &address))
+}
+
+fn parse_p2pk_address(answer: &str, network: NetworkPrefix) -> Result<NetworkAddress, InitError> {
+    let address = parse_address(answer, network)?;
+    if let Address::P2Pk(_) = address.address() {
+        Ok(address)
+    } else {
+        Err(InitError::NotP2PKAddress(answer.to_string()))
+    }
+}
+
+fn parse_data_point_source(answer: &str) -> (Option<PredefinedDataPointSource>, Option<String>) {
+    match answer {
+        "NanoErgUsd" => (Some(PredefinedDataPointSource::NanoErgUsd), None),
+        "NanoErgXau" => (Some(PredefinedDataPointSource::NanoErgXau), None),
+        "NanoAdaUsd" => (Some(PredefinedDataPointSource::NanoAdaUsd), None),
+        script => (None, Some(script.to_string())),
+    }
+}
+
+fn check_file_does_not_exist(file_name: &str) -> Result<(), InitError> {
+    if Path::new(file_name).exists() {
+        Err(InitError::FileAlreadyExists(file_name.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+fn write_yaml_file(file_name: &str, s: &str) -> Result<(), InitError> {
+    let mut file = std::fs::File::create(file_name)?;
+    file.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_ask() {
+        let mut input = Cursor::new("\n\nanswer\n  \n".as_bytes());
+        assert_eq!(ask(&mut input, "q", Some("default")).unwrap(), "default");
+        // empty answer without default asks again
+        assert_eq!(ask(&mut input, "q", None).unwrap(), "answer");
+        assert!(ask(&mut input, "q", None).is_err());
+    }
+
+    #[test]
+    fn test_parse_address_network_mismatch() {
+        let mainnet_address = "9hEQHEMyY1K1vs79vJXFtNjr2dbQbtWXF99oVWGJ5c4xbcLdBsw";
+        assert!(parse_p2pk_address(mainnet_address, NetworkPrefix::Mainnet).is_ok());
+        assert!(parse_p2pk_address(mainnet_address, NetworkPrefix::Testnet).is_err());
+    }
+}
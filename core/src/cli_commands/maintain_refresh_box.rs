@@ -0,0 +1,206 @@
+//! Maintenance of the refresh box: its value against the storage rent and whether it is still the
+//! refresh box of the pool contract. The refresh contract keeps the refresh box at the same
+//! contract in every refresh, so a new refresh contract comes with a new refresh NFT minted by
+//! `prepare-update` and takes over once `update-pool` points the pool contract to it. Until then
+//! the refresh box is topped up, like the pool box, by the refresh tx of the current epoch.
+use derive_more::From;
+use ergo_node_interface::node_interface::NodeError;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    box_kind::{PoolBox, PoolBoxError, RefreshBox},
+    cli_commands::{
+        json_output,
+        prepare_update::UPDATED_CONFIG_FILE_NAME,
+        print_json,
+        top_up_pool_box::{submit_refresh_with_top_up, TopUpPoolBoxError},
+    },
+    node_interface::current_block_height,
+    oracle_state::{OraclePool, PoolBoxSource, RefreshBoxSource, StageError},
+    pool_commands::refresh::BoxTopUp,
+    prompt::Prompt,
+    storage_rent::{ProtocolBox, RentStatus},
+    tx_preview::format_erg,
+    wallet::WalletDataSource,
+};
+
+#[derive(Debug, Error, From)]
+pub enum MaintainRefreshBoxError {
+    #[error("stage error: {0}")]
+    StageError(#[source] StageError),
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("{0}")]
+    TopUp(TopUpPoolBoxError),
+    #[error("The refresh box isn't the one of the pool contract anymore, it can't be spent")]
+    RefreshBoxRetired,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RefreshBoxStatus {
+    pub box_id: String,
+    pub value: u64,
+    /// Whether the pool contract refers to the refresh NFT of the box, otherwise the pool was
+    /// updated to a new refresh box
+    pub in_pool_contract: bool,
+    /// The value doesn't cover the storage fee, the box would be destroyed with its NFT when the
+    /// rent is collected
+    pub low_value: bool,
+    pub rent: RentStatus,
+}
+
+pub fn get_refresh_box_status(
+    pool_box_source: &dyn PoolBoxSource,
+    refresh_box_source: &dyn RefreshBoxSource,
+    height: u32,
+) -> Result<RefreshBoxStatus, StageError> {
+    let refresh_box = refresh_box_source.get_refresh_box()?;
+    // Once the pool was updated to another refresh NFT, the pool box doesn't match the pool
+    // contract of the config anymore
+    let in_pool_contract = match pool_box_source.get_pool_box() {
+        Ok(pool_box) => {
+            *pool_box.contract().refresh_nft_token_id() == refresh_box.refresh_nft_token().token_id
+        }
+        Err(StageError::PoolBoxError(PoolBoxError::PoolContractError(_))) => false,
+        Err(e) => return Err(e),
+    };
+    let rent = RentStatus::new(ProtocolBox::Refresh, refresh_box.get_box(), height);
+    Ok(RefreshBoxStatus {
+        box_id: rent.box_id.clone(),
+        value: rent.value,
+        in_pool_contract,
+        low_value: rent.destroyed_on_collection(),
+        rent,
+    })
+}
+
+pub fn maintain_refresh_box(
+    op: &OraclePool,
+    wallet: &dyn WalletDataSource,
+    top_up: Option<u64>,
+    prompt: &dyn Prompt,
+) -> Result<(), MaintainRefreshBoxError> {
+    let height = current_block_height()? as u32;
+    let status = get_refresh_box_status(
+        op.get_pool_box_source(),
+        op.get_refresh_box_source(),
+        height,
+    )?;
+    if json_output() && top_up.is_none() {
+        print_json(&status);
+    } else if !json_output() {
+        print_refresh_box_status(&status);
+    }
+    let nanoergs = match top_up {
+        Some(nanoergs) => nanoergs,
+        None => return Ok(()),
+    };
+    if !status.in_pool_contract {
+        return Err(MaintainRefreshBoxError::RefreshBoxRetired);
+    }
+    if nanoergs == 0 {
+        return Err(TopUpPoolBoxError::ZeroAmount.into());
+    }
+    submit_refresh_with_top_up(
+        op,
+        wallet,
+        BoxTopUp {
+            pool: 0,
+            refresh: nanoergs,
+        },
+        &format!(
+            "YOU WILL BE ADDING {} TO THE REFRESH BOX ({} -> {}) WITH THE REFRESH OF THE EPOCH.",
+            format_erg(nanoergs),
+            format_erg(status.value),
+            format_erg(status.value + nanoergs)
+        ),
+        "top_up_refresh_box",
+        prompt,
+    )?;
+    Ok(())
+}
+
+fn print_refresh_box_status(status: &RefreshBoxStatus) {
+    println!(
+        "Refresh box {}: {}, storage rent due at height {} ({} blocks left)",
+        status.box_id,
+        format_erg(status.value),
+        status.rent.rent_height,
+        status.rent.blocks_left
+    );
+    if !status.in_pool_contract {
+        println!(
+            "The pool contract refers to another refresh NFT: the pool was updated to a new \
+             refresh box. Switch to the config of the update ({}).",
+            UPDATED_CONFIG_FILE_NAME
+        );
+    } else if status.low_value {
+        println!(
+            "The value doesn't cover the storage fee of {}, top it up with --top-up <NANOERGS>",
+            format_erg(status.rent.storage_fee)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::box_kind::RefreshBoxWrapperInputs;
+    use crate::contracts::pool::PoolContractParameters;
+    use crate::contracts::refresh::{RefreshContractInputs, RefreshContractParameters};
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_commands::test_utils::{
+        generate_token_ids, make_pool_box, make_refresh_box, PoolBoxMock, RefreshBoxMock,
+    };
+    use ergo_lib::ergo_chain_types::Digest32;
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_refresh_box_status() {
+        let height = 100;
+        let token_ids = generate_token_ids();
+        let inputs = RefreshBoxWrapperInputs {
+            refresh_nft_token_id: token_ids.refresh_nft_token_id.clone(),
+            contract_inputs: RefreshContractInputs::build_with(
+                RefreshContractParameters::default(),
+                token_ids.oracle_token_id.clone(),
+                token_ids.pool_nft_token_id.clone(),
+            )
+            .unwrap(),
+        };
+        let refresh_box_mock = RefreshBoxMock {
+            refresh_box: make_refresh_box(*BASE_FEE, &inputs, height - 32),
+        };
+        let pool_box_mock = PoolBoxMock {
+            pool_box: make_pool_box(
+                200,
+                1,
+                *BASE_FEE,
+                height - 32,
+                &PoolContractParameters::default(),
+                &token_ids,
+            ),
+        };
+        let status = get_refresh_box_status(&pool_box_mock, &refresh_box_mock, height).unwrap();
+        assert!(status.in_pool_contract);
+        assert_eq!(status.value, *BASE_FEE.as_u64());
+        assert_eq!(status.rent.creation_height, height - 32);
+
+        // The pool contract refers to another refresh NFT after an update
+        let mut updated_token_ids = token_ids.clone();
+        updated_token_ids.refresh_nft_token_id = force_any_val::<Digest32>().into();
+        let pool_box_mock = PoolBoxMock {
+            pool_box: make_pool_box(
+                200,
+                1,
+                *BASE_FEE,
+                height - 32,
+                &PoolContractParameters::default(),
+                &updated_token_ids,
+            ),
+        };
+        let status = get_refresh_box_status(&pool_box_mock, &refresh_box_mock, height).unwrap();
+        assert!(!status.in_pool_contract);
+    }
+}
@@ -0,0 +1,352 @@
+//! Guided migration of an existing (v1-style) pool to a new pool running the current (EIP-23)
+//! contracts.
+//!
+//! The v1 contracts don't allow spending their boxes into the EIP-23 contracts, so the pool can't
+//! be moved in place. Instead the migration is done in the following steps, each of them confirmed:
+//!   1. bootstrap the new pool (mints the tokens and creates the pool, refresh and update boxes);
+//!   2. once the bootstrap transactions are confirmed, send one oracle token and one reward token
+//!      to each operator of the old pool in a single transaction.
+//!
+//! The steps done and the operators who were sent their tokens are kept in
+//! [`MIGRATION_STATE_FILE_NAME`], running `migrate-pool` again after an interruption continues with
+//! the first step not yet done and only sends the tokens to the remaining operators. Winding down the
+//! old pool is left to its operators.
+use std::{convert::TryInto, path::Path};
+
+use derive_more::From;
+use ergo_lib::{
+    chain::{
+        ergo_box::box_builder::{ErgoBoxCandidateBuilder, ErgoBoxCandidateBuilderError},
+        transaction::unsigned::UnsignedTransaction,
+    },
+    ergotree_ir::{
+        chain::{
+            address::{Address, AddressEncoder, AddressEncoderError},
+            ergo_box::box_value::BoxValueError,
+            token::Token,
+        },
+        serialization::SigmaParsingError,
+    },
+    wallet::{
+        box_selector::{BoxSelector, BoxSelectorError, SimpleBoxSelector},
+        tx_builder::{TxBuilder, TxBuilderError},
+    },
+};
+use ergo_node_interface::node_interface::NodeError;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    audit_log,
+    cli_commands::{
+        bootstrap::{bootstrap, BootstrapError},
+        confirm_transaction, confirm_transaction_preview, print_transaction_aborted,
+        print_transaction_submitted,
+    },
+    fee_estimation::apply_estimated_fee,
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_config::{tx_fee, TokenIds, TxFeeAction, BASE_FEE, ORACLE_CONFIG},
+    prompt::Prompt,
+    wallet::{WalletData, WalletDataError, WalletDataSource},
+};
+
+#[derive(Debug, Error, From)]
+pub enum MigratePoolError {
+    #[error("Migrate pool: IO error {0}")]
+    Io(#[source] std::io::Error),
+    #[error("Migrate pool: yaml error {0}")]
+    Yaml(serde_yaml::Error),
+    #[error("Migrate pool: bootstrap error {0}")]
+    Bootstrap(BootstrapError),
+    #[error("Migrate pool: node error {0}")]
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
+    #[error("Migrate pool: AddressEncoder error {0}")]
+    AddressEncoder(#[source] AddressEncoderError),
+    #[error("Migrate pool: box value error {0}")]
+    BoxValue(BoxValueError),
+    #[error("Migrate pool: box builder error {0}")]
+    ErgoBoxCandidateBuilder(ErgoBoxCandidateBuilderError),
+    #[error("Migrate pool: box selector error {0}. Are the bootstrap transactions confirmed?")]
+    BoxSelector(BoxSelectorError),
+    #[error("Migrate pool: tx builder error {0}")]
+    TxBuilder(TxBuilderError),
+    #[error("Migrate pool: sigma parsing error {0}")]
+    SigmaParse(SigmaParsingError),
+    #[error("Migrate pool: WalletData error {0}")]
+    WalletData(#[source] WalletDataError),
+    #[error("Migrate pool: node doesn't have a change address set")]
+    NoChangeAddressSetInNode,
+    #[error("Migrate pool: operator address {0} is not P2PK or is for another network")]
+    IncorrectOperatorAddress(String),
+    #[error("Migrate pool: no operator addresses in the migration file")]
+    NoOperators,
+    #[error("Migrate pool: {0} exists but the new pool wasn't bootstrapped by migrate-pool, move away the config of the old pool")]
+    ConfigFileExists(String),
+}
+
+/// File keeping the progress of the migration
+pub const MIGRATION_STATE_FILE_NAME: &str = "migration_state.yaml";
+
+/// Migration file given to the `migrate-pool` command
+#[derive(Debug, Clone, Deserialize)]
+pub struct MigrationConfig {
+    /// Bootstrap config file of the new pool
+    pub bootstrap_config_file: String,
+    /// Addresses of the operators of the old pool, each of them receives one oracle token and one
+    /// reward token of the new pool
+    pub operator_addresses: Vec<String>,
+}
+
+/// Progress of the migration, saved after each step
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MigrationState {
+    /// Step 1: the new pool is bootstrapped
+    pub bootstrapped: bool,
+    /// Step 2: the operator addresses that were sent their tokens
+    pub tokens_sent_to: Vec<String>,
+    /// Step 2: the ids of the token distribution transactions
+    pub token_distribution_tx_ids: Vec<String>,
+}
+
+impl MigrationState {
+    fn load() -> Result<Self, MigratePoolError> {
+        if !Path::new(MIGRATION_STATE_FILE_NAME).exists() {
+            return Ok(MigrationState::default());
+        }
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(
+            MIGRATION_STATE_FILE_NAME,
+        )?)?)
+    }
+
+    fn save(&self) -> Result<(), MigratePoolError> {
+        std::fs::write(MIGRATION_STATE_FILE_NAME, serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+pub fn migrate_pool(
+    migration_file_name: String,
+    prompt: &dyn Prompt,
+) -> Result<(), MigratePoolError> {
+    let config: MigrationConfig =
+        serde_yaml::from_str(&std::fs::read_to_string(migration_file_name)?)?;
+    if config.operator_addresses.is_empty() {
+        return Err(MigratePoolError::NoOperators);
+    }
+    let oracle_config_file_name = crate::oracle_config::CONFIG_FILE_PATH
+        .get()
+        .cloned()
+        .unwrap_or_else(|| crate::oracle_config::DEFAULT_CONFIG_FILE_NAME.to_string());
+
+    let mut state = MigrationState::load()?;
+
+    if !state.bootstrapped {
+        if Path::new(&oracle_config_file_name).exists() {
+            return Err(MigratePoolError::ConfigFileExists(oracle_config_file_name));
+        }
+        if !confirm_transaction(prompt, &format!(
+            "STEP 1/2: THE NEW POOL WILL BE BOOTSTRAPPED WITH {} (MINTING THE POOL TOKENS AND CREATING THE POOL, REFRESH AND UPDATE BOXES).",
+            config.bootstrap_config_file
+        ))? {
+            print_transaction_aborted();
+            return Ok(());
+        }
+        bootstrap(config.bootstrap_config_file, None)?;
+        state.bootstrapped = true;
+        state.save()?;
+        println!(
+            "Step 1/2 done, {} created. Run `migrate-pool` again after the bootstrap transactions are confirmed to distribute the tokens.",
+            oracle_config_file_name
+        );
+        return Ok(());
+    }
+    println!(
+        "Step 1/2 already done: the new pool is bootstrapped, its config is {}.",
+        oracle_config_file_name
+    );
+    distribute_tokens(&config.operator_addresses, &mut state, prompt)
+}
+
+/// Step 2: send one oracle token and one reward token to each operator not sent them yet.
+fn distribute_tokens(
+    operator_addresses: &[String],
+    state: &mut MigrationState,
+    prompt: &dyn Prompt,
+) -> Result<(), MigratePoolError> {
+    let network_prefix = ORACLE_CONFIG.oracle_address.network();
+    let own_address = ORACLE_CONFIG.oracle_address.address();
+    let mut operators = Vec::new();
+    for address_str in operator_addresses {
+        let address = AddressEncoder::unchecked_parse_network_address_from_str(address_str)?;
+        if address.network() != network_prefix || !matches!(address.address(), Address::P2Pk(_)) {
+            return Err(MigratePoolError::IncorrectOperatorAddress(
+                address_str.clone(),
+            ));
+        }
+        // The tokens minted on bootstrap are already in our wallet
+        if address.address() != own_address
+            && !state.tokens_sent_to.contains(address_str)
+            && !operators.iter().any(|(a, _)| a == address_str)
+        {
+            operators.push((address_str.clone(), address.address()));
+        }
+    }
+    if operators.is_empty() {
+        println!(
+            "Step 2/2 already done: the tokens were sent to all operators (txs: {}).",
+            state.token_distribution_tx_ids.join(", ")
+        );
+        return Ok(());
+    }
+    let change_address_str =
+        get_change_address()?.ok_or(MigratePoolError::NoChangeAddressSetInNode)?;
+    let change_address = AddressEncoder::unchecked_parse_address_from_str(&change_address_str)?;
+    let unsigned_tx = build_token_distribution_tx(
+        &WalletData::new(),
+        &ORACLE_CONFIG.token_ids,
+        &operators
+            .iter()
+            .map(|(_, address)| address.clone())
+            .collect::<Vec<_>>(),
+        current_block_height()? as u32,
+        change_address,
+    )?;
+    if confirm_transaction_preview(prompt, &unsigned_tx, network_prefix, &format!(
+        "STEP 2/2: ONE ORACLE TOKEN AND ONE REWARD TOKEN WILL BE SENT TO EACH OF THE {} OPERATOR(S) OF THE OLD POOL.",
+        operators.len()
+    ))? {
+        let tx_id = sign_and_submit_transaction(&unsigned_tx, "migrate_pool")?;
+        state
+            .tokens_sent_to
+            .extend(operators.into_iter().map(|(address_str, _)| address_str));
+        state.token_distribution_tx_ids.push(tx_id);
+        state.save()?;
+        print_transaction_submitted(&unsigned_tx, network_prefix);
+        println!(
+            "Migration done. Send the oracle config file (without `node_api_key` and `oracle_address`) to the operators so that they can join the new pool."
+        );
+    } else {
+        audit_log::record_declined("migrate_pool", &unsigned_tx);
+        print_transaction_aborted();
+    }
+    Ok(())
+}
+
+/// Builds a transaction with an output for each operator holding one oracle token and one reward
+/// token.
+fn build_token_distribution_tx(
+    wallet: &dyn WalletDataSource,
+    token_ids: &TokenIds,
+    operators: &[Address],
+    height: u32,
+    change_address: Address,
+) -> Result<UnsignedTransaction, MigratePoolError> {
+    let oracle_token = Token {
+        token_id: token_ids.oracle_token_id.clone(),
+        amount: 1u64.try_into().unwrap(),
+    };
+    let reward_token = Token {
+        token_id: token_ids.reward_token_id.clone(),
+        amount: 1u64.try_into().unwrap(),
+    };
+    let mut output_candidates = Vec::new();
+    for operator in operators {
+        let mut builder = ErgoBoxCandidateBuilder::new(*BASE_FEE, operator.script()?, height);
+        builder.add_token(oracle_token.clone());
+        builder.add_token(reward_token.clone());
+        output_candidates.push(builder.build()?);
+    }
+    let num_operators = operators.len() as u64;
+    let target_tokens = vec![
+        Token {
+            token_id: oracle_token.token_id.clone(),
+            amount: num_operators.try_into().unwrap(),
+        },
+        Token {
+            token_id: reward_token.token_id.clone(),
+            amount: num_operators.try_into().unwrap(),
+        },
+    ];
+    // `BASE_FEE` for each operator box and the fee
+    let tx_fee = tx_fee(TxFeeAction::MigratePool);
+    let target_balance = BASE_FEE
+        .checked_mul_u32(operators.len() as u32)?
+        .checked_add(&tx_fee)?;
+    let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
+    let box_selector = SimpleBoxSelector::new();
+    let selection = box_selector.select(unspent_boxes, target_balance, &target_tokens)?;
+    let tx_builder = TxBuilder::new(
+        selection,
+        output_candidates,
+        height,
+        tx_fee,
+        change_address.clone(),
+    );
+    Ok(apply_estimated_fee(
+        tx_builder.build()?,
+        TxFeeAction::MigratePool,
+        &change_address,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool_commands::test_utils::{
+        generate_token_ids, make_wallet_unspent_box, WalletDataMock,
+    };
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::ergo_box::BoxTokens;
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_build_token_distribution_tx() {
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet_mock = WalletDataMock {
+            unspent_boxes: vec![make_wallet_unspent_box(
+                secret.public_image(),
+                BASE_FEE.checked_mul_u32(10000).unwrap(),
+                Some(
+                    BoxTokens::from_vec(vec![
+                        Token {
+                            token_id: token_ids.oracle_token_id.clone(),
+                            amount: 10u64.try_into().unwrap(),
+                        },
+                        Token {
+                            token_id: token_ids.reward_token_id.clone(),
+                            amount: 100u64.try_into().unwrap(),
+                        },
+                    ])
+                    .unwrap(),
+                ),
+            )],
+        };
+        let operators: Vec<Address> = (0..3)
+            .map(|_| Address::P2Pk(force_any_val::<DlogProverInput>().public_image()))
+            .collect();
+        let tx = build_token_distribution_tx(
+            &wallet_mock,
+            &token_ids,
+            &operators,
+            100,
+            Address::P2Pk(secret.public_image()),
+        )
+        .unwrap();
+        for operator in &operators {
+            let operator_box = tx
+                .output_candidates
+                .iter()
+                .find(|b| b.ergo_tree == operator.script().unwrap())
+                .unwrap();
+            let tokens = operator_box.tokens.as_ref().unwrap();
+            assert_eq!(tokens.get(0).unwrap().token_id, token_ids.oracle_token_id);
+            assert_eq!(*tokens.get(0).unwrap().amount.as_u64(), 1);
+            assert_eq!(tokens.get(1).unwrap().token_id, token_ids.reward_token_id);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Report of the peer oracles saved by the `peer_monitor` of the running daemon.
+use std::path::Path;
+
+use crate::{
+    cli_commands::{json_output, print_json},
+    peer_monitor::{load_peer_report, PeerReportError, PEER_REPORT_FILE},
+};
+
+pub fn print_peer_report() -> Result<(), PeerReportError> {
+    let report = load_peer_report(Path::new(PEER_REPORT_FILE))?;
+    if json_output() {
+        print_json(&report);
+        return Ok(());
+    }
+    if report.is_empty() {
+        println!("No peer report, enable `peer_monitor` in the config of the daemon");
+        return Ok(());
+    }
+    println!(
+        "{:<52} {:>9} {:>10} {:>10} {:>10} {:>6}",
+        "oracle", "compared", "deviating", "last dev.", "stale", "flag"
+    );
+    for peer in &report {
+        println!(
+            "{:<52} {:>9} {:>10} {:>10} {:>10} {:>6}",
+            peer.oracle_address,
+            peer.compared_datapoints,
+            format!(
+                "{} ({})",
+                peer.deviating_datapoints, peer.consecutive_deviating
+            ),
+            peer.last_deviation_percent
+                .map_or("-".to_string(), |d| format!("{:.2}%", d)),
+            peer.consecutive_stale_epochs,
+            if peer.flagged { "yes" } else { "" }
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use crate::{
+    cli_commands::{json_output, print_json},
+    oracle_config::OracleConfig,
+    pool_archive::{
+        backfill, default_explorer_url, PoolArchive, PoolArchiveError, POOL_ARCHIVE_FILE,
+    },
+};
+
+/// Print the archived pool boxes (the latest `limit` ones if given). With `backfill` the pool boxes
+/// missing in the archive are fetched from the explorer first.
+pub fn pool_history(
+    config: &OracleConfig,
+    backfill_archive: bool,
+    explorer_url: Option<String>,
+    limit: Option<usize>,
+) -> Result<(), PoolArchiveError> {
+    let path = Path::new(POOL_ARCHIVE_FILE);
+    let pool_nft_token_id = &config.token_ids.pool_nft_token_id;
+    let archive = if backfill_archive {
+        let explorer_url = explorer_url
+            .unwrap_or_else(|| default_explorer_url(config.oracle_address.network()).to_string());
+        let (archive, added) = backfill(path, &explorer_url, pool_nft_token_id)?;
+        log::info!("Archived {} new pool boxes from {}", added, explorer_url);
+        archive
+    } else {
+        PoolArchive::load(path, pool_nft_token_id)?
+    };
+    let skip = limit.map_or(0, |limit| archive.pool_boxes.len().saturating_sub(limit));
+    let pool_boxes = &archive.pool_boxes[skip..];
+    if json_output() {
+        print_json(&pool_boxes);
+        return Ok(());
+    }
+    if archive.pool_boxes.is_empty() {
+        println!("The pool archive is empty, run with --backfill to fetch the pool boxes");
+        return Ok(());
+    }
+    println!("{:>10} {:>8} {:>20}", "height", "epoch", "rate");
+    for pool_box in pool_boxes {
+        println!(
+            "{:>10} {:>8} {:>20}",
+            pool_box.height, pool_box.epoch_counter, pool_box.rate
+        );
+    }
+    Ok(())
+}
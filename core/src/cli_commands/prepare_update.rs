@@ -1,6 +1,7 @@
 use std::{
     convert::{TryFrom, TryInto},
     io::Write,
+    path::Path,
 };
 
 use derive_more::From;
@@ -29,10 +30,12 @@ use ergo_lib::{
 use ergo_node_interface::node_interface::NodeError;
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
 use thiserror::Error;
 
 use crate::{
     box_kind::{PoolBoxWrapperInputs, RefreshBoxWrapperInputs, UpdateBoxWrapperInputs},
+    config_format::{ConfigFormat, ConfigFormatError},
     contracts::{
         pool::{PoolContractError, PoolContractParameters},
         refresh::{
@@ -42,8 +45,12 @@ use crate::{
             UpdateContract, UpdateContractError, UpdateContractInputs, UpdateContractParameters,
         },
     },
-    node_interface::{new_node_interface, SignTransaction, SubmitTransaction},
-    oracle_config::{OracleConfig, BASE_FEE, ORACLE_CONFIG},
+    node_interface::{get_change_address, new_node_interface, SignTransaction, SubmitTransaction},
+    oracle_config::{
+        tx_fee, OracleConfig, TxFeeAction, BASE_FEE, CONFIG_FILE_PATH, DEFAULT_CONFIG_FILE_NAME,
+        ORACLE_CONFIG,
+    },
+    pool_config::{merge_pool_config, PoolConfigError},
     serde::{OracleConfigSerde, SerdeConversionError, UpdateBootstrapConfigSerde},
     wallet::{WalletDataError, WalletDataSource},
 };
@@ -67,16 +74,16 @@ pub struct UpdateBootstrapConfig {
     pub tokens_to_mint: UpdateTokensToMint,
 }
 
+/// Config file with the new contracts/parameters created by the `prepare-update` command.
+pub const UPDATED_CONFIG_FILE_NAME: &str = "oracle_config_updated.yaml";
+
 pub fn prepare_update(config_file_name: String) -> Result<(), PrepareUpdateError> {
     let s = std::fs::read_to_string(config_file_name)?;
     let config_serde: UpdateBootstrapConfigSerde = serde_yaml::from_str(&s)?;
 
     let node_interface = new_node_interface();
     let change_address = AddressEncoder::unchecked_parse_address_from_str(
-        &node_interface
-            .wallet_status()?
-            .change_address
-            .ok_or(PrepareUpdateError::NoChangeAddressSetInNode)?,
+        &get_change_address()?.ok_or(PrepareUpdateError::NoChangeAddressSetInNode)?,
     )?;
     let config = UpdateBootstrapConfig::try_from(config_serde)?;
     let update_bootstrap_input = PrepareUpdateInput {
@@ -84,7 +91,7 @@ pub fn prepare_update(config_file_name: String) -> Result<(), PrepareUpdateError
         wallet: &node_interface,
         tx_signer: &node_interface,
         submit_tx: &node_interface,
-        tx_fee: *BASE_FEE,
+        tx_fee: tx_fee(TxFeeAction::PrepareUpdate),
         erg_value_per_box: *BASE_FEE,
         change_address,
         height: node_interface
@@ -98,15 +105,47 @@ pub fn prepare_update(config_file_name: String) -> Result<(), PrepareUpdateError
     let new_config = perform_update_chained_transaction(update_bootstrap_input)?;
 
     info!("Update chain-transaction complete");
-    info!("Writing new config file to oracle_config_updated.yaml");
-    let config = OracleConfigSerde::from(new_config);
+    info!("Writing new config file to {}", UPDATED_CONFIG_FILE_NAME);
+    let config_path = CONFIG_FILE_PATH
+        .get()
+        .map_or(DEFAULT_CONFIG_FILE_NAME, String::as_str);
+    let config = updated_config_value(Path::new(config_path), &ORACLE_CONFIG, new_config)?;
     let s = serde_yaml::to_string(&config)?;
-    let mut file = std::fs::File::create("oracle_config_updated.yaml")?;
+    let mut file = std::fs::File::create(UPDATED_CONFIG_FILE_NAME)?;
     file.write_all(s.as_bytes())?;
-    info!("Updated oracle configuration file oracle_config_updated.yaml");
+    info!(
+        "Updated oracle configuration file {}",
+        UPDATED_CONFIG_FILE_NAME
+    );
     Ok(())
 }
 
+/// The config file at `config_path` with its pool definition inlined and the keys changed by the
+/// update replaced. The other keys are kept as written in the file, with the references to their
+/// secrets (`encrypted:`, `keyring:`) and without the `ORACLE_CORE__*` environment variables
+/// merged in the loaded `old_config`.
+fn updated_config_value(
+    config_path: &Path,
+    old_config: &OracleConfig,
+    new_config: OracleConfig,
+) -> Result<Value, PrepareUpdateError> {
+    let mut config =
+        ConfigFormat::of_path(config_path).parse(&std::fs::read_to_string(config_path)?)?;
+    merge_pool_config(&mut config, config_path)?;
+    let old_value = serde_yaml::to_value(OracleConfigSerde::from(old_config.clone()))?;
+    let new_value = serde_yaml::to_value(OracleConfigSerde::from(new_config))?;
+    if let (Value::Mapping(mapping), Value::Mapping(old_mapping), Value::Mapping(new_mapping)) =
+        (&mut config, old_value, new_value)
+    {
+        for (key, value) in new_mapping {
+            if old_mapping.get(&key) != Some(&value) {
+                mapping.insert(key, value);
+            }
+        }
+    }
+    Ok(config)
+}
+
 pub struct PrepareUpdateInput<'a> {
     pub config: UpdateBootstrapConfig,
     pub wallet: &'a dyn WalletDataSource,
@@ -355,22 +394,22 @@ pub enum PrepareUpdateError {
     #[error("box builder error: {0}")]
     ErgoBoxCandidateBuilder(ErgoBoxCandidateBuilderError),
     #[error("node error: {0}")]
-    Node(NodeError),
+    Node(#[source] NodeError),
     #[error("box selector error: {0}")]
     BoxSelector(BoxSelectorError),
     #[error("box value error: {0}")]
     BoxValue(BoxValueError),
     #[error("IO error: {0}")]
-    Io(std::io::Error),
+    Io(#[source] std::io::Error),
     #[error("serde-yaml error: {0}")]
     SerdeYaml(serde_yaml::Error),
     #[error("yaml-rust error: {0}")]
     YamlRust(String),
     #[error("AddressEncoder error: {0}")]
-    AddressEncoder(AddressEncoderError),
+    AddressEncoder(#[source] AddressEncoderError),
     #[error("SigmaParsing error: {0}")]
     SigmaParse(SigmaParsingError),
-    #[error("Node doesn't have a change address set")]
+    #[error("Node doesn't have a change address set and change_address isn't set in the config")]
     NoChangeAddressSetInNode,
     #[error("Refresh contract failed: {0}")]
     RefreshContract(RefreshContractError),
@@ -387,7 +426,11 @@ pub enum PrepareUpdateError {
     #[error("Serde conversion error {0}")]
     SerdeConversion(SerdeConversionError),
     #[error("WalletData error: {0}")]
-    WalletData(WalletDataError),
+    WalletData(#[source] WalletDataError),
+    #[error("config error: {0}")]
+    ConfigFormat(ConfigFormatError),
+    #[error("pool config error: {0}")]
+    PoolConfig(PoolConfigError),
 }
 
 #[cfg(test)]
@@ -407,10 +450,7 @@ mod test {
     use crate::cli_commands::bootstrap::tests::SubmitTxMock;
     use crate::pool_commands::test_utils::{LocalTxSigner, WalletDataMock};
 
-    #[test]
-    fn test_prepare_update_transaction() {
-        let old_config: OracleConfig = serde_yaml::from_str(
-            "---
+    const OLD_CONFIG: &str = "---
 token_ids:
   pool_nft_token_id: FHF/kXzbGVH8N44x/8Cgp3i92xDWUJwgHLTtJVDvn4M=
   refresh_nft_token_id: L5ERlF2PBfXBJzJ0PmAbHegC/nQcOAZeamNy4TKclvo=
@@ -471,7 +511,11 @@ ballot_parameters:
     min_storage_rent: 10000000
     update_nft_index: 6
   vote_parameters: ~
-  ballot_token_owner_address: 3WzD3VNSK4RtDCZe8njzLzRnWbxcfpCneUcQncAVV9JBDE37nLxR").unwrap();
+  ballot_token_owner_address: 3WzD3VNSK4RtDCZe8njzLzRnWbxcfpCneUcQncAVV9JBDE37nLxR";
+
+    #[test]
+    fn test_prepare_update_transaction() {
+        let old_config: OracleConfig = serde_yaml::from_str(OLD_CONFIG).unwrap();
         let ctx = force_any_val::<ErgoStateContext>();
         let height = ctx.pre_header.height;
         let secret = force_any_val::<DlogProverInput>();
@@ -555,4 +599,30 @@ ballot_parameters:
 
         assert!(oracle_config.token_ids != old_config.token_ids);
     }
+
+    #[test]
+    fn test_updated_config_value() {
+        let dir = std::env::temp_dir().join("oracle_core_test_prepare_update");
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("oracle_config.yaml");
+        std::fs::write(
+            &config_path,
+            OLD_CONFIG.replace("node_api_key: hello", "node_api_key: keyring:node_api_key"),
+        )
+        .unwrap();
+        // The loaded config holds the resolved secret
+        let old_config: OracleConfig = serde_yaml::from_str(OLD_CONFIG).unwrap();
+        let new_config = OracleConfig {
+            rescan_height: 1000,
+            ..old_config.clone()
+        };
+        let config = updated_config_value(&config_path, &old_config, new_config).unwrap();
+        assert_eq!(config["node_api_key"], Value::from("keyring:node_api_key"));
+        assert_eq!(config["rescan_height"], Value::from(1000));
+        assert_eq!(
+            config["token_ids"],
+            serde_yaml::from_str::<Value>(OLD_CONFIG).unwrap()["token_ids"]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,105 @@
+//! P2S addresses of the contracts of the config, to cross-check them against the boxes of the
+//! deployed pool.
+use derive_more::From;
+use ergo_lib::ergotree_ir::{
+    chain::address::{Address, AddressEncoder},
+    ergo_tree::ErgoTree,
+    serialization::{SigmaSerializable, SigmaSerializationError},
+};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    cli_commands::{json_output, print_json, verify_contracts::template_hash},
+    contracts::{
+        ballot::{BallotContract, BallotContractError},
+        oracle::{OracleContract, OracleContractError},
+        pool::{PoolContract, PoolContractError},
+        refresh::{RefreshContract, RefreshContractError},
+        update::{UpdateContract, UpdateContractError},
+    },
+    oracle_config::OracleConfig,
+};
+
+#[derive(Debug, Error, From)]
+pub enum PrintContractAddressesError {
+    #[error("pool contract error: {0:?}")]
+    PoolContract(PoolContractError),
+    #[error("refresh contract error: {0:?}")]
+    RefreshContract(RefreshContractError),
+    #[error("oracle contract error: {0:?}")]
+    OracleContract(OracleContractError),
+    #[error("ballot contract error: {0:?}")]
+    BallotContract(BallotContractError),
+    #[error("update contract error: {0:?}")]
+    UpdateContract(UpdateContractError),
+    #[error("sigma serialization error: {0}")]
+    SigmaSerialization(SigmaSerializationError),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContractAddress {
+    pub contract: &'static str,
+    /// P2S address for the network of `oracle_address`
+    pub address: String,
+    /// Base64 encoded blake2b256 hash of the contract template
+    pub template_hash: String,
+}
+
+/// Instantiate each contract from the config and compute its address and template hash.
+pub fn get_contract_addresses(
+    config: &OracleConfig,
+) -> Result<Vec<ContractAddress>, PrintContractAddressesError> {
+    let contracts: Vec<(&'static str, ErgoTree)> = vec![
+        (
+            "pool",
+            PoolContract::checked_load(&config.pool_box_wrapper_inputs.contract_inputs)?
+                .ergo_tree(),
+        ),
+        (
+            "refresh",
+            RefreshContract::checked_load(&config.refresh_box_wrapper_inputs.contract_inputs)?
+                .ergo_tree(),
+        ),
+        (
+            "oracle",
+            OracleContract::checked_load(&config.oracle_box_wrapper_inputs.contract_inputs)?
+                .ergo_tree(),
+        ),
+        (
+            "ballot",
+            BallotContract::checked_load(&config.ballot_box_wrapper_inputs.contract_inputs)?
+                .ergo_tree(),
+        ),
+        (
+            "update",
+            UpdateContract::checked_load(&config.update_box_wrapper_inputs.contract_inputs)?
+                .ergo_tree(),
+        ),
+    ];
+    let encoder = AddressEncoder::new(config.oracle_address.network());
+    contracts
+        .into_iter()
+        .map(|(contract, ergo_tree)| {
+            let bytes = ergo_tree.sigma_serialize_bytes()?;
+            Ok(ContractAddress {
+                contract,
+                template_hash: template_hash(&bytes),
+                address: encoder.address_to_str(&Address::P2S(bytes)),
+            })
+        })
+        .collect()
+}
+
+pub fn print_contract_addresses(config: &OracleConfig) -> Result<(), PrintContractAddressesError> {
+    let addresses = get_contract_addresses(config)?;
+    if json_output() {
+        print_json(&addresses);
+    } else {
+        for a in &addresses {
+            println!("{} contract address: {}", a.contract, a.address);
+            println!("{} contract template hash: {}", a.contract, a.template_hash);
+        }
+    }
+    Ok(())
+}
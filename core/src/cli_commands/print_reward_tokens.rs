@@ -1,5 +1,8 @@
+use serde_json::json;
+
 use crate::{
     box_kind::OracleBox,
+    cli_commands::{json_output, print_json},
     oracle_state::{LocalDatapointBoxSource, StageError},
 };
 
@@ -8,11 +11,18 @@ pub fn print_reward_tokens(
 ) -> Result<(), StageError> {
     if let Some(oracle_box) = local_datapoint_box_source.get_local_oracle_datapoint_box()? {
         let num_tokens = *oracle_box.reward_token().amount.as_u64();
-        if num_tokens == 0 {
+        if json_output() {
+            print_json(&json!({
+                "reward_tokens": num_tokens,
+                "claimable_reward_tokens": num_tokens.saturating_sub(1),
+            }));
+        } else if num_tokens == 0 {
             println!("Oracle box contains zero reward tokens");
         } else {
             println!("Number of claimable reward tokens: {}", num_tokens - 1);
         }
+    } else if json_output() {
+        print_json(&json!({ "reward_tokens": null, "claimable_reward_tokens": null }));
     } else {
         println!("No datapoint box exists");
     }
@@ -0,0 +1,204 @@
+use serde::Serialize;
+
+use crate::{
+    box_kind::{OracleBox, OracleBoxWrapper, PoolBox},
+    cli_commands::{json_output, print_json},
+    fees::FeeStatus,
+    oracle_state::{LocalDatapointBoxSource, PendingTxs, PoolBoxSource, StageError},
+};
+
+/// State of the local oracle box and the pool epoch
+#[derive(Debug, Serialize)]
+pub struct OracleStatus {
+    pub oracle_box: Option<OracleBoxStatus>,
+    pub pool_epoch_counter: u32,
+    pub pool_rate: i64,
+    pub blocks_until_next_epoch: u32,
+    /// Unconfirmed txs of the oracle in the node mempool
+    pub pending_txs: PendingTxs,
+    /// Tx fees paid by the oracle wallet
+    pub fees: FeeStatus,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OracleBoxStatus {
+    pub box_id: String,
+    pub posted: bool,
+    pub datapoint: Option<u64>,
+    pub epoch_counter: Option<u32>,
+    pub reward_tokens: u64,
+    pub claimable_reward_tokens: u64,
+    pub box_value: u64,
+}
+
+pub fn print_status(
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    pool_box_source: &dyn PoolBoxSource,
+    pending_txs: PendingTxs,
+    fees: FeeStatus,
+    epoch_length: u32,
+    height: u32,
+) -> Result<(), StageError> {
+    let status = get_status(
+        local_datapoint_box_source,
+        pool_box_source,
+        pending_txs,
+        fees,
+        epoch_length,
+        height,
+    )?;
+    if json_output() {
+        print_json(&status);
+        return Ok(());
+    }
+    match &status.oracle_box {
+        Some(oracle_box) => {
+            println!("Oracle box id: {}", oracle_box.box_id);
+            if oracle_box.posted {
+                println!("State: posted");
+                println!("Datapoint: {}", oracle_box.datapoint.unwrap_or_default());
+                println!(
+                    "Epoch counter: {}",
+                    oracle_box.epoch_counter.unwrap_or_default()
+                );
+            } else {
+                println!("State: collected");
+            }
+            println!("Reward tokens: {}", oracle_box.reward_tokens);
+            println!(
+                "Claimable reward tokens: {}",
+                oracle_box.claimable_reward_tokens
+            );
+            println!("Box value: {} nanoErgs", oracle_box.box_value);
+        }
+        None => println!("No datapoint box exists"),
+    }
+    println!("Pool epoch counter: {}", status.pool_epoch_counter);
+    println!("Pool rate: {}", status.pool_rate);
+    println!(
+        "Blocks until next epoch: {}",
+        status.blocks_until_next_epoch
+    );
+    if let Some(tx_id) = &status.pending_txs.datapoint_tx_id {
+        println!("Pending datapoint tx: {}", tx_id);
+    }
+    if let Some(tx_id) = &status.pending_txs.refresh_tx_id {
+        println!("Pending refresh tx: {}", tx_id);
+    }
+    for (label, total) in [
+        ("Fees paid", status.fees.total),
+        ("Fees paid today (UTC)", status.fees.today),
+        ("Fees paid in the current epoch", status.fees.current_epoch),
+    ] {
+        println!(
+            "{}: {} nanoErgs in {} txs",
+            label, total.nanoergs, total.txs
+        );
+    }
+    Ok(())
+}
+
+pub fn get_status(
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    pool_box_source: &dyn PoolBoxSource,
+    pending_txs: PendingTxs,
+    fees: FeeStatus,
+    epoch_length: u32,
+    height: u32,
+) -> Result<OracleStatus, StageError> {
+    let oracle_box = local_datapoint_box_source
+        .get_local_oracle_datapoint_box()?
+        .map(|oracle_box| {
+            let reward_tokens = *oracle_box.reward_token().amount.as_u64();
+            let (datapoint, epoch_counter) = match &oracle_box {
+                OracleBoxWrapper::Posted(posted) => {
+                    (Some(posted.rate()), Some(posted.epoch_counter()))
+                }
+                OracleBoxWrapper::Collected(_) => (None, None),
+            };
+            OracleBoxStatus {
+                box_id: String::from(oracle_box.get_box().box_id()),
+                posted: matches!(oracle_box, OracleBoxWrapper::Posted(_)),
+                datapoint,
+                epoch_counter,
+                reward_tokens,
+                claimable_reward_tokens: reward_tokens.saturating_sub(1),
+                box_value: *oracle_box.get_box().value.as_u64(),
+            }
+        });
+    let pool_box = pool_box_source.get_pool_box()?;
+    let next_epoch_height = pool_box.get_box().creation_height + epoch_length;
+    Ok(OracleStatus {
+        oracle_box,
+        pool_epoch_counter: pool_box.epoch_counter(),
+        pool_rate: pool_box.rate(),
+        blocks_until_next_epoch: next_epoch_height.saturating_sub(height),
+        pending_txs,
+        fees,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::box_kind::OracleBoxWrapperInputs;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::contracts::pool::PoolContractParameters;
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_commands::test_utils::{
+        generate_token_ids, make_datapoint_box, make_pool_box, OracleBoxMock, PoolBoxMock,
+    };
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_get_status() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = ctx.pre_header.height;
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let oracle_box = OracleBoxWrapper::new(
+            make_datapoint_box(
+                *secret.public_image().h,
+                200,
+                3,
+                &token_ids,
+                BASE_FEE.checked_mul_u32(100).unwrap(),
+                height - 9,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        let pool_box = make_pool_box(
+            200,
+            3,
+            *BASE_FEE,
+            height - 10,
+            &PoolContractParameters::default(),
+            &token_ids,
+        );
+        let status = get_status(
+            &OracleBoxMock { oracle_box },
+            &PoolBoxMock { pool_box },
+            PendingTxs::default(),
+            FeeStatus::default(),
+            30,
+            height,
+        )
+        .unwrap();
+        let oracle_box_status = status.oracle_box.unwrap();
+        assert!(oracle_box_status.posted);
+        assert_eq!(oracle_box_status.datapoint, Some(200));
+        assert_eq!(oracle_box_status.epoch_counter, Some(3));
+        assert_eq!(oracle_box_status.reward_tokens, 100);
+        assert_eq!(oracle_box_status.claimable_reward_tokens, 99);
+        assert_eq!(status.pool_epoch_counter, 3);
+        assert_eq!(status.blocks_until_next_epoch, 20);
+    }
+}
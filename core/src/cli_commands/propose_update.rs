@@ -0,0 +1,305 @@
+//! Update proposal files, to be shared with the other operators of the pool so that they can vote
+//! for the update without hand-typing hashes.
+//!
+//! The proposal is signed with the key of the oracle address of the proposing operator, taken from
+//! the node wallet. The signature is checked whenever the file is loaded and the new pool box
+//! address hash is recomputed from the included contract parameters before voting.
+use derive_more::From;
+use ergo_lib::{
+    ergo_chain_types::{blake2b256_hash, Digest32},
+    ergotree_interpreter::sigma_protocol::{
+        private_input::DlogProverInput, verifier::verify_signature,
+    },
+    ergotree_ir::{
+        chain::{
+            address::{Address, AddressEncoder, NetworkAddress, NetworkPrefix},
+            token::TokenId,
+        },
+        serialization::SigmaSerializable,
+    },
+    wallet::{Wallet, WalletError},
+};
+use ergo_node_interface::node_interface::NodeError;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    box_kind::PoolBox,
+    cli_commands::{json_output, prepare_update::UPDATED_CONFIG_FILE_NAME, print_json},
+    contracts::pool::{
+        PoolContract, PoolContractError, PoolContractInputs, PoolContractParameters,
+    },
+    node_interface::get_wallet_secret_key,
+    oracle_config::{OracleConfig, ORACLE_CONFIG},
+    oracle_state::{PoolBoxSource, StageError, UpdateBoxSource},
+};
+
+#[derive(Debug, Error, From)]
+pub enum ProposeUpdateError {
+    #[error("Propose update: IO error {0}")]
+    Io(#[source] std::io::Error),
+    #[error("Propose update: yaml error {0}")]
+    Yaml(serde_yaml::Error),
+    #[error("Propose update: json error {0}")]
+    Json(serde_json::Error),
+    #[error("Propose update: stage error {0}")]
+    StageError(#[source] StageError),
+    #[error("Propose update: pool contract error {0}")]
+    PoolContract(PoolContractError),
+    #[error("Propose update: invalid reward token id {0}")]
+    InvalidRewardTokenId(String),
+    #[error("Propose update: file {0} already exists")]
+    FileAlreadyExists(String),
+    #[error("Propose update: node error {0}")]
+    Node(#[source] NodeError),
+    #[error("Propose update: signing error {0}")]
+    Signing(WalletError),
+    #[error("Propose update: proposer address {0} is not a P2PK address")]
+    InvalidProposerAddress(String),
+    #[error("Propose update: the signature doesn't match the proposal and its proposer address")]
+    InvalidSignature,
+}
+
+/// Everything needed to vote for (and independently verify) an update of the pool box contract
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    try_from = "crate::serde::UpdateProposalSerde",
+    into = "crate::serde::UpdateProposalSerde"
+)]
+pub struct UpdateProposal {
+    pub pool_contract_parameters: PoolContractParameters,
+    pub refresh_nft_token_id: TokenId,
+    pub update_nft_token_id: TokenId,
+    pub new_pool_box_address_hash: Digest32,
+    pub reward_token_id: TokenId,
+    pub reward_token_amount: u64,
+    pub update_box_creation_height: u32,
+}
+
+impl UpdateProposal {
+    /// Build the proposed pool contract from the proposal parameters.
+    pub fn pool_contract(&self) -> Result<PoolContract, PoolContractError> {
+        let inputs = PoolContractInputs::build_with(
+            self.pool_contract_parameters.clone(),
+            self.refresh_nft_token_id.clone(),
+            self.update_nft_token_id.clone(),
+        )?;
+        PoolContract::checked_load(&inputs)
+    }
+}
+
+/// Update proposal signed with the key of the operator who proposed it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedUpdateProposal {
+    pub proposal: UpdateProposal,
+    /// P2PK address of the operator who signed the proposal
+    pub proposer_address: String,
+    /// Signature of the JSON of `proposal` by the key of `proposer_address`, base16
+    pub signature: String,
+}
+
+impl SignedUpdateProposal {
+    pub fn sign(
+        proposal: UpdateProposal,
+        secret: DlogProverInput,
+        network_prefix: NetworkPrefix,
+    ) -> Result<Self, ProposeUpdateError> {
+        let public_key = secret.public_image();
+        let signature = Wallet::from_secrets(vec![secret.into()])
+            .sign_message(public_key.clone().into(), &serde_json::to_vec(&proposal)?)?;
+        Ok(SignedUpdateProposal {
+            proposal,
+            proposer_address: NetworkAddress::new(network_prefix, &Address::P2Pk(public_key))
+                .to_base58(),
+            signature: base16::encode_lower(&signature),
+        })
+    }
+
+    /// Load the proposal file, failing if the signature doesn't match the proposal and its proposer
+    pub fn load(file_name: &str) -> Result<Self, ProposeUpdateError> {
+        let signed: SignedUpdateProposal =
+            serde_json::from_str(&std::fs::read_to_string(file_name)?)?;
+        signed.verify()?;
+        Ok(signed)
+    }
+
+    fn verify(&self) -> Result<(), ProposeUpdateError> {
+        let public_key =
+            match AddressEncoder::unchecked_parse_network_address_from_str(&self.proposer_address)
+                .map(|a| a.address())
+            {
+                Ok(Address::P2Pk(public_key)) => public_key,
+                _ => {
+                    return Err(ProposeUpdateError::InvalidProposerAddress(
+                        self.proposer_address.clone(),
+                    ))
+                }
+            };
+        let signature =
+            base16::decode(&self.signature).map_err(|_| ProposeUpdateError::InvalidSignature)?;
+        match verify_signature(
+            public_key.into(),
+            &serde_json::to_vec(&self.proposal)?,
+            &signature,
+        ) {
+            Ok(true) => Ok(()),
+            _ => Err(ProposeUpdateError::InvalidSignature),
+        }
+    }
+}
+
+/// Sign `proposal` with the key of the oracle address of `config` (from the node wallet) and write
+/// it to `file_name`
+pub(crate) fn write_signed_proposal(
+    proposal: UpdateProposal,
+    config: &OracleConfig,
+    file_name: &str,
+) -> Result<SignedUpdateProposal, ProposeUpdateError> {
+    let secret = get_wallet_secret_key(&config.oracle_address.to_base58())?;
+    let signed = SignedUpdateProposal::sign(proposal, secret, config.oracle_address.network())?;
+    std::fs::write(file_name, serde_json::to_string_pretty(&signed)?)?;
+    Ok(signed)
+}
+
+/// Blake2b256 hash of the pool contract's ErgoTree bytes, as voted for in the ballot boxes.
+pub(crate) fn pool_box_address_hash(pool_contract: &PoolContract) -> Digest32 {
+    blake2b256_hash(&pool_contract.ergo_tree().sigma_serialize_bytes().unwrap())
+}
+
+pub fn propose_update(
+    pool_box_source: &dyn PoolBoxSource,
+    update_box_source: &dyn UpdateBoxSource,
+    reward_token_id: Option<String>,
+    reward_token_amount: Option<u64>,
+    proposal_file_name: String,
+) -> Result<(), ProposeUpdateError> {
+    if std::path::Path::new(&proposal_file_name).exists() {
+        return Err(ProposeUpdateError::FileAlreadyExists(proposal_file_name));
+    }
+    let new_oracle_config: OracleConfig =
+        serde_yaml::from_str(&std::fs::read_to_string(UPDATED_CONFIG_FILE_NAME)?)?;
+    let current_reward_token = pool_box_source.get_pool_box()?.reward_token();
+    let reward_token_id = match reward_token_id {
+        Some(id) => {
+            TokenId::from_base64(&id).map_err(|_| ProposeUpdateError::InvalidRewardTokenId(id))?
+        }
+        None => current_reward_token.token_id,
+    };
+    let reward_token_amount =
+        reward_token_amount.unwrap_or_else(|| *current_reward_token.amount.as_u64());
+    let update_box_creation_height = update_box_source
+        .get_update_box()?
+        .get_box()
+        .creation_height;
+    let proposal = build_update_proposal(
+        &new_oracle_config,
+        reward_token_id,
+        reward_token_amount,
+        update_box_creation_height,
+    )?;
+    let signed = write_signed_proposal(proposal, &ORACLE_CONFIG, &proposal_file_name)?;
+    if json_output() {
+        print_json(&serde_json::json!({
+            "status": "ok",
+            "proposal_file": proposal_file_name,
+            "new_pool_box_address_hash": String::from(signed.proposal.new_pool_box_address_hash),
+            "proposer_address": signed.proposer_address,
+        }));
+    } else {
+        println!(
+            "Update proposal signed by {} written to {}. New pool box address hash: {}",
+            signed.proposer_address,
+            proposal_file_name,
+            String::from(signed.proposal.new_pool_box_address_hash)
+        );
+        println!(
+            "Operators can vote with: oracle-core vote-update-pool --proposal-file {}",
+            proposal_file_name
+        );
+    }
+    Ok(())
+}
+
+pub(crate) fn build_update_proposal(
+    new_oracle_config: &OracleConfig,
+    reward_token_id: TokenId,
+    reward_token_amount: u64,
+    update_box_creation_height: u32,
+) -> Result<UpdateProposal, ProposeUpdateError> {
+    let contract_inputs = &new_oracle_config.pool_box_wrapper_inputs.contract_inputs;
+    let new_pool_contract = PoolContract::checked_load(contract_inputs)?;
+    Ok(UpdateProposal {
+        pool_contract_parameters: contract_inputs.contract_parameters().clone(),
+        refresh_nft_token_id: contract_inputs.refresh_nft_token_id.clone(),
+        update_nft_token_id: contract_inputs.update_nft_token_id.clone(),
+        new_pool_box_address_hash: pool_box_address_hash(&new_pool_contract),
+        reward_token_id,
+        reward_token_amount,
+        update_box_creation_height,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    #[test]
+    fn test_update_proposal_roundtrip() {
+        let token_ids = generate_token_ids();
+        let pool_contract_inputs = PoolContractInputs::build_with(
+            PoolContractParameters::default(),
+            token_ids.refresh_nft_token_id.clone(),
+            token_ids.update_nft_token_id.clone(),
+        )
+        .unwrap();
+        let pool_contract = PoolContract::checked_load(&pool_contract_inputs).unwrap();
+        let proposal = UpdateProposal {
+            pool_contract_parameters: PoolContractParameters::default(),
+            refresh_nft_token_id: token_ids.refresh_nft_token_id,
+            update_nft_token_id: token_ids.update_nft_token_id,
+            new_pool_box_address_hash: pool_box_address_hash(&pool_contract),
+            reward_token_id: token_ids.reward_token_id,
+            reward_token_amount: 1000,
+            update_box_creation_height: 100,
+        };
+        let json = serde_json::to_string_pretty(&proposal).unwrap();
+        let loaded: UpdateProposal = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            loaded.new_pool_box_address_hash,
+            proposal.new_pool_box_address_hash
+        );
+        assert_eq!(
+            pool_box_address_hash(&loaded.pool_contract().unwrap()),
+            proposal.new_pool_box_address_hash
+        );
+        assert_eq!(loaded.reward_token_amount, 1000);
+        assert_eq!(loaded.update_box_creation_height, 100);
+
+        let signed =
+            SignedUpdateProposal::sign(proposal, DlogProverInput::random(), NetworkPrefix::Mainnet)
+                .unwrap();
+        let loaded: SignedUpdateProposal =
+            serde_json::from_str(&serde_json::to_string_pretty(&signed).unwrap()).unwrap();
+        assert!(loaded.verify().is_ok());
+
+        let mut tampered = loaded.clone();
+        tampered.proposal.reward_token_amount = 2000;
+        assert!(matches!(
+            tampered.verify(),
+            Err(ProposeUpdateError::InvalidSignature)
+        ));
+        let mut other_proposer = loaded;
+        other_proposer.proposer_address = SignedUpdateProposal::sign(
+            other_proposer.proposal.clone(),
+            DlogProverInput::random(),
+            NetworkPrefix::Mainnet,
+        )
+        .unwrap()
+        .proposer_address;
+        assert!(matches!(
+            other_proposer.verify(),
+            Err(ProposeUpdateError::InvalidSignature)
+        ));
+    }
+}
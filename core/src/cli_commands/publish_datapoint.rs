@@ -0,0 +1,259 @@
+use derive_more::From;
+use ergo_lib::{
+    chain::transaction::unsigned::UnsignedTransaction,
+    ergotree_ir::chain::address::{Address, AddressEncoder, AddressEncoderError},
+};
+use ergo_node_interface::node_interface::NodeError;
+use thiserror::Error;
+
+use crate::{
+    audit_log,
+    box_kind::{OracleBoxWrapperInputs, PoolBox},
+    cli_commands::{
+        confirm_transaction_preview, print_transaction_aborted, print_transaction_submitted,
+    },
+    datapoint_source::ConstantDataPointSource,
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_state::{LocalDatapointBoxSource, PoolBoxSource, StageError},
+    pool_commands::publish_datapoint::{
+        build_publish_first_datapoint_action, build_subsequent_publish_datapoint_action,
+        PublishDatapointActionError,
+    },
+    prompt::Prompt,
+    wallet::WalletDataSource,
+};
+
+#[derive(Debug, Error, From)]
+pub enum PublishDatapointError {
+    #[error("Datapoint must be a positive number, got {0}")]
+    InvalidDatapoint(i64),
+    #[error("Oracle address not P2PK")]
+    IncorrectOracleAddress,
+    #[error("stage error: {0}")]
+    StageError(#[source] StageError),
+    #[error("publish datapoint action error: {0}")]
+    PublishDatapointAction(PublishDatapointActionError),
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
+    #[error("AddressEncoder error: {0}")]
+    AddressEncoder(#[source] AddressEncoderError),
+    #[error("Node doesn't have a change address set and change_address isn't set in the config")]
+    NoChangeAddressSetInNode,
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+}
+
+/// Publish the given datapoint bypassing the configured datapoint source. Meant for emergency
+/// corrections when the datapoint source is unavailable.
+pub fn publish_datapoint(
+    wallet: &dyn WalletDataSource,
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    pool_box_source: &dyn PoolBoxSource,
+    oracle_box_wrapper_inputs: OracleBoxWrapperInputs,
+    oracle_address: Address,
+    datapoint: i64,
+    prompt: &dyn Prompt,
+) -> Result<(), PublishDatapointError> {
+    let change_address_str =
+        get_change_address()?.ok_or(PublishDatapointError::NoChangeAddressSetInNode)?;
+    let (change_address, network_prefix) = {
+        let a = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
+        (a.address(), a.network())
+    };
+    let unsigned_tx = build_publish_datapoint_tx(
+        wallet,
+        local_datapoint_box_source,
+        pool_box_source,
+        oracle_box_wrapper_inputs,
+        oracle_address,
+        datapoint,
+        current_block_height()? as u32,
+        change_address,
+    )?;
+
+    if confirm_transaction_preview(
+        prompt,
+        &unsigned_tx,
+        network_prefix,
+        &format!(
+            "YOU WILL BE PUBLISHING DATAPOINT {}, BYPASSING THE DATAPOINT SOURCE.",
+            datapoint
+        ),
+    )? {
+        sign_and_submit_transaction(&unsigned_tx, "manual_publish_datapoint")?;
+        print_transaction_submitted(&unsigned_tx, network_prefix);
+    } else {
+        audit_log::record_declined("manual_publish_datapoint", &unsigned_tx);
+        print_transaction_aborted();
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_publish_datapoint_tx(
+    wallet: &dyn WalletDataSource,
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    pool_box_source: &dyn PoolBoxSource,
+    oracle_box_wrapper_inputs: OracleBoxWrapperInputs,
+    oracle_address: Address,
+    datapoint: i64,
+    height: u32,
+    change_address: Address,
+) -> Result<UnsignedTransaction, PublishDatapointError> {
+    if datapoint <= 0 {
+        return Err(PublishDatapointError::InvalidDatapoint(datapoint));
+    }
+    let datapoint_source = ConstantDataPointSource(datapoint);
+    let pool_box = pool_box_source.get_pool_box()?;
+    let action = if let Some(local_datapoint_box) =
+        local_datapoint_box_source.get_local_oracle_datapoint_box()?
+    {
+        build_subsequent_publish_datapoint_action(
+            &local_datapoint_box,
+            wallet,
+            height,
+            change_address,
+            &datapoint_source,
+            pool_box.epoch_counter(),
+            pool_box.reward_token().token_id,
+        )?
+    } else if let Address::P2Pk(public_key) = oracle_address {
+        let mut oracle_box_wrapper_inputs = oracle_box_wrapper_inputs;
+        oracle_box_wrapper_inputs.reward_token_id = pool_box.reward_token().token_id;
+        build_publish_first_datapoint_action(
+            wallet,
+            height,
+            change_address,
+            public_key,
+            oracle_box_wrapper_inputs,
+            &datapoint_source,
+        )?
+    } else {
+        return Err(PublishDatapointError::IncorrectOracleAddress);
+    };
+    Ok(action.tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::box_kind::{OracleBox, OracleBoxWrapper};
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::contracts::pool::PoolContractParameters;
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_commands::test_utils::{
+        find_input_boxes, generate_token_ids, make_datapoint_box, make_pool_box,
+        make_wallet_unspent_box, OracleBoxMock, PoolBoxMock, WalletDataMock,
+    };
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+    use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisterId;
+    use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
+    use ergo_lib::wallet::signing::TransactionContext;
+    use ergo_lib::wallet::Wallet;
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_publish_manual_datapoint() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = ctx.pre_header.height;
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let oracle_box = OracleBoxWrapper::new(
+            make_datapoint_box(
+                *secret.public_image().h,
+                200,
+                1,
+                &token_ids,
+                BASE_FEE.checked_mul_u32(100).unwrap(),
+                height - 9,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        let pool_box_mock = PoolBoxMock {
+            pool_box: make_pool_box(
+                200,
+                2,
+                *BASE_FEE,
+                height - 5,
+                &PoolContractParameters::default(),
+                &token_ids,
+            ),
+        };
+        let local_datapoint_box_source = OracleBoxMock { oracle_box };
+        let change_address = AddressEncoder::new(NetworkPrefix::Mainnet)
+            .parse_address_from_str("9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r")
+            .unwrap();
+        let wallet_mock = WalletDataMock {
+            unspent_boxes: vec![make_wallet_unspent_box(
+                secret.public_image(),
+                BASE_FEE.checked_mul_u32(10000).unwrap(),
+                None,
+            )],
+        };
+
+        assert!(matches!(
+            build_publish_datapoint_tx(
+                &wallet_mock,
+                &local_datapoint_box_source,
+                &pool_box_mock,
+                oracle_box_wrapper_inputs.clone(),
+                Address::P2Pk(secret.public_image()),
+                0,
+                height,
+                change_address.clone(),
+            ),
+            Err(PublishDatapointError::InvalidDatapoint(0))
+        ));
+
+        let tx = build_publish_datapoint_tx(
+            &wallet_mock,
+            &local_datapoint_box_source,
+            &pool_box_mock,
+            oracle_box_wrapper_inputs,
+            Address::P2Pk(secret.public_image()),
+            12345,
+            height,
+            change_address,
+        )
+        .unwrap();
+        let out_oracle_box = tx.output_candidates.first();
+        assert_eq!(
+            out_oracle_box
+                .additional_registers
+                .get(NonMandatoryRegisterId::R6)
+                .unwrap()
+                .clone()
+                .try_extract_into::<i64>()
+                .unwrap(),
+            12345
+        );
+
+        let mut possible_input_boxes = vec![local_datapoint_box_source
+            .get_local_oracle_datapoint_box()
+            .unwrap()
+            .unwrap()
+            .get_box()
+            .clone()];
+        possible_input_boxes.append(&mut wallet_mock.get_unspent_wallet_boxes().unwrap());
+        let tx_context = TransactionContext::new(
+            tx.clone(),
+            find_input_boxes(tx, possible_input_boxes),
+            Vec::new(),
+        )
+        .unwrap();
+        let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
+    }
+}
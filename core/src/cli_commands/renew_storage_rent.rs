@@ -0,0 +1,214 @@
+//! Renewal of the protocol boxes before their storage rent can be collected, see
+//! [`crate::storage_rent`]. The pool and refresh boxes are renewed by the next refresh, the update
+//! box by an update of the pool to its current contract. The ballot holders vote for this update
+//! like for any other, with the proposal file written when the votes are missing.
+use std::convert::TryInto;
+use std::path::Path;
+
+use derive_more::From;
+use ergo_lib::{
+    chain::transaction::unsigned::UnsignedTransaction,
+    ergotree_ir::chain::{
+        address::{Address, AddressEncoder, AddressEncoderError},
+        token::Token,
+    },
+};
+use ergo_node_interface::node_interface::NodeError;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    audit_log,
+    box_kind::PoolBox,
+    cli_commands::{
+        confirm_transaction_preview, json_output, print_json, print_transaction_aborted,
+        print_transaction_submitted,
+        propose_update::{build_update_proposal, write_signed_proposal, ProposeUpdateError},
+        update_pool::{build_update_pool_box_tx, UpdatePoolError},
+    },
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_config::{OracleConfig, ORACLE_CONFIG},
+    oracle_state::{OraclePool, StageError},
+    prompt::Prompt,
+    storage_rent::{protocol_boxes_rent, renewal_window, ProtocolBox, RentStatus},
+    wallet::WalletData,
+};
+
+#[derive(Debug, Error, From)]
+pub enum RenewStorageRentError {
+    #[error("Renew storage rent: stage error {0}")]
+    StageError(#[source] StageError),
+    #[error("Renew storage rent: node error {0}")]
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
+    #[error("Renew storage rent: {0}")]
+    UpdatePool(UpdatePoolError),
+    #[error("Renew storage rent: {0}")]
+    ProposeUpdate(ProposeUpdateError),
+    #[error("Renew storage rent: address encoder error {0}")]
+    AddressEncoder(#[source] AddressEncoderError),
+    #[error("Renew storage rent: IO error {0}")]
+    Io(#[source] std::io::Error),
+    #[error("Renew storage rent: invalid reward token amount {0}")]
+    #[from(ignore)]
+    InvalidRewardTokenAmount(u64),
+    #[error("No change address in node")]
+    NoChangeAddressSetInNode,
+}
+
+#[derive(Debug, Serialize)]
+struct StorageRentOutput<'a> {
+    height: u32,
+    renewal_window_blocks: u32,
+    boxes: &'a [RentStatus],
+}
+
+/// Update of the pool to its current contract, re-creating the update box at `height`. The pool
+/// box keeps its reward tokens unless `reward_token_amount` is given.
+pub fn build_update_box_renewal_tx(
+    op: &OraclePool,
+    height: u32,
+    change_address: Address,
+    reward_token_amount: Option<u64>,
+) -> Result<UnsignedTransaction, RenewStorageRentError> {
+    let pool_box = op.get_pool_box_source().get_pool_box()?;
+    let reward_tokens = match reward_token_amount {
+        Some(amount) => Some(Token {
+            token_id: pool_box.reward_token().token_id,
+            amount: amount
+                .try_into()
+                .map_err(|_| RenewStorageRentError::InvalidRewardTokenAmount(amount))?,
+        }),
+        None => None,
+    };
+    let wallet = WalletData {};
+    Ok(build_update_pool_box_tx(
+        op.get_pool_box_source(),
+        op.get_ballot_boxes_source(),
+        &wallet,
+        op.get_update_box_source(),
+        pool_box.contract().clone(),
+        reward_tokens,
+        height,
+        change_address,
+    )?
+    .spending_tx)
+}
+
+pub fn renew_storage_rent(
+    op: &OraclePool,
+    force: bool,
+    reward_token_amount: Option<u64>,
+    proposal_file: String,
+    prompt: &dyn Prompt,
+) -> Result<(), RenewStorageRentError> {
+    let height = current_block_height()? as u32;
+    let window = renewal_window(ORACLE_CONFIG.storage_rent.as_ref());
+    let statuses = protocol_boxes_rent(op, height)?;
+    if json_output() {
+        print_json(&StorageRentOutput {
+            height,
+            renewal_window_blocks: window,
+            boxes: &statuses,
+        });
+    } else {
+        print_rent_statuses(&statuses, window);
+    }
+    let update_box_due = statuses
+        .iter()
+        .any(|s| s.protocol_box == ProtocolBox::Update && s.due(window));
+    if !update_box_due && !force {
+        if !json_output() {
+            println!("The update box isn't due for renewal, use --force to renew it anyway");
+        }
+        return Ok(());
+    }
+
+    let change_address_str =
+        get_change_address()?.ok_or(RenewStorageRentError::NoChangeAddressSetInNode)?;
+    let (change_address, network_prefix) = {
+        let a = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
+        (a.address(), a.network())
+    };
+    let tx = match build_update_box_renewal_tx(op, height, change_address, reward_token_amount) {
+        Ok(tx) => tx,
+        Err(RenewStorageRentError::UpdatePool(UpdatePoolError::NotEnoughVotes(
+            expected,
+            found,
+        ))) => {
+            if Path::new(&proposal_file).exists() {
+                eprintln!(
+                    "Proposal file {} already exists, left unchanged",
+                    proposal_file
+                );
+            } else {
+                write_renewal_proposal(op, &ORACLE_CONFIG, reward_token_amount, &proposal_file)?;
+            }
+            eprintln!(
+                "Not enough votes to renew the update box: {} of {} needed. The ballot holders can \
+                 vote for the renewal with: oracle-core vote-update-pool --proposal-file {}",
+                found, expected, proposal_file
+            );
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    if confirm_transaction_preview(
+        prompt,
+        &tx,
+        network_prefix,
+        "YOU WILL BE RE-CREATING THE UPDATE BOX WITH AN UPDATE OF THE POOL TO ITS CURRENT CONTRACT.",
+    )? {
+        sign_and_submit_transaction(&tx, "renew_storage_rent")?;
+        print_transaction_submitted(&tx, network_prefix);
+    } else {
+        audit_log::record_declined("renew_storage_rent", &tx);
+        print_transaction_aborted();
+    }
+    Ok(())
+}
+
+fn print_rent_statuses(statuses: &[RentStatus], window: u32) {
+    for status in statuses {
+        if status.due(window) {
+            println!("{}", status.message());
+            if status.protocol_box != ProtocolBox::Update {
+                println!("  It is renewed by the next refresh of the pool");
+            }
+        } else {
+            println!(
+                "{} {}: storage rent due at height {} ({} blocks left)",
+                status.protocol_box.name(),
+                status.box_id,
+                status.rent_height,
+                status.blocks_left
+            );
+        }
+    }
+}
+
+/// Proposal of the update of the pool to its current contract, for the ballot holders to vote for
+fn write_renewal_proposal(
+    op: &OraclePool,
+    config: &OracleConfig,
+    reward_token_amount: Option<u64>,
+    proposal_file: &str,
+) -> Result<(), RenewStorageRentError> {
+    let reward_token = op.get_pool_box_source().get_pool_box()?.reward_token();
+    let update_box_creation_height = op
+        .get_update_box_source()
+        .get_update_box()?
+        .get_box()
+        .creation_height;
+    let proposal = build_update_proposal(
+        config,
+        reward_token.token_id,
+        reward_token_amount.unwrap_or_else(|| *reward_token.amount.as_u64()),
+        update_box_creation_height,
+    )?;
+    write_signed_proposal(proposal, config, proposal_file)?;
+    Ok(())
+}
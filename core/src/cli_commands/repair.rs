@@ -0,0 +1,289 @@
+//! Repair of duplicate local oracle boxes. When more than one box holds the local oracle token
+//! (e.g. after a manual tx went wrong) the daemon refuses to act, since acting on one of the boxes
+//! can leave the oracle stuck. The repair spends all of them into a single collected oracle box
+//! holding one oracle token and all the reward tokens, the daemon then posts a fresh datapoint.
+//!
+//! Every oracle box input points `outIndex` to the repaired box, which is a valid copy for each of
+//! them. The surplus oracle tokens are sent to the change address.
+use std::convert::TryInto;
+
+use derive_more::From;
+use ergo_lib::{
+    chain::{
+        ergo_box::box_builder::ErgoBoxCandidateBuilderError,
+        transaction::unsigned::UnsignedTransaction,
+    },
+    ergotree_interpreter::sigma_protocol::prover::ContextExtension,
+    ergotree_ir::chain::{
+        address::{Address, AddressEncoder, AddressEncoderError},
+        token::Token,
+    },
+    wallet::{
+        box_selector::{BoxSelection, BoxSelector, BoxSelectorError, SimpleBoxSelector},
+        tx_builder::{TxBuilder, TxBuilderError},
+    },
+};
+use ergo_node_interface::node_interface::NodeError;
+use thiserror::Error;
+
+use crate::{
+    audit_log,
+    box_kind::{make_collected_oracle_box_candidate, OracleBox, OracleBoxWrapper},
+    cli_commands::{
+        confirm_transaction_preview, print_transaction_aborted, print_transaction_submitted,
+    },
+    fee_estimation::apply_estimated_fee,
+    min_box_value::{check_min_box_values, MinBoxValueError},
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_config::{tx_fee, TxFeeAction, BASE_FEE},
+    oracle_state::{LocalDatapointBoxSource, StageError},
+    prompt::Prompt,
+    wallet::{WalletDataError, WalletDataSource},
+};
+
+#[derive(Debug, Error, From)]
+pub enum RepairActionError {
+    #[error("box builder error: {0}")]
+    ErgoBoxCandidateBuilder(ErgoBoxCandidateBuilderError),
+    #[error("stage error: {0}")]
+    StageError(#[source] StageError),
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
+    #[error("box selector error: {0}")]
+    BoxSelector(BoxSelectorError),
+    #[error("tx builder error: {0}")]
+    TxBuilder(TxBuilderError),
+    #[error("Node doesn't have a change address set and change_address isn't set in the config")]
+    NoChangeAddressSetInNode,
+    #[error("AddressEncoder error: {0}")]
+    AddressEncoder(#[source] AddressEncoderError),
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("WalletData error: {0}")]
+    WalletData(#[source] WalletDataError),
+    #[error("{0} box(es) hold the local oracle token, nothing to repair")]
+    NothingToRepair(usize),
+    #[error("oracle box {0} holds more than one oracle token, it must be repaired manually")]
+    MultipleOracleTokensInBox(String),
+    #[error("{0}")]
+    MinBoxValue(MinBoxValueError),
+}
+
+pub fn repair(
+    wallet: &dyn WalletDataSource,
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    prompt: &dyn Prompt,
+) -> Result<(), RepairActionError> {
+    let oracle_boxes = local_datapoint_box_source.get_local_oracle_datapoint_boxes()?;
+    if oracle_boxes.len() < 2 {
+        println!(
+            "{} box(es) hold the local oracle token, nothing to repair",
+            oracle_boxes.len()
+        );
+        return Ok(());
+    }
+    let change_address_str =
+        get_change_address()?.ok_or(RepairActionError::NoChangeAddressSetInNode)?;
+    let (change_address, network_prefix) = {
+        let a = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
+        (a.address(), a.network())
+    };
+    let unsigned_tx = build_repair_tx(
+        oracle_boxes.clone(),
+        wallet,
+        current_block_height()? as u32,
+        change_address,
+    )?;
+
+    for oracle_box in &oracle_boxes {
+        println!(
+            "Oracle box {} (height {}, {} reward tokens)",
+            String::from(oracle_box.get_box().box_id()),
+            oracle_box.get_box().creation_height,
+            oracle_box.reward_token().amount.as_u64()
+        );
+    }
+    if confirm_transaction_preview(prompt, &unsigned_tx, network_prefix, &format!(
+        "YOU WILL BE CONSOLIDATING {} ORACLE BOXES INTO ONE, THE {} SURPLUS ORACLE TOKEN(S) WILL BE SENT TO {}.",
+        oracle_boxes.len(),
+        oracle_boxes.len() - 1,
+        change_address_str
+    ))? {
+        sign_and_submit_transaction(&unsigned_tx, "repair_oracle_boxes")?;
+        print_transaction_submitted(&unsigned_tx, network_prefix);
+    } else {
+        audit_log::record_declined("repair_oracle_boxes", &unsigned_tx);
+        print_transaction_aborted();
+    }
+    Ok(())
+}
+
+/// Builds a transaction spending all of `oracle_boxes` into a single collected oracle box, which
+/// keeps the value of the latest box and holds the reward tokens of all of them.
+pub(crate) fn build_repair_tx(
+    mut oracle_boxes: Vec<OracleBoxWrapper>,
+    wallet: &dyn WalletDataSource,
+    height: u32,
+    change_address: Address,
+) -> Result<UnsignedTransaction, RepairActionError> {
+    if oracle_boxes.len() < 2 {
+        return Err(RepairActionError::NothingToRepair(oracle_boxes.len()));
+    }
+    // The oracle contract requires the copy to hold the same oracle token amount as the input
+    if let Some(oracle_box) = oracle_boxes
+        .iter()
+        .find(|b| *b.oracle_token().amount.as_u64() != 1)
+    {
+        return Err(RepairActionError::MultipleOracleTokensInBox(String::from(
+            oracle_box.get_box().box_id(),
+        )));
+    }
+    oracle_boxes.sort_by_key(|b| b.get_box().creation_height);
+    let latest_box = oracle_boxes.last().unwrap();
+    let num_reward_tokens: u64 = oracle_boxes
+        .iter()
+        .map(|b| *b.reward_token().amount.as_u64())
+        .sum();
+    let oracle_box_candidate = make_collected_oracle_box_candidate(
+        latest_box.contract(),
+        latest_box.public_key(),
+        latest_box.oracle_token(),
+        Token {
+            token_id: latest_box.reward_token().token_id.clone(),
+            amount: num_reward_tokens.try_into().unwrap(),
+        },
+        latest_box.get_box().value,
+        height,
+    )?;
+
+    let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
+    // The fee and `BASE_FEE` for the change box holding the surplus oracle tokens.
+    let tx_fee = tx_fee(TxFeeAction::Repair);
+    let target_balance = tx_fee.checked_add(&BASE_FEE).unwrap();
+    let box_selector = SimpleBoxSelector::new();
+    let selection = box_selector.select(unspent_boxes, target_balance, &[])?;
+    let mut input_boxes: Vec<_> = oracle_boxes.iter().map(|b| b.get_box().clone()).collect();
+    input_boxes.append(selection.boxes.as_vec().clone().as_mut());
+    let box_selection = BoxSelection {
+        boxes: input_boxes.try_into().unwrap(),
+        change_boxes: selection.change_boxes,
+    };
+    let output_candidates = vec![oracle_box_candidate];
+    check_min_box_values(&output_candidates)?;
+    let mut tx_builder = TxBuilder::new(
+        box_selection,
+        output_candidates,
+        height,
+        tx_fee,
+        change_address.clone(),
+    );
+    // The following context value ensures that `outIndex` in the oracle contract is properly set.
+    for oracle_box in &oracle_boxes {
+        let ctx_ext = ContextExtension {
+            values: vec![(0, 0i32.into())].into_iter().collect(),
+        };
+        tx_builder.set_context_extension(oracle_box.get_box().box_id(), ctx_ext);
+    }
+    Ok(apply_estimated_fee(
+        tx_builder.build()?,
+        TxFeeAction::Repair,
+        &change_address,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::box_kind::OracleBoxWrapperInputs;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::pool_commands::test_utils::{
+        find_input_boxes, generate_token_ids, make_datapoint_box, make_wallet_unspent_box,
+        WalletDataMock,
+    };
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+    use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+    use ergo_lib::wallet::signing::TransactionContext;
+    use ergo_lib::wallet::Wallet;
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_repair_duplicate_oracle_boxes() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = ctx.pre_header.height;
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let oracle_boxes: Vec<OracleBoxWrapper> = vec![height - 20, height - 9]
+            .into_iter()
+            .map(|creation_height| {
+                OracleBoxWrapper::new(
+                    make_datapoint_box(
+                        *secret.public_image().h,
+                        200,
+                        1,
+                        &token_ids,
+                        BASE_FEE.checked_mul_u32(100).unwrap(),
+                        creation_height,
+                    ),
+                    &oracle_box_wrapper_inputs,
+                )
+                .unwrap()
+            })
+            .collect();
+        let wallet_mock = WalletDataMock {
+            unspent_boxes: vec![make_wallet_unspent_box(
+                secret.public_image(),
+                BASE_FEE.checked_mul_u32(10000).unwrap(),
+                None,
+            )],
+        };
+        let change_address = AddressEncoder::new(NetworkPrefix::Mainnet)
+            .parse_address_from_str("9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r")
+            .unwrap();
+
+        assert!(matches!(
+            build_repair_tx(
+                oracle_boxes[..1].to_vec(),
+                &wallet_mock,
+                height,
+                change_address.clone()
+            ),
+            Err(RepairActionError::NothingToRepair(1))
+        ));
+        let tx =
+            build_repair_tx(oracle_boxes.clone(), &wallet_mock, height, change_address).unwrap();
+        let out_oracle_box = OracleBoxWrapper::new(
+            ErgoBox::from_box_candidate(tx.output_candidates.first(), force_any_val::<TxId>(), 0)
+                .unwrap(),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        assert!(matches!(out_oracle_box, OracleBoxWrapper::Collected(_)));
+        assert_eq!(*out_oracle_box.oracle_token().amount.as_u64(), 1);
+        assert_eq!(*out_oracle_box.reward_token().amount.as_u64(), 200);
+
+        let mut possible_input_boxes: Vec<ErgoBox> =
+            oracle_boxes.iter().map(|b| b.get_box().clone()).collect();
+        possible_input_boxes.append(&mut wallet_mock.get_unspent_wallet_boxes().unwrap());
+        let tx_context = TransactionContext::new(
+            tx.clone(),
+            find_input_boxes(tx, possible_input_boxes),
+            Vec::new(),
+        )
+        .unwrap();
+        let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+//! Resynchronize the node wallet scans, e.g. after restoring the node from a backup. The rescan
+//! is started at the given height, and once the wallet caught up with the chain the scans are
+//! polled until the pool, refresh and local oracle boxes are found again.
+
+use std::time::Duration;
+
+use derive_more::From;
+use ergo_node_interface::node_interface::NodeError;
+use thiserror::Error;
+
+use crate::{
+    node_interface::{current_block_height, get_wallet_status, rescan_from_height},
+    oracle_state::{LocalDatapointBoxSource, PoolBoxSource, RefreshBoxSource, StageError},
+    scans::clear_scan_cache,
+};
+
+/// How many times the scans are queried for the boxes after the wallet caught up
+const MAX_BOX_POLLS: u32 = 30;
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Error, From)]
+pub enum RescanError {
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("stage error: {0}")]
+    StageError(#[source] StageError),
+    #[error("requested rescan height {from_height} is above the current height {height}")]
+    HeightAboveChain { from_height: u32, height: u32 },
+    #[error("boxes not found after the rescan: {}. Try an earlier --from-height", .0.join(", "))]
+    BoxesNotFound(Vec<&'static str>),
+}
+
+/// Boxes of the pool found by the scans
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FoundBoxes {
+    pub pool_box: bool,
+    pub refresh_box: bool,
+    pub oracle_box: bool,
+}
+
+impl FoundBoxes {
+    /// Boxes which must be found for the oracle to operate
+    fn missing_required(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if !self.pool_box {
+            missing.push("pool box");
+        }
+        if !self.refresh_box {
+            missing.push("refresh box");
+        }
+        missing
+    }
+}
+
+pub fn rescan(
+    pool_box_source: &dyn PoolBoxSource,
+    refresh_box_source: &dyn RefreshBoxSource,
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    from_height: u32,
+) -> Result<(), RescanError> {
+    let height = current_block_height()? as u32;
+    if from_height > height {
+        return Err(RescanError::HeightAboveChain {
+            from_height,
+            height,
+        });
+    }
+    rescan_from_height(from_height)?;
+    println!("Rescanning from height {}", from_height);
+    // The node resets the wallet height asynchronously, give it a moment before checking progress
+    std::thread::sleep(POLL_INTERVAL);
+    loop {
+        let wallet_height = get_wallet_status()?.height;
+        let block_height = current_block_height()?;
+        if wallet_height == block_height {
+            break;
+        }
+        println!("Scanned {}/{} blocks", wallet_height, block_height);
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    let mut found = FoundBoxes::default();
+    for attempt in 1..=MAX_BOX_POLLS {
+        // The scan boxes are cached per height, the rescan doesn't change the height
+        clear_scan_cache();
+        found = find_boxes(
+            pool_box_source,
+            refresh_box_source,
+            local_datapoint_box_source,
+        )?;
+        if found.pool_box && found.refresh_box && found.oracle_box {
+            break;
+        }
+        println!(
+            "Waiting for the scans ({}/{}): pool box {}, refresh box {}, oracle box {}",
+            attempt,
+            MAX_BOX_POLLS,
+            found_str(found.pool_box),
+            found_str(found.refresh_box),
+            found_str(found.oracle_box),
+        );
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    let missing = found.missing_required();
+    if !missing.is_empty() {
+        return Err(RescanError::BoxesNotFound(missing));
+    }
+    if !found.oracle_box {
+        // A new oracle has not posted a datapoint yet
+        println!("Note: no local oracle box found, expected if no datapoint was posted yet");
+    }
+    println!("Rescan complete, pool and refresh boxes found");
+    Ok(())
+}
+
+/// Query the scans for the boxes, a box the scan doesn't report is not an error
+pub fn find_boxes(
+    pool_box_source: &dyn PoolBoxSource,
+    refresh_box_source: &dyn RefreshBoxSource,
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+) -> Result<FoundBoxes, StageError> {
+    let pool_box = match pool_box_source.get_pool_box() {
+        Ok(_) => true,
+        Err(StageError::PoolBoxNotFoundError) => false,
+        Err(e) => return Err(e),
+    };
+    let refresh_box = match refresh_box_source.get_refresh_box() {
+        Ok(_) => true,
+        Err(StageError::RefreshBoxNotFoundError) => false,
+        Err(e) => return Err(e),
+    };
+    let oracle_box = local_datapoint_box_source
+        .get_local_oracle_datapoint_box()?
+        .is_some();
+    Ok(FoundBoxes {
+        pool_box,
+        refresh_box,
+        oracle_box,
+    })
+}
+
+fn found_str(found: bool) -> &'static str {
+    if found {
+        "found"
+    } else {
+        "missing"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::box_kind::{OracleBoxWrapper, RefreshBoxWrapper};
+    use crate::contracts::pool::PoolContractParameters;
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_commands::test_utils::{generate_token_ids, make_pool_box, PoolBoxMock};
+
+    struct NoRefreshBox {}
+
+    impl RefreshBoxSource for NoRefreshBox {
+        fn get_refresh_box(&self) -> Result<RefreshBoxWrapper, StageError> {
+            Err(StageError::RefreshBoxNotFoundError)
+        }
+    }
+
+    struct NoOracleBox {}
+
+    impl LocalDatapointBoxSource for NoOracleBox {
+        fn get_local_oracle_datapoint_box(&self) -> Result<Option<OracleBoxWrapper>, StageError> {
+            Ok(None)
+        }
+
+        fn get_local_oracle_datapoint_boxes(&self) -> Result<Vec<OracleBoxWrapper>, StageError> {
+            Ok(Vec::new())
+        }
+
+        fn get_spent_local_oracle_datapoint_boxes(
+            &self,
+        ) -> Result<Vec<OracleBoxWrapper>, StageError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_find_boxes_reports_missing_boxes() {
+        let height = force_any_val::<ErgoStateContext>().pre_header.height;
+        let token_ids = generate_token_ids();
+        let pool_box = make_pool_box(
+            200,
+            1,
+            *BASE_FEE,
+            height,
+            &PoolContractParameters::default(),
+            &token_ids,
+        );
+        let found =
+            find_boxes(&PoolBoxMock { pool_box }, &NoRefreshBox {}, &NoOracleBox {}).unwrap();
+        assert_eq!(
+            found,
+            FoundBoxes {
+                pool_box: true,
+                refresh_box: false,
+                oracle_box: false,
+            }
+        );
+        assert_eq!(found.missing_required(), vec!["refresh box"]);
+    }
+}
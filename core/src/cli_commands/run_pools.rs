@@ -0,0 +1,398 @@
+//! Several pools run by one oracle-core instance. The pools file (`pools.yaml`) holds the node
+//! connection shared by the pools and the config file of each pool, with its own pool definition,
+//! data source and schedule. A daemon is started per pool (`oracle-core -c <config> run`) in the
+//! directory of its config, so that the state files of the pools (store, scans, downtime) are
+//! kept apart, and is restarted when it stops. The node connection is passed to the daemons by
+//! the `ORACLE_CORE__NODE_*` environment overrides, all the pools use the wallet of the node. The
+//! node scans of a pool are named with its pool NFT id, a daemon only deregisters the scans of its
+//! own pool (see `scans::sync_scans`).
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::Duration;
+
+use derive_more::From;
+use ergo_node_interface::node_interface::{NodeError, NodeInterface};
+use serde::Deserialize;
+use serde_yaml::Value;
+use thiserror::Error;
+
+use crate::config_format::{ConfigFormat, ConfigFormatError};
+use crate::node_interface::unlock_wallet;
+use crate::oracle_config::{merged_config_value, ENV_OVERRIDE_PREFIX};
+use crate::secrets::{self, SecretsError, SECRETS_PASSPHRASE_ENV_VAR};
+
+/// Delay before a stopped pool daemon is restarted
+const RESTART_DELAY: Duration = Duration::from_secs(10);
+
+/// Node connection fields of the pools file, left out of the config of the pools
+const NODE_KEYS: [&str; 4] = [
+    "node_ip",
+    "node_port",
+    "node_api_key",
+    "node_wallet_password",
+];
+
+#[derive(Debug, Error, From)]
+pub enum RunPoolsError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("{0}")]
+    Format(ConfigFormatError),
+    #[error("pools file error: {0}")]
+    Yaml(serde_yaml::Error),
+    #[error("{0}")]
+    Secrets(SecretsError),
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("config of the pool {0}: {1}")]
+    #[from(ignore)]
+    PoolConfig(String, anyhow::Error),
+    #[error("the pools file has no pools")]
+    NoPools,
+    #[error("pool name {0} is used twice")]
+    #[from(ignore)]
+    DuplicateName(String),
+    #[error("the pools {0} and {1} are the same pool")]
+    #[from(ignore)]
+    DuplicatePool(String, String),
+    #[error("the configs of the pools {0} and {1} are in the same directory, they would share their state files")]
+    #[from(ignore)]
+    SharedDirectory(String, String),
+    #[error("the pools {0} and {1} both serve the REST API on port {2}")]
+    #[from(ignore)]
+    DuplicatePort(String, String, u64),
+    #[error("the config of the pool {0} sets {1}, the node connection is set in the pools file")]
+    #[from(ignore)]
+    NodeKeyInPoolConfig(String, String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolsConfig {
+    pub node_ip: String,
+    pub node_port: u16,
+    pub node_api_key: String,
+    /// Password of the node wallet, unlocked at start if locked
+    #[serde(default)]
+    pub node_wallet_password: Option<String>,
+    pub pools: Vec<PoolEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolEntry {
+    /// Name of the pool in the logs
+    pub name: String,
+    /// Config of the pool, relative to the pools file
+    pub config_file: PathBuf,
+    #[serde(default)]
+    pub enable_rest_api: bool,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub watch_only: bool,
+}
+
+impl PoolEntry {
+    fn run_args(&self) -> Vec<&'static str> {
+        let mut args = vec!["run"];
+        if self.enable_rest_api {
+            args.push("--enable-rest-api");
+        }
+        if self.read_only {
+            args.push("--read-only");
+        }
+        if self.watch_only {
+            args.push("--watch-only");
+        }
+        args
+    }
+}
+
+/// Parse the pools file `pools_str` (at `pools_path`), its secrets resolved
+pub fn load_pools_config(pools_str: &str, pools_path: &Path) -> Result<PoolsConfig, RunPoolsError> {
+    let mut value = ConfigFormat::of_path(pools_path).parse(pools_str)?;
+    secrets::resolve_secrets(&mut value)?;
+    let mut config: PoolsConfig = serde_yaml::from_value(value)?;
+    let dir = pools_path.parent().unwrap_or_else(|| Path::new(""));
+    for pool in config.pools.iter_mut() {
+        pool.config_file = dir.join(&pool.config_file);
+    }
+    Ok(config)
+}
+
+/// Check that the pools are distinct and that their configs load and leave the node connection to
+/// the pools file. `pool_configs` are the configs of the `pools` (with their pool definition). The
+/// daemons of the pools keep their state files (e.g. the scans of the pool in `scan_state.json`)
+/// in the directory of their config, which must be distinct.
+fn check_pools(pools: &[PoolEntry], pool_configs: &[Value]) -> Result<(), RunPoolsError> {
+    if pools.is_empty() {
+        return Err(RunPoolsError::NoPools);
+    }
+    let mut names = HashSet::new();
+    let mut dirs: HashMap<&Path, &str> = HashMap::new();
+    let mut pool_nfts: HashMap<String, &str> = HashMap::new();
+    let mut ports: HashMap<u64, &str> = HashMap::new();
+    for (pool, config) in pools.iter().zip(pool_configs) {
+        let name = pool.name.as_str();
+        if !names.insert(name) {
+            return Err(RunPoolsError::DuplicateName(name.to_string()));
+        }
+        for key in NODE_KEYS {
+            if config.get(key).is_some() {
+                return Err(RunPoolsError::NodeKeyInPoolConfig(
+                    name.to_string(),
+                    key.to_string(),
+                ));
+            }
+        }
+        let dir = pool.config_file.parent().unwrap_or_else(|| Path::new(""));
+        if let Some(other) = dirs.insert(dir, name) {
+            return Err(RunPoolsError::SharedDirectory(
+                other.to_string(),
+                name.to_string(),
+            ));
+        }
+        let pool_nft = config
+            .get("token_ids")
+            .and_then(|ids| ids.get("pool_nft_token_id"))
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        if let Some(other) = pool_nfts.insert(pool_nft, name) {
+            return Err(RunPoolsError::DuplicatePool(
+                other.to_string(),
+                name.to_string(),
+            ));
+        }
+        if pool.enable_rest_api {
+            let port = config
+                .get("core_api_port")
+                .and_then(Value::as_u64)
+                .unwrap_or_default();
+            if let Some(other) = ports.insert(port, name) {
+                return Err(RunPoolsError::DuplicatePort(
+                    other.to_string(),
+                    name.to_string(),
+                    port,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Environment overrides of the node connection of the pool daemons
+fn node_env_vars(config: &PoolsConfig) -> Result<Vec<(String, String)>, RunPoolsError> {
+    // Quoted, a missing field being parsed as YAML
+    let api_key = serde_yaml::to_string(&Value::String(config.node_api_key.clone()))?;
+    Ok(vec![
+        (
+            format!("{}NODE_IP", ENV_OVERRIDE_PREFIX),
+            config.node_ip.clone(),
+        ),
+        (
+            format!("{}NODE_PORT", ENV_OVERRIDE_PREFIX),
+            config.node_port.to_string(),
+        ),
+        (format!("{}NODE_API_KEY", ENV_OVERRIDE_PREFIX), api_key),
+    ])
+}
+
+fn start_pool(
+    exe: &Path,
+    pool: &PoolEntry,
+    env_vars: &[(String, String)],
+) -> Result<Child, std::io::Error> {
+    let dir = pool.config_file.parent().unwrap_or_else(|| Path::new(""));
+    let mut command = Command::new(exe);
+    command
+        .arg("-c")
+        .arg(pool.config_file.file_name().unwrap_or_default())
+        .args(pool.run_args())
+        .envs(env_vars.iter().cloned());
+    if !dir.as_os_str().is_empty() {
+        command.current_dir(dir);
+    }
+    if let Some(passphrase) = secrets::cached_passphrase() {
+        command.env(SECRETS_PASSPHRASE_ENV_VAR, passphrase);
+    }
+    command.spawn()
+}
+
+/// Run a daemon for each pool of the pools file at `pools_path`, restarting the stopped ones
+pub fn run_pools(pools_path: &Path) -> Result<(), RunPoolsError> {
+    let config = load_pools_config(&std::fs::read_to_string(pools_path)?, pools_path)?;
+    let pool_configs = config
+        .pools
+        .iter()
+        .map(|pool| {
+            std::fs::read_to_string(&pool.config_file)
+                .map_err(anyhow::Error::from)
+                .and_then(|s| merged_config_value(&s, &pool.config_file))
+                .map_err(|e| RunPoolsError::PoolConfig(pool.name.clone(), e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    check_pools(&config.pools, &pool_configs)?;
+
+    let node = NodeInterface::new(
+        &config.node_api_key,
+        &config.node_ip,
+        &config.node_port.to_string(),
+    );
+    if let Some(password) = &config.node_wallet_password {
+        unlock_wallet(&node, password)?;
+    }
+    let env_vars = node_env_vars(&config)?;
+    let exe = std::env::current_exe()?;
+    let mut children = Vec::new();
+    for pool in &config.pools {
+        log::info!("Starting the daemon of the pool {}", pool.name);
+        children.push(start_pool(&exe, pool, &env_vars)?);
+    }
+    loop {
+        std::thread::sleep(RESTART_DELAY);
+        for (pool, child) in config.pools.iter().zip(children.iter_mut()) {
+            if let Some(status) = child.try_wait()? {
+                log::error!(
+                    "The daemon of the pool {} stopped ({}), restarting it",
+                    pool.name,
+                    status
+                );
+                *child = start_pool(&exe, pool, &env_vars)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use serde_yaml::Mapping;
+
+    use super::*;
+    use crate::oracle_config::TokenIds;
+    use crate::pool_commands::test_utils::generate_token_ids;
+    use crate::scans::tests::RegistryMock;
+    use crate::scans::{sync_scans, ScanKind, ScanSpec, ScanState};
+
+    #[test]
+    fn test_check_pools() {
+        let pools_path = Path::new("/srv/oracle/pools.yaml");
+        let config = load_pools_config(
+            "node_ip: 127.0.0.1\nnode_port: 9053\nnode_api_key: '12345'\npools:\n  \
+            - name: erg-usd\n    config_file: erg-usd/oracle_config.yaml\n    enable_rest_api: true\n  \
+            - name: erg-xau\n    config_file: erg-xau/oracle_config.yaml\n    enable_rest_api: true\n",
+            pools_path,
+        )
+        .unwrap();
+        assert_eq!(
+            config.pools[1].config_file,
+            Path::new("/srv/oracle/erg-xau/oracle_config.yaml")
+        );
+        assert_eq!(config.pools[0].run_args(), vec!["run", "--enable-rest-api"]);
+        let pool_config = |pool_nft: &str, port: u64| -> Value {
+            serde_yaml::from_str(&format!(
+                "core_api_port: {}\ntoken_ids:\n  pool_nft_token_id: {}\n",
+                port, pool_nft
+            ))
+            .unwrap()
+        };
+        assert!(check_pools(
+            &config.pools,
+            &[pool_config("usd", 9010), pool_config("xau", 9011)]
+        )
+        .is_ok());
+        assert!(matches!(
+            check_pools(
+                &config.pools,
+                &[pool_config("usd", 9010), pool_config("usd", 9011)]
+            ),
+            Err(RunPoolsError::DuplicatePool(_, _))
+        ));
+        assert!(matches!(
+            check_pools(
+                &config.pools,
+                &[pool_config("usd", 9010), pool_config("xau", 9010)]
+            ),
+            Err(RunPoolsError::DuplicatePort(_, _, 9010))
+        ));
+        let mut with_node = pool_config("xau", 9011);
+        with_node
+            .as_mapping_mut()
+            .unwrap()
+            .insert("node_port".into(), 9052.into());
+        assert!(matches!(
+            check_pools(&config.pools, &[pool_config("usd", 9010), with_node]),
+            Err(RunPoolsError::NodeKeyInPoolConfig(_, _))
+        ));
+
+        // The API key stays a string
+        let env_vars = node_env_vars(&config).unwrap();
+        let api_key: Value = serde_yaml::from_str(&env_vars[2].1).unwrap();
+        assert_eq!(api_key, Value::from("12345"));
+    }
+
+    #[test]
+    fn test_pools_share_node_scans() {
+        let pools_file = |second_dir: &str| {
+            load_pools_config(
+                &format!(
+                    "node_ip: 127.0.0.1\nnode_port: 9053\nnode_api_key: '12345'\npools:\n  \
+                    - name: erg-usd\n    config_file: erg-usd/oracle_config.yaml\n  \
+                    - name: erg-xau\n    config_file: {}/oracle_config.yaml\n",
+                    second_dir
+                ),
+                Path::new("/srv/oracle/pools.yaml"),
+            )
+            .unwrap()
+        };
+        let pool_config = |token_ids: &TokenIds| -> Value {
+            let mut config = Mapping::new();
+            config.insert("token_ids".into(), serde_yaml::to_value(token_ids).unwrap());
+            Value::Mapping(config)
+        };
+        let pool_configs = vec![
+            pool_config(&generate_token_ids()),
+            pool_config(&generate_token_ids()),
+        ];
+        // The scan state files of the pools are kept apart
+        assert!(matches!(
+            check_pools(&pools_file("erg-usd").pools, &pool_configs),
+            Err(RunPoolsError::SharedDirectory(_, _))
+        ));
+        check_pools(&pools_file("erg-xau").pools, &pool_configs).unwrap();
+
+        // The daemons of the pools sync their scans with the same node
+        let registry = RegistryMock::default();
+        let specs: Vec<Vec<ScanSpec>> = pool_configs
+            .iter()
+            .map(|config| {
+                let token_ids: TokenIds =
+                    serde_yaml::from_value(config["token_ids"].clone()).unwrap();
+                let pool_nft_token_id = token_ids.pool_nft_token_id;
+                vec![ScanSpec::new(
+                    ScanKind::Pool,
+                    &pool_nft_token_id,
+                    json!({
+                        "predicate": "containsAsset",
+                        "assetId": String::from(pool_nft_token_id.clone()),
+                    }),
+                )]
+            })
+            .collect();
+        let states: Vec<ScanState> = specs
+            .iter()
+            .map(|specs| sync_scans(&registry, None, &[], specs).unwrap().state)
+            .collect();
+        // Restarting a daemon leaves the scans of the other pool alone
+        for _ in 0..2 {
+            for (specs, state) in specs.iter().zip(&states) {
+                let sync = sync_scans(&registry, Some(state), &[], specs).unwrap();
+                assert_eq!(&sync.state, state);
+                assert!(sync.registered.is_empty() && sync.deregistered.is_empty());
+            }
+        }
+        assert_eq!(registry.scans.borrow().len(), 2);
+    }
+}
@@ -0,0 +1,528 @@
+//! Simulation of a pool on an in-memory chain (`oracle-core simulate`), no node needed. A pool is
+//! bootstrapped for N virtual oracles and the blocks are produced one by one: each oracle runs its
+//! own `PoolController`, the decided commands are built with the production box wrappers,
+//! contracts and tx builders, and the txs are put in the next block. It shows the full epoch
+//! lifecycle (publish, refresh, reward accrual) of a pool.
+//!
+//! The txs are added to the chain without proofs, neither the contracts nor the signatures are
+//! checked.
+
+use std::cell::RefCell;
+use std::convert::TryInto;
+
+use anyhow::anyhow;
+use ergo_chain_sim::{Block, ChainSim};
+use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::{Input, Transaction, TxIoVec};
+use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+use ergo_lib::ergotree_interpreter::sigma_protocol::prover::{ProofBytes, ProverResult};
+use ergo_lib::ergotree_ir::chain::address::{Address, NetworkAddress, NetworkPrefix};
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+use ergo_lib::ergotree_ir::chain::ergo_box::{BoxId, ErgoBox};
+use ergo_lib::ergotree_ir::chain::token::{Token, TokenId};
+use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
+use ergo_lib::wallet::box_selector::{BoxSelector, SimpleBoxSelector};
+use ergo_lib::wallet::tx_builder::TxBuilder;
+
+use crate::actions::PoolAction;
+use crate::box_kind::{
+    OracleBox, OracleBoxWrapper, PoolBox, PoolBoxWrapper, PostedOracleBox, RefreshBoxWrapper,
+};
+use crate::cli_commands::bootstrap::{
+    perform_bootstrap_chained_transaction, BootstrapConfig, BootstrapInput,
+};
+use crate::contracts::refresh::RefreshContractParameters;
+use crate::datapoint_source::{DataPointSource, DataPointSourceError};
+use crate::node_interface::{SignTransaction, SubmitTransaction};
+use crate::oracle_config::{OracleConfig, BASE_FEE};
+use crate::oracle_state::{
+    DatapointBoxesSource, LiveEpochState, LocalDatapointState, PendingTxs, PoolBoxSource,
+    RefreshBoxSource, StageError,
+};
+use crate::pool_commands::publish_datapoint::{
+    build_publish_first_datapoint_action, build_subsequent_publish_datapoint_action,
+};
+use crate::pool_commands::refresh::build_refresh_action;
+use crate::pool_commands::PoolCommand;
+use crate::pool_controller::{CollectorElection, EpochStrategyKind, PoolController, PublishTiming};
+use crate::state::PoolState;
+use crate::wallet::{WalletDataError, WalletDataSource};
+
+/// Rate the virtual oracles report around (nanoERG per USD)
+const BASE_RATE: i64 = 300_000_000;
+
+/// ERGs each virtual oracle starts with
+const ORACLE_FUNDS: u64 = 10_000_000_000;
+
+struct VirtualOracle {
+    index: u32,
+    address: Address,
+    public_key: ProveDlog,
+    controller: PoolController,
+}
+
+impl VirtualOracle {
+    /// The market rate moves by up to 2% every 10 blocks, each oracle reports it up to 0.5% off
+    fn datapoint(&self, height: u32) -> i64 {
+        let step = (height / 10) as i64;
+        let market_rate = BASE_RATE + BASE_RATE * ((step * 7) % 5 - 2) / 100;
+        market_rate + market_rate * (self.index as i64 % 3 - 1) / 200
+    }
+}
+
+#[derive(Debug)]
+struct SimDataPointSource {
+    datapoint: i64,
+}
+
+impl DataPointSource for SimDataPointSource {
+    fn get_datapoint(&self) -> Result<i64, DataPointSourceError> {
+        Ok(self.datapoint)
+    }
+}
+
+/// The chain the bootstrap txs are submitted to, a block per tx
+struct SimChain {
+    chain: RefCell<ChainSim>,
+}
+
+impl SubmitTransaction for SimChain {
+    fn submit_transaction(&self, tx: &Transaction) -> crate::node_interface::Result<String> {
+        self.chain
+            .borrow_mut()
+            .add_block(Block::new(vec![tx.clone()]));
+        Ok(String::from(tx.id().0))
+    }
+}
+
+struct NoProofSigner {}
+
+impl SignTransaction for NoProofSigner {
+    fn sign_transaction_with_inputs(
+        &self,
+        unsigned_tx: &UnsignedTransaction,
+        _inputs: TxIoVec<ErgoBox>,
+        _data_boxes: Option<TxIoVec<ErgoBox>>,
+    ) -> crate::node_interface::Result<Transaction> {
+        Ok(without_proofs(unsigned_tx))
+    }
+}
+
+fn without_proofs(unsigned_tx: &UnsignedTransaction) -> Transaction {
+    Transaction::new(
+        unsigned_tx.inputs.clone().mapped(|i| {
+            Input::new(
+                i.box_id,
+                ProverResult {
+                    proof: ProofBytes::Empty,
+                    extension: i.extension,
+                },
+            )
+        }),
+        unsigned_tx.data_inputs.clone(),
+        unsigned_tx.output_candidates.clone(),
+    )
+    .unwrap()
+}
+
+struct SimWallet {
+    boxes: Vec<ErgoBox>,
+}
+
+impl WalletDataSource for SimWallet {
+    fn get_unspent_wallet_boxes(&self) -> Result<Vec<ErgoBox>, WalletDataError> {
+        Ok(self.boxes.clone())
+    }
+}
+
+/// The unspent boxes at the current height
+struct SimBoxes<'a> {
+    boxes: Vec<ErgoBox>,
+    config: &'a OracleConfig,
+}
+
+impl<'a> SimBoxes<'a> {
+    /// Boxes holding `token_id` as their first token
+    fn with_token(&self, token_id: &TokenId) -> Vec<&ErgoBox> {
+        self.boxes
+            .iter()
+            .filter(|b| {
+                b.tokens
+                    .as_ref()
+                    .and_then(|tokens| tokens.get(0))
+                    .map_or(false, |t| &t.token_id == token_id)
+            })
+            .collect()
+    }
+
+    fn oracle_boxes(&self) -> Vec<OracleBoxWrapper> {
+        self.with_token(&self.config.token_ids.oracle_token_id)
+            .into_iter()
+            .filter_map(|b| {
+                OracleBoxWrapper::new(b.clone(), &self.config.oracle_box_wrapper_inputs).ok()
+            })
+            .collect()
+    }
+
+    fn local_oracle_box(&self, public_key: &ProveDlog) -> Option<OracleBoxWrapper> {
+        self.oracle_boxes()
+            .into_iter()
+            .find(|b| &b.public_key() == public_key)
+    }
+
+    fn wallet(&self, address: &Address) -> Result<SimWallet, anyhow::Error> {
+        let ergo_tree = address.script()?;
+        Ok(SimWallet {
+            boxes: self
+                .boxes
+                .iter()
+                .filter(|b| b.ergo_tree == ergo_tree)
+                .cloned()
+                .collect(),
+        })
+    }
+}
+
+impl<'a> PoolBoxSource for SimBoxes<'a> {
+    fn get_pool_box(&self) -> Result<PoolBoxWrapper, StageError> {
+        let pool_box = self
+            .with_token(&self.config.token_ids.pool_nft_token_id)
+            .first()
+            .cloned()
+            .ok_or(StageError::PoolBoxNotFoundError)?;
+        Ok(PoolBoxWrapper::new(
+            pool_box.clone(),
+            &self.config.pool_box_wrapper_inputs,
+        )?)
+    }
+}
+
+impl<'a> RefreshBoxSource for SimBoxes<'a> {
+    fn get_refresh_box(&self) -> Result<RefreshBoxWrapper, StageError> {
+        let refresh_box = self
+            .with_token(&self.config.token_ids.refresh_nft_token_id)
+            .first()
+            .cloned()
+            .ok_or(StageError::RefreshBoxNotFoundError)?;
+        Ok(RefreshBoxWrapper::new(
+            refresh_box.clone(),
+            &self.config.refresh_box_wrapper_inputs,
+        )?)
+    }
+}
+
+impl<'a> DatapointBoxesSource for SimBoxes<'a> {
+    fn get_oracle_datapoint_boxes(&self) -> Result<Vec<PostedOracleBox>, StageError> {
+        Ok(self
+            .oracle_boxes()
+            .into_iter()
+            .filter_map(|b| match b {
+                OracleBoxWrapper::Posted(posted_box) => Some(posted_box),
+                OracleBoxWrapper::Collected(_) => None,
+            })
+            .collect())
+    }
+}
+
+/// Run a pool of `num_oracles` virtual oracles for `num_epochs` epochs
+pub fn simulate(num_oracles: u32, num_epochs: u32) -> Result<(), anyhow::Error> {
+    let refresh_parameters = RefreshContractParameters::default();
+    let min_data_points = refresh_parameters.min_data_points() as u32;
+    if num_oracles < min_data_points || num_oracles > u8::MAX as u32 {
+        return Err(anyhow!(
+            "the number of oracles must be between {} (the minimum number of datapoints of a refresh) and {}",
+            min_data_points,
+            u8::MAX
+        ));
+    }
+    let epoch_length = refresh_parameters.epoch_length() as u32;
+    let mut chain = ChainSim::new();
+    let mut oracles = Vec::new();
+    for index in 0..num_oracles {
+        let secret = DlogProverInput::from_bytes(&[index as u8 + 1; 32])
+            .ok_or_else(|| anyhow!("invalid secret of oracle {}", index))?;
+        let public_key = secret.public_image();
+        let address = Address::P2Pk(public_key.clone());
+        chain.generate_unspent_box(address.script()?, ORACLE_FUNDS.try_into()?, None);
+        let controller = PoolController::new(
+            EpochStrategyKind::Standard.strategy(),
+            epoch_length,
+            PublishTiming {
+                offset_blocks: None,
+                jitter_blocks: epoch_length / 10,
+                seed: NetworkAddress::new(NetworkPrefix::Mainnet, &address).to_base58(),
+            },
+            Some(CollectorElection {
+                local_public_key: *public_key.h.clone(),
+                timeout_blocks: 3,
+            }),
+        );
+        oracles.push(VirtualOracle {
+            index,
+            address,
+            public_key,
+            controller,
+        });
+    }
+
+    // The first oracle bootstraps the pool and hands an oracle token to each of the others
+    let operator = &oracles[0];
+    let mut bootstrap_config = BootstrapConfig {
+        oracle_address: NetworkAddress::new(NetworkPrefix::Mainnet, &operator.address),
+        ..BootstrapConfig::default()
+    };
+    bootstrap_config.tokens_to_mint.oracle_tokens.quantity = num_oracles as u64;
+    let height = chain.height;
+    let wallet = SimWallet {
+        boxes: chain.get_unspent_boxes(&operator.address.script()?),
+    };
+    let sim_chain = SimChain {
+        chain: RefCell::new(chain),
+    };
+    let config = perform_bootstrap_chained_transaction(BootstrapInput {
+        config: bootstrap_config,
+        wallet: &wallet,
+        tx_signer: &NoProofSigner {},
+        submit_tx: &sim_chain,
+        tx_fee: *BASE_FEE,
+        erg_value_per_box: *BASE_FEE,
+        change_address: operator.address.clone(),
+        height,
+    })?;
+    let mut chain = sim_chain.chain.into_inner();
+    distribute_oracle_tokens(&mut chain, &config, &oracles)?;
+    println!(
+        "Bootstrapped a pool of {} oracles at height {}, epoch length {} blocks",
+        num_oracles, chain.height, epoch_length
+    );
+
+    let end_height = chain.height + num_epochs * epoch_length;
+    let mut last_epoch = None;
+    while chain.height < end_height {
+        let height = chain.height;
+        let boxes = SimBoxes {
+            boxes: chain.unspent_boxes().to_vec(),
+            config: &config,
+        };
+        let pool_box = boxes.get_pool_box()?;
+        if last_epoch != Some(pool_box.epoch_counter()) {
+            last_epoch = Some(pool_box.epoch_counter());
+            println!(
+                "Height {}: epoch {} started, pool rate {}",
+                height,
+                pool_box.epoch_counter(),
+                pool_box.rate()
+            );
+            print_rewards(&boxes, &oracles);
+        }
+        let mut txs = Vec::new();
+        let mut spent_box_ids: Vec<BoxId> = Vec::new();
+        for oracle in oracles.iter_mut() {
+            let local_box = boxes.local_oracle_box(&oracle.public_key);
+            let pool_state = PoolState::LiveEpoch(LiveEpochState {
+                pool_box_epoch_id: pool_box.epoch_counter(),
+                local_datapoint_box_state: local_box.as_ref().map(local_datapoint_state),
+                latest_pool_datapoint: pool_box.rate() as u64,
+                latest_pool_box_height: pool_box.get_box().creation_height,
+                pending_txs: PendingTxs::default(),
+                pool_box_id: pool_box.get_box().box_id(),
+                local_datapoint_box_id: local_box.as_ref().map(|b| b.get_box().box_id()),
+            });
+            let cmd = match oracle.controller.next_command(pool_state, height, &boxes) {
+                Some(cmd) => cmd,
+                None => continue,
+            };
+            let action = match build_sim_action(&cmd, oracle, &boxes, local_box, height) {
+                Ok(action) => action,
+                Err(e) => {
+                    println!(
+                        "Height {}: oracle {} failed to build {:?}: {}",
+                        height, oracle.index, cmd, e
+                    );
+                    continue;
+                }
+            };
+            let tx = match &action {
+                PoolAction::PublishDatapoint(action) => {
+                    println!(
+                        "Height {}: oracle {} publishes {}",
+                        height, oracle.index, action.datapoint
+                    );
+                    without_proofs(&action.tx)
+                }
+                PoolAction::Refresh(action) => {
+                    println!(
+                        "Height {}: oracle {} refreshes the pool box",
+                        height, oracle.index
+                    );
+                    without_proofs(&action.tx)
+                }
+            };
+            if tx.inputs.iter().any(|i| spent_box_ids.contains(&i.box_id)) {
+                println!(
+                    "Height {}: the tx of oracle {} spends a box spent in this block, dropped",
+                    height, oracle.index
+                );
+                continue;
+            }
+            spent_box_ids.extend(tx.inputs.iter().map(|i| i.box_id));
+            txs.push(tx);
+        }
+        chain.add_block(Block::new(txs));
+    }
+    println!("Simulation ended at height {}", chain.height);
+    print_rewards(
+        &SimBoxes {
+            boxes: chain.unspent_boxes().to_vec(),
+            config: &config,
+        },
+        &oracles,
+    );
+    Ok(())
+}
+
+/// Send an oracle token and a reward token (for the first datapoint box) to each oracle but the
+/// first one, which got them from the bootstrap
+fn distribute_oracle_tokens(
+    chain: &mut ChainSim,
+    config: &OracleConfig,
+    oracles: &[VirtualOracle],
+) -> Result<(), anyhow::Error> {
+    let operator = &oracles[0];
+    let recipients = &oracles[1..];
+    let token = |token_id: &TokenId, amount: u64| -> Result<Token, anyhow::Error> {
+        Ok(Token {
+            token_id: token_id.clone(),
+            amount: amount.try_into()?,
+        })
+    };
+    let oracle_token_id = &config.token_ids.oracle_token_id;
+    let reward_token_id = &config.token_ids.reward_token_id;
+    let mut outputs = Vec::new();
+    for oracle in recipients {
+        let mut builder =
+            ErgoBoxCandidateBuilder::new(*BASE_FEE, oracle.address.script()?, chain.height);
+        builder.add_token(token(oracle_token_id, 1)?);
+        builder.add_token(token(reward_token_id, 1)?);
+        outputs.push(builder.build()?);
+    }
+    let target_balance: BoxValue = BASE_FEE.checked_mul_u32(recipients.len() as u32 + 1)?;
+    let selection = SimpleBoxSelector::new().select(
+        chain.get_unspent_boxes(&operator.address.script()?),
+        target_balance,
+        &[
+            token(oracle_token_id, recipients.len() as u64)?,
+            token(reward_token_id, recipients.len() as u64)?,
+        ],
+    )?;
+    let tx = TxBuilder::new(
+        selection,
+        outputs,
+        chain.height,
+        *BASE_FEE,
+        operator.address.clone(),
+    )
+    .build()?;
+    chain.add_block(Block::new(vec![without_proofs(&tx)]));
+    Ok(())
+}
+
+fn local_datapoint_state(oracle_box: &OracleBoxWrapper) -> LocalDatapointState {
+    match oracle_box {
+        OracleBoxWrapper::Posted(posted_box) => LocalDatapointState::Posted {
+            epoch_id: posted_box.epoch_counter(),
+            height: posted_box.get_box().creation_height,
+        },
+        OracleBoxWrapper::Collected(_) => LocalDatapointState::Collected {
+            height: oracle_box.get_box().creation_height,
+        },
+    }
+}
+
+/// Build the action of `cmd` the way the daemon does, the oracle's datapoint depends on the height
+fn build_sim_action(
+    cmd: &PoolCommand,
+    oracle: &VirtualOracle,
+    boxes: &SimBoxes,
+    local_box: Option<OracleBoxWrapper>,
+    height: u32,
+) -> Result<PoolAction, anyhow::Error> {
+    let config = boxes.config;
+    let wallet = boxes.wallet(&oracle.address)?;
+    let pool_box = boxes.get_pool_box()?;
+    let datapoint_source = SimDataPointSource {
+        datapoint: oracle.datapoint(height),
+    };
+    Ok(match cmd {
+        PoolCommand::PublishFirstDataPoint => {
+            let mut inputs = config.oracle_box_wrapper_inputs.clone();
+            inputs.reward_token_id = pool_box.reward_token().token_id;
+            build_publish_first_datapoint_action(
+                &wallet,
+                height,
+                oracle.address.clone(),
+                oracle.public_key.clone(),
+                inputs,
+                &datapoint_source,
+            )?
+            .into()
+        }
+        PoolCommand::PublishSubsequentDataPoint { .. } => {
+            let local_box = local_box.ok_or_else(|| anyhow!("no local datapoint box found"))?;
+            build_subsequent_publish_datapoint_action(
+                &local_box,
+                &wallet,
+                height,
+                oracle.address.clone(),
+                &datapoint_source,
+                pool_box.epoch_counter(),
+                pool_box.reward_token().token_id,
+            )?
+            .into()
+        }
+        PoolCommand::Refresh => {
+            let parameters = config
+                .refresh_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters();
+            build_refresh_action(
+                boxes,
+                boxes,
+                boxes,
+                None,
+                parameters.max_deviation_percent() as u32,
+                parameters.min_data_points() as u32,
+                &wallet,
+                height,
+                oracle.address.clone(),
+                oracle.public_key.h.as_ref(),
+            )?
+            .into()
+        }
+    })
+}
+
+fn print_rewards(boxes: &SimBoxes, oracles: &[VirtualOracle]) {
+    let rewards: Vec<String> = oracles
+        .iter()
+        .map(|oracle| {
+            let amount = boxes
+                .local_oracle_box(&oracle.public_key)
+                .map_or(0, |b| u64::from(b.reward_token().amount));
+            format!("oracle {}: {}", oracle.index, amount)
+        })
+        .collect();
+    println!("  reward tokens: {}", rewards.join(", "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulate() {
+        assert!(simulate(3, 1).is_err());
+        simulate(4, 2).unwrap();
+    }
+}
@@ -0,0 +1,467 @@
+//! Snapshot of the observed state of the pool (pool box, refresh box, all the oracle boxes and the
+//! pending txs at a height), saved to a file by `snapshot export` to be attached to bug reports.
+//! `snapshot import` replays the snapshot offline: the boxes are parsed with the box wrappers of
+//! the config and the pool controller of the config decides the command at the snapshot height,
+//! as the daemon did.
+use std::path::Path;
+
+use derive_more::From;
+use ergo_lib::ergotree_ir::chain::address::{Address, NetworkAddress, NetworkPrefix};
+use ergo_lib::ergotree_ir::chain::ergo_box::{BoxId, ErgoBox};
+use ergo_node_interface::node_interface::NodeError;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::box_kind::{
+    OracleBox, OracleBoxWrapper, OracleBoxWrapperInputs, PoolBox, PoolBoxWrapper,
+    PoolBoxWrapperInputs, RefreshBox, RefreshBoxWrapper, RefreshBoxWrapperInputs,
+};
+use crate::cli_commands::{json_output, print_json};
+use crate::downtime::unix_now;
+use crate::node_interface::current_block_height;
+use crate::oracle_config::OracleConfig;
+use crate::oracle_state::{
+    live_epoch_state, DatapointBoxesSource, LocalDatapointBoxSource, OraclePool, PendingTxs,
+    PoolBoxSource, RefreshBoxSource, StageError,
+};
+use crate::pool_controller::PoolController;
+use crate::state::PoolState;
+
+/// Version of the snapshot file format
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, From, Error)]
+pub enum SnapshotError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("snapshot file error: {0}")]
+    SnapshotFile(serde_json::Error),
+    #[error("stage error: {0}")]
+    Stage(#[source] StageError),
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("snapshot version {0} is not supported")]
+    UnsupportedVersion(u32),
+    #[error("the snapshot was taken on {snapshot}, the config is for {config}")]
+    NetworkMismatch { snapshot: String, config: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolSnapshot {
+    pub version: u32,
+    /// `mainnet` or `testnet`
+    pub network: String,
+    /// Height the snapshot was taken at
+    pub height: u32,
+    /// Unix time in seconds
+    pub taken_at: u64,
+    pub pool_box: ErgoBox,
+    pub refresh_box: ErgoBox,
+    /// The posted and collected oracle boxes of all the oracles
+    pub oracle_boxes: Vec<ErgoBox>,
+    /// The boxes holding the local oracle token
+    pub local_oracle_boxes: Vec<ErgoBox>,
+    pub pending_txs: PendingTxs,
+}
+
+fn network_name(network: NetworkPrefix) -> &'static str {
+    match network {
+        NetworkPrefix::Mainnet => "mainnet",
+        NetworkPrefix::Testnet => "testnet",
+    }
+}
+
+impl PoolSnapshot {
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let snapshot: PoolSnapshot = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version));
+        }
+        Ok(snapshot)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// The boxes of a snapshot parsed with the box wrappers of the config
+pub struct SnapshotSources {
+    pool_box: PoolBoxWrapper,
+    refresh_box: RefreshBoxWrapper,
+    oracle_boxes: Vec<OracleBoxWrapper>,
+    local_oracle_boxes: Vec<OracleBoxWrapper>,
+}
+
+impl SnapshotSources {
+    pub fn parse(
+        snapshot: &PoolSnapshot,
+        oracle_box_wrapper_inputs: &OracleBoxWrapperInputs,
+        pool_box_wrapper_inputs: &PoolBoxWrapperInputs,
+        refresh_box_wrapper_inputs: &RefreshBoxWrapperInputs,
+    ) -> Result<Self, StageError> {
+        let parse_oracle_boxes = |boxes: &[ErgoBox]| -> Result<Vec<OracleBoxWrapper>, StageError> {
+            boxes
+                .iter()
+                .map(|b| Ok(OracleBoxWrapper::new(b.clone(), oracle_box_wrapper_inputs)?))
+                .collect()
+        };
+        Ok(SnapshotSources {
+            pool_box: PoolBoxWrapper::new(snapshot.pool_box.clone(), pool_box_wrapper_inputs)?,
+            refresh_box: RefreshBoxWrapper::new(
+                snapshot.refresh_box.clone(),
+                refresh_box_wrapper_inputs,
+            )?,
+            oracle_boxes: parse_oracle_boxes(&snapshot.oracle_boxes)?,
+            local_oracle_boxes: parse_oracle_boxes(&snapshot.local_oracle_boxes)?,
+        })
+    }
+}
+
+impl PoolBoxSource for SnapshotSources {
+    fn get_pool_box(&self) -> Result<PoolBoxWrapper, StageError> {
+        Ok(self.pool_box.clone())
+    }
+}
+
+impl RefreshBoxSource for SnapshotSources {
+    fn get_refresh_box(&self) -> Result<RefreshBoxWrapper, StageError> {
+        Ok(self.refresh_box.clone())
+    }
+}
+
+impl DatapointBoxesSource for SnapshotSources {
+    fn get_oracle_boxes(&self) -> Result<Vec<OracleBoxWrapper>, StageError> {
+        Ok(self.oracle_boxes.clone())
+    }
+}
+
+impl LocalDatapointBoxSource for SnapshotSources {
+    fn get_local_oracle_datapoint_box(&self) -> Result<Option<OracleBoxWrapper>, StageError> {
+        if self.local_oracle_boxes.len() > 1 {
+            return Err(StageError::DuplicateOracleBoxes(
+                self.local_oracle_boxes
+                    .iter()
+                    .map(|b| b.get_box().box_id())
+                    .collect(),
+            ));
+        }
+        Ok(self.local_oracle_boxes.first().cloned())
+    }
+
+    fn get_local_oracle_datapoint_boxes(&self) -> Result<Vec<OracleBoxWrapper>, StageError> {
+        Ok(self.local_oracle_boxes.clone())
+    }
+
+    /// Not in the snapshot
+    fn get_spent_local_oracle_datapoint_boxes(&self) -> Result<Vec<OracleBoxWrapper>, StageError> {
+        Ok(Vec::new())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotOracleBox {
+    pub box_id: BoxId,
+    pub oracle_address: String,
+    pub epoch: Option<u32>,
+    /// `None` for a collected box
+    pub rate: Option<u64>,
+    pub height: u32,
+}
+
+/// The state of the pool in a snapshot and the command the controller decided on it
+#[derive(Debug, Serialize)]
+pub struct SnapshotReplay {
+    pub height: u32,
+    pub epoch: u32,
+    pub pool_rate: u64,
+    pub pool_box_height: u32,
+    pub refresh_box_id: BoxId,
+    pub oracle_boxes: Vec<SnapshotOracleBox>,
+    /// e.g. `Posted { epoch_id: 5, height: 1000 }`
+    pub local_datapoint: Option<String>,
+    pub pending_txs: PendingTxs,
+    /// e.g. `Refresh`, `None` if the controller waits
+    pub command: Option<String>,
+}
+
+/// Take a snapshot of the pool observed by the scans
+pub fn take_snapshot(
+    op: &OraclePool,
+    network: NetworkPrefix,
+) -> Result<PoolSnapshot, SnapshotError> {
+    Ok(PoolSnapshot {
+        version: SNAPSHOT_VERSION,
+        network: network_name(network).to_string(),
+        height: current_block_height()? as u32,
+        taken_at: unix_now(),
+        pool_box: op.get_pool_box_source().get_pool_box()?.get_box().clone(),
+        refresh_box: op
+            .get_refresh_box_source()
+            .get_refresh_box()?
+            .get_box()
+            .clone(),
+        oracle_boxes: op
+            .get_all_oracle_boxes()?
+            .iter()
+            .map(|b| b.get_box().clone())
+            .collect(),
+        local_oracle_boxes: op
+            .get_local_datapoint_box_source()
+            .get_local_oracle_datapoint_boxes()?
+            .iter()
+            .map(|b| b.get_box().clone())
+            .collect(),
+        pending_txs: op.get_pending_txs()?,
+    })
+}
+
+pub fn export_snapshot(
+    op: &OraclePool,
+    network: NetworkPrefix,
+    path: &Path,
+) -> Result<(), SnapshotError> {
+    let snapshot = take_snapshot(op, network)?;
+    snapshot.save(path)?;
+    println!(
+        "Saved the pool state at height {} ({} oracle boxes) to {}",
+        snapshot.height,
+        snapshot.oracle_boxes.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Replay `snapshot` with the pool `controller`
+pub fn replay_snapshot(
+    snapshot: &PoolSnapshot,
+    sources: &SnapshotSources,
+    mut controller: PoolController,
+    network: NetworkPrefix,
+) -> Result<SnapshotReplay, StageError> {
+    let live_epoch = live_epoch_state(sources, sources, |_, _| snapshot.pending_txs.clone())?;
+    let local_datapoint = live_epoch
+        .local_datapoint_box_state
+        .as_ref()
+        .map(|state| format!("{:?}", state));
+    let oracle_boxes = sources
+        .oracle_boxes
+        .iter()
+        .map(|b| {
+            let (epoch, rate) = match b {
+                OracleBoxWrapper::Posted(p) => (Some(p.epoch_counter()), Some(p.rate())),
+                OracleBoxWrapper::Collected(_) => (None, None),
+            };
+            SnapshotOracleBox {
+                box_id: b.get_box().box_id(),
+                oracle_address: NetworkAddress::new(network, &Address::P2Pk(b.public_key()))
+                    .to_base58(),
+                epoch,
+                rate,
+                height: b.get_box().creation_height,
+            }
+        })
+        .collect();
+    let replay = SnapshotReplay {
+        height: snapshot.height,
+        epoch: live_epoch.pool_box_epoch_id,
+        pool_rate: live_epoch.latest_pool_datapoint,
+        pool_box_height: live_epoch.latest_pool_box_height,
+        refresh_box_id: sources.refresh_box.get_box().box_id(),
+        oracle_boxes,
+        local_datapoint,
+        pending_txs: live_epoch.pending_txs.clone(),
+        command: None,
+    };
+    let command =
+        controller.next_command(PoolState::LiveEpoch(live_epoch), snapshot.height, sources);
+    Ok(SnapshotReplay {
+        command: command.map(|cmd| format!("{:?}", cmd)),
+        ..replay
+    })
+}
+
+/// Load the snapshot at `path` and print the pool state and the command `controller` decides on it
+pub fn import_snapshot(
+    config: &OracleConfig,
+    controller: PoolController,
+    path: &Path,
+) -> Result<(), SnapshotError> {
+    let snapshot = PoolSnapshot::load(path)?;
+    let network = config.oracle_address.network();
+    if snapshot.network != network_name(network) {
+        return Err(SnapshotError::NetworkMismatch {
+            snapshot: snapshot.network,
+            config: network_name(network).to_string(),
+        });
+    }
+    let sources = SnapshotSources::parse(
+        &snapshot,
+        &config.oracle_box_wrapper_inputs,
+        &config.pool_box_wrapper_inputs,
+        &config.refresh_box_wrapper_inputs,
+    )?;
+    let replay = replay_snapshot(&snapshot, &sources, controller, network)?;
+    if json_output() {
+        print_json(&replay);
+        return Ok(());
+    }
+    println!(
+        "Height {}, epoch {}, pool rate {} (pool box at height {})",
+        replay.height, replay.epoch, replay.pool_rate, replay.pool_box_height
+    );
+    println!(
+        "Local datapoint: {}",
+        replay.local_datapoint.as_deref().unwrap_or("none")
+    );
+    if !replay.pending_txs.is_empty() {
+        println!("Pending txs: {:?}", replay.pending_txs);
+    }
+    println!(
+        "{:<52} {:>8} {:>20} {:>10}",
+        "oracle", "epoch", "rate", "height"
+    );
+    for oracle_box in &replay.oracle_boxes {
+        println!(
+            "{:<52} {:>8} {:>20} {:>10}",
+            oracle_box.oracle_address,
+            oracle_box.epoch.map_or("-".to_string(), |e| e.to_string()),
+            oracle_box
+                .rate
+                .map_or("collected".to_string(), |r| r.to_string()),
+            oracle_box.height
+        );
+    }
+    println!(
+        "Command: {}",
+        replay.command.as_deref().unwrap_or("none, waiting")
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergo_chain_types::EcPoint;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::token::Token;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::box_kind::make_refresh_box_candidate;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::contracts::pool::PoolContractParameters;
+    use crate::contracts::refresh::{RefreshContract, RefreshContractParameters};
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_commands::test_utils::{generate_token_ids, make_datapoint_box, make_pool_box};
+    use crate::pool_controller::{EpochStrategyKind, PublishTiming};
+
+    #[test]
+    fn test_snapshot_replay() {
+        let height = 1000;
+        let token_ids = generate_token_ids();
+        let oracle_box_wrapper_inputs = OracleBoxWrapperInputs::build_with(
+            OracleContractParameters::default(),
+            token_ids.pool_nft_token_id.clone(),
+            token_ids.oracle_token_id.clone(),
+            token_ids.reward_token_id.clone(),
+        )
+        .unwrap();
+        let pool_box_wrapper_inputs = PoolBoxWrapperInputs::build_with(
+            PoolContractParameters::default(),
+            token_ids.refresh_nft_token_id.clone(),
+            token_ids.update_nft_token_id.clone(),
+            token_ids.pool_nft_token_id.clone(),
+            token_ids.reward_token_id.clone(),
+        )
+        .unwrap();
+        let refresh_box_wrapper_inputs = RefreshBoxWrapperInputs::build_with(
+            RefreshContractParameters::default(),
+            token_ids.oracle_token_id.clone(),
+            token_ids.pool_nft_token_id.clone(),
+            token_ids.refresh_nft_token_id.clone(),
+        )
+        .unwrap();
+        let pool_box = make_pool_box(
+            200,
+            1,
+            *BASE_FEE,
+            height - 32,
+            &PoolContractParameters::default(),
+            &token_ids,
+        );
+        let refresh_box = ErgoBox::from_box_candidate(
+            &make_refresh_box_candidate(
+                &RefreshContract::checked_load(&refresh_box_wrapper_inputs.contract_inputs)
+                    .unwrap(),
+                Token::from((
+                    token_ids.refresh_nft_token_id.clone(),
+                    1u64.try_into().unwrap(),
+                )),
+                *BASE_FEE,
+                height - 32,
+            )
+            .unwrap(),
+            force_any_val::<TxId>(),
+            0,
+        )
+        .unwrap();
+        let local_pub_key = *force_any_val::<DlogProverInput>().public_image().h;
+        let local_box =
+            make_datapoint_box(local_pub_key, 199, 1, &token_ids, *BASE_FEE, height - 9);
+        let mut oracle_boxes = vec![local_box.clone()];
+        oracle_boxes.extend((0..3).map(|i| {
+            make_datapoint_box(
+                force_any_val::<EcPoint>(),
+                200 + i,
+                1,
+                &token_ids,
+                *BASE_FEE,
+                height - 9,
+            )
+        }));
+        let snapshot = PoolSnapshot {
+            version: SNAPSHOT_VERSION,
+            network: "mainnet".to_string(),
+            height,
+            taken_at: 1_700_000_000,
+            pool_box: pool_box.get_box().clone(),
+            refresh_box,
+            oracle_boxes,
+            local_oracle_boxes: vec![local_box],
+            pending_txs: PendingTxs::default(),
+        };
+        let path = std::env::temp_dir().join("oracle_core_test_snapshot.json");
+        snapshot.save(&path).unwrap();
+        let loaded = PoolSnapshot::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let sources = SnapshotSources::parse(
+            &loaded,
+            &oracle_box_wrapper_inputs,
+            &pool_box_wrapper_inputs,
+            &refresh_box_wrapper_inputs,
+        )
+        .unwrap();
+        let controller = PoolController::new(
+            EpochStrategyKind::Standard.strategy(),
+            30,
+            PublishTiming::default(),
+            None,
+        );
+        let replay =
+            replay_snapshot(&loaded, &sources, controller, NetworkPrefix::Mainnet).unwrap();
+        assert_eq!(replay.epoch, 1);
+        assert_eq!(replay.pool_rate, 200);
+        assert_eq!(replay.oracle_boxes.len(), 4);
+        assert_eq!(
+            replay.local_datapoint,
+            Some(format!("Posted {{ epoch_id: 1, height: {} }}", height - 9))
+        );
+        // The epoch is over and the local oracle posted in it
+        assert_eq!(replay.command.as_deref(), Some("Refresh"));
+    }
+}
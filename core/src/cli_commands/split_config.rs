@@ -0,0 +1,93 @@
+//! Split of an oracle config into the pool definition, shared by all the operators of the pool,
+//! and the operator config pointing to it (see `pool_config`).
+
+use std::path::Path;
+
+use derive_more::From;
+use serde_json::json;
+use serde_yaml::Value;
+use thiserror::Error;
+
+use crate::{
+    cli_commands::{json_output, print_json},
+    config_format::{ConfigFormat, ConfigFormatError},
+    pool_config::{
+        pool_config_hash, resolve_path, split, PoolConfigError, POOL_CONFIG_FILE_KEY,
+        POOL_CONFIG_HASH_KEY,
+    },
+};
+
+#[derive(Debug, Error, From)]
+pub enum SplitConfigError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("pool config error: {0}")]
+    PoolConfig(PoolConfigError),
+    #[error("{0}")]
+    Format(ConfigFormatError),
+    #[error("{0} exists already")]
+    #[from(ignore)]
+    FileExists(String),
+}
+
+/// Move the pool keys of the config at `config_path` to `pool_config_file` (relative to the
+/// config directory) and point the config to it. The original config is kept as `<config>.bak`.
+/// Each file is written in the format of its extension (see `config_format`).
+pub fn split_config(config_path: &Path, pool_config_file: &str) -> Result<(), SplitConfigError> {
+    let config_str = std::fs::read_to_string(config_path)?;
+    let config_format = ConfigFormat::of_path(config_path);
+    let config = config_format.parse(&config_str)?;
+    let (mut operator_config, pool_config) = split(&config)?;
+    let pool_config_path = resolve_path(config_path, pool_config_file);
+    if pool_config_path.exists() {
+        return Err(SplitConfigError::FileExists(
+            pool_config_path.display().to_string(),
+        ));
+    }
+    let pool_config_str =
+        ConfigFormat::of_path(&pool_config_path).to_string(&Value::Mapping(pool_config))?;
+    let hash = pool_config_hash(pool_config_str.as_bytes());
+    operator_config.insert(
+        Value::String(POOL_CONFIG_FILE_KEY.to_string()),
+        Value::String(pool_config_file.to_string()),
+    );
+    operator_config.insert(
+        Value::String(POOL_CONFIG_HASH_KEY.to_string()),
+        Value::String(hash.clone()),
+    );
+    std::fs::write(&pool_config_path, pool_config_str)?;
+    std::fs::write(
+        format!("{}.bak", config_path.display()),
+        config_str.as_bytes(),
+    )?;
+    std::fs::write(
+        config_path,
+        config_format.to_string(&Value::Mapping(operator_config))?,
+    )?;
+    if json_output() {
+        print_json(&json!({
+            "pool_config_file": pool_config_path.display().to_string(),
+            "pool_config_hash": hash,
+        }));
+    } else {
+        println!(
+            "Pool definition written to {}, its hash is {}. Share the file and its hash with the \
+            operators of the pool. The original config is kept as {}.bak.",
+            pool_config_path.display(),
+            hash,
+            config_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Print the hash of the pool definition file, to compare with the one of the pool admin
+pub fn print_pool_config_hash(pool_config_path: &Path) -> Result<(), SplitConfigError> {
+    let hash = pool_config_hash(&std::fs::read(pool_config_path)?);
+    if json_output() {
+        print_json(&json!({ "pool_config_hash": hash }));
+    } else {
+        println!("{}", hash);
+    }
+    Ok(())
+}
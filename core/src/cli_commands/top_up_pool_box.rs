@@ -0,0 +1,121 @@
+//! Top-up of the pool box value from the wallet. The pool contract only lets the refresh and update
+//! txs spend the pool box, so the nanoERGs are added by the refresh tx of the current epoch, built
+//! and submitted by this command instead of the collector. `maintain-refresh-box` tops up the refresh
+//! box the same way.
+use derive_more::From;
+use ergo_lib::ergotree_ir::chain::address::{AddressEncoder, AddressEncoderError};
+use ergo_node_interface::node_interface::NodeError;
+use thiserror::Error;
+
+use crate::{
+    audit_log,
+    box_kind::{PoolBox, RefreshBox},
+    cli_commands::{
+        confirm_transaction_preview, print_transaction_aborted, print_transaction_submitted,
+    },
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_state::{OraclePool, StageError},
+    pool_commands::{build_pool_refresh_action, refresh::BoxTopUp, PoolCommandError},
+    prompt::Prompt,
+    tx_preview::format_erg,
+    wallet::WalletDataSource,
+};
+
+#[derive(Debug, Error, From)]
+pub enum TopUpPoolBoxError {
+    #[error("Top-up amount must be greater than zero")]
+    ZeroAmount,
+    #[error(
+        "The box can only be topped up by a refresh tx, the next refresh is possible at height {0}"
+    )]
+    #[from(ignore)]
+    EpochNotOver(u32),
+    #[error("stage error: {0}")]
+    StageError(#[source] StageError),
+    #[error("refresh error: {0}")]
+    PoolCommand(PoolCommandError),
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
+    #[error("AddressEncoder error: {0}")]
+    AddressEncoder(#[source] AddressEncoderError),
+    #[error("Node doesn't have a change address set and change_address isn't set in the config")]
+    NoChangeAddressSetInNode,
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+}
+
+pub fn top_up_pool_box(
+    op: &OraclePool,
+    wallet: &dyn WalletDataSource,
+    nanoergs: u64,
+    prompt: &dyn Prompt,
+) -> Result<(), TopUpPoolBoxError> {
+    if nanoergs == 0 {
+        return Err(TopUpPoolBoxError::ZeroAmount);
+    }
+    let value = *op
+        .get_pool_box_source()
+        .get_pool_box()?
+        .get_box()
+        .value
+        .as_u64();
+    submit_refresh_with_top_up(
+        op,
+        wallet,
+        BoxTopUp {
+            pool: nanoergs,
+            refresh: 0,
+        },
+        &format!(
+            "YOU WILL BE ADDING {} TO THE POOL BOX ({} -> {}) WITH THE REFRESH OF THE EPOCH.",
+            format_erg(nanoergs),
+            format_erg(value),
+            format_erg(value + nanoergs)
+        ),
+        "top_up_pool_box",
+        prompt,
+    )
+}
+
+/// Build the refresh tx of the current epoch adding the `top_up`, and submit it once the `prompt`
+/// confirmed it with the `message`
+pub(crate) fn submit_refresh_with_top_up(
+    op: &OraclePool,
+    wallet: &dyn WalletDataSource,
+    top_up: BoxTopUp,
+    message: &str,
+    action: &str,
+    prompt: &dyn Prompt,
+) -> Result<(), TopUpPoolBoxError> {
+    let height = current_block_height()? as u32;
+    let pool_box = op.get_pool_box_source().get_pool_box()?;
+    let epoch_length = op
+        .get_refresh_box_source()
+        .get_refresh_box()?
+        .contract()
+        .epoch_length() as u32;
+    // The refresh contract requires a pool box older than the epoch length
+    let next_refresh_height = pool_box.get_box().creation_height + epoch_length + 1;
+    if height < next_refresh_height {
+        return Err(TopUpPoolBoxError::EpochNotOver(next_refresh_height));
+    }
+    let change_address_str =
+        get_change_address()?.ok_or(TopUpPoolBoxError::NoChangeAddressSetInNode)?;
+    let (change_address, network_prefix) = {
+        let a = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
+        (a.address(), a.network())
+    };
+    let action_tx = build_pool_refresh_action(op, wallet, height, change_address, top_up)?.tx;
+    if confirm_transaction_preview(prompt, &action_tx, network_prefix, message)? {
+        sign_and_submit_transaction(&action_tx, action)?;
+        print_transaction_submitted(&action_tx, network_prefix);
+    } else {
+        audit_log::record_declined(action, &action_tx);
+        print_transaction_aborted();
+    }
+    Ok(())
+}
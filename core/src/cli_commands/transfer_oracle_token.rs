@@ -8,8 +8,9 @@ use ergo_lib::{
     },
     ergotree_interpreter::sigma_protocol::prover::ContextExtension,
     ergotree_ir::{
-        chain::address::{Address, AddressEncoder, AddressEncoderError},
+        chain::address::{Address, AddressEncoder, AddressEncoderError, NetworkPrefix},
         serialization::SigmaParsingError,
+        sigma_protocol::sigma_boolean::ProveDlog,
     },
     wallet::{
         box_selector::{BoxSelection, BoxSelector, BoxSelectorError, SimpleBoxSelector},
@@ -20,13 +21,21 @@ use ergo_node_interface::node_interface::NodeError;
 use thiserror::Error;
 
 use crate::{
+    audit_log,
     box_kind::{
         make_collected_oracle_box_candidate, make_oracle_box_candidate, OracleBox, OracleBoxWrapper,
     },
-    cli_commands::ergo_explorer_transaction_link,
-    node_interface::{current_block_height, get_wallet_status, sign_and_submit_transaction},
-    oracle_config::BASE_FEE,
-    oracle_state::{LocalDatapointBoxSource, StageError},
+    cli_commands::{
+        confirm_transaction_preview, print_transaction_aborted, print_transaction_submitted,
+    },
+    fee_estimation::apply_estimated_fee,
+    min_box_value::{check_min_box_values, MinBoxValueError},
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_config::{tx_fee, TxFeeAction},
+    oracle_state::{DatapointBoxesSource, LocalDatapointBoxSource, StageError},
+    prompt::Prompt,
     wallet::{WalletDataError, WalletDataSource},
 };
 
@@ -34,43 +43,72 @@ use crate::{
 pub enum TransferOracleTokenActionError {
     #[error("Oracle box should contain exactly 1 reward token. It contains {0} tokens")]
     IncorrectNumberOfRewardTokensInOracleBox(usize),
-    #[error("Destination address not P2PK")]
+    /// The oracle contract requires the oracle token to stay in an oracle box which is spendable
+    /// by the owner of the public key in R4, so the destination can't be a script.
+    #[error("Destination address not P2PK. The oracle token can only be owned by a public key (P2PK address), script (P2S) destinations are not supported by the oracle contract")]
     IncorrectDestinationAddress,
+    #[error("Destination address is for {found:?} network, but the oracle is configured for {expected:?}")]
+    WrongNetwork {
+        expected: NetworkPrefix,
+        found: NetworkPrefix,
+    },
     #[error("box builder error: {0}")]
     ErgoBoxCandidateBuilder(ErgoBoxCandidateBuilderError),
     #[error("stage error: {0}")]
-    StageError(StageError),
+    StageError(#[source] StageError),
     #[error("node error: {0}")]
-    Node(NodeError),
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
     #[error("box selector error: {0}")]
     BoxSelector(BoxSelectorError),
     #[error("Sigma parsing error: {0}")]
     SigmaParse(SigmaParsingError),
     #[error("tx builder error: {0}")]
     TxBuilder(TxBuilderError),
-    #[error("Node doesn't have a change address set")]
+    #[error("Node doesn't have a change address set and change_address isn't set in the config")]
     NoChangeAddressSetInNode,
     #[error("No local datapoint box")]
     NoLocalDatapointBox,
     #[error("AddressEncoder error: {0}")]
-    AddressEncoder(AddressEncoderError),
+    AddressEncoder(#[source] AddressEncoderError),
     #[error("IO error: {0}")]
-    Io(std::io::Error),
+    Io(#[source] std::io::Error),
     #[error("WalletData error: {0}")]
-    WalletData(WalletDataError),
+    WalletData(#[source] WalletDataError),
+    #[error("{0}")]
+    MinBoxValue(MinBoxValueError),
 }
 
 pub fn transfer_oracle_token(
     wallet: &dyn WalletDataSource,
     local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    datapoint_boxes_source: &dyn DatapointBoxesSource,
     rewards_destination_str: String,
+    expected_network: NetworkPrefix,
+    prompt: &dyn Prompt,
 ) -> Result<(), TransferOracleTokenActionError> {
     let rewards_destination =
         AddressEncoder::unchecked_parse_network_address_from_str(&rewards_destination_str)?;
+    if rewards_destination.network() != expected_network {
+        return Err(TransferOracleTokenActionError::WrongNetwork {
+            expected: expected_network,
+            found: rewards_destination.network(),
+        });
+    }
+    let already_holds_oracle_token = match rewards_destination.address() {
+        Address::P2Pk(pk) => destination_holds_oracle_token(datapoint_boxes_source, &pk)?,
+        _ => false,
+    };
+    if already_holds_oracle_token {
+        log::warn!(
+            "Destination {} already owns an oracle box of this pool",
+            rewards_destination_str
+        );
+    }
 
-    let change_address_str = get_wallet_status()?
-        .change_address
-        .ok_or(TransferOracleTokenActionError::NoChangeAddressSetInNode)?;
+    let change_address_str =
+        get_change_address()?.ok_or(TransferOracleTokenActionError::NoChangeAddressSetInNode)?;
 
     let (change_address, network_prefix) = {
         let a = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
@@ -84,23 +122,40 @@ pub fn transfer_oracle_token(
         change_address,
     )?;
 
-    println!(
-        "YOU WILL BE TRANSFERRING YOUR ORACLE TOKEN TO {}. TYPE 'YES' TO INITIATE THE TRANSACTION.",
-        rewards_destination_str
-    );
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-    if input.trim() == "YES" {
-        let tx_id_str = sign_and_submit_transaction(&unsigned_tx)?;
-        println!(
-            "Transaction made. Check status here: {}",
-            ergo_explorer_transaction_link(tx_id_str, network_prefix)
-        );
+    let warning = if already_holds_oracle_token {
+        " WARNING: THE DESTINATION ALREADY OWNS AN ORACLE TOKEN OF THIS POOL, IT CAN ONLY POST WITH ONE OF THEM."
     } else {
-        println!("Aborting the transaction.")
+        ""
+    };
+    if confirm_transaction_preview(
+        prompt,
+        &unsigned_tx,
+        network_prefix,
+        &format!(
+            "YOU WILL BE TRANSFERRING YOUR ORACLE TOKEN TO {}.{}",
+            rewards_destination_str, warning
+        ),
+    )? {
+        sign_and_submit_transaction(&unsigned_tx, "transfer_oracle_token")?;
+        print_transaction_submitted(&unsigned_tx, network_prefix);
+    } else {
+        audit_log::record_declined("transfer_oracle_token", &unsigned_tx);
+        print_transaction_aborted();
     }
     Ok(())
 }
+
+/// Returns `true` if one of the oracle boxes of the pool, posted or collected, is owned by `pk`.
+fn destination_holds_oracle_token(
+    datapoint_boxes_source: &dyn DatapointBoxesSource,
+    pk: &ProveDlog,
+) -> Result<bool, TransferOracleTokenActionError> {
+    Ok(datapoint_boxes_source
+        .get_oracle_boxes()?
+        .iter()
+        .any(|b| &b.public_key() == pk))
+}
+
 fn build_transfer_oracle_token_tx(
     local_datapoint_box_source: &dyn LocalDatapointBoxSource,
     wallet: &dyn WalletDataSource,
@@ -145,7 +200,7 @@ fn build_transfer_oracle_token_tx(
 
         let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
 
-        let target_balance = *BASE_FEE;
+        let target_balance = tx_fee(TxFeeAction::TransferOracleToken);
 
         let box_selector = SimpleBoxSelector::new();
         let selection = box_selector.select(unspent_boxes, target_balance, &[])?;
@@ -155,19 +210,25 @@ fn build_transfer_oracle_token_tx(
             boxes: input_boxes.try_into().unwrap(),
             change_boxes: selection.change_boxes,
         };
+        let output_candidates = vec![oracle_box_candidate];
+        check_min_box_values(&output_candidates)?;
         let mut tx_builder = TxBuilder::new(
             box_selection,
-            vec![oracle_box_candidate],
+            output_candidates,
             height,
             target_balance,
-            change_address,
+            change_address.clone(),
         );
         // The following context value ensures that `outIndex` in the oracle contract is properly set.
         let ctx_ext = ContextExtension {
             values: vec![(0, 0i32.into())].into_iter().collect(),
         };
         tx_builder.set_context_extension(in_oracle_box.get_box().box_id(), ctx_ext);
-        let tx = tx_builder.build()?;
+        let tx = apply_estimated_fee(
+            tx_builder.build()?,
+            TxFeeAction::TransferOracleToken,
+            &change_address,
+        );
         Ok(tx)
     } else {
         Err(TransferOracleTokenActionError::IncorrectDestinationAddress)
@@ -182,9 +243,10 @@ mod tests {
     use super::*;
     use crate::box_kind::{OracleBoxWrapper, OracleBoxWrapperInputs};
     use crate::contracts::oracle::OracleContractParameters;
+    use crate::oracle_config::BASE_FEE;
     use crate::pool_commands::test_utils::{
-        find_input_boxes, generate_token_ids, make_datapoint_box, make_wallet_unspent_box,
-        OracleBoxMock, WalletDataMock,
+        find_input_boxes, generate_token_ids, make_collected_datapoint_box, make_datapoint_box,
+        make_wallet_unspent_box, OracleBoxMock, OracleBoxesMock, WalletDataMock,
     };
     use ergo_lib::chain::ergo_state_context::ErgoStateContext;
     use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
@@ -258,4 +320,53 @@ mod tests {
 
         let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
     }
+
+    #[test]
+    fn test_destination_holds_oracle_token() {
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let posted_box = match OracleBoxWrapper::new(
+            make_datapoint_box(
+                *secret.public_image().h,
+                200,
+                1,
+                &token_ids,
+                BASE_FEE.checked_mul_u32(100).unwrap(),
+                1,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap()
+        {
+            OracleBoxWrapper::Posted(p) => p,
+            OracleBoxWrapper::Collected(_) => panic!("expected posted oracle box"),
+        };
+        let collected_secret = force_any_val::<DlogProverInput>();
+        let collected_box = OracleBoxWrapper::new(
+            make_collected_datapoint_box(
+                *collected_secret.public_image().h,
+                &token_ids,
+                BASE_FEE.checked_mul_u32(100).unwrap(),
+                1,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        let datapoint_stage_mock = OracleBoxesMock {
+            oracle_boxes: vec![OracleBoxWrapper::Posted(posted_box), collected_box],
+        };
+        assert!(
+            destination_holds_oracle_token(&datapoint_stage_mock, &secret.public_image()).unwrap()
+        );
+        assert!(destination_holds_oracle_token(
+            &datapoint_stage_mock,
+            &collected_secret.public_image()
+        )
+        .unwrap());
+        let other_pk = force_any_val::<DlogProverInput>().public_image();
+        assert!(!destination_holds_oracle_token(&datapoint_stage_mock, &other_pk).unwrap());
+    }
 }
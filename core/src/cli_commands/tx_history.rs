@@ -0,0 +1,38 @@
+//! History of the txs signed by the node wallet, from the store (see `tx_history`).
+use crate::{
+    cli_commands::{json_output, print_json},
+    store::{self, StoreError, TxStatus},
+};
+
+/// Txs printed without `limit`
+const DEFAULT_LIMIT: u32 = 50;
+
+pub fn print_tx_history(status: Option<TxStatus>, limit: Option<u32>) -> Result<(), StoreError> {
+    let txs = store::get()?.txs(status, limit.unwrap_or(DEFAULT_LIMIT))?;
+    if json_output() {
+        print_json(&txs);
+        return Ok(());
+    }
+    if txs.is_empty() {
+        println!("No txs");
+        return Ok(());
+    }
+    println!(
+        "{:<64} {:<24} {:>9} {:<10} {:>9} {:>12} {:>6}",
+        "tx id", "action", "submitted", "status", "confirmed", "fee", "resub."
+    );
+    for tx in &txs {
+        println!(
+            "{:<64} {:<24} {:>9} {:<10} {:>9} {:>12} {:>6}",
+            tx.tx_id,
+            tx.action,
+            tx.height.map_or("-".to_string(), |h| h.to_string()),
+            tx.status.name(),
+            tx.confirmed_height
+                .map_or("-".to_string(), |h| h.to_string()),
+            tx.fee,
+            tx.resubmissions
+        );
+    }
+    Ok(())
+}
@@ -8,7 +8,7 @@ use ergo_lib::{
     ergotree_interpreter::sigma_protocol::prover::ContextExtension,
     ergotree_ir::chain::{
         address::{Address, AddressEncoder, AddressEncoderError},
-        ergo_box::{ErgoBox, NonMandatoryRegisterId},
+        ergo_box::ErgoBox,
         token::Token,
     },
     ergotree_ir::serialization::SigmaSerializable,
@@ -23,11 +23,20 @@ use log::{error, info};
 use std::convert::TryInto;
 
 use crate::{
-    box_kind::{make_pool_box_candidate, BallotBox, PoolBox, PoolBoxWrapper, VoteBallotBoxWrapper},
-    cli_commands::ergo_explorer_transaction_link,
+    box_kind::{
+        make_ballot_box_candidate, make_pool_box_candidate, BallotBox, PoolBox, PoolBoxWrapper,
+        VoteBallotBoxWrapper,
+    },
+    cli_commands::{prepare_update::UPDATED_CONFIG_FILE_NAME, print_transaction_submitted},
     contracts::pool::PoolContract,
-    node_interface::{current_block_height, get_wallet_status, sign_and_submit_transaction},
-    oracle_config::{CastBallotBoxVoteParameters, OracleConfig, BASE_FEE, ORACLE_CONFIG},
+    fee_estimation::apply_estimated_fee,
+    min_box_value::{check_min_box_values, MinBoxValueError},
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_config::{
+        tx_fee, CastBallotBoxVoteParameters, OracleConfig, TxFeeAction, ORACLE_CONFIG,
+    },
     oracle_state::{OraclePool, PoolBoxSource, StageError, UpdateBoxSource, VoteBallotBoxesSource},
     wallet::{WalletDataError, WalletDataSource},
 };
@@ -49,23 +58,29 @@ pub enum UpdatePoolError {
     #[error("Update pool: tx context error {0}")]
     TxSigningError(TxSigningError),
     #[error("Update pool: stage error {0}")]
-    StageError(StageError),
+    StageError(#[source] StageError),
     #[error("Update pool: node error {0}")]
-    Node(NodeError),
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
     #[error("No change address in node")]
     NoChangeAddressSetInNode,
     #[error("Update pool: address encoder error {0}")]
-    AddressEncoderError(AddressEncoderError),
+    AddressEncoderError(#[source] AddressEncoderError),
     #[error("Update pool: pool contract error {0}")]
     PoolContractError(crate::contracts::pool::PoolContractError),
     #[error("Update pool: io error {0}")]
-    IoError(std::io::Error),
+    IoError(#[source] std::io::Error),
     #[error("Update pool: yaml error {0}")]
     YamlError(serde_yaml::Error),
     #[error("Update pool: could not find unspent wallot boxes that do not contain ballot tokens")]
     NoUsableWalletBoxes,
     #[error("WalletData error: {0}")]
-    WalletData(WalletDataError),
+    WalletData(#[source] WalletDataError),
+    #[error("Update pool: given pool box hash {0} doesn't match the hash {1} of the updated pool contract")]
+    PoolBoxHashMismatch(String, String),
+    #[error("{0}")]
+    MinBoxValue(MinBoxValueError),
 }
 
 pub fn update_pool(
@@ -73,13 +88,12 @@ pub fn update_pool(
     new_pool_box_hash_str: Option<String>,
     new_reward_tokens: Option<Token>,
 ) -> Result<(), UpdatePoolError> {
-    info!("Opening oracle_config_updated.yaml");
-    let s = std::fs::read_to_string("oracle_config_updated.yaml")?;
+    info!("Opening {}", UPDATED_CONFIG_FILE_NAME);
+    let s = std::fs::read_to_string(UPDATED_CONFIG_FILE_NAME)?;
     let new_oracle_config: OracleConfig = serde_yaml::from_str(&s)?;
     let wallet = crate::wallet::WalletData {};
-    let change_address_str = get_wallet_status()?
-        .change_address
-        .ok_or(UpdatePoolError::NoChangeAddressSetInNode)?;
+    let change_address_str =
+        get_change_address()?.ok_or(UpdatePoolError::NoChangeAddressSetInNode)?;
 
     let (change_address, network_prefix) = {
         let a = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
@@ -102,12 +116,21 @@ pub fn update_pool(
         new_reward_tokens.clone(),
     );
 
-    if new_pool_box_hash_str.is_none() {
-        println!(
-            "Run ./oracle-core --new_pool_box_hash {} to update pool",
-            String::from(new_pool_box_hash)
-        );
-        return Ok(());
+    match new_pool_box_hash_str {
+        None => {
+            println!(
+                "Run ./oracle-core --new_pool_box_hash {} to update pool",
+                String::from(new_pool_box_hash)
+            );
+            return Ok(());
+        }
+        Some(hash_str) if hash_str != String::from(new_pool_box_hash.clone()) => {
+            return Err(UpdatePoolError::PoolBoxHashMismatch(
+                hash_str,
+                String::from(new_pool_box_hash),
+            ));
+        }
+        Some(_) => (),
     }
 
     let tx = build_update_pool_box_tx(
@@ -121,11 +144,8 @@ pub fn update_pool(
         change_address,
     )?;
 
-    let tx_id_str = sign_and_submit_transaction(&tx.spending_tx)?;
-    println!(
-        "Update pool box transaction submitted: view here, {}",
-        ergo_explorer_transaction_link(tx_id_str, network_prefix)
-    );
+    sign_and_submit_transaction(&tx.spending_tx, "update_pool")?;
+    print_transaction_submitted(&tx.spending_tx, network_prefix);
     Ok(())
 }
 
@@ -179,7 +199,7 @@ fn display_update_diff(
 }
 
 #[allow(clippy::too_many_arguments)]
-fn build_update_pool_box_tx(
+pub(crate) fn build_update_pool_box_tx(
     pool_box_source: &dyn PoolBoxSource,
     ballot_boxes: &dyn VoteBallotBoxesSource,
     wallet: &dyn WalletDataSource,
@@ -220,20 +240,23 @@ fn build_update_pool_box_tx(
     });
     sorted_ballot_boxes.reverse();
 
+    // Spend only as many ballot boxes (largest first) as needed to reach the quorum
     let mut votes_cast = 0;
-    let vote_ballot_boxes: Vec<VoteBallotBoxWrapper> = ballot_boxes
-        .get_ballot_boxes()?
+    let vote_ballot_boxes: Vec<VoteBallotBoxWrapper> = sorted_ballot_boxes
         .into_iter()
         .filter(|ballot_box| *ballot_box.vote_parameters() == vote_parameters)
-        .scan(&mut votes_cast, |votes_cast, ballot_box| {
-            **votes_cast += *ballot_box.ballot_token().amount.as_u64();
-            Some(ballot_box)
+        .take_while(|ballot_box| {
+            if votes_cast >= min_votes as u64 {
+                return false;
+            }
+            votes_cast += *ballot_box.ballot_token().amount.as_u64();
+            true
         })
         .collect();
     if votes_cast < min_votes as u64 {
         return Err(UpdatePoolError::NotEnoughVotes(
             min_votes as usize,
-            vote_ballot_boxes.len(),
+            votes_cast as usize,
         ));
     }
 
@@ -272,7 +295,7 @@ fn build_update_pool_box_tx(
         return Err(UpdatePoolError::NoUsableWalletBoxes);
     }
 
-    let target_balance = *BASE_FEE;
+    let target_balance = tx_fee(TxFeeAction::UpdatePool);
     let target_tokens = if reward_tokens.token_id != old_pool_box.reward_token().token_id {
         vec![reward_tokens.clone()]
     } else {
@@ -295,25 +318,22 @@ fn build_update_pool_box_tx(
 
     let mut outputs = vec![pool_box_candidate, update_box_candidate];
     for ballot_box in vote_ballot_boxes.iter() {
-        let mut ballot_box_candidate = ErgoBoxCandidateBuilder::new(
+        outputs.push(make_ballot_box_candidate(
+            ballot_box.contract(),
+            ballot_box.ballot_token_owner(),
+            ballot_box.ballot_token(),
             ballot_box.get_box().value, // value must be preserved or increased
-            ballot_box.contract().ergo_tree(),
             height,
-        );
-        ballot_box_candidate.add_token(ballot_box.ballot_token());
-        ballot_box_candidate.set_register_value(
-            NonMandatoryRegisterId::R4,
-            (*ballot_box.ballot_token_owner().h).clone().into(),
-        );
-        outputs.push(ballot_box_candidate.build()?)
+        )?)
     }
 
+    check_min_box_values(&outputs)?;
     let mut tx_builder = TxBuilder::new(
         box_selection.clone(),
         outputs.clone(),
         height,
-        *BASE_FEE,
-        change_address,
+        target_balance,
+        change_address.clone(),
     );
 
     if reward_tokens.token_id != old_pool_box.reward_token().token_id {
@@ -328,7 +348,11 @@ fn build_update_pool_box_tx(
             },
         )
     }
-    let unsigned_tx = tx_builder.build()?;
+    let unsigned_tx = apply_estimated_fee(
+        tx_builder.build()?,
+        TxFeeAction::UpdatePool,
+        &change_address,
+    );
     Ok(TransactionContext::new(
         unsigned_tx,
         box_selection.boxes.into(),
@@ -393,7 +417,6 @@ mod tests {
         let height = ctx.pre_header.height;
 
         let token_ids = generate_token_ids();
-        dbg!(&token_ids);
         let reward_tokens = Token {
             token_id: token_ids.reward_token_id.clone(),
             amount: 1500.try_into().unwrap(),
@@ -402,7 +425,6 @@ mod tests {
             token_id: force_any_tokenid(),
             amount: force_any_val(),
         };
-        dbg!(&new_reward_tokens);
 
         let default_update_contract_parameters = UpdateContractParameters::default();
         let update_contract_parameters = UpdateContractParameters::build_with(
@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use derive_more::From;
+use serde_json::json;
+use thiserror::Error;
+
+use crate::{
+    cli_commands::{json_output, print_json},
+    config_schema::{config_schema, validate_config_value},
+    oracle_config::merged_config_value,
+};
+
+#[derive(Debug, Error, From)]
+pub enum ValidateConfigError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("failed to read the config: {0}")]
+    Load(anyhow::Error),
+    #[error("the config is not representable as JSON: {0}")]
+    Json(serde_json::Error),
+    #[error("the config has {0} issues")]
+    #[from(ignore)]
+    Invalid(usize),
+}
+
+/// Check the config at `config_path` (with its pool definition and the environment overrides)
+/// against the config schema, and print the issues with their location
+pub fn validate_config(config_path: &Path) -> Result<(), ValidateConfigError> {
+    let config_str = std::fs::read_to_string(config_path)?;
+    let config = serde_json::to_value(merged_config_value(&config_str, config_path)?)?;
+    let issues = validate_config_value(&config);
+    if json_output() {
+        print_json(&json!({
+            "valid": issues.is_empty(),
+            "issues": issues,
+        }));
+    } else if issues.is_empty() {
+        println!("{} is valid", config_path.display());
+    } else {
+        for issue in &issues {
+            println!("{}: {}", issue.location, issue.message);
+        }
+    }
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidateConfigError::Invalid(issues.len()))
+    }
+}
+
+/// Print the JSON Schema of the config file
+pub fn print_config_schema() {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&config_schema()).unwrap()
+    );
+}
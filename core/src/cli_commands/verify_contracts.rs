@@ -0,0 +1,195 @@
+//! Verification of the contract templates of the config against the EIP-23 reference contracts.
+//!
+//! Only the templates (the ErgoTree without its constants) are compared, so pools with custom
+//! parameters (token ids, epoch length, etc.) pass, while modified scripts don't.
+use ergo_lib::{
+    ergo_chain_types::blake2b256_hash,
+    ergotree_ir::{ergo_tree::ErgoTree, serialization::SigmaSerializable},
+};
+use log::{error, warn};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    cli_commands::{json_output, print_json},
+    contracts::{
+        ballot::BallotContractParameters, oracle::OracleContractParameters,
+        pool::PoolContractParameters, refresh::RefreshContractParameters,
+        update::UpdateContractParameters,
+    },
+    oracle_config::OracleConfig,
+};
+
+#[derive(Debug, Error)]
+pub enum VerifyContractsError {
+    #[error("Contract templates differ from EIP-23: {}. Use --allow-custom-contracts to run them anyway", .0.join(", "))]
+    CustomContracts(Vec<String>),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContractTemplateCheck {
+    pub contract: &'static str,
+    /// Base64 encoded blake2b256 hash of the template of the configured contract
+    pub template_hash: String,
+    /// Base64 encoded blake2b256 hash of the template of the EIP-23 contract
+    pub reference_template_hash: String,
+    pub matches: bool,
+}
+
+/// Compare the templates of the configured contracts with the EIP-23 ones (the default contract
+/// parameters).
+pub fn check_contract_templates(config: &OracleConfig) -> Vec<ContractTemplateCheck> {
+    let contracts = [
+        (
+            "pool",
+            config
+                .pool_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .ergo_tree_bytes(),
+            PoolContractParameters::default().ergo_tree_bytes(),
+        ),
+        (
+            "refresh",
+            config
+                .refresh_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .ergo_tree_bytes(),
+            RefreshContractParameters::default().ergo_tree_bytes(),
+        ),
+        (
+            "oracle",
+            config
+                .oracle_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .ergo_tree_bytes(),
+            OracleContractParameters::default().ergo_tree_bytes(),
+        ),
+        (
+            "ballot",
+            config
+                .ballot_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .ergo_tree_bytes(),
+            BallotContractParameters::default().ergo_tree_bytes(),
+        ),
+        (
+            "update",
+            config
+                .update_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .ergo_tree_bytes(),
+            UpdateContractParameters::default().ergo_tree_bytes(),
+        ),
+    ];
+    contracts
+        .iter()
+        .map(|(contract, bytes, reference_bytes)| {
+            let hash = template_hash(bytes);
+            let reference_hash = template_hash(reference_bytes);
+            ContractTemplateCheck {
+                contract: *contract,
+                matches: hash == reference_hash,
+                template_hash: hash,
+                reference_template_hash: reference_hash,
+            }
+        })
+        .collect()
+}
+
+/// Print the result of the template checks (`verify-contracts` command).
+pub fn verify_contracts(config: &OracleConfig) -> Result<(), VerifyContractsError> {
+    let checks = check_contract_templates(config);
+    if json_output() {
+        print_json(&checks);
+    } else {
+        for check in &checks {
+            println!(
+                "{} contract template hash: {} ({})",
+                check.contract,
+                check.template_hash,
+                if check.matches {
+                    "matches EIP-23".to_string()
+                } else {
+                    format!("EIP-23: {}", check.reference_template_hash)
+                }
+            );
+        }
+    }
+    mismatches_to_result(&checks)
+}
+
+/// Startup check. Custom contracts are only logged if `allow_custom_contracts` is set.
+pub fn assert_contract_templates(
+    config: &OracleConfig,
+    allow_custom_contracts: bool,
+) -> Result<(), VerifyContractsError> {
+    let checks = check_contract_templates(config);
+    match mismatches_to_result(&checks) {
+        Err(e) if allow_custom_contracts => {
+            warn!("{}. Running anyway (--allow-custom-contracts)", e);
+            Ok(())
+        }
+        Err(e) => {
+            error!("{}", e);
+            Err(e)
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+fn mismatches_to_result(checks: &[ContractTemplateCheck]) -> Result<(), VerifyContractsError> {
+    let mismatched: Vec<String> = checks
+        .iter()
+        .filter(|c| !c.matches)
+        .map(|c| c.contract.to_string())
+        .collect();
+    if mismatched.is_empty() {
+        Ok(())
+    } else {
+        Err(VerifyContractsError::CustomContracts(mismatched))
+    }
+}
+
+/// Base64 encoded blake2b256 hash of the ErgoTree template (the tree with constants left as
+/// placeholders). Falls back to hashing the whole tree if it can't be parsed.
+pub(crate) fn template_hash(ergo_tree_bytes: &[u8]) -> String {
+    let template_bytes = ErgoTree::sigma_parse_bytes(ergo_tree_bytes)
+        .ok()
+        .and_then(|tree| tree.template_bytes().ok())
+        .unwrap_or_else(|| ergo_tree_bytes.to_vec());
+    base64::encode(blake2b256_hash(&template_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contracts::pool::{PoolContract, PoolContractInputs};
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    #[test]
+    fn test_template_hash_ignores_constants() {
+        let token_ids = generate_token_ids();
+        let pool_contract = PoolContract::build_with(
+            &PoolContractInputs::build_with(
+                PoolContractParameters::default(),
+                token_ids.refresh_nft_token_id,
+                token_ids.update_nft_token_id,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            template_hash(&pool_contract.ergo_tree().sigma_serialize_bytes().unwrap()),
+            template_hash(&PoolContractParameters::default().ergo_tree_bytes())
+        );
+        assert_ne!(
+            template_hash(&PoolContractParameters::default().ergo_tree_bytes()),
+            template_hash(&RefreshContractParameters::default().ergo_tree_bytes())
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! Verification of the pool definition of the config against the live pool. The token ids and the
+//! contract parameters of the config are compared with the ones read from the live boxes (see
+//! `import_pool`). A mismatch is fatal if the daemon would miss the pool boxes or build txs the
+//! contracts reject, and cosmetic if it works around it: the reward token id (read from the pool
+//! box) and the constant indices (a wrong index shows up as a fatal value or contract mismatch).
+
+use ergo_lib::ergo_chain_types::blake2b256_hash;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use serde::Serialize;
+use serde_json::json;
+use thiserror::Error;
+
+use crate::{
+    cli_commands::{
+        import_pool::{fetch_pool_definition, ImportPoolError, PoolDefinition},
+        json_output, print_json,
+    },
+    oracle_config::OracleConfig,
+    pool_archive::default_explorer_url,
+};
+
+#[derive(Debug, Error)]
+pub enum VerifyPoolError {
+    #[error("failed to read the live pool: {0}")]
+    ImportPool(ImportPoolError),
+    #[error("the config differs from the live pool: {0} fatal mismatches")]
+    FatalMismatches(usize),
+    #[error("the config differs from the live pool: {0} cosmetic mismatches (--strict)")]
+    CosmeticMismatches(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Fatal,
+    Cosmetic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mismatch {
+    pub severity: Severity,
+    /// Config key of the field, e.g. `refresh_contract_parameters.epoch_length`
+    pub field: String,
+    pub config: String,
+    pub chain: String,
+}
+
+/// Pool definition of the config
+pub fn config_pool_definition(config: &OracleConfig) -> PoolDefinition {
+    PoolDefinition {
+        oracle_contract_parameters: config
+            .oracle_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .clone(),
+        pool_contract_parameters: config
+            .pool_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .clone(),
+        refresh_contract_parameters: config
+            .refresh_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .clone(),
+        update_contract_parameters: config
+            .update_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .clone(),
+        ballot_contract_parameters: config
+            .ballot_box_wrapper_inputs
+            .contract_inputs
+            .contract_parameters()
+            .clone(),
+        token_ids: config.token_ids.clone(),
+    }
+}
+
+/// Fields of the `config` pool definition differing from the `live` one
+pub fn compare_pool_definitions(config: &PoolDefinition, live: &PoolDefinition) -> Vec<Mismatch> {
+    use Severity::*;
+    let mut mismatches = Vec::new();
+    let mut check = |severity, field: &str, config: String, chain: String| {
+        if config != chain {
+            mismatches.push(Mismatch {
+                severity,
+                field: field.to_string(),
+                config,
+                chain,
+            });
+        }
+    };
+    let token_id = |id: &TokenId| String::from(id.clone());
+    let tree_hash = |bytes: Vec<u8>| base64::encode(blake2b256_hash(&bytes));
+
+    let (c, l) = (&config.token_ids, &live.token_ids);
+    for (field, config_id, live_id) in [
+        (
+            "pool_nft_token_id",
+            &c.pool_nft_token_id,
+            &l.pool_nft_token_id,
+        ),
+        (
+            "refresh_nft_token_id",
+            &c.refresh_nft_token_id,
+            &l.refresh_nft_token_id,
+        ),
+        (
+            "update_nft_token_id",
+            &c.update_nft_token_id,
+            &l.update_nft_token_id,
+        ),
+        ("oracle_token_id", &c.oracle_token_id, &l.oracle_token_id),
+        ("ballot_token_id", &c.ballot_token_id, &l.ballot_token_id),
+    ] {
+        check(
+            Fatal,
+            &format!("token_ids.{}", field),
+            token_id(config_id),
+            token_id(live_id),
+        );
+    }
+    check(
+        Cosmetic,
+        "token_ids.reward_token_id",
+        token_id(&c.reward_token_id),
+        token_id(&l.reward_token_id),
+    );
+
+    let (c, l) = (
+        &config.pool_contract_parameters,
+        &live.pool_contract_parameters,
+    );
+    check(
+        Fatal,
+        "pool_contract_parameters.ergo_tree_bytes",
+        tree_hash(c.ergo_tree_bytes()),
+        tree_hash(l.ergo_tree_bytes()),
+    );
+    check(
+        Cosmetic,
+        "pool_contract_parameters.refresh_nft_index",
+        c.refresh_nft_index().to_string(),
+        l.refresh_nft_index().to_string(),
+    );
+    check(
+        Cosmetic,
+        "pool_contract_parameters.update_nft_index",
+        c.update_nft_index().to_string(),
+        l.update_nft_index().to_string(),
+    );
+
+    let (c, l) = (
+        &config.refresh_contract_parameters,
+        &live.refresh_contract_parameters,
+    );
+    check(
+        Fatal,
+        "refresh_contract_parameters.ergo_tree_bytes",
+        tree_hash(c.ergo_tree_bytes()),
+        tree_hash(l.ergo_tree_bytes()),
+    );
+    for (field, config_value, live_value) in [
+        ("min_data_points", c.min_data_points(), l.min_data_points()),
+        ("buffer_length", c.buffer_length(), l.buffer_length()),
+        (
+            "max_deviation_percent",
+            c.max_deviation_percent(),
+            l.max_deviation_percent(),
+        ),
+        ("epoch_length", c.epoch_length(), l.epoch_length()),
+    ] {
+        check(
+            Fatal,
+            &format!("refresh_contract_parameters.{}", field),
+            config_value.to_string(),
+            live_value.to_string(),
+        );
+    }
+    for (field, config_index, live_index) in [
+        ("pool_nft_index", c.pool_nft_index(), l.pool_nft_index()),
+        (
+            "oracle_token_id_index",
+            c.oracle_token_id_index(),
+            l.oracle_token_id_index(),
+        ),
+        (
+            "min_data_points_index",
+            c.min_data_points_index(),
+            l.min_data_points_index(),
+        ),
+        (
+            "buffer_length_index",
+            c.buffer_length_index(),
+            l.buffer_length_index(),
+        ),
+        (
+            "max_deviation_percent_index",
+            c.max_deviation_percent_index(),
+            l.max_deviation_percent_index(),
+        ),
+        (
+            "epoch_length_index",
+            c.epoch_length_index(),
+            l.epoch_length_index(),
+        ),
+    ] {
+        check(
+            Cosmetic,
+            &format!("refresh_contract_parameters.{}", field),
+            config_index.to_string(),
+            live_index.to_string(),
+        );
+    }
+
+    let (c, l) = (
+        &config.update_contract_parameters,
+        &live.update_contract_parameters,
+    );
+    check(
+        Fatal,
+        "update_contract_parameters.ergo_tree_bytes",
+        tree_hash(c.ergo_tree_bytes()),
+        tree_hash(l.ergo_tree_bytes()),
+    );
+    check(
+        Fatal,
+        "update_contract_parameters.min_votes",
+        c.min_votes().to_string(),
+        l.min_votes().to_string(),
+    );
+    for (field, config_index, live_index) in [
+        ("pool_nft_index", c.pool_nft_index(), l.pool_nft_index()),
+        (
+            "ballot_token_index",
+            c.ballot_token_index(),
+            l.ballot_token_index(),
+        ),
+        ("min_votes_index", c.min_votes_index(), l.min_votes_index()),
+    ] {
+        check(
+            Cosmetic,
+            &format!("update_contract_parameters.{}", field),
+            config_index.to_string(),
+            live_index.to_string(),
+        );
+    }
+
+    let (c, l) = (
+        &config.oracle_contract_parameters,
+        &live.oracle_contract_parameters,
+    );
+    check(
+        Fatal,
+        "oracle_contract_parameters.ergo_tree_bytes",
+        tree_hash(c.ergo_tree_bytes()),
+        tree_hash(l.ergo_tree_bytes()),
+    );
+    check(
+        Fatal,
+        "oracle_contract_parameters.min_storage_rent",
+        c.min_storage_rent.as_u64().to_string(),
+        l.min_storage_rent.as_u64().to_string(),
+    );
+    check(
+        Cosmetic,
+        "oracle_contract_parameters.pool_nft_index",
+        c.pool_nft_index.to_string(),
+        l.pool_nft_index.to_string(),
+    );
+    check(
+        Cosmetic,
+        "oracle_contract_parameters.min_storage_rent_index",
+        c.min_storage_rent_index.to_string(),
+        l.min_storage_rent_index.to_string(),
+    );
+
+    let (c, l) = (
+        &config.ballot_contract_parameters,
+        &live.ballot_contract_parameters,
+    );
+    check(
+        Fatal,
+        "ballot_contract_parameters.ergo_tree_bytes",
+        tree_hash(c.ergo_tree_bytes()),
+        tree_hash(l.ergo_tree_bytes()),
+    );
+    check(
+        Fatal,
+        "ballot_contract_parameters.min_storage_rent",
+        c.min_storage_rent().as_u64().to_string(),
+        l.min_storage_rent().as_u64().to_string(),
+    );
+    check(
+        Cosmetic,
+        "ballot_contract_parameters.min_storage_rent_index",
+        c.min_storage_rent_index().to_string(),
+        l.min_storage_rent_index().to_string(),
+    );
+    check(
+        Cosmetic,
+        "ballot_contract_parameters.update_nft_index",
+        c.update_nft_index().to_string(),
+        l.update_nft_index().to_string(),
+    );
+    mismatches
+}
+
+/// Compare the pool definition of the config with the live pool (`verify-pool` command). Fails on
+/// a fatal mismatch, and on a cosmetic one too if `strict` is set.
+pub fn verify_pool(
+    config: &OracleConfig,
+    explorer_url: Option<String>,
+    strict: bool,
+) -> Result<(), VerifyPoolError> {
+    let explorer_url = explorer_url
+        .unwrap_or_else(|| default_explorer_url(config.oracle_address.network()).to_string());
+    let live = fetch_pool_definition(&explorer_url, &config.token_ids.pool_nft_token_id)
+        .map_err(VerifyPoolError::ImportPool)?;
+    let mismatches = compare_pool_definitions(&config_pool_definition(config), &live);
+    let fatal = mismatches
+        .iter()
+        .filter(|m| m.severity == Severity::Fatal)
+        .count();
+    let cosmetic = mismatches.len() - fatal;
+    if json_output() {
+        print_json(&json!({
+            "mismatches": mismatches,
+            "fatal": fatal,
+            "cosmetic": cosmetic,
+        }));
+    } else if mismatches.is_empty() {
+        println!("The config matches the live pool");
+    } else {
+        for m in &mismatches {
+            let severity = match m.severity {
+                Severity::Fatal => "FATAL",
+                Severity::Cosmetic => "cosmetic",
+            };
+            println!(
+                "{:<8} {}: config {}, chain {}",
+                severity, m.field, m.config, m.chain
+            );
+        }
+    }
+    if fatal > 0 {
+        Err(VerifyPoolError::FatalMismatches(fatal))
+    } else if strict && cosmetic > 0 {
+        Err(VerifyPoolError::CosmeticMismatches(cosmetic))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergo_chain_types::Digest32;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::contracts::{
+        ballot::BallotContractParameters, oracle::OracleContractParameters,
+        pool::PoolContractParameters, refresh::RefreshContractParameters,
+        update::UpdateContractParameters,
+    };
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    fn pool_definition() -> PoolDefinition {
+        PoolDefinition {
+            oracle_contract_parameters: OracleContractParameters::default(),
+            pool_contract_parameters: PoolContractParameters::default(),
+            refresh_contract_parameters: RefreshContractParameters::default(),
+            update_contract_parameters: UpdateContractParameters::default(),
+            ballot_contract_parameters: BallotContractParameters::default(),
+            token_ids: generate_token_ids(),
+        }
+    }
+
+    #[test]
+    fn test_compare_pool_definitions() {
+        let config = pool_definition();
+        assert!(compare_pool_definitions(&config, &config).is_empty());
+
+        let mut live = config.clone();
+        live.token_ids.reward_token_id = force_any_val::<Digest32>().into();
+        let default_update = UpdateContractParameters::default();
+        live.update_contract_parameters = UpdateContractParameters::build_with(
+            default_update.ergo_tree_bytes(),
+            default_update.pool_nft_index(),
+            default_update.ballot_token_index(),
+            default_update.min_votes_index(),
+            default_update.min_votes() + 1,
+        )
+        .unwrap();
+        let mismatches = compare_pool_definitions(&config, &live);
+        assert_eq!(
+            mismatches
+                .iter()
+                .map(|m| (m.severity, m.field.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Severity::Cosmetic, "token_ids.reward_token_id"),
+                (
+                    Severity::Fatal,
+                    "update_contract_parameters.ergo_tree_bytes"
+                ),
+                (Severity::Fatal, "update_contract_parameters.min_votes"),
+            ]
+        );
+        assert_eq!(
+            mismatches[2].chain,
+            (default_update.min_votes() + 1).to_string()
+        );
+    }
+}
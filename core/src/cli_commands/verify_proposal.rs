@@ -0,0 +1,279 @@
+//! Independent verification of an update proposal before voting for it: the proposed pool contract
+//! is rebuilt locally from its parameters and its address hash compared with the hash stated in the
+//! proposal file or voted for in an on-chain ballot box.
+use derive_more::From;
+use ergo_lib::ergotree_ir::{mir::constant::Constant, serialization::SigmaSerializable};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    box_kind::{BallotBox, PoolBox},
+    cli_commands::{
+        json_output,
+        prepare_update::UPDATED_CONFIG_FILE_NAME,
+        print_json,
+        propose_update::{pool_box_address_hash, ProposeUpdateError, SignedUpdateProposal},
+    },
+    contracts::pool::{PoolContract, PoolContractError},
+    oracle_config::OracleConfig,
+    oracle_state::{PoolBoxSource, StageError, VoteBallotBoxesSource},
+};
+
+#[derive(Debug, Error, From)]
+pub enum VerifyProposalError {
+    #[error("Verify proposal: IO error {0}")]
+    Io(#[source] std::io::Error),
+    #[error("Verify proposal: yaml error {0}")]
+    Yaml(serde_yaml::Error),
+    #[error("Verify proposal: proposal error {0}")]
+    Proposal(ProposeUpdateError),
+    #[error("Verify proposal: stage error {0}")]
+    StageError(#[source] StageError),
+    #[error("Verify proposal: pool contract error {0}")]
+    PoolContract(PoolContractError),
+    #[error("Verify proposal: ballot box {0} not found")]
+    BallotBoxNotFound(String),
+    #[error("Verify proposal: hash {expected} doesn't match the hash {rebuilt} of the locally rebuilt pool contract")]
+    HashMismatch { expected: String, rebuilt: String },
+}
+
+/// Value that differs between the current and the proposed pool
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ParameterDiff {
+    pub name: String,
+    pub current: String,
+    pub proposed: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProposalVerification {
+    /// Address of the operator who signed the proposal file
+    pub proposer_address: Option<String>,
+    /// Hash stated in the proposal file or voted for in the ballot box
+    pub expected_hash: Option<String>,
+    /// Hash of the locally rebuilt pool contract
+    pub rebuilt_hash: String,
+    /// `true` if the pool contract changed beyond its constants
+    pub script_changed: bool,
+    pub diff: Vec<ParameterDiff>,
+}
+
+/// Verify the proposal in `proposal_file` (or `oracle_config_updated.yaml` if not given). If
+/// `ballot_box_id` is given, the vote of that ballot box is verified instead of the hash stated in
+/// the proposal file. The signature of the proposal file is checked when it's loaded.
+pub fn verify_proposal(
+    pool_box_source: &dyn PoolBoxSource,
+    ballot_boxes_source: &dyn VoteBallotBoxesSource,
+    proposal_file: Option<String>,
+    ballot_box_id: Option<String>,
+) -> Result<(), VerifyProposalError> {
+    let (new_pool_contract, proposer_address, mut expected_hash, mut new_reward_token) =
+        match proposal_file {
+            Some(file_name) => {
+                let SignedUpdateProposal {
+                    proposal,
+                    proposer_address,
+                    ..
+                } = SignedUpdateProposal::load(&file_name)?;
+                (
+                    proposal.pool_contract()?,
+                    Some(proposer_address),
+                    Some(proposal.new_pool_box_address_hash.clone()),
+                    Some((
+                        String::from(proposal.reward_token_id.clone()),
+                        proposal.reward_token_amount,
+                    )),
+                )
+            }
+            None => {
+                let new_oracle_config: OracleConfig =
+                    serde_yaml::from_str(&std::fs::read_to_string(UPDATED_CONFIG_FILE_NAME)?)?;
+                (
+                    PoolContract::checked_load(
+                        &new_oracle_config.pool_box_wrapper_inputs.contract_inputs,
+                    )?,
+                    None,
+                    None,
+                    None,
+                )
+            }
+        };
+    if let Some(box_id) = ballot_box_id {
+        let ballot_box = ballot_boxes_source
+            .get_ballot_boxes()?
+            .into_iter()
+            .find(|b| String::from(b.get_box().box_id()) == box_id)
+            .ok_or(VerifyProposalError::BallotBoxNotFound(box_id))?;
+        let vote = ballot_box.vote_parameters();
+        expected_hash = Some(vote.pool_box_address_hash.clone());
+        new_reward_token = Some((
+            String::from(vote.reward_token_id.clone()),
+            vote.reward_token_quantity,
+        ));
+    }
+
+    let current_pool_box = pool_box_source.get_pool_box()?;
+    let mut diff = pool_contract_diff(current_pool_box.contract(), &new_pool_contract);
+    if let Some((new_reward_token_id, new_reward_token_amount)) = new_reward_token {
+        let current_reward_token = current_pool_box.reward_token();
+        let current_reward_token_id = String::from(current_reward_token.token_id);
+        if current_reward_token_id != new_reward_token_id {
+            diff.push(ParameterDiff {
+                name: "reward token id".to_string(),
+                current: current_reward_token_id,
+                proposed: new_reward_token_id,
+            });
+        }
+        let current_reward_token_amount = *current_reward_token.amount.as_u64();
+        if current_reward_token_amount != new_reward_token_amount {
+            diff.push(ParameterDiff {
+                name: "reward token amount".to_string(),
+                current: current_reward_token_amount.to_string(),
+                proposed: new_reward_token_amount.to_string(),
+            });
+        }
+    }
+    let rebuilt_hash = pool_box_address_hash(&new_pool_contract);
+    let verification = ProposalVerification {
+        proposer_address,
+        expected_hash: expected_hash.clone().map(String::from),
+        rebuilt_hash: String::from(rebuilt_hash.clone()),
+        script_changed: script_changed(current_pool_box.contract(), &new_pool_contract),
+        diff,
+    };
+    print_verification(&verification);
+    match expected_hash {
+        Some(expected) if expected != rebuilt_hash => Err(VerifyProposalError::HashMismatch {
+            expected: String::from(expected),
+            rebuilt: String::from(rebuilt_hash),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn print_verification(verification: &ProposalVerification) {
+    if json_output() {
+        print_json(verification);
+        return;
+    }
+    if let Some(proposer_address) = &verification.proposer_address {
+        println!("Proposal signed by: {}", proposer_address);
+    }
+    println!(
+        "Rebuilt pool box address hash: {}",
+        verification.rebuilt_hash
+    );
+    match &verification.expected_hash {
+        Some(expected) if *expected == verification.rebuilt_hash => {
+            println!("Proposed pool box address hash: {} (MATCHES)", expected)
+        }
+        Some(expected) => println!(
+            "Proposed pool box address hash: {} (DOES NOT MATCH)",
+            expected
+        ),
+        None => println!("No proposed hash to verify against"),
+    }
+    if verification.script_changed {
+        println!("Pool contract script: CHANGED (beyond its constants)");
+    } else {
+        println!("Pool contract script: unchanged");
+    }
+    if verification.diff.is_empty() {
+        println!("No changed parameters");
+    }
+    for d in &verification.diff {
+        println!("{}: {} -> {}", d.name, d.current, d.proposed);
+    }
+}
+
+/// Lists the constants of the pool contract that differ between the current and the proposed
+/// contract.
+fn pool_contract_diff(current: &PoolContract, proposed: &PoolContract) -> Vec<ParameterDiff> {
+    let current_constants = constants(current);
+    let proposed_constants = constants(proposed);
+    let proposed_parameters = proposed.parameters();
+    let constant_name = |index: usize| {
+        if index == proposed_parameters.refresh_nft_index() {
+            format!("constant {} (refresh NFT id)", index)
+        } else if index == proposed_parameters.update_nft_index() {
+            format!("constant {} (update NFT id)", index)
+        } else {
+            format!("constant {}", index)
+        }
+    };
+    let format_constant = |c: Option<&Constant>| match c {
+        Some(c) => format!("{:?}", c.v),
+        None => "-".to_string(),
+    };
+    (0..current_constants.len().max(proposed_constants.len()))
+        .filter(|i| current_constants.get(*i) != proposed_constants.get(*i))
+        .map(|i| ParameterDiff {
+            name: constant_name(i),
+            current: format_constant(current_constants.get(i)),
+            proposed: format_constant(proposed_constants.get(i)),
+        })
+        .collect()
+}
+
+/// Returns `true` if the proposed contract differs from the current one in more than its constants.
+fn script_changed(current: &PoolContract, proposed: &PoolContract) -> bool {
+    let current_constants = constants(current);
+    if current_constants.len() != constants(proposed).len() {
+        return true;
+    }
+    // Substitute the current constants into the proposed tree, what's left are script changes.
+    let substituted = current_constants
+        .into_iter()
+        .enumerate()
+        .try_fold(proposed.ergo_tree(), |tree, (i, c)| {
+            tree.with_constant(i, c)
+        });
+    match substituted {
+        Ok(tree) => {
+            tree.sigma_serialize_bytes().ok() != current.ergo_tree().sigma_serialize_bytes().ok()
+        }
+        Err(_) => true,
+    }
+}
+
+fn constants(contract: &PoolContract) -> Vec<Constant> {
+    contract.ergo_tree().get_constants().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contracts::pool::{PoolContractInputs, PoolContractParameters};
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    #[test]
+    fn test_pool_contract_diff() {
+        let token_ids = generate_token_ids();
+        let current = PoolContract::checked_load(
+            &PoolContractInputs::build_with(
+                PoolContractParameters::default(),
+                token_ids.refresh_nft_token_id.clone(),
+                token_ids.update_nft_token_id.clone(),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(pool_contract_diff(&current, &current).is_empty());
+        assert!(!script_changed(&current, &current));
+
+        let new_token_ids = generate_token_ids();
+        let proposed = PoolContract::build_with(
+            &PoolContractInputs::build_with(
+                PoolContractParameters::default(),
+                new_token_ids.refresh_nft_token_id,
+                token_ids.update_nft_token_id,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let diff = pool_contract_diff(&current, &proposed);
+        assert_eq!(diff.len(), 1);
+        assert!(diff[0].name.contains("refresh NFT id"));
+        assert!(!script_changed(&current, &proposed));
+    }
+}
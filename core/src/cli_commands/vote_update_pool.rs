@@ -19,14 +19,28 @@ use ergo_lib::{
 use ergo_node_interface::node_interface::NodeError;
 
 use crate::{
+    audit_log,
     box_kind::{make_local_ballot_box_candidate, BallotBox, BallotBoxWrapper},
-    cli_commands::ergo_explorer_transaction_link,
-    contracts::ballot::{
-        BallotContract, BallotContractError, BallotContractInputs, BallotContractParameters,
+    cli_commands::{
+        confirm_transaction_preview,
+        prepare_update::UPDATED_CONFIG_FILE_NAME,
+        print_transaction_aborted, print_transaction_submitted,
+        propose_update::{pool_box_address_hash, ProposeUpdateError, SignedUpdateProposal},
     },
-    node_interface::{current_block_height, get_wallet_status, sign_and_submit_transaction},
-    oracle_config::{TokenIds, BASE_FEE, ORACLE_CONFIG},
+    contracts::{
+        ballot::{
+            BallotContract, BallotContractError, BallotContractInputs, BallotContractParameters,
+        },
+        pool::{PoolContract, PoolContractError},
+    },
+    fee_estimation::apply_estimated_fee,
+    min_box_value::{check_min_box_values, MinBoxValueError},
+    node_interface::{
+        current_block_height, get_change_address, sign_and_submit_transaction, SubmitTxError,
+    },
+    oracle_config::{tx_fee, OracleConfig, TokenIds, TxFeeAction, ORACLE_CONFIG},
     oracle_state::{LocalBallotBoxSource, StageError},
+    prompt::Prompt,
     wallet::{WalletDataError, WalletDataSource},
 };
 use derive_more::From;
@@ -35,11 +49,13 @@ use thiserror::Error;
 #[derive(Debug, Error, From)]
 pub enum VoteUpdatePoolError {
     #[error("Vote update pool: stage error {0}")]
-    StageError(StageError),
+    StageError(#[source] StageError),
     #[error("Vote update pool: ErgoBoxCandidateBuilder error {0}")]
     ErgoBoxCandidateBuilder(ErgoBoxCandidateBuilderError),
     #[error("Vote update pool: node error {0}")]
-    Node(NodeError),
+    Node(#[source] NodeError),
+    #[error("tx submission error: {0}")]
+    SubmitTx(#[source] SubmitTxError),
     #[error("Vote update pool: box selector error {0}")]
     BoxSelector(BoxSelectorError),
     #[error("Vote update pool: tx builder error {0}")]
@@ -47,17 +63,64 @@ pub enum VoteUpdatePoolError {
     #[error("Vote update pool: Node doesn't have a change address set")]
     NoChangeAddressSetInNode,
     #[error("Vote update pool: AddressEncoder error: {0}")]
-    AddressEncoder(AddressEncoderError),
+    AddressEncoder(#[source] AddressEncoderError),
     #[error("Vote update pool: Ballot token owner address not P2PK")]
     IncorrectBallotTokenOwnerAddress,
     #[error("Vote update pool: IO error {0}")]
-    Io(std::io::Error),
+    Io(#[source] std::io::Error),
     #[error("Vote update pool: Digest32 error {0}")]
     Digest(DigestNError),
     #[error("Vote update pool: Ballot contract error {0}")]
     BallotContract(BallotContractError),
     #[error("WalletData error: {0}")]
-    WalletData(WalletDataError),
+    WalletData(#[source] WalletDataError),
+    #[error("Vote update pool: pool contract error {0}")]
+    PoolContract(PoolContractError),
+    #[error("Vote update pool: yaml error {0}")]
+    Yaml(serde_yaml::Error),
+    #[error("Vote update pool: proposal error {0}")]
+    Proposal(ProposeUpdateError),
+    #[error("Vote update pool: reward token amount {0} is too large")]
+    InvalidRewardTokenAmount(u64),
+    #[error("Vote update pool: new pool box address hash {voted} doesn't match the hash {expected} of the pool contract in oracle_config_updated.yaml")]
+    PoolBoxAddressHashMismatch { voted: String, expected: String },
+    #[error("{0}")]
+    MinBoxValue(MinBoxValueError),
+}
+
+/// Vote for the update described in the given proposal file (created with `propose-update`). The
+/// signature of the proposal is checked and the new pool box address hash is recomputed from the
+/// proposal's contract parameters first.
+pub fn vote_update_pool_with_proposal(
+    wallet: &dyn WalletDataSource,
+    local_ballot_box_source: &dyn LocalBallotBoxSource,
+    proposal_file_name: String,
+    prompt: &dyn Prompt,
+) -> Result<(), VoteUpdatePoolError> {
+    let SignedUpdateProposal {
+        proposal,
+        proposer_address,
+        ..
+    } = SignedUpdateProposal::load(&proposal_file_name)?;
+    validate_new_pool_box_address_hash(
+        &proposal.new_pool_box_address_hash,
+        &proposal.pool_contract()?,
+    )?;
+    let reward_token_amount = u32::try_from(proposal.reward_token_amount)
+        .map_err(|_| VoteUpdatePoolError::InvalidRewardTokenAmount(proposal.reward_token_amount))?;
+    cast_vote(
+        wallet,
+        local_ballot_box_source,
+        proposal.new_pool_box_address_hash,
+        proposal.reward_token_id,
+        reward_token_amount,
+        proposal.update_box_creation_height,
+        &format!(
+            "matches the proposal parameters, signed by {}",
+            proposer_address
+        ),
+        prompt,
+    )
 }
 
 pub fn vote_update_pool(
@@ -67,17 +130,58 @@ pub fn vote_update_pool(
     reward_token_id_str: String,
     reward_token_amount: u32,
     update_box_creation_height: u32,
+    prompt: &dyn Prompt,
+) -> Result<(), VoteUpdatePoolError> {
+    let new_pool_box_address_hash = Digest32::try_from(new_pool_box_address_hash_str)?;
+    let hash_verified = if std::path::Path::new(UPDATED_CONFIG_FILE_NAME).exists() {
+        let new_oracle_config: OracleConfig =
+            serde_yaml::from_str(&std::fs::read_to_string(UPDATED_CONFIG_FILE_NAME)?)?;
+        let new_pool_contract =
+            PoolContract::checked_load(&new_oracle_config.pool_box_wrapper_inputs.contract_inputs)?;
+        validate_new_pool_box_address_hash(&new_pool_box_address_hash, &new_pool_contract)?;
+        true
+    } else {
+        log::warn!(
+            "{} not found, can't verify the new pool box address hash",
+            UPDATED_CONFIG_FILE_NAME
+        );
+        false
+    };
+    let reward_token_id = TokenId::from_base64(&reward_token_id_str)?;
+    cast_vote(
+        wallet,
+        local_ballot_box_source,
+        new_pool_box_address_hash,
+        reward_token_id,
+        reward_token_amount,
+        update_box_creation_height,
+        if hash_verified {
+            "matches the locally prepared update"
+        } else {
+            "NOT VERIFIED"
+        },
+        prompt,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cast_vote(
+    wallet: &dyn WalletDataSource,
+    local_ballot_box_source: &dyn LocalBallotBoxSource,
+    new_pool_box_address_hash: Digest32,
+    reward_token_id: TokenId,
+    reward_token_amount: u32,
+    update_box_creation_height: u32,
+    hash_verification_note: &str,
+    prompt: &dyn Prompt,
 ) -> Result<(), VoteUpdatePoolError> {
-    let change_address_str = get_wallet_status()?
-        .change_address
-        .ok_or(VoteUpdatePoolError::NoChangeAddressSetInNode)?;
+    let change_address_str =
+        get_change_address()?.ok_or(VoteUpdatePoolError::NoChangeAddressSetInNode)?;
 
     let change_network_address =
         AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
     let network_prefix = change_network_address.network();
     let height = current_block_height()? as u32;
-    let new_pool_box_address_hash = Digest32::try_from(new_pool_box_address_hash_str)?;
-    let reward_token_id = TokenId::from_base64(&reward_token_id_str)?;
     let unsigned_tx = if let Some(local_ballot_box) = local_ballot_box_source.get_ballot_box()? {
         // Note: the ballot box contains the ballot token, but the box is guarded by the contract,
         // which stipulates that the address in R4 is the 'owner' of the token
@@ -109,27 +213,43 @@ pub fn vote_update_pool(
             change_network_address.address(),
         )?
     };
-    println!(
-        "YOU WILL BE CASTING A VOTE FOR THE FOLLOWING ITEMS:\
-           - Hash of new pool box address: {}\
+    if confirm_transaction_preview(
+        prompt,
+        &unsigned_tx,
+        network_prefix,
+        &format!(
+            "YOU WILL BE CASTING A VOTE FOR THE FOLLOWING ITEMS:\
+           - Hash of new pool box address: {} ({})\
            - Reward token Id: {}\
            - Reward token amount: {}\
-         TYPE 'YES' TO INITIATE THE TRANSACTION.
         ",
-        String::from(new_pool_box_address_hash),
-        String::from(reward_token_id),
-        reward_token_amount,
-    );
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-    if input.trim_end() == "YES" {
-        let tx_id_str = sign_and_submit_transaction(&unsigned_tx)?;
-        println!(
-            "Transaction made. Check status here: {}",
-            ergo_explorer_transaction_link(tx_id_str, network_prefix)
-        );
+            String::from(new_pool_box_address_hash),
+            hash_verification_note,
+            String::from(reward_token_id),
+            reward_token_amount,
+        ),
+    )? {
+        sign_and_submit_transaction(&unsigned_tx, "vote_update_pool")?;
+        print_transaction_submitted(&unsigned_tx, network_prefix);
     } else {
-        println!("Aborting the transaction.")
+        audit_log::record_declined("vote_update_pool", &unsigned_tx);
+        print_transaction_aborted();
+    }
+    Ok(())
+}
+
+/// Checks that the voted hash is the hash of the new pool contract's ErgoTree, guarding against
+/// voting for a typo or a contract other than the one prepared with `prepare-update`.
+fn validate_new_pool_box_address_hash(
+    new_pool_box_address_hash: &Digest32,
+    new_pool_contract: &PoolContract,
+) -> Result<(), VoteUpdatePoolError> {
+    let expected = pool_box_address_hash(new_pool_contract);
+    if &expected != new_pool_box_address_hash {
+        return Err(VoteUpdatePoolError::PoolBoxAddressHashMismatch {
+            voted: String::from(new_pool_box_address_hash.clone()),
+            expected: String::from(expected),
+        });
     }
     Ok(())
 }
@@ -160,27 +280,34 @@ fn build_tx_with_existing_ballot_box(
         in_ballot_box.get_box().value,
         update_box_creation_height,
     )?;
+    let tx_fee = tx_fee(TxFeeAction::VoteUpdatePool);
     let box_selector = SimpleBoxSelector::new();
-    let selection = box_selector.select(unspent_boxes, *BASE_FEE, &[])?;
+    let selection = box_selector.select(unspent_boxes, tx_fee, &[])?;
     let mut input_boxes = vec![in_ballot_box.get_box().clone()];
     input_boxes.append(selection.boxes.as_vec().clone().as_mut());
     let box_selection = BoxSelection {
         boxes: input_boxes.try_into().unwrap(),
         change_boxes: selection.change_boxes,
     };
+    let output_candidates = vec![ballot_box_candidate];
+    check_min_box_values(&output_candidates)?;
     let mut tx_builder = TxBuilder::new(
         box_selection,
-        vec![ballot_box_candidate],
+        output_candidates,
         height,
-        *BASE_FEE,
-        change_address,
+        tx_fee,
+        change_address.clone(),
     );
     // The following context value ensures that `outIndex` in the ballot contract is properly set.
     let ctx_ext = ContextExtension {
         values: vec![(0, 0i32.into())].into_iter().collect(),
     };
     tx_builder.set_context_extension(in_ballot_box.get_box().box_id(), ctx_ext);
-    let tx = tx_builder.build()?;
+    let tx = apply_estimated_fee(
+        tx_builder.build()?,
+        TxFeeAction::VoteUpdatePool,
+        &change_address,
+    );
     Ok(tx)
 }
 
@@ -223,27 +350,34 @@ fn build_tx_for_first_ballot_box(
             out_ballot_box_value,
             height,
         )?;
+        let tx_fee = tx_fee(TxFeeAction::VoteUpdatePool);
         let box_selector = SimpleBoxSelector::new();
-        let selection_target_balance = out_ballot_box_value.checked_add(&*BASE_FEE).unwrap();
+        let selection_target_balance = out_ballot_box_value.checked_add(&tx_fee).unwrap();
         let selection =
             box_selector.select(unspent_boxes, selection_target_balance, &[ballot_token])?;
         let box_selection = BoxSelection {
             boxes: selection.boxes.as_vec().clone().try_into().unwrap(),
             change_boxes: selection.change_boxes,
         };
+        let output_candidates = vec![ballot_box_candidate];
+        check_min_box_values(&output_candidates)?;
         let mut tx_builder = TxBuilder::new(
             box_selection,
-            vec![ballot_box_candidate],
+            output_candidates,
             height,
-            *BASE_FEE,
-            change_address,
+            tx_fee,
+            change_address.clone(),
         );
         // The following context value ensures that `outIndex` in the ballot contract is properly set.
         let ctx_ext = ContextExtension {
             values: vec![(0, 0i32.into())].into_iter().collect(),
         };
         tx_builder.set_context_extension(selection.boxes.first().box_id(), ctx_ext);
-        let tx = tx_builder.build()?;
+        let tx = apply_estimated_fee(
+            tx_builder.build()?,
+            TxFeeAction::VoteUpdatePool,
+            &change_address,
+        );
         Ok(tx)
     } else {
         Err(VoteUpdatePoolError::IncorrectBallotTokenOwnerAddress)
@@ -256,13 +390,14 @@ mod tests {
 
     use ergo_lib::{
         chain::{ergo_state_context::ErgoStateContext, transaction::TxId},
-        ergo_chain_types::Digest32,
+        ergo_chain_types::{blake2b256_hash, Digest32},
         ergotree_interpreter::sigma_protocol::private_input::DlogProverInput,
         ergotree_ir::chain::{
             address::{Address, AddressEncoder},
             ergo_box::{box_value::BoxValue, BoxTokens, ErgoBox},
             token::{Token, TokenId},
         },
+        ergotree_ir::serialization::SigmaSerializable,
         wallet::{signing::TransactionContext, Wallet},
     };
     use sigma_test_util::force_any_val;
@@ -270,6 +405,7 @@ mod tests {
     use crate::{
         box_kind::{make_local_ballot_box_candidate, BallotBoxWrapper, BallotBoxWrapperInputs},
         contracts::ballot::{BallotContract, BallotContractInputs, BallotContractParameters},
+        contracts::pool::{PoolContract, PoolContractInputs, PoolContractParameters},
         oracle_config::BASE_FEE,
         pool_commands::test_utils::{
             find_input_boxes, generate_token_ids, make_wallet_unspent_box, WalletDataMock,
@@ -277,7 +413,10 @@ mod tests {
         wallet::WalletDataSource,
     };
 
-    use super::{build_tx_for_first_ballot_box, build_tx_with_existing_ballot_box};
+    use super::{
+        build_tx_for_first_ballot_box, build_tx_with_existing_ballot_box,
+        validate_new_pool_box_address_hash, VoteUpdatePoolError,
+    };
 
     #[test]
     fn test_vote_update_pool_no_existing_ballot_box() {
@@ -419,4 +558,22 @@ mod tests {
 
         let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
     }
+
+    #[test]
+    fn test_validate_new_pool_box_address_hash() {
+        let token_ids = generate_token_ids();
+        let pool_contract_inputs = PoolContractInputs::build_with(
+            PoolContractParameters::default(),
+            token_ids.refresh_nft_token_id,
+            token_ids.update_nft_token_id,
+        )
+        .unwrap();
+        let pool_contract = PoolContract::checked_load(&pool_contract_inputs).unwrap();
+        let hash = blake2b256_hash(&pool_contract.ergo_tree().sigma_serialize_bytes().unwrap());
+        assert!(validate_new_pool_box_address_hash(&hash, &pool_contract).is_ok());
+        assert!(matches!(
+            validate_new_pool_box_address_hash(&force_any_val::<Digest32>(), &pool_contract),
+            Err(VoteUpdatePoolError::PoolBoxAddressHashMismatch { .. })
+        ));
+    }
 }
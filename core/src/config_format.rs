@@ -0,0 +1,110 @@
+//! Formats of the config files. The config and the pool definition are YAML, or TOML if their file
+//! name ends with `.toml`. A TOML file is parsed into the same value as the YAML one, so it goes
+//! through the same pool definition merge, environment overrides, serde structs and validation.
+
+use std::path::Path;
+
+use derive_more::From;
+use serde_yaml::{Mapping, Value};
+use thiserror::Error;
+
+#[derive(Debug, Error, From)]
+pub enum ConfigFormatError {
+    #[error("YAML error: {0}")]
+    Yaml(serde_yaml::Error),
+    #[error("TOML error: {0}")]
+    TomlDe(toml::de::Error),
+    #[error("TOML error: {0}")]
+    TomlSer(toml::ser::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Format of the file at `path`, from its extension
+    pub fn of_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    pub fn parse(self, s: &str) -> Result<Value, ConfigFormatError> {
+        match self {
+            ConfigFormat::Yaml => Ok(serde_yaml::from_str(s)?),
+            ConfigFormat::Toml => {
+                let value: toml::Value = toml::from_str(s)?;
+                Ok(serde_yaml::to_value(value)?)
+            }
+        }
+    }
+
+    /// Serialize `value`. TOML has no null, the null fields are left out.
+    pub fn to_string(self, value: &Value) -> Result<String, ConfigFormatError> {
+        match self {
+            ConfigFormat::Yaml => Ok(serde_yaml::to_string(value)?),
+            ConfigFormat::Toml => {
+                // Serialized through `toml::Value`, which writes the tables after the values
+                let value = toml::Value::try_from(without_nulls(value))?;
+                Ok(toml::to_string_pretty(&value)?)
+            }
+        }
+    }
+}
+
+fn without_nulls(value: &Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k.clone(), without_nulls(v)))
+                .collect::<Mapping>(),
+        ),
+        Value::Sequence(values) => Value::Sequence(values.iter().map(without_nulls).collect()),
+        _ => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_config() {
+        assert_eq!(
+            ConfigFormat::of_path(Path::new("/etc/oracle/oracle_config.TOML")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::of_path(Path::new("oracle_config.yaml")),
+            ConfigFormat::Yaml
+        );
+        let yaml = ConfigFormat::Yaml
+            .parse(
+                "node_ip: 127.0.0.1\nnode_port: 9053\nlog_level: ~\n\
+                reward_extraction:\n  min_reward_tokens: 100\n  dry_run: true\n\
+                core_api_cors_allowed_origins: [a, b]\n",
+            )
+            .unwrap();
+        let toml = ConfigFormat::Toml
+            .parse(
+                "node_ip = \"127.0.0.1\"\nnode_port = 9053\n\
+                core_api_cors_allowed_origins = [\"a\", \"b\"]\n\
+                [reward_extraction]\nmin_reward_tokens = 100\ndry_run = true\n",
+            )
+            .unwrap();
+        // The key order of the formats differs
+        let json = |value: &Value| serde_json::to_value(value).unwrap();
+        assert_eq!(json(&toml), json(&without_nulls(&yaml)));
+        let written = ConfigFormat::Toml.to_string(&yaml).unwrap();
+        assert_eq!(
+            json(&ConfigFormat::Toml.parse(&written).unwrap()),
+            json(&toml)
+        );
+    }
+}
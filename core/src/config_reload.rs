@@ -0,0 +1,170 @@
+//! Reloading the oracle config while the daemon runs. The config file is checked for changes on
+//! every iteration of the main loop, the data source and the publication, extraction and epoch
+//! settings are applied without a restart. Any other change rejects the whole reload (the running
+//! config is kept), the token ids and contracts of the pool can't be changed at all.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::anyhow;
+use serde_yaml::Mapping;
+use thiserror::Error;
+
+use crate::oracle_config::OracleConfig;
+
+/// Fields of the oracle config applied without a restart
+pub const RELOADABLE_FIELDS: &[&str] = &[
+    "data_point_source",
+    "data_point_source_custom_script",
+    "reward_extraction",
+    "epoch_strategy",
+    "publish_timing",
+    "skip_publish",
+    "collector_election",
+];
+
+/// Fields identifying the pool and the oracle
+const POOL_FIELDS: &[&str] = &[
+    "oracle_address",
+    "token_ids",
+    "oracle_contract_parameters",
+    "pool_contract_parameters",
+    "refresh_contract_parameters",
+    "update_contract_parameters",
+    "ballot_contract_parameters",
+    "buyback_token_id",
+];
+
+#[derive(Debug, Error)]
+pub enum ConfigReloadError {
+    #[error("failed to load the config: {0}")]
+    Load(anyhow::Error),
+    #[error(
+        "the token ids and contracts of the pool can't be changed on a running oracle, changed: {}",
+        .0.join(", ")
+    )]
+    PoolChanged(Vec<String>),
+    #[error("changing {} requires a restart", .0.join(", "))]
+    RestartRequired(Vec<String>),
+}
+
+/// Names of the fields `new_config` changes. Fails if any of them can't be applied by the running
+/// daemon.
+pub fn reloadable_changes(
+    config: &OracleConfig,
+    new_config: &OracleConfig,
+) -> Result<Vec<String>, ConfigReloadError> {
+    let fields = |c: &OracleConfig| -> Result<Mapping, ConfigReloadError> {
+        serde_yaml::to_value(c)
+            .map_err(|e| ConfigReloadError::Load(e.into()))?
+            .as_mapping()
+            .cloned()
+            .ok_or_else(|| ConfigReloadError::Load(anyhow!("config is not a mapping")))
+    };
+    let (fields, new_fields) = (fields(config)?, fields(new_config)?);
+    let mut changed: Vec<String> = fields
+        .iter()
+        .chain(new_fields.iter())
+        .filter(|(key, _)| fields.get(key) != new_fields.get(key))
+        .filter_map(|(key, _)| key.as_str().map(String::from))
+        .collect();
+    changed.sort();
+    changed.dedup();
+    let pool_changes: Vec<String> = changed
+        .iter()
+        .filter(|f| POOL_FIELDS.contains(&f.as_str()))
+        .cloned()
+        .collect();
+    if !pool_changes.is_empty() {
+        return Err(ConfigReloadError::PoolChanged(pool_changes));
+    }
+    let restart_changes: Vec<String> = changed
+        .iter()
+        .filter(|f| !RELOADABLE_FIELDS.contains(&f.as_str()))
+        .cloned()
+        .collect();
+    if !restart_changes.is_empty() {
+        return Err(ConfigReloadError::RestartRequired(restart_changes));
+    }
+    Ok(changed)
+}
+
+/// Watches the modification time of the config file
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> Self {
+        ConfigWatcher {
+            path: path.to_path_buf(),
+            modified: modified(path),
+        }
+    }
+
+    /// The config read from the file if it was modified since the last call
+    pub fn poll(&mut self) -> Option<Result<OracleConfig, ConfigReloadError>> {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(self.reload())
+    }
+
+    /// The config read from the file, whether it was modified or not
+    pub fn reload(&mut self) -> Result<OracleConfig, ConfigReloadError> {
+        self.modified = modified(&self.path);
+        std::fs::read_to_string(&self.path)
+            .map_err(|e| ConfigReloadError::Load(e.into()))
+            .and_then(|s| OracleConfig::load_from_str(&s).map_err(ConfigReloadError::Load))
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli_commands::bootstrap::BootstrapConfig;
+    use crate::oracle_config::SkipPublishConfig;
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    #[test]
+    fn test_reloadable_changes() {
+        let config =
+            OracleConfig::create(BootstrapConfig::default(), generate_token_ids(), 0).unwrap();
+        assert!(reloadable_changes(&config, &config.clone())
+            .unwrap()
+            .is_empty());
+
+        let mut new_config = config.clone();
+        new_config.skip_publish = Some(SkipPublishConfig {
+            min_change_percent: 0.5,
+            max_skipped_epochs: 3,
+        });
+        new_config.data_point_source_custom_script = Some("./datapoint.sh".to_string());
+        assert_eq!(
+            reloadable_changes(&config, &new_config).unwrap(),
+            vec![
+                "data_point_source_custom_script".to_string(),
+                "skip_publish".to_string()
+            ]
+        );
+
+        new_config.node_port += 1;
+        assert!(matches!(
+            reloadable_changes(&config, &new_config),
+            Err(ConfigReloadError::RestartRequired(fields)) if fields == vec!["node_port".to_string()]
+        ));
+
+        new_config.token_ids = generate_token_ids();
+        assert!(matches!(
+            reloadable_changes(&config, &new_config),
+            Err(ConfigReloadError::PoolChanged(fields)) if fields.contains(&"token_ids".to_string())
+        ));
+    }
+}
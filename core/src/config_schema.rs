@@ -0,0 +1,224 @@
+//! JSON Schema of the config file, derived from the serde structs of the config (see `serde`), and
+//! the checks of the `validate-config` command. A config is checked for the placeholders left from
+//! the config template and against the schema first (types, required fields, unknown fields), then for the values the schema can't express: the token ids
+//! must be base64 encoded 32 byte ids and the constant indices of the contract parameters must be
+//! in range of the constants of their contract. A config passing both is finally loaded as the
+//! daemon loads it, to catch the contract constant mismatches.
+
+use ergo_lib::ergotree_ir::ergo_tree::ErgoTree;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use schemars::schema::RootSchema;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::contracts::refresh::RefreshContractParameters;
+use crate::oracle_config::OracleConfig;
+use crate::serde::OracleConfigSerde;
+
+/// Contract parameter sections of the config
+const CONTRACT_PARAMETERS_KEYS: [&str; 5] = [
+    "oracle_contract_parameters",
+    "pool_contract_parameters",
+    "refresh_contract_parameters",
+    "update_contract_parameters",
+    "ballot_contract_parameters",
+];
+
+/// Start of the placeholders of the config template (see `generate-config`)
+pub const PLACEHOLDER_PREFIX: &str = "<REPLACE";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigIssue {
+    /// JSON pointer of the field, e.g. `/refresh_contract_parameters/epoch_length`
+    pub location: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn new(location: String, message: String) -> Self {
+        ConfigIssue {
+            location: if location.is_empty() {
+                "/".to_string()
+            } else {
+                location
+            },
+            message,
+        }
+    }
+}
+
+pub fn config_schema() -> RootSchema {
+    schemars::schema_for!(OracleConfigSerde)
+}
+
+/// Issues of the `config` (with its pool definition merged), an empty list if it is valid
+pub fn validate_config_value(config: &Value) -> Vec<ConfigIssue> {
+    let schema = serde_json::to_value(config_schema()).unwrap();
+    let compiled = jsonschema::JSONSchema::compile(&schema).expect("invalid config schema");
+    let mut issues = Vec::new();
+    find_placeholders(config, String::new(), &mut issues);
+    if let Err(errors) = compiled.validate(config) {
+        // A placeholder of a number field isn't reported twice
+        let placeholders: Vec<String> = issues.iter().map(|i| i.location.clone()).collect();
+        issues.extend(
+            errors
+                .map(|e| ConfigIssue::new(e.instance_path.to_string(), e.to_string()))
+                .filter(|issue| !placeholders.contains(&issue.location)),
+        );
+    }
+    if !issues.is_empty() {
+        return issues;
+    }
+    let mut issues = check_token_ids(config);
+    issues.extend(check_constant_indices(config));
+    if issues.is_empty() {
+        if let Err(e) = serde_json::from_value::<OracleConfig>(config.clone()) {
+            issues.push(ConfigIssue::new(String::new(), e.to_string()));
+        }
+    }
+    issues
+}
+
+fn find_placeholders(value: &Value, location: String, issues: &mut Vec<ConfigIssue>) {
+    match value {
+        Value::String(s) if s.starts_with(PLACEHOLDER_PREFIX) => issues.push(ConfigIssue::new(
+            location,
+            format!("placeholder {} to fill in", s),
+        )),
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                find_placeholders(field, format!("{}/{}", location, name), issues);
+            }
+        }
+        Value::Array(values) => {
+            for (i, v) in values.iter().enumerate() {
+                find_placeholders(v, format!("{}/{}", location, i), issues);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn check_token_ids(config: &Value) -> Vec<ConfigIssue> {
+    let mut token_ids: Vec<(String, &Value)> = config["token_ids"]
+        .as_object()
+        .map(|ids| {
+            ids.iter()
+                .map(|(name, id)| (format!("/token_ids/{}", name), id))
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(id) = config.get("buyback_token_id").filter(|id| !id.is_null()) {
+        token_ids.push(("/buyback_token_id".to_string(), id));
+    }
+    token_ids
+        .into_iter()
+        .filter_map(|(location, id)| {
+            let id = id.as_str().unwrap_or_default();
+            match base64::decode(id) {
+                Ok(bytes) if bytes.len() == 32 => None,
+                Ok(bytes) => Some(ConfigIssue::new(
+                    location,
+                    format!("a token id is 32 bytes long, {} holds {}", id, bytes.len()),
+                )),
+                Err(e) => Some(ConfigIssue::new(
+                    location,
+                    format!("{} is not a base64 encoded token id: {}", id, e),
+                )),
+            }
+        })
+        .collect()
+}
+
+fn check_constant_indices(config: &Value) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    for key in CONTRACT_PARAMETERS_KEYS {
+        let parameters = match config[key].as_object() {
+            Some(parameters) => parameters,
+            None => continue,
+        };
+        let ergo_tree_bytes = match parameters.get("ergo_tree_bytes").and_then(Value::as_str) {
+            Some(bytes) => base16::decode(bytes).map_err(|e| e.to_string()),
+            // The EIP-23 refresh contract
+            None => Ok(RefreshContractParameters::default().ergo_tree_bytes()),
+        };
+        let constants_len = ergo_tree_bytes.and_then(|bytes| {
+            ErgoTree::sigma_parse_bytes(&bytes)
+                .map_err(|e| e.to_string())
+                .and_then(|tree| tree.constants_len().map_err(|e| format!("{:?}", e)))
+        });
+        let constants_len = match constants_len {
+            Ok(len) => len,
+            Err(e) => {
+                issues.push(ConfigIssue::new(
+                    format!("/{}/ergo_tree_bytes", key),
+                    format!("invalid ergo tree: {}", e),
+                ));
+                continue;
+            }
+        };
+        for (name, index) in parameters {
+            match index.as_u64() {
+                Some(index) if name.ends_with("_index") && index as usize >= constants_len => {
+                    issues.push(ConfigIssue::new(
+                        format!("/{}/{}", key, name),
+                        format!(
+                            "index {} is out of range, the contract has {} constants",
+                            index, constants_len
+                        ),
+                    ))
+                }
+                _ => (),
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli_commands::bootstrap::BootstrapConfig;
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    #[test]
+    fn test_validate_config() {
+        let config =
+            OracleConfig::create(BootstrapConfig::default(), generate_token_ids(), 0).unwrap();
+        let valid = serde_json::to_value(&config).unwrap();
+        assert_eq!(validate_config_value(&valid), vec![]);
+
+        // Typo
+        let mut invalid = valid.clone();
+        let port = invalid["node_port"].take();
+        invalid.as_object_mut().unwrap().remove("node_port");
+        invalid["node_prot"] = port;
+        let issues = validate_config_value(&invalid);
+        assert!(!issues.is_empty());
+        assert!(issues.iter().all(|issue| issue.location == "/"));
+
+        let mut invalid = valid.clone();
+        invalid["refresh_contract_parameters"]["epoch_length"] = Value::from("30");
+        let issues = validate_config_value(&invalid);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(
+            issues[0].location,
+            "/refresh_contract_parameters/epoch_length"
+        );
+
+        let mut invalid = valid;
+        invalid["token_ids"]["oracle_token_id"] = Value::from("aGVsbG8=");
+        invalid["pool_contract_parameters"]["update_nft_index"] = Value::from(99);
+        let issues = validate_config_value(&invalid);
+        assert_eq!(
+            issues
+                .iter()
+                .map(|issue| issue.location.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "/token_ids/oracle_token_id",
+                "/pool_contract_parameters/update_nft_index"
+            ]
+        );
+    }
+}
@@ -1,4 +1,9 @@
 pub mod ballot;
+pub mod buyback;
+pub mod cache;
+#[cfg(feature = "compile-contracts")]
+pub mod compile;
+pub mod constants;
 pub mod oracle;
 pub mod pool;
 pub mod refresh;
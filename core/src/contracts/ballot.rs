@@ -14,6 +14,8 @@ use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
 use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
 use thiserror::Error;
 
+use super::constants::{verify_constants, with_constants, ConstantMismatch, ExpectedConstant};
+
 #[derive(Clone, Debug)]
 pub struct BallotContract {
     ergo_tree: ErgoTree,
@@ -25,8 +27,8 @@ pub struct BallotContract {
 pub enum BallotContractError {
     #[error("ballot contract: parameter error: {0}")]
     ParametersError(BallotContractParametersError),
-    #[error("ballot contract: unknown update NFT defined in constant")]
-    UnknownUpdateNftId,
+    #[error("ballot contract: unexpected {0}")]
+    ConstantMismatch(ConstantMismatch),
     #[error("ballot contract: sigma parsing error {0}")]
     SigmaParsing(SigmaParsingError),
     #[error("ballot contract: ergo tree constant error {0:?}")]
@@ -94,17 +96,10 @@ impl BallotContract {
     }
 
     fn build_with(inputs: &BallotContractInputs) -> Result<Self, BallotContractError> {
-        let parameters = inputs.contract_parameters.clone();
-        let ergo_tree =
-            ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?
-                .with_constant(
-                    parameters.min_storage_rent_index,
-                    parameters.min_storage_rent.into(),
-                )?
-                .with_constant(
-                    parameters.update_nft_index,
-                    inputs.update_nft_token_id.clone().into(),
-                )?;
+        let ergo_tree = with_constants(
+            ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?,
+            &Self::expected_constants(inputs),
+        )?;
         let contract = Self::from_ergo_tree(ergo_tree, inputs)?;
         Ok(contract)
     }
@@ -113,48 +108,29 @@ impl BallotContract {
         ergo_tree: ErgoTree,
         inputs: &BallotContractInputs,
     ) -> Result<Self, BallotContractError> {
-        // dbg!(ergo_tree.get_constants().unwrap());
-        let parameters = inputs.contract_parameters.clone();
-        let min_storage_rent: BoxValue = ergo_tree
-            .get_constant(parameters.min_storage_rent_index)
-            .map_err(|_| {
-                BallotContractError::ParametersError(
-                    BallotContractParametersError::NoMinStorageRent,
-                )
-            })?
-            .ok_or(BallotContractError::ParametersError(
-                BallotContractParametersError::NoMinStorageRent,
-            ))?
-            .try_extract_into::<i64>()?
-            .try_into()?;
-        if min_storage_rent != parameters.min_storage_rent {
-            return Err(BallotContractError::ParametersError(
-                BallotContractParametersError::MinStorageRentDiffers {
-                    expected: parameters.min_storage_rent,
-                    actual: min_storage_rent,
-                },
-            ));
-        }
-
-        let token_id = ergo_tree
-            .get_constant(parameters.update_nft_index)
-            .map_err(|_| {
-                BallotContractError::ParametersError(BallotContractParametersError::NoUpdateNftId)
-            })?
-            .ok_or(BallotContractError::ParametersError(
-                BallotContractParametersError::NoUpdateNftId,
-            ))?
-            .try_extract_into::<TokenId>()?;
-        if token_id != inputs.update_nft_token_id {
-            return Err(BallotContractError::UnknownUpdateNftId);
-        }
+        verify_constants(&ergo_tree, &Self::expected_constants(inputs))?;
         Ok(Self {
             ergo_tree,
-            min_storage_rent_index: parameters.min_storage_rent_index,
-            update_nft_index: parameters.update_nft_index,
+            min_storage_rent_index: inputs.contract_parameters.min_storage_rent_index,
+            update_nft_index: inputs.contract_parameters.update_nft_index,
         })
     }
 
+    fn expected_constants(inputs: &BallotContractInputs) -> Vec<ExpectedConstant> {
+        vec![
+            ExpectedConstant::new(
+                "min storage rent",
+                inputs.contract_parameters.min_storage_rent_index,
+                inputs.contract_parameters.min_storage_rent,
+            ),
+            ExpectedConstant::new(
+                "update NFT",
+                inputs.contract_parameters.update_nft_index,
+                inputs.update_nft_token_id.clone(),
+            ),
+        ]
+    }
+
     pub fn min_storage_rent(&self) -> BoxValue {
         self.ergo_tree
             .get_constant(self.min_storage_rent_index)
@@ -338,4 +314,39 @@ mod tests {
         assert_eq!(new_contract.update_nft_token_id(), new_update_nft_token_id);
         assert_eq!(new_contract.min_storage_rent(), new_min_storage_rent);
     }
+
+    #[test]
+    fn test_from_ergo_tree_validation() {
+        let contract_parameters = BallotContractParameters::default();
+        let inputs = BallotContractInputs {
+            contract_parameters: contract_parameters.clone(),
+            update_nft_token_id: force_any_val::<Digest32>().into(),
+        };
+        let ergo_tree = BallotContract::build_with(&inputs).unwrap().ergo_tree();
+        assert!(BallotContract::from_ergo_tree(ergo_tree.clone(), &inputs).is_ok());
+
+        let other_update_nft_inputs = BallotContractInputs {
+            update_nft_token_id: force_any_val::<Digest32>().into(),
+            ..inputs.clone()
+        };
+        assert!(matches!(
+            BallotContract::from_ergo_tree(ergo_tree.clone(), &other_update_nft_inputs),
+            Err(BallotContractError::ConstantMismatch(m)) if m.name == "update NFT"
+        ));
+
+        let other_min_storage_rent_inputs = BallotContractInputs {
+            contract_parameters: BallotContractParameters {
+                min_storage_rent: contract_parameters
+                    .min_storage_rent
+                    .checked_mul_u32(2)
+                    .unwrap(),
+                ..contract_parameters
+            },
+            ..inputs
+        };
+        assert!(matches!(
+            BallotContract::from_ergo_tree(ergo_tree, &other_min_storage_rent_inputs),
+            Err(BallotContractError::ConstantMismatch(m)) if m.name == "min storage rent"
+        ));
+    }
 }
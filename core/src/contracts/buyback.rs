@@ -0,0 +1,32 @@
+//! Buyback contract, used by the pools that route part of the refresh reward through a buyback
+//! box (e.g. the GORT buyback of newer EIP-23 deployments).
+//!
+//! The buyback contract is deployed independently of the pool and its box is identified by the
+//! buyback NFT, so unlike the other contracts it's not built from parameters but taken from the
+//! box as is.
+use derive_more::From;
+use ergo_lib::ergotree_ir::ergo_tree::{ErgoTree, ErgoTreeError};
+use thiserror::Error;
+
+#[derive(Clone, Debug)]
+pub struct BuybackContract {
+    ergo_tree: ErgoTree,
+}
+
+#[derive(Debug, Error, From)]
+pub enum BuybackContractError {
+    #[error("buyback contract: ergo tree error {0:?}")]
+    ErgoTree(ErgoTreeError),
+}
+
+impl BuybackContract {
+    pub fn from_ergo_tree(ergo_tree: ErgoTree) -> Result<Self, BuybackContractError> {
+        // The box can't be spent if its script can't be parsed
+        ergo_tree.proposition()?;
+        Ok(Self { ergo_tree })
+    }
+
+    pub fn ergo_tree(&self) -> ErgoTree {
+        self.ergo_tree.clone()
+    }
+}
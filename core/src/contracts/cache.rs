@@ -0,0 +1,67 @@
+//! Memoization of the constructed contracts.
+//!
+//! Parsing a contract and checking its constants is done on every box parse (e.g. for each
+//! datapoint box collected by the refresh action). A constructed contract only depends on its
+//! inputs (ergo tree, constant indices and values, token ids), so it is cached per inputs.
+use std::{collections::HashMap, hash::Hash, sync::Mutex};
+
+pub struct ContractCache<K, C> {
+    contracts: Mutex<HashMap<K, C>>,
+}
+
+impl<K: Hash + Eq, C: Clone> ContractCache<K, C> {
+    pub fn new() -> Self {
+        ContractCache {
+            contracts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<C> {
+        self.contracts.lock().unwrap().get(key).cloned()
+    }
+
+    /// Returns the cached contract for `key`, or constructs it with `f` and caches it on success.
+    /// The lock is not held while constructing, so `f` may use the cache as well.
+    pub fn get_or_try_insert_with<E>(
+        &self,
+        key: K,
+        f: impl FnOnce() -> Result<C, E>,
+    ) -> Result<C, E> {
+        if let Some(contract) = self.get(&key) {
+            return Ok(contract);
+        }
+        let contract = f()?;
+        self.contracts.lock().unwrap().insert(key, contract.clone());
+        Ok(contract)
+    }
+}
+
+impl<K: Hash + Eq, C: Clone> Default for ContractCache<K, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_are_not_cached() {
+        let cache: ContractCache<u32, String> = ContractCache::new();
+        assert_eq!(
+            cache.get_or_try_insert_with(1, || Err::<String, _>("failed")),
+            Err("failed")
+        );
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(
+            cache.get_or_try_insert_with(1, || Ok::<_, &str>("contract".to_string())),
+            Ok("contract".to_string())
+        );
+        // Cached, `f` is not called again
+        assert_eq!(
+            cache.get_or_try_insert_with(1, || Err::<String, _>("failed")),
+            Ok("contract".to_string())
+        );
+    }
+}
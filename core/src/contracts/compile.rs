@@ -0,0 +1,316 @@
+//! Compilation of the pool contracts from ErgoScript sources (`compile-contracts` feature), for
+//! contract developers iterating on the scripts.
+//!
+//! The sources are read from `<name>.es` files (`pool.es`, `refresh.es`, `oracle.es`, `ballot.es`,
+//! `update.es`) in the given directory, contracts without a source file keep the precompiled
+//! ergo tree of the bootstrap config. Each contract parameter is written in the source as a `$name`
+//! placeholder, used once:
+//!   - pool: `$refreshNft`, `$updateNft`;
+//!   - refresh: `$poolNft`, `$oracleTokenId`, `$minDataPoints`, `$bufferLength`,
+//!     `$maxDeviationPercent`, `$epochLength`;
+//!   - oracle: `$poolNft`, `$minStorageRent`;
+//!   - ballot: `$updateNft`, `$minStorageRent`;
+//!   - update: `$poolNft`, `$ballotTokenId`, `$minVotes`.
+//!
+//! Before compiling, every placeholder is replaced with a marker value of its type. The constant
+//! index of the parameter is the index of its marker among the constants of the compiled tree, the
+//! values of the bootstrap config are then set at these indices (token ids once minted, as for the
+//! precompiled contracts).
+use std::path::Path;
+
+use derive_more::From;
+use ergo_lib::{
+    ergo_chain_types::blake2b256_hash,
+    ergoscript_compiler::{compiler::compile, script_env::ScriptEnv},
+    ergotree_ir::{
+        ergo_tree::{ErgoTree, ErgoTreeError, ErgoTreeHeader},
+        mir::constant::Constant,
+        serialization::{SigmaSerializable, SigmaSerializationError},
+    },
+};
+use thiserror::Error;
+
+use crate::cli_commands::bootstrap::BootstrapConfig;
+
+use super::{
+    ballot::{BallotContractParameters, BallotContractParametersError},
+    oracle::{OracleContractParameters, OracleContractParametersError},
+    pool::{PoolContractParameters, PoolContractParametersError},
+    refresh::{
+        RefreshContractParameters, RefreshContractParametersError, RefreshContractParametersInputs,
+    },
+    update::{UpdateContractParameters, UpdateContractParametersError},
+};
+
+#[derive(Debug, Error, From)]
+pub enum CompileContractError {
+    #[error("compile contract: IO error {0}")]
+    Io(#[source] std::io::Error),
+    #[error("compile contract: {0} failed to compile: {1}")]
+    #[from(ignore)]
+    Compile(String, String),
+    #[error("compile contract: placeholder ${1} must be used exactly once in {0}.es, found {2} constants for it")]
+    #[from(ignore)]
+    Placeholder(String, String, usize),
+    #[error("compile contract: ergo tree error {0:?}")]
+    ErgoTree(ErgoTreeError),
+    #[error("compile contract: sigma serialization error {0}")]
+    SigmaSerialization(SigmaSerializationError),
+    #[error("compile contract: pool contract parameters error {0:?}")]
+    PoolContractParameters(PoolContractParametersError),
+    #[error("compile contract: refresh contract parameters error {0:?}")]
+    RefreshContractParameters(RefreshContractParametersError),
+    #[error("compile contract: oracle contract parameters error {0:?}")]
+    OracleContractParameters(OracleContractParametersError),
+    #[error("compile contract: ballot contract parameters error {0:?}")]
+    BallotContractParameters(BallotContractParametersError),
+    #[error("compile contract: update contract parameters error {0:?}")]
+    UpdateContractParameters(UpdateContractParametersError),
+}
+
+/// Placeholder of a contract parameter in the source, by the type of its constant
+#[derive(Debug, Clone, Copy)]
+enum Placeholder {
+    TokenId(&'static str),
+    Int(&'static str),
+    Long(&'static str),
+}
+
+impl Placeholder {
+    fn name(&self) -> &'static str {
+        match self {
+            Placeholder::TokenId(name) | Placeholder::Int(name) | Placeholder::Long(name) => *name,
+        }
+    }
+
+    /// Marker value of the placeholder, as written in the source and as found among the constants
+    /// of the compiled tree. `position` keeps the markers of a source apart.
+    fn marker(&self, position: usize) -> (String, Constant) {
+        match self {
+            Placeholder::TokenId(name) => {
+                let hash = blake2b256_hash(format!("oracle-core placeholder {}", name).as_bytes());
+                let bytes: Vec<u8> = hash.as_ref().to_vec();
+                (
+                    format!("fromBase16(\"{}\")", base16::encode_lower(&bytes)),
+                    bytes.into(),
+                )
+            }
+            Placeholder::Int(_) => {
+                let value = 0x7eed_0000 + position as i32;
+                (value.to_string(), value.into())
+            }
+            Placeholder::Long(_) => {
+                let value = 0x7eed_0000_0000 + position as i64;
+                (format!("{}L", value), value.into())
+            }
+        }
+    }
+}
+
+/// Replace the ergo trees of the contract parameters in `config` with the ones compiled from the
+/// sources found in `sources_dir`.
+pub fn compile_bootstrap_contracts(
+    config: &mut BootstrapConfig,
+    sources_dir: &Path,
+) -> Result<(), CompileContractError> {
+    if let Some((bytes, indices)) = compile_source(
+        sources_dir,
+        "pool",
+        &[
+            Placeholder::TokenId("refreshNft"),
+            Placeholder::TokenId("updateNft"),
+        ],
+    )? {
+        config.pool_contract_parameters =
+            PoolContractParameters::checked_load(bytes, indices[0], indices[1])?;
+    }
+    if let Some((bytes, indices)) = compile_source(
+        sources_dir,
+        "refresh",
+        &[
+            Placeholder::TokenId("poolNft"),
+            Placeholder::TokenId("oracleTokenId"),
+            Placeholder::Int("minDataPoints"),
+            Placeholder::Int("bufferLength"),
+            Placeholder::Int("maxDeviationPercent"),
+            Placeholder::Int("epochLength"),
+        ],
+    )? {
+        let p = &config.refresh_contract_parameters;
+        config.refresh_contract_parameters =
+            RefreshContractParameters::build_with(RefreshContractParametersInputs {
+                ergo_tree_bytes: bytes,
+                pool_nft_index: indices[0],
+                oracle_token_id_index: indices[1],
+                min_data_points_index: indices[2],
+                min_data_points: p.min_data_points(),
+                buffer_length_index: indices[3],
+                buffer_length: p.buffer_length(),
+                max_deviation_percent_index: indices[4],
+                max_deviation_percent: p.max_deviation_percent(),
+                epoch_length_index: indices[5],
+                epoch_length: p.epoch_length(),
+            })?;
+    }
+    if let Some((bytes, indices)) = compile_source(
+        sources_dir,
+        "oracle",
+        &[
+            Placeholder::TokenId("poolNft"),
+            Placeholder::Long("minStorageRent"),
+        ],
+    )? {
+        config.oracle_contract_parameters = OracleContractParameters::build_with(
+            bytes,
+            indices[0],
+            indices[1],
+            config.oracle_contract_parameters.min_storage_rent,
+        )?;
+    }
+    if let Some((bytes, indices)) = compile_source(
+        sources_dir,
+        "ballot",
+        &[
+            Placeholder::TokenId("updateNft"),
+            Placeholder::Long("minStorageRent"),
+        ],
+    )? {
+        config.ballot_contract_parameters = BallotContractParameters::build_with(
+            bytes,
+            indices[1],
+            config.ballot_contract_parameters.min_storage_rent(),
+            indices[0],
+        )?;
+    }
+    if let Some((bytes, indices)) = compile_source(
+        sources_dir,
+        "update",
+        &[
+            Placeholder::TokenId("poolNft"),
+            Placeholder::TokenId("ballotTokenId"),
+            Placeholder::Int("minVotes"),
+        ],
+    )? {
+        config.update_contract_parameters = UpdateContractParameters::build_with(
+            bytes,
+            indices[0],
+            indices[1],
+            indices[2],
+            config.update_contract_parameters.min_votes(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Compile `<sources_dir>/<name>.es` if it exists, returning the ergo tree bytes and the constant
+/// index of each of the `placeholders`.
+fn compile_source(
+    sources_dir: &Path,
+    name: &str,
+    placeholders: &[Placeholder],
+) -> Result<Option<(Vec<u8>, Vec<usize>)>, CompileContractError> {
+    let path = sources_dir.join(format!("{}.es", name));
+    if !path.exists() {
+        return Ok(None);
+    }
+    let markers: Vec<(String, Constant)> = placeholders
+        .iter()
+        .enumerate()
+        .map(|(position, p)| p.marker(position))
+        .collect();
+    let source = inject_constants(
+        &std::fs::read_to_string(path)?,
+        &placeholders
+            .iter()
+            .zip(&markers)
+            .map(|(p, (literal, _))| (p.name(), literal.clone()))
+            .collect::<Vec<_>>(),
+    );
+    let compiled = compile(&source, ScriptEnv::new())
+        .map_err(|e| CompileContractError::Compile(name.to_string(), format!("{:?}", e)))?;
+    // The constants are segregated to give each marker its constant index
+    let ergo_tree = ErgoTree::new(ErgoTreeHeader::v0(true), &compiled.proposition()?)?;
+    let constants = ergo_tree.get_constants()?;
+    let indices = placeholders
+        .iter()
+        .zip(&markers)
+        .map(|(p, (_, marker))| constant_index(&constants, marker, name, p.name()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some((ergo_tree.sigma_serialize_bytes()?, indices)))
+}
+
+/// Index of the only constant equal to `marker`
+fn constant_index(
+    constants: &[Constant],
+    marker: &Constant,
+    source_name: &str,
+    placeholder_name: &str,
+) -> Result<usize, CompileContractError> {
+    let found: Vec<usize> = constants
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| *c == marker)
+        .map(|(i, _)| i)
+        .collect();
+    match found.as_slice() {
+        [index] => Ok(*index),
+        _ => Err(CompileContractError::Placeholder(
+            source_name.to_string(),
+            placeholder_name.to_string(),
+            found.len(),
+        )),
+    }
+}
+
+fn inject_constants(source: &str, constants: &[(&str, String)]) -> String {
+    constants
+        .iter()
+        .fold(source.to_string(), |s, (name, value)| {
+            s.replace(&format!("${}", name), value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inject_constants() {
+        assert_eq!(
+            inject_constants(
+                "HEIGHT > $epochLength && $minVotes < $epochLength",
+                &[
+                    ("epochLength", "30".to_string()),
+                    ("minVotes", "6".to_string())
+                ]
+            ),
+            "HEIGHT > 30 && 6 < 30"
+        );
+    }
+
+    #[test]
+    fn test_constant_index() {
+        let placeholders = [
+            Placeholder::TokenId("poolNft"),
+            Placeholder::Int("minVotes"),
+        ];
+        let markers: Vec<Constant> = placeholders
+            .iter()
+            .enumerate()
+            .map(|(position, p)| p.marker(position).1)
+            .collect();
+        let constants = vec![Constant::from(1i32), markers[1].clone(), markers[0].clone()];
+        assert_eq!(
+            constant_index(&constants, &markers[0], "update", "poolNft").unwrap(),
+            2
+        );
+        assert_eq!(
+            constant_index(&constants, &markers[1], "update", "minVotes").unwrap(),
+            1
+        );
+        assert!(matches!(
+            constant_index(&constants[..1], &markers[1], "update", "minVotes"),
+            Err(CompileContractError::Placeholder(_, _, 0))
+        ));
+    }
+}
@@ -0,0 +1,204 @@
+//! Constants of the contract ergo trees. Each contract declares the constants it expects (the
+//! token ids and parameters at their index) in a table of [`ExpectedConstant`], which is used both
+//! to build the tree ([`with_constants`]) and to validate a tree found on chain
+//! ([`verify_constants`]). Mismatches are reported with a rendered constant table, to tell which
+//! constant of a contract doesn't match the expected value without adding print statements.
+use std::fmt;
+
+use ergo_lib::ergotree_ir::{
+    chain::token::TokenId,
+    ergo_tree::{ErgoTree, ErgoTreeConstantError},
+    mir::constant::{Constant, TryExtractInto},
+};
+
+/// A constant a contract expects at `index` of its ergo tree.
+#[derive(Clone, Debug)]
+pub struct ExpectedConstant {
+    /// Name of the contract parameter, used in the error reporting
+    pub name: &'static str,
+    pub index: usize,
+    pub expected: Constant,
+}
+
+impl ExpectedConstant {
+    pub fn new(name: &'static str, index: usize, expected: impl Into<Constant>) -> Self {
+        ExpectedConstant {
+            name,
+            index,
+            expected: expected.into(),
+        }
+    }
+}
+
+/// Substitute the expected constants in `ergo_tree`.
+pub fn with_constants(
+    ergo_tree: ErgoTree,
+    constants: &[ExpectedConstant],
+) -> Result<ErgoTree, ErgoTreeConstantError> {
+    constants.iter().try_fold(ergo_tree, |tree, c| {
+        tree.with_constant(c.index, c.expected.clone())
+    })
+}
+
+/// Check the constants of `ergo_tree` against the expected ones (value and type), returning the
+/// first mismatch.
+pub fn verify_constants(
+    ergo_tree: &ErgoTree,
+    constants: &[ExpectedConstant],
+) -> Result<(), ConstantMismatch> {
+    for c in constants {
+        match ergo_tree.get_constant(c.index) {
+            Ok(Some(actual)) if actual == c.expected => (),
+            _ => {
+                return Err(ConstantMismatch::new(
+                    c.name,
+                    c.index,
+                    &c.expected,
+                    ergo_tree,
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A constant of a contract ergo tree which differs from the expected value.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ConstantMismatch {
+    pub index: usize,
+    /// Name of the contract parameter at `index`
+    pub name: &'static str,
+    pub expected: String,
+    /// `None` if the tree has no constant at `index`
+    pub actual: Option<String>,
+    /// All the constants of the tree, see [`render_constant_table`]
+    pub constants: String,
+}
+
+impl ConstantMismatch {
+    pub fn new(
+        name: &'static str,
+        index: usize,
+        expected: &Constant,
+        ergo_tree: &ErgoTree,
+    ) -> Self {
+        let actual = ergo_tree.get_constant(index).ok().flatten();
+        // The same value with another type (e.g. SInt instead of SLong) renders the same
+        let render = |c: &Constant| match &actual {
+            Some(a) if a.tpe != expected.tpe => format!("{} ({:?})", render_constant(c), c.tpe),
+            _ => render_constant(c),
+        };
+        ConstantMismatch {
+            index,
+            name,
+            expected: render(expected),
+            actual: actual.as_ref().map(render),
+            constants: render_constant_table(ergo_tree),
+        }
+    }
+}
+
+impl fmt::Display for ConstantMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "constant {} ({}): expected {}, found {}\nconstants of the tree:\n{}",
+            self.index,
+            self.name,
+            self.expected,
+            self.actual.as_deref().unwrap_or("<none>"),
+            self.constants
+        )
+    }
+}
+
+// Contract errors are mostly printed with `{:?}`, keep the table readable there as well
+impl fmt::Debug for ConstantMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for ConstantMismatch {}
+
+/// Token ids and numbers are rendered as such, other constants with their debug representation.
+pub(crate) fn render_constant(c: &Constant) -> String {
+    if let Ok(v) = c.clone().try_extract_into::<i32>() {
+        return v.to_string();
+    }
+    if let Ok(v) = c.clone().try_extract_into::<i64>() {
+        return v.to_string();
+    }
+    if let Ok(token_id) = c.clone().try_extract_into::<TokenId>() {
+        return String::from(token_id);
+    }
+    format!("{:?}", c.v)
+}
+
+/// One `<index>: <type> = <value>` line per constant of the tree.
+pub fn render_constant_table(ergo_tree: &ErgoTree) -> String {
+    match ergo_tree.get_constants() {
+        Ok(constants) => constants
+            .iter()
+            .enumerate()
+            .map(|(index, c)| format!("  {}: {:?} = {}", index, c.tpe, render_constant(c)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Err(e) => format!("  failed to parse the constants: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+
+    use super::*;
+    use crate::contracts::refresh::RefreshContractParameters;
+
+    #[test]
+    fn test_verify_constants() {
+        let p = RefreshContractParameters::default();
+        let constants = vec![
+            ExpectedConstant::new("epoch length", p.epoch_length_index(), 7),
+            ExpectedConstant::new("min data points", p.min_data_points_index(), 3),
+        ];
+        let ergo_tree = with_constants(
+            ErgoTree::sigma_parse_bytes(&p.ergo_tree_bytes()).unwrap(),
+            &constants,
+        )
+        .unwrap();
+        assert!(verify_constants(&ergo_tree, &constants).is_ok());
+
+        let mismatch = verify_constants(
+            &ergo_tree,
+            &[ExpectedConstant::new(
+                "min data points",
+                p.min_data_points_index(),
+                4,
+            )],
+        )
+        .unwrap_err();
+        assert_eq!(mismatch.index, p.min_data_points_index());
+        assert_eq!(mismatch.expected, "4");
+        assert_eq!(mismatch.actual.as_deref(), Some("3"));
+
+        // Same value, other type
+        let mismatch = verify_constants(
+            &ergo_tree,
+            &[ExpectedConstant::new(
+                "epoch length",
+                p.epoch_length_index(),
+                7i64,
+            )],
+        )
+        .unwrap_err();
+        assert_ne!(mismatch.expected, mismatch.actual.unwrap());
+
+        let mismatch = verify_constants(
+            &ergo_tree,
+            &[ExpectedConstant::new("out of range", 1000, 7)],
+        )
+        .unwrap_err();
+        assert_eq!(mismatch.actual, None);
+    }
+}
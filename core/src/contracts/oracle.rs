@@ -11,21 +11,32 @@ use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
 use ergo_lib::ergotree_ir::serialization::SigmaParsingError;
 use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
 use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
+use once_cell::sync::Lazy;
 use thiserror::Error;
 
+use super::cache::ContractCache;
+use super::constants::{verify_constants, with_constants, ConstantMismatch, ExpectedConstant};
+
 #[derive(Clone, Debug)]
 pub struct OracleContract {
     ergo_tree: ErgoTree,
     pool_nft_index: usize,
     min_storage_rent_index: usize,
+    pool_nft_token_id: TokenId,
 }
 
+/// Ergo tree bytes, pool NFT index, min storage rent index, min storage rent and pool NFT (base16)
+type OracleContractKey = (Vec<u8>, usize, usize, u64, String);
+
+static ORACLE_CONTRACT_CACHE: Lazy<ContractCache<OracleContractKey, OracleContract>> =
+    Lazy::new(ContractCache::new);
+
 #[derive(Debug, Error)]
 pub enum OracleContractError {
     #[error("oracle contract: parameter error: {0}")]
     ParametersError(OracleContractParametersError),
-    #[error("oracle contract: expected pool NFT {expected:?}, got {got:?} defined in constant")]
-    UnknownPoolNftId { expected: TokenId, got: TokenId },
+    #[error("oracle contract: unexpected {0}")]
+    ConstantMismatch(#[from] ConstantMismatch),
     #[error("oracle contract: sigma parsing error {0}")]
     SigmaParsing(#[from] SigmaParsingError),
     #[error("oracle contract: ergo tree constant error {0:?}")]
@@ -75,27 +86,39 @@ impl OracleContractInputs {
     pub fn contract_parameters(&self) -> &OracleContractParameters {
         &self.contract_parameters
     }
+
+    fn cache_key(&self) -> OracleContractKey {
+        (
+            self.contract_parameters.ergo_tree_bytes.clone(),
+            self.contract_parameters.pool_nft_index,
+            self.contract_parameters.min_storage_rent_index,
+            *self.contract_parameters.min_storage_rent.as_u64(),
+            String::from(self.pool_nft_token_id.clone()),
+        )
+    }
 }
 
 impl OracleContract {
     pub fn checked_load(inputs: &OracleContractInputs) -> Result<Self, OracleContractError> {
-        let checked_contract_parameters = OracleContractParameters::checked_load(
-            inputs.contract_parameters.ergo_tree_bytes(),
-            inputs.contract_parameters.pool_nft_index,
-            inputs.contract_parameters.min_storage_rent_index,
-            inputs.contract_parameters.min_storage_rent,
-        )?;
-        let ergo_tree =
-            ErgoTree::sigma_parse_bytes(checked_contract_parameters.ergo_tree_bytes.as_slice())?;
-        let contract = Self::from_ergo_tree(ergo_tree, inputs).map_err(|e| {
-            let expected_base16 = Self::build_with(inputs)
-                .unwrap()
-                .ergo_tree
-                .to_base16_bytes()
-                .unwrap();
-            OracleContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
-        })?;
-        Ok(contract)
+        ORACLE_CONTRACT_CACHE.get_or_try_insert_with(inputs.cache_key(), || {
+            let checked_contract_parameters = OracleContractParameters::checked_load(
+                inputs.contract_parameters.ergo_tree_bytes(),
+                inputs.contract_parameters.pool_nft_index,
+                inputs.contract_parameters.min_storage_rent_index,
+                inputs.contract_parameters.min_storage_rent,
+            )?;
+            let ergo_tree = ErgoTree::sigma_parse_bytes(
+                checked_contract_parameters.ergo_tree_bytes.as_slice(),
+            )?;
+            Self::from_ergo_tree(ergo_tree, inputs).map_err(|e| {
+                let expected_base16 = Self::build_with(inputs)
+                    .unwrap()
+                    .ergo_tree
+                    .to_base16_bytes()
+                    .unwrap();
+                OracleContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            })
+        })
     }
 
     fn build_with(inputs: &OracleContractInputs) -> Result<Self, OracleContractError> {
@@ -105,13 +128,11 @@ impl OracleContract {
             inputs.contract_parameters.min_storage_rent_index,
             inputs.contract_parameters.min_storage_rent,
         )?;
-        let ergo_tree =
-            ErgoTree::sigma_parse_bytes(new_contract_parameters.ergo_tree_bytes().as_slice())?
-                .with_constant(
-                    inputs.contract_parameters.pool_nft_index,
-                    inputs.pool_nft_token_id.clone().into(),
-                )
-                .map_err(OracleContractError::ErgoTreeConstant)?;
+        let ergo_tree = with_constants(
+            ErgoTree::sigma_parse_bytes(new_contract_parameters.ergo_tree_bytes().as_slice())?,
+            &Self::expected_constants(inputs),
+        )
+        .map_err(OracleContractError::ErgoTreeConstant)?;
         let contract = Self::from_ergo_tree(ergo_tree, inputs)?;
         Ok(contract)
     }
@@ -121,41 +142,42 @@ impl OracleContract {
         ergo_tree: ErgoTree,
         inputs: &OracleContractInputs,
     ) -> Result<Self, OracleContractError> {
-        // dbg!(ergo_tree.get_constants().unwrap());
-
-        let checked_contract_parameters = OracleContractParameters::checked_load(
-            ergo_tree.sigma_serialize_bytes().unwrap(),
-            inputs.contract_parameters.pool_nft_index,
-            inputs.contract_parameters.min_storage_rent_index,
-            inputs.contract_parameters.min_storage_rent,
-        )?;
-
-        let pool_nft_token_id = checked_contract_parameters.pool_nft_token_id()?;
-        if pool_nft_token_id != inputs.pool_nft_token_id {
-            return Err(OracleContractError::UnknownPoolNftId {
-                expected: inputs.pool_nft_token_id.clone(),
-                got: pool_nft_token_id,
-            });
+        // Oracle boxes of the pool have the same tree as the contract loaded from the inputs
+        if let Some(contract) = ORACLE_CONTRACT_CACHE.get(&inputs.cache_key()) {
+            if contract.ergo_tree == ergo_tree {
+                return Ok(contract);
+            }
         }
-
+        verify_constants(&ergo_tree, &Self::expected_constants(inputs))?;
         Ok(Self {
             ergo_tree,
             pool_nft_index: inputs.contract_parameters.pool_nft_index,
             min_storage_rent_index: inputs.contract_parameters.min_storage_rent_index,
+            pool_nft_token_id: inputs.pool_nft_token_id.clone(),
         })
     }
 
+    fn expected_constants(inputs: &OracleContractInputs) -> Vec<ExpectedConstant> {
+        vec![
+            ExpectedConstant::new(
+                "pool NFT",
+                inputs.contract_parameters.pool_nft_index,
+                inputs.pool_nft_token_id.clone(),
+            ),
+            ExpectedConstant::new(
+                "min storage rent",
+                inputs.contract_parameters.min_storage_rent_index,
+                inputs.contract_parameters.min_storage_rent,
+            ),
+        ]
+    }
+
     pub fn ergo_tree(&self) -> ErgoTree {
         self.ergo_tree.clone()
     }
 
     pub fn pool_nft_token_id(&self) -> TokenId {
-        self.ergo_tree
-            .get_constant(self.pool_nft_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
+        self.pool_nft_token_id.clone()
     }
 
     pub fn parameters(&self) -> OracleContractParameters {
@@ -217,7 +239,6 @@ impl OracleContractParameters {
         min_storage_rent: BoxValue,
     ) -> Result<Self, OracleContractParametersError> {
         let ergo_tree = ErgoTree::sigma_parse_bytes(ergo_tree_bytes.as_slice())?;
-        // dbg!(ergo_tree.get_constants().unwrap());
 
         let min_storage_rent_from_tree: BoxValue = ergo_tree
             .get_constant(min_storage_rent_index)
@@ -7,23 +7,33 @@ use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
 
 use ergo_lib::ergotree_ir::serialization::SigmaParsingError;
 use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use once_cell::sync::Lazy;
 use thiserror::Error;
 
+use super::cache::ContractCache;
+use super::constants::{verify_constants, with_constants, ConstantMismatch, ExpectedConstant};
+
 #[derive(Clone, Debug)]
 pub struct PoolContract {
     ergo_tree: ErgoTree,
     refresh_nft_index: usize,
     update_nft_index: usize,
+    refresh_nft_token_id: TokenId,
+    update_nft_token_id: TokenId,
 }
 
+/// Ergo tree bytes, refresh NFT index, update NFT index, refresh NFT and update NFT (base16)
+type PoolContractKey = (Vec<u8>, usize, usize, String, String);
+
+static POOL_CONTRACT_CACHE: Lazy<ContractCache<PoolContractKey, PoolContract>> =
+    Lazy::new(ContractCache::new);
+
 #[derive(Debug, From, Error)]
 pub enum PoolContractError {
     #[error("pool contract: parameter error: {0}")]
     ParametersError(PoolContractParametersError),
-    #[error("pool contract: unknown refresh NFT in box")]
-    UnknownRefreshNftId,
-    #[error("pool contract: unknown update NFT in box")]
-    UnknownUpdateNftId,
+    #[error("pool contract: unexpected {0}")]
+    ConstantMismatch(ConstantMismatch),
     #[error("pool contract: sigma parsing error {0}")]
     SigmaParsing(SigmaParsingError),
     #[error("pool contract: ergo tree constant error {0:?}")]
@@ -77,34 +87,39 @@ impl PoolContractInputs {
     pub fn contract_parameters(&self) -> &PoolContractParameters {
         &self.contract_parameters
     }
+
+    fn cache_key(&self) -> PoolContractKey {
+        (
+            self.contract_parameters.ergo_tree_bytes.clone(),
+            self.contract_parameters.refresh_nft_index,
+            self.contract_parameters.update_nft_index,
+            String::from(self.refresh_nft_token_id.clone()),
+            String::from(self.update_nft_token_id.clone()),
+        )
+    }
 }
 
 impl PoolContract {
     pub fn checked_load(inputs: &PoolContractInputs) -> Result<Self, PoolContractError> {
-        let ergo_tree =
-            ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?;
-        let contract = Self::from_ergo_tree(ergo_tree, inputs).map_err(|e| {
-            let expected_base16 = Self::build_with(inputs)
-                .unwrap()
-                .ergo_tree
-                .to_base16_bytes()
-                .unwrap();
-            PoolContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
-        })?;
-        Ok(contract)
+        POOL_CONTRACT_CACHE.get_or_try_insert_with(inputs.cache_key(), || {
+            let ergo_tree =
+                ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?;
+            Self::from_ergo_tree(ergo_tree, inputs).map_err(|e| {
+                let expected_base16 = Self::build_with(inputs)
+                    .unwrap()
+                    .ergo_tree
+                    .to_base16_bytes()
+                    .unwrap();
+                PoolContractError::WrappedWithExpectedP2SAddress(expected_base16, e.into())
+            })
+        })
     }
 
     pub fn build_with(inputs: &PoolContractInputs) -> Result<Self, PoolContractError> {
-        let ergo_tree =
-            ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?
-                .with_constant(
-                    inputs.contract_parameters.refresh_nft_index,
-                    inputs.refresh_nft_token_id.clone().into(),
-                )?
-                .with_constant(
-                    inputs.contract_parameters.update_nft_index,
-                    inputs.update_nft_token_id.clone().into(),
-                )?;
+        let ergo_tree = with_constants(
+            ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?,
+            &Self::expected_constants(inputs),
+        )?;
         let contract = Self::from_ergo_tree(ergo_tree, inputs)?;
         Ok(contract)
     }
@@ -113,59 +128,47 @@ impl PoolContract {
         ergo_tree: ErgoTree,
         inputs: &PoolContractInputs,
     ) -> Result<Self, PoolContractError> {
-        // dbg!(ergo_tree.get_constants().unwrap());
-        let refresh_nft_token_id = ergo_tree
-            .get_constant(inputs.contract_parameters.refresh_nft_index)
-            .map_err(|_| {
-                PoolContractError::ParametersError(PoolContractParametersError::NoRefreshNftId)
-            })?
-            .ok_or(PoolContractError::ParametersError(
-                PoolContractParametersError::NoRefreshNftId,
-            ))?
-            .try_extract_into::<TokenId>()?;
-        if refresh_nft_token_id != inputs.refresh_nft_token_id {
-            return Err(PoolContractError::UnknownRefreshNftId);
-        }
-
-        let update_nft_token_id = ergo_tree
-            .get_constant(inputs.contract_parameters.update_nft_index)
-            .map_err(|_| {
-                PoolContractError::ParametersError(PoolContractParametersError::NoUpdateNftId)
-            })?
-            .ok_or(PoolContractError::ParametersError(
-                PoolContractParametersError::NoUpdateNftId,
-            ))?
-            .try_extract_into::<TokenId>()?;
-        if update_nft_token_id != inputs.update_nft_token_id {
-            return Err(PoolContractError::UnknownUpdateNftId);
+        // Boxes of the pool have the same tree as the contract loaded from the inputs
+        if let Some(contract) = POOL_CONTRACT_CACHE.get(&inputs.cache_key()) {
+            if contract.ergo_tree == ergo_tree {
+                return Ok(contract);
+            }
         }
+        verify_constants(&ergo_tree, &Self::expected_constants(inputs))?;
         Ok(Self {
             ergo_tree,
             refresh_nft_index: inputs.contract_parameters.refresh_nft_index,
             update_nft_index: inputs.contract_parameters.update_nft_index,
+            refresh_nft_token_id: inputs.refresh_nft_token_id.clone(),
+            update_nft_token_id: inputs.update_nft_token_id.clone(),
         })
     }
 
+    fn expected_constants(inputs: &PoolContractInputs) -> Vec<ExpectedConstant> {
+        vec![
+            ExpectedConstant::new(
+                "refresh NFT",
+                inputs.contract_parameters.refresh_nft_index,
+                inputs.refresh_nft_token_id.clone(),
+            ),
+            ExpectedConstant::new(
+                "update NFT",
+                inputs.contract_parameters.update_nft_index,
+                inputs.update_nft_token_id.clone(),
+            ),
+        ]
+    }
+
     pub fn ergo_tree(&self) -> ErgoTree {
         self.ergo_tree.clone()
     }
 
     pub fn refresh_nft_token_id(&self) -> TokenId {
-        self.ergo_tree
-            .get_constant(self.refresh_nft_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
+        self.refresh_nft_token_id.clone()
     }
 
     pub fn update_nft_token_id(&self) -> TokenId {
-        self.ergo_tree
-            .get_constant(self.update_nft_index)
-            .unwrap()
-            .unwrap()
-            .try_extract_into::<TokenId>()
-            .unwrap()
+        self.update_nft_token_id.clone()
     }
 
     pub fn parameters(&self) -> PoolContractParameters {
@@ -269,4 +272,39 @@ mod tests {
         assert_eq!(c.refresh_nft_token_id(), token_ids.refresh_nft_token_id,);
         assert_eq!(c.update_nft_token_id(), token_ids.update_nft_token_id,);
     }
+
+    #[test]
+    fn test_from_ergo_tree_constant_mismatch() {
+        let token_ids = generate_token_ids();
+        let other_token_ids = generate_token_ids();
+        let inputs = PoolContractInputs {
+            contract_parameters: PoolContractParameters::default(),
+            refresh_nft_token_id: token_ids.refresh_nft_token_id.clone(),
+            update_nft_token_id: token_ids.update_nft_token_id.clone(),
+        };
+        let other_inputs = PoolContractInputs {
+            refresh_nft_token_id: other_token_ids.refresh_nft_token_id.clone(),
+            ..inputs.clone()
+        };
+        let ergo_tree = PoolContract::build_with(&other_inputs).unwrap().ergo_tree();
+        match PoolContract::from_ergo_tree(ergo_tree, &inputs) {
+            Err(PoolContractError::ConstantMismatch(mismatch)) => {
+                assert_eq!(mismatch.name, "refresh NFT");
+                let index = inputs.contract_parameters.refresh_nft_index();
+                assert_eq!(mismatch.index, index);
+                assert_eq!(
+                    mismatch.expected,
+                    String::from(token_ids.refresh_nft_token_id)
+                );
+                let actual = String::from(other_token_ids.refresh_nft_token_id);
+                assert_eq!(mismatch.actual.as_deref(), Some(actual.as_str()));
+                assert!(mismatch
+                    .constants
+                    .lines()
+                    .any(|l| l.trim_start().starts_with(&format!("{}:", index))
+                        && l.ends_with(&actual)));
+            }
+            r => panic!("expected a refresh NFT mismatch, got {:?}", r),
+        }
+    }
 }
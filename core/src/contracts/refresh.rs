@@ -9,6 +9,8 @@ use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
 use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
 use thiserror::Error;
 
+use super::constants::{verify_constants, with_constants, ConstantMismatch, ExpectedConstant};
+
 #[derive(Clone)]
 pub struct RefreshContract {
     ergo_tree: ErgoTree,
@@ -25,14 +27,8 @@ pub struct RefreshContract {
 pub enum RefreshContractError {
     #[error("refresh contract: parameter error: {0}")]
     Parameters(RefreshContractParametersError),
-    #[error(
-        "refresh contract: unexpected `pool NFT` token id. Expected {expected:?}, got {actual:?}"
-    )]
-    PoolNftTokenIdDiffers { expected: TokenId, actual: TokenId },
-    #[error(
-        "refresh contract: unexpected `oracle` token id. Expected {expected:?}, got {actual:?}"
-    )]
-    OracleTokenIdDiffers { expected: TokenId, actual: TokenId },
+    #[error("refresh contract: unexpected {0}")]
+    ConstantMismatch(#[from] ConstantMismatch),
     #[error("refresh contract: sigma parsing error {0}")]
     SigmaParsing(#[from] SigmaParsingError),
     #[error("refresh contract: ergo tree constant error {0:?}")]
@@ -62,116 +58,21 @@ impl RefreshContract {
         ergo_tree: ErgoTree,
         inputs: &RefreshContractInputs,
     ) -> Result<Self, RefreshContractError> {
-        // dbg!(ergo_tree.get_constants().unwrap());
-
-        let parameters = inputs.contract_parameters.clone();
-        let pool_nft_token_id = ergo_tree
-            .get_constant(parameters.pool_nft_index)
-            .map_err(|_| {
-                RefreshContractError::Parameters(RefreshContractParametersError::NoPoolNftId)
-            })?
-            .ok_or(RefreshContractError::Parameters(
-                RefreshContractParametersError::NoPoolNftId,
-            ))?
-            .try_extract_into::<TokenId>()?;
-        if pool_nft_token_id != inputs.pool_nft_token_id {
-            return Err(RefreshContractError::PoolNftTokenIdDiffers {
-                expected: inputs.pool_nft_token_id.clone(),
-                actual: pool_nft_token_id,
-            });
-        }
-
-        let oracle_token_id = ergo_tree
-            .get_constant(parameters.oracle_token_id_index)
-            .map_err(|_| {
-                RefreshContractError::Parameters(RefreshContractParametersError::NoOracleTokenId)
-            })?
-            .ok_or(RefreshContractError::Parameters(
-                RefreshContractParametersError::NoOracleTokenId,
-            ))?
-            .try_extract_into::<TokenId>()?;
-        if oracle_token_id != inputs.oracle_token_id {
-            return Err(RefreshContractError::OracleTokenIdDiffers {
-                expected: inputs.oracle_token_id.clone(),
-                actual: oracle_token_id,
-            });
-        }
-
-        let min_data_points = ergo_tree
-            .get_constant(parameters.min_data_points_index)
-            .map_err(|_| {
-                RefreshContractError::Parameters(RefreshContractParametersError::NoMinDataPoints)
-            })?
-            .ok_or(RefreshContractError::Parameters(
-                RefreshContractParametersError::NoMinDataPoints,
-            ))?
-            .try_extract_into::<i32>()?;
-        if min_data_points != parameters.min_data_points {
-            return Err(RefreshContractError::Parameters(
-                RefreshContractParametersError::MinDataPointsDiffers {
-                    expected: parameters.min_data_points,
-                    actual: min_data_points,
-                },
-            ));
-        }
-
-        let buffer_length = ergo_tree
-            .get_constant(parameters.buffer_length_index)
-            .map_err(|_| {
-                RefreshContractError::Parameters(RefreshContractParametersError::NoBufferLength)
-            })?
-            .ok_or(RefreshContractError::Parameters(
-                RefreshContractParametersError::NoBufferLength,
-            ))?
-            .try_extract_into::<i32>()?;
-        if buffer_length != parameters.buffer_length {
-            return Err(RefreshContractError::Parameters(
-                RefreshContractParametersError::BufferLengthDiffers {
-                    expected: parameters.buffer_length,
-                    actual: buffer_length,
-                },
-            ));
-        }
-
-        let max_deviation_percent = ergo_tree
-            .get_constant(parameters.max_deviation_percent_index)
-            .map_err(|_| {
-                RefreshContractError::Parameters(
-                    RefreshContractParametersError::NoMaxDeviationPercent,
-                )
-            })?
-            .ok_or(RefreshContractError::Parameters(
-                RefreshContractParametersError::NoMaxDeviationPercent,
-            ))?
-            .try_extract_into::<i32>()?;
-        if max_deviation_percent != parameters.max_deviation_percent {
-            return Err(RefreshContractError::Parameters(
-                RefreshContractParametersError::MaxDeviationPercentDiffers {
-                    expected: parameters.max_deviation_percent,
-                    actual: max_deviation_percent,
-                },
-            ));
-        }
+        verify_constants(&ergo_tree, &Self::expected_constants(inputs))?;
+        Ok(Self::with_indices(ergo_tree, &inputs.contract_parameters))
+    }
 
-        let epoch_length = ergo_tree
-            .get_constant(parameters.epoch_length_index)
-            .map_err(|_| {
-                RefreshContractError::Parameters(RefreshContractParametersError::NoEpochLength)
-            })?
-            .ok_or(RefreshContractError::Parameters(
-                RefreshContractParametersError::NoEpochLength,
-            ))?
-            .try_extract_into::<i32>()?;
-        if epoch_length != parameters.epoch_length {
-            return Err(RefreshContractError::Parameters(
-                RefreshContractParametersError::EpochLengthDiffers {
-                    expected: parameters.epoch_length,
-                    actual: epoch_length,
-                },
-            ));
-        }
+    fn build_with(inputs: &RefreshContractInputs) -> Result<Self, RefreshContractError> {
+        let ergo_tree = with_constants(
+            ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?,
+            &Self::expected_constants(inputs),
+        )
+        .map_err(RefreshContractError::ErgoTreeConstant)?;
+        Ok(Self::with_indices(ergo_tree, &inputs.contract_parameters))
+    }
 
-        Ok(Self {
+    fn with_indices(ergo_tree: ErgoTree, parameters: &RefreshContractParameters) -> Self {
+        Self {
             ergo_tree,
             pool_nft_index: parameters.pool_nft_index,
             oracle_token_id_index: parameters.oracle_token_id_index,
@@ -179,51 +80,43 @@ impl RefreshContract {
             buffer_index: parameters.buffer_length_index,
             max_deviation_percent_index: parameters.max_deviation_percent_index,
             epoch_length_index: parameters.epoch_length_index,
-        })
+        }
     }
 
-    fn build_with(inputs: &RefreshContractInputs) -> Result<Self, RefreshContractError> {
-        let ergo_tree =
-            ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?
-                .with_constant(
-                    inputs.contract_parameters.pool_nft_index,
-                    inputs.pool_nft_token_id.clone().into(),
-                )
-                .map_err(RefreshContractError::ErgoTreeConstant)?
-                .with_constant(
-                    inputs.contract_parameters.oracle_token_id_index,
-                    inputs.oracle_token_id.clone().into(),
-                )
-                .map_err(RefreshContractError::ErgoTreeConstant)?
-                .with_constant(
-                    inputs.contract_parameters.min_data_points_index,
-                    (inputs.contract_parameters.min_data_points).into(),
-                )
-                .map_err(RefreshContractError::ErgoTreeConstant)?
-                .with_constant(
-                    inputs.contract_parameters.buffer_length_index,
-                    (inputs.contract_parameters.buffer_length).into(),
-                )
-                .map_err(RefreshContractError::ErgoTreeConstant)?
-                .with_constant(
-                    inputs.contract_parameters.max_deviation_percent_index,
-                    (inputs.contract_parameters.max_deviation_percent).into(),
-                )
-                .map_err(RefreshContractError::ErgoTreeConstant)?
-                .with_constant(
-                    inputs.contract_parameters.epoch_length_index,
-                    (inputs.contract_parameters.epoch_length).into(),
-                )
-                .map_err(RefreshContractError::ErgoTreeConstant)?;
-        Ok(Self {
-            ergo_tree,
-            pool_nft_index: inputs.contract_parameters.pool_nft_index,
-            oracle_token_id_index: inputs.contract_parameters.oracle_token_id_index,
-            min_data_points_index: inputs.contract_parameters.min_data_points_index,
-            buffer_index: inputs.contract_parameters.buffer_length_index,
-            max_deviation_percent_index: inputs.contract_parameters.max_deviation_percent_index,
-            epoch_length_index: inputs.contract_parameters.epoch_length_index,
-        })
+    fn expected_constants(inputs: &RefreshContractInputs) -> Vec<ExpectedConstant> {
+        let parameters = &inputs.contract_parameters;
+        vec![
+            ExpectedConstant::new(
+                "pool NFT",
+                parameters.pool_nft_index,
+                inputs.pool_nft_token_id.clone(),
+            ),
+            ExpectedConstant::new(
+                "oracle token id",
+                parameters.oracle_token_id_index,
+                inputs.oracle_token_id.clone(),
+            ),
+            ExpectedConstant::new(
+                "min data points",
+                parameters.min_data_points_index,
+                parameters.min_data_points,
+            ),
+            ExpectedConstant::new(
+                "buffer length",
+                parameters.buffer_length_index,
+                parameters.buffer_length,
+            ),
+            ExpectedConstant::new(
+                "max deviation percent",
+                parameters.max_deviation_percent_index,
+                parameters.max_deviation_percent,
+            ),
+            ExpectedConstant::new(
+                "epoch length",
+                parameters.epoch_length_index,
+                parameters.epoch_length,
+            ),
+        ]
     }
 
     pub fn ergo_tree(&self) -> ErgoTree {
@@ -416,12 +309,66 @@ pub enum RefreshContractParametersError {
     TryExtractFrom(#[from] TryExtractFromError),
     #[error("refresh contract parameters: ergo tree constant error {0:?}")]
     ErgoTreeConstant(ErgoTreeConstantError),
+    #[error("refresh contract parameters: constant index {0} is used for more than one parameter")]
+    DuplicateConstantIndex(usize),
+    #[error("refresh contract parameters: `epoch length` must be positive, got {0}")]
+    EpochLengthNotPositive(i32),
+    #[error("refresh contract parameters: `min data points` must be positive, got {0}")]
+    MinDataPointsNotPositive(i32),
+    #[error("refresh contract parameters: `buffer length` must not be negative, got {0}")]
+    BufferLengthNegative(i32),
+    #[error(
+        "refresh contract parameters: `max deviation percent` must be between 0 and 100, got {0}"
+    )]
+    MaxDeviationPercentOutOfRange(i32),
+}
+
+impl RefreshContractParametersInputs {
+    /// Check the parameter values and that each parameter has its own constant index.
+    fn validate(&self) -> Result<(), RefreshContractParametersError> {
+        if self.epoch_length <= 0 {
+            return Err(RefreshContractParametersError::EpochLengthNotPositive(
+                self.epoch_length,
+            ));
+        }
+        if self.min_data_points <= 0 {
+            return Err(RefreshContractParametersError::MinDataPointsNotPositive(
+                self.min_data_points,
+            ));
+        }
+        if self.buffer_length < 0 {
+            return Err(RefreshContractParametersError::BufferLengthNegative(
+                self.buffer_length,
+            ));
+        }
+        if !(0..=100).contains(&self.max_deviation_percent) {
+            return Err(
+                RefreshContractParametersError::MaxDeviationPercentOutOfRange(
+                    self.max_deviation_percent,
+                ),
+            );
+        }
+        let mut indices = vec![
+            self.pool_nft_index,
+            self.oracle_token_id_index,
+            self.min_data_points_index,
+            self.buffer_length_index,
+            self.max_deviation_percent_index,
+            self.epoch_length_index,
+        ];
+        indices.sort_unstable();
+        if let Some(w) = indices.windows(2).find(|w| w[0] == w[1]) {
+            return Err(RefreshContractParametersError::DuplicateConstantIndex(w[0]));
+        }
+        Ok(())
+    }
 }
 
 impl RefreshContractParameters {
     pub fn build_with(
         inputs: RefreshContractParametersInputs,
     ) -> Result<Self, RefreshContractParametersError> {
+        inputs.validate()?;
         let ergo_tree = ErgoTree::sigma_parse_bytes(inputs.ergo_tree_bytes.as_slice())?
             .with_constant(inputs.min_data_points_index, inputs.min_data_points.into())
             .map_err(RefreshContractParametersError::ErgoTreeConstant)?
@@ -462,6 +409,7 @@ impl RefreshContractParameters {
     pub fn checked_load(
         inputs: RefreshContractParametersInputs,
     ) -> Result<Self, RefreshContractParametersError> {
+        inputs.validate()?;
         let ergo_tree = ErgoTree::sigma_parse_bytes(inputs.ergo_tree_bytes.as_slice())?;
         let min_data_points = ergo_tree
             .get_constant(inputs.min_data_points_index)
@@ -650,4 +598,52 @@ mod tests {
         assert_eq!(new_contract.oracle_token_id(), inputs.oracle_token_id);
         assert_eq!(new_contract.pool_nft_token_id(), inputs.pool_nft_token_id);
     }
+
+    #[test]
+    fn test_build_with_validation() {
+        let p = RefreshContractParameters::default();
+        let inputs = || RefreshContractParametersInputs {
+            ergo_tree_bytes: p.ergo_tree_bytes(),
+            pool_nft_index: p.pool_nft_index(),
+            oracle_token_id_index: p.oracle_token_id_index(),
+            min_data_points_index: p.min_data_points_index(),
+            min_data_points: p.min_data_points(),
+            buffer_length_index: p.buffer_length_index(),
+            buffer_length: p.buffer_length(),
+            max_deviation_percent_index: p.max_deviation_percent_index(),
+            max_deviation_percent: p.max_deviation_percent(),
+            epoch_length_index: p.epoch_length_index(),
+            epoch_length: p.epoch_length(),
+        };
+        assert!(matches!(
+            RefreshContractParameters::build_with(RefreshContractParametersInputs {
+                epoch_length: 0,
+                ..inputs()
+            }),
+            Err(RefreshContractParametersError::EpochLengthNotPositive(0))
+        ));
+        assert!(matches!(
+            RefreshContractParameters::build_with(RefreshContractParametersInputs {
+                max_deviation_percent: 101,
+                ..inputs()
+            }),
+            Err(RefreshContractParametersError::MaxDeviationPercentOutOfRange(101))
+        ));
+        assert!(matches!(
+            RefreshContractParameters::build_with(RefreshContractParametersInputs {
+                buffer_length_index: p.epoch_length_index(),
+                ..inputs()
+            }),
+            Err(RefreshContractParametersError::DuplicateConstantIndex(_))
+        ));
+        // Faster epochs, e.g. for testnet
+        let fast = RefreshContractParameters::build_with(RefreshContractParametersInputs {
+            epoch_length: 5,
+            buffer_length: 2,
+            min_data_points: 1,
+            ..inputs()
+        })
+        .unwrap();
+        assert_eq!(fast.epoch_length(), 5);
+    }
 }
@@ -11,7 +11,9 @@ use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
 use ergo_lib::ergotree_ir::serialization::SigmaSerializationError;
 use thiserror::Error;
 
-#[derive(Clone)]
+use super::constants::{verify_constants, with_constants, ConstantMismatch, ExpectedConstant};
+
+#[derive(Clone, Debug)]
 pub struct UpdateContract {
     ergo_tree: ErgoTree,
     pool_nft_index: usize,
@@ -21,20 +23,8 @@ pub struct UpdateContract {
 
 #[derive(Debug, Error, From)]
 pub enum UpdateContractError {
-    #[error("update contract: failed to get pool NFT from constants")]
-    NoPoolNftId,
-    #[error("update contract: unknown pool NFT defined in constants")]
-    UnknownPoolNftId,
-    #[error("update contract: failed to get ballot token id from constants")]
-    NoBallotTokenId,
-    #[error("update contract: unknown ballot token id defined in constants")]
-    UnknownBallotTokenId,
-    #[error("update contract: failed to get minimum votes (must be SInt)")]
-    NoMinVotes,
-    #[error(
-        "update contract: unexpected `min votes` value from constants. Expected {expected}, got {actual}"
-    )]
-    MinVotesDiffers { expected: u64, actual: u64 },
+    #[error("update contract: unexpected {0}")]
+    ConstantMismatch(ConstantMismatch),
     #[error("update contract: sigma parsing error {0}")]
     SigmaParsing(SigmaParsingError),
     #[error("update contract: ergo tree constant error {0:?}")]
@@ -92,20 +82,10 @@ impl UpdateContractInputs {
 
 impl UpdateContract {
     fn build_with(inputs: &UpdateContractInputs) -> Result<Self, UpdateContractError> {
-        let ergo_tree =
-            ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?
-                .with_constant(
-                    inputs.contract_parameters.pool_nft_index,
-                    inputs.pool_nft_token_id.clone().into(),
-                )?
-                .with_constant(
-                    inputs.contract_parameters.ballot_token_index,
-                    inputs.ballot_token_id.clone().into(),
-                )?
-                .with_constant(
-                    inputs.contract_parameters.min_votes_index,
-                    (inputs.contract_parameters.min_votes as i32).into(),
-                )?;
+        let ergo_tree = with_constants(
+            ErgoTree::sigma_parse_bytes(inputs.contract_parameters.ergo_tree_bytes.as_slice())?,
+            &Self::expected_constants(inputs),
+        )?;
         let contract = Self::from_ergo_tree(ergo_tree, inputs)?;
         Ok(contract)
     }
@@ -127,36 +107,7 @@ impl UpdateContract {
         ergo_tree: ErgoTree,
         inputs: &UpdateContractInputs,
     ) -> Result<Self, UpdateContractError> {
-        // dbg!(ergo_tree.get_constants().unwrap());
-        let pool_nft_token_id = ergo_tree
-            .get_constant(inputs.contract_parameters.pool_nft_index)
-            .map_err(|_| UpdateContractError::NoPoolNftId)?
-            .ok_or(UpdateContractError::NoPoolNftId)?
-            .try_extract_into::<TokenId>()?;
-        if pool_nft_token_id != inputs.pool_nft_token_id {
-            return Err(UpdateContractError::UnknownPoolNftId);
-        };
-
-        let ballot_token_id = ergo_tree
-            .get_constant(inputs.contract_parameters.ballot_token_index)
-            .map_err(|_| UpdateContractError::NoBallotTokenId)?
-            .ok_or(UpdateContractError::NoBallotTokenId)?
-            .try_extract_into::<TokenId>()?;
-        if ballot_token_id != inputs.ballot_token_id {
-            return Err(UpdateContractError::UnknownBallotTokenId);
-        };
-
-        let min_votes = ergo_tree
-            .get_constant(inputs.contract_parameters.min_votes_index)
-            .map_err(|_| UpdateContractError::NoMinVotes)?
-            .ok_or(UpdateContractError::NoMinVotes)?
-            .try_extract_into::<i32>()? as u64;
-        if min_votes != inputs.contract_parameters.min_votes {
-            return Err(UpdateContractError::MinVotesDiffers {
-                expected: inputs.contract_parameters.min_votes,
-                actual: min_votes,
-            });
-        };
+        verify_constants(&ergo_tree, &Self::expected_constants(inputs))?;
         Ok(Self {
             ergo_tree,
             pool_nft_index: inputs.contract_parameters.pool_nft_index,
@@ -165,6 +116,26 @@ impl UpdateContract {
         })
     }
 
+    fn expected_constants(inputs: &UpdateContractInputs) -> Vec<ExpectedConstant> {
+        vec![
+            ExpectedConstant::new(
+                "pool NFT",
+                inputs.contract_parameters.pool_nft_index,
+                inputs.pool_nft_token_id.clone(),
+            ),
+            ExpectedConstant::new(
+                "ballot token id",
+                inputs.contract_parameters.ballot_token_index,
+                inputs.ballot_token_id.clone(),
+            ),
+            ExpectedConstant::new(
+                "min votes",
+                inputs.contract_parameters.min_votes_index,
+                inputs.contract_parameters.min_votes as i32,
+            ),
+        ]
+    }
+
     pub fn ergo_tree(&self) -> ErgoTree {
         self.ergo_tree.clone()
     }
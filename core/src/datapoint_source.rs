@@ -5,21 +5,32 @@ mod erg_xau;
 use derive_more::From;
 use thiserror::Error;
 
+use crate::health::HEALTH;
+use crate::latency::{LatencyStage, POSTING_LATENCY};
+
 pub trait DataPointSource: std::fmt::Debug {
     fn get_datapoint(&self) -> Result<i64, DataPointSourceError>;
 
+    /// The outcome is reported by the readiness check of the REST API
     fn get_datapoint_retry(&self, retries: u8) -> Result<i64, DataPointSourceError> {
+        let _span = tracing::info_span!("fetch_datapoint", retries).entered();
         let mut last_error = None;
         for _ in 0..retries {
             match self.get_datapoint() {
-                Ok(datapoint) => return Ok(datapoint),
+                Ok(datapoint) => {
+                    HEALTH.record_data_source_result(None);
+                    POSTING_LATENCY.record(LatencyStage::Fetched);
+                    return Ok(datapoint);
+                }
                 Err(err) => {
                     log::warn!("Failed to get datapoint from source: {}, retrying ...", err);
                     last_error = Some(err)
                 }
             }
         }
-        Err(last_error.unwrap())
+        let err = last_error.unwrap();
+        HEALTH.record_data_source_result(Some(err.to_string()));
+        Err(err)
     }
 }
 
@@ -38,7 +49,7 @@ pub enum DataPointSourceError {
 #[derive(Debug, From, Error)]
 pub enum ExternalScriptError {
     #[error("external script child process error: {0}")]
-    ChildProcess(std::io::Error),
+    ChildProcess(#[source] std::io::Error),
     #[error("String from bytes error: {0}")]
     StringFromBytes(std::string::FromUtf8Error),
     #[error("Parse i64 from string error: {0}")]
@@ -67,11 +78,23 @@ impl DataPointSource for ExternalScript {
     }
 }
 
+/// Always returns the given datapoint. Used to publish a manually provided datapoint.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantDataPointSource(pub i64);
+
+impl DataPointSource for ConstantDataPointSource {
+    fn get_datapoint(&self) -> Result<i64, DataPointSourceError> {
+        Ok(self.0)
+    }
+}
+
 pub use ada_usd::NanoAdaUsd;
 pub use erg_usd::NanoErgUsd;
 pub use erg_xau::NanoErgXau;
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone)]
+#[derive(
+    serde::Serialize, serde::Deserialize, schemars::JsonSchema, Debug, Copy, Clone, PartialEq, Eq,
+)]
 #[allow(clippy::enum_variant_names)]
 pub enum PredefinedDataPointSource {
     NanoErgUsd,
@@ -108,6 +108,17 @@ impl Default for UpdateContractParameters {
 pub fn print_contract_hashes() {
     let encoded_hash = |bytes| base64::encode(blake2b256_hash(bytes));
 
+    if crate::cli_commands::json_output() {
+        crate::cli_commands::print_json(&serde_json::json!({
+            "pool": encoded_hash(&PoolContractParameters::default().ergo_tree_bytes()),
+            "refresh": encoded_hash(&RefreshContractParameters::default().ergo_tree_bytes()),
+            "oracle": encoded_hash(&OracleContractParameters::default().ergo_tree_bytes()),
+            "ballot": encoded_hash(&BallotContractParameters::default().ergo_tree_bytes()),
+            "update": encoded_hash(&UpdateContractParameters::default().ergo_tree_bytes()),
+        }));
+        return;
+    }
+
     println!("BASE 64 ENCODING OF BLAKE2B HASH OF CONTRACT ERGO-TREE BYTES");
     println!("------------------------------------------------------------\n");
 
@@ -0,0 +1,228 @@
+//! Intervals in which the daemon could not operate, by cause, to tell the failures of the
+//! infrastructure of an oracle from its negligence. The main loop checks the node, the wallet and
+//! the data source on every iteration, an interval opens at the first failed check and closes at
+//! the next successful one. The intervals are saved to `downtime.json` and the report is served on
+//! `/downtime` of the REST API. The time the daemon was stopped is not counted.
+
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use derive_more::From;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+pub const DOWNTIME_FILE: &str = "downtime.json";
+
+pub static DOWNTIME: Lazy<Mutex<DowntimeTracker>> =
+    Lazy::new(|| Mutex::new(DowntimeTracker::default()));
+
+#[derive(Debug, From, Error)]
+pub enum DowntimeError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("downtime file error: {0}")]
+    DowntimeFile(serde_json::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DowntimeCause {
+    NodeDown,
+    SourceDown,
+    WalletLocked,
+}
+
+const CAUSES: [DowntimeCause; 3] = [
+    DowntimeCause::NodeDown,
+    DowntimeCause::SourceDown,
+    DowntimeCause::WalletLocked,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct DowntimeInterval {
+    pub cause: DowntimeCause,
+    /// Unix time in seconds
+    pub start: u64,
+    /// `None` while the interval lasts
+    pub end: Option<u64>,
+    /// Error of the first failed check
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DowntimeTracker {
+    /// First observation, the start of the report
+    pub first_observed: Option<u64>,
+    /// Last observation, ends the intervals left open by a stopped daemon
+    pub last_observed: Option<u64>,
+    /// Oldest first
+    pub intervals: Vec<DowntimeInterval>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct CauseDowntime {
+    pub cause: DowntimeCause,
+    pub intervals: u32,
+    pub downtime_secs: u64,
+    /// Share of the report period without this cause
+    pub uptime_percent: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct DowntimeReport {
+    pub from: u64,
+    pub to: u64,
+    pub causes: Vec<CauseDowntime>,
+    /// The intervals overlapping the report period, oldest first
+    pub intervals: Vec<DowntimeInterval>,
+}
+
+impl DowntimeTracker {
+    /// The saved intervals, the ones left open by the previous run end at its last observation
+    pub fn load(path: &Path) -> Result<Self, DowntimeError> {
+        if !path.exists() {
+            return Ok(DowntimeTracker::default());
+        }
+        let mut tracker: DowntimeTracker = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let last_observed = tracker.last_observed;
+        for interval in tracker.intervals.iter_mut().filter(|i| i.end.is_none()) {
+            interval.end = Some(last_observed.unwrap_or(interval.start));
+        }
+        Ok(tracker)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), DowntimeError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Record the outcome of the check of `cause` at `now`, `Err` with the error of a failed check.
+    /// Returns `true` if an interval opened or closed.
+    pub fn observe(&mut self, now: u64, cause: DowntimeCause, check: Result<(), String>) -> bool {
+        self.first_observed.get_or_insert(now);
+        self.last_observed = Some(now);
+        let open = self
+            .intervals
+            .iter_mut()
+            .find(|i| i.cause == cause && i.end.is_none());
+        match (open, check) {
+            (None, Err(detail)) => {
+                self.intervals.push(DowntimeInterval {
+                    cause,
+                    start: now,
+                    end: None,
+                    detail,
+                });
+                true
+            }
+            (Some(interval), Ok(())) => {
+                interval.end = Some(now);
+                true
+            }
+            (Some(_), Err(_)) | (None, Ok(())) => false,
+        }
+    }
+
+    pub fn has_open_interval(&self) -> bool {
+        self.intervals.iter().any(|i| i.end.is_none())
+    }
+
+    /// Downtime by cause between `from` (the first observation if not given) and `now`
+    pub fn report(&self, from: Option<u64>, now: u64) -> DowntimeReport {
+        let from = from.or(self.first_observed).unwrap_or(now).min(now);
+        let period = now - from;
+        let intervals: Vec<DowntimeInterval> = self
+            .intervals
+            .iter()
+            .filter(|i| i.end.map_or(true, |end| end > from))
+            .cloned()
+            .collect();
+        let causes = CAUSES
+            .iter()
+            .map(|cause| {
+                let cause_intervals: Vec<&DowntimeInterval> =
+                    intervals.iter().filter(|i| i.cause == *cause).collect();
+                let downtime_secs: u64 = cause_intervals
+                    .iter()
+                    .map(|i| {
+                        i.end
+                            .unwrap_or(now)
+                            .min(now)
+                            .saturating_sub(i.start.max(from))
+                    })
+                    .sum();
+                CauseDowntime {
+                    cause: *cause,
+                    intervals: cause_intervals.len() as u32,
+                    downtime_secs,
+                    uptime_percent: if period == 0 {
+                        100.0
+                    } else {
+                        100.0 - downtime_secs as f64 / period as f64 * 100.0
+                    },
+                }
+            })
+            .collect();
+        DowntimeReport {
+            from,
+            to: now,
+            causes,
+            intervals,
+        }
+    }
+}
+
+/// Unix time in seconds
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downtime_tracker() {
+        let mut tracker = DowntimeTracker::default();
+        assert!(!tracker.observe(1000, DowntimeCause::NodeDown, Ok(())));
+        assert!(tracker.observe(1100, DowntimeCause::NodeDown, Err("refused".to_string())));
+        assert!(!tracker.observe(1130, DowntimeCause::NodeDown, Err("timeout".to_string())));
+        assert!(tracker.observe(1200, DowntimeCause::SourceDown, Err("503".to_string())));
+        assert!(tracker.observe(1300, DowntimeCause::NodeDown, Ok(())));
+
+        let report = tracker.report(None, 2000);
+        assert_eq!(report.from, 1000);
+        assert_eq!(report.intervals.len(), 2);
+        assert_eq!(report.intervals[0].detail, "refused");
+        let node = &report.causes[0];
+        assert_eq!(node.intervals, 1);
+        assert_eq!(node.downtime_secs, 200);
+        assert_eq!(node.uptime_percent, 80.0);
+        // Still open
+        assert_eq!(report.causes[1].downtime_secs, 800);
+        assert_eq!(report.causes[2].downtime_secs, 0);
+        assert_eq!(report.causes[2].uptime_percent, 100.0);
+
+        // Clipped to the period
+        let report = tracker.report(Some(1250), 2000);
+        assert_eq!(report.causes[0].downtime_secs, 50);
+        assert_eq!(report.causes[1].downtime_secs, 750);
+    }
+
+    #[test]
+    fn test_load_closes_open_intervals() {
+        let mut tracker = DowntimeTracker::default();
+        tracker.observe(1000, DowntimeCause::WalletLocked, Err("locked".to_string()));
+        tracker.observe(1030, DowntimeCause::NodeDown, Ok(()));
+        let path = std::env::temp_dir().join("oracle_core_test_downtime.json");
+        tracker.save(&path).unwrap();
+        let loaded = DowntimeTracker::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.intervals[0].end, Some(1030));
+    }
+}
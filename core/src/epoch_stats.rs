@@ -0,0 +1,259 @@
+//! Statistics of the finished epochs served on `/epochStats` of the REST API, for pool admins to
+//! spot underperforming oracles. The main loop follows the datapoints posted in the current epoch
+//! and completes the statistics of the epoch with the refresh tx ending it. The statistics are kept
+//! in memory for the last `epoch_stats_window` epochs.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use ergo_lib::ergotree_ir::chain::address::{Address, NetworkAddress, NetworkPrefix};
+use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::oracle_config::ORACLE_CONFIG;
+
+/// Epochs kept without `epoch_stats_window` in the config
+const DEFAULT_WINDOW: usize = 100;
+
+pub static EPOCH_STATS: Lazy<EpochStatsLog> = Lazy::new(|| {
+    EpochStatsLog::new(
+        ORACLE_CONFIG
+            .epoch_stats_window
+            .map_or(DEFAULT_WINDOW, |w| w as usize),
+    )
+});
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct EpochStats {
+    pub epoch: u32,
+    /// Oracles that posted a datapoint in the epoch
+    pub posted: usize,
+    /// Datapoints included in the rate by the refresh tx
+    pub collected: usize,
+    pub min_datapoint: Option<u64>,
+    pub max_datapoint: Option<u64>,
+    /// Difference between the highest and the lowest datapoint relative to the finalized rate
+    pub spread_percent: Option<f64>,
+    /// Rate set by the refresh tx ending the epoch
+    pub finalized_rate: u64,
+    pub refresh_height: u32,
+    /// Address of the oracle that built the refresh tx, `None` if its datapoint wasn't seen
+    pub collector: Option<String>,
+    /// Deviation of the datapoint of this oracle from the finalized rate, `None` if it didn't post
+    pub local_deviation_percent: Option<f64>,
+}
+
+/// A datapoint box of the current epoch
+#[derive(Debug, Clone)]
+pub struct PostedDatapoint {
+    pub oracle: ProveDlog,
+    pub rate: u64,
+    pub reward_tokens: u64,
+}
+
+/// An oracle box recreated by the refresh tx
+#[derive(Debug, Clone)]
+pub struct CollectedDatapoint {
+    pub oracle: ProveDlog,
+    pub reward_tokens: u64,
+}
+
+/// Follows the epochs of the pool box
+pub struct EpochTracker {
+    network_prefix: NetworkPrefix,
+    local_public_key: Option<ProveDlog>,
+    epoch: Option<u32>,
+    /// The current epoch was followed since its start
+    complete: bool,
+    datapoints: Vec<PostedDatapoint>,
+}
+
+impl EpochTracker {
+    pub fn new(network_prefix: NetworkPrefix, local_public_key: Option<ProveDlog>) -> Self {
+        EpochTracker {
+            network_prefix,
+            local_public_key,
+            epoch: None,
+            complete: false,
+            datapoints: Vec::new(),
+        }
+    }
+
+    /// Observe the pool box of `epoch` (set by a refresh tx at `pool_box_height`), the datapoints
+    /// posted in `epoch` and the oracle boxes created by the refresh tx. Returns the statistics of
+    /// the previous epoch when the refresh tx ending it is observed. The first epoch observed is
+    /// skipped, its datapoints posted before the daemon started are not known.
+    pub fn observe(
+        &mut self,
+        epoch: u32,
+        pool_rate: u64,
+        pool_box_height: u32,
+        datapoints: Vec<PostedDatapoint>,
+        collected: &[CollectedDatapoint],
+    ) -> Option<EpochStats> {
+        let mut stats = None;
+        if self.epoch != Some(epoch) {
+            if self.complete && self.epoch.map(|e| e + 1) == Some(epoch) {
+                stats = Some(self.finish_epoch(pool_rate, pool_box_height, collected));
+            }
+            self.complete = self.epoch.is_some();
+            self.epoch = Some(epoch);
+            self.datapoints.clear();
+        }
+        // The latest box of an oracle replaces its earlier one (republished datapoint)
+        for datapoint in datapoints {
+            self.datapoints.retain(|d| d.oracle != datapoint.oracle);
+            self.datapoints.push(datapoint);
+        }
+        stats
+    }
+
+    fn finish_epoch(
+        &self,
+        finalized_rate: u64,
+        refresh_height: u32,
+        collected: &[CollectedDatapoint],
+    ) -> EpochStats {
+        let rates = self.datapoints.iter().map(|d| d.rate);
+        let min_datapoint = rates.clone().min();
+        let max_datapoint = rates.max();
+        let percent_of_rate = |diff: f64| {
+            Some(finalized_rate)
+                .filter(|r| *r != 0)
+                .map(|r| diff / r as f64 * 100.0)
+        };
+        // The collector gets 1 reward token per collected datapoint on top of its own
+        let collector = collected
+            .iter()
+            .find(|c| {
+                self.datapoints.iter().any(|d| {
+                    d.oracle == c.oracle && c.reward_tokens.saturating_sub(d.reward_tokens) > 1
+                })
+            })
+            .map(|c| {
+                NetworkAddress::new(self.network_prefix, &Address::P2Pk(c.oracle.clone()))
+                    .to_base58()
+            });
+        let local_deviation_percent = self
+            .datapoints
+            .iter()
+            .find(|d| Some(&d.oracle) == self.local_public_key.as_ref())
+            .and_then(|d| percent_of_rate(d.rate as f64 - finalized_rate as f64));
+        EpochStats {
+            epoch: self.epoch.unwrap_or_default(),
+            posted: self.datapoints.len(),
+            collected: collected.len(),
+            min_datapoint,
+            max_datapoint,
+            spread_percent: min_datapoint
+                .zip(max_datapoint)
+                .and_then(|(min, max)| percent_of_rate((max - min) as f64)),
+            finalized_rate,
+            refresh_height,
+            collector,
+            local_deviation_percent,
+        }
+    }
+}
+
+/// Statistics of the last `window` epochs, oldest first
+pub struct EpochStatsLog {
+    window: usize,
+    stats: Mutex<VecDeque<EpochStats>>,
+}
+
+impl EpochStatsLog {
+    pub fn new(window: usize) -> Self {
+        EpochStatsLog {
+            window,
+            stats: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn push(&self, stats: EpochStats) {
+        let mut log = self.stats.lock().unwrap();
+        log.push_back(stats);
+        while log.len() > self.window {
+            log.pop_front();
+        }
+    }
+
+    /// The statistics of the last `count` epochs (all of them if `None`)
+    pub fn last(&self, count: Option<usize>) -> Vec<EpochStats> {
+        let log = self.stats.lock().unwrap();
+        let skip = count.map_or(0, |c| log.len().saturating_sub(c));
+        log.iter().skip(skip).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    #[test]
+    fn test_epoch_tracker() {
+        let oracles: Vec<ProveDlog> = (0..3)
+            .map(|_| force_any_val::<DlogProverInput>().public_image())
+            .collect();
+        let posted = |i: usize, rate: u64| PostedDatapoint {
+            oracle: oracles[i].clone(),
+            rate,
+            reward_tokens: 10,
+        };
+        let mut tracker = EpochTracker::new(NetworkPrefix::Mainnet, Some(oracles[0].clone()));
+        // First epoch observed
+        assert_eq!(
+            tracker.observe(5, 1000, 100, vec![posted(0, 990)], &[]),
+            None
+        );
+        assert_eq!(
+            tracker.observe(6, 1000, 130, vec![posted(0, 1040)], &[]),
+            None
+        );
+        assert_eq!(
+            tracker.observe(6, 1000, 130, vec![posted(1, 960), posted(2, 1500)], &[]),
+            None
+        );
+        // Oracle 1 collected the datapoints of oracles 0 and 1, oracle 2 is an outlier
+        let collected = [
+            CollectedDatapoint {
+                oracle: oracles[1].clone(),
+                reward_tokens: 13,
+            },
+            CollectedDatapoint {
+                oracle: oracles[0].clone(),
+                reward_tokens: 11,
+            },
+        ];
+        let stats = tracker.observe(7, 1000, 160, vec![], &collected).unwrap();
+        assert_eq!(stats.epoch, 6);
+        assert_eq!(stats.posted, 3);
+        assert_eq!(stats.collected, 2);
+        assert_eq!(stats.min_datapoint, Some(960));
+        assert_eq!(stats.max_datapoint, Some(1500));
+        assert_eq!(stats.spread_percent, Some(54.0));
+        assert_eq!(
+            stats.collector,
+            Some(
+                NetworkAddress::new(NetworkPrefix::Mainnet, &Address::P2Pk(oracles[1].clone()))
+                    .to_base58()
+            )
+        );
+        assert_eq!(stats.local_deviation_percent, Some(4.0));
+
+        let log = EpochStatsLog::new(2);
+        for epoch in 0..3 {
+            log.push(EpochStats {
+                epoch,
+                ..stats.clone()
+            });
+        }
+        assert_eq!(log.last(None).len(), 2);
+        assert_eq!(log.last(Some(1))[0].epoch, 2);
+    }
+}
@@ -0,0 +1,156 @@
+//! Events of the pool pushed to the clients of the `/events` WebSocket of the REST API, as JSON
+//! text messages tagged with `event`. The epochs and datapoints are reported by the
+//! [`PoolWatcher`](crate::pool_watch::PoolWatcher) of the main loop, the txs of the daemon when
+//! they are submitted and settled.
+
+use ergo_lib::ergotree_ir::chain::address::{Address, NetworkAddress, NetworkPrefix};
+use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::node_interface::TxId;
+use crate::pool_watch::WatchEvent;
+
+/// Events a slow client can lag behind before missing some
+const EVENTS_CAPACITY: usize = 256;
+
+pub static EVENTS: Lazy<EventBus> = Lazy::new(|| EventBus::new(EVENTS_CAPACITY));
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum PoolEvent {
+    /// The pool box was refreshed with `rate`
+    EpochStarted {
+        epoch: u32,
+        rate: u64,
+        height: u32,
+    },
+    DatapointPosted {
+        oracle_address: String,
+        epoch: u32,
+        rate: u64,
+        /// Posted by this oracle
+        local: bool,
+        /// Outside of the deviation range of the refresh contract
+        outlier: bool,
+    },
+    EpochOverdue {
+        epoch: u32,
+        blocks: u32,
+    },
+    TxSubmitted {
+        tx_id: TxId,
+        action: String,
+    },
+    TxConfirmed {
+        tx_id: TxId,
+    },
+    /// The tx left the mempool without being confirmed, its action is built again
+    TxFailed {
+        tx_id: TxId,
+    },
+    /// Implausible jump of the finalized pool rate, see [`crate::rate_anomaly`]
+    RateAnomaly {
+        epoch: u32,
+        rate: u64,
+        previous_rate: u64,
+        change_percent: f64,
+        z_score: Option<f64>,
+    },
+}
+
+impl PoolEvent {
+    pub fn from_watch_event(
+        event: &WatchEvent,
+        network_prefix: NetworkPrefix,
+        local_public_key: Option<&ProveDlog>,
+    ) -> Self {
+        match event {
+            WatchEvent::NewEpoch {
+                epoch,
+                rate,
+                height,
+            } => PoolEvent::EpochStarted {
+                epoch: *epoch,
+                rate: *rate,
+                height: *height,
+            },
+            WatchEvent::Datapoint {
+                oracle,
+                epoch,
+                rate,
+                outlier,
+                ..
+            } => PoolEvent::DatapointPosted {
+                oracle_address: NetworkAddress::new(network_prefix, &Address::P2Pk(oracle.clone()))
+                    .to_base58(),
+                epoch: *epoch,
+                rate: *rate,
+                local: local_public_key == Some(oracle),
+                outlier: *outlier,
+            },
+            WatchEvent::EpochOverdue { epoch, blocks } => PoolEvent::EpochOverdue {
+                epoch: *epoch,
+                blocks: *blocks,
+            },
+        }
+    }
+}
+
+pub struct EventBus {
+    sender: broadcast::Sender<PoolEvent>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        EventBus { sender }
+    }
+
+    /// Send `event` to the current subscribers, dropped if there are none
+    pub fn publish(&self, event: PoolEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PoolEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    #[test]
+    fn test_event_bus() {
+        let bus = EventBus::new(4);
+        // No subscriber
+        bus.publish(PoolEvent::TxConfirmed {
+            tx_id: "tx0".to_string(),
+        });
+        let mut receiver = bus.subscribe();
+        let local_public_key = force_any_val::<DlogProverInput>().public_image();
+        let event = PoolEvent::from_watch_event(
+            &WatchEvent::Datapoint {
+                oracle: local_public_key.clone(),
+                epoch: 3,
+                rate: 1000,
+                deviation_percent: Some(1.0),
+                outlier: false,
+            },
+            NetworkPrefix::Mainnet,
+            Some(&local_public_key),
+        );
+        bus.publish(event.clone());
+        assert_eq!(receiver.try_recv().unwrap(), event);
+        assert!(receiver.try_recv().is_err());
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "datapoint_posted");
+        assert_eq!(json["local"], true);
+        assert_eq!(json["epoch"], 3);
+    }
+}
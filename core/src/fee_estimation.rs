@@ -0,0 +1,201 @@
+//! Fee of the txs estimated from their size. With `fee_estimation` in the config the built txs pay
+//! `nanoergs_per_byte` for every byte of the signed tx (between `min_fee` and `max_fee`) instead of
+//! the flat `base_fee`, so that the small txs (e.g. publishing a datapoint) don't overpay and the
+//! refresh txs with many datapoint inputs don't underpay. The tx builders build the tx with the
+//! flat fee first, the difference with the estimated fee is then taken from or returned to the
+//! change box. The actions with a fee set in `tx_fees` keep paying it.
+
+use std::convert::{TryFrom, TryInto};
+
+use derive_more::From;
+use ergo_lib::{
+    chain::transaction::unsigned::UnsignedTransaction,
+    ergotree_ir::{
+        chain::{address::Address, ergo_box::box_value::BoxValue},
+        serialization::{SigmaParsingError, SigmaSerializationError},
+    },
+    wallet::miner_fee::MINERS_FEE_ADDRESS,
+};
+use thiserror::Error;
+
+use crate::oracle_config::{FeeEstimationConfig, TxFeeAction, MAYBE_ORACLE_CONFIG};
+
+/// Default `min_fee`, the minimal fee of the node mempool
+pub const DEFAULT_MIN_FEE: u64 = 1_000_000;
+
+/// Size of the proof of a P2PK input (Schnorr signature), the unsigned tx has empty proofs
+const SIGNATURE_SIZE: usize = 56;
+
+#[derive(Debug, Error, From)]
+pub enum FeeEstimationError {
+    #[error("tx serialization error: {0}")]
+    Serialization(SigmaSerializationError),
+    #[error("address error: {0}")]
+    Address(SigmaParsingError),
+    #[error("the tx has no fee box")]
+    NoFeeBox,
+    #[error("the tx has no change box")]
+    NoChangeBox,
+    #[error("the change box would be left with {0} nanoERGs")]
+    #[from(ignore)]
+    ChangeTooLow(i128),
+}
+
+/// Fee in nanoERGs of a signed tx of `tx_size` bytes
+pub fn estimate_fee(config: &FeeEstimationConfig, tx_size: usize) -> u64 {
+    let fee = (tx_size as u64)
+        .saturating_mul(config.nanoergs_per_byte)
+        .max(config.min_fee.unwrap_or(DEFAULT_MIN_FEE));
+    match config.max_fee {
+        Some(max_fee) => fee.min(max_fee),
+        None => fee,
+    }
+}
+
+/// Size in bytes of `tx` once signed, every input counted with a signature
+pub fn signed_tx_size(tx: &UnsignedTransaction) -> Result<usize, SigmaSerializationError> {
+    Ok(tx.bytes_to_sign()?.len() + tx.inputs.len() * SIGNATURE_SIZE)
+}
+
+/// `tx` paying the fee estimated from its size, if `fee_estimation` is set in the config and the
+/// fee of the `action` isn't set in `tx_fees`. The flat fee is kept when the fee can't be
+/// adjusted on the change box to `change_address` (e.g. the tx has no change box).
+pub fn apply_estimated_fee(
+    tx: UnsignedTransaction,
+    action: TxFeeAction,
+    change_address: &Address,
+) -> UnsignedTransaction {
+    let config = match MAYBE_ORACLE_CONFIG.as_ref() {
+        Ok(config) => config,
+        Err(_) => return tx,
+    };
+    let fee_estimation = match &config.fee_estimation {
+        Some(fee_estimation)
+            if config
+                .tx_fees
+                .as_ref()
+                .and_then(|fees| fees.fee(action))
+                .is_none() =>
+        {
+            fee_estimation
+        }
+        _ => return tx,
+    };
+    match with_estimated_fee(&tx, fee_estimation, change_address) {
+        Ok(estimated_tx) => estimated_tx,
+        Err(e) => {
+            log::warn!("Keeping the flat fee of the {:?} tx: {}", action, e);
+            tx
+        }
+    }
+}
+
+fn with_estimated_fee(
+    tx: &UnsignedTransaction,
+    config: &FeeEstimationConfig,
+    change_address: &Address,
+) -> Result<UnsignedTransaction, FeeEstimationError> {
+    let fee = estimate_fee(config, signed_tx_size(tx)?);
+    let fee_tree = MINERS_FEE_ADDRESS.script()?;
+    let change_tree = change_address.script()?;
+    let mut outputs = tx.output_candidates.as_vec().clone();
+    // The tx builder puts the fee box last, after the change boxes
+    let fee_index = outputs
+        .iter()
+        .rposition(|b| b.ergo_tree == fee_tree)
+        .ok_or(FeeEstimationError::NoFeeBox)?;
+    let change_index = outputs[..fee_index]
+        .iter()
+        .rposition(|b| b.ergo_tree == change_tree)
+        .ok_or(FeeEstimationError::NoChangeBox)?;
+    let flat_fee = *outputs[fee_index].value.as_u64();
+    if fee == flat_fee {
+        return Ok(tx.clone());
+    }
+    let change = *outputs[change_index].value.as_u64() as i128 + flat_fee as i128 - fee as i128;
+    if change < *BoxValue::SAFE_USER_MIN.as_u64() as i128 {
+        return Err(FeeEstimationError::ChangeTooLow(change));
+    }
+    // Both values were checked above
+    outputs[change_index].value = BoxValue::try_from(change as u64).unwrap();
+    outputs[fee_index].value = BoxValue::try_from(fee).unwrap();
+    Ok(UnsignedTransaction::new(
+        tx.inputs.clone(),
+        tx.data_inputs.clone(),
+        outputs.try_into().unwrap(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool_commands::test_utils::make_wallet_unspent_box;
+    use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::wallet::box_selector::{BoxSelector, SimpleBoxSelector};
+    use ergo_lib::wallet::tx_builder::{TxBuilder, SUGGESTED_TX_FEE};
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_estimated_fee() {
+        let config = FeeEstimationConfig {
+            nanoergs_per_byte: 5000,
+            min_fee: None,
+            max_fee: Some(10_000_000),
+        };
+        assert_eq!(estimate_fee(&config, 100), DEFAULT_MIN_FEE);
+        assert_eq!(estimate_fee(&config, 1000), 5_000_000);
+        assert_eq!(estimate_fee(&config, 3000), 10_000_000);
+
+        let wallet_secret = force_any_val::<DlogProverInput>();
+        let change_address = Address::P2Pk(force_any_val::<DlogProverInput>().public_image());
+        let wallet_box = make_wallet_unspent_box(
+            wallet_secret.public_image(),
+            BoxValue::try_from(100_000_000u64).unwrap(),
+            None,
+        );
+        let output = ErgoBoxCandidateBuilder::new(
+            BoxValue::SAFE_USER_MIN,
+            Address::P2Pk(wallet_secret.public_image())
+                .script()
+                .unwrap(),
+            1,
+        )
+        .build()
+        .unwrap();
+        let target_balance = BoxValue::SAFE_USER_MIN
+            .checked_add(&SUGGESTED_TX_FEE())
+            .unwrap();
+        let selection = SimpleBoxSelector::new()
+            .select(vec![wallet_box], target_balance, &[])
+            .unwrap();
+        let tx = TxBuilder::new(
+            selection,
+            vec![output],
+            1,
+            SUGGESTED_TX_FEE(),
+            change_address.clone(),
+        )
+        .build()
+        .unwrap();
+        let size = signed_tx_size(&tx).unwrap();
+        let estimated_tx = with_estimated_fee(&tx, &config, &change_address).unwrap();
+        let values = |tx: &UnsignedTransaction| {
+            tx.output_candidates
+                .iter()
+                .map(|b| *b.value.as_u64())
+                .collect::<Vec<_>>()
+        };
+        let (flat, estimated) = (values(&tx), values(&estimated_tx));
+        assert_eq!(estimated[0], flat[0]);
+        assert_eq!(estimated[2], estimate_fee(&config, size));
+        assert_eq!(estimated.iter().sum::<u64>(), flat.iter().sum::<u64>());
+
+        // Without a change box to adjust the flat fee is kept
+        let other_address = Address::P2Pk(force_any_val::<DlogProverInput>().public_image());
+        assert!(matches!(
+            with_estimated_fee(&tx, &config, &other_address),
+            Err(FeeEstimationError::NoChangeBox)
+        ));
+    }
+}
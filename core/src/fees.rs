@@ -0,0 +1,215 @@
+//! Accounting of the tx fees paid by the oracle wallet, for the operators budgeting ERG for the
+//! fees. Every tx signed and submitted by the daemon or by a command is recorded in the store with
+//! its miner fee, the UTC day and the pool epoch last observed by the daemon (see `tx_history`). The ledger sums the
+//! fees by action, day and epoch, it is shown by `print-status`, served on `/fees` of the REST API
+//! and exported as metrics.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::Transaction;
+use ergo_lib::wallet::miner_fee::MINERS_FEE_ADDRESS;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::metrics::METRICS;
+use crate::store;
+
+/// Epochs kept in `by_epoch`
+const EPOCHS_KEPT: usize = 1000;
+
+/// The epoch the daemon last observed
+static OBSERVED_EPOCH: Lazy<Mutex<Option<u32>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FeeTotal {
+    pub txs: u64,
+    pub nanoergs: u64,
+}
+
+impl FeeTotal {
+    fn add(&mut self, nanoergs: u64) {
+        self.txs += 1;
+        self.nanoergs += nanoergs;
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FeeLedger {
+    /// Epoch of the pool last observed by the daemon, the new fees are counted in it
+    pub epoch: Option<u32>,
+    pub total: FeeTotal,
+    /// By action, e.g. `refresh` or `transfer_oracle_token`
+    #[schema(value_type = Object)]
+    pub by_action: BTreeMap<String, FeeTotal>,
+    /// By UTC day (`YYYY-MM-DD`)
+    #[schema(value_type = Object)]
+    pub by_day: BTreeMap<String, FeeTotal>,
+    /// The last 1000 epochs
+    #[schema(value_type = Object)]
+    pub by_epoch: BTreeMap<u32, FeeTotal>,
+}
+
+impl FeeLedger {
+    /// Count a fee in the current epoch
+    pub fn record(&mut self, action: &str, nanoergs: u64, day: String) {
+        self.record_in_epoch(action, nanoergs, day, self.epoch);
+    }
+
+    pub fn record_in_epoch(
+        &mut self,
+        action: &str,
+        nanoergs: u64,
+        day: String,
+        epoch: Option<u32>,
+    ) {
+        self.total.add(nanoergs);
+        self.by_action
+            .entry(action.to_string())
+            .or_default()
+            .add(nanoergs);
+        self.by_day.entry(day).or_default().add(nanoergs);
+        if let Some(epoch) = epoch {
+            self.by_epoch.entry(epoch).or_default().add(nanoergs);
+            while self.by_epoch.len() > EPOCHS_KEPT {
+                let oldest = *self.by_epoch.keys().next().unwrap();
+                self.by_epoch.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn day(&self, day: &str) -> FeeTotal {
+        self.by_day.get(day).copied().unwrap_or_default()
+    }
+
+    pub fn current_epoch(&self) -> FeeTotal {
+        self.epoch
+            .and_then(|e| self.by_epoch.get(&e))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Fees paid in total, today and in the current epoch, shown by `print-status`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FeeStatus {
+    pub total: FeeTotal,
+    pub today: FeeTotal,
+    pub current_epoch: FeeTotal,
+}
+
+impl From<&FeeLedger> for FeeStatus {
+    fn from(ledger: &FeeLedger) -> Self {
+        FeeStatus {
+            total: ledger.total,
+            today: ledger.day(&today()),
+            current_epoch: ledger.current_epoch(),
+        }
+    }
+}
+
+/// Sum of the outputs of `tx` paying the miner fee
+pub fn tx_fee(tx: &Transaction) -> u64 {
+    let fee_tree = match MINERS_FEE_ADDRESS.script() {
+        Ok(fee_tree) => fee_tree,
+        Err(_) => return 0,
+    };
+    tx.outputs
+        .iter()
+        .filter(|b| b.ergo_tree == fee_tree)
+        .map(|b| *b.value.as_u64())
+        .sum()
+}
+
+/// Sum of the output candidates of `tx` paying the miner fee
+pub fn unsigned_tx_fee(tx: &UnsignedTransaction) -> u64 {
+    let fee_tree = match MINERS_FEE_ADDRESS.script() {
+        Ok(fee_tree) => fee_tree,
+        Err(_) => return 0,
+    };
+    tx.output_candidates
+        .iter()
+        .filter(|b| b.ergo_tree == fee_tree)
+        .map(|b| *b.value.as_u64())
+        .sum()
+}
+
+/// The daemon observed the pool box of `epoch`, the following fees are counted in it. The metrics
+/// pick up the fees paid by the commands when the epoch changes.
+pub fn observe_epoch(epoch: u32) {
+    let mut observed_epoch = OBSERVED_EPOCH.lock().unwrap();
+    if *observed_epoch == Some(epoch) {
+        return;
+    }
+    *observed_epoch = Some(epoch);
+    let res = store::get().and_then(|store| {
+        store.set_epoch(epoch)?;
+        store.fee_ledger()
+    });
+    match res {
+        Ok(ledger) => METRICS.set_fee_totals(ledger.by_action),
+        Err(e) => log::warn!("Failed to update the fee ledger: {}", e),
+    }
+}
+
+/// Today's UTC date
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    utc_date(secs)
+}
+
+/// UTC date (`YYYY-MM-DD`) of the unix time `secs`
+fn utc_date(secs: u64) -> String {
+    // Days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_date() {
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_date(951_782_400), "2000-02-29");
+        assert_eq!(utc_date(1_700_000_000), "2023-11-14");
+    }
+
+    #[test]
+    fn test_fee_ledger() {
+        let mut ledger = FeeLedger::default();
+        ledger.record("transfer_oracle_token", 1_000_000, "2023-11-14".to_string());
+        ledger.epoch = Some(7);
+        ledger.record("refresh", 2_000_000, "2023-11-14".to_string());
+        ledger.record("refresh", 2_000_000, "2023-11-15".to_string());
+        assert_eq!(
+            ledger.total,
+            FeeTotal {
+                txs: 3,
+                nanoergs: 5_000_000
+            }
+        );
+        assert_eq!(ledger.by_action["refresh"].nanoergs, 4_000_000);
+        assert_eq!(ledger.day("2023-11-14").txs, 2);
+        assert_eq!(ledger.day("2023-11-16"), FeeTotal::default());
+        assert_eq!(ledger.current_epoch().nanoergs, 4_000_000);
+        assert_eq!(ledger.by_epoch.len(), 1);
+
+        let json = serde_json::to_string(&ledger).unwrap();
+        assert_eq!(serde_json::from_str::<FeeLedger>(&json).unwrap(), ledger);
+    }
+}
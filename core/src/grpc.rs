@@ -0,0 +1,179 @@
+//! gRPC service of the daemon (`grpc` feature), see `proto/oracle_core.proto`. The status RPCs
+//! serve the same data as the REST API, the admin RPCs are forwarded to the main loop through
+//! [`ADMIN_REQUESTS`]. Every call must carry the token of `auth_token_file` as an
+//! `authorization: Bearer <token>` metadata entry.
+
+use std::fs;
+
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use tonic::service::Interceptor;
+use tonic::transport::{Identity, Server, ServerTlsConfig};
+use tonic::{Request, Response, Status};
+
+use crate::admin::{read_token_file, token_matches, ADMIN_REQUESTS};
+use crate::api::{get_oracle_status, get_pool_info, get_pool_status};
+use crate::node_interface::current_block_height;
+use crate::oracle_config::GrpcConfig;
+
+pub mod proto {
+    tonic::include_proto!("oracle_core");
+}
+
+use proto::oracle_core_server::{OracleCore, OracleCoreServer};
+use proto::{BlockHeight, Empty, OracleStatus, PoolInfo, PoolStatus};
+
+fn token_id_to_base64(token_id: TokenId) -> String {
+    let bytes: Vec<u8> = token_id.into();
+    base64::encode(bytes)
+}
+
+/// Run `f` (which talks to the node) on the blocking thread pool
+async fn blocking<T, F>(f: F) -> Result<Response<T>, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, anyhow::Error> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(Ok(value)) => Ok(Response::new(value)),
+        Ok(Err(e)) => Err(Status::unavailable(e.to_string())),
+        Err(e) => Err(Status::internal(e.to_string())),
+    }
+}
+
+pub struct OracleCoreService;
+
+#[tonic::async_trait]
+impl OracleCore for OracleCoreService {
+    async fn get_pool_info(&self, _: Request<Empty>) -> Result<Response<PoolInfo>, Status> {
+        blocking(|| {
+            let info = get_pool_info()?;
+            Ok(PoolInfo {
+                number_of_oracles: info.number_of_oracles,
+                datapoint_address: info.datapoint_address,
+                live_epoch_length: info.live_epoch_length,
+                deviation_range: info.deviation_range,
+                consensus_num: info.consensus_num,
+                oracle_pool_nft_id: token_id_to_base64(info.oracle_pool_nft_id),
+                oracle_pool_participant_token_id: token_id_to_base64(
+                    info.oracle_pool_participant_token_id,
+                ),
+            })
+        })
+        .await
+    }
+
+    async fn get_pool_status(&self, _: Request<Empty>) -> Result<Response<PoolStatus>, Status> {
+        blocking(|| {
+            let status = get_pool_status()?;
+            Ok(PoolStatus {
+                current_pool_stage: status.current_pool_stage.to_string(),
+                latest_datapoint: status.latest_datapoint,
+                current_epoch_id: status.current_epoch_id,
+                pool_box_height: status.pool_box_height,
+                blocks_since_pool_update: status.blocks_since_pool_update,
+                epoch_datapoints: status.epoch_datapoints as u64,
+                pool_reward_tokens: status.pool_reward_tokens,
+            })
+        })
+        .await
+    }
+
+    async fn get_oracle_status(&self, _: Request<Empty>) -> Result<Response<OracleStatus>, Status> {
+        blocking(|| {
+            let status = get_oracle_status()?;
+            Ok(OracleStatus {
+                state: status.state.to_string(),
+                latest_datapoint: status.latest_datapoint,
+                latest_datapoint_epoch: status.latest_datapoint_epoch,
+                latest_datapoint_creation_height: status.latest_datapoint_creation_height,
+                reward_tokens: status.reward_tokens,
+                posting_paused: ADMIN_REQUESTS.posting_paused(),
+            })
+        })
+        .await
+    }
+
+    async fn get_block_height(&self, _: Request<Empty>) -> Result<Response<BlockHeight>, Status> {
+        blocking(|| {
+            Ok(BlockHeight {
+                height: current_block_height()?,
+            })
+        })
+        .await
+    }
+
+    async fn trigger_extraction(&self, _: Request<Empty>) -> Result<Response<Empty>, Status> {
+        log::info!("gRPC: reward extraction requested");
+        ADMIN_REQUESTS.request_extraction();
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn pause_posting(&self, _: Request<Empty>) -> Result<Response<Empty>, Status> {
+        log::info!("gRPC: datapoint posting paused");
+        ADMIN_REQUESTS.set_posting_paused(true);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn resume_posting(&self, _: Request<Empty>) -> Result<Response<Empty>, Status> {
+        log::info!("gRPC: datapoint posting resumed");
+        ADMIN_REQUESTS.set_posting_paused(false);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn reload_config(&self, _: Request<Empty>) -> Result<Response<Empty>, Status> {
+        log::info!("gRPC: config reload requested");
+        ADMIN_REQUESTS.request_reload();
+        Ok(Response::new(Empty {}))
+    }
+}
+
+/// Rejects the calls without the `authorization: Bearer <token>` metadata entry
+#[derive(Clone)]
+struct TokenAuth {
+    token: String,
+}
+
+impl Interceptor for TokenAuth {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let authorized = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map_or(false, |token| token_matches(token, &self.token));
+        if authorized {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("invalid or missing token"))
+        }
+    }
+}
+
+pub async fn start_grpc_server(config: GrpcConfig) -> Result<(), anyhow::Error> {
+    let token = read_token_file(&config.auth_token_file)?;
+    let mut builder = Server::builder();
+    match (&config.tls_cert_file, &config.tls_key_file) {
+        (Some(cert_file), Some(key_file)) => {
+            let identity = Identity::from_pem(fs::read(cert_file)?, fs::read(key_file)?);
+            builder = builder.tls_config(ServerTlsConfig::new().identity(identity))?;
+        }
+        (None, None) => log::warn!(
+            "gRPC service without TLS, the auth token is sent in plaintext. Only bind it to a \
+             trusted network."
+        ),
+        (Some(_), None) | (None, Some(_)) => {
+            return Err(anyhow::anyhow!(
+                "gRPC TLS needs both tls_cert_file and tls_key_file"
+            ))
+        }
+    }
+    log::info!("gRPC service listening on {}", config.bind_address);
+    builder
+        .add_service(OracleCoreServer::with_interceptor(
+            OracleCoreService,
+            TokenAuth { token },
+        ))
+        .serve(config.bind_address)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,247 @@
+//! Health of the daemon served on `/health/live` and `/health/ready` of the REST API for container
+//! probes and load balancers. Liveness only tells whether the main loop still runs, readiness
+//! checks the node, the wallet, the scans, the data source and the participation of the oracle
+//! (including the epochs it missed in a row).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::box_kind::{OracleBox, OracleBoxWrapper, PoolBox, PoolBoxWrapper};
+use crate::node_interface::{current_block_height, get_wallet_status};
+use crate::oracle_state::{LocalDatapointBoxSource, OraclePool, PoolBoxSource};
+
+/// The main loop sleeps 30 seconds between iterations, an iteration waiting on a slow node can
+/// take a few minutes
+const LIVENESS_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub static HEALTH: Lazy<Health> = Lazy::new(Health::new);
+
+pub struct Health {
+    started: Instant,
+    last_iteration: Mutex<Option<Instant>>,
+    /// Error of the last datapoint request, `None` if it succeeded
+    data_source: Mutex<Option<Option<String>>>,
+    /// Error of the last datapoint tx submission, `None` if it succeeded
+    posting_error: Mutex<Option<String>>,
+    /// Consecutive missed epochs, the alert threshold and the probable cause of the last miss
+    missed_epochs: Mutex<(u32, u32, Option<String>)>,
+    watch_only: AtomicBool,
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Health {
+            started: Instant::now(),
+            last_iteration: Mutex::new(None),
+            data_source: Mutex::new(None),
+            posting_error: Mutex::new(None),
+            missed_epochs: Mutex::new((0, u32::MAX, None)),
+            watch_only: AtomicBool::new(false),
+        }
+    }
+
+    /// Without a wallet there is no wallet to check and no datapoint to post
+    pub fn set_watch_only(&self, watch_only: bool) {
+        self.watch_only.store(watch_only, Ordering::SeqCst);
+    }
+
+    /// Called at the end of every iteration of the main loop, failed or not
+    pub fn record_iteration(&self) {
+        *self.last_iteration.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub fn record_data_source_result(&self, error: Option<String>) {
+        *self.data_source.lock().unwrap() = Some(error);
+    }
+
+    /// Error of the last datapoint request, `None` if it succeeded or wasn't made yet
+    pub fn data_source_error(&self) -> Option<String> {
+        self.data_source.lock().unwrap().clone().flatten()
+    }
+
+    pub fn record_posting_result(&self, error: Option<String>) {
+        *self.posting_error.lock().unwrap() = error;
+    }
+
+    pub fn posting_error(&self) -> Option<String> {
+        self.posting_error.lock().unwrap().clone()
+    }
+
+    /// Readiness fails once `consecutive` reaches `threshold`
+    pub fn record_missed_epochs(&self, consecutive: u32, threshold: u32, cause: Option<String>) {
+        *self.missed_epochs.lock().unwrap() = (consecutive, threshold, cause);
+    }
+
+    /// Whether the main loop completed an iteration (or started) in the last `LIVENESS_TIMEOUT`
+    pub fn live(&self) -> bool {
+        self.live_at(Instant::now())
+    }
+
+    fn live_at(&self, now: Instant) -> bool {
+        let last = self.last_iteration.lock().unwrap().unwrap_or(self.started);
+        now.saturating_duration_since(last) < LIVENESS_TIMEOUT
+    }
+
+    /// Queries the node, the data source is checked with the last datapoint request of the daemon
+    pub fn readiness(&self) -> Readiness {
+        let watch_only = self.watch_only.load(Ordering::SeqCst);
+        let mut checks = vec![];
+        checks.push(HealthCheck::new(
+            "node",
+            current_block_height().map(|height| format!("height {}", height)),
+        ));
+        if !watch_only {
+            checks.push(HealthCheck::new(
+                "wallet",
+                get_wallet_status()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|status| {
+                        if status.unlocked {
+                            Ok("unlocked".to_string())
+                        } else {
+                            Err(anyhow::anyhow!("wallet is locked"))
+                        }
+                    }),
+            ));
+        }
+        let pool = OraclePool::new()
+            .map_err(anyhow::Error::from)
+            .and_then(|op| {
+                let pool_box = op.get_pool_box_source().get_pool_box()?;
+                Ok((op, pool_box))
+            });
+        match pool {
+            Ok((op, pool_box)) => {
+                checks.push(HealthCheck::ok(
+                    "scans",
+                    format!("pool box at epoch {}", pool_box.epoch_counter()),
+                ));
+                if !watch_only {
+                    let (consecutive, threshold, cause) =
+                        self.missed_epochs.lock().unwrap().clone();
+                    checks.push(if consecutive < threshold {
+                        HealthCheck::ok(
+                            "missed_epochs",
+                            format!("{} epochs missed in a row", consecutive),
+                        )
+                    } else {
+                        HealthCheck::failed(
+                            "missed_epochs",
+                            format!(
+                                "{} epochs missed in a row, probable cause: {}",
+                                consecutive,
+                                cause.unwrap_or_else(|| "unknown".to_string())
+                            ),
+                        )
+                    });
+                    checks.push(HealthCheck::new(
+                        "posted_last_epoch",
+                        op.get_local_datapoint_box_source()
+                            .get_local_oracle_datapoint_box()
+                            .map_err(anyhow::Error::from)
+                            .and_then(|local_box| {
+                                if posted_in_last_epoch(&pool_box, local_box.as_ref()) {
+                                    Ok("datapoint collected or posted".to_string())
+                                } else {
+                                    Err(anyhow::anyhow!(
+                                        "no datapoint in the last epoch of the pool"
+                                    ))
+                                }
+                            }),
+                    ));
+                }
+            }
+            Err(e) => checks.push(HealthCheck::failed("scans", e.to_string())),
+        }
+        if !watch_only {
+            checks.push(match self.data_source.lock().unwrap().clone() {
+                None => HealthCheck::ok("data_source", "not queried yet".to_string()),
+                Some(None) => HealthCheck::ok("data_source", "last request succeeded".to_string()),
+                Some(Some(e)) => HealthCheck::failed("data_source", e),
+            });
+        }
+        Readiness {
+            ready: checks.iter().all(|check| check.ok),
+            checks,
+        }
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<HealthCheck>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthCheck {
+    #[schema(value_type = String)]
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl HealthCheck {
+    fn new<E: std::fmt::Display>(name: &'static str, result: Result<impl ToString, E>) -> Self {
+        match result {
+            Ok(detail) => HealthCheck::ok(name, detail.to_string()),
+            Err(e) => HealthCheck::failed(name, e.to_string()),
+        }
+    }
+
+    fn failed(name: &'static str, detail: String) -> Self {
+        HealthCheck {
+            name,
+            ok: false,
+            detail,
+        }
+    }
+
+    fn ok(name: &'static str, detail: String) -> Self {
+        HealthCheck {
+            name,
+            ok: true,
+            detail,
+        }
+    }
+}
+
+/// The datapoint of the oracle was collected by the last refresh of the pool box, or it posted in
+/// the current epoch
+fn posted_in_last_epoch(pool_box: &PoolBoxWrapper, local_box: Option<&OracleBoxWrapper>) -> bool {
+    match local_box {
+        Some(OracleBoxWrapper::Posted(posted_box)) => {
+            posted_box.epoch_counter() == pool_box.epoch_counter()
+        }
+        Some(collected_box @ OracleBoxWrapper::Collected(_)) => {
+            collected_box.get_box().creation_height >= pool_box.get_box().creation_height
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_liveness() {
+        let health = Health::new();
+        let now = Instant::now();
+        assert!(health.live_at(now));
+        assert!(!health.live_at(now + LIVENESS_TIMEOUT * 2));
+        health.record_iteration();
+        assert!(health.live_at(Instant::now() + LIVENESS_TIMEOUT / 2));
+    }
+}
@@ -0,0 +1,138 @@
+//! Latency of the datapoint posting pipeline, to tune the publish timing, the data source retries
+//! and the node timeouts with real data. The epoch window opens when the main loop first observes
+//! the pool box of a new epoch, the seconds from there to the datapoint being fetched, the tx built,
+//! submitted and confirmed are recorded once per epoch into the `oracle_posting_latency_seconds`
+//! histograms of the metrics.
+
+use std::sync::Mutex;
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+
+use crate::metrics::METRICS;
+use crate::node_interface::TxId;
+
+pub static POSTING_LATENCY: Lazy<PostingLatency> = Lazy::new(PostingLatency::new);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LatencyStage {
+    /// The datapoint was fetched from the data source
+    Fetched,
+    /// The datapoint tx was built
+    Built,
+    /// The datapoint tx was accepted by the node
+    Submitted,
+    /// The datapoint tx left the mempool with its inputs spent
+    Confirmed,
+}
+
+impl LatencyStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LatencyStage::Fetched => "fetched",
+            LatencyStage::Built => "built",
+            LatencyStage::Submitted => "submitted",
+            LatencyStage::Confirmed => "confirmed",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct EpochWindow {
+    epoch: u32,
+    /// `None` for the epoch the daemon started in, its opening wasn't observed
+    opened: Option<Instant>,
+    recorded: Vec<LatencyStage>,
+    submitted_tx_id: Option<TxId>,
+}
+
+#[derive(Debug, Default)]
+pub struct PostingLatency {
+    window: Mutex<Option<EpochWindow>>,
+}
+
+impl PostingLatency {
+    pub fn new() -> Self {
+        PostingLatency::default()
+    }
+
+    /// The main loop observed the pool box of `epoch`
+    pub fn observe_epoch(&self, epoch: u32) {
+        let mut window = self.window.lock().unwrap();
+        let opened = match window.as_ref() {
+            Some(w) if w.epoch >= epoch => return,
+            Some(_) => Some(Instant::now()),
+            None => None,
+        };
+        *window = Some(EpochWindow {
+            epoch,
+            opened,
+            ..EpochWindow::default()
+        });
+    }
+
+    /// Seconds since the epoch window opened when `stage` is reached the first time in the epoch
+    pub fn record(&self, stage: LatencyStage) -> Option<f64> {
+        let mut window = self.window.lock().unwrap();
+        let window = window.as_mut()?;
+        let opened = window.opened?;
+        if window.recorded.contains(&stage) {
+            return None;
+        }
+        window.recorded.push(stage);
+        let seconds = opened.elapsed().as_secs_f64();
+        METRICS.record_posting_latency(stage, seconds);
+        Some(seconds)
+    }
+
+    /// The datapoint tx `tx_id` was submitted
+    pub fn record_submitted(&self, tx_id: TxId) -> Option<f64> {
+        if let Some(window) = self.window.lock().unwrap().as_mut() {
+            window.submitted_tx_id.get_or_insert(tx_id);
+        }
+        self.record(LatencyStage::Submitted)
+    }
+
+    /// A tx of the daemon was confirmed, only the datapoint tx of the current epoch counts
+    pub fn record_confirmed(&self, tx_id: &TxId) -> Option<f64> {
+        let submitted = self
+            .window
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(false, |w| w.submitted_tx_id.as_ref() == Some(tx_id));
+        if submitted {
+            self.record(LatencyStage::Confirmed)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_posting_latency() {
+        let latency = PostingLatency::new();
+        assert_eq!(latency.record(LatencyStage::Fetched), None);
+        // The opening of the first epoch wasn't observed
+        latency.observe_epoch(5);
+        assert_eq!(latency.record(LatencyStage::Fetched), None);
+        latency.observe_epoch(6);
+        assert!(latency.record(LatencyStage::Fetched).is_some());
+        // Once per epoch
+        assert_eq!(latency.record(LatencyStage::Fetched), None);
+        latency.observe_epoch(6);
+        assert_eq!(latency.record(LatencyStage::Fetched), None);
+        assert_eq!(latency.record_confirmed(&"tx0".to_string()), None);
+        assert!(latency.record_submitted("tx0".to_string()).is_some());
+        assert_eq!(latency.record_confirmed(&"tx1".to_string()), None);
+        assert!(latency.record_confirmed(&"tx0".to_string()).is_some());
+
+        latency.observe_epoch(7);
+        assert!(latency.record(LatencyStage::Fetched).is_some());
+        assert_eq!(latency.record_confirmed(&"tx0".to_string()), None);
+    }
+}
@@ -1,83 +1,106 @@
+//! Logging through `tracing`, to stdout and to `oracle-core.log` (rotated daily, the last 3 files
+//! are kept). The `log` records of the code and of the dependencies are forwarded to `tracing`, so
+//! they show up in the spans of the actions (fetching the datapoint, building and submitting a
+//! tx).
+//!
+//! The level of oracle-core is `log_level` of the config (`info` if not set), the dependencies log
+//! at `info`. `log_filter` adds per-module directives (e.g.
+//! `oracle_core::scans=debug,reqwest=warn`), the `RUST_LOG` environment variable replaces all of
+//! them. With `log_format: json` every line is a JSON object.
+
 use log::LevelFilter;
-use log4rs::append::console::ConsoleAppender;
-use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRoller;
-use log4rs::append::rolling_file::policy::compound::trigger::size::SizeTrigger;
-use log4rs::append::rolling_file::policy::compound::CompoundPolicy;
-use log4rs::append::rolling_file::RollingFileAppender;
-use log4rs::config::Appender;
-use log4rs::config::Logger;
-use log4rs::config::Root;
-use log4rs::Config;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 
 use crate::oracle_config::MAYBE_ORACLE_CONFIG;
 
-fn load_log_level() -> Option<LevelFilter> {
-    MAYBE_ORACLE_CONFIG.clone().ok()?.log_level
-    // let config_file = std::fs::read_to_string(oracle_config::DEFAULT_CONFIG_FILE_NAME).ok()?;
-    // YamlLoader::load_from_str(&config_file).ok()?.first()?["log_level"]
-    //     .as_str()
-    //     .map(|s| s.to_string())
+/// Rotated log files kept
+const MAX_LOG_FILES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
 }
 
-fn get_level_filter() -> LevelFilter {
-    load_log_level().unwrap_or(LevelFilter::Info)
-    // let log_level = load_log_level().unwrap_or_else(|| "info".to_string());
-    // match log_level.to_lowercase().as_str() {
-    //     "trace" => LevelFilter::Trace,
-    //     "debug" => LevelFilter::Debug,
-    //     "info" => LevelFilter::Info,
-    //     "warn" => LevelFilter::Warn,
-    //     "error" => LevelFilter::Error,
-    //     "off" => LevelFilter::Off,
-    //     _ => LevelFilter::Info,
-    // }
+/// The filter directives, `RUST_LOG` if set
+fn filter_directives(log_level: LevelFilter, log_filter: Option<&str>) -> String {
+    if let Ok(rust_log) = std::env::var(EnvFilter::DEFAULT_ENV) {
+        return rust_log;
+    }
+    let mut directives = format!("info,oracle_core={}", log_level.to_string().to_lowercase());
+    if let Some(log_filter) = log_filter.filter(|f| !f.trim().is_empty()) {
+        directives.push(',');
+        directives.push_str(log_filter.trim());
+    }
+    directives
 }
 
 pub fn setup_log(override_log_level: Option<LevelFilter>) {
-    let stdout = ConsoleAppender::builder().build();
+    let config = MAYBE_ORACLE_CONFIG.clone().ok();
+    let log_level = override_log_level
+        .or_else(|| config.as_ref().and_then(|c| c.log_level))
+        .unwrap_or(LevelFilter::Info);
+    let log_filter = config.as_ref().and_then(|c| c.log_filter.clone());
+    let log_format = config
+        .as_ref()
+        .and_then(|c| c.log_format)
+        .unwrap_or(LogFormat::Text);
 
-    // via https://stackoverflow.com/questions/56345288/how-do-i-use-log4rs-rollingfileappender-to-incorporate-rolling-logging#
-    let window_size = 3; // log0, log1, log2
-    let fixed_window_roller = FixedWindowRoller::builder()
-        .build("oracle-core.log{}", window_size)
+    let directives = filter_directives(log_level, log_filter.as_deref());
+    let filter = EnvFilter::try_new(&directives).unwrap_or_else(|e| {
+        eprintln!("Invalid log filter {:?} ({}), using info", directives, e);
+        EnvFilter::new("info")
+    });
+    let log_file = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("oracle-core.log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(".")
         .unwrap();
+    let layers = match log_format {
+        LogFormat::Text => fmt::layer()
+            .and_then(fmt::layer().with_writer(log_file).with_ansi(false))
+            .boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .and_then(fmt::layer().json().with_writer(log_file))
+            .boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layers)
+        .init();
 
-    let size_limit = 5 * 1024 * 1024; // 5MB as max log file size to roll
-    let size_trigger = SizeTrigger::new(size_limit);
-
-    let compound_policy =
-        CompoundPolicy::new(Box::new(size_trigger), Box::new(fixed_window_roller));
+    log_panics::init();
+}
 
-    let log_level = override_log_level.unwrap_or_else(get_level_filter);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let config = Config::builder()
-        .appender(Appender::builder().build("stdout", Box::new(stdout)))
-        .appender(
-            Appender::builder().build(
-                "logfile",
-                Box::new(
-                    RollingFileAppender::builder()
-                        .build("oracle-core.log", Box::new(compound_policy))
-                        .unwrap(),
-                ),
+    #[test]
+    fn test_filter_directives() {
+        if std::env::var(EnvFilter::DEFAULT_ENV).is_ok() {
+            return;
+        }
+        assert_eq!(
+            filter_directives(LevelFilter::Debug, None),
+            "info,oracle_core=debug"
+        );
+        assert_eq!(
+            filter_directives(
+                LevelFilter::Info,
+                Some(" oracle_core::scans=trace,reqwest=warn ")
             ),
-        )
-        .logger(
-            Logger::builder()
-                .appender("logfile")
-                .appender("stdout")
-                .additive(false)
-                .build("oracle_core", log_level),
-        )
-        .build(
-            Root::builder()
-                .appender("stdout")
-                .appender("logfile")
-                .build(LevelFilter::Info),
-        )
-        .unwrap();
-
-    let _ = log4rs::init_config(config).unwrap();
-
-    log_panics::init();
+            "info,oracle_core=info,oracle_core::scans=trace,reqwest=warn"
+        );
+        assert!(EnvFilter::try_new(filter_directives(LevelFilter::Off, None)).is_ok());
+    }
 }
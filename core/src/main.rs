@@ -16,62 +16,179 @@
 #[macro_use]
 extern crate lazy_static;
 
+mod action_queue;
 mod actions;
 mod address_util;
+mod admin;
 mod api;
+mod audit_log;
 mod box_kind;
 mod cli_commands;
+mod config_format;
+mod config_reload;
+mod config_schema;
 mod contracts;
 mod datapoint_source;
 mod default_parameters;
+mod downtime;
+mod epoch_stats;
+mod events;
+mod fee_estimation;
+mod fees;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod health;
+mod latency;
 mod logging;
+mod metrics;
+mod min_box_value;
+mod missed_epochs;
 mod node_interface;
+mod notifications;
 mod oracle_config;
 mod oracle_state;
+mod peer_monitor;
+mod pending_outputs;
+mod pool_archive;
 mod pool_commands;
+mod pool_config;
+mod pool_controller;
+mod pool_watch;
+mod prompt;
+mod publish_skip;
+mod rate_anomaly;
+mod recovery;
+mod reorg;
+mod reward_extraction;
 mod scans;
+mod secrets;
 mod serde;
+mod shutdown;
 mod state;
+mod storage_rent;
+mod store;
 mod templates;
 #[cfg(test)]
 mod tests;
+mod tx_history;
+mod tx_preview;
+mod tx_validation;
 mod wallet;
+mod wallet_balance;
 
+use action_queue::ActionKind;
 use actions::execute_action;
 use actions::PoolAction;
 use anyhow::anyhow;
 use anyhow::Context;
+use box_kind::OracleBox;
+use box_kind::OracleBoxWrapper;
+use box_kind::PoolBox;
+use box_kind::PoolBoxWrapper;
+use box_kind::PostedOracleBox;
 use clap::{Parser, Subcommand};
+use cli_commands::generate_config::ConfigPreset;
+use cli_commands::renew_storage_rent::build_update_box_renewal_tx;
+use cli_commands::renew_storage_rent::RenewStorageRentError;
+use cli_commands::update_pool::UpdatePoolError;
+use config_reload::ConfigWatcher;
 use crossbeam::channel::bounded;
+use downtime::unix_now;
+use downtime::DowntimeCause;
+use downtime::DowntimeTracker;
+use downtime::DOWNTIME;
+use downtime::DOWNTIME_FILE;
+use epoch_stats::CollectedDatapoint;
+use epoch_stats::EpochTracker;
+use epoch_stats::PostedDatapoint;
+use epoch_stats::EPOCH_STATS;
 use ergo_lib::ergotree_ir::chain::address::Address;
 use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
 use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
 use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
 use ergo_lib::ergotree_ir::chain::token::Token;
 use ergo_lib::ergotree_ir::chain::token::TokenId;
+use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
+use events::PoolEvent;
+use events::EVENTS;
+use fees::FeeStatus;
+use latency::LatencyStage;
+use latency::POSTING_LATENCY;
 use log::debug;
 use log::error;
 use log::LevelFilter;
+use metrics::PoolGauges;
+use metrics::METRICS;
+use missed_epochs::MissedEpochCause;
+use missed_epochs::MissedEpochTracker;
+use missed_epochs::DEFAULT_ALERT_THRESHOLD;
 use node_interface::assert_wallet_unlocked;
 use node_interface::current_block_height;
+use node_interface::get_change_address;
+use node_interface::get_wallet_addresses;
 use node_interface::get_wallet_status;
 use node_interface::new_node_interface;
+use node_interface::sign_and_submit_transaction;
+use node_interface::unlock_wallet;
+use notifications::NotificationKind;
+use notifications::NotificationTriggers;
+use notifications::NOTIFICATIONS;
+use oracle_config::get_core_api_address;
+use oracle_config::OracleConfig;
 use oracle_config::ORACLE_CONFIG;
+use oracle_state::find_conflicting_tx;
 use oracle_state::register_and_save_scans;
+use oracle_state::MempoolSource;
 use oracle_state::OraclePool;
+use oracle_state::StageError;
+use peer_monitor::load_peer_report;
+use peer_monitor::save_peer_report;
+use peer_monitor::PeerBox;
+use peer_monitor::PeerMonitor;
+use peer_monitor::PEER_REPORT;
+use peer_monitor::PEER_REPORT_FILE;
+use pending_outputs::PENDING_OUTPUTS;
+use pool_archive::PoolArchive;
+use pool_archive::POOL_ARCHIVE_FILE;
 use pool_commands::build_action;
 use pool_commands::publish_datapoint::PublishDatapointActionError::DataPointSource;
 use pool_commands::refresh::RefreshActionError;
+use pool_commands::PoolCommand;
 use pool_commands::PoolCommandError;
-use state::process;
+use pool_controller::CollectorElection;
+use pool_controller::ControllerState;
+use pool_controller::EpochStrategyKind;
+use pool_controller::PoolController;
+use pool_controller::PublishTiming;
+use pool_watch::PoolWatcher;
+use publish_skip::PublishSkipper;
+use rate_anomaly::RateAnomalyDetector;
+use recovery::recover_pending_operations;
+use reorg::NodeChain;
+use reorg::ReorgCheck;
+use reorg::ReorgTracker;
+use reward_extraction::RewardExtractor;
 use state::PoolState;
+use std::convert::TryFrom;
 use std::convert::TryInto;
-use std::thread;
+use std::path::Path;
 use std::time::Duration;
+use storage_rent::ProtocolBox;
+use storage_rent::RentStatus;
+use storage_rent::StorageRentMonitor;
+use store::TxStatus;
+use tx_history::poll_tx_statuses;
+use tx_history::TxHistoryError;
 use wallet::WalletData;
+use wallet::WalletDataSource;
+use wallet_balance::BalanceMonitor;
+use wallet_balance::DEFAULT_MIN_EPOCHS;
 
+use crate::admin::ADMIN_REQUESTS;
 use crate::api::start_rest_server;
+use crate::cli_commands::ballot_status::get_ballot_status;
 use crate::default_parameters::print_contract_hashes;
+use crate::health::HEALTH;
 use crate::oracle_config::MAYBE_ORACLE_CONFIG;
 
 /// A Base58 encoded String of a Ergo P2PK address. Using this type def until sigma-rust matures further with the actual Address type.
@@ -104,9 +221,20 @@ struct Args {
     /// Increase the verbosity of the output to trace log level overriding the log level in the config file.
     #[clap(short, long)]
     verbose: bool,
-    /// Set path of configuration file to use. Default is ./oracle_config.yaml
+    /// Set path of configuration file to use (YAML, or TOML if it ends with `.toml`). Default is
+    /// ./oracle_config.yaml
     #[clap(short, long)]
     config_file: Option<String>,
+    /// Answer 'YES' to all confirmation prompts. Can also be set with the ORACLE_CORE_ASSUME_YES
+    /// env var.
+    #[clap(short = 'y', long, global = true)]
+    yes: bool,
+    /// Print the command results (tx id, box ids, errors) as JSON.
+    #[clap(long, global = true)]
+    json: bool,
+    /// Run with contracts whose templates differ from the EIP-23 ones.
+    #[clap(long, global = true)]
+    allow_custom_contracts: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -120,6 +248,22 @@ enum Command {
         /// Set this flag to output a bootstrap config template file to the given filename. If
         /// filename already exists, return error.
         generate_config_template: bool,
+        /// Compile the contracts from the ErgoScript sources (`<name>.es`) in this directory
+        /// instead of using the precompiled ones of the config. Requires the `compile-contracts`
+        /// feature.
+        #[clap(long)]
+        contract_sources: Option<String>,
+    },
+
+    /// Interactively create the oracle config file (to join an existing pool) or the bootstrap
+    /// config file (to bootstrap a new pool) and register the node scans.
+    Init,
+
+    /// Migrate an existing (v1-style) pool to a new pool running the current contracts: bootstrap
+    /// the new pool, then send its oracle and reward tokens to the operators of the old pool.
+    MigratePool {
+        /// The name of the migration file (.yaml)
+        migration_file: String,
     },
 
     /// Run the oracle-pool
@@ -130,33 +274,182 @@ enum Command {
         #[clap(long)]
         /// Set this flag to enable the REST API. NOTE: SSL is not used!
         enable_rest_api: bool,
+        /// Follow the pool and alert on outlier datapoints and late refreshes without a wallet,
+        /// no tx is ever built (also with the `watch_only` epoch strategy)
+        #[clap(long)]
+        watch_only: bool,
     },
 
     /// Send reward tokens accumulated in the oracle box to a chosen address
     ExtractRewardTokens {
         /// Base58 encoded address to send reward tokens to
         rewards_address: String,
+        /// Number of reward tokens to extract. Extracts all but one (required by the contract)
+        /// if omitted.
+        #[clap(long)]
+        amount: Option<u64>,
     },
 
     /// Print the number of reward tokens earned by the oracle (in the last posted/collected oracle box)
     PrintRewardTokens,
 
+    /// Print the state of the local oracle box (posted/collected, datapoint, epoch counter, reward
+    /// tokens, box value) and the number of blocks until the next epoch
+    PrintStatus,
+
+    /// Print the peer oracles flagged by the `peer_monitor` of the running daemon (from
+    /// `peer_report.json`)
+    PeerReport,
+
+    /// Print the datapoints posted by the oracle and the pool rates their epochs were finalized
+    /// with (from the pool archive, see `pool-history`)
+    DatapointHistory,
+
+    /// Export the epochs the oracle participated in, the reward tokens earned and the fees paid
+    /// (CSV by default), for accounting
+    EarningsReport {
+        /// Value the earnings in the fiat unit of the pool rate (e.g. USD for NanoErgUsd) at the
+        /// rate each epoch was finalized with, from the pool archive (see `pool-history`)
+        #[clap(long)]
+        fiat: bool,
+        /// Value of a reward token in nanoErgs, to value the reward tokens with `--fiat`
+        #[clap(long)]
+        reward_token_price: Option<u64>,
+        /// Write the report to this file instead of stdout
+        #[clap(long)]
+        output: Option<String>,
+    },
+
+    /// Export the pool rates and the datapoints posted by the oracle over a height range as a CSV
+    /// (by default) or JSON time series, from the pool archive (see `pool-history`)
+    ExportHistory {
+        /// First height of the range
+        #[clap(long)]
+        from_height: Option<u32>,
+        /// Last height of the range
+        #[clap(long)]
+        to_height: Option<u32>,
+        /// Fetch the pool boxes missing in the archive from the explorer first
+        #[clap(long)]
+        backfill: bool,
+        /// Explorer API to backfill from, the public explorer of the network by default
+        #[clap(long)]
+        explorer_url: Option<String>,
+        /// Write the series to this file instead of stdout
+        #[clap(long)]
+        output: Option<String>,
+    },
+
+    /// Print the txs signed by the node wallet, newest first, with their status (submitted,
+    /// confirmed, evicted or failed)
+    TxHistory {
+        /// Only the txs with this status
+        #[clap(long)]
+        status: Option<TxStatus>,
+        /// Print the last `limit` txs (50 by default)
+        #[clap(long)]
+        limit: Option<u32>,
+    },
+
+    /// Publish the given datapoint, bypassing the configured datapoint source. Use for emergency
+    /// corrections when the datapoint source is unavailable.
+    PublishDatapoint {
+        /// The datapoint to publish
+        #[clap(long)]
+        value: i64,
+    },
+
+    /// Rescan the node wallet from the given height and wait until the scans find the pool,
+    /// refresh and local oracle boxes again. Use after restoring the node from a backup.
+    Rescan {
+        /// Height to start the rescan from
+        #[clap(long)]
+        from_height: u32,
+    },
+
+    /// Consolidate the boxes holding the local oracle token into a single oracle box, if more than
+    /// one exists (e.g. after a manual tx). The daemon doesn't act until they are consolidated.
+    Repair,
+
     /// Transfer an oracle token to a chosen address.
     TransferOracleToken {
         /// Base58 encoded address to send oracle token to
         oracle_token_address: String,
     },
 
+    /// Leave the pool: extract all reward tokens and hand the oracle token over to the pool admin
+    /// in a single transaction.
+    ExitPool {
+        /// Base58 encoded (P2PK) address of the pool admin to send the oracle token to
+        pool_admin_address: String,
+        /// Base58 encoded address to send the reward tokens to
+        rewards_address: String,
+    },
+
+    /// Add nanoERGs from the wallet to the pool box with the refresh tx of the current epoch,
+    /// built and submitted instead of the collector.
+    TopUpPoolBox {
+        /// nanoERGs to add to the pool box
+        nanoergs: u64,
+    },
+
+    /// Print the value of the refresh box against its storage rent and whether it is still the
+    /// refresh box of the pool contract. With `--top-up`, add nanoERGs from the wallet to it with
+    /// the refresh tx of the current epoch.
+    MaintainRefreshBox {
+        /// nanoERGs to add to the refresh box
+        #[clap(long)]
+        top_up: Option<u64>,
+    },
+
     /// Vote to update the oracle pool
     VoteUpdatePool {
         /// The Blake2 hash of the address for the new pool box.
-        new_pool_box_address_hash_str: String,
+        #[clap(required_unless_present = "proposal-file")]
+        new_pool_box_address_hash_str: Option<String>,
         /// The base-16 representation of the TokenId of the new reward tokens to be used.
-        reward_token_id_str: String,
+        #[clap(required_unless_present = "proposal-file")]
+        reward_token_id_str: Option<String>,
         /// The reward token amount.
-        reward_token_amount: u32,
+        #[clap(required_unless_present = "proposal-file")]
+        reward_token_amount: Option<u32>,
         /// The creation height of the update box.
-        update_box_creation_height: u32,
+        #[clap(required_unless_present = "proposal-file")]
+        update_box_creation_height: Option<u32>,
+        /// Vote for the update described in this proposal file (created with `propose-update`)
+        /// instead of the arguments above.
+        #[clap(long)]
+        proposal_file: Option<String>,
+    },
+    /// Rebuild the proposed pool contract locally and check that its hash matches the proposal
+    /// file (or the vote of a ballot box). Prints the changed parameters.
+    VerifyProposal {
+        /// Proposal file created with `propose-update`. oracle_config_updated.yaml is used if
+        /// omitted.
+        #[clap(long)]
+        proposal_file: Option<String>,
+        /// Verify the vote of this ballot box instead of the hash in the proposal file
+        #[clap(long)]
+        ballot_box_id: Option<String>,
+    },
+    /// Print the votes cast in the ballot boxes, whether the quorum is reached and which operators
+    /// haven't voted yet.
+    BallotStatus,
+    /// Compare the constants of the live pool, refresh and update contracts with the ones of the
+    /// config.
+    DiffPool,
+    /// Write the update prepared with `prepare-update` to a proposal file to be shared with the
+    /// other operators, who can vote for it with `vote-update-pool --proposal-file`.
+    ProposeUpdate {
+        /// Name of the proposal file to create
+        #[clap(default_value = "update_proposal.json")]
+        proposal_file: String,
+        /// New reward token id (base64). The current reward token is kept if omitted.
+        #[clap(long)]
+        reward_token_id: Option<String>,
+        /// New reward token amount. The current amount is kept if omitted.
+        #[clap(long)]
+        reward_token_amount: Option<u64>,
     },
     /// Initiate the Update Pool transaction.
     /// Run with no arguments to show diff between oracle_config.yaml and oracle_config_updated.yaml
@@ -169,6 +462,21 @@ enum Command {
         /// New reward token amount, required if new token id was voted for
         reward_token_amount: Option<u64>,
     },
+    /// Print when the storage rent of the pool, refresh and update boxes can be collected and
+    /// re-create the update box (with an update of the pool to its current contract) if it is
+    /// within the renewal window. Writes a proposal file for the ballot holders if the votes are
+    /// missing.
+    RenewStorageRent {
+        /// Renew the update box even if it isn't within the renewal window
+        #[clap(long)]
+        force: bool,
+        /// Reward token amount of the pool box voted for, the current amount if omitted
+        #[clap(long)]
+        reward_token_amount: Option<u64>,
+        /// Proposal file written when the votes are missing
+        #[clap(long, default_value = "storage_rent_proposal.json")]
+        proposal_file: String,
+    },
     /// Prepare updating oracle pool with new contracts/parameters.
     PrepareUpdate {
         /// Name of update parameters file (.yaml)
@@ -177,6 +485,160 @@ enum Command {
 
     /// Print base 64 encodings of the blake2b hash of ergo-tree bytes of each contract
     PrintContractHashes,
+
+    /// Print the P2S address and the template hash of each contract of the config for the
+    /// configured network
+    PrintContractAddresses,
+
+    /// Compare the contract templates of the config with the EIP-23 contracts
+    VerifyContracts,
+
+    /// Compare the token ids and the contract parameters of the config with the live pool boxes
+    /// and print the mismatches, fatal or cosmetic. Exits with an error on a fatal mismatch.
+    VerifyPool {
+        /// Explorer API to read the pool from, the public explorer of the network by default
+        #[clap(long)]
+        explorer_url: Option<String>,
+        /// Fail on cosmetic mismatches too
+        #[clap(long)]
+        strict: bool,
+    },
+
+    /// Print the archived history of the pool boxes (height, epoch counter, rate). With
+    /// `--backfill` the pool boxes missing in the archive are fetched from the explorer first.
+    PoolHistory {
+        /// Fetch the pool boxes from the explorer into the archive
+        #[clap(long)]
+        backfill: bool,
+        /// Explorer API to backfill from, the public explorer of the network by default
+        #[clap(long)]
+        explorer_url: Option<String>,
+        /// Print only the latest N pool boxes
+        #[clap(long)]
+        limit: Option<usize>,
+    },
+
+    /// Sweep the wallet's dust boxes (below the given value) into a single box, preserving
+    /// tokens.
+    ConsolidateUtxos {
+        /// Boxes with a value (in nanoErgs) below this threshold are swept.
+        #[clap(long, default_value = "100000000")]
+        dust_threshold: u64,
+    },
+
+    /// Simulate a pool of virtual oracles on an in-memory chain (no node or config needed) and
+    /// print the publications, refreshes and rewards of each epoch
+    Simulate {
+        /// Number of virtual oracles
+        #[clap(long, default_value = "5")]
+        oracles: u32,
+        /// Number of epochs to simulate
+        #[clap(long, default_value = "3")]
+        epochs: u32,
+    },
+
+    /// Save the observed pool state (pool, refresh and oracle boxes) to a file for a bug report, or
+    /// replay a saved one offline
+    Snapshot {
+        #[clap(subcommand)]
+        command: SnapshotCommand,
+    },
+
+    /// Bundle the config (without its secrets), the registered scans, the state database and the
+    /// state files into an archive, to move the oracle to another server
+    Backup {
+        /// Archive to create
+        #[clap(default_value = "oracle_core_backup.tar.gz")]
+        archive: String,
+    },
+
+    /// Restore a backup made with `backup` to the working directory and the config path
+    Restore {
+        /// Archive to restore
+        archive: String,
+        /// Overwrite the existing files
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Move the pool definition (contract parameters and token ids) of the config to a file to
+    /// share with the other operators, the config points to it and pins its hash
+    SplitConfig {
+        /// Pool definition file to create, relative to the config directory
+        #[clap(default_value = "pool_config.yaml")]
+        pool_config_file: String,
+    },
+
+    /// Check the config file against the config schema and for malformed token ids and constant
+    /// indices out of range, and print the issues with their location
+    ValidateConfig {
+        /// Print the JSON Schema of the config file instead
+        #[clap(long)]
+        print_schema: bool,
+    },
+
+    /// Print the hash of a pool definition file, as expected in `pool_config_hash`
+    PoolConfigHash {
+        /// Pool definition file
+        #[clap(default_value = "pool_config.yaml")]
+        pool_config_file: String,
+    },
+
+    /// Write the pool definition (contract parameters and token ids) of a live pool, read from its
+    /// boxes on the explorer, to join the pool without copying its parameters by hand
+    ImportPool {
+        /// Base16 id of the pool NFT
+        #[clap(long)]
+        pool_nft: String,
+        /// Use the public explorer of the testnet
+        #[clap(long)]
+        testnet: bool,
+        /// Explorer API to read the pool from, the public explorer of the network by default
+        #[clap(long)]
+        explorer_url: Option<String>,
+        /// Pool definition file to create, relative to the config directory
+        #[clap(long, default_value = "pool_config.yaml")]
+        output: String,
+    },
+
+    /// Write a commented config template for a network and pool preset to the config file, with
+    /// placeholders for the values to fill in
+    GenerateConfig {
+        /// erg-usd-mainnet or testnet-demo
+        #[clap(long, default_value = "erg-usd-mainnet")]
+        preset: ConfigPreset,
+    },
+
+    /// Ask for a secret of the config (e.g. `node_api_key`) and print the value replacing it in the
+    /// config, the secret encrypted with a passphrase or stored in the OS keyring
+    EncryptSecret {
+        /// Store the secret in the OS keyring under this name instead
+        #[clap(long)]
+        keyring: Option<String>,
+    },
+
+    /// Run a daemon for each pool of the pools file, sharing the node connection and the wallet
+    RunPools {
+        /// Pools file with the node connection and the config file of each pool
+        #[clap(default_value = "pools.yaml")]
+        pools_file: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SnapshotCommand {
+    /// Save the pool, refresh and oracle boxes and the pending txs at the current height
+    Export {
+        /// Snapshot file to create
+        #[clap(default_value = "pool_snapshot.json")]
+        snapshot_file: String,
+    },
+    /// Print the pool state of a snapshot and the command the daemon would act on (no node
+    /// needed)
+    Import {
+        /// Snapshot file to replay
+        snapshot_file: String,
+    },
 }
 
 fn main() {
@@ -188,6 +650,8 @@ fn main() {
                 .unwrap_or_else(|| oracle_config::DEFAULT_CONFIG_FILE_NAME.to_string()),
         )
         .unwrap();
+    cli_commands::ASSUME_YES.set(args.yes).unwrap();
+    cli_commands::JSON_OUTPUT.set(args.json).unwrap();
 
     let cmdline_log_level = if args.verbose {
         Some(LevelFilter::Debug)
@@ -203,65 +667,469 @@ fn main() {
         Command::Bootstrap {
             yaml_config_name,
             generate_config_template,
+            contract_sources,
         } => {
             if let Err(e) = (|| -> Result<(), anyhow::Error> {
                 if generate_config_template {
                     cli_commands::bootstrap::generate_bootstrap_config_template(yaml_config_name)?;
                 } else {
-                    cli_commands::bootstrap::bootstrap(yaml_config_name)?;
+                    cli_commands::bootstrap::bootstrap(yaml_config_name, contract_sources)?;
                 }
                 Ok(())
             })() {
-                {
-                    error!("Fatal advanced-bootstrap error: {:?}", e);
-                    std::process::exit(exitcode::SOFTWARE);
-                }
+                exit_on_error("advanced-bootstrap", e);
             };
         }
+        Command::Init => {
+            match cli_commands::init::init(oracle_config::CONFIG_FILE_PATH.get().unwrap()) {
+                Ok(cli_commands::init::InitOutcome::OracleConfigCreated) => {
+                    assert_wallet_unlocked(&new_node_interface());
+                    register_and_save_scans().unwrap();
+                }
+                Ok(cli_commands::init::InitOutcome::BootstrapConfigCreated(_)) => (),
+                Err(e) => exit_on_error("init", e),
+            }
+        }
+        Command::MigratePool { migration_file } => {
+            if let Err(e) =
+                cli_commands::migrate_pool::migrate_pool(migration_file, cli_commands::cli_prompt())
+            {
+                exit_on_error("migrate-pool", e);
+            }
+        }
         Command::PrintContractHashes => {
             print_contract_hashes();
         }
-        oracle_command => handle_oracle_command(oracle_command),
+        Command::PrintContractAddresses => {
+            if let Err(e) =
+                cli_commands::print_contract_addresses::print_contract_addresses(&ORACLE_CONFIG)
+            {
+                exit_on_error("print-contract-addresses", e);
+            }
+        }
+        Command::VerifyContracts => {
+            if let Err(e) = cli_commands::verify_contracts::verify_contracts(&ORACLE_CONFIG) {
+                exit_on_error("verify-contracts", e);
+            }
+        }
+        Command::VerifyPool {
+            explorer_url,
+            strict,
+        } => {
+            if let Err(e) =
+                cli_commands::verify_pool::verify_pool(&ORACLE_CONFIG, explorer_url, strict)
+            {
+                exit_on_error("verify-pool", e);
+            }
+        }
+        Command::PoolHistory {
+            backfill,
+            explorer_url,
+            limit,
+        } => {
+            if let Err(e) = cli_commands::pool_history::pool_history(
+                &ORACLE_CONFIG,
+                backfill,
+                explorer_url,
+                limit,
+            ) {
+                exit_on_error("pool-history", e);
+            }
+        }
+        Command::Simulate { oracles, epochs } => {
+            if let Err(e) = cli_commands::simulate::simulate(oracles, epochs) {
+                exit_on_error("simulate", e);
+            }
+        }
+        Command::Snapshot {
+            command: SnapshotCommand::Import { snapshot_file },
+        } => {
+            if let Err(e) = cli_commands::snapshot::import_snapshot(
+                &ORACLE_CONFIG,
+                make_pool_controller(&ORACLE_CONFIG),
+                Path::new(&snapshot_file),
+            ) {
+                exit_on_error("snapshot import", e);
+            }
+        }
+        Command::Backup { archive } => {
+            if let Err(e) = cli_commands::backup::backup(
+                Path::new(oracle_config::CONFIG_FILE_PATH.get().unwrap()),
+                Path::new(&archive),
+            ) {
+                exit_on_error("backup", e);
+            }
+        }
+        Command::Restore { archive, force } => {
+            if let Err(e) = cli_commands::backup::restore(
+                Path::new(&archive),
+                Path::new(oracle_config::CONFIG_FILE_PATH.get().unwrap()),
+                force,
+            ) {
+                exit_on_error("restore", e);
+            }
+        }
+        Command::SplitConfig { pool_config_file } => {
+            if let Err(e) = cli_commands::split_config::split_config(
+                Path::new(oracle_config::CONFIG_FILE_PATH.get().unwrap()),
+                &pool_config_file,
+            ) {
+                exit_on_error("split-config", e);
+            }
+        }
+        Command::ValidateConfig { print_schema } => {
+            if print_schema {
+                cli_commands::validate_config::print_config_schema();
+            } else if let Err(e) = cli_commands::validate_config::validate_config(Path::new(
+                oracle_config::CONFIG_FILE_PATH.get().unwrap(),
+            )) {
+                exit_on_error("validate-config", e);
+            }
+        }
+        Command::PoolConfigHash { pool_config_file } => {
+            if let Err(e) =
+                cli_commands::split_config::print_pool_config_hash(Path::new(&pool_config_file))
+            {
+                exit_on_error("pool-config-hash", e);
+            }
+        }
+        Command::ImportPool {
+            pool_nft,
+            testnet,
+            explorer_url,
+            output,
+        } => {
+            let network_prefix = if testnet {
+                NetworkPrefix::Testnet
+            } else {
+                NetworkPrefix::Mainnet
+            };
+            let explorer_url = explorer_url
+                .unwrap_or_else(|| pool_archive::default_explorer_url(network_prefix).to_string());
+            if let Err(e) = cli_commands::import_pool::parse_token_id(&pool_nft).and_then(|id| {
+                cli_commands::import_pool::import_pool(
+                    &id,
+                    &explorer_url,
+                    Path::new(oracle_config::CONFIG_FILE_PATH.get().unwrap()),
+                    &output,
+                )
+            }) {
+                exit_on_error("import-pool", e);
+            }
+        }
+        Command::GenerateConfig { preset } => {
+            if let Err(e) = cli_commands::generate_config::generate_config(
+                Path::new(oracle_config::CONFIG_FILE_PATH.get().unwrap()),
+                preset,
+            ) {
+                exit_on_error("generate-config", e);
+            }
+        }
+        Command::EncryptSecret { keyring } => {
+            if let Err(e) = cli_commands::encrypt_secret::encrypt_secret(keyring) {
+                exit_on_error("encrypt-secret", e);
+            }
+        }
+        Command::RunPools { pools_file } => {
+            if let Err(e) = cli_commands::run_pools::run_pools(Path::new(&pools_file)) {
+                exit_on_error("run-pools", e);
+            }
+        }
+        oracle_command => handle_oracle_command(oracle_command, args.allow_custom_contracts),
     }
 }
 
 /// Handle all non-bootstrap commands that require ORACLE_CONFIG/OraclePool
-fn handle_oracle_command(command: Command) {
-    assert_wallet_unlocked(&new_node_interface());
+fn handle_oracle_command(command: Command, allow_custom_contracts: bool) {
+    if let Err(e) = cli_commands::verify_contracts::assert_contract_templates(
+        &ORACLE_CONFIG,
+        allow_custom_contracts,
+    ) {
+        exit_on_error("verify-contracts", e);
+    }
+    let watch_only = runs_watch_only(&command);
+    if !watch_only {
+        let node = new_node_interface();
+        if let Some(password) = &ORACLE_CONFIG.node_wallet_password {
+            if let Err(e) = unlock_wallet(&node, password) {
+                error!("Failed to unlock the node wallet: {:?}", e);
+            }
+        }
+        assert_wallet_unlocked(&node);
+        warn_on_foreign_change_address();
+    }
     register_and_save_scans().unwrap();
+    if let Err(e) = store::init(Path::new(store::STORE_FILE)) {
+        exit_on_error("store", e);
+    }
     let op = OraclePool::new().unwrap();
     match command {
         Command::Run {
             read_only,
             enable_rest_api,
+            ..
         } => {
-            assert_wallet_unlocked(&new_node_interface());
             let (_, repost_receiver) = bounded::<bool>(1);
-            let op = OraclePool::new().unwrap();
+            let mut op = OraclePool::new().unwrap();
 
             // Start Oracle Core GET API Server
             if enable_rest_api {
-                let rt = tokio::runtime::Runtime::new().unwrap();
-                rt.block_on(start_rest_server(repost_receiver));
+                let address = get_core_api_address();
+                let admin_token = ORACLE_CONFIG
+                    .core_api_admin_token_file
+                    .as_ref()
+                    .map(|path| admin::read_token_file(path))
+                    .transpose()
+                    .unwrap_or_else(|e| {
+                        error!("Failed to read core_api_admin_token_file: {}", e);
+                        std::process::exit(exitcode::CONFIG);
+                    });
+                std::thread::spawn(move || {
+                    let rt = tokio::runtime::Runtime::new().unwrap();
+                    if let Err(e) =
+                        rt.block_on(start_rest_server(repost_receiver, address, admin_token))
+                    {
+                        error!("REST API server on {} failed: {}", address, e);
+                    }
+                });
+            }
+            if let Some(grpc_config) = ORACLE_CONFIG.grpc.clone() {
+                start_grpc_service(grpc_config);
+            }
+            let mut reward_extractor = ORACLE_CONFIG
+                .reward_extraction
+                .clone()
+                .map(RewardExtractor::new);
+            let mut publish_skipper = ORACLE_CONFIG.skip_publish.clone().map(PublishSkipper::new);
+            let mut reorg_tracker = ReorgTracker::new();
+            if let Err(e) = store::get().and_then(|store| reorg_tracker.load_pending_txs(store)) {
+                log::warn!(
+                    "Failed to load the txs submitted by the previous run: {}",
+                    e
+                );
+            }
+            if !watch_only {
+                let epoch = op
+                    .get_pool_box_source()
+                    .get_pool_box()
+                    .map(|pool_box| pool_box.epoch_counter())
+                    .ok();
+                if let Err(e) = store::get()
+                    .map_err(TxHistoryError::from)
+                    .and_then(|store| {
+                        recover_pending_operations(
+                            &NodeChain {},
+                            &new_node_interface(),
+                            store,
+                            epoch,
+                        )
+                    })
+                {
+                    log::warn!("Failed to recover the pending operations: {}", e);
+                }
+            }
+            let mut controller = make_pool_controller(&ORACLE_CONFIG);
+            let mut pool_watcher = PoolWatcher::new(
+                epoch_length(&ORACLE_CONFIG),
+                ORACLE_CONFIG
+                    .refresh_box_wrapper_inputs
+                    .contract_inputs
+                    .contract_parameters()
+                    .max_deviation_percent() as u32,
+            );
+            let mut epoch_tracker =
+                EpochTracker::new(ORACLE_CONFIG.oracle_address.network(), local_public_key());
+            if watch_only {
+                log::info!("Watch-only mode, no tx will be built");
+            }
+            HEALTH.set_watch_only(watch_only);
+            match DowntimeTracker::load(Path::new(DOWNTIME_FILE)) {
+                Ok(tracker) => *DOWNTIME.lock().unwrap() = tracker,
+                Err(e) => log::warn!("Failed to load the downtime of the previous run: {}", e),
+            }
+            let mut missed_epoch_tracker = MissedEpochTracker::new(
+                ORACLE_CONFIG
+                    .missed_epochs_alert_threshold
+                    .unwrap_or(DEFAULT_ALERT_THRESHOLD),
+            );
+            let mut balance_monitor = match &ORACLE_CONFIG.low_balance_alert {
+                Some(alert) => BalanceMonitor::new(
+                    alert.min_nanoergs,
+                    alert.min_epochs,
+                    ORACLE_CONFIG.base_fee,
+                ),
+                None => BalanceMonitor::new(None, Some(DEFAULT_MIN_EPOCHS), ORACLE_CONFIG.base_fee),
+            };
+            let mut peer_monitor = ORACLE_CONFIG.peer_monitor.as_ref().map(|config| {
+                let report = load_peer_report(Path::new(PEER_REPORT_FILE)).unwrap_or_else(|e| {
+                    log::warn!("Failed to load the peer report of the previous run: {}", e);
+                    Vec::new()
+                });
+                *PEER_REPORT.lock().unwrap() = report.clone();
+                PeerMonitor::new(
+                    config,
+                    ORACLE_CONFIG.oracle_address.network(),
+                    local_public_key(),
+                    report,
+                )
+            });
+            let mut storage_rent_monitor = StorageRentMonitor::default();
+            let mut rate_anomaly_detector =
+                RateAnomalyDetector::new(ORACLE_CONFIG.rate_anomaly.as_ref());
+            match PoolArchive::load(
+                Path::new(POOL_ARCHIVE_FILE),
+                &ORACLE_CONFIG.token_ids.pool_nft_token_id,
+            ) {
+                Ok(archive) => rate_anomaly_detector.seed(
+                    archive
+                        .pool_boxes
+                        .iter()
+                        .filter_map(|b| u64::try_from(b.rate).ok()),
+                ),
+                Err(e) => log::warn!(
+                    "Failed to load the pool archive for the rate anomalies: {}",
+                    e
+                ),
             }
-            loop {
-                if let Err(e) = main_loop_iteration(&op, read_only) {
+            let mut notification_triggers = NotificationTriggers::new(
+                ORACLE_CONFIG
+                    .notifications
+                    .as_ref()
+                    .and_then(|n| n.reward_threshold),
+            );
+            let mut config = ORACLE_CONFIG.clone();
+            let mut config_watcher =
+                ConfigWatcher::new(Path::new(oracle_config::CONFIG_FILE_PATH.get().unwrap()));
+            shutdown::listen_for_shutdown_signals();
+            shutdown::notify_systemd("READY=1");
+            while !shutdown::shutdown_requested() {
+                let reload_res = config_watcher.poll().or_else(|| {
+                    ADMIN_REQUESTS
+                        .take_reload_request()
+                        .then(|| config_watcher.reload())
+                });
+                if let Some(reload_res) = reload_res {
+                    match reload_res
+                        .map_err(anyhow::Error::from)
+                        .and_then(|new_config| {
+                            reload_config(
+                                &mut config,
+                                new_config,
+                                &mut op,
+                                &mut reward_extractor,
+                                &mut publish_skipper,
+                                &mut controller,
+                            )
+                        }) {
+                        Ok(changed) if changed.is_empty() => (),
+                        Ok(changed) => {
+                            log::info!("Config reloaded, applied: {}", changed.join(", "))
+                        }
+                        Err(e) => {
+                            log::error!("Config reload rejected, keeping the running config: {}", e)
+                        }
+                    }
+                }
+                if let Some(change) = ADMIN_REQUESTS.take_data_source_change() {
+                    let mut new_config = config.clone();
+                    new_config.data_point_source = change.data_point_source;
+                    new_config.data_point_source_custom_script =
+                        change.data_point_source_custom_script;
+                    match new_config.data_point_source() {
+                        Ok(data_point_source) => {
+                            op.data_point_source = data_point_source;
+                            config = new_config;
+                            log::info!("Data source changed by the admin API");
+                        }
+                        Err(e) => log::error!("Data source change rejected: {}", e),
+                    }
+                }
+                if ADMIN_REQUESTS.take_extraction_request() {
+                    if let Some(reward_extractor) = reward_extractor.as_mut() {
+                        reward_extractor.force_next_extraction();
+                    } else {
+                        log::warn!("Reward extraction requested without a reward_extraction config, ignored");
+                    }
+                }
+                // Before the iteration, a resubmitted tx is in the mempool again when it checks the
+                // submitted txs
+                if !watch_only {
+                    if let Err(e) = store::get()
+                        .map_err(TxHistoryError::from)
+                        .and_then(|store| {
+                            poll_tx_statuses(&NodeChain {}, &new_node_interface(), store)
+                        })
+                    {
+                        log::warn!("Failed to check the status of the submitted txs: {}", e);
+                    }
+                }
+                let iteration_res = if watch_only {
+                    watch_loop_iteration(&op, &mut pool_watcher, &mut epoch_tracker)
+                } else {
+                    main_loop_iteration(
+                        &op,
+                        read_only,
+                        reward_extractor.as_mut(),
+                        publish_skipper.as_mut(),
+                        &mut reorg_tracker,
+                        &mut controller,
+                    )
+                    .and_then(|()| {
+                        // Events of the other oracles for the `/events` WebSocket
+                        if enable_rest_api {
+                            let height = current_block_height()? as u32;
+                            watch_pool(&op, &mut pool_watcher, &mut epoch_tracker, height, false)
+                        } else {
+                            Ok(())
+                        }
+                    })
+                };
+                if let Err(e) = iteration_res {
                     error!("error: {:?}", e);
                 }
+                check_notifications(
+                    &op,
+                    &mut notification_triggers,
+                    &mut missed_epoch_tracker,
+                    &mut balance_monitor,
+                    watch_only,
+                );
+                if let Some(peer_monitor) = peer_monitor.as_mut() {
+                    if let Err(e) = monitor_peers(&op, peer_monitor) {
+                        log::warn!("Failed to check the peer oracles: {}", e);
+                    }
+                }
+                if let Err(e) = check_pool_rate(&op, &mut rate_anomaly_detector) {
+                    log::warn!("Failed to check the pool rate: {}", e);
+                }
+                if let Err(e) =
+                    check_storage_rent(&op, &mut storage_rent_monitor, read_only || watch_only)
+                {
+                    log::warn!("Failed to check the storage rent: {}", e);
+                }
+                record_downtime(watch_only);
+                HEALTH.record_iteration();
                 // Delay loop restart
-                thread::sleep(Duration::new(30, 0));
+                shutdown::sleep_unless_shutdown(Duration::new(30, 0));
             }
+            shutdown::notify_systemd("STOPPING=1");
+            log::info!("Oracle core stopped");
+            log::logger().flush();
         }
 
-        Command::ExtractRewardTokens { rewards_address } => {
+        Command::ExtractRewardTokens {
+            rewards_address,
+            amount,
+        } => {
             let wallet = WalletData {};
             if let Err(e) = cli_commands::extract_reward_tokens::extract_reward_tokens(
                 &wallet,
                 op.get_local_datapoint_box_source(),
                 rewards_address,
+                amount,
+                cli_commands::cli_prompt(),
             ) {
-                error!("Fatal extract-rewards-token error: {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_on_error("extract-rewards-token", e);
             }
         }
 
@@ -269,8 +1137,103 @@ fn handle_oracle_command(command: Command) {
             if let Err(e) = cli_commands::print_reward_tokens::print_reward_tokens(
                 op.get_local_datapoint_box_source(),
             ) {
-                error!("Fatal print-rewards-token error: {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_on_error("print-rewards-token", e);
+            }
+        }
+
+        Command::PrintStatus => {
+            if let Err(e) = (|| -> Result<(), anyhow::Error> {
+                let height = current_block_height()? as u32;
+                let epoch_length = ORACLE_CONFIG
+                    .refresh_box_wrapper_inputs
+                    .contract_inputs
+                    .contract_parameters()
+                    .epoch_length() as u32;
+                let fee_ledger = store::get()?.fee_ledger()?;
+                cli_commands::print_status::print_status(
+                    op.get_local_datapoint_box_source(),
+                    op.get_pool_box_source(),
+                    op.get_pending_txs()?,
+                    FeeStatus::from(&fee_ledger),
+                    epoch_length,
+                    height,
+                )?;
+                Ok(())
+            })() {
+                exit_on_error("print-status", e);
+            }
+        }
+
+        Command::TxHistory { status, limit } => {
+            if let Err(e) = cli_commands::tx_history::print_tx_history(status, limit) {
+                exit_on_error("tx-history", e);
+            }
+        }
+
+        Command::PeerReport => {
+            if let Err(e) = cli_commands::peer_report::print_peer_report() {
+                exit_on_error("peer-report", e);
+            }
+        }
+
+        Command::DatapointHistory => {
+            let max_deviation_percent = ORACLE_CONFIG
+                .refresh_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .max_deviation_percent() as u32;
+            if let Err(e) = cli_commands::datapoint_history::print_datapoint_history(
+                op.get_local_datapoint_box_source(),
+                max_deviation_percent,
+            ) {
+                exit_on_error("datapoint-history", e);
+            }
+        }
+        Command::EarningsReport {
+            fiat,
+            reward_token_price,
+            output,
+        } => {
+            if let Err(e) = cli_commands::earnings_report::print_earnings_report(
+                op.get_local_datapoint_box_source(),
+                fiat,
+                reward_token_price,
+                output,
+            ) {
+                exit_on_error("earnings-report", e);
+            }
+        }
+        Command::ExportHistory {
+            from_height,
+            to_height,
+            backfill,
+            explorer_url,
+            output,
+        } => {
+            if let Err(e) = cli_commands::export_history::export_history(
+                &ORACLE_CONFIG,
+                op.get_local_datapoint_box_source(),
+                from_height,
+                to_height,
+                backfill,
+                explorer_url,
+                output,
+            ) {
+                exit_on_error("export-history", e);
+            }
+        }
+        Command::PublishDatapoint { value } => {
+            let wallet = WalletData {};
+            if let Err(e) = cli_commands::publish_datapoint::publish_datapoint(
+                &wallet,
+                op.get_local_datapoint_box_source(),
+                op.get_pool_box_source(),
+                ORACLE_CONFIG.oracle_box_wrapper_inputs.clone(),
+                ORACLE_CONFIG.oracle_address.address(),
+                value,
+                cli_commands::cli_prompt(),
+            ) {
+                exit_on_error("publish-datapoint", e);
             }
         }
 
@@ -281,10 +1244,51 @@ fn handle_oracle_command(command: Command) {
             if let Err(e) = cli_commands::transfer_oracle_token::transfer_oracle_token(
                 &wallet,
                 op.get_local_datapoint_box_source(),
+                op.get_datapoint_boxes_source(),
                 oracle_token_address,
+                ORACLE_CONFIG.oracle_address.network(),
+                cli_commands::cli_prompt(),
+            ) {
+                exit_on_error("transfer-oracle-token", e);
+            }
+        }
+        Command::ExitPool {
+            pool_admin_address,
+            rewards_address,
+        } => {
+            let wallet = WalletData {};
+            if let Err(e) = cli_commands::exit_pool::exit_pool(
+                &wallet,
+                op.get_local_datapoint_box_source(),
+                pool_admin_address,
+                rewards_address,
+                cli_commands::cli_prompt(),
             ) {
-                error!("Fatal transfer-oracle-token error: {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_on_error("exit-pool", e);
+            }
+        }
+
+        Command::TopUpPoolBox { nanoergs } => {
+            let wallet = WalletData {};
+            if let Err(e) = cli_commands::top_up_pool_box::top_up_pool_box(
+                &op,
+                &wallet,
+                nanoergs,
+                cli_commands::cli_prompt(),
+            ) {
+                exit_on_error("top-up-pool-box", e);
+            }
+        }
+
+        Command::MaintainRefreshBox { top_up } => {
+            let wallet = WalletData {};
+            if let Err(e) = cli_commands::maintain_refresh_box::maintain_refresh_box(
+                &op,
+                &wallet,
+                top_up,
+                cli_commands::cli_prompt(),
+            ) {
+                exit_on_error("maintain-refresh-box", e);
             }
         }
 
@@ -293,18 +1297,98 @@ fn handle_oracle_command(command: Command) {
             reward_token_id_str,
             reward_token_amount,
             update_box_creation_height,
+            proposal_file,
         } => {
             let wallet = WalletData {};
-            if let Err(e) = cli_commands::vote_update_pool::vote_update_pool(
-                &wallet,
-                op.get_local_ballot_box_source(),
+            let res = match (
+                proposal_file,
                 new_pool_box_address_hash_str,
                 reward_token_id_str,
                 reward_token_amount,
                 update_box_creation_height,
             ) {
-                error!("Fatal vote-update-pool error: {:?}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                (Some(proposal_file), ..) => {
+                    cli_commands::vote_update_pool::vote_update_pool_with_proposal(
+                        &wallet,
+                        op.get_local_ballot_box_source(),
+                        proposal_file,
+                        cli_commands::cli_prompt(),
+                    )
+                }
+                (
+                    None,
+                    Some(new_pool_box_address_hash_str),
+                    Some(reward_token_id_str),
+                    Some(reward_token_amount),
+                    Some(update_box_creation_height),
+                ) => cli_commands::vote_update_pool::vote_update_pool(
+                    &wallet,
+                    op.get_local_ballot_box_source(),
+                    new_pool_box_address_hash_str,
+                    reward_token_id_str,
+                    reward_token_amount,
+                    update_box_creation_height,
+                    cli_commands::cli_prompt(),
+                ),
+                // clap ensures all arguments are present without a proposal file
+                _ => unreachable!(),
+            };
+            if let Err(e) = res {
+                exit_on_error("vote-update-pool", e);
+            }
+        }
+        Command::VerifyProposal {
+            proposal_file,
+            ballot_box_id,
+        } => {
+            if let Err(e) = cli_commands::verify_proposal::verify_proposal(
+                op.get_pool_box_source(),
+                op.get_ballot_boxes_source(),
+                proposal_file,
+                ballot_box_id,
+            ) {
+                exit_on_error("verify-proposal", e);
+            }
+        }
+        Command::DiffPool => {
+            if let Err(e) = cli_commands::diff_pool::diff_pool(&ORACLE_CONFIG, &op) {
+                exit_on_error("diff-pool", e);
+            }
+        }
+        Command::Snapshot {
+            command: SnapshotCommand::Export { snapshot_file },
+        } => {
+            if let Err(e) = cli_commands::snapshot::export_snapshot(
+                &op,
+                ORACLE_CONFIG.oracle_address.network(),
+                Path::new(&snapshot_file),
+            ) {
+                exit_on_error("snapshot export", e);
+            }
+        }
+        Command::BallotStatus => {
+            if let Err(e) = cli_commands::ballot_status::print_ballot_status(
+                op.get_ballot_boxes_source(),
+                op.get_update_box_source(),
+                op.get_datapoint_boxes_source(),
+                ORACLE_CONFIG.oracle_address.network(),
+            ) {
+                exit_on_error("ballot-status", e);
+            }
+        }
+        Command::ProposeUpdate {
+            proposal_file,
+            reward_token_id,
+            reward_token_amount,
+        } => {
+            if let Err(e) = cli_commands::propose_update::propose_update(
+                op.get_pool_box_source(),
+                op.get_update_box_source(),
+                reward_token_id,
+                reward_token_amount,
+                proposal_file,
+            ) {
+                exit_on_error("propose-update", e);
             }
         }
         Command::UpdatePool {
@@ -322,52 +1406,842 @@ fn handle_oracle_command(command: Command) {
             if let Err(e) =
                 cli_commands::update_pool::update_pool(&op, new_pool_box_hash, new_reward_tokens)
             {
-                error!("Fatal update-pool error: {}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_on_error("update-pool", e);
+            }
+        }
+        Command::RenewStorageRent {
+            force,
+            reward_token_amount,
+            proposal_file,
+        } => {
+            if let Err(e) = cli_commands::renew_storage_rent::renew_storage_rent(
+                &op,
+                force,
+                reward_token_amount,
+                proposal_file,
+                cli_commands::cli_prompt(),
+            ) {
+                exit_on_error("renew-storage-rent", e);
             }
         }
         Command::PrepareUpdate { update_file } => {
             if let Err(e) = cli_commands::prepare_update::prepare_update(update_file) {
-                error!("Fatal update error : {}", e);
-                std::process::exit(exitcode::SOFTWARE);
+                exit_on_error("prepare-update", e);
+            }
+        }
+        Command::Rescan { from_height } => {
+            if let Err(e) = cli_commands::rescan::rescan(
+                op.get_pool_box_source(),
+                op.get_refresh_box_source(),
+                op.get_local_datapoint_box_source(),
+                from_height,
+            ) {
+                exit_on_error("rescan", e);
+            }
+        }
+        Command::Repair => {
+            let wallet = WalletData {};
+            if let Err(e) = cli_commands::repair::repair(
+                &wallet,
+                op.get_local_datapoint_box_source(),
+                cli_commands::cli_prompt(),
+            ) {
+                exit_on_error("repair", e);
+            }
+        }
+        Command::ConsolidateUtxos { dust_threshold } => {
+            let wallet = WalletData {};
+            if let Err(e) = cli_commands::consolidate_utxos::consolidate_utxos(
+                &wallet,
+                dust_threshold,
+                cli_commands::cli_prompt(),
+            ) {
+                exit_on_error("consolidate-utxos", e);
             }
         }
-        Command::Bootstrap { .. } | Command::PrintContractHashes => unreachable!(),
+        Command::Bootstrap { .. }
+        | Command::Init
+        | Command::MigratePool { .. }
+        | Command::PrintContractHashes
+        | Command::PrintContractAddresses
+        | Command::VerifyContracts
+        | Command::VerifyPool { .. }
+        | Command::PoolHistory { .. }
+        | Command::Simulate { .. }
+        | Command::Snapshot {
+            command: SnapshotCommand::Import { .. },
+        }
+        | Command::Backup { .. }
+        | Command::Restore { .. }
+        | Command::SplitConfig { .. }
+        | Command::ValidateConfig { .. }
+        | Command::PoolConfigHash { .. }
+        | Command::ImportPool { .. }
+        | Command::GenerateConfig { .. }
+        | Command::EncryptSecret { .. }
+        | Command::RunPools { .. } => {
+            unreachable!()
+        }
     }
 }
 
-fn main_loop_iteration(op: &OraclePool, read_only: bool) -> std::result::Result<(), anyhow::Error> {
+/// Log the error of the failed command (and print it in `--json` mode) and exit.
+fn exit_on_error<E: Into<anyhow::Error>>(command_name: &str, e: E) -> ! {
+    let e = e.into();
+    let code = cli_commands::exit_code(&e);
+    error!("Fatal {} error: {:?}", command_name, e);
+    cli_commands::print_error_json(command_name, code, e.to_string());
+    std::process::exit(code);
+}
+
+fn epoch_length(config: &OracleConfig) -> u32 {
+    config
+        .refresh_box_wrapper_inputs
+        .contract_inputs
+        .contract_parameters()
+        .epoch_length() as u32
+}
+
+fn watch_loop_iteration(
+    op: &OraclePool,
+    pool_watcher: &mut PoolWatcher,
+    epoch_tracker: &mut EpochTracker,
+) -> std::result::Result<(), anyhow::Error> {
     let height = current_block_height().context("Failed to get the current height")? as u32;
+    record_metrics(op, None, height);
+    watch_pool(op, pool_watcher, epoch_tracker, height, true)
+}
+
+fn local_public_key() -> Option<ProveDlog> {
+    if let Address::P2Pk(public_key) = ORACLE_CONFIG.oracle_address.address() {
+        Some(public_key)
+    } else {
+        None
+    }
+}
+
+/// Report the new epochs and datapoints of the pool (see [`PoolWatcher`]) on the `/events`
+/// WebSocket, and in the log with `log_events`. The statistics of the finished epochs are recorded
+/// for `/epochStats`.
+fn watch_pool(
+    op: &OraclePool,
+    pool_watcher: &mut PoolWatcher,
+    epoch_tracker: &mut EpochTracker,
+    height: u32,
+    log_events: bool,
+) -> std::result::Result<(), anyhow::Error> {
+    let network_prefix = ORACLE_CONFIG.oracle_address.network();
+    let local_public_key = local_public_key();
+    let pool_box = op.get_pool_box_source().get_pool_box()?;
+    let oracle_boxes = op.get_all_oracle_boxes()?;
+    let datapoints: Vec<PostedOracleBox> = oracle_boxes
+        .iter()
+        .filter_map(|b| {
+            if let OracleBoxWrapper::Posted(posted_box) = b {
+                Some(posted_box.clone())
+            } else {
+                None
+            }
+        })
+        .collect();
+    // The oracle boxes created by the refresh tx of the pool box
+    let collected: Vec<CollectedDatapoint> = oracle_boxes
+        .iter()
+        .filter(|b| {
+            matches!(b, OracleBoxWrapper::Collected(_))
+                && b.get_box().transaction_id == pool_box.get_box().transaction_id
+        })
+        .map(|b| CollectedDatapoint {
+            oracle: b.public_key(),
+            reward_tokens: u64::from(b.reward_token().amount),
+        })
+        .collect();
+    if let Some(stats) = epoch_tracker.observe(
+        pool_box.epoch_counter(),
+        pool_box.rate() as u64,
+        pool_box.get_box().creation_height,
+        datapoints
+            .iter()
+            .filter(|b| b.epoch_counter() == pool_box.epoch_counter())
+            .map(|b| PostedDatapoint {
+                oracle: b.public_key(),
+                rate: b.rate(),
+                reward_tokens: u64::from(b.reward_token().amount),
+            })
+            .collect(),
+        &collected,
+    ) {
+        EPOCH_STATS.push(stats);
+    }
+    for event in pool_watcher.observe(
+        pool_box.epoch_counter(),
+        pool_box.rate() as u64,
+        pool_box.get_box().creation_height,
+        &datapoints,
+        height,
+    ) {
+        if log_events {
+            event.log(network_prefix);
+        }
+        EVENTS.publish(PoolEvent::from_watch_event(
+            &event,
+            network_prefix,
+            local_public_key.as_ref(),
+        ));
+    }
+    Ok(())
+}
+
+/// Notify the operator of the node being unreachable and of the changes of the pool needing
+/// attention
+fn check_notifications(
+    op: &OraclePool,
+    triggers: &mut NotificationTriggers,
+    missed_epoch_tracker: &mut MissedEpochTracker,
+    balance_monitor: &mut BalanceMonitor,
+    watch_only: bool,
+) {
+    if let Err(e) = current_block_height() {
+        NOTIFICATIONS.notify(
+            NotificationKind::NodeUnreachable,
+            format!("The node doesn't respond: {}", e),
+        );
+        return;
+    }
+    if !watch_only {
+        let pool_box = op.get_pool_box_source().get_pool_box();
+        let local_box = op
+            .get_local_datapoint_box_source()
+            .get_local_oracle_datapoint_box();
+        if let (Ok(pool_box), Ok(local_box)) = (pool_box, local_box) {
+            let epoch = pool_box.epoch_counter();
+            let consecutive = missed_epoch_tracker.consecutive();
+            let alert = missed_epoch_tracker.observe(
+                epoch,
+                || collected_by_refresh(op, &pool_box),
+                || {
+                    let posted_in_missed_epoch = matches!(
+                        &local_box,
+                        Some(OracleBoxWrapper::Posted(posted_box)) if posted_box.epoch_counter() + 1 == epoch
+                    );
+                    MissedEpochCause::probable(
+                        posted_in_missed_epoch,
+                        HEALTH.data_source_error(),
+                        HEALTH.posting_error(),
+                    )
+                },
+            );
+            if missed_epoch_tracker.consecutive() > consecutive {
+                METRICS.record_missed_epoch(missed_epoch_tracker.consecutive());
+            } else if missed_epoch_tracker.consecutive() < consecutive {
+                METRICS.record_collected_epoch();
+            }
+            HEALTH.record_missed_epochs(
+                missed_epoch_tracker.consecutive(),
+                missed_epoch_tracker.threshold(),
+                missed_epoch_tracker.cause().map(|c| c.to_string()),
+            );
+            if let Some(message) = alert {
+                NOTIFICATIONS.notify(NotificationKind::EpochMissed, message);
+            }
+            if let Some(message) = local_box.and_then(|local_box| {
+                triggers.reward_threshold_reached(*local_box.reward_token().amount.as_u64())
+            }) {
+                NOTIFICATIONS.notify(NotificationKind::RewardThreshold, message);
+            }
+            match WalletData::new().get_unspent_wallet_boxes() {
+                Ok(boxes) => {
+                    let balance = boxes.iter().map(|b| *b.value.as_u64()).sum();
+                    let alert = balance_monitor.observe(epoch, balance);
+                    METRICS.set_wallet_epochs_remaining(balance_monitor.epochs_remaining(balance));
+                    if let Some(message) = alert {
+                        log::warn!("{}", message);
+                        NOTIFICATIONS.notify(NotificationKind::LowBalance, message);
+                    }
+                }
+                Err(e) => debug!("Failed to get the wallet balance: {}", e),
+            }
+        }
+    }
+    match get_ballot_status(
+        op.get_ballot_boxes_source(),
+        op.get_update_box_source(),
+        op.get_datapoint_boxes_source(),
+        ORACLE_CONFIG.oracle_address.network(),
+    ) {
+        Ok(status) => {
+            let messages = triggers.new_update_proposals(&status.proposals);
+            if !messages.is_empty() {
+                NOTIFICATIONS.notify(NotificationKind::UpdateProposal, messages.join("\n"));
+            }
+        }
+        Err(e) => debug!(
+            "Failed to get the ballot boxes for the notifications: {}",
+            e
+        ),
+    }
+}
+
+/// Compare the datapoints of the peer oracles with the data source, see [`PeerMonitor`]
+fn monitor_peers(
+    op: &OraclePool,
+    peer_monitor: &mut PeerMonitor,
+) -> std::result::Result<(), anyhow::Error> {
+    let pool_box = op.get_pool_box_source().get_pool_box()?;
+    let refresh_tx_id = pool_box.get_box().transaction_id;
+    let peer_boxes: Vec<PeerBox> = op
+        .get_all_oracle_boxes()?
+        .iter()
+        .map(|b| PeerBox {
+            oracle: b.public_key(),
+            box_id: b.get_box().box_id(),
+            posted: match b {
+                OracleBoxWrapper::Posted(posted_box) => {
+                    Some((posted_box.epoch_counter(), posted_box.rate()))
+                }
+                OracleBoxWrapper::Collected(_) => None,
+            },
+            collected_by_last_refresh: b.get_box().transaction_id == refresh_tx_id,
+        })
+        .collect();
+    let messages = peer_monitor.observe(pool_box.epoch_counter(), &peer_boxes, || {
+        op.data_point_source
+            .get_datapoint()
+            .map_err(|e| log::warn!("Failed to get the datapoint to check the peers: {}", e))
+            .ok()
+    });
+    for message in &messages {
+        log::warn!("{}", message);
+    }
+    if !messages.is_empty() {
+        NOTIFICATIONS.notify(NotificationKind::PeerMisbehavior, messages.join("\n"));
+    }
+    let report = peer_monitor.report();
+    let mut last_report = PEER_REPORT.lock().unwrap();
+    if *last_report != report {
+        save_peer_report(Path::new(PEER_REPORT_FILE), &report)?;
+        *last_report = report;
+    }
+    Ok(())
+}
+
+/// Check the node, the wallet and the data source for the downtime report, see [`DowntimeTracker`]
+fn record_downtime(watch_only: bool) {
+    let now = unix_now();
+    let mut tracker = DOWNTIME.lock().unwrap();
+    let mut changed = tracker.observe(
+        now,
+        DowntimeCause::NodeDown,
+        current_block_height()
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+    );
+    if !watch_only {
+        // The wallet can't be checked without the node
+        if let Ok(status) = get_wallet_status() {
+            changed |= tracker.observe(
+                now,
+                DowntimeCause::WalletLocked,
+                if status.unlocked {
+                    Ok(())
+                } else {
+                    Err("wallet is locked".to_string())
+                },
+            );
+        }
+        changed |= tracker.observe(
+            now,
+            DowntimeCause::SourceDown,
+            HEALTH.data_source_error().map_or(Ok(()), Err),
+        );
+    }
+    // Saved while an interval lasts as well, it ends at the last observation if the daemon stops
+    if changed || tracker.has_open_interval() {
+        if let Err(e) = tracker.save(Path::new(DOWNTIME_FILE)) {
+            log::warn!("Failed to save the downtime: {}", e);
+        }
+    }
+}
+
+/// Record the confirmed pool box in the store and check its rate for an implausible jump, see
+/// [`RateAnomalyDetector`]
+fn check_pool_rate(
+    op: &OraclePool,
+    detector: &mut RateAnomalyDetector,
+) -> std::result::Result<(), anyhow::Error> {
+    let pool_box = op.get_confirmed_pool_box()?;
+    let rate = u64::try_from(pool_box.rate())?;
+    store::get()?.record_pool_state(
+        pool_box.epoch_counter(),
+        rate,
+        &pool_box.get_box().box_id(),
+        pool_box.get_box().creation_height,
+    )?;
+    if let Some(anomaly) = detector.observe(pool_box.epoch_counter(), rate) {
+        let message = anomaly.message();
+        log::warn!("Pool rate anomaly: {}", message);
+        EVENTS.publish(PoolEvent::RateAnomaly {
+            epoch: anomaly.epoch,
+            rate: anomaly.rate,
+            previous_rate: anomaly.previous_rate,
+            change_percent: anomaly.change_percent,
+            z_score: anomaly.z_score,
+        });
+        NOTIFICATIONS.notify(NotificationKind::RateAnomaly, message);
+    }
+    Ok(())
+}
+
+/// Alert when the storage rent of the pool, refresh or update box is due, see [`storage_rent`].
+/// With `auto_renew` the update box is renewed once the ballot holders voted for it.
+fn check_storage_rent(
+    op: &OraclePool,
+    monitor: &mut StorageRentMonitor,
+    read_only: bool,
+) -> std::result::Result<(), anyhow::Error> {
+    let height = current_block_height()? as u32;
+    if !monitor.should_check(height) {
+        return Ok(());
+    }
+    let config = ORACLE_CONFIG.storage_rent.as_ref();
+    let window = storage_rent::renewal_window(config);
+    let due: Vec<RentStatus> = storage_rent::protocol_boxes_rent(op, height)?
+        .into_iter()
+        .filter(|status| status.due(window))
+        .collect();
+    for status in &due {
+        let message = status.message();
+        log::warn!("{}", message);
+        NOTIFICATIONS.notify(NotificationKind::StorageRent, message);
+    }
+    let auto_renew = config.map_or(false, |c| c.auto_renew);
+    if read_only || !auto_renew || !due.iter().any(|s| s.protocol_box == ProtocolBox::Update) {
+        return Ok(());
+    }
+    let change_address =
+        get_change_address()?.ok_or_else(|| anyhow!("no change address set in the node"))?;
+    let change_address =
+        AddressEncoder::unchecked_parse_network_address_from_str(&change_address)?.address();
+    match build_update_box_renewal_tx(op, height, change_address, None) {
+        Ok(tx) => {
+            let tx_id = sign_and_submit_transaction(&tx, "renew_storage_rent")?;
+            log::info!("Update box renewed in tx {}", tx_id);
+        }
+        Err(RenewStorageRentError::UpdatePool(UpdatePoolError::NotEnoughVotes(
+            expected,
+            found,
+        ))) => {
+            log::info!(
+                "Update box renewal waiting for the votes of the ballot holders: {} of {}",
+                found,
+                expected
+            );
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+/// Whether the refresh tx creating `pool_box` recreated the oracle box of this oracle, spent since
+/// or not
+fn collected_by_refresh(op: &OraclePool, pool_box: &PoolBoxWrapper) -> bool {
+    let refresh_tx_id = pool_box.get_box().transaction_id;
+    let source = op.get_local_datapoint_box_source();
+    source
+        .get_local_oracle_datapoint_boxes()
+        .into_iter()
+        .flatten()
+        .chain(
+            source
+                .get_spent_local_oracle_datapoint_boxes()
+                .into_iter()
+                .flatten(),
+        )
+        .any(|b| b.get_box().transaction_id == refresh_tx_id)
+}
+
+/// Update the gauges served on `/metrics`, the wallet balance is left out without a wallet
+fn record_metrics(op: &OraclePool, wallet: Option<&dyn WalletDataSource>, height: u32) {
+    let pool_box = match op.get_pool_box_source().get_pool_box() {
+        Ok(pool_box) => pool_box,
+        Err(e) => {
+            debug!("Failed to get the pool box for the metrics: {}", e);
+            return;
+        }
+    };
+    let local_box = op
+        .get_local_datapoint_box_source()
+        .get_local_oracle_datapoint_box()
+        .ok()
+        .flatten();
+    let wallet_nanoergs = wallet
+        .and_then(|wallet| wallet.get_unspent_wallet_boxes().ok())
+        .map(|boxes| boxes.iter().map(|b| *b.value.as_u64()).sum());
+    METRICS.set_pool_gauges(PoolGauges::new(
+        &pool_box,
+        local_box.as_ref(),
+        wallet_nanoergs,
+        height,
+    ));
+}
+
+/// Whether the daemon follows the pool without a wallet, with `run --watch-only` or the
+/// `watch_only` epoch strategy
+fn runs_watch_only(command: &Command) -> bool {
+    match command {
+        Command::Run { watch_only, .. } => {
+            *watch_only || ORACLE_CONFIG.epoch_strategy == Some(EpochStrategyKind::WatchOnly)
+        }
+        _ => false,
+    }
+}
+
+fn make_pool_controller(config: &OracleConfig) -> PoolController {
+    let epoch_strategy = config.epoch_strategy.unwrap_or_default();
+    log::info!("Epoch strategy: {:?}", epoch_strategy);
+    let epoch_length = epoch_length(config);
+    let publish_timing = config
+        .publish_timing
+        .as_ref()
+        .map(|timing| PublishTiming {
+            offset_blocks: timing.offset_blocks,
+            jitter_blocks: timing.jitter_blocks,
+            seed: config.oracle_address.to_base58(),
+        })
+        .unwrap_or_default();
+    let collector_election = config.collector_election.as_ref().map(|election| {
+        let local_public_key = if let Address::P2Pk(public_key) = config.oracle_address.address() {
+            *public_key.h
+        } else {
+            exit_on_error("run", anyhow!("oracle address must be a P2PK address"))
+        };
+        CollectorElection {
+            local_public_key,
+            timeout_blocks: election.timeout_blocks,
+        }
+    });
+    PoolController::new(
+        epoch_strategy.strategy(),
+        epoch_length,
+        publish_timing,
+        collector_election,
+    )
+}
+
+#[cfg(feature = "grpc")]
+fn start_grpc_service(grpc_config: oracle_config::GrpcConfig) {
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let bind_address = grpc_config.bind_address;
+        if let Err(e) = rt.block_on(grpc::start_grpc_server(grpc_config)) {
+            error!("gRPC service on {} failed: {}", bind_address, e);
+        }
+    });
+}
+
+#[cfg(not(feature = "grpc"))]
+fn start_grpc_service(_grpc_config: oracle_config::GrpcConfig) {
+    log::warn!("The config has a grpc section but oracle-core was built without the grpc feature");
+}
+
+/// Apply the changes of `new_config` to the running daemon. Returns the names of the changed
+/// fields, nothing is applied if any of them requires a restart.
+fn reload_config(
+    config: &mut OracleConfig,
+    new_config: OracleConfig,
+    op: &mut OraclePool,
+    reward_extractor: &mut Option<RewardExtractor>,
+    publish_skipper: &mut Option<PublishSkipper>,
+    controller: &mut PoolController,
+) -> Result<Vec<String>, anyhow::Error> {
+    let changed = config_reload::reloadable_changes(config, &new_config)?;
+    let is_changed = |fields: &[&str]| changed.iter().any(|f| fields.contains(&f.as_str()));
+    if is_changed(&["data_point_source", "data_point_source_custom_script"]) {
+        op.data_point_source = new_config.data_point_source()?;
+    }
+    if is_changed(&["reward_extraction"]) {
+        *reward_extractor = new_config
+            .reward_extraction
+            .clone()
+            .map(RewardExtractor::new);
+    }
+    if is_changed(&["skip_publish"]) {
+        *publish_skipper = new_config.skip_publish.clone().map(PublishSkipper::new);
+    }
+    if is_changed(&["epoch_strategy", "publish_timing", "collector_election"]) {
+        *controller = make_pool_controller(&new_config);
+    }
+    *config = new_config;
+    Ok(changed)
+}
+
+fn main_loop_iteration(
+    op: &OraclePool,
+    read_only: bool,
+    reward_extractor: Option<&mut RewardExtractor>,
+    publish_skipper: Option<&mut PublishSkipper>,
+    reorg_tracker: &mut ReorgTracker,
+    controller: &mut PoolController,
+) -> std::result::Result<(), anyhow::Error> {
+    let height = current_block_height().context("Failed to get the current height")? as u32;
+    let _span = tracing::info_span!("iteration", height).entered();
     let wallet = WalletData::new();
-    let network_change_address = get_change_address_from_node()?;
+    let network_change_address = get_network_change_address()?;
+    let chain = NodeChain {};
+    match reorg_tracker.check(&chain) {
+        Ok(ReorgCheck::Reorg {
+            height: observed_height,
+            removed_box_ids,
+        }) => {
+            log::warn!(
+                "Height {height}. Chain reorganization, the block observed at height {observed_height} is not on the best chain anymore, removed boxes: {:?}",
+                removed_box_ids
+            );
+            scans::clear_scan_cache();
+            return Ok(());
+        }
+        Ok(ReorgCheck::Unchanged) => (),
+        Err(e) => log::warn!("Failed to check for chain reorganizations: {}", e),
+    }
+    let submitted_tx_ids = reorg_tracker.unconfirmed_submitted_tx_ids();
+    // With chained txs the scans see the outputs of the submitted txs, no need to wait for them
+    PENDING_OUTPUTS.retain_unconfirmed(&submitted_tx_ids);
+    if !submitted_tx_ids.is_empty() && !ORACLE_CONFIG.chain_transactions {
+        log::info!(
+            "Height {height}. Waiting for the submitted txs to be confirmed: {:?}",
+            submitted_tx_ids
+        );
+        return Ok(());
+    }
     let pool_state = match op.get_live_epoch_state() {
-        Ok(live_epoch_state) => PoolState::LiveEpoch(live_epoch_state),
+        Ok(live_epoch_state) => {
+            let mut observed_box_ids = vec![live_epoch_state.pool_box_id.clone()];
+            observed_box_ids.extend(live_epoch_state.local_datapoint_box_id.clone());
+            if !reorg_tracker
+                .observe(&chain, height, observed_box_ids)
+                .unwrap_or(true)
+            {
+                log::info!("Height {height}. Waiting for the scans to drop the boxes removed by the chain reorganization");
+                return Ok(());
+            }
+            PoolState::LiveEpoch(live_epoch_state)
+        }
+        Err(error @ StageError::DuplicateOracleBoxes(_)) => {
+            // Acting on one of the boxes could leave the oracle stuck, wait for the repair
+            log::error!("Height {height}. Refusing to act: {}", error);
+            return Ok(());
+        }
         Err(error) => {
             log::debug!("error getting live epoch state: {}", error);
             PoolState::NeedsBootstrap
         }
     };
-    let epoch_length = ORACLE_CONFIG
-        .refresh_box_wrapper_inputs
-        .contract_inputs
-        .contract_parameters()
-        .epoch_length() as u32;
-    if let Some(cmd) = process(pool_state, epoch_length, height) {
+    record_metrics(op, Some(&wallet), height);
+    let (current_epoch, pool_rate) = match &pool_state {
+        PoolState::LiveEpoch(live_epoch_state) => (
+            Some(live_epoch_state.pool_box_epoch_id),
+            Some(live_epoch_state.latest_pool_datapoint),
+        ),
+        PoolState::NeedsBootstrap => (None, None),
+    };
+    if let Some(current_epoch) = current_epoch {
+        POSTING_LATENCY.observe_epoch(current_epoch);
+        fees::observe_epoch(current_epoch);
+        if let Err(e) = action_queue::abandon_stale(current_epoch) {
+            log::warn!(
+                "Failed to abandon the actions of the previous epochs: {}",
+                e
+            );
+        }
+    }
+    if let Some(cmd) = controller.next_command(pool_state, height, op.get_datapoint_boxes_source())
+    {
+        if ADMIN_REQUESTS.posting_paused() && !matches!(cmd, PoolCommand::Refresh) {
+            log::info!(
+                "Height {height}. Datapoint posting is paused, not acting on {:?}",
+                cmd
+            );
+            return Ok(());
+        }
+        let queued_action = if read_only {
+            None
+        } else {
+            let queued_action =
+                action_queue::enqueue(store::get()?, ActionKind::of_command(&cmd), current_epoch)?;
+            if !queued_action.is_due(unix_now()) {
+                log::info!(
+                    "Height {height}. The {} action {} is {}, next attempt at {}",
+                    queued_action.kind.name(),
+                    queued_action.id,
+                    queued_action.status.name(),
+                    queued_action.next_attempt_at
+                );
+                return Ok(());
+            }
+            Some(queued_action)
+        };
         log::info!("Height {height}. Building action for command: {:?}", cmd);
-        let build_action_res = build_action(
+        // Only the publication after a collection can be skipped, the oracle posted in the
+        // previous epoch
+        let skippable = matches!(
             cmd,
-            op,
-            &wallet,
-            height as u32,
-            network_change_address.address(),
+            PoolCommand::PublishSubsequentDataPoint { republish: false }
         );
-        if let Some(action) =
-            log_and_continue_if_non_fatal(network_change_address.network(), build_action_res)?
-        {
+        let build_action_res = tracing::info_span!("build_action", command = ?cmd).in_scope(|| {
+            build_action(
+                cmd,
+                op,
+                &wallet,
+                height as u32,
+                network_change_address.address(),
+            )
+        });
+        let built_action =
+            log_and_continue_if_non_fatal(network_change_address.network(), build_action_res);
+        if let (Err(e), Some(queued_action)) = (&built_action, &queued_action) {
+            action_queue::record_attempt(store::get()?, queued_action.clone(), Err(e.to_string()))?;
+        }
+        if let Some(action) = built_action? {
+            if matches!(action, PoolAction::PublishDatapoint(_)) {
+                POSTING_LATENCY.record(LatencyStage::Built);
+            }
+            if let (
+                PoolAction::PublishDatapoint(publish_action),
+                true,
+                Some(publish_skipper),
+                Some(current_epoch),
+                Some(pool_rate),
+            ) = (
+                &action,
+                skippable,
+                publish_skipper,
+                current_epoch,
+                pool_rate,
+            ) {
+                if publish_skipper.should_skip(publish_action.datapoint, pool_rate, current_epoch) {
+                    log::info!(
+                        "Height {height}. Skipping the publication of datapoint {}, too close to the pool rate {}",
+                        publish_action.datapoint,
+                        pool_rate
+                    );
+                    if let Some(queued_action) = queued_action {
+                        action_queue::abandon(
+                            store::get()?,
+                            queued_action,
+                            "the datapoint is too close to the pool rate",
+                        )?;
+                    }
+                    return Ok(());
+                }
+            }
+            if let (PoolAction::Refresh(refresh_action), false) = (&action, read_only) {
+                // The pool box is the first input of the refresh tx
+                let pool_box_id = refresh_action.tx.inputs.first().box_id;
+                match op.get_mempool_source().get_unconfirmed_transactions() {
+                    Ok(unconfirmed_txs) => {
+                        if let Some(tx_id) = find_conflicting_tx(&unconfirmed_txs, &[pool_box_id]) {
+                            log::info!(
+                                "Height {height}. Tx {tx_id} in the mempool already spends the pool box, backing off the refresh until the next block"
+                            );
+                            controller.back_off_refresh(height);
+                            return Ok(());
+                        }
+                    }
+                    Err(e) => log::warn!("Failed to check the mempool before the refresh: {}", e),
+                }
+            }
             if !read_only {
-                execute_action(action)?;
+                if shutdown::shutdown_requested() {
+                    log::info!("Height {height}. Shutdown requested, dropping the action before submitting it");
+                    return Ok(());
+                }
+                let input_box_ids = action.input_box_ids();
+                let datapoint = match &action {
+                    PoolAction::PublishDatapoint(publish_action) => Some(publish_action.datapoint),
+                    PoolAction::Refresh(_) => None,
+                };
+                let publishing = datapoint.is_some();
+                let exec_res = execute_action(action);
+                if let Some(queued_action) = queued_action {
+                    let store = store::get()?;
+                    match &exec_res {
+                        Ok(Some((tx_id, _))) => {
+                            action_queue::record_attempt(store, queued_action, Ok(tx_id.clone()))?
+                        }
+                        Ok(None) => action_queue::abandon(
+                            store,
+                            queued_action,
+                            "the node rejected its tx as a double spend",
+                        )?,
+                        Err(e) => {
+                            action_queue::record_attempt(store, queued_action, Err(e.to_string()))?
+                        }
+                    }
+                }
+                if publishing {
+                    HEALTH.record_posting_result(exec_res.as_ref().err().map(|e| e.to_string()));
+                }
+                if let (Err(e), true) = (&exec_res, publishing) {
+                    NOTIFICATIONS.notify(
+                        NotificationKind::PostingFailed,
+                        format!("Failed to submit the datapoint tx: {}", e),
+                    );
+                }
+                if let Some((tx_id, tx)) = exec_res? {
+                    if let Some(datapoint) = datapoint {
+                        POSTING_LATENCY.record_submitted(tx_id.clone());
+                        let res = current_epoch.map_or(Ok(()), |epoch| {
+                            store::get()?.record_datapoint(epoch, datapoint, &tx_id)
+                        });
+                        if let Err(e) = res {
+                            log::warn!("Failed to record the posted datapoint: {}", e);
+                        }
+                    }
+                    reorg_tracker.track_submitted_tx(tx_id, input_box_ids);
+                    if ORACLE_CONFIG.chain_transactions {
+                        PENDING_OUTPUTS.track(tx);
+                    }
+                }
             }
         };
+    } else if let (Some(reward_extractor), Some(current_epoch), false, false) = (
+        reward_extractor,
+        current_epoch,
+        read_only,
+        controller.state() == ControllerState::StandingDown,
+    ) {
+        // Only extract when no other action spent the oracle box in this iteration
+        let store = store::get()?;
+        let open_action = store.open_action(ActionKind::RewardExtraction, None)?;
+        if let Some(open_action) = &open_action {
+            if !open_action.is_due(unix_now()) {
+                return Ok(());
+            }
+        }
+        match reward_extractor.extract_if_due(
+            op.get_local_datapoint_box_source(),
+            &wallet,
+            current_epoch,
+            height,
+            network_change_address.address(),
+        ) {
+            Ok(Some(tx_id)) => {
+                let queued_action =
+                    action_queue::enqueue(store, ActionKind::RewardExtraction, None)?;
+                action_queue::record_attempt(store, queued_action, Ok(tx_id))?;
+            }
+            Ok(None) => {
+                if let Some(open_action) = open_action {
+                    action_queue::abandon(store, open_action, "the extraction is no longer due")?;
+                }
+            }
+            Err(e) => {
+                log::error!("Automatic reward extraction failed: {}", e);
+                let queued_action =
+                    action_queue::enqueue(store, ActionKind::RewardExtraction, None)?;
+                action_queue::record_attempt(store, queued_action, Err(e.to_string()))?;
+            }
+        }
     }
     Ok(())
 }
@@ -393,20 +2267,40 @@ fn log_and_continue_if_non_fatal(
         }
         Err(PoolCommandError::PublishDatapointActionError(DataPointSource(e))) => {
             log::error!("Failed to get datapoint with error: {}", e);
+            NOTIFICATIONS.notify(
+                NotificationKind::PostingFailed,
+                format!("Failed to get the datapoint from the data source: {}", e),
+            );
             Ok(None)
         }
         Err(e) => Err(e),
     }
 }
 
-fn get_change_address_from_node() -> Result<NetworkAddress, anyhow::Error> {
-    let change_address_str = get_wallet_status()?
-        .change_address
-        .ok_or_else(|| anyhow!("failed to get wallet's change address (locked wallet?)"))?;
+/// Change address of the config, or of the node wallet if not set
+fn get_network_change_address() -> Result<NetworkAddress, anyhow::Error> {
+    let change_address_str = get_change_address()?.ok_or_else(|| {
+        anyhow!("failed to get wallet's change address (locked wallet?), it can be set in change_address of the config")
+    })?;
     let addr = AddressEncoder::unchecked_parse_network_address_from_str(&change_address_str)?;
     Ok(addr)
 }
 
+/// Warns if the change address of the config is not an address of the node wallet
+fn warn_on_foreign_change_address() {
+    if let Some(change_address) = &ORACLE_CONFIG.change_address {
+        let change_address = change_address.to_base58();
+        match get_wallet_addresses() {
+            Ok(addresses) if !addresses.contains(&change_address) => log::warn!(
+                "The change address {} of the config is not an address of the node wallet",
+                change_address
+            ),
+            Ok(_) => (),
+            Err(e) => log::warn!("Failed to get the addresses of the node wallet: {}", e),
+        }
+    }
+}
+
 fn log_on_launch() {
     log::info!("{}", APP_VERSION);
     if let Ok(config) = MAYBE_ORACLE_CONFIG.clone() {
@@ -0,0 +1,400 @@
+//! Metrics of the daemon served on `/metrics` of the REST API in the Prometheus text format. The
+//! gauges are updated on every iteration of the main loop, the counters when a tx is submitted or
+//! a node request fails, the latency histograms by the [`PostingLatency`](crate::latency) of the
+//! posting pipeline.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::box_kind::{OracleBox, OracleBoxWrapper, PoolBox, PoolBoxWrapper};
+use crate::fees::FeeTotal;
+use crate::latency::LatencyStage;
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+/// Upper bounds (seconds) of the buckets of the posting latency histograms, an epoch lasts about an
+/// hour
+const LATENCY_BUCKETS: [f64; 11] = [
+    1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0,
+];
+
+/// State of the pool and of the local oracle at the last iteration of the main loop
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoolGauges {
+    pub pool_rate: i64,
+    pub epoch_counter: u32,
+    pub blocks_since_pool_update: u32,
+    /// `None` without a local datapoint box
+    pub local_datapoint_age_blocks: Option<u32>,
+    pub reward_tokens: Option<u64>,
+    /// `None` in watch-only mode
+    pub wallet_nanoergs: Option<u64>,
+}
+
+impl PoolGauges {
+    pub fn new(
+        pool_box: &PoolBoxWrapper,
+        local_box: Option<&OracleBoxWrapper>,
+        wallet_nanoergs: Option<u64>,
+        height: u32,
+    ) -> Self {
+        PoolGauges {
+            pool_rate: pool_box.rate(),
+            epoch_counter: pool_box.epoch_counter(),
+            blocks_since_pool_update: height.saturating_sub(pool_box.get_box().creation_height),
+            local_datapoint_age_blocks: local_box
+                .map(|b| height.saturating_sub(b.get_box().creation_height)),
+            reward_tokens: local_box.map(|b| u64::from(b.reward_token().amount)),
+            wallet_nanoergs,
+        }
+    }
+}
+
+/// Prometheus histogram, the counts are per bucket (not cumulative)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(buckets: &'static [f64]) -> Self {
+        Histogram {
+            buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        if let Some(i) = self.buckets.iter().position(|le| value <= *le) {
+            self.counts[i] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// The `_bucket`, `_sum` and `_count` samples of `name` with the `labels` (e.g. `stage="built"`)
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (le, count) in self.buckets.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name, labels, le, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"+Inf\"}} {}",
+            name, labels, self.count
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+pub struct Metrics {
+    gauges: Mutex<Option<PoolGauges>>,
+    /// Submitted txs by action
+    tx_submissions: Mutex<BTreeMap<&'static str, u64>>,
+    node_errors: AtomicU64,
+    consecutive_missed_epochs: AtomicU64,
+    missed_epochs: AtomicU64,
+    wallet_epochs_remaining: Mutex<Option<u64>>,
+    /// Seconds from the opening of the epoch to each stage of the datapoint posting
+    posting_latency: Mutex<BTreeMap<LatencyStage, Histogram>>,
+    /// Fees of the fee ledger by action
+    fees: Mutex<BTreeMap<String, FeeTotal>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            gauges: Mutex::new(None),
+            tx_submissions: Mutex::new(BTreeMap::new()),
+            node_errors: AtomicU64::new(0),
+            consecutive_missed_epochs: AtomicU64::new(0),
+            missed_epochs: AtomicU64::new(0),
+            wallet_epochs_remaining: Mutex::new(None),
+            posting_latency: Mutex::new(BTreeMap::new()),
+            fees: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn set_pool_gauges(&self, gauges: PoolGauges) {
+        *self.gauges.lock().unwrap() = Some(gauges);
+    }
+
+    /// Count a tx submitted for `action` (e.g. `refresh`)
+    pub fn record_tx_submission(&self, action: &'static str) {
+        *self
+            .tx_submissions
+            .lock()
+            .unwrap()
+            .entry(action)
+            .or_insert(0) += 1;
+    }
+
+    pub fn record_node_error(&self) {
+        self.node_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a missed epoch, `consecutive` including it
+    pub fn record_missed_epoch(&self, consecutive: u32) {
+        self.missed_epochs.fetch_add(1, Ordering::Relaxed);
+        self.consecutive_missed_epochs
+            .store(consecutive as u64, Ordering::Relaxed);
+    }
+
+    /// A datapoint of the oracle was collected, ending the missed epochs in a row
+    pub fn record_collected_epoch(&self) {
+        self.consecutive_missed_epochs.store(0, Ordering::Relaxed);
+    }
+
+    /// Epochs the wallet balance still covers at the measured fee burn
+    pub fn set_wallet_epochs_remaining(&self, epochs: Option<u64>) {
+        *self.wallet_epochs_remaining.lock().unwrap() = epochs;
+    }
+
+    pub fn record_posting_latency(&self, stage: LatencyStage, seconds: f64) {
+        self.posting_latency
+            .lock()
+            .unwrap()
+            .entry(stage)
+            .or_insert_with(|| Histogram::new(&LATENCY_BUCKETS))
+            .observe(seconds);
+    }
+
+    /// Totals of the fee ledger by action
+    pub fn set_fee_totals(&self, fees: BTreeMap<String, FeeTotal>) {
+        *self.fees.lock().unwrap() = fees;
+    }
+
+    /// The metrics in the Prometheus text exposition format. The gauges are left out until the
+    /// main loop observed the pool.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if let Some(gauges) = self.gauges.lock().unwrap().as_ref() {
+            let mut gauge = |name: &str, help: &str, value: Option<String>| {
+                if let Some(value) = value {
+                    let _ = writeln!(out, "# HELP {} {}", name, help);
+                    let _ = writeln!(out, "# TYPE {} gauge", name);
+                    let _ = writeln!(out, "{} {}", name, value);
+                }
+            };
+            gauge(
+                "oracle_pool_rate",
+                "Rate of the pool box",
+                Some(gauges.pool_rate.to_string()),
+            );
+            gauge(
+                "oracle_pool_epoch_counter",
+                "Epoch counter of the pool box",
+                Some(gauges.epoch_counter.to_string()),
+            );
+            gauge(
+                "oracle_pool_blocks_since_update",
+                "Blocks since the pool box was created",
+                Some(gauges.blocks_since_pool_update.to_string()),
+            );
+            gauge(
+                "oracle_local_datapoint_age_blocks",
+                "Blocks since the local oracle box was created",
+                gauges.local_datapoint_age_blocks.map(|v| v.to_string()),
+            );
+            gauge(
+                "oracle_reward_tokens",
+                "Reward tokens in the local oracle box",
+                gauges.reward_tokens.map(|v| v.to_string()),
+            );
+            gauge(
+                "oracle_wallet_nanoergs",
+                "Balance of the node wallet in nanoERG",
+                gauges.wallet_nanoergs.map(|v| v.to_string()),
+            );
+            gauge(
+                "oracle_wallet_epochs_remaining",
+                "Epochs the wallet balance covers at the measured fee burn",
+                self.wallet_epochs_remaining
+                    .lock()
+                    .unwrap()
+                    .map(|v| v.to_string()),
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP oracle_tx_submissions_total Txs submitted by the daemon"
+        );
+        let _ = writeln!(out, "# TYPE oracle_tx_submissions_total counter");
+        for (action, count) in self.tx_submissions.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "oracle_tx_submissions_total{{action=\"{}\"}} {}",
+                action, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP oracle_node_errors_total Failed requests to the node"
+        );
+        let _ = writeln!(out, "# TYPE oracle_node_errors_total counter");
+        let _ = writeln!(
+            out,
+            "oracle_node_errors_total {}",
+            self.node_errors.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "# HELP oracle_missed_epochs_total Epochs whose refresh didn't collect the datapoint of the oracle"
+        );
+        let _ = writeln!(out, "# TYPE oracle_missed_epochs_total counter");
+        let _ = writeln!(
+            out,
+            "oracle_missed_epochs_total {}",
+            self.missed_epochs.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "# HELP oracle_consecutive_missed_epochs Epochs missed in a row by the oracle"
+        );
+        let _ = writeln!(out, "# TYPE oracle_consecutive_missed_epochs gauge");
+        let _ = writeln!(
+            out,
+            "oracle_consecutive_missed_epochs {}",
+            self.consecutive_missed_epochs.load(Ordering::Relaxed)
+        );
+        let fees = self.fees.lock().unwrap();
+        let _ = writeln!(
+            out,
+            "# HELP oracle_fees_nanoergs_total Tx fees paid by the oracle wallet in nanoERG"
+        );
+        let _ = writeln!(out, "# TYPE oracle_fees_nanoergs_total counter");
+        for (action, total) in fees.iter() {
+            let _ = writeln!(
+                out,
+                "oracle_fees_nanoergs_total{{action=\"{}\"}} {}",
+                action, total.nanoergs
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP oracle_fee_txs_total Txs paid by the oracle wallet"
+        );
+        let _ = writeln!(out, "# TYPE oracle_fee_txs_total counter");
+        for (action, total) in fees.iter() {
+            let _ = writeln!(
+                out,
+                "oracle_fee_txs_total{{action=\"{}\"}} {}",
+                action, total.txs
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP oracle_posting_latency_seconds Seconds from the opening of the epoch to each stage of the datapoint posting"
+        );
+        let _ = writeln!(out, "# TYPE oracle_posting_latency_seconds histogram");
+        for (stage, histogram) in self.posting_latency.lock().unwrap().iter() {
+            histogram.render(
+                &mut out,
+                "oracle_posting_latency_seconds",
+                &format!("stage=\"{}\"", stage.name()),
+            );
+        }
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        let rendered = metrics.render();
+        assert!(!rendered.contains("oracle_pool_rate"));
+        assert!(rendered.contains("oracle_node_errors_total 0\n"));
+
+        metrics.set_pool_gauges(PoolGauges {
+            pool_rate: 1000,
+            epoch_counter: 7,
+            blocks_since_pool_update: 12,
+            local_datapoint_age_blocks: Some(3),
+            reward_tokens: Some(42),
+            wallet_nanoergs: None,
+        });
+        metrics.record_tx_submission("refresh");
+        metrics.record_tx_submission("refresh");
+        metrics.record_tx_submission("publish_datapoint");
+        metrics.record_node_error();
+        metrics.record_missed_epoch(1);
+        metrics.record_missed_epoch(2);
+        metrics.set_wallet_epochs_remaining(Some(12));
+        metrics.set_fee_totals(
+            vec![(
+                "refresh".to_string(),
+                FeeTotal {
+                    txs: 2,
+                    nanoergs: 2_000_000,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let rendered = metrics.render();
+        assert!(rendered.contains("# TYPE oracle_pool_rate gauge\noracle_pool_rate 1000\n"));
+        assert!(rendered.contains("oracle_pool_epoch_counter 7\n"));
+        assert!(rendered.contains("oracle_reward_tokens 42\n"));
+        assert!(!rendered.contains("oracle_wallet_nanoergs"));
+        assert!(rendered.contains("oracle_wallet_epochs_remaining 12\n"));
+        assert!(rendered.contains("oracle_tx_submissions_total{action=\"refresh\"} 2\n"));
+        assert!(rendered.contains("oracle_tx_submissions_total{action=\"publish_datapoint\"} 1\n"));
+        assert!(rendered.contains("oracle_node_errors_total 1\n"));
+        assert!(rendered.contains("oracle_missed_epochs_total 2\n"));
+        assert!(rendered.contains("oracle_consecutive_missed_epochs 2\n"));
+        assert!(rendered.contains("oracle_fees_nanoergs_total{action=\"refresh\"} 2000000\n"));
+        assert!(rendered.contains("oracle_fee_txs_total{action=\"refresh\"} 2\n"));
+        metrics.record_collected_epoch();
+        assert!(metrics
+            .render()
+            .contains("oracle_consecutive_missed_epochs 0\n"));
+    }
+
+    #[test]
+    fn test_render_histogram() {
+        let metrics = Metrics::new();
+        metrics.record_posting_latency(LatencyStage::Built, 3.0);
+        metrics.record_posting_latency(LatencyStage::Built, 40.0);
+        metrics.record_posting_latency(LatencyStage::Built, 5000.0);
+        let rendered = metrics.render();
+        assert!(rendered.contains("# TYPE oracle_posting_latency_seconds histogram\n"));
+        assert!(rendered
+            .contains("oracle_posting_latency_seconds_bucket{stage=\"built\",le=\"1\"} 0\n"));
+        assert!(rendered
+            .contains("oracle_posting_latency_seconds_bucket{stage=\"built\",le=\"5\"} 1\n"));
+        assert!(rendered
+            .contains("oracle_posting_latency_seconds_bucket{stage=\"built\",le=\"60\"} 2\n"));
+        assert!(rendered
+            .contains("oracle_posting_latency_seconds_bucket{stage=\"built\",le=\"3600\"} 2\n"));
+        assert!(rendered
+            .contains("oracle_posting_latency_seconds_bucket{stage=\"built\",le=\"+Inf\"} 3\n"));
+        assert!(rendered.contains("oracle_posting_latency_seconds_sum{stage=\"built\"} 5043\n"));
+        assert!(rendered.contains("oracle_posting_latency_seconds_count{stage=\"built\"} 3\n"));
+        assert!(!rendered.contains("stage=\"fetched\""));
+    }
+}
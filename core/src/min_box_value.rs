@@ -0,0 +1,107 @@
+//! Minimum value of the boxes. The network rejects a box holding fewer than
+//! [`BoxValue::MIN_VALUE_PER_BOX_BYTE`] nanoERGs per byte of the box. The output candidates are
+//! checked before the tx is built from them and the outputs of every tx (change and fee boxes
+//! included) before it is signed, so that a value computed by a command (the oracle boxes at
+//! `min_storage_rent`, a topped up box, the change after the estimated fee) fails with the output
+//! and its minimum instead of the node's rejection.
+
+use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
+use ergo_lib::chain::transaction::TxId;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+use ergo_lib::ergotree_ir::chain::ergo_box::{ErgoBox, ErgoBoxCandidate};
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MinBoxValueError {
+    #[error(
+        "output {index} holds {value} nanoERGs, below the minimum of {min_value} nanoERGs for its \
+         {size} bytes ({} per byte)",
+        BoxValue::MIN_VALUE_PER_BOX_BYTE
+    )]
+    BelowMinValue {
+        index: usize,
+        value: u64,
+        min_value: u64,
+        size: usize,
+    },
+    #[error("output {0} can't be serialized: {1}")]
+    Serialization(usize, String),
+}
+
+/// Size in bytes of `candidate` as an output of a tx and its minimum value
+pub fn min_box_value(candidate: &ErgoBoxCandidate) -> Result<(usize, u64), String> {
+    // The size of the box doesn't depend on the tx id
+    let b = ErgoBox::from_box_candidate(candidate, TxId::zero(), 0).map_err(|e| e.to_string())?;
+    let size = b.sigma_serialize_bytes().map_err(|e| e.to_string())?.len();
+    Ok((size, size as u64 * BoxValue::MIN_VALUE_PER_BOX_BYTE as u64))
+}
+
+/// Check that every output holds its minimum value, before the tx is built from them
+pub fn check_min_box_values(outputs: &[ErgoBoxCandidate]) -> Result<(), MinBoxValueError> {
+    for (index, candidate) in outputs.iter().enumerate() {
+        let (size, min_value) =
+            min_box_value(candidate).map_err(|e| MinBoxValueError::Serialization(index, e))?;
+        let value = *candidate.value.as_u64();
+        if value < min_value {
+            return Err(MinBoxValueError::BelowMinValue {
+                index,
+                value,
+                min_value,
+                size,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the outputs of `tx` (change and fee boxes included) before it is signed, see
+/// [`check_min_box_values`]
+pub fn check_tx_min_box_values(tx: &UnsignedTransaction) -> Result<(), MinBoxValueError> {
+    let res = check_min_box_values(tx.output_candidates.as_vec());
+    if let Err(e) = &res {
+        log::error!(
+            "The tx fails the min box value check, it was not submitted: {}",
+            e
+        );
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::Address;
+    use sigma_test_util::force_any_val;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_min_box_values() {
+        let tree = Address::P2Pk(force_any_val::<DlogProverInput>().public_image())
+            .script()
+            .unwrap();
+        let candidate = ErgoBoxCandidateBuilder::new(BoxValue::SAFE_USER_MIN, tree.clone(), 1)
+            .build()
+            .unwrap();
+        let (size, min_value) = min_box_value(&candidate).unwrap();
+        assert_eq!(
+            min_value,
+            size as u64 * BoxValue::MIN_VALUE_PER_BOX_BYTE as u64
+        );
+        assert_eq!(check_min_box_values(&[candidate.clone()]), Ok(()));
+
+        let mut dust = candidate.clone();
+        dust.value = BoxValue::try_from(min_value - 1).unwrap();
+        assert_eq!(
+            check_min_box_values(&[candidate, dust]),
+            Err(MinBoxValueError::BelowMinValue {
+                index: 1,
+                value: min_value - 1,
+                min_value,
+                size,
+            })
+        );
+    }
+}
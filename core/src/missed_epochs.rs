@@ -0,0 +1,166 @@
+//! Epochs of the pool missed by the oracle, its datapoint not being collected by the refresh ending
+//! them. After `missed_epochs_alert_threshold` consecutive missed epochs the operator is notified
+//! with the probable cause and the readiness check of the REST API fails until a datapoint of the
+//! oracle is collected again.
+
+use std::fmt;
+
+/// Consecutive missed epochs alerted without `missed_epochs_alert_threshold` in the config
+pub const DEFAULT_ALERT_THRESHOLD: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MissedEpochCause {
+    /// A datapoint was posted in the epoch, the refresh left it out (outlier or posted too late)
+    NotCollected,
+    /// The last datapoint request to the data source failed
+    SourceDown(String),
+    /// The last datapoint tx was rejected by the node
+    TxFailed(String),
+    NoDatapointPosted,
+}
+
+impl MissedEpochCause {
+    /// The most specific cause known when the miss is observed
+    pub fn probable(
+        posted_in_missed_epoch: bool,
+        data_source_error: Option<String>,
+        posting_error: Option<String>,
+    ) -> Self {
+        if posted_in_missed_epoch {
+            MissedEpochCause::NotCollected
+        } else if let Some(e) = data_source_error {
+            MissedEpochCause::SourceDown(e)
+        } else if let Some(e) = posting_error {
+            MissedEpochCause::TxFailed(e)
+        } else {
+            MissedEpochCause::NoDatapointPosted
+        }
+    }
+}
+
+impl fmt::Display for MissedEpochCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissedEpochCause::NotCollected => write!(
+                f,
+                "the datapoint was posted but not collected (outlier or posted too late)"
+            ),
+            MissedEpochCause::SourceDown(e) => write!(f, "the data source is down: {}", e),
+            MissedEpochCause::TxFailed(e) => write!(f, "the datapoint tx failed: {}", e),
+            MissedEpochCause::NoDatapointPosted => write!(f, "no datapoint was posted"),
+        }
+    }
+}
+
+pub struct MissedEpochTracker {
+    threshold: u32,
+    epoch: Option<u32>,
+    consecutive: u32,
+    cause: Option<MissedEpochCause>,
+}
+
+impl MissedEpochTracker {
+    pub fn new(threshold: u32) -> Self {
+        MissedEpochTracker {
+            threshold,
+            epoch: None,
+            consecutive: 0,
+            cause: None,
+        }
+    }
+
+    /// Observe the pool box of `epoch`. On a new epoch `collected` tells whether the refresh
+    /// starting it collected the datapoint of the oracle, `cause` is evaluated for a missed epoch.
+    /// The epoch the daemon started in is skipped. Returns the alert message when the consecutive
+    /// missed epochs reach the threshold.
+    pub fn observe(
+        &mut self,
+        epoch: u32,
+        collected: impl FnOnce() -> bool,
+        cause: impl FnOnce() -> MissedEpochCause,
+    ) -> Option<String> {
+        let new_epoch = self.epoch.map_or(false, |e| epoch > e);
+        if self.epoch.map_or(true, |e| epoch > e) {
+            self.epoch = Some(epoch);
+        }
+        if !new_epoch {
+            return None;
+        }
+        if collected() {
+            self.consecutive = 0;
+            self.cause = None;
+            return None;
+        }
+        let cause = cause();
+        self.consecutive += 1;
+        log::warn!(
+            "Epoch {} missed ({} in a row), probable cause: {}",
+            epoch - 1,
+            self.consecutive,
+            cause
+        );
+        self.cause = Some(cause);
+        (self.consecutive == self.threshold).then(|| self.alert())
+    }
+
+    pub fn consecutive(&self) -> u32 {
+        self.consecutive
+    }
+
+    pub fn threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    /// Probable cause of the last missed epoch, `None` once a datapoint was collected again
+    pub fn cause(&self) -> Option<&MissedEpochCause> {
+        self.cause.as_ref()
+    }
+
+    pub fn alert(&self) -> String {
+        format!(
+            "The datapoint of the oracle wasn't collected in the last {} epochs, probable cause: {}",
+            self.consecutive,
+            self.cause
+                .as_ref()
+                .map_or("unknown".to_string(), |c| c.to_string())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missed_epoch_tracker() {
+        let mut tracker = MissedEpochTracker::new(2);
+        let no_datapoint = || MissedEpochCause::NoDatapointPosted;
+        let missed = || false;
+        // Started in epoch 5
+        assert_eq!(tracker.observe(5, missed, no_datapoint), None);
+        assert_eq!(tracker.consecutive(), 0);
+        assert_eq!(tracker.observe(6, missed, no_datapoint), None);
+        assert_eq!(tracker.observe(6, missed, no_datapoint), None);
+        assert_eq!(tracker.consecutive(), 1);
+        let alert = tracker
+            .observe(7, missed, || {
+                MissedEpochCause::probable(false, Some("timeout".to_string()), None)
+            })
+            .unwrap();
+        assert!(alert.contains("the data source is down: timeout"));
+        assert_eq!(tracker.observe(8, missed, no_datapoint), None);
+        assert_eq!(tracker.consecutive(), 3);
+        assert_eq!(tracker.observe(9, || true, no_datapoint), None);
+        assert_eq!(tracker.consecutive(), 0);
+        assert_eq!(tracker.cause(), None);
+
+        assert_eq!(
+            MissedEpochCause::probable(true, Some("timeout".to_string()), None),
+            MissedEpochCause::NotCollected
+        );
+        assert_eq!(
+            MissedEpochCause::probable(false, None, Some("rejected".to_string())),
+            MissedEpochCause::TxFailed("rejected".to_string())
+        );
+    }
+}
@@ -1,10 +1,22 @@
+use std::convert::TryFrom;
+
+use derive_more::From;
+use thiserror::Error;
+
 use crate::{
-    oracle_config::{get_node_api_key, get_node_ip, get_node_port},
+    audit_log,
+    metrics::METRICS,
+    min_box_value::{check_tx_min_box_values, MinBoxValueError},
+    oracle_config::{get_node_api_key, get_node_ip, get_node_port, MAYBE_ORACLE_CONFIG},
+    tx_history::record_submitted_tx,
+    tx_validation::{check_tx_before_submit, TxValidationError},
     wallet::{WalletDataError, WalletDataSource},
 };
 use ergo_lib::{
     chain::transaction::{unsigned::UnsignedTransaction, Transaction, TxIoVec},
-    ergotree_ir::chain::ergo_box::ErgoBox,
+    ergo_chain_types::Header,
+    ergotree_interpreter::sigma_protocol::private_input::DlogProverInput,
+    ergotree_ir::chain::ergo_box::{BoxId, ErgoBox},
 };
 use ergo_node_interface::{
     node_interface::{NodeError, NodeInterface, WalletStatus},
@@ -12,6 +24,7 @@ use ergo_node_interface::{
 };
 use log::debug;
 use log::error;
+use log::info;
 
 pub type Result<T> = std::result::Result<T, NodeError>;
 pub type ScanID = String;
@@ -19,6 +32,18 @@ pub type TxId = String;
 pub type P2PKAddressString = String;
 pub type P2SAddressString = String;
 
+/// Error of a tx signed and submitted by the node, a tx failing the local checks isn't sent to
+/// the node
+#[derive(Debug, Error, From)]
+pub enum SubmitTxError {
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("tx validation error: {0}")]
+    TxValidation(#[source] TxValidationError),
+    #[error("min box value error: {0}")]
+    MinBoxValue(#[source] MinBoxValueError),
+}
+
 pub trait SubmitTransaction {
     fn submit_transaction(&self, tx: &Transaction) -> Result<String>;
 }
@@ -67,15 +92,46 @@ pub fn new_node_interface() -> NodeInterface {
     NodeInterface::new(&get_node_api_key(), &get_node_ip(), &get_node_port())
 }
 
+/// Count the failed requests of the main loop for the `/metrics` endpoint
+fn record_error<T>(res: Result<T>) -> Result<T> {
+    if res.is_err() {
+        METRICS.record_node_error();
+    }
+    res
+}
+
 /// Registers a scan with the node and either returns the `scan_id` or an error
 pub fn register_scan(scan_json: &serde_json::Value) -> Result<ScanID> {
     let scan_json_t = json::parse(&serde_json::to_string(scan_json).unwrap()).unwrap();
     new_node_interface().register_scan(&scan_json_t)
 }
 
+/// Ids and names of the scans registered in the node
+pub fn list_scans() -> Result<Vec<(ScanID, String)>> {
+    let node = new_node_interface();
+    let res = node.send_get_req("/scan/listAll");
+    let res_json = node.parse_response_to_json(res)?;
+    Ok(res_json
+        .members()
+        .map(|scan| {
+            (
+                scan["scanId"].to_string(),
+                scan["scanName"].as_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect())
+}
+
+/// Deregisters the scan, the node stops tracking its boxes
+pub fn deregister_scan(scan_id: &ScanID) -> Result<()> {
+    new_node_interface()
+        .send_post_req("/scan/deregister", format!("{{ \"scanId\": {} }}", scan_id))?;
+    Ok(())
+}
+
 /// Acquires unspent boxes from the node wallet
 pub fn get_unspent_wallet_boxes() -> Result<Vec<ErgoBox>> {
-    new_node_interface().unspent_boxes()
+    record_error(new_node_interface().unspent_boxes())
 }
 
 /// Acquires the unspent box with the highest value of Ergs inside
@@ -96,7 +152,22 @@ pub fn get_serialized_highest_value_unspent_box() -> Result<String> {
 
 /// Using the `scan_id` of a registered scan, acquires unspent boxes which have been found by said scan
 pub fn get_scan_boxes(scan_id: &String) -> Result<Vec<ErgoBox>> {
-    new_node_interface().scan_boxes(scan_id)
+    record_error(new_node_interface().scan_boxes(scan_id))
+}
+
+/// Using the `scan_id` of a registered scan, acquires the spent boxes which have been found by said
+/// scan
+pub fn get_spent_scan_boxes(scan_id: &String) -> Result<Vec<ErgoBox>> {
+    let node = new_node_interface();
+    let res = node.send_get_req(&format!("/scan/spentBoxes/{}?limit=1000", scan_id));
+    let res_json = node.parse_response_to_json(res)?;
+    res_json
+        .members()
+        .map(|wallet_box| {
+            serde_json::from_str(&wallet_box["box"].dump())
+                .map_err(|e| NodeError::FailedParsingNodeResponse(e.to_string()))
+        })
+        .collect()
 }
 
 pub fn rescan_from_height(height: u32) -> Result<()> {
@@ -107,15 +178,138 @@ pub fn rescan_from_height(height: u32) -> Result<()> {
     Ok(())
 }
 
+/// Unconfirmed transactions in the node mempool
+pub fn get_unconfirmed_transactions() -> Result<Vec<Transaction>> {
+    let node = new_node_interface();
+    let res = node.send_get_req("/transactions/unconfirmed?limit=1000");
+    let res_json = record_error(node.parse_response_to_json(res))?;
+    res_json
+        .members()
+        .map(|tx_json| {
+            serde_json::from_str(&tx_json.dump())
+                .map_err(|e| NodeError::FailedParsingNodeResponse(e.to_string()))
+        })
+        .collect()
+}
+
+/// Id of the block at the given height of the best chain, `None` if the chain is not that high
+pub fn get_block_id_at_height(height: u32) -> Result<Option<String>> {
+    let node = new_node_interface();
+    let res = node.send_get_req(&format!("/blocks/at/{}", height));
+    let res_json = node.parse_response_to_json(res)?;
+    Ok(res_json[0].as_str().map(str::to_string))
+}
+
+/// Whether the box is in the UTXO set of the node
+pub fn is_box_unspent(box_id: &BoxId) -> Result<bool> {
+    let res = new_node_interface()
+        .send_get_req(&format!("/utxo/byId/{}", String::from(box_id.clone())))?;
+    match res.status().as_u16() {
+        200 => Ok(true),
+        404 => Ok(false),
+        status => Err(NodeError::BadRequest(format!(
+            "/utxo/byId responded with status {}",
+            status
+        ))),
+    }
+}
+
+/// Unspent box or box created by a tx of the mempool, `None` if the node knows neither
+pub fn get_box_with_pool(box_id: &str) -> Result<Option<ErgoBox>> {
+    let node = new_node_interface();
+    let res = node.send_get_req(&format!("/utxo/withPool/byId/{}", box_id))?;
+    match res.status().as_u16() {
+        200 => serde_json::from_str(&node.parse_response_to_json(Ok(res))?.dump())
+            .map(Some)
+            .map_err(|e| NodeError::FailedParsingNodeResponse(e.to_string())),
+        404 => Ok(None),
+        status => Err(NodeError::BadRequest(format!(
+            "/utxo/withPool/byId responded with status {}",
+            status
+        ))),
+    }
+}
+
+/// The last `count` headers of the best chain, the oldest first
+pub fn get_last_headers(count: u32) -> Result<Vec<Header>> {
+    let node = new_node_interface();
+    let res = node.send_get_req(&format!("/blocks/lastHeaders/{}", count));
+    let res_json = node.parse_response_to_json(res)?;
+    res_json
+        .members()
+        .map(|header_json| {
+            serde_json::from_str(&header_json.dump())
+                .map_err(|e| NodeError::FailedParsingNodeResponse(e.to_string()))
+        })
+        .collect()
+}
+
+/// Height of the block including the wallet tx `tx_id`, `None` if the wallet doesn't know it as
+/// confirmed
+pub fn get_wallet_tx_inclusion_height(tx_id: &str) -> Result<Option<u32>> {
+    let node = new_node_interface();
+    let res = node.send_get_req(&format!("/wallet/transactionById?id={}", tx_id))?;
+    match res.status().as_u16() {
+        200 => Ok(node.parse_response_to_json(Ok(res))?["inclusionHeight"].as_u32()),
+        404 => Ok(None),
+        status => Err(NodeError::BadRequest(format!(
+            "/wallet/transactionById responded with status {}",
+            status
+        ))),
+    }
+}
+
+/// Secret key of the node wallet address `address`, the wallet has to be unlocked
+pub fn get_wallet_secret_key(address: &str) -> Result<DlogProverInput> {
+    let node = new_node_interface();
+    let res = node.send_post_req("/wallet/getPrivateKey", format!("\"{}\"", address));
+    let res_json = node.parse_response_to_json(res)?;
+    res_json
+        .as_str()
+        .and_then(|secret| base16::decode(secret).ok())
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| DlogProverInput::from_bytes(&bytes))
+        .ok_or_else(|| {
+            NodeError::FailedParsingNodeResponse(format!(
+                "no secret key for the wallet address {}",
+                address
+            ))
+        })
+}
+
 /// Get the current block height of the chain
 pub fn current_block_height() -> Result<BlockHeight> {
-    new_node_interface().current_block_height()
+    record_error(new_node_interface().current_block_height())
 }
 
 pub fn get_wallet_status() -> Result<WalletStatus> {
     new_node_interface().wallet_status()
 }
 
+/// Addresses of the node wallet
+pub fn get_wallet_addresses() -> Result<Vec<P2PKAddressString>> {
+    let node = new_node_interface();
+    let res = node.send_get_req("/wallet/addresses");
+    let res_json = node.parse_response_to_json(res)?;
+    Ok(res_json
+        .members()
+        .filter_map(|address| address.as_str().map(str::to_string))
+        .collect())
+}
+
+/// Change address of the txs, `change_address` of the config if set, otherwise the change address
+/// of the node wallet (`None` if the node has none)
+pub fn get_change_address() -> Result<Option<P2PKAddressString>> {
+    match MAYBE_ORACLE_CONFIG
+        .as_ref()
+        .ok()
+        .and_then(|config| config.change_address.as_ref())
+    {
+        Some(change_address) => Ok(Some(change_address.to_base58())),
+        None => Ok(get_wallet_status()?.change_address),
+    }
+}
+
 // /// Sign an `UnsignedTransaction`.
 // pub fn sign_transaction(unsigned_tx: &UnsignedTransaction) -> Result<Transaction> {
 //     new_node_interface().sign_transaction(unsigned_tx)
@@ -126,19 +320,64 @@ pub fn submit_transaction(signed_tx: &Transaction) -> Result<TxId> {
     new_node_interface().submit_transaction(signed_tx)
 }
 
-/// Sign an `UnsignedTransaction` and then submit it to the mempool.
-pub fn sign_and_submit_transaction(unsigned_tx: &UnsignedTransaction) -> Result<TxId> {
+/// Sign an `UnsignedTransaction` and then submit it to the mempool. The tx and its fee are
+/// recorded in the store for `action`, the attempt in the audit log.
+pub fn sign_and_submit_transaction(
+    unsigned_tx: &UnsignedTransaction,
+    action: &str,
+) -> std::result::Result<TxId, SubmitTxError> {
+    sign_and_submit_transaction_with_outputs(unsigned_tx, action).map(|(tx_id, _)| tx_id)
+}
+
+/// Sign an `UnsignedTransaction` and then submit it to the mempool. Returns the signed tx as
+/// well, its outputs can be spent by a chained tx before it is confirmed.
+pub fn sign_and_submit_transaction_with_outputs(
+    unsigned_tx: &UnsignedTransaction,
+    action: &str,
+) -> std::result::Result<(TxId, Transaction), SubmitTxError> {
+    let submitted = sign_and_submit(unsigned_tx);
+    audit_log::record_tx(
+        action,
+        unsigned_tx,
+        submitted
+            .as_ref()
+            .map(|(_, signed_tx)| signed_tx)
+            .map_err(|e| e.to_string()),
+    );
+    let (tx_id, signed_tx) = submitted?;
+    record_submitted_tx(action, &signed_tx);
+    Ok((tx_id, signed_tx))
+}
+
+fn sign_and_submit(
+    unsigned_tx: &UnsignedTransaction,
+) -> std::result::Result<(TxId, Transaction), SubmitTxError> {
     let node = new_node_interface();
     log::trace!(
         "Signing transaction: {}",
         serde_json::to_string_pretty(&unsigned_tx).unwrap()
     );
-    let signed_tx = node.sign_transaction(unsigned_tx, None, None)?;
+    check_tx_min_box_values(unsigned_tx)?;
+    check_tx_before_submit(unsigned_tx)?;
+    let signed_tx = record_error(node.sign_transaction(unsigned_tx, None, None))?;
     log::trace!(
         "Submitting signed transaction: {}",
         serde_json::to_string_pretty(&signed_tx).unwrap()
     );
-    node.submit_transaction(&signed_tx)
+    let tx_id = record_error(node.submit_transaction(&signed_tx))?;
+    Ok((tx_id, signed_tx))
+}
+
+/// Unlocks the node wallet with `password` if it is locked
+pub fn unlock_wallet(node: &NodeInterface, password: &str) -> Result<()> {
+    if !node.wallet_status()?.unlocked {
+        node.send_post_req(
+            "/wallet/unlock",
+            serde_json::json!({ "pass": password }).to_string(),
+        )?;
+        info!("Node wallet unlocked");
+    }
+    Ok(())
 }
 
 pub fn assert_wallet_unlocked(node: &NodeInterface) {
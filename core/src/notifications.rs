@@ -0,0 +1,471 @@
+//! Notifications of the operator about failures of the daemon and events of the pool needing
+//! attention, sent to the `notifications` targets of the config (Telegram, Discord, Slack or a
+//! generic webhook). A background thread sends them so a slow target never delays the main loop.
+//! A notification of the same kind is sent at most once per `cooldown_minutes`. The alerts are
+//! recorded in the store, at most once per cooldown by kind, sent or not.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{self, Sender};
+use derive_more::From;
+use once_cell::sync::Lazy;
+use reqwest::blocking::Client;
+use reqwest::header::CONTENT_TYPE;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::cli_commands::ballot_status::ProposalStatus;
+use crate::oracle_config::{NotificationsConfig, ORACLE_CONFIG};
+use crate::store;
+
+/// Minimum time between two notifications of the same kind without `cooldown_minutes`
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60 * 60);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub static NOTIFICATIONS: Lazy<Notifications> = Lazy::new(|| match &ORACLE_CONFIG.notifications {
+    Some(config) => Notifications::new(config, ORACLE_CONFIG.oracle_address.to_base58()),
+    None => Notifications::disabled(),
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// The datapoint couldn't be fetched or its tx couldn't be submitted
+    PostingFailed,
+    /// The datapoint of the oracle wasn't collected in `missed_epochs_alert_threshold` epochs in
+    /// a row
+    EpochMissed,
+    /// The oracle box holds `reward_threshold` reward tokens
+    RewardThreshold,
+    NodeUnreachable,
+    /// Ballot boxes vote for a new update of the pool
+    UpdateProposal,
+    /// The wallet balance went below the `low_balance_alert` thresholds
+    LowBalance,
+    /// A peer oracle was flagged by the `peer_monitor`
+    PeerMisbehavior,
+    /// Implausible jump of the finalized pool rate
+    RateAnomaly,
+    /// The storage rent of the pool, refresh or update box is due soon
+    StorageRent,
+}
+
+impl fmt::Display for NotificationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let title = match self {
+            NotificationKind::PostingFailed => "Posting failed",
+            NotificationKind::EpochMissed => "Epoch missed",
+            NotificationKind::RewardThreshold => "Reward threshold reached",
+            NotificationKind::NodeUnreachable => "Node unreachable",
+            NotificationKind::UpdateProposal => "Update proposal",
+            NotificationKind::LowBalance => "Low wallet balance",
+            NotificationKind::PeerMisbehavior => "Peer oracle misbehaving",
+            NotificationKind::RateAnomaly => "Pool rate anomaly",
+            NotificationKind::StorageRent => "Storage rent due",
+        };
+        write!(f, "{}", title)
+    }
+}
+
+/// Where the notifications are sent
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationTarget {
+    /// Message of a bot to a chat
+    Telegram { bot_token: String, chat_id: String },
+    /// Webhook of a channel
+    Discord { webhook_url: String },
+    /// Incoming webhook of a channel
+    Slack { webhook_url: String },
+    /// The notification is POSTed as JSON
+    Webhook { url: String },
+}
+
+impl NotificationTarget {
+    fn notifier(&self, client: Client) -> Box<dyn Notifier> {
+        match self {
+            NotificationTarget::Telegram { bot_token, chat_id } => Box::new(TelegramNotifier {
+                client,
+                bot_token: bot_token.clone(),
+                chat_id: chat_id.clone(),
+            }),
+            NotificationTarget::Discord { webhook_url } => Box::new(DiscordNotifier {
+                client,
+                webhook_url: webhook_url.clone(),
+            }),
+            NotificationTarget::Slack { webhook_url } => Box::new(SlackNotifier {
+                client,
+                webhook_url: webhook_url.clone(),
+            }),
+            NotificationTarget::Webhook { url } => Box::new(WebhookNotifier {
+                client,
+                url: url.clone(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub oracle_address: String,
+    pub message: String,
+}
+
+impl Notification {
+    /// Text of the chat messages
+    pub fn text(&self) -> String {
+        format!(
+            "{} (oracle {})\n{}",
+            self.kind, self.oracle_address, self.message
+        )
+    }
+}
+
+#[derive(Debug, From, Error)]
+pub enum NotifierError {
+    #[error("HTTP request failed: {0}")]
+    Reqwest(reqwest::Error),
+    #[error("JSON error: {0}")]
+    Json(serde_json::Error),
+}
+
+/// A backend delivering the notifications
+pub trait Notifier: Send {
+    fn name(&self) -> &'static str;
+    fn send(&self, notification: &Notification) -> Result<(), NotifierError>;
+}
+
+fn post_json(client: &Client, url: &str, body: serde_json::Value) -> Result<(), NotifierError> {
+    client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+pub struct TelegramNotifier {
+    client: Client,
+    bot_token: String,
+    chat_id: String,
+}
+
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<(), NotifierError> {
+        post_json(
+            &self.client,
+            &format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token),
+            json!({ "chat_id": self.chat_id, "text": notification.text() }),
+        )
+    }
+}
+
+pub struct DiscordNotifier {
+    client: Client,
+    webhook_url: String,
+}
+
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<(), NotifierError> {
+        post_json(
+            &self.client,
+            &self.webhook_url,
+            json!({ "content": notification.text() }),
+        )
+    }
+}
+
+pub struct SlackNotifier {
+    client: Client,
+    webhook_url: String,
+}
+
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<(), NotifierError> {
+        post_json(
+            &self.client,
+            &self.webhook_url,
+            json!({ "text": notification.text() }),
+        )
+    }
+}
+
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+}
+
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn send(&self, notification: &Notification) -> Result<(), NotifierError> {
+        post_json(&self.client, &self.url, serde_json::to_value(notification)?)
+    }
+}
+
+/// Filters the notifications by kind and cooldown and queues them for the sending thread
+pub struct Notifications {
+    sender: Option<Sender<Notification>>,
+    oracle_address: String,
+    /// Kinds notified, all of them if `None`
+    kinds: Option<HashSet<NotificationKind>>,
+    cooldown: Duration,
+    last_sent: Mutex<HashMap<NotificationKind, Instant>>,
+    last_recorded: Mutex<HashMap<NotificationKind, Instant>>,
+}
+
+impl Notifications {
+    /// Starts the sending thread if `config` has targets
+    pub fn new(config: &NotificationsConfig, oracle_address: String) -> Self {
+        let sender = if config.targets.is_empty() {
+            None
+        } else {
+            let client = Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_else(|_| Client::new());
+            let notifiers = config
+                .targets
+                .iter()
+                .map(|target| target.notifier(client.clone()))
+                .collect();
+            Some(spawn_sending_thread(notifiers))
+        };
+        Notifications {
+            sender,
+            oracle_address,
+            kinds: config
+                .events
+                .as_ref()
+                .map(|events| events.iter().copied().collect()),
+            cooldown: config
+                .cooldown_minutes
+                .map_or(DEFAULT_COOLDOWN, |m| Duration::from_secs(m * 60)),
+            last_sent: Mutex::new(HashMap::new()),
+            last_recorded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Notifications {
+            sender: None,
+            oracle_address: String::new(),
+            kinds: Some(HashSet::new()),
+            cooldown: DEFAULT_COOLDOWN,
+            last_sent: Mutex::new(HashMap::new()),
+            last_recorded: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Queue a notification, dropped if `kind` isn't enabled or was notified less than the
+    /// cooldown ago
+    pub fn notify(&self, kind: NotificationKind, message: String) {
+        let now = Instant::now();
+        let sender = self.sender.as_ref().filter(|_| self.should_send(kind, now));
+        if cooled_down(&self.last_recorded, kind, self.cooldown, now) {
+            store::record_alert(kind, &message, sender.is_some());
+        }
+        let sender = match sender {
+            Some(sender) => sender,
+            None => {
+                log::debug!("{} notification not sent: {}", kind, message);
+                return;
+            }
+        };
+        let _ = sender.send(Notification {
+            kind,
+            oracle_address: self.oracle_address.clone(),
+            message,
+        });
+    }
+
+    fn should_send(&self, kind: NotificationKind, now: Instant) -> bool {
+        if !self
+            .kinds
+            .as_ref()
+            .map_or(true, |kinds| kinds.contains(&kind))
+        {
+            return false;
+        }
+        cooled_down(&self.last_sent, kind, self.cooldown, now)
+    }
+}
+
+/// Whether `kind` was last seen at least `cooldown` before `now`, `now` is recorded if so
+fn cooled_down(
+    last_seen: &Mutex<HashMap<NotificationKind, Instant>>,
+    kind: NotificationKind,
+    cooldown: Duration,
+    now: Instant,
+) -> bool {
+    let mut last_seen = last_seen.lock().unwrap();
+    match last_seen.get(&kind) {
+        Some(seen) if now.saturating_duration_since(*seen) < cooldown => false,
+        Some(_) | None => {
+            last_seen.insert(kind, now);
+            true
+        }
+    }
+}
+
+fn spawn_sending_thread(notifiers: Vec<Box<dyn Notifier>>) -> Sender<Notification> {
+    let (sender, receiver) = channel::unbounded::<Notification>();
+    std::thread::spawn(move || {
+        for notification in receiver {
+            for notifier in &notifiers {
+                if let Err(e) = notifier.send(&notification) {
+                    log::warn!(
+                        "Failed to send the {} notification to {}: {}",
+                        notification.kind,
+                        notifier.name(),
+                        e
+                    );
+                }
+            }
+        }
+    });
+    sender
+}
+
+/// Notifications derived from the state of the pool, checked by the main loop after every
+/// iteration. Each returns the message when its condition starts to hold.
+pub struct NotificationTriggers {
+    reward_threshold: Option<u64>,
+    reward_notified: bool,
+    seen_proposals: HashSet<String>,
+}
+
+impl NotificationTriggers {
+    pub fn new(reward_threshold: Option<u64>) -> Self {
+        NotificationTriggers {
+            reward_threshold,
+            reward_notified: false,
+            seen_proposals: HashSet::new(),
+        }
+    }
+
+    /// Notified again once the reward tokens went below the threshold (extracted) and reached it
+    /// again
+    pub fn reward_threshold_reached(&mut self, reward_tokens: u64) -> Option<String> {
+        let threshold = self.reward_threshold?;
+        let reached = reward_tokens >= threshold;
+        let notify = reached && !self.reward_notified;
+        self.reward_notified = reached;
+        notify.then(|| {
+            format!(
+                "The oracle box holds {} reward tokens (threshold {}), they can be extracted",
+                reward_tokens, threshold
+            )
+        })
+    }
+
+    /// The proposals voted for the current update box not seen before
+    pub fn new_update_proposals(&mut self, proposals: &[ProposalStatus]) -> Vec<String> {
+        proposals
+            .iter()
+            .filter(|p| !p.stale)
+            .filter(|p| {
+                self.seen_proposals.insert(format!(
+                    "{}:{}:{}:{}",
+                    p.pool_box_address_hash,
+                    p.reward_token_id,
+                    p.reward_token_quantity,
+                    p.update_box_creation_height
+                ))
+            })
+            .map(|p| {
+                format!(
+                    "Update proposal with {} votes{}: pool box address hash {}, reward token {} (quantity {}). Check it with the ballot-status command.",
+                    p.votes,
+                    if p.quorum_reached { " (quorum reached)" } else { "" },
+                    p.pool_box_address_hash,
+                    p.reward_token_id,
+                    p.reward_token_quantity
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_triggers() {
+        let mut triggers = NotificationTriggers::new(Some(100));
+        assert_eq!(triggers.reward_threshold_reached(50), None);
+        assert!(triggers.reward_threshold_reached(100).is_some());
+        assert_eq!(triggers.reward_threshold_reached(120), None);
+        assert_eq!(triggers.reward_threshold_reached(1), None);
+        assert!(triggers.reward_threshold_reached(101).is_some());
+
+        let proposal = |hash: &str, stale: bool| ProposalStatus {
+            pool_box_address_hash: hash.to_string(),
+            reward_token_id: "token".to_string(),
+            reward_token_quantity: 1000,
+            update_box_creation_height: 10,
+            votes: 2,
+            stale,
+            quorum_reached: false,
+        };
+        assert_eq!(
+            triggers
+                .new_update_proposals(&[proposal("a", false), proposal("b", true)])
+                .len(),
+            1
+        );
+        assert!(triggers
+            .new_update_proposals(&[proposal("a", false)])
+            .is_empty());
+    }
+
+    #[test]
+    fn test_notification_filter() {
+        let config = NotificationsConfig {
+            targets: vec![],
+            events: Some(vec![NotificationKind::PostingFailed]),
+            reward_threshold: None,
+            cooldown_minutes: Some(10),
+        };
+        let notifications = Notifications::new(&config, "address".to_string());
+        let now = Instant::now();
+        assert!(!notifications.should_send(NotificationKind::NodeUnreachable, now));
+        assert!(notifications.should_send(NotificationKind::PostingFailed, now));
+        assert!(!notifications.should_send(
+            NotificationKind::PostingFailed,
+            now + Duration::from_secs(60)
+        ));
+        assert!(notifications.should_send(
+            NotificationKind::PostingFailed,
+            now + Duration::from_secs(11 * 60)
+        ));
+
+        let target: NotificationTarget =
+            serde_yaml::from_str("type: telegram\nbot_token: token\nchat_id: '42'").unwrap();
+        assert!(matches!(target, NotificationTarget::Telegram { .. }));
+    }
+}
@@ -1,4 +1,6 @@
 use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 
 use crate::{
     box_kind::{
@@ -6,11 +8,17 @@ use crate::{
         RefreshBoxWrapperInputs, UpdateBoxWrapperInputs,
     },
     cli_commands::bootstrap::BootstrapConfig,
+    config_format::ConfigFormat,
     contracts::{
         ballot::BallotContractError, oracle::OracleContractError, pool::PoolContractError,
         refresh::RefreshContractError, update::UpdateContractError,
     },
     datapoint_source::{DataPointSource, ExternalScript, PredefinedDataPointSource},
+    logging::LogFormat,
+    notifications::{NotificationKind, NotificationTarget},
+    pool_config::merge_pool_config,
+    pool_controller::EpochStrategyKind,
+    secrets::resolve_secrets,
 };
 use anyhow::anyhow;
 use derive_more::From;
@@ -22,11 +30,16 @@ use ergo_lib::{
 };
 use log::LevelFilter;
 use once_cell::sync;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
 use thiserror::Error;
 
 pub const DEFAULT_CONFIG_FILE_NAME: &str = "oracle_config.yaml";
 
+/// Prefix of the environment variables overriding the fields of the config file
+pub const ENV_OVERRIDE_PREFIX: &str = "ORACLE_CORE__";
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(
     try_from = "crate::serde::OracleConfigSerde",
@@ -36,10 +49,33 @@ pub struct OracleConfig {
     pub node_ip: String,
     pub node_port: u16,
     pub node_api_key: String,
+    /// Password of the node wallet, unlocked by the daemon at start if locked
+    pub node_wallet_password: Option<String>,
+    /// Fee of the txs in nanoERGs, `SUGGESTED_TX_FEE` if not set
     pub base_fee: u64,
+    /// Fees of the txs of the actions replacing `base_fee`
+    pub tx_fees: Option<TxFeesConfig>,
+    /// Fee of the txs estimated from their size, replacing `base_fee` (not the `tx_fees`)
+    pub fee_estimation: Option<FeeEstimationConfig>,
     pub log_level: Option<LevelFilter>,
+    /// Additional `tracing` filter directives per module, e.g. `oracle_core::scans=debug,reqwest=warn`
+    pub log_filter: Option<String>,
+    /// Format of the log lines (`text` or `json`), `text` if not set
+    pub log_format: Option<LogFormat>,
     pub core_api_port: u16,
+    /// Address the REST API binds to, all interfaces if not set
+    pub core_api_bind_address: Option<IpAddr>,
+    /// File with the bearer token of the `/admin` routes of the REST API, the admin routes are
+    /// disabled if not set
+    pub core_api_admin_token_file: Option<PathBuf>,
+    /// Custom datapoint scripts the `/admin/dataSource` route may switch to, none if not set
+    pub core_api_admin_allowed_scripts: Option<Vec<String>>,
+    /// Origins allowed to read the REST API from a browser (CORS), any origin if not set and none
+    /// if empty
+    pub core_api_cors_allowed_origins: Option<Vec<String>>,
     pub oracle_address: NetworkAddress,
+    /// Address receiving the change of the txs, the change address of the node wallet if not set
+    pub change_address: Option<NetworkAddress>,
     pub data_point_source: Option<PredefinedDataPointSource>,
     pub data_point_source_custom_script: Option<String>,
     pub oracle_box_wrapper_inputs: OracleBoxWrapperInputs,
@@ -49,6 +85,250 @@ pub struct OracleConfig {
     pub ballot_box_wrapper_inputs: BallotBoxWrapperInputs,
     pub token_ids: TokenIds,
     pub rescan_height: u32,
+    pub reward_extraction: Option<RewardExtractionConfig>,
+    /// Buyback NFT of pools routing part of the refresh reward through a buyback box. The reward
+    /// tokens of the buyback box are moved to the pool box on refresh.
+    pub buyback_token_id: Option<TokenId>,
+    /// When the daemon publishes datapoints and refreshes the pool box, `standard` if not set
+    pub epoch_strategy: Option<EpochStrategyKind>,
+    pub publish_timing: Option<PublishTimingConfig>,
+    pub skip_publish: Option<SkipPublishConfig>,
+    pub collector_election: Option<CollectorElectionConfig>,
+    /// Build txs on top of the unconfirmed txs submitted by the daemon instead of waiting for
+    /// their confirmation
+    pub chain_transactions: bool,
+    /// gRPC service (needs the `grpc` feature)
+    pub grpc: Option<GrpcConfig>,
+    /// Number of finished epochs served on `/epochStats`, 100 if not set
+    pub epoch_stats_window: Option<u32>,
+    pub notifications: Option<NotificationsConfig>,
+    /// Epochs missed in a row before the operator is alerted, 3 if not set
+    pub missed_epochs_alert_threshold: Option<u32>,
+    pub low_balance_alert: Option<LowBalanceAlertConfig>,
+    /// Detection of the misbehaving peer oracles, disabled if not set
+    pub peer_monitor: Option<PeerMonitorConfig>,
+    /// Thresholds of the pool rate anomaly detection, the defaults if not set
+    pub rate_anomaly: Option<RateAnomalyConfig>,
+    /// Renewal of the protocol boxes before their storage rent is due, the defaults if not set
+    pub storage_rent: Option<StorageRentConfig>,
+}
+
+/// Settings for the automatic extraction of reward tokens from the oracle box by the daemon.
+/// Extraction is triggered when either of `min_reward_tokens` or `every_n_epochs` is reached.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(
+    try_from = "crate::serde::RewardExtractionConfigSerde",
+    into = "crate::serde::RewardExtractionConfigSerde"
+)]
+pub struct RewardExtractionConfig {
+    /// Address to send the extracted reward tokens to
+    pub payout_address: NetworkAddress,
+    /// Extract once at least this many reward tokens can be extracted
+    pub min_reward_tokens: Option<u64>,
+    /// Extract every N epochs of the pool
+    pub every_n_epochs: Option<u32>,
+    /// Maximum number of reward tokens extracted within 24 hours
+    pub max_tokens_per_day: Option<u64>,
+    /// Only log the extraction that would have been made without submitting the tx
+    pub dry_run: bool,
+}
+
+/// When the daemon publishes its datapoint after a collection, counted in blocks after the pool
+/// box. A random jitter (picked per epoch) keeps the oracles from publishing in the same block.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct PublishTimingConfig {
+    /// Replaces the delay of the epoch strategy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset_blocks: Option<u32>,
+    /// Up to this many blocks are added to the delay
+    #[serde(default)]
+    pub jitter_blocks: u32,
+}
+
+/// Skipping the publication after a collection when the datapoint barely changed, to save tx fees
+/// in flat markets.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct SkipPublishConfig {
+    /// Skip if the datapoint differs from the pool rate by less than this percentage
+    pub min_change_percent: f64,
+    /// Publish anyway after this many skipped epochs in a row
+    pub max_skipped_epochs: u32,
+}
+
+/// Deterministic election of the oracle attempting the refresh of each epoch. The oracles of the
+/// pool must enable it with the same `timeout_blocks`.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct CollectorElectionConfig {
+    /// Blocks after the end of the epoch after which the next ranked oracle is elected
+    pub timeout_blocks: u32,
+}
+
+/// Settings of the gRPC service. The clients authenticate with the `authorization: Bearer <token>`
+/// metadata, the token is read from `auth_token_file` to keep it out of the config.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct GrpcConfig {
+    pub bind_address: SocketAddr,
+    pub auth_token_file: PathBuf,
+    /// PEM certificate and private key, the service is plaintext without them (e.g. behind a TLS
+    /// terminating proxy)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_cert_file: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_key_file: Option<PathBuf>,
+}
+
+/// Notifications of the operator, see [`crate::notifications`]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct NotificationsConfig {
+    pub targets: Vec<NotificationTarget>,
+    /// Kinds of notifications sent, all of them if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<NotificationKind>>,
+    /// Notify when the oracle box holds at least this many reward tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reward_threshold: Option<u64>,
+    /// Minimum minutes between two notifications of the same kind, 60 if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooldown_minutes: Option<u64>,
+}
+
+/// Thresholds of the low wallet balance alert, the operator is alerted when the balance goes
+/// below either of them
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct LowBalanceAlertConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_nanoergs: Option<u64>,
+    /// Epochs the balance still covers at the measured fee burn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_epochs: Option<u64>,
+}
+
+/// Flagging of the peer oracles deviating from the data source of this oracle or not posting, see
+/// [`crate::peer_monitor`]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct PeerMonitorConfig {
+    /// A datapoint deviates if it differs from the data source by more than this percentage
+    pub max_deviation_percent: f64,
+    /// Deviating datapoints or stale epochs in a row before an oracle is flagged
+    pub flag_after: u32,
+}
+
+/// Actions with a fee setting in `tx_fees`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxFeeAction {
+    PublishDatapoint,
+    Refresh,
+    ExtractRewardTokens,
+    TransferOracleToken,
+    VoteUpdatePool,
+    UpdatePool,
+    ConsolidateUtxos,
+    ExitPool,
+    Repair,
+    MigratePool,
+    PrepareUpdate,
+}
+
+impl TxFeeAction {
+    pub const ALL: [TxFeeAction; 11] = [
+        TxFeeAction::PublishDatapoint,
+        TxFeeAction::Refresh,
+        TxFeeAction::ExtractRewardTokens,
+        TxFeeAction::TransferOracleToken,
+        TxFeeAction::VoteUpdatePool,
+        TxFeeAction::UpdatePool,
+        TxFeeAction::ConsolidateUtxos,
+        TxFeeAction::ExitPool,
+        TxFeeAction::Repair,
+        TxFeeAction::MigratePool,
+        TxFeeAction::PrepareUpdate,
+    ];
+}
+
+/// Fees in nanoERGs of the txs of the actions, e.g. to raise the fee of the refresh during
+/// congestion. The actions not set pay `base_fee`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+pub struct TxFeesConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish_datapoint: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extract_reward_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_oracle_token: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vote_update_pool: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_pool: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consolidate_utxos: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_pool: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repair: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub migrate_pool: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prepare_update: Option<u64>,
+}
+
+impl TxFeesConfig {
+    pub fn fee(&self, action: TxFeeAction) -> Option<u64> {
+        match action {
+            TxFeeAction::PublishDatapoint => self.publish_datapoint,
+            TxFeeAction::Refresh => self.refresh,
+            TxFeeAction::ExtractRewardTokens => self.extract_reward_tokens,
+            TxFeeAction::TransferOracleToken => self.transfer_oracle_token,
+            TxFeeAction::VoteUpdatePool => self.vote_update_pool,
+            TxFeeAction::UpdatePool => self.update_pool,
+            TxFeeAction::ConsolidateUtxos => self.consolidate_utxos,
+            TxFeeAction::ExitPool => self.exit_pool,
+            TxFeeAction::Repair => self.repair,
+            TxFeeAction::MigratePool => self.migrate_pool,
+            TxFeeAction::PrepareUpdate => self.prepare_update,
+        }
+    }
+}
+
+/// Renewal of the pool, refresh and update boxes before the storage rent can be collected, see
+/// [`crate::storage_rent`]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct StorageRentConfig {
+    /// Blocks before the storage rent is due from which the operator is alerted, 64800 (90 days)
+    /// if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renewal_window_blocks: Option<u32>,
+    /// Submit the renewal of the update box by the daemon once the ballots voted for it
+    #[serde(default)]
+    pub auto_renew: bool,
+}
+
+/// Fee of the txs computed from their size, see [`crate::fee_estimation`]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct FeeEstimationConfig {
+    /// Fee per byte of the signed tx in nanoERGs
+    pub nanoergs_per_byte: u64,
+    /// Lowest fee in nanoERGs, the minimal fee of the node mempool (1000000) if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_fee: Option<u64>,
+    /// Highest fee in nanoERGs, no limit if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_fee: Option<u64>,
+}
+
+/// Thresholds of the anomaly detection on the finalized pool rates, see [`crate::rate_anomaly`]
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct RateAnomalyConfig {
+    /// Change from the previous rate, 10% if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_change_percent: Option<f64>,
+    /// z-score against the last `window` rates, 4 if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_z_score: Option<f64>,
+    /// Rates of the z-score, 30 if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<u32>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -60,37 +340,43 @@ pub struct CastBallotBoxVoteParameters {
 }
 
 /// Holds the token ids of every important token used by the oracle pool.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct TokenIds {
     #[serde(
         serialize_with = "crate::serde::token_id_as_base64_string",
         deserialize_with = "crate::serde::token_id_from_base64"
     )]
+    #[schemars(with = "String")]
     pub pool_nft_token_id: TokenId,
     #[serde(
         serialize_with = "crate::serde::token_id_as_base64_string",
         deserialize_with = "crate::serde::token_id_from_base64"
     )]
+    #[schemars(with = "String")]
     pub refresh_nft_token_id: TokenId,
     #[serde(
         serialize_with = "crate::serde::token_id_as_base64_string",
         deserialize_with = "crate::serde::token_id_from_base64"
     )]
+    #[schemars(with = "String")]
     pub update_nft_token_id: TokenId,
     #[serde(
         serialize_with = "crate::serde::token_id_as_base64_string",
         deserialize_with = "crate::serde::token_id_from_base64"
     )]
+    #[schemars(with = "String")]
     pub oracle_token_id: TokenId,
     #[serde(
         serialize_with = "crate::serde::token_id_as_base64_string",
         deserialize_with = "crate::serde::token_id_from_base64"
     )]
+    #[schemars(with = "String")]
     pub reward_token_id: TokenId,
     #[serde(
         serialize_with = "crate::serde::token_id_as_base64_string",
         deserialize_with = "crate::serde::token_id_from_base64"
     )]
+    #[schemars(with = "String")]
     pub ballot_token_id: TokenId,
 }
 
@@ -134,10 +420,20 @@ impl OracleConfig {
             node_ip: bootstrap.node_ip,
             node_port: bootstrap.node_port,
             node_api_key: bootstrap.node_api_key,
+            node_wallet_password: None,
             base_fee: bootstrap.base_fee,
+            tx_fees: None,
+            fee_estimation: None,
             log_level: None,
+            log_filter: None,
+            log_format: None,
             core_api_port: bootstrap.core_api_port,
+            core_api_bind_address: None,
+            core_api_admin_token_file: None,
+            core_api_admin_allowed_scripts: None,
+            core_api_cors_allowed_origins: None,
             oracle_address: bootstrap.oracle_address,
+            change_address: None,
             data_point_source: bootstrap.data_point_source,
             data_point_source_custom_script: bootstrap.data_point_source_custom_script,
             oracle_box_wrapper_inputs,
@@ -147,6 +443,21 @@ impl OracleConfig {
             update_box_wrapper_inputs,
             token_ids,
             rescan_height,
+            reward_extraction: None,
+            buyback_token_id: None,
+            epoch_strategy: None,
+            publish_timing: None,
+            skip_publish: None,
+            collector_election: None,
+            chain_transactions: false,
+            grpc: None,
+            epoch_stats_window: None,
+            notifications: None,
+            missed_epochs_alert_threshold: None,
+            low_balance_alert: None,
+            peer_monitor: None,
+            rate_anomaly: None,
+            storage_rent: None,
         })
     }
 
@@ -157,8 +468,23 @@ impl OracleConfig {
         Self::load_from_str(&std::fs::read_to_string(config_file_path)?)
     }
 
-    fn load_from_str(config_str: &str) -> Result<OracleConfig, anyhow::Error> {
-        serde_yaml::from_str(config_str).map_err(|e| anyhow!(e))
+    /// Parse the config with its pool definition (see `pool_config`) and the
+    /// `ORACLE_CORE__*` environment variables merged over it, its secrets resolved (see `secrets`)
+    pub(crate) fn load_from_str(config_str: &str) -> Result<OracleConfig, anyhow::Error> {
+        let config_path = CONFIG_FILE_PATH
+            .get()
+            .map_or(DEFAULT_CONFIG_FILE_NAME, String::as_str);
+        let mut config = merged_config_value(config_str, Path::new(config_path))?;
+        resolve_secrets(&mut config)?;
+        serde_yaml::from_value(config).map_err(|e| anyhow!(e))
+    }
+
+    /// Fee of the txs of the `action` in nanoERGs
+    pub fn tx_fee(&self, action: TxFeeAction) -> u64 {
+        self.tx_fees
+            .as_ref()
+            .and_then(|fees| fees.fee(action))
+            .unwrap_or(self.base_fee)
     }
 
     pub fn data_point_source(
@@ -180,6 +506,67 @@ impl OracleConfig {
     }
 }
 
+/// The config at `config_path` (YAML or TOML) with its pool definition and the `ORACLE_CORE__*`
+/// environment variables merged over it, as the config is loaded
+pub(crate) fn merged_config_value(
+    config_str: &str,
+    config_path: &Path,
+) -> Result<Value, anyhow::Error> {
+    let mut config = ConfigFormat::of_path(config_path).parse(config_str)?;
+    merge_pool_config(&mut config, config_path)?;
+    apply_env_overrides(&mut config, std::env::vars())?;
+    Ok(config)
+}
+
+/// Set the config fields of the `ORACLE_CORE__<FIELD>` variables of `vars`, the sections of a
+/// nested field separated by `__` (e.g. `ORACLE_CORE__REWARD_EXTRACTION__MIN_REWARD_TOKENS`). The
+/// value is parsed as YAML, except for a field holding a string in the config. Returns the
+/// overridden fields.
+fn apply_env_overrides(
+    config: &mut Value,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut overridden = Vec::new();
+    for (name, raw_value) in vars {
+        let path = match name.strip_prefix(ENV_OVERRIDE_PREFIX) {
+            Some(path) => path.to_lowercase(),
+            None => continue,
+        };
+        let keys: Vec<&str> = path.split("__").collect();
+        if keys.iter().any(|key| key.is_empty()) {
+            return Err(anyhow!("{}: empty config field name", name));
+        }
+        let mut section = &mut *config;
+        for key in &keys[..keys.len() - 1] {
+            let mapping = as_section(section)
+                .ok_or_else(|| anyhow!("{}: {} is not a config section", name, key))?;
+            let key = Value::String(key.to_string());
+            if !mapping.contains_key(&key) {
+                mapping.insert(key.clone(), Value::Null);
+            }
+            section = mapping.get_mut(&key).unwrap();
+        }
+        let mapping =
+            as_section(section).ok_or_else(|| anyhow!("{}: not a config section", name))?;
+        let key = Value::String(keys[keys.len() - 1].to_string());
+        let value = match mapping.get(&key) {
+            Some(Value::String(_)) => Value::String(raw_value),
+            _ => serde_yaml::from_str(&raw_value).unwrap_or(Value::String(raw_value)),
+        };
+        mapping.insert(key, value);
+        overridden.push(keys.join("."));
+    }
+    Ok(overridden)
+}
+
+/// The mapping of a config section, created if the section is not set
+fn as_section(value: &mut Value) -> Option<&mut Mapping> {
+    if value.is_null() {
+        *value = Value::Mapping(Mapping::new());
+    }
+    value.as_mapping_mut()
+}
+
 #[derive(Debug, From, Error)]
 pub enum OracleConfigError {
     #[error("Oracle contract error: {0}")]
@@ -205,9 +592,23 @@ lazy_static! {
         .unwrap_or_else(|_| SUGGESTED_TX_FEE());
 }
 
-/// Returns "core_api_port" from the config file
-pub fn get_core_api_port() -> String {
-    ORACLE_CONFIG.core_api_port.to_string()
+/// Fee of the txs of the `action` set in the config, `SUGGESTED_TX_FEE` without a config
+pub fn tx_fee(action: TxFeeAction) -> BoxValue {
+    MAYBE_ORACLE_CONFIG
+        .as_ref()
+        .map(|c| BoxValue::try_from(c.tx_fee(action)).unwrap())
+        .unwrap_or_else(|_| SUGGESTED_TX_FEE())
+}
+
+/// Returns the address of the REST API from "core_api_bind_address" (all interfaces if not set) and
+/// "core_api_port" of the config file
+pub fn get_core_api_address() -> SocketAddr {
+    SocketAddr::new(
+        ORACLE_CONFIG
+            .core_api_bind_address
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        ORACLE_CONFIG.core_api_port,
+    )
 }
 
 pub fn get_node_ip() -> String {
@@ -227,6 +628,7 @@ pub fn get_node_api_key() -> String {
 mod tests {
 
     use crate::pool_commands::test_utils::generate_token_ids;
+    use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
 
     use super::*;
 
@@ -236,4 +638,130 @@ mod tests {
         let s = serde_yaml::to_string(&token_ids).unwrap();
         assert_eq!(token_ids, serde_yaml::from_str::<TokenIds>(&s).unwrap());
     }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut config: Value = serde_yaml::from_str(
+            "node_api_key: hello\nnode_port: 9053\nreward_extraction:\n  dry_run: true\n",
+        )
+        .unwrap();
+        let vars = vec![
+            ("ORACLE_CORE__NODE_API_KEY", "12345"),
+            ("ORACLE_CORE__NODE_PORT", "9052"),
+            ("ORACLE_CORE__REWARD_EXTRACTION__MIN_REWARD_TOKENS", "100"),
+            ("ORACLE_CORE__LOW_BALANCE_ALERT__MIN_EPOCHS", "50"),
+            ("ORACLE_CORE_ASSUME_YES", "true"),
+            ("HOME", "/root"),
+        ];
+        let overridden = apply_env_overrides(
+            &mut config,
+            vars.into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string())),
+        )
+        .unwrap();
+        assert_eq!(
+            overridden,
+            vec![
+                "node_api_key",
+                "node_port",
+                "reward_extraction.min_reward_tokens",
+                "low_balance_alert.min_epochs"
+            ]
+        );
+        assert_eq!(
+            config,
+            serde_yaml::from_str::<Value>(
+                "node_api_key: '12345'\nnode_port: 9052\nreward_extraction:\n  dry_run: true\n  \
+                min_reward_tokens: 100\nlow_balance_alert:\n  min_epochs: 50\n"
+            )
+            .unwrap()
+        );
+        assert!(apply_env_overrides(
+            &mut config,
+            vec![("ORACLE_CORE__NODE_PORT__X".to_string(), "1".to_string())]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_tx_fees() {
+        let config =
+            OracleConfig::create(BootstrapConfig::default(), generate_token_ids(), 0).unwrap();
+        let mut value = serde_yaml::to_value(&config).unwrap();
+        let mapping = value.as_mapping_mut().unwrap();
+        mapping.remove(&Value::from("base_fee"));
+        mapping.insert(
+            Value::from("tx_fees"),
+            serde_yaml::from_str("refresh: 5000000").unwrap(),
+        );
+        let config: OracleConfig = serde_yaml::from_value(value.clone()).unwrap();
+        assert_eq!(config.base_fee, *SUGGESTED_TX_FEE().as_u64());
+        assert_eq!(config.tx_fee(TxFeeAction::Refresh), 5000000);
+        assert_eq!(
+            config.tx_fee(TxFeeAction::PublishDatapoint),
+            config.base_fee
+        );
+
+        value["tx_fees"]["refresh"] = Value::from(0);
+        assert!(serde_yaml::from_value::<OracleConfig>(value).is_err());
+    }
+
+    #[test]
+    fn test_refresh_contract_values_only() {
+        let config =
+            OracleConfig::create(BootstrapConfig::default(), generate_token_ids(), 0).unwrap();
+        let mut value = serde_yaml::to_value(&config).unwrap();
+        let refresh = value["refresh_contract_parameters"]
+            .as_mapping_mut()
+            .unwrap();
+        // Only the values, the default EIP-23 contract is built with them
+        for key in [
+            "ergo_tree_bytes",
+            "pool_nft_index",
+            "oracle_token_id_index",
+            "min_data_points_index",
+            "buffer_length_index",
+            "max_deviation_percent_index",
+            "epoch_length_index",
+        ] {
+            refresh.remove(&Value::from(key));
+        }
+        refresh.insert(Value::from("epoch_length"), Value::from(45));
+        let config: OracleConfig = serde_yaml::from_value(value).unwrap();
+        assert_eq!(
+            config
+                .refresh_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .epoch_length(),
+            45
+        );
+    }
+
+    #[test]
+    fn test_change_address() {
+        let config =
+            OracleConfig::create(BootstrapConfig::default(), generate_token_ids(), 0).unwrap();
+        let network = config.oracle_address.network();
+        let other_network = match network {
+            NetworkPrefix::Mainnet => NetworkPrefix::Testnet,
+            NetworkPrefix::Testnet => NetworkPrefix::Mainnet,
+        };
+        let address = config.oracle_address.address();
+        let mut value = serde_yaml::to_value(&config).unwrap();
+        let mapping = value.as_mapping_mut().unwrap();
+        mapping.insert(
+            Value::from("change_address"),
+            Value::from(NetworkAddress::new(network, &address).to_base58()),
+        );
+        let with_change_address: OracleConfig = serde_yaml::from_value(value.clone()).unwrap();
+        assert_eq!(
+            with_change_address.change_address.unwrap().to_base58(),
+            config.oracle_address.to_base58()
+        );
+
+        value["change_address"] =
+            Value::from(NetworkAddress::new(other_network, &address).to_base58());
+        assert!(serde_yaml::from_value::<OracleConfig>(value).is_err());
+    }
 }
@@ -1,29 +1,40 @@
 use crate::box_kind::{
-    BallotBoxError, BallotBoxWrapper, BallotBoxWrapperInputs, OracleBox, OracleBoxError,
-    OracleBoxWrapper, OracleBoxWrapperInputs, PoolBox, PoolBoxError, PoolBoxWrapper,
-    PoolBoxWrapperInputs, PostedOracleBox, RefreshBoxError, RefreshBoxWrapper,
+    BallotBoxError, BallotBoxWrapper, BallotBoxWrapperInputs, BuybackBoxError, BuybackBoxWrapper,
+    OracleBox, OracleBoxError, OracleBoxWrapper, OracleBoxWrapperInputs, PoolBox, PoolBoxError,
+    PoolBoxWrapper, PoolBoxWrapperInputs, PostedOracleBox, RefreshBoxError, RefreshBoxWrapper,
     RefreshBoxWrapperInputs, UpdateBoxError, UpdateBoxWrapper, UpdateBoxWrapperInputs,
     VoteBallotBoxWrapper,
 };
 use crate::contracts::ballot::BallotContract;
 use crate::contracts::oracle::OracleContract;
 use crate::datapoint_source::{DataPointSource, DataPointSourceError};
-use crate::node_interface::{current_block_height, get_wallet_status, rescan_from_height};
+use crate::node_interface::{
+    current_block_height, get_unconfirmed_transactions, get_wallet_status, rescan_from_height,
+};
 use crate::oracle_config::ORACLE_CONFIG;
+use crate::pending_outputs::PENDING_OUTPUTS;
 use crate::scans::{
-    register_ballot_box_scan, register_datapoint_scan, register_local_ballot_box_scan,
-    register_local_oracle_datapoint_scan, register_pool_box_scan, register_refresh_box_scan,
-    register_update_box_scan, save_scan_ids_locally, Scan, ScanError,
+    ballot_box_tracking_rule, buyback_box_tracking_rule, datapoint_tracking_rule,
+    load_legacy_scan_ids, local_ballot_box_tracking_rule, local_oracle_datapoint_tracking_rule,
+    pool_box_tracking_rule, refresh_box_tracking_rule, sync_scans, update_box_tracking_rule,
+    NodeScanRegistry, Scan, ScanError, ScanKind, ScanSpec, ScanState, LEGACY_SCAN_IDS_FILE,
+    SCAN_STATE_FILE,
 };
 use crate::state::PoolState;
 use anyhow::Error;
 use derive_more::From;
+use serde::{Deserialize, Serialize};
 
-use ergo_lib::ergo_chain_types::blake2b256_hash;
+use ergo_lib::chain::transaction::Transaction;
+use ergo_lib::ergo_chain_types::EcPoint;
 use ergo_lib::ergotree_ir::chain::address::Address;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
 use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisterId;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
 use ergo_lib::ergotree_ir::mir::constant::TryExtractFromError;
-use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use ergo_lib::ergotree_ir::mir::constant::TryExtractInto;
+use ergo_node_interface::node_interface::NodeError;
 use std::path::Path;
 use thiserror::Error;
 
@@ -53,17 +64,52 @@ pub enum StageError {
     UpdateBoxError(UpdateBoxError),
     #[error("update box not found")]
     UpdateBoxNotFoundError,
+    #[error("buyback box error: {0}")]
+    BuybackBoxError(BuybackBoxError),
+    #[error("node error: {0}")]
+    NodeError(#[source] NodeError),
+    #[error(
+        "{} boxes hold the local oracle token: {:?}. Run `oracle-core repair` to consolidate them",
+        .0.len(),
+        .0
+    )]
+    DuplicateOracleBoxes(Vec<BoxId>),
 }
 
-pub trait StageDataSource {
-    /// Returns all boxes held at the given stage based on the registered scan
-    fn get_boxes(&self) -> Result<Vec<ErgoBox>>;
+/// A box kind of the protocol read from the scan registered for it. `KIND` maps the scan struct
+/// to its scan in the scan state, and `parse` turns a box found by the scan into the box wrapper of
+/// the kind.
+pub trait StageScan {
+    const KIND: ScanKind;
+    type Box;
+
+    fn scan(&self) -> &Scan;
+
+    fn parse(&self, ergo_box: ErgoBox) -> Result<Self::Box>;
+
+    /// Whether an output of a pending tx of the daemon would be found by the scan
+    fn tracks(&self, ergo_box: &ErgoBox) -> bool {
+        self.parse(ergo_box.clone()).is_ok()
+    }
 
-    /// Returns the first box found by the registered scan for a given `Stage`
-    fn get_box(&self) -> Result<Option<ErgoBox>>;
+    /// Returns the boxes found by the scan, updated with the pending txs of the daemon
+    fn get_boxes(&self) -> Result<Vec<ErgoBox>> {
+        Ok(PENDING_OUTPUTS.apply(self.scan().get_boxes()?, |b| self.tracks(b)))
+    }
 
-    /// Returns the number of boxes held at the given stage based on the registered scan
-    fn number_of_boxes(&self) -> Result<u64>;
+    /// Returns the first box found by the scan, parsed
+    fn get_parsed_box(&self) -> Result<Option<Self::Box>> {
+        self.get_boxes()?
+            .into_iter()
+            .next()
+            .map(|b| self.parse(b))
+            .transpose()
+    }
+
+    /// Returns the number of boxes found by the scan
+    fn number_of_boxes(&self) -> Result<u64> {
+        Ok(self.get_boxes()?.len() as u64)
+    }
 }
 
 pub trait PoolBoxSource {
@@ -79,11 +125,33 @@ pub trait RefreshBoxSource {
 }
 
 pub trait DatapointBoxesSource {
-    fn get_oracle_datapoint_boxes(&self) -> Result<Vec<PostedOracleBox>>;
+    /// The oracle boxes of the pool, posted and collected
+    fn get_oracle_boxes(&self) -> Result<Vec<OracleBoxWrapper>>;
+
+    /// The posted oracle boxes of the pool
+    fn get_oracle_datapoint_boxes(&self) -> Result<Vec<PostedOracleBox>> {
+        Ok(self
+            .get_oracle_boxes()?
+            .into_iter()
+            .filter_map(|b| match b {
+                OracleBoxWrapper::Posted(p) => Some(p),
+                OracleBoxWrapper::Collected(_) => None,
+            })
+            .collect())
+    }
 }
 
 pub trait LocalDatapointBoxSource {
+    /// Fails with `StageError::DuplicateOracleBoxes` if more than one box holds the local oracle
+    /// token
     fn get_local_oracle_datapoint_box(&self) -> Result<Option<OracleBoxWrapper>>;
+
+    /// All the unspent boxes holding the local oracle token. More than one box is a conflict (e.g.
+    /// after a manual tx) which must be repaired before the daemon acts again.
+    fn get_local_oracle_datapoint_boxes(&self) -> Result<Vec<OracleBoxWrapper>>;
+
+    /// Previous (spent) datapoint boxes of the local oracle, oldest first
+    fn get_spent_local_oracle_datapoint_boxes(&self) -> Result<Vec<OracleBoxWrapper>>;
 }
 
 pub trait VoteBallotBoxesSource {
@@ -94,30 +162,32 @@ pub trait UpdateBoxSource {
     fn get_update_box(&self) -> Result<UpdateBoxWrapper>;
 }
 
-/// A `Stage` in the multi-stage smart contract protocol. Is defined here by it's contract address & it's scan_id
-#[derive(Debug, Clone)]
-pub struct Stage {
-    pub contract_address: String,
-    pub scan: Scan,
+pub trait BuybackBoxSource {
+    fn get_buyback_box(&self) -> Result<Option<BuybackBoxWrapper>>;
+}
+
+pub trait MempoolSource {
+    fn get_unconfirmed_transactions(&self) -> Result<Vec<Transaction>>;
 }
 
 /// Overarching struct which allows for acquiring the state of the whole oracle pool protocol
 #[derive(Debug)]
 pub struct OraclePool<'a> {
     pub data_point_source: Box<dyn DataPointSource + Sync + Send>,
-    /// Stages
-    pub datapoint_stage: DatapointStage<'a>,
+    pub datapoint_boxes_scan: DatapointBoxesScan<'a>,
     local_oracle_datapoint_scan: LocalOracleDatapointScan<'a>,
     local_ballot_box_scan: LocalBallotBoxScan<'a>,
     pool_box_scan: PoolBoxScan<'a>,
     refresh_box_scan: RefreshBoxScan<'a>,
     ballot_boxes_scan: BallotBoxesScan<'a>,
     update_box_scan: UpdateBoxScan<'a>,
+    buyback_box_scan: Option<BuybackBoxScan<'a>>,
+    mempool: NodeMempool,
 }
 
 #[derive(Debug)]
-pub struct DatapointStage<'a> {
-    pub stage: Stage,
+pub struct DatapointBoxesScan<'a> {
+    scan: Scan,
     oracle_box_wrapper_inputs: &'a OracleBoxWrapperInputs,
 }
 
@@ -157,6 +227,16 @@ pub struct UpdateBoxScan<'a> {
     update_box_wrapper_inputs: &'a UpdateBoxWrapperInputs,
 }
 
+#[derive(Debug)]
+pub struct BuybackBoxScan<'a> {
+    scan: Scan,
+    buyback_token_id: &'a TokenId,
+}
+
+/// Mempool of the node
+#[derive(Debug)]
+pub struct NodeMempool {}
+
 /// The state of the oracle pool when it is in the Live Epoch stage
 #[derive(Debug, Clone)]
 pub struct LiveEpochState {
@@ -164,6 +244,71 @@ pub struct LiveEpochState {
     pub local_datapoint_box_state: Option<LocalDatapointState>,
     pub latest_pool_datapoint: u64,
     pub latest_pool_box_height: u32,
+    pub pending_txs: PendingTxs,
+    pub pool_box_id: BoxId,
+    pub local_datapoint_box_id: Option<BoxId>,
+}
+
+/// Unconfirmed transactions of the pool touching the local oracle, found in the node mempool
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PendingTxs {
+    /// Id of the unconfirmed tx spending the pool box (refresh or pool update)
+    pub refresh_tx_id: Option<String>,
+    /// Id of the unconfirmed tx spending the local oracle box or creating a new one (datapoint)
+    pub datapoint_tx_id: Option<String>,
+}
+
+impl PendingTxs {
+    pub fn is_empty(&self) -> bool {
+        self.refresh_tx_id.is_none() && self.datapoint_tx_id.is_none()
+    }
+}
+
+/// Id of an unconfirmed tx spending any of `box_ids`, e.g. the refresh of another oracle spending
+/// the pool box
+pub fn find_conflicting_tx(unconfirmed_txs: &[Transaction], box_ids: &[BoxId]) -> Option<String> {
+    unconfirmed_txs
+        .iter()
+        .find(|tx| tx.inputs.iter().any(|i| box_ids.contains(&i.box_id)))
+        .map(|tx| String::from(tx.id().0))
+}
+
+/// Find the unconfirmed txs spending the pool box or the local oracle box. Without a local oracle
+/// box (first datapoint), a tx creating a box with the oracle token and `oracle_public_key` in R4
+/// is pending.
+pub fn find_pending_txs(
+    unconfirmed_txs: &[Transaction],
+    pool_box_id: BoxId,
+    local_oracle_box_id: Option<BoxId>,
+    oracle_token_id: &TokenId,
+    oracle_public_key: &EcPoint,
+) -> PendingTxs {
+    let spends = |tx: &Transaction, box_id: BoxId| tx.inputs.iter().any(|i| i.box_id == box_id);
+    let creates_local_oracle_box = |tx: &Transaction| {
+        tx.outputs.iter().any(|b| {
+            b.tokens
+                .as_ref()
+                .and_then(|tokens| tokens.get(0))
+                .map_or(false, |t| &t.token_id == oracle_token_id)
+                && b.get_register(NonMandatoryRegisterId::R4.into())
+                    .and_then(|r| r.try_extract_into::<EcPoint>().ok())
+                    .map_or(false, |pk| &pk == oracle_public_key)
+        })
+    };
+    let tx_id = |tx: &Transaction| String::from(tx.id().0);
+    PendingTxs {
+        refresh_tx_id: unconfirmed_txs
+            .iter()
+            .find(|tx| spends(tx, pool_box_id))
+            .map(tx_id),
+        datapoint_tx_id: unconfirmed_txs
+            .iter()
+            .find(|tx| match local_oracle_box_id {
+                Some(box_id) => spends(tx, box_id),
+                None => creates_local_oracle_box(tx),
+            })
+            .map(tx_id),
+    }
 }
 
 /// Last posted datapoint box info by the local oracle
@@ -173,6 +318,50 @@ pub enum LocalDatapointState {
     Posted { epoch_id: u32, height: u32 },
 }
 
+/// State of the current epoch of the pool with the given boxes, `pending_txs` finds the unconfirmed
+/// txs spending the pool box or the local oracle box
+pub fn live_epoch_state(
+    pool_box_source: &dyn PoolBoxSource,
+    local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+    pending_txs: impl FnOnce(BoxId, Option<BoxId>) -> PendingTxs,
+) -> Result<LiveEpochState> {
+    let pool_box = pool_box_source.get_pool_box()?;
+    let epoch_id: u32 = pool_box.epoch_counter();
+
+    // Whether datapoint was commit in the current Live Epoch
+    let local_datapoint_box = local_datapoint_box_source.get_local_oracle_datapoint_box()?;
+    let local_datapoint_box_state =
+        local_datapoint_box
+            .as_ref()
+            .map(|local_data_point_box| match local_data_point_box {
+                OracleBoxWrapper::Posted(posted_box) => LocalDatapointState::Posted {
+                    epoch_id: posted_box.epoch_counter(),
+                    height: local_data_point_box.get_box().creation_height,
+                },
+                OracleBoxWrapper::Collected(_) => LocalDatapointState::Collected {
+                    height: local_data_point_box.get_box().creation_height,
+                },
+            });
+
+    let latest_pool_datapoint = pool_box.rate() as u64;
+
+    let pool_box_id = pool_box.get_box().box_id();
+    let local_datapoint_box_id = local_datapoint_box.map(|b| b.get_box().box_id());
+    let pending_txs = pending_txs(pool_box_id.clone(), local_datapoint_box_id.clone());
+
+    let epoch_state = LiveEpochState {
+        pool_box_epoch_id: epoch_id,
+        latest_pool_datapoint,
+        latest_pool_box_height: pool_box.get_box().creation_height,
+        local_datapoint_box_state,
+        pending_txs,
+        pool_box_id,
+        local_datapoint_box_id,
+    };
+
+    Ok(epoch_state)
+}
+
 impl<'a> OraclePool<'a> {
     /// Create a new `OraclePool` struct
     pub fn new() -> std::result::Result<OraclePool<'static>, Error> {
@@ -180,80 +369,67 @@ impl<'a> OraclePool<'a> {
 
         let data_point_source = config.data_point_source()?;
 
-        let refresh_box_scan_name = "Refresh Box Scan";
-
-        let datapoint_contract =
-            OracleContract::checked_load(&config.oracle_box_wrapper_inputs.contract_inputs)?
-                .ergo_tree();
-
-        // Read scanIDs.json for scan ids
-        let scan_json = json::parse(
-            &std::fs::read_to_string("scanIDs.json").expect("Unable to read scanIDs.json"),
-        )
-        .expect("Failed to parse scanIDs.json");
+        let scan_state = ScanState::load(Path::new(SCAN_STATE_FILE))?;
 
         // Create all `Scan` structs for protocol
-        let datapoint_scan = Scan::new(
-            "All Oracle Datapoints Scan",
-            &scan_json["All Datapoints Scan"].to_string(),
-        );
-        let local_scan_str = "Local Oracle Datapoint Scan";
+        let datapoint_boxes_scan = DatapointBoxesScan {
+            scan: stage_scan::<DatapointBoxesScan>(&scan_state)?,
+            oracle_box_wrapper_inputs: &config.oracle_box_wrapper_inputs,
+        };
+
         let local_oracle_datapoint_scan = LocalOracleDatapointScan {
-            scan: Scan::new(
-                "Local Oracle Datapoint Scan",
-                &scan_json[local_scan_str].to_string(),
-            ),
+            scan: stage_scan::<LocalOracleDatapointScan>(&scan_state)?,
             oracle_box_wrapper_inputs: &config.oracle_box_wrapper_inputs,
         };
 
-        let local_scan_str = "Local Ballot Box Scan";
         let local_ballot_box_scan = LocalBallotBoxScan {
-            scan: Scan::new(local_scan_str, &scan_json[local_scan_str].to_string()),
+            scan: stage_scan::<LocalBallotBoxScan>(&scan_state)?,
             ballot_box_wrapper_inputs: &config.ballot_box_wrapper_inputs,
             ballot_token_owner_address: config.oracle_address.address(),
         };
 
         let ballot_boxes_scan = BallotBoxesScan {
-            scan: Scan::new("Ballot Box Scan", &scan_json["Ballot Box Scan"].to_string()),
+            scan: stage_scan::<BallotBoxesScan>(&scan_state)?,
             ballot_box_wrapper_inputs: &config.ballot_box_wrapper_inputs,
         };
 
         let pool_box_scan = PoolBoxScan {
-            scan: Scan::new("Pool Box Scan", &scan_json["Pool Box Scan"].to_string()),
+            scan: stage_scan::<PoolBoxScan>(&scan_state)?,
             pool_box_wrapper_inputs: &config.pool_box_wrapper_inputs,
         };
 
         let refresh_box_scan = RefreshBoxScan {
-            scan: Scan::new(
-                refresh_box_scan_name,
-                &scan_json[refresh_box_scan_name].to_string(),
-            ),
+            scan: stage_scan::<RefreshBoxScan>(&scan_state)?,
             refresh_box_wrapper_inputs: &config.refresh_box_wrapper_inputs,
         };
 
         let update_box_scan = UpdateBoxScan {
-            scan: Scan::new("Update Box Scan", &scan_json["Update Box Scan"].to_string()),
+            scan: stage_scan::<UpdateBoxScan>(&scan_state)?,
             update_box_wrapper_inputs: &config.update_box_wrapper_inputs,
         };
 
+        let buyback_box_scan = match &config.buyback_token_id {
+            Some(buyback_token_id) => Some(BuybackBoxScan {
+                scan: stage_scan::<BuybackBoxScan>(&scan_state)?,
+                buyback_token_id,
+            }),
+            None => None,
+        };
+
         log::debug!("Scans loaded");
 
         // Create `OraclePool` struct
         Ok(OraclePool {
             data_point_source,
-            datapoint_stage: DatapointStage {
-                stage: Stage {
-                    contract_address: datapoint_contract.to_base16_bytes()?,
-                    scan: datapoint_scan,
-                },
-                oracle_box_wrapper_inputs: &config.oracle_box_wrapper_inputs,
-            },
+            datapoint_boxes_scan,
             local_oracle_datapoint_scan,
             local_ballot_box_scan,
             ballot_boxes_scan,
             pool_box_scan,
             refresh_box_scan,
             update_box_scan,
+            buyback_box_scan,
+            mempool: NodeMempool {},
         })
     }
 
@@ -267,33 +443,49 @@ impl<'a> OraclePool<'a> {
 
     /// Get the state of the current oracle pool epoch
     pub fn get_live_epoch_state(&self) -> Result<LiveEpochState> {
-        let pool_box = self.get_pool_box_source().get_pool_box()?;
-        let epoch_id: u32 = pool_box.epoch_counter();
+        live_epoch_state(
+            self.get_pool_box_source(),
+            self.get_local_datapoint_box_source(),
+            |pool_box_id, local_datapoint_box_id| {
+                self.pending_txs(pool_box_id, local_datapoint_box_id)
+            },
+        )
+    }
 
-        // Whether datapoint was commit in the current Live Epoch
-        let local_datapoint_box_state = self
+    /// Unconfirmed txs spending the pool box or the local oracle box
+    pub fn get_pending_txs(&self) -> Result<PendingTxs> {
+        let pool_box = self.get_pool_box_source().get_pool_box()?;
+        let local_datapoint_box = self
             .get_local_datapoint_box_source()
-            .get_local_oracle_datapoint_box()?
-            .map(|local_data_point_box| match local_data_point_box {
-                OracleBoxWrapper::Posted(ref posted_box) => LocalDatapointState::Posted {
-                    epoch_id: posted_box.epoch_counter(),
-                    height: local_data_point_box.get_box().creation_height,
-                },
-                OracleBoxWrapper::Collected(_) => LocalDatapointState::Collected {
-                    height: local_data_point_box.get_box().creation_height,
-                },
-            });
-
-        let latest_pool_datapoint = pool_box.rate() as u64;
+            .get_local_oracle_datapoint_box()?;
+        Ok(self.pending_txs(
+            pool_box.get_box().box_id(),
+            local_datapoint_box.map(|b| b.get_box().box_id()),
+        ))
+    }
 
-        let epoch_state = LiveEpochState {
-            pool_box_epoch_id: epoch_id,
-            latest_pool_datapoint,
-            latest_pool_box_height: pool_box.get_box().creation_height,
-            local_datapoint_box_state,
+    /// Failing to read the mempool is not fatal, no pending txs are assumed then
+    fn pending_txs(&self, pool_box_id: BoxId, local_oracle_box_id: Option<BoxId>) -> PendingTxs {
+        let mut unconfirmed_txs = match self.get_mempool_source().get_unconfirmed_transactions() {
+            Ok(txs) => txs,
+            Err(e) => {
+                log::warn!("Failed to get the unconfirmed transactions: {}", e);
+                return PendingTxs::default();
+            }
         };
-
-        Ok(epoch_state)
+        // The pending txs of the daemon are already applied to the scans
+        let own_tx_ids = PENDING_OUTPUTS.tx_ids();
+        unconfirmed_txs.retain(|tx| !own_tx_ids.contains(&String::from(tx.id().0)));
+        match ORACLE_CONFIG.oracle_address.address() {
+            Address::P2Pk(oracle_public_key) => find_pending_txs(
+                &unconfirmed_txs,
+                pool_box_id,
+                local_oracle_box_id,
+                &ORACLE_CONFIG.token_ids.oracle_token_id,
+                &oracle_public_key.h,
+            ),
+            _ => PendingTxs::default(),
+        }
     }
 
     pub fn get_pool_box_source(&self) -> &dyn PoolBoxSource {
@@ -312,80 +504,268 @@ impl<'a> OraclePool<'a> {
         &self.refresh_box_scan as &dyn RefreshBoxSource
     }
 
-    pub fn get_datapoint_boxes_source(&self) -> &dyn DatapointBoxesSource {
-        &self.datapoint_stage as &dyn DatapointBoxesSource
+    pub fn get_datapoint_boxes_source(&self) -> &(dyn DatapointBoxesSource + Sync) {
+        &self.datapoint_boxes_scan as &(dyn DatapointBoxesSource + Sync)
     }
 
     pub fn get_local_datapoint_box_source(&self) -> &dyn LocalDatapointBoxSource {
         &self.local_oracle_datapoint_scan as &dyn LocalDatapointBoxSource
     }
 
+    pub fn get_mempool_source(&self) -> &dyn MempoolSource {
+        &self.mempool as &dyn MempoolSource
+    }
+
+    /// `None` if the pool has no buyback box
+    pub fn get_buyback_box_source(&self) -> Option<&dyn BuybackBoxSource> {
+        self.buyback_box_scan
+            .as_ref()
+            .map(|s| s as &dyn BuybackBoxSource)
+    }
+
+    /// The pool box found by the scan, without checking its contract against the config
+    pub fn get_unchecked_pool_box(&self) -> Result<ErgoBox> {
+        self.pool_box_scan
+            .scan
+            .get_box()?
+            .ok_or(StageError::PoolBoxNotFoundError)
+    }
+
+    /// The posted and collected oracle boxes of all the oracles
+    pub fn get_all_oracle_boxes(&self) -> Result<Vec<OracleBoxWrapper>> {
+        let scan = &self.datapoint_boxes_scan;
+        parse_boxes_parallel(scan.get_boxes()?, |b| scan.parse(b))
+    }
+
+    /// The pool box confirmed on chain, without the outputs of the pending txs of the daemon
+    pub fn get_confirmed_pool_box(&self) -> Result<PoolBoxWrapper> {
+        self.pool_box_scan.parse(self.get_unchecked_pool_box()?)
+    }
+
+    /// The refresh box found by the scan, without checking its contract against the config
+    pub fn get_unchecked_refresh_box(&self) -> Result<ErgoBox> {
+        self.refresh_box_scan
+            .scan
+            .get_box()?
+            .ok_or(StageError::RefreshBoxNotFoundError)
+    }
+
+    /// The update box found by the scan, without checking its contract against the config
+    pub fn get_unchecked_update_box(&self) -> Result<ErgoBox> {
+        self.update_box_scan
+            .scan
+            .get_box()?
+            .ok_or(StageError::UpdateBoxNotFoundError)
+    }
+
     pub fn get_update_box_source(&self) -> &dyn UpdateBoxSource {
         &self.update_box_scan as &dyn UpdateBoxSource
     }
 }
 
+impl MempoolSource for NodeMempool {
+    fn get_unconfirmed_transactions(&self) -> Result<Vec<Transaction>> {
+        Ok(get_unconfirmed_transactions()?)
+    }
+}
+
+/// The scan registered for the box kind of `S`
+fn stage_scan<S: StageScan>(scan_state: &ScanState) -> Result<Scan> {
+    Ok(scan_state.scan(S::KIND)?)
+}
+
+impl<'a> StageScan for DatapointBoxesScan<'a> {
+    const KIND: ScanKind = ScanKind::Datapoints;
+    type Box = OracleBoxWrapper;
+
+    fn scan(&self) -> &Scan {
+        &self.scan
+    }
+
+    fn parse(&self, ergo_box: ErgoBox) -> Result<OracleBoxWrapper> {
+        Ok(OracleBoxWrapper::new(
+            ergo_box,
+            self.oracle_box_wrapper_inputs,
+        )?)
+    }
+}
+
+impl<'a> StageScan for LocalOracleDatapointScan<'a> {
+    const KIND: ScanKind = ScanKind::LocalDatapoint;
+    type Box = OracleBoxWrapper;
+
+    fn scan(&self) -> &Scan {
+        &self.scan
+    }
+
+    fn parse(&self, ergo_box: ErgoBox) -> Result<OracleBoxWrapper> {
+        Ok(OracleBoxWrapper::new(
+            ergo_box,
+            self.oracle_box_wrapper_inputs,
+        )?)
+    }
+
+    fn tracks(&self, ergo_box: &ErgoBox) -> bool {
+        match (
+            self.parse(ergo_box.clone()),
+            ORACLE_CONFIG.oracle_address.address(),
+        ) {
+            (Ok(oracle_box), Address::P2Pk(oracle_public_key)) => {
+                oracle_box.public_key() == oracle_public_key
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a> StageScan for LocalBallotBoxScan<'a> {
+    const KIND: ScanKind = ScanKind::LocalBallot;
+    type Box = BallotBoxWrapper;
+
+    fn scan(&self) -> &Scan {
+        &self.scan
+    }
+
+    fn parse(&self, ergo_box: ErgoBox) -> Result<BallotBoxWrapper> {
+        Ok(BallotBoxWrapper::new(
+            ergo_box,
+            self.ballot_box_wrapper_inputs,
+            &self.ballot_token_owner_address,
+        )?)
+    }
+}
+
+impl<'a> StageScan for BallotBoxesScan<'a> {
+    const KIND: ScanKind = ScanKind::Ballots;
+    type Box = VoteBallotBoxWrapper;
+
+    fn scan(&self) -> &Scan {
+        &self.scan
+    }
+
+    fn parse(&self, ergo_box: ErgoBox) -> Result<VoteBallotBoxWrapper> {
+        Ok(VoteBallotBoxWrapper::new(
+            ergo_box,
+            self.ballot_box_wrapper_inputs,
+        )?)
+    }
+}
+
+impl<'a> StageScan for PoolBoxScan<'a> {
+    const KIND: ScanKind = ScanKind::Pool;
+    type Box = PoolBoxWrapper;
+
+    fn scan(&self) -> &Scan {
+        &self.scan
+    }
+
+    fn parse(&self, ergo_box: ErgoBox) -> Result<PoolBoxWrapper> {
+        Ok(PoolBoxWrapper::new(ergo_box, self.pool_box_wrapper_inputs)?)
+    }
+}
+
+impl<'a> StageScan for RefreshBoxScan<'a> {
+    const KIND: ScanKind = ScanKind::Refresh;
+    type Box = RefreshBoxWrapper;
+
+    fn scan(&self) -> &Scan {
+        &self.scan
+    }
+
+    fn parse(&self, ergo_box: ErgoBox) -> Result<RefreshBoxWrapper> {
+        Ok(RefreshBoxWrapper::new(
+            ergo_box,
+            self.refresh_box_wrapper_inputs,
+        )?)
+    }
+}
+
+impl<'a> StageScan for UpdateBoxScan<'a> {
+    const KIND: ScanKind = ScanKind::Update;
+    type Box = UpdateBoxWrapper;
+
+    fn scan(&self) -> &Scan {
+        &self.scan
+    }
+
+    fn parse(&self, ergo_box: ErgoBox) -> Result<UpdateBoxWrapper> {
+        Ok(UpdateBoxWrapper::new(
+            ergo_box,
+            self.update_box_wrapper_inputs,
+        )?)
+    }
+}
+
+impl<'a> StageScan for BuybackBoxScan<'a> {
+    const KIND: ScanKind = ScanKind::Buyback;
+    type Box = BuybackBoxWrapper;
+
+    fn scan(&self) -> &Scan {
+        &self.scan
+    }
+
+    fn parse(&self, ergo_box: ErgoBox) -> Result<BuybackBoxWrapper> {
+        Ok(BuybackBoxWrapper::new(ergo_box, self.buyback_token_id)?)
+    }
+}
+
 impl<'a> PoolBoxSource for PoolBoxScan<'a> {
     fn get_pool_box(&self) -> Result<PoolBoxWrapper> {
-        let box_wrapper = PoolBoxWrapper::new(
-            self.scan
-                .get_box()?
-                .ok_or(StageError::PoolBoxNotFoundError)?,
-            self.pool_box_wrapper_inputs,
-        )?;
-        Ok(box_wrapper)
+        self.get_parsed_box()?
+            .ok_or(StageError::PoolBoxNotFoundError)
     }
 }
 
 impl<'a> LocalBallotBoxSource for LocalBallotBoxScan<'a> {
     fn get_ballot_box(&self) -> Result<Option<BallotBoxWrapper>> {
-        self.scan
-            .get_box()?
-            .map(|b| {
-                BallotBoxWrapper::new(
-                    b,
-                    self.ballot_box_wrapper_inputs,
-                    &self.ballot_token_owner_address,
-                )
-                .map_err(Into::into)
-            })
-            .transpose()
+        self.get_parsed_box()
     }
 }
 
 impl<'a> RefreshBoxSource for RefreshBoxScan<'a> {
     fn get_refresh_box(&self) -> Result<RefreshBoxWrapper> {
-        let box_wrapper = RefreshBoxWrapper::new(
-            self.scan
-                .get_box()?
-                .ok_or(StageError::RefreshBoxNotFoundError)?,
-            self.refresh_box_wrapper_inputs,
-        )?;
-        Ok(box_wrapper)
+        self.get_parsed_box()?
+            .ok_or(StageError::RefreshBoxNotFoundError)
     }
 }
 
 impl<'a> LocalDatapointBoxSource for LocalOracleDatapointScan<'a> {
     fn get_local_oracle_datapoint_box(&self) -> Result<Option<OracleBoxWrapper>> {
-        self.scan
-            .get_box()?
-            .map(|b| OracleBoxWrapper::new(b, self.oracle_box_wrapper_inputs).map_err(Into::into))
-            .transpose()
+        let mut oracle_boxes = self.get_local_oracle_datapoint_boxes()?;
+        if oracle_boxes.len() > 1 {
+            return Err(StageError::DuplicateOracleBoxes(
+                oracle_boxes.iter().map(|b| b.get_box().box_id()).collect(),
+            ));
+        }
+        Ok(oracle_boxes.pop())
+    }
+
+    fn get_local_oracle_datapoint_boxes(&self) -> Result<Vec<OracleBoxWrapper>> {
+        self.get_boxes()?
+            .into_iter()
+            .map(|b| self.parse(b))
+            .collect()
+    }
+
+    fn get_spent_local_oracle_datapoint_boxes(&self) -> Result<Vec<OracleBoxWrapper>> {
+        let mut oracle_boxes: Vec<OracleBoxWrapper> = self
+            .scan
+            .get_spent_boxes()?
+            .into_iter()
+            .map(|b| self.parse(b))
+            .filter_map(Result::ok) // Boxes of a previous oracle contract don't parse
+            .collect();
+        oracle_boxes.sort_by_key(|b| b.get_box().creation_height);
+        Ok(oracle_boxes)
     }
 }
 
 impl<'a> VoteBallotBoxesSource for BallotBoxesScan<'a> {
     fn get_ballot_boxes(&self) -> Result<Vec<VoteBallotBoxWrapper>> {
         Ok(self
-            .scan
             .get_boxes()?
             .into_iter()
-            .map(|ballot_box| {
-                Ok(VoteBallotBoxWrapper::new(
-                    ballot_box,
-                    self.ballot_box_wrapper_inputs,
-                )?)
-            })
+            .map(|ballot_box| self.parse(ballot_box))
             .filter_map(Result::ok) // Filter out boxes that are not participating in voting
             .collect())
     }
@@ -393,87 +773,100 @@ impl<'a> VoteBallotBoxesSource for BallotBoxesScan<'a> {
 
 impl<'a> UpdateBoxSource for UpdateBoxScan<'a> {
     fn get_update_box(&self) -> Result<UpdateBoxWrapper> {
-        let box_wrapper = UpdateBoxWrapper::new(
-            self.scan
-                .get_box()?
-                .ok_or(StageError::UpdateBoxNotFoundError)?,
-            self.update_box_wrapper_inputs,
-        )?;
-        Ok(box_wrapper)
+        self.get_parsed_box()?
+            .ok_or(StageError::UpdateBoxNotFoundError)
     }
 }
 
-impl StageDataSource for Stage {
-    /// Returns all boxes held at the given stage based on the registered scan
-    fn get_boxes(&self) -> Result<Vec<ErgoBox>> {
-        self.scan.get_boxes().map_err(Into::into)
-    }
-
-    /// Returns the first box found by the registered scan for a given `Stage`
-    fn get_box(&self) -> Result<Option<ErgoBox>> {
-        self.scan.get_box().map_err(Into::into)
+impl<'a> BuybackBoxSource for BuybackBoxScan<'a> {
+    fn get_buyback_box(&self) -> Result<Option<BuybackBoxWrapper>> {
+        self.get_parsed_box()
     }
+}
 
-    /// Returns the number of boxes held at the given stage based on the registered scan
-    fn number_of_boxes(&self) -> Result<u64> {
-        Ok(self.get_boxes()?.len() as u64)
+impl<'a> DatapointBoxesSource for DatapointBoxesScan<'a> {
+    fn get_oracle_boxes(&self) -> Result<Vec<OracleBoxWrapper>> {
+        parse_boxes_parallel(self.get_boxes()?, |b| {
+            OracleBoxWrapper::new(b, self.oracle_box_wrapper_inputs)
+        })
     }
 }
 
-impl<'a> DatapointBoxesSource for DatapointStage<'a> {
-    fn get_oracle_datapoint_boxes(&self) -> Result<Vec<PostedOracleBox>> {
-        let oracle_boxes: Vec<OracleBoxWrapper> = self
-            .stage
-            .get_boxes()?
-            .into_iter()
-            .map(|b| OracleBoxWrapper::new(b, self.oracle_box_wrapper_inputs))
-            .collect::<std::result::Result<Vec<OracleBoxWrapper>, _>>()?;
-
-        let posted_boxes = oracle_boxes
-            .into_iter()
-            .filter_map(|b| match b {
-                OracleBoxWrapper::Posted(p) => Some(p),
-                OracleBoxWrapper::Collected(_) => None,
+/// Maximum number of threads parsing the boxes of a scan
+const MAX_PARSE_THREADS: usize = 4;
+
+/// Parse the boxes on up to [`MAX_PARSE_THREADS`] threads, keeping their order. With 20+ oracles
+/// parsing the datapoint boxes one by one delays the refresh tx.
+pub fn parse_boxes_parallel<T: Send, E: Send>(
+    boxes: Vec<ErgoBox>,
+    parse: impl Fn(ErgoBox) -> std::result::Result<T, E> + Sync,
+) -> std::result::Result<Vec<T>, E> {
+    if boxes.len() < 2 {
+        return boxes.into_iter().map(parse).collect();
+    }
+    let chunk_size = (boxes.len() + MAX_PARSE_THREADS - 1) / MAX_PARSE_THREADS;
+    let parse = &parse;
+    let chunks = crossbeam::thread::scope(|s| {
+        let handles: Vec<_> = boxes
+            .chunks(chunk_size)
+            .map(|chunk| {
+                s.spawn(move |_| {
+                    chunk
+                        .iter()
+                        .cloned()
+                        .map(parse)
+                        .collect::<std::result::Result<Vec<T>, E>>()
+                })
             })
             .collect();
-        Ok(posted_boxes)
-    }
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect::<std::result::Result<Vec<Vec<T>>, E>>()
+    })
+    .unwrap_or_else(|e| std::panic::resume_unwind(e))?;
+    Ok(chunks.into_iter().flatten().collect())
 }
 
-/// Register scans and save in scanIDs.json (if it doesn't already exist), and wait for rescan to complete
+/// Sync the node scans with the ones the config requires (see [`sync_scans`]), save them in
+/// `scan_state.json`, and wait for the rescan to complete if any scan was registered
 pub fn register_and_save_scans() -> std::result::Result<(), Error> {
-    let config = &ORACLE_CONFIG;
-    if !Path::new("scanIDs.json").exists() {
-        register_and_save_scans_inner()?;
-    } else {
-        // If the UpdatePool command was issued values relating to the pool box in `scanIDs.json` will be out
-        // of date. So we regenerate `scanIDs.json` and initiate a wallet rescan.
-
-        // Note that the following variable was created from the existing `scanIDs.json`.
-        let oracle_pool = OraclePool::new()?;
-
-        let scan_pool_box_wrapper = oracle_pool.get_pool_box_source().get_pool_box()?;
-        let config_pool_box_bytes = &config
-            .pool_box_wrapper_inputs
-            .contract_inputs
-            .contract_parameters()
-            .ergo_tree_bytes();
-
-        let pool_hash_changed = blake2b256_hash(
-            &scan_pool_box_wrapper
-                .get_box()
-                .ergo_tree
-                .sigma_serialize_bytes()?,
-        ) != blake2b256_hash(config_pool_box_bytes);
-
-        let reward_tokens_changed = scan_pool_box_wrapper.reward_token().token_id
-            != config.pool_box_wrapper_inputs.reward_token_id;
-
-        // The UpdatePool command will lead to either a change in the pool box script and/or a
-        // change in the reward tokens.
-        if pool_hash_changed || reward_tokens_changed {
-            register_and_save_scans_inner()?;
+    let state_path = Path::new(SCAN_STATE_FILE);
+    let state = if state_path.exists() {
+        match ScanState::load(state_path) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                log::warn!("Ignoring {}: {}", SCAN_STATE_FILE, e);
+                None
+            }
         }
+    } else {
+        None
+    };
+    let legacy_scan_ids = if Path::new(LEGACY_SCAN_IDS_FILE).exists() {
+        load_legacy_scan_ids(Path::new(LEGACY_SCAN_IDS_FILE)).unwrap_or_else(|e| {
+            log::warn!("Ignoring {}: {}", LEGACY_SCAN_IDS_FILE, e);
+            Vec::new()
+        })
+    } else {
+        Vec::new()
+    };
+    let sync = sync_scans(
+        &NodeScanRegistry {},
+        state.as_ref(),
+        &legacy_scan_ids,
+        &scan_specs()?,
+    )?;
+    if state.as_ref() != Some(&sync.state) {
+        sync.state.save(state_path)?;
+    }
+    // Its scans were deregistered by `sync_scans`
+    if Path::new(LEGACY_SCAN_IDS_FILE).exists() {
+        std::fs::remove_file(LEGACY_SCAN_IDS_FILE)?;
+    }
+    if !sync.registered.is_empty() {
+        log::info!("Triggering wallet rescan");
+        rescan_from_height(ORACLE_CONFIG.rescan_height)?;
     }
 
     loop {
@@ -483,21 +876,15 @@ pub fn register_and_save_scans() -> std::result::Result<(), Error> {
             break;
         }
         std::thread::sleep(std::time::Duration::from_secs(1));
-        println!("Scanned {}/{} blocks", wallet_height, block_height);
+        log::info!("Scanned {}/{} blocks", wallet_height, block_height);
     }
     Ok(())
 }
 
-/// Registers and saves scans to `scanIDs.json` as well as performing wallet rescanning.
-///
-/// WARNING: will overwrite existing `scanIDs.json`!
-fn register_and_save_scans_inner() -> std::result::Result<(), Error> {
+/// The scans of the box kinds the oracle tracks, with the tracking rules built from the config
+fn scan_specs() -> std::result::Result<Vec<ScanSpec>, Error> {
     let config = &ORACLE_CONFIG;
-    let local_oracle_address = config.oracle_address.clone();
-
-    let oracle_pool_participant_token_id = config.token_ids.oracle_token_id.clone();
-
-    let refresh_box_scan_name = "Refresh Box Scan";
+    let oracle_token_id = &config.token_ids.oracle_token_id;
 
     let datapoint_contract_address =
         OracleContract::checked_load(&config.oracle_box_wrapper_inputs.contract_inputs)?
@@ -507,33 +894,122 @@ fn register_and_save_scans_inner() -> std::result::Result<(), Error> {
         BallotContract::checked_load(&config.ballot_box_wrapper_inputs.contract_inputs)?
             .ergo_tree();
 
-    let scans = vec![
-        register_datapoint_scan(
-            &oracle_pool_participant_token_id,
-            &datapoint_contract_address,
-        )?,
-        register_update_box_scan(&config.token_ids.update_nft_token_id)?,
-        register_pool_box_scan(config.pool_box_wrapper_inputs.clone())?,
-        register_refresh_box_scan(
-            refresh_box_scan_name,
-            config.refresh_box_wrapper_inputs.clone(),
-        )?,
-        register_local_oracle_datapoint_scan(
-            &oracle_pool_participant_token_id,
-            &datapoint_contract_address,
-            &local_oracle_address,
-        )?,
-        register_local_ballot_box_scan(
-            &ballot_contract_address,
-            &config.token_ids.ballot_token_id,
-            &config.oracle_address,
-        )?,
-        register_ballot_box_scan(&ballot_contract_address, &config.token_ids.ballot_token_id)?,
-    ];
-
-    log::info!("Registering UTXO-Set Scans");
-    save_scan_ids_locally(scans)?;
-    log::info!("Triggering wallet rescan");
-    rescan_from_height(ORACLE_CONFIG.rescan_height)?;
-    Ok(())
+    let mut specs = Vec::new();
+    for kind in ScanKind::ALL {
+        // No wildcard arm, a new box kind must declare its tracking rule here
+        let tracking_rule = match kind {
+            ScanKind::Datapoints => {
+                datapoint_tracking_rule(oracle_token_id, &datapoint_contract_address)
+            }
+            ScanKind::LocalDatapoint => local_oracle_datapoint_tracking_rule(
+                oracle_token_id,
+                &datapoint_contract_address,
+                &config.oracle_address,
+            )?,
+            ScanKind::LocalBallot => local_ballot_box_tracking_rule(
+                &ballot_contract_address,
+                &config.token_ids.ballot_token_id,
+                &config.oracle_address,
+            )?,
+            ScanKind::Ballots => ballot_box_tracking_rule(
+                &ballot_contract_address,
+                &config.token_ids.ballot_token_id,
+            ),
+            ScanKind::Pool => pool_box_tracking_rule(&config.pool_box_wrapper_inputs)?,
+            ScanKind::Refresh => refresh_box_tracking_rule(&config.refresh_box_wrapper_inputs)?,
+            ScanKind::Update => update_box_tracking_rule(&config.token_ids.update_nft_token_id),
+            // Only pools with a buyback box track it
+            ScanKind::Buyback => match &config.buyback_token_id {
+                Some(buyback_token_id) => buyback_box_tracking_rule(buyback_token_id),
+                None => continue,
+            },
+        };
+        specs.push(ScanSpec::new(
+            kind,
+            &config.token_ids.pool_nft_token_id,
+            tracking_rule,
+        ));
+    }
+    Ok(specs)
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergo_chain_types::Digest32;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_boxes_parallel() {
+        let boxes: Vec<ErgoBox> = (0..10).map(|_| force_any_val::<ErgoBox>()).collect();
+        let box_ids: Vec<BoxId> = boxes.iter().map(|b| b.box_id()).collect();
+        let parsed: std::result::Result<Vec<BoxId>, ()> =
+            parse_boxes_parallel(boxes.clone(), |b| Ok(b.box_id()));
+        assert_eq!(parsed.unwrap(), box_ids);
+
+        let failing = box_ids[7].clone();
+        let parsed = parse_boxes_parallel(boxes, |b| {
+            if b.box_id() == failing {
+                Err(b.box_id())
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(parsed, Err(failing));
+    }
+
+    #[test]
+    fn test_find_pending_txs() {
+        let tx = force_any_val::<Transaction>();
+        let spent_box_id = tx.inputs.first().box_id;
+        let other_box_id = force_any_val::<BoxId>();
+        let oracle_token_id: TokenId = force_any_val::<Digest32>().into();
+        let oracle_public_key = force_any_val::<EcPoint>();
+        let txs = vec![tx.clone()];
+
+        let pending = find_pending_txs(
+            &txs,
+            spent_box_id,
+            Some(other_box_id),
+            &oracle_token_id,
+            &oracle_public_key,
+        );
+        assert_eq!(pending.refresh_tx_id, Some(String::from(tx.id().0)));
+        assert_eq!(pending.datapoint_tx_id, None);
+
+        let pending = find_pending_txs(
+            &txs,
+            other_box_id,
+            Some(spent_box_id),
+            &oracle_token_id,
+            &oracle_public_key,
+        );
+        assert_eq!(pending.refresh_tx_id, None);
+        assert_eq!(pending.datapoint_tx_id, Some(String::from(tx.id().0)));
+
+        // No local oracle box and no output with the oracle token
+        assert!(find_pending_txs(
+            &txs,
+            other_box_id,
+            None,
+            &oracle_token_id,
+            &oracle_public_key
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_find_conflicting_tx() {
+        let tx = force_any_val::<Transaction>();
+        let spent_box_id = tx.inputs.last().box_id;
+        let other_box_id = force_any_val::<BoxId>();
+        let txs = vec![tx.clone()];
+
+        assert_eq!(
+            find_conflicting_tx(&txs, &[other_box_id, spent_box_id]),
+            Some(String::from(tx.id().0))
+        );
+        assert_eq!(find_conflicting_tx(&txs, &[other_box_id]), None);
+    }
 }
@@ -0,0 +1,279 @@
+//! Detection of the peer oracles of the pool misbehaving, to give the pool operators the data to
+//! justify removing an oracle. The datapoints posted by the peers are compared with the value of
+//! the data source of this oracle (fetched once per epoch), an oracle is flagged when its
+//! datapoints deviate beyond `max_deviation_percent` or its epochs end without a datapoint (stale)
+//! `flag_after` times in a row. The report is served on `/peers` of the REST API and saved to
+//! `peer_report.json` for the `peer-report` command.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use derive_more::From;
+use ergo_lib::ergotree_ir::chain::address::{Address, NetworkAddress, NetworkPrefix};
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::oracle_config::PeerMonitorConfig;
+
+pub const PEER_REPORT_FILE: &str = "peer_report.json";
+
+/// The last report of the daemon
+pub static PEER_REPORT: Lazy<Mutex<Vec<PeerStatus>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+#[derive(Debug, From, Error)]
+pub enum PeerReportError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("report file error: {0}")]
+    ReportFile(serde_json::Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PeerStatus {
+    pub oracle_address: String,
+    /// Datapoints compared with the data source of this oracle
+    pub compared_datapoints: u32,
+    /// Compared datapoints deviating beyond `max_deviation_percent`
+    pub deviating_datapoints: u32,
+    pub consecutive_deviating: u32,
+    pub last_deviation_percent: Option<f64>,
+    /// Epochs in a row that ended without a datapoint of the oracle for them
+    pub consecutive_stale_epochs: u32,
+    pub flagged: bool,
+}
+
+impl PeerStatus {
+    fn new(oracle_address: String) -> Self {
+        PeerStatus {
+            oracle_address,
+            compared_datapoints: 0,
+            deviating_datapoints: 0,
+            consecutive_deviating: 0,
+            last_deviation_percent: None,
+            consecutive_stale_epochs: 0,
+            flagged: false,
+        }
+    }
+}
+
+/// An oracle box of a peer
+#[derive(Debug, Clone)]
+pub struct PeerBox {
+    pub oracle: ProveDlog,
+    pub box_id: BoxId,
+    /// Epoch and rate of a posted datapoint, `None` for a collected box
+    pub posted: Option<(u32, u64)>,
+    /// Recreated by the refresh tx of the current pool box
+    pub collected_by_last_refresh: bool,
+}
+
+pub struct PeerMonitor {
+    network_prefix: NetworkPrefix,
+    local_public_key: Option<ProveDlog>,
+    max_deviation_percent: f64,
+    flag_after: u32,
+    epoch: Option<u32>,
+    /// Value of the data source for the current epoch, `None` until fetched
+    source_value: Option<Option<i64>>,
+    compared_box_ids: Vec<BoxId>,
+    peers: BTreeMap<String, PeerStatus>,
+}
+
+impl PeerMonitor {
+    /// Continues the counts of `report` (saved by the previous run)
+    pub fn new(
+        config: &PeerMonitorConfig,
+        network_prefix: NetworkPrefix,
+        local_public_key: Option<ProveDlog>,
+        report: Vec<PeerStatus>,
+    ) -> Self {
+        PeerMonitor {
+            network_prefix,
+            local_public_key,
+            max_deviation_percent: config.max_deviation_percent,
+            flag_after: config.flag_after,
+            epoch: None,
+            source_value: None,
+            compared_box_ids: Vec::new(),
+            peers: report
+                .into_iter()
+                .map(|p| (p.oracle_address.clone(), p))
+                .collect(),
+        }
+    }
+
+    /// Observe the oracle boxes of the pool in `epoch`. `source_value` is called at most once per
+    /// epoch, when the first datapoint of the epoch is compared. Returns the messages of the
+    /// oracles flagged by this observation.
+    pub fn observe(
+        &mut self,
+        epoch: u32,
+        peer_boxes: &[PeerBox],
+        source_value: impl FnOnce() -> Option<i64>,
+    ) -> Vec<String> {
+        let peer_boxes: Vec<(String, &PeerBox)> = peer_boxes
+            .iter()
+            .filter(|b| Some(&b.oracle) != self.local_public_key.as_ref())
+            .map(|b| {
+                let address =
+                    NetworkAddress::new(self.network_prefix, &Address::P2Pk(b.oracle.clone()))
+                        .to_base58();
+                (address, b)
+            })
+            .collect();
+        if self.epoch.map_or(true, |e| epoch > e) {
+            let new_epoch = self.epoch.is_some();
+            self.epoch = Some(epoch);
+            self.source_value = None;
+            self.compared_box_ids.clear();
+            if new_epoch {
+                for (address, peer_box) in &peer_boxes {
+                    // Collected by the refresh or posted for the ended epoch (left out)
+                    let fresh = peer_box.collected_by_last_refresh
+                        || peer_box.posted.map_or(false, |(e, _)| e + 1 >= epoch);
+                    let status = self.status(address);
+                    if fresh {
+                        status.consecutive_stale_epochs = 0;
+                    } else {
+                        status.consecutive_stale_epochs += 1;
+                    }
+                }
+            }
+        }
+        let mut source_value = Some(source_value);
+        for (address, peer_box) in &peer_boxes {
+            let rate = match peer_box.posted {
+                Some((e, rate)) if e == epoch => rate,
+                Some(_) | None => continue,
+            };
+            if self.compared_box_ids.contains(&peer_box.box_id) {
+                continue;
+            }
+            if self.source_value.is_none() {
+                self.source_value = Some(source_value.take().and_then(|f| f()));
+            }
+            let reference = match self.source_value.flatten().filter(|v| *v != 0) {
+                Some(reference) => reference,
+                None => continue,
+            };
+            self.compared_box_ids.push(peer_box.box_id.clone());
+            let deviation_percent = (rate as f64 - reference as f64) / reference as f64 * 100.0;
+            let max_deviation_percent = self.max_deviation_percent;
+            let status = self.status(address);
+            status.compared_datapoints += 1;
+            status.last_deviation_percent = Some(deviation_percent);
+            if deviation_percent.abs() > max_deviation_percent {
+                status.deviating_datapoints += 1;
+                status.consecutive_deviating += 1;
+            } else {
+                status.consecutive_deviating = 0;
+            }
+        }
+        let flag_after = self.flag_after;
+        let mut messages = Vec::new();
+        for status in self.peers.values_mut() {
+            let flagged = status.consecutive_deviating >= flag_after
+                || status.consecutive_stale_epochs >= flag_after;
+            if flagged && !status.flagged {
+                messages.push(if status.consecutive_deviating >= flag_after {
+                    format!(
+                        "Oracle {} posted {} datapoints in a row deviating from the data source (last {:.2}%)",
+                        status.oracle_address,
+                        status.consecutive_deviating,
+                        status.last_deviation_percent.unwrap_or_default()
+                    )
+                } else {
+                    format!(
+                        "Oracle {} didn't post a datapoint for the last {} epochs",
+                        status.oracle_address, status.consecutive_stale_epochs
+                    )
+                });
+            }
+            status.flagged = flagged;
+        }
+        messages
+    }
+
+    fn status(&mut self, address: &str) -> &mut PeerStatus {
+        self.peers
+            .entry(address.to_string())
+            .or_insert_with(|| PeerStatus::new(address.to_string()))
+    }
+
+    /// The flagged oracles first
+    pub fn report(&self) -> Vec<PeerStatus> {
+        let mut report: Vec<PeerStatus> = self.peers.values().cloned().collect();
+        report.sort_by_key(|p| !p.flagged);
+        report
+    }
+}
+
+/// The report saved by the daemon, empty if there is none
+pub fn load_peer_report(path: &Path) -> Result<Vec<PeerStatus>, PeerReportError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+pub fn save_peer_report(path: &Path, report: &[PeerStatus]) -> Result<(), PeerReportError> {
+    std::fs::write(path, serde_json::to_string_pretty(report)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    #[test]
+    fn test_peer_monitor() {
+        let oracles: Vec<ProveDlog> = (0..3)
+            .map(|_| force_any_val::<DlogProverInput>().public_image())
+            .collect();
+        let config = PeerMonitorConfig {
+            max_deviation_percent: 5.0,
+            flag_after: 2,
+        };
+        let mut monitor = PeerMonitor::new(
+            &config,
+            NetworkPrefix::Mainnet,
+            Some(oracles[0].clone()),
+            vec![],
+        );
+        let posted = |i: usize, epoch: u32, rate: u64| PeerBox {
+            oracle: oracles[i].clone(),
+            box_id: force_any_val::<BoxId>(),
+            posted: Some((epoch, rate)),
+            collected_by_last_refresh: false,
+        };
+        // Oracle 1 deviates, oracle 2 is stuck in epoch 1
+        let epoch_2 = [posted(0, 2, 1500), posted(1, 2, 1100), posted(2, 1, 1000)];
+        assert!(monitor.observe(2, &epoch_2, || Some(1000)).is_empty());
+        // Compared once
+        assert!(monitor
+            .observe(2, &epoch_2, || panic!("fetched twice"))
+            .is_empty());
+        let epoch_3 = [posted(1, 3, 1200), posted(2, 1, 1000)];
+        let messages = monitor.observe(3, &epoch_3, || Some(1000));
+        assert_eq!(messages.len(), 1);
+        let messages = monitor.observe(4, &[posted(2, 1, 1000)], || None);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("didn't post a datapoint for the last 2 epochs"));
+
+        let report = monitor.report();
+        // The local oracle is left out
+        assert_eq!(report.len(), 2);
+        assert!(report.iter().all(|p| p.flagged));
+        let oracle_1 = report.iter().find(|p| p.compared_datapoints == 2).unwrap();
+        assert_eq!(oracle_1.deviating_datapoints, 2);
+        assert_eq!(oracle_1.last_deviation_percent, Some(20.0));
+    }
+}
@@ -0,0 +1,127 @@
+//! Outputs of the txs submitted by the daemon which are not confirmed yet. With
+//! `chain_transactions` enabled in the oracle config the submitted txs are tracked here and the
+//! scans report their outputs as if they were confirmed (dropping the boxes they spend), so the
+//! daemon can build a tx on top of its own unconfirmed tx, e.g. publish a datapoint right after
+//! its refresh tx.
+
+use std::sync::Mutex;
+
+use ergo_lib::chain::transaction::Transaction;
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use once_cell::sync::Lazy;
+
+use crate::node_interface::TxId;
+
+/// The pending txs of the process, shared by the scans and the wallet
+pub static PENDING_OUTPUTS: Lazy<PendingOutputs> = Lazy::new(PendingOutputs::new);
+
+pub struct PendingOutputs {
+    txs: Mutex<Vec<Transaction>>,
+}
+
+impl PendingOutputs {
+    pub fn new() -> Self {
+        PendingOutputs {
+            txs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Track a submitted tx, its outputs are spendable right away
+    pub fn track(&self, tx: Transaction) {
+        self.txs.lock().unwrap().push(tx);
+    }
+
+    /// Drop the txs which are not in `unconfirmed_tx_ids` anymore (confirmed or dropped)
+    pub fn retain_unconfirmed(&self, unconfirmed_tx_ids: &[TxId]) {
+        self.txs
+            .lock()
+            .unwrap()
+            .retain(|tx| unconfirmed_tx_ids.contains(&String::from(tx.id().0)));
+    }
+
+    pub fn tx_ids(&self) -> Vec<TxId> {
+        self.txs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|tx| String::from(tx.id().0))
+            .collect()
+    }
+
+    /// `boxes` without the ones spent by the pending txs, preceded by the unspent outputs of the
+    /// pending txs accepted by `tracks` (latest tx first)
+    pub fn apply(&self, boxes: Vec<ErgoBox>, tracks: impl Fn(&ErgoBox) -> bool) -> Vec<ErgoBox> {
+        let txs = self.txs.lock().unwrap();
+        if txs.is_empty() {
+            return boxes;
+        }
+        let is_spent = |b: &ErgoBox| {
+            txs.iter()
+                .any(|tx| tx.inputs.iter().any(|i| i.box_id == b.box_id()))
+        };
+        let mut applied: Vec<ErgoBox> = txs
+            .iter()
+            .rev()
+            .flat_map(|tx| tx.outputs.iter().cloned())
+            .filter(|b| !is_spent(b) && tracks(b))
+            .collect();
+        applied.extend(boxes.into_iter().filter(|b| !is_spent(b)));
+        applied
+    }
+}
+
+impl Default for PendingOutputs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+    use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBoxCandidate;
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_apply_pending_outputs() {
+        let pending = PendingOutputs::new();
+        let first_tx = force_any_val::<Transaction>();
+        let confirmed_box = force_any_val::<ErgoBox>();
+        assert_eq!(
+            pending.apply(vec![confirmed_box.clone()], |_| true),
+            vec![confirmed_box.clone()]
+        );
+        pending.track(first_tx.clone());
+        let first_outputs: Vec<ErgoBox> = first_tx.outputs.iter().cloned().collect();
+        let mut expected = first_outputs.clone();
+        expected.push(confirmed_box.clone());
+        assert_eq!(
+            pending.apply(vec![confirmed_box.clone()], |_| true),
+            expected
+        );
+        assert_eq!(
+            pending.apply(vec![confirmed_box.clone()], |_| false),
+            vec![confirmed_box.clone()]
+        );
+
+        // The pending outputs spent by a later pending tx are dropped
+        let any_tx = force_any_val::<Transaction>();
+        let mut inputs = any_tx.inputs.as_vec().clone();
+        inputs[0].box_id = first_outputs[0].box_id();
+        let second_tx = Transaction::new(
+            inputs.try_into().unwrap(),
+            None,
+            any_tx.outputs.mapped(ErgoBoxCandidate::from),
+        )
+        .unwrap();
+        pending.track(second_tx.clone());
+        let applied = pending.apply(vec![], |_| true);
+        assert!(!applied.contains(&first_outputs[0]));
+        assert_eq!(applied[0], *second_tx.outputs.first());
+
+        pending.retain_unconfirmed(&[String::from(second_tx.id().0)]);
+        assert_eq!(pending.tx_ids(), vec![String::from(second_tx.id().0)]);
+    }
+}
@@ -0,0 +1,344 @@
+//! Archive of the historical pool boxes. The pool boxes holding the pool NFT (spent ones
+//! included) are backfilled from the explorer, and their height, rate and epoch counter are saved
+//! locally in `pool_archive.json` to serve the historical rate series from the CLI and the REST API.
+
+use derive_more::From;
+use ergo_lib::ergotree_ir::chain::address::NetworkPrefix;
+use ergo_lib::ergotree_ir::chain::token::TokenId;
+use ergo_lib::ergotree_ir::mir::constant::{Constant, TryExtractInto};
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+pub const POOL_ARCHIVE_FILE: &str = "pool_archive.json";
+
+/// Number of boxes requested from the explorer at once
+const EXPLORER_PAGE_LIMIT: usize = 100;
+
+#[derive(Debug, From, Error)]
+pub enum PoolArchiveError {
+    #[error("Reqwest error: {0}")]
+    Reqwest(reqwest::Error),
+    #[error("JSON parse error: {0}")]
+    JsonParse(json::Error),
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("archive file error: {0}")]
+    ArchiveFile(serde_json::Error),
+    #[error("unexpected explorer response: {0}")]
+    UnexpectedExplorerResponse(String),
+    #[error("the archive holds the pool boxes of pool NFT {found}, expected {expected}")]
+    PoolNftMismatch { found: String, expected: String },
+}
+
+/// A pool box of the pool history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedPoolBox {
+    pub box_id: String,
+    pub height: u32,
+    pub rate: i64,
+    pub epoch_counter: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolArchive {
+    pub pool_nft_token_id: String,
+    /// Sorted by height
+    pub pool_boxes: Vec<ArchivedPoolBox>,
+}
+
+impl PoolArchive {
+    pub fn new(pool_nft_token_id: &TokenId) -> Self {
+        PoolArchive {
+            pool_nft_token_id: String::from(pool_nft_token_id.clone()),
+            pool_boxes: Vec::new(),
+        }
+    }
+
+    /// Load the archive of the pool, an empty one if the file doesn't exist
+    pub fn load(path: &Path, pool_nft_token_id: &TokenId) -> Result<Self, PoolArchiveError> {
+        if !path.exists() {
+            return Ok(PoolArchive::new(pool_nft_token_id));
+        }
+        let archive: PoolArchive = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let expected = String::from(pool_nft_token_id.clone());
+        if archive.pool_nft_token_id != expected {
+            return Err(PoolArchiveError::PoolNftMismatch {
+                found: archive.pool_nft_token_id,
+                expected,
+            });
+        }
+        Ok(archive)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), PoolArchiveError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Add the pool boxes not archived yet. Returns the number of added boxes.
+    pub fn merge(&mut self, pool_boxes: Vec<ArchivedPoolBox>) -> usize {
+        let count = self.pool_boxes.len();
+        for pool_box in pool_boxes {
+            if !self.pool_boxes.iter().any(|b| b.box_id == pool_box.box_id) {
+                self.pool_boxes.push(pool_box);
+            }
+        }
+        self.pool_boxes.sort_by_key(|b| (b.height, b.epoch_counter));
+        self.pool_boxes.len() - count
+    }
+
+    /// The first `limit` points of the pool boxes created between the heights `from` and `to`
+    /// (inclusive), down-sampled to one point per `interval` if given
+    pub fn history(
+        &self,
+        from: Option<u32>,
+        to: Option<u32>,
+        limit: usize,
+        interval: Option<HistoryInterval>,
+    ) -> HistoryPage {
+        let bucket_blocks = interval.map_or(1, |i| i.blocks());
+        let mut buckets: Vec<Vec<&ArchivedPoolBox>> = Vec::new();
+        for pool_box in self.pool_boxes.iter().filter(|b| {
+            from.map_or(true, |from| b.height >= from) && to.map_or(true, |to| b.height <= to)
+        }) {
+            let bucket_start = pool_box.height / bucket_blocks * bucket_blocks;
+            match buckets.last_mut() {
+                Some(bucket)
+                    if bucket[0].height / bucket_blocks * bucket_blocks == bucket_start =>
+                {
+                    bucket.push(pool_box)
+                }
+                Some(_) | None => buckets.push(vec![pool_box]),
+            }
+        }
+        let next_from = buckets
+            .get(limit)
+            .map(|bucket| bucket[0].height / bucket_blocks * bucket_blocks);
+        let points = buckets
+            .into_iter()
+            .take(limit)
+            .map(|bucket| {
+                let last = bucket[bucket.len() - 1];
+                let rates = bucket.iter().map(|b| b.rate);
+                HistoryPoint {
+                    height: bucket[0].height / bucket_blocks * bucket_blocks,
+                    epoch_counter: last.epoch_counter,
+                    rate: last.rate,
+                    min_rate: rates.clone().min().unwrap_or_default(),
+                    max_rate: rates.clone().max().unwrap_or_default(),
+                    avg_rate: (rates.map(i128::from).sum::<i128>() / bucket.len() as i128) as i64,
+                    pool_boxes: bucket.len(),
+                }
+            })
+            .collect();
+        HistoryPage { points, next_from }
+    }
+}
+
+/// Down-sampling of the rate history to one point per bucket of blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryInterval {
+    Hourly,
+    Daily,
+}
+
+impl HistoryInterval {
+    /// Buckets of blocks at the 2 minutes target block time of Ergo
+    pub fn blocks(&self) -> u32 {
+        match self {
+            HistoryInterval::Hourly => 30,
+            HistoryInterval::Daily => 720,
+        }
+    }
+}
+
+/// A pool box, or the aggregate of the pool boxes of a bucket when down-sampled
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct HistoryPoint {
+    /// Height of the pool box, first height of the bucket when down-sampled
+    pub height: u32,
+    /// Epoch counter and rate of the last pool box of the bucket
+    pub epoch_counter: u32,
+    pub rate: i64,
+    pub min_rate: i64,
+    pub max_rate: i64,
+    pub avg_rate: i64,
+    pub pool_boxes: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct HistoryPage {
+    pub points: Vec<HistoryPoint>,
+    /// `from` of the next page, `None` on the last page
+    pub next_from: Option<u32>,
+}
+
+/// Public explorer API of the network
+pub fn default_explorer_url(prefix: NetworkPrefix) -> &'static str {
+    match prefix {
+        NetworkPrefix::Mainnet => "https://api.ergoplatform.com",
+        NetworkPrefix::Testnet => "https://api-testnet.ergoplatform.com",
+    }
+}
+
+/// Fetch all the pool boxes (spent ones included) holding the pool NFT from the explorer
+pub fn fetch_pool_boxes(
+    explorer_url: &str,
+    pool_nft_token_id: &TokenId,
+) -> Result<Vec<ArchivedPoolBox>, PoolArchiveError> {
+    let client = reqwest::blocking::Client::new();
+    let token_id = String::from(pool_nft_token_id.clone());
+    let mut pool_boxes = Vec::new();
+    let mut offset = 0;
+    loop {
+        let url = format!(
+            "{}/api/v1/boxes/byTokenId/{}?offset={}&limit={}",
+            explorer_url.trim_end_matches('/'),
+            token_id,
+            offset,
+            EXPLORER_PAGE_LIMIT
+        );
+        let page = json::parse(&client.get(&url).send()?.text()?)?;
+        let items = &page["items"];
+        if !items.is_array() {
+            return Err(PoolArchiveError::UnexpectedExplorerResponse(page.dump()));
+        }
+        pool_boxes.extend(parse_explorer_boxes(items));
+        offset += items.len();
+        log::debug!("Fetched {} pool boxes from the explorer", offset);
+        if items.len() < EXPLORER_PAGE_LIMIT {
+            break;
+        }
+    }
+    Ok(pool_boxes)
+}
+
+/// Pool boxes of an explorer box list. Boxes without a rate (R4) and epoch counter (R5) are
+/// skipped.
+pub fn parse_explorer_boxes(items: &json::JsonValue) -> Vec<ArchivedPoolBox> {
+    let register = |item: &json::JsonValue, id: &str| -> Option<Constant> {
+        let bytes =
+            base16::decode(item["additionalRegisters"][id]["serializedValue"].as_str()?).ok()?;
+        Constant::sigma_parse_bytes(&bytes).ok()
+    };
+    items
+        .members()
+        .filter_map(|item| {
+            let pool_box = ArchivedPoolBox {
+                box_id: item["boxId"].as_str()?.to_string(),
+                height: item["creationHeight"].as_u32()?,
+                rate: register(item, "R4")?.try_extract_into::<i64>().ok()?,
+                epoch_counter: register(item, "R5")?.try_extract_into::<i32>().ok()? as u32,
+            };
+            Some(pool_box)
+        })
+        .collect()
+}
+
+/// Fetch the pool boxes from the explorer and add the ones missing in the archive file. Returns
+/// the updated archive and the number of added boxes.
+pub fn backfill(
+    path: &Path,
+    explorer_url: &str,
+    pool_nft_token_id: &TokenId,
+) -> Result<(PoolArchive, usize), PoolArchiveError> {
+    let mut archive = PoolArchive::load(path, pool_nft_token_id)?;
+    let added = archive.merge(fetch_pool_boxes(explorer_url, pool_nft_token_id)?);
+    archive.save(path)?;
+    Ok((archive, added))
+}
+
+#[cfg(test)]
+mod tests {
+    use ergo_lib::ergo_chain_types::Digest32;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    fn serialized(c: Constant) -> String {
+        base16::encode_lower(&c.sigma_serialize_bytes().unwrap())
+    }
+
+    #[test]
+    fn test_parse_explorer_boxes_and_merge() {
+        let pool_box_json = |box_id: &str, height: u32, rate: i64, epoch_counter: i32| {
+            format!(
+                r#"{{ "boxId": "{}", "creationHeight": {}, "additionalRegisters": {{
+                    "R4": {{ "serializedValue": "{}" }},
+                    "R5": {{ "serializedValue": "{}" }} }} }}"#,
+                box_id,
+                height,
+                serialized(rate.into()),
+                serialized(epoch_counter.into())
+            )
+        };
+        let items = json::parse(&format!(
+            r#"[{}, {}, {{ "boxId": "x", "creationHeight": 110, "additionalRegisters": {{}} }}]"#,
+            pool_box_json("b2", 120, 210, 2),
+            pool_box_json("b1", 100, 200, 1),
+        ))
+        .unwrap();
+        let pool_boxes = parse_explorer_boxes(&items);
+        assert_eq!(pool_boxes.len(), 2);
+
+        let mut archive = PoolArchive::new(&force_any_val::<Digest32>().into());
+        assert_eq!(archive.merge(pool_boxes.clone()), 2);
+        assert_eq!(archive.merge(pool_boxes), 0);
+        assert_eq!(
+            archive.pool_boxes[0],
+            ArchivedPoolBox {
+                box_id: "b1".to_string(),
+                height: 100,
+                rate: 200,
+                epoch_counter: 1,
+            }
+        );
+        assert_eq!(archive.pool_boxes[1].rate, 210);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut archive = PoolArchive::new(&force_any_val::<Digest32>().into());
+        archive.merge(
+            vec![(700, 100), (710, 110), (725, 120), (740, 150), (1500, 200)]
+                .into_iter()
+                .enumerate()
+                .map(|(i, (height, rate))| ArchivedPoolBox {
+                    box_id: i.to_string(),
+                    height,
+                    rate,
+                    epoch_counter: i as u32,
+                })
+                .collect(),
+        );
+        let page = archive.history(Some(710), None, 2, None);
+        assert_eq!(
+            page.points.iter().map(|p| p.rate).collect::<Vec<_>>(),
+            vec![110, 120]
+        );
+        assert_eq!(page.next_from, Some(740));
+        let page = archive.history(page.next_from, Some(1000), 2, None);
+        assert_eq!(page.points.len(), 1);
+        assert_eq!(page.next_from, None);
+
+        let page = archive.history(None, None, 10, Some(HistoryInterval::Daily));
+        assert_eq!(page.points.len(), 3);
+        assert_eq!(
+            page.points[1],
+            HistoryPoint {
+                height: 720,
+                epoch_counter: 3,
+                rate: 150,
+                min_rate: 120,
+                max_rate: 150,
+                avg_rate: 135,
+                pool_boxes: 2,
+            }
+        );
+        assert_eq!(page.points[0].avg_rate, 105);
+    }
+}
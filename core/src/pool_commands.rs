@@ -3,7 +3,7 @@ use ergo_lib::ergo_chain_types::DigestNError;
 use ergo_lib::ergotree_ir::chain::address::{Address, AddressEncoderError};
 use thiserror::Error;
 
-use crate::actions::PoolAction;
+use crate::actions::{PoolAction, RefreshAction};
 use crate::box_kind::PoolBox;
 use crate::oracle_config::ORACLE_CONFIG;
 use crate::oracle_state::{OraclePool, StageError};
@@ -13,7 +13,7 @@ use self::publish_datapoint::build_publish_first_datapoint_action;
 use self::publish_datapoint::{
     build_subsequent_publish_datapoint_action, PublishDatapointActionError,
 };
-use self::refresh::build_refresh_action;
+use self::refresh::BoxTopUp;
 use self::refresh::RefreshActionError;
 
 pub mod publish_datapoint;
@@ -31,7 +31,7 @@ pub enum PoolCommand {
 #[derive(Debug, From, Error)]
 pub enum PoolCommandError {
     #[error("stage error: {0}")]
-    StageError(StageError),
+    StageError(#[source] StageError),
     #[error("unexpected error: {0}")]
     Unexpected(String),
     #[error("error on building RefreshAction: {0}")]
@@ -41,7 +41,7 @@ pub enum PoolCommandError {
     #[error("Digest error: {0}")]
     Digest(DigestNError),
     #[error("Address encoder error: {0}")]
-    AddressEncoder(AddressEncoderError),
+    AddressEncoder(#[source] AddressEncoderError),
     #[error("Wrong oracle address type")]
     WrongOracleAddressType,
 }
@@ -53,8 +53,6 @@ pub fn build_action(
     height: u32,
     change_address: Address,
 ) -> Result<PoolAction, PoolCommandError> {
-    let refresh_box_source = op.get_refresh_box_source();
-    let datapoint_stage_src = op.get_datapoint_boxes_source();
     let pool_box = op.get_pool_box_source().get_pool_box()?;
     let current_epoch_counter = pool_box.epoch_counter();
     let oracle_public_key =
@@ -65,16 +63,22 @@ pub fn build_action(
         };
 
     match cmd {
-        PoolCommand::PublishFirstDataPoint => build_publish_first_datapoint_action(
-            wallet,
-            height,
-            change_address,
-            oracle_public_key,
-            ORACLE_CONFIG.oracle_box_wrapper_inputs.clone(),
-            &*op.data_point_source,
-        )
-        .map_err(Into::into)
-        .map(Into::into),
+        PoolCommand::PublishFirstDataPoint => {
+            // The reward token can be changed in the update pool transaction, so the pool box is
+            // the authority on its id rather than the config.
+            let mut oracle_box_wrapper_inputs = ORACLE_CONFIG.oracle_box_wrapper_inputs.clone();
+            oracle_box_wrapper_inputs.reward_token_id = pool_box.reward_token().token_id;
+            build_publish_first_datapoint_action(
+                wallet,
+                height,
+                change_address,
+                oracle_public_key,
+                oracle_box_wrapper_inputs,
+                &*op.data_point_source,
+            )
+            .map_err(Into::into)
+            .map(Into::into)
+        }
         PoolCommand::PublishSubsequentDataPoint { republish: _ } => {
             if let Some(local_datapoint_box) = op
                 .get_local_datapoint_box_source()
@@ -88,7 +92,7 @@ pub fn build_action(
                     change_address,
                     &*op.data_point_source,
                     new_epoch_counter,
-                    pool_box.rate(),
+                    pool_box.reward_token().token_id,
                 )
                 .map_err(Into::into)
                 .map(Into::into)
@@ -98,26 +102,43 @@ pub fn build_action(
                 ))
             }
         }
-        PoolCommand::Refresh => build_refresh_action(
-            op.get_pool_box_source(),
-            refresh_box_source,
-            datapoint_stage_src,
-            ORACLE_CONFIG
-                .refresh_box_wrapper_inputs
-                .contract_inputs
-                .contract_parameters()
-                .max_deviation_percent() as u32,
-            ORACLE_CONFIG
-                .refresh_box_wrapper_inputs
-                .contract_inputs
-                .contract_parameters()
-                .min_data_points() as u32,
-            wallet,
-            height,
-            change_address,
-            oracle_public_key.h.as_ref(),
-        )
-        .map_err(Into::into)
-        .map(Into::into),
+        PoolCommand::Refresh => {
+            build_pool_refresh_action(op, wallet, height, change_address, BoxTopUp::default())
+                .map(Into::into)
+        }
     }
 }
+
+/// Refresh tx of the pool adding the `top_up` to the pool and refresh boxes
+pub fn build_pool_refresh_action(
+    op: &OraclePool,
+    wallet: &dyn WalletDataSource,
+    height: u32,
+    change_address: Address,
+    top_up: BoxTopUp,
+) -> Result<RefreshAction, PoolCommandError> {
+    let oracle_public_key =
+        if let Address::P2Pk(public_key) = ORACLE_CONFIG.oracle_address.address() {
+            public_key
+        } else {
+            return Err(PoolCommandError::WrongOracleAddressType);
+        };
+    let refresh_contract_parameters = ORACLE_CONFIG
+        .refresh_box_wrapper_inputs
+        .contract_inputs
+        .contract_parameters();
+    refresh::build_refresh_action_with_top_up(
+        op.get_pool_box_source(),
+        op.get_refresh_box_source(),
+        op.get_datapoint_boxes_source(),
+        op.get_buyback_box_source(),
+        refresh_contract_parameters.max_deviation_percent() as u32,
+        refresh_contract_parameters.min_data_points() as u32,
+        wallet,
+        height,
+        change_address,
+        oracle_public_key.h.as_ref(),
+        top_up,
+    )
+    .map_err(Into::into)
+}
@@ -2,13 +2,14 @@ use std::convert::{TryFrom, TryInto};
 
 use derive_more::From;
 use ergo_lib::{
-    chain::ergo_box::box_builder::ErgoBoxCandidateBuilderError,
+    chain::ergo_box::box_builder::{ErgoBoxCandidateBuilder, ErgoBoxCandidateBuilderError},
     ergotree_interpreter::sigma_protocol::prover::ContextExtension,
     ergotree_ir::{
         chain::{
             address::Address,
-            token::{Token, TokenAmount},
+            token::{Token, TokenAmount, TokenId},
         },
+        serialization::SigmaParsingError,
         sigma_protocol::sigma_boolean::ProveDlog,
     },
     wallet::{
@@ -23,7 +24,9 @@ use crate::{
     box_kind::{make_oracle_box_candidate, OracleBox, OracleBoxWrapper, OracleBoxWrapperInputs},
     contracts::oracle::{OracleContract, OracleContractError},
     datapoint_source::{DataPointSource, DataPointSourceError},
-    oracle_config::BASE_FEE,
+    fee_estimation::apply_estimated_fee,
+    min_box_value::{check_min_box_values, MinBoxValueError},
+    oracle_config::{tx_fee, TxFeeAction, BASE_FEE},
     oracle_state::StageError,
     wallet::{WalletDataError, WalletDataSource},
 };
@@ -31,7 +34,7 @@ use crate::{
 #[derive(Debug, Error, From)]
 pub enum PublishDatapointActionError {
     #[error("stage error: {0}")]
-    StageError(StageError),
+    StageError(#[source] StageError),
     #[error("Oracle box has no reward token")]
     NoRewardTokenInOracleBox,
     #[error("tx builder error: {0}")]
@@ -39,15 +42,23 @@ pub enum PublishDatapointActionError {
     #[error("box builder error: {0}")]
     ErgoBoxCandidateBuilder(ErgoBoxCandidateBuilderError),
     #[error("WalletData error: {0}")]
-    WalletData(WalletDataError),
+    WalletData(#[source] WalletDataError),
     #[error("box selector error: {0}")]
     BoxSelector(BoxSelectorError),
     #[error("datapoint source error: {0}")]
     DataPointSource(DataPointSourceError),
     #[error("oracle contract error: {0}")]
     OracleContract(OracleContractError),
+    #[error("Sigma parsing error: {0}")]
+    SigmaParse(SigmaParsingError),
+    #[error("{0}")]
+    MinBoxValue(MinBoxValueError),
 }
 
+/// Builds the transaction publishing a new datapoint in the local oracle box. If the oracle box
+/// holds a reward token other than `pool_reward_token_id` (the reward token was changed in the
+/// pool box), it is swapped for one reward token of the pool from the wallet and the old reward
+/// tokens are sent to `change_address`.
 pub fn build_subsequent_publish_datapoint_action(
     local_datapoint_box: &OracleBoxWrapper,
     wallet: &dyn WalletDataSource,
@@ -55,13 +66,29 @@ pub fn build_subsequent_publish_datapoint_action(
     change_address: Address,
     datapoint_source: &dyn DataPointSource,
     new_epoch_counter: u32,
-    _pool_datapoint: i64,
+    pool_reward_token_id: TokenId,
 ) -> Result<PublishDataPointAction, PublishDatapointActionError> {
     let new_datapoint = datapoint_source.get_datapoint_retry(3)?;
     let in_oracle_box = local_datapoint_box;
     if *in_oracle_box.reward_token().amount.as_u64() == 0 {
         return Err(PublishDatapointActionError::NoRewardTokenInOracleBox);
     }
+    let tx_fee = tx_fee(TxFeeAction::PublishDatapoint);
+    let mut target_balance = tx_fee;
+    let mut target_tokens = vec![];
+    let mut reward_token = in_oracle_box.reward_token();
+    let mut old_reward_tokens_box = None;
+    if reward_token.token_id != pool_reward_token_id {
+        let mut builder = ErgoBoxCandidateBuilder::new(*BASE_FEE, change_address.script()?, height);
+        builder.add_token(reward_token);
+        old_reward_tokens_box = Some(builder.build()?);
+        target_balance = target_balance.checked_add(&BASE_FEE).unwrap();
+        reward_token = Token {
+            token_id: pool_reward_token_id,
+            amount: TokenAmount::try_from(1).unwrap(),
+        };
+        target_tokens.push(reward_token.clone());
+    }
 
     let output_candidate = make_oracle_box_candidate(
         in_oracle_box.contract(),
@@ -69,27 +96,29 @@ pub fn build_subsequent_publish_datapoint_action(
         new_datapoint,
         new_epoch_counter,
         in_oracle_box.oracle_token(),
-        in_oracle_box.reward_token(),
+        reward_token,
         in_oracle_box.get_box().value,
         height,
     )?;
+    let mut output_candidates = vec![output_candidate];
+    output_candidates.extend(old_reward_tokens_box);
 
     let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
-    let tx_fee = *BASE_FEE;
     let box_selector = SimpleBoxSelector::new();
-    let selection = box_selector.select(unspent_boxes, tx_fee, &[])?;
+    let selection = box_selector.select(unspent_boxes, target_balance, &target_tokens)?;
     let mut input_boxes = vec![in_oracle_box.get_box().clone()];
     input_boxes.append(selection.boxes.as_vec().clone().as_mut());
     let box_selection = BoxSelection {
         boxes: input_boxes.try_into().unwrap(),
         change_boxes: selection.change_boxes,
     };
+    check_min_box_values(&output_candidates)?;
     let mut tx_builder = TxBuilder::new(
         box_selection,
-        vec![output_candidate],
+        output_candidates,
         height,
         tx_fee,
-        change_address,
+        change_address.clone(),
     );
 
     // The following context value ensures that `outIndex` in the oracle contract is properly set.
@@ -97,8 +126,15 @@ pub fn build_subsequent_publish_datapoint_action(
         values: vec![(0, 0i32.into())].into_iter().collect(),
     };
     tx_builder.set_context_extension(in_oracle_box.get_box().box_id(), ctx_ext);
-    let tx = tx_builder.build()?;
-    Ok(PublishDataPointAction { tx })
+    let tx = apply_estimated_fee(
+        tx_builder.build()?,
+        TxFeeAction::PublishDatapoint,
+        &change_address,
+    );
+    Ok(PublishDataPointAction {
+        tx,
+        datapoint: new_datapoint,
+    })
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -112,7 +148,7 @@ pub fn build_publish_first_datapoint_action(
 ) -> Result<PublishDataPointAction, PublishDatapointActionError> {
     let new_datapoint = datapoint_source.get_datapoint_retry(3)?;
     let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
-    let tx_fee = *BASE_FEE;
+    let tx_fee = tx_fee(TxFeeAction::PublishDatapoint);
     let box_selector = SimpleBoxSelector::new();
     let oracle_token = Token {
         token_id: inputs.oracle_token_id.clone(),
@@ -145,12 +181,14 @@ pub fn build_publish_first_datapoint_action(
     )?;
 
     let box_id = wallet_boxes_selection.boxes.first().box_id();
+    let output_candidates = vec![output_candidate];
+    check_min_box_values(&output_candidates)?;
     let mut tx_builder = TxBuilder::new(
         wallet_boxes_selection,
-        vec![output_candidate],
+        output_candidates,
         height,
         tx_fee,
-        change_address,
+        change_address.clone(),
     );
 
     // The following context value ensures that `outIndex` in the oracle contract is properly set.
@@ -158,8 +196,15 @@ pub fn build_publish_first_datapoint_action(
         values: vec![(0, 0i32.into())].into_iter().collect(),
     };
     tx_builder.set_context_extension(box_id, ctx_ext);
-    let tx = tx_builder.build()?;
-    Ok(PublishDataPointAction { tx })
+    let tx = apply_estimated_fee(
+        tx_builder.build()?,
+        TxFeeAction::PublishDatapoint,
+        &change_address,
+    );
+    Ok(PublishDataPointAction {
+        tx,
+        datapoint: new_datapoint,
+    })
 }
 
 #[cfg(test)]
@@ -208,7 +253,6 @@ mod tests {
         let oracle_contract_parameters = OracleContractParameters::default();
         let pool_contract_parameters = PoolContractParameters::default();
         let pool_box_epoch_id = 1;
-        dbg!(&reward_token_id);
         let in_pool_box = make_pool_box(
             200,
             pool_box_epoch_id,
@@ -265,7 +309,7 @@ mod tests {
             change_address.clone(),
             &datapoint_source,
             pool_box_epoch_id as u32,
-            datapoint_source.datapoint - 1,
+            token_ids.reward_token_id.clone(),
         )
         .unwrap();
 
@@ -305,6 +349,81 @@ mod tests {
         //     .unwrap();
     }
 
+    #[test]
+    fn test_subsequent_publish_datapoint_swaps_reward_token() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = ctx.pre_header.height;
+        let token_ids = generate_token_ids();
+        let new_reward_token_id = force_any_val::<TokenId>();
+        let secret = force_any_val::<DlogProverInput>();
+        let wallet = Wallet::from_secrets(vec![secret.clone().into()]);
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let oracle_box = OracleBoxWrapper::new(
+            make_datapoint_box(
+                *secret.public_image().h,
+                200,
+                1,
+                &token_ids,
+                BASE_FEE.checked_mul_u32(100).unwrap(),
+                height - 99,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        let change_address = Address::P2Pk(secret.public_image());
+        let wallet_mock = WalletDataMock {
+            unspent_boxes: vec![make_wallet_unspent_box(
+                secret.public_image(),
+                BASE_FEE.checked_mul_u32(10000).unwrap(),
+                Some(
+                    BoxTokens::from_vec(vec![Token::from((
+                        new_reward_token_id.clone(),
+                        10u64.try_into().unwrap(),
+                    ))])
+                    .unwrap(),
+                ),
+            )],
+        };
+
+        let action = build_subsequent_publish_datapoint_action(
+            &oracle_box,
+            &wallet_mock,
+            height,
+            change_address.clone(),
+            &MockDatapointSource { datapoint: 201 },
+            2,
+            new_reward_token_id.clone(),
+        )
+        .unwrap();
+        let out_oracle_box_tokens = action.tx.output_candidates.first().tokens.clone().unwrap();
+        assert_eq!(
+            out_oracle_box_tokens.get(1).unwrap().token_id,
+            new_reward_token_id
+        );
+        assert_eq!(*out_oracle_box_tokens.get(1).unwrap().amount.as_u64(), 1);
+        // The old reward tokens are sent to the change address
+        assert!(action.tx.output_candidates.iter().any(|b| {
+            b.ergo_tree == change_address.script().unwrap()
+                && b.tokens.as_ref().map_or(false, |tokens| {
+                    tokens.iter().any(|t| {
+                        t.token_id == token_ids.reward_token_id && *t.amount.as_u64() == 100
+                    })
+                })
+        }));
+
+        let mut possible_input_boxes = vec![oracle_box.get_box().clone()];
+        possible_input_boxes.append(&mut wallet_mock.get_unspent_wallet_boxes().unwrap());
+        let tx_context = TransactionContext::new(
+            action.tx.clone(),
+            find_input_boxes(action.tx, possible_input_boxes),
+            Vec::new(),
+        )
+        .unwrap();
+        let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
+    }
+
     #[test]
     fn test_first_publish_datapoint() {
         let ctx = force_any_val::<ErgoStateContext>();
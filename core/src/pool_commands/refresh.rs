@@ -1,13 +1,20 @@
 use crate::actions::RefreshAction;
+use crate::box_kind::make_buyback_box_candidate;
 use crate::box_kind::make_collected_oracle_box_candidate;
 use crate::box_kind::make_pool_box_candidate;
 use crate::box_kind::make_refresh_box_candidate;
+use crate::box_kind::BuybackBoxWrapper;
 use crate::box_kind::PoolBox;
 use crate::box_kind::PoolBoxWrapper;
 use crate::box_kind::PostedOracleBox;
 use crate::box_kind::RefreshBox;
 use crate::box_kind::RefreshBoxWrapper;
-use crate::oracle_config::BASE_FEE;
+use crate::fee_estimation::apply_estimated_fee;
+use crate::min_box_value::check_min_box_values;
+use crate::min_box_value::MinBoxValueError;
+use crate::oracle_config::tx_fee;
+use crate::oracle_config::TxFeeAction;
+use crate::oracle_state::BuybackBoxSource;
 use crate::oracle_state::DatapointBoxesSource;
 use crate::oracle_state::PoolBoxSource;
 use crate::oracle_state::RefreshBoxSource;
@@ -20,9 +27,12 @@ use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilderError;
 use ergo_lib::ergo_chain_types::EcPoint;
 use ergo_lib::ergotree_interpreter::sigma_protocol::prover::ContextExtension;
 use ergo_lib::ergotree_ir::chain::address::Address;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValueError;
 use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBoxCandidate;
 use ergo_lib::ergotree_ir::chain::token::Token;
 use ergo_lib::ergotree_ir::chain::token::TokenAmount;
+use ergo_lib::ergotree_ir::chain::token::TokenAmountError;
 use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
 use ergo_lib::wallet::box_selector::BoxSelection;
 use ergo_lib::wallet::box_selector::BoxSelector;
@@ -32,6 +42,7 @@ use ergo_lib::wallet::tx_builder::TxBuilder;
 use ergo_lib::wallet::tx_builder::TxBuilderError;
 use thiserror::Error;
 
+use std::convert::TryFrom;
 use std::convert::TryInto;
 
 #[derive(Debug, From, Error)]
@@ -45,9 +56,9 @@ pub enum RefreshActionError {
     #[error("Not enough datapoints left during the removal of the outliers")]
     NotEnoughDatapoints,
     #[error("stage error: {0}")]
-    StageError(StageError),
+    StageError(#[source] StageError),
     #[error("WalletData error: {0}")]
-    WalletData(WalletDataError),
+    WalletData(#[source] WalletDataError),
     #[error("box selector error: {0}")]
     BoxSelectorError(BoxSelectorError),
     #[error("tx builder error: {0}")]
@@ -56,13 +67,37 @@ pub enum RefreshActionError {
     ErgoBoxCandidateBuilderError(ErgoBoxCandidateBuilderError),
     #[error("failed to found my own oracle box in the filtered posted oracle boxes")]
     MyOracleBoxNoFound,
+    #[error("box value error: {0}")]
+    BoxValue(BoxValueError),
+    #[error("the top-up overflows the box value")]
+    TopUpOverflow,
+    #[error("the pool box holds {available} reward tokens, {decrement} are needed for the oracle rewards")]
+    NotEnoughRewardTokens { available: u64, decrement: u64 },
+    #[error(
+        "the reward tokens of the buyback box overflow the reward token amount of the pool box"
+    )]
+    RewardTokenOverflow,
+    #[error("token amount error: {0}")]
+    TokenAmount(TokenAmountError),
+    #[error("{0}")]
+    MinBoxValue(MinBoxValueError),
+}
+
+/// nanoERGs added to the pool and refresh boxes by the refresh tx, from the wallet. The pool and
+/// refresh contracts only allow these boxes to be spent by the refresh and update txs, both
+/// accepting a higher value in the outputs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BoxTopUp {
+    pub pool: u64,
+    pub refresh: u64,
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn build_refresh_action(
     pool_box_source: &dyn PoolBoxSource,
     refresh_box_source: &dyn RefreshBoxSource,
-    datapoint_stage_src: &dyn DatapointBoxesSource,
+    datapoint_stage_src: &(dyn DatapointBoxesSource + Sync),
+    buyback_box_source: Option<&dyn BuybackBoxSource>,
     max_deviation_percent: u32,
     min_data_points: u32,
     wallet: &dyn WalletDataSource,
@@ -70,17 +105,69 @@ pub fn build_refresh_action(
     change_address: Address,
     my_oracle_pk: &EcPoint,
 ) -> Result<RefreshAction, RefreshActionError> {
-    let tx_fee = *BASE_FEE;
-    let in_pool_box = pool_box_source.get_pool_box()?;
-    let in_refresh_box = refresh_box_source.get_refresh_box()?;
+    build_refresh_action_with_top_up(
+        pool_box_source,
+        refresh_box_source,
+        datapoint_stage_src,
+        buyback_box_source,
+        max_deviation_percent,
+        min_data_points,
+        wallet,
+        height,
+        change_address,
+        my_oracle_pk,
+        BoxTopUp::default(),
+    )
+}
+
+/// Refresh tx adding the `top_up` to the pool and refresh boxes
+#[allow(clippy::too_many_arguments)]
+pub fn build_refresh_action_with_top_up(
+    pool_box_source: &dyn PoolBoxSource,
+    refresh_box_source: &dyn RefreshBoxSource,
+    datapoint_stage_src: &(dyn DatapointBoxesSource + Sync),
+    buyback_box_source: Option<&dyn BuybackBoxSource>,
+    max_deviation_percent: u32,
+    min_data_points: u32,
+    wallet: &dyn WalletDataSource,
+    height: u32,
+    change_address: Address,
+    my_oracle_pk: &EcPoint,
+    top_up: BoxTopUp,
+) -> Result<RefreshAction, RefreshActionError> {
+    let tx_fee = tx_fee(TxFeeAction::Refresh);
+    // The datapoint boxes are fetched and parsed on another thread meanwhile the other input boxes
+    // are fetched
+    let (in_pool_box, in_refresh_box, posted_oracle_boxes) = crossbeam::thread::scope(|s| {
+        let posted_oracle_boxes = s.spawn(|_| datapoint_stage_src.get_oracle_datapoint_boxes());
+        let in_pool_box = pool_box_source.get_pool_box()?;
+        let in_refresh_box = refresh_box_source.get_refresh_box()?;
+        let posted_oracle_boxes = posted_oracle_boxes
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e))?;
+        Ok::<_, StageError>((in_pool_box, in_refresh_box, posted_oracle_boxes))
+    })
+    .unwrap_or_else(|e| std::panic::resume_unwind(e))?;
     let min_start_height = height - in_refresh_box.contract().epoch_length() as u32;
     let in_pool_box_epoch_id = in_pool_box.epoch_counter();
-    let mut in_oracle_boxes: Vec<PostedOracleBox> = datapoint_stage_src
-        .get_oracle_datapoint_boxes()?
+    let reward_token_id = in_pool_box.reward_token().token_id;
+    // The buyback box is only spent when it holds reward tokens of the pool
+    let in_buyback_box: Option<BuybackBoxWrapper> = match buyback_box_source {
+        Some(source) => source.get_buyback_box()?,
+        None => None,
+    }
+    .filter(|b| {
+        b.reward_token()
+            .map_or(false, |t| t.token_id == reward_token_id)
+    });
+    let mut in_oracle_boxes: Vec<PostedOracleBox> = posted_oracle_boxes
         .into_iter()
         .filter(|b| {
             b.get_box().creation_height > min_start_height
                 && b.epoch_counter() == in_pool_box_epoch_id
+                // Oracle boxes still holding the previous reward token (after a reward token
+                // update) can't be rewarded from the pool box.
+                && b.reward_token().token_id == reward_token_id
         })
         .collect();
     // log::info!("Building refresh action {:?}", in_oracle_boxes);
@@ -106,14 +193,31 @@ pub fn build_refresh_action(
     }
     let rate = calc_pool_rate(valid_in_oracle_boxes.iter().map(|b| b.rate()).collect());
     let reward_decrement = valid_in_oracle_boxes.len() as u64 * 2;
-    let out_pool_box = build_out_pool_box(&in_pool_box, height, rate, reward_decrement)?;
-    let out_refresh_box = build_out_refresh_box(&in_refresh_box, height)?;
+    let reward_increment = in_buyback_box
+        .as_ref()
+        .and_then(|b| b.reward_token())
+        .map_or(0, |t| *t.amount.as_u64());
+    let out_pool_box = build_out_pool_box(
+        &in_pool_box,
+        height,
+        rate,
+        reward_decrement,
+        reward_increment,
+        top_up.pool,
+    )?;
+    let out_refresh_box = build_out_refresh_box(&in_refresh_box, height, top_up.refresh)?;
     let mut out_oracle_boxes =
         build_out_oracle_boxes(&valid_in_oracle_boxes, height, my_oracle_pk)?;
 
     let unspent_boxes = wallet.get_unspent_wallet_boxes()?;
     let box_selector = SimpleBoxSelector::new();
-    let selection = box_selector.select(unspent_boxes, tx_fee, &[])?;
+    let target_balance = top_up
+        .pool
+        .checked_add(top_up.refresh)
+        .and_then(|v| v.checked_add(*tx_fee.as_u64()))
+        .ok_or(RefreshActionError::TopUpOverflow)
+        .and_then(|v| Ok(BoxValue::try_from(v)?))?;
+    let selection = box_selector.select(unspent_boxes, target_balance, &[])?;
 
     let mut input_boxes = vec![
         in_pool_box.get_box().clone(),
@@ -131,6 +235,9 @@ pub fn build_refresh_action(
         .map(|ob| ob.get_box().clone())
         .collect();
     input_boxes.append(&mut valid_in_oracle_raw_boxes);
+    if let Some(buyback_box) = &in_buyback_box {
+        input_boxes.push(buyback_box.get_box().clone());
+    }
     input_boxes.append(selection.boxes.as_vec().clone().as_mut());
     let box_selection = BoxSelection {
         boxes: input_boxes.try_into().unwrap(),
@@ -139,13 +246,24 @@ pub fn build_refresh_action(
 
     let mut output_candidates = vec![out_pool_box, out_refresh_box];
     output_candidates.append(&mut out_oracle_boxes);
+    // The buyback box goes after the oracle boxes, without its reward tokens
+    let out_buyback_box_index = output_candidates.len() as i32;
+    if let Some(buyback_box) = &in_buyback_box {
+        output_candidates.push(make_buyback_box_candidate(
+            buyback_box.contract(),
+            buyback_box.buyback_nft_token(),
+            buyback_box.get_box().value,
+            height,
+        )?);
+    }
 
+    check_min_box_values(&output_candidates)?;
     let mut b = TxBuilder::new(
         box_selection,
         output_candidates,
         height as u32,
         tx_fee,
-        change_address,
+        change_address.clone(),
     );
     let in_refresh_box_ctx_ext = ContextExtension {
         values: vec![(0, my_input_oracle_box_index.into())]
@@ -163,7 +281,15 @@ pub fn build_refresh_action(
             };
             b.set_context_extension(ob.get_box().box_id(), ob_ctx_ext);
         });
-    let tx = b.build()?;
+    if let Some(buyback_box) = &in_buyback_box {
+        let buyback_box_ctx_ext = ContextExtension {
+            values: vec![(0, out_buyback_box_index.into())]
+                .into_iter()
+                .collect(),
+        };
+        b.set_context_extension(buyback_box.get_box().box_id(), buyback_box_ctx_ext);
+    }
+    let tx = apply_estimated_fee(b.build()?, TxFeeAction::Refresh, &change_address);
     Ok(RefreshAction { tx })
 }
 
@@ -180,7 +306,6 @@ fn filtered_oracle_boxes_by_rate(
         // Removing largest deviation outlier
         successful_boxes = remove_largest_local_deviation_datapoint(successful_boxes)?;
     }
-    // dbg!(&successful_boxes);
     Ok(successful_boxes)
 }
 
@@ -232,17 +357,22 @@ fn build_out_pool_box(
     creation_height: u32,
     rate: u64,
     reward_decrement: u64,
+    reward_increment: u64,
+    top_up: u64,
 ) -> Result<ErgoBoxCandidate, RefreshActionError> {
     let new_epoch_counter: i32 = (in_pool_box.epoch_counter() + 1) as i32;
     let reward_token = in_pool_box.reward_token();
-    let new_reward_token: Token = (
-        reward_token.token_id,
-        reward_token
-            .amount
-            .checked_sub(&reward_decrement.try_into().unwrap())
-            .unwrap(),
-    )
-        .into();
+    let available = *reward_token.amount.as_u64();
+    let new_reward_token_amount = available
+        .checked_sub(reward_decrement)
+        .ok_or(RefreshActionError::NotEnoughRewardTokens {
+            available,
+            decrement: reward_decrement,
+        })?
+        .checked_add(reward_increment)
+        .ok_or(RefreshActionError::RewardTokenOverflow)?;
+    let new_reward_token: Token =
+        (reward_token.token_id, new_reward_token_amount.try_into()?).into();
 
     make_pool_box_candidate(
         in_pool_box.contract(),
@@ -250,7 +380,7 @@ fn build_out_pool_box(
         new_epoch_counter,
         in_pool_box.pool_nft_token().clone(),
         new_reward_token,
-        in_pool_box.get_box().value,
+        topped_up(in_pool_box.get_box().value, top_up)?,
         creation_height,
     )
     .map_err(Into::into)
@@ -259,16 +389,25 @@ fn build_out_pool_box(
 fn build_out_refresh_box(
     in_refresh_box: &RefreshBoxWrapper,
     creation_height: u32,
+    top_up: u64,
 ) -> Result<ErgoBoxCandidate, RefreshActionError> {
     make_refresh_box_candidate(
         in_refresh_box.contract(),
         in_refresh_box.refresh_nft_token(),
-        in_refresh_box.get_box().value,
+        topped_up(in_refresh_box.get_box().value, top_up)?,
         creation_height,
     )
     .map_err(Into::into)
 }
 
+fn topped_up(value: BoxValue, top_up: u64) -> Result<BoxValue, RefreshActionError> {
+    value
+        .as_u64()
+        .checked_add(top_up)
+        .ok_or(RefreshActionError::TopUpOverflow)
+        .and_then(|v| Ok(BoxValue::try_from(v)?))
+}
+
 fn build_out_oracle_boxes(
     valid_oracle_boxes: &Vec<PostedOracleBox>,
     creation_height: u32,
@@ -308,7 +447,9 @@ mod tests {
     use std::convert::TryInto;
 
     use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::chain::transaction::unsigned::UnsignedTransaction;
     use ergo_lib::chain::transaction::TxId;
+    use ergo_lib::ergo_chain_types::Digest32;
     use ergo_lib::ergo_chain_types::EcPoint;
     use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
     use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
@@ -316,17 +457,17 @@ mod tests {
     use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
     use ergo_lib::ergotree_ir::chain::ergo_box::NonMandatoryRegisters;
     use ergo_lib::ergotree_ir::chain::token::Token;
+    use ergo_lib::ergotree_ir::chain::token::TokenId;
     use ergo_lib::wallet::signing::TransactionContext;
     use ergo_lib::wallet::Wallet;
     use sigma_test_util::force_any_val;
 
+    use crate::box_kind::OracleBoxWrapper;
     use crate::box_kind::OracleBoxWrapperInputs;
     use crate::box_kind::PostedOracleBox;
-    use crate::box_kind::RefreshBoxWrapper;
     use crate::box_kind::RefreshBoxWrapperInputs;
     use crate::contracts::oracle::OracleContractParameters;
     use crate::contracts::pool::PoolContractParameters;
-    use crate::contracts::refresh::RefreshContract;
     use crate::contracts::refresh::RefreshContractInputs;
     use crate::contracts::refresh::RefreshContractParameters;
     use crate::oracle_config::TokenIds;
@@ -334,65 +475,29 @@ mod tests {
     use crate::oracle_state::StageError;
     use crate::pool_commands::test_utils::generate_token_ids;
     use crate::pool_commands::test_utils::{
-        find_input_boxes, make_datapoint_box, make_pool_box, make_wallet_unspent_box, PoolBoxMock,
-        WalletDataMock,
+        find_input_boxes, make_datapoint_box, make_pool_box, make_refresh_box,
+        make_wallet_unspent_box, PoolBoxMock, RefreshBoxMock, WalletDataMock,
     };
+    use crate::tx_validation::{validate_tx, InputKind, PoolParameters};
 
     use super::*;
 
-    #[derive(Clone)]
-    struct RefreshBoxMock {
-        refresh_box: RefreshBoxWrapper,
-    }
-
-    impl RefreshBoxSource for RefreshBoxMock {
-        fn get_refresh_box(&self) -> std::result::Result<RefreshBoxWrapper, StageError> {
-            Ok(self.refresh_box.clone())
-        }
-    }
-
     #[derive(Clone)]
     struct DatapointStageMock {
         datapoints: Vec<PostedOracleBox>,
     }
 
     impl DatapointBoxesSource for DatapointStageMock {
-        fn get_oracle_datapoint_boxes(
-            &self,
-        ) -> std::result::Result<Vec<PostedOracleBox>, StageError> {
-            Ok(self.datapoints.clone())
+        fn get_oracle_boxes(&self) -> std::result::Result<Vec<OracleBoxWrapper>, StageError> {
+            Ok(self
+                .datapoints
+                .iter()
+                .cloned()
+                .map(OracleBoxWrapper::Posted)
+                .collect())
         }
     }
 
-    fn make_refresh_box(
-        value: BoxValue,
-        inputs: &RefreshBoxWrapperInputs,
-        creation_height: u32,
-    ) -> RefreshBoxWrapper {
-        let tokens = vec![Token::from((
-            inputs.refresh_nft_token_id.clone(),
-            1u64.try_into().unwrap(),
-        ))]
-        .try_into()
-        .unwrap();
-        RefreshBoxWrapper::new(
-            ErgoBox::new(
-                value,
-                RefreshContract::checked_load(&inputs.contract_inputs)
-                    .unwrap()
-                    .ergo_tree(),
-                Some(tokens),
-                NonMandatoryRegisters::empty(),
-                creation_height,
-                force_any_val::<TxId>(),
-                0,
-            )
-            .unwrap(),
-            inputs,
-        )
-        .unwrap()
-    }
-
     #[allow(clippy::too_many_arguments)]
     fn make_datapoint_boxes(
         pub_keys: Vec<EcPoint>,
@@ -434,7 +539,6 @@ mod tests {
         let oracle_contract_parameters = OracleContractParameters::default();
         let refresh_contract_parameters = RefreshContractParameters::default();
         let token_ids = generate_token_ids();
-        dbg!(&token_ids);
 
         let refresh_contract_inputs = RefreshContractInputs::build_with(
             refresh_contract_parameters,
@@ -510,6 +614,7 @@ mod tests {
             &(DatapointStageMock {
                 datapoints: in_oracle_boxes.clone(),
             }),
+            None,
             5,
             4,
             &wallet_mock,
@@ -530,13 +635,70 @@ mod tests {
         possible_input_boxes.append(&mut in_oracle_boxes_raw);
         possible_input_boxes.append(&mut wallet_mock.get_unspent_wallet_boxes().unwrap());
 
-        let tx_context = TransactionContext::new(
-            action.tx.clone(),
-            find_input_boxes(action.tx, possible_input_boxes),
-            Vec::new(),
+        let input_boxes = find_input_boxes(action.tx.clone(), possible_input_boxes.clone());
+        let tx_context =
+            TransactionContext::new(action.tx.clone(), input_boxes.clone(), Vec::new()).unwrap();
+
+        let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
+
+        // The local validation explains a refresh box sent to a wrong contract
+        let pool_parameters = PoolParameters {
+            token_ids: token_ids.clone(),
+            refresh_contract_parameters: RefreshContractParameters::default(),
+            oracle_min_storage_rent: oracle_contract_parameters.min_storage_rent,
+        };
+        assert_eq!(
+            validate_tx(&action.tx, &input_boxes, &[], &ctx, Some(&pool_parameters)).unwrap(),
+            vec![]
+        );
+        let mut outputs = action.tx.output_candidates.as_vec().clone();
+        outputs[1].ergo_tree = change_address.script().unwrap();
+        let wrong_tx = UnsignedTransaction::new(
+            action.tx.inputs.clone(),
+            action.tx.data_inputs.clone(),
+            outputs.try_into().unwrap(),
         )
         .unwrap();
+        let failures =
+            validate_tx(&wrong_tx, &input_boxes, &[], &ctx, Some(&pool_parameters)).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].index, 1);
+        assert_eq!(failures[0].kind, InputKind::Refresh);
+        assert_eq!(
+            failures[0].violated_clauses,
+            vec!["the refresh box is the second output, with the refresh contract".to_string()]
+        );
 
+        // The pool and refresh contracts accept the boxes topped up from the wallet
+        let top_up = BoxTopUp {
+            pool: 1_000_000,
+            refresh: 2_000_000,
+        };
+        let action = build_refresh_action_with_top_up(
+            &pool_box_mock,
+            &refresh_box_mock,
+            &(DatapointStageMock {
+                datapoints: in_oracle_boxes.clone(),
+            }),
+            None,
+            5,
+            4,
+            &wallet_mock,
+            height,
+            change_address.clone(),
+            &oracle_pub_key,
+            top_up,
+        )
+        .unwrap();
+        let outputs = action.tx.output_candidates.as_vec();
+        assert_eq!(*outputs[0].value.as_u64(), *BASE_FEE.as_u64() + top_up.pool);
+        assert_eq!(
+            *outputs[1].value.as_u64(),
+            *BASE_FEE.as_u64() + top_up.refresh
+        );
+        let input_boxes = find_input_boxes(action.tx.clone(), possible_input_boxes.clone());
+        let tx_context =
+            TransactionContext::new(action.tx.clone(), input_boxes, Vec::new()).unwrap();
         let _signed_tx = wallet.sign_transaction(tx_context, &ctx, None).unwrap();
 
         assert!(
@@ -554,6 +716,7 @@ mod tests {
                         &token_ids,
                     ),
                 }),
+                None,
                 5,
                 4,
                 &wallet_mock,
@@ -566,6 +729,165 @@ mod tests {
         );
     }
 
+    struct BuybackBoxMock {
+        buyback_box: BuybackBoxWrapper,
+    }
+
+    impl BuybackBoxSource for BuybackBoxMock {
+        fn get_buyback_box(&self) -> std::result::Result<Option<BuybackBoxWrapper>, StageError> {
+            Ok(Some(self.buyback_box.clone()))
+        }
+    }
+
+    #[test]
+    fn test_refresh_pool_with_buyback() {
+        let height = force_any_val::<ErgoStateContext>().pre_header.height;
+        let token_ids = generate_token_ids();
+        let inputs = RefreshBoxWrapperInputs {
+            refresh_nft_token_id: token_ids.refresh_nft_token_id.clone(),
+            contract_inputs: RefreshContractInputs::build_with(
+                RefreshContractParameters::default(),
+                token_ids.oracle_token_id.clone(),
+                token_ids.pool_nft_token_id.clone(),
+            )
+            .unwrap(),
+        };
+        let pool_box_epoch_id = 1;
+        let pool_box_mock = PoolBoxMock {
+            pool_box: make_pool_box(
+                200,
+                pool_box_epoch_id,
+                *BASE_FEE,
+                height - 32,
+                &PoolContractParameters::default(),
+                &token_ids,
+            ),
+        };
+        let refresh_box_mock = RefreshBoxMock {
+            refresh_box: make_refresh_box(*BASE_FEE, &inputs, height - 32),
+        };
+        let secret = force_any_val::<DlogProverInput>();
+        let oracle_pub_key = secret.public_image().h;
+        let mut oracle_pub_keys = vec![*oracle_pub_key.clone()];
+        oracle_pub_keys.extend((0..3).map(|_| force_any_val::<EcPoint>()));
+        let in_oracle_boxes = make_datapoint_boxes(
+            oracle_pub_keys,
+            vec![199, 198, 200, 197],
+            pool_box_epoch_id,
+            BASE_FEE.checked_mul_u32(100).unwrap(),
+            height - 9,
+            &OracleContractParameters::default(),
+            &token_ids,
+        );
+
+        let buyback_token_id: TokenId = force_any_val::<Digest32>().into();
+        let buyback_tree = Address::P2Pk(force_any_val::<DlogProverInput>().public_image())
+            .script()
+            .unwrap();
+        let buyback_box = BuybackBoxWrapper::new(
+            ErgoBox::new(
+                *BASE_FEE,
+                buyback_tree.clone(),
+                Some(
+                    vec![
+                        Token::from((buyback_token_id.clone(), 1u64.try_into().unwrap())),
+                        Token::from((token_ids.reward_token_id.clone(), 50u64.try_into().unwrap())),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                ),
+                NonMandatoryRegisters::empty(),
+                height - 10,
+                force_any_val::<TxId>(),
+                0,
+            )
+            .unwrap(),
+            &buyback_token_id,
+        )
+        .unwrap();
+        let buyback_box_id = buyback_box.get_box().box_id();
+
+        let wallet_mock = WalletDataMock {
+            unspent_boxes: vec![make_wallet_unspent_box(
+                secret.public_image(),
+                BASE_FEE.checked_mul_u32(10000).unwrap(),
+                None,
+            )],
+        };
+        let action = build_refresh_action(
+            &pool_box_mock,
+            &refresh_box_mock,
+            &(DatapointStageMock {
+                datapoints: in_oracle_boxes.clone(),
+            }),
+            Some(&BuybackBoxMock { buyback_box }),
+            5,
+            4,
+            &wallet_mock,
+            height,
+            Address::P2Pk(secret.public_image()),
+            &oracle_pub_key,
+        )
+        .unwrap();
+
+        assert!(action.tx.inputs.iter().any(|i| i.box_id == buyback_box_id));
+        let outputs: Vec<_> = action.tx.output_candidates.iter().collect();
+        let in_pool_reward_amount = *pool_box_mock
+            .get_pool_box()
+            .unwrap()
+            .reward_token()
+            .amount
+            .as_u64();
+        // 2 reward tokens per collected oracle box are taken, the bought back ones are added
+        let out_pool_reward_token = outputs[0].tokens.as_ref().unwrap().get(1).unwrap().clone();
+        assert_eq!(
+            *out_pool_reward_token.amount.as_u64(),
+            in_pool_reward_amount - 2 * in_oracle_boxes.len() as u64 + 50
+        );
+        let out_buyback_box = outputs[2 + in_oracle_boxes.len()];
+        assert_eq!(out_buyback_box.ergo_tree, buyback_tree);
+        let out_buyback_tokens = out_buyback_box.tokens.as_ref().unwrap();
+        assert_eq!(out_buyback_tokens.len(), 1);
+        assert_eq!(
+            out_buyback_tokens.get(0).unwrap().token_id,
+            buyback_token_id
+        );
+    }
+
+    #[test]
+    fn test_build_out_pool_box_reward_tokens() {
+        let token_ids = generate_token_ids();
+        // The pool box holds 100 reward tokens
+        let in_pool_box = make_pool_box(
+            200,
+            1,
+            *BASE_FEE,
+            100,
+            &PoolContractParameters::default(),
+            &token_ids,
+        );
+        let out_pool_box = build_out_pool_box(&in_pool_box, 101, 300, 8, 10, 0).unwrap();
+        assert_eq!(
+            *out_pool_box.tokens.unwrap().get(1).unwrap().amount.as_u64(),
+            102
+        );
+        assert!(matches!(
+            build_out_pool_box(&in_pool_box, 101, 300, 102, 0, 0),
+            Err(RefreshActionError::NotEnoughRewardTokens {
+                available: 100,
+                decrement: 102
+            })
+        ));
+        assert!(matches!(
+            build_out_pool_box(&in_pool_box, 101, 300, 0, u64::MAX, 0),
+            Err(RefreshActionError::RewardTokenOverflow)
+        ));
+        assert!(matches!(
+            build_out_pool_box(&in_pool_box, 101, 300, 100, 0, 0),
+            Err(RefreshActionError::TokenAmount(_))
+        ));
+    }
+
     #[test]
     fn test_oracle_deviation_check() {
         assert_eq!(
@@ -28,6 +28,9 @@ use crate::box_kind::OracleBoxWrapper;
 use crate::box_kind::OracleBoxWrapperInputs;
 use crate::box_kind::PoolBoxWrapper;
 use crate::box_kind::PoolBoxWrapperInputs;
+use crate::box_kind::PostedOracleBox;
+use crate::box_kind::RefreshBoxWrapper;
+use crate::box_kind::RefreshBoxWrapperInputs;
 use crate::box_kind::UpdateBoxWrapper;
 use crate::box_kind::VoteBallotBoxWrapper;
 use crate::contracts::oracle::OracleContract;
@@ -37,12 +40,14 @@ use crate::contracts::oracle::OracleContractParameters;
 use crate::contracts::pool::PoolContract;
 use crate::contracts::pool::PoolContractInputs;
 use crate::contracts::pool::PoolContractParameters;
+use crate::contracts::refresh::RefreshContract;
 use crate::node_interface::SignTransaction;
 use crate::oracle_config::TokenIds;
+use crate::oracle_state::DatapointBoxesSource;
 use crate::oracle_state::LocalBallotBoxSource;
 use crate::oracle_state::UpdateBoxSource;
 use crate::oracle_state::VoteBallotBoxesSource;
-use crate::oracle_state::{LocalDatapointBoxSource, PoolBoxSource, StageError};
+use crate::oracle_state::{LocalDatapointBoxSource, PoolBoxSource, RefreshBoxSource, StageError};
 use crate::wallet::WalletDataError;
 
 use super::*;
@@ -58,6 +63,17 @@ impl PoolBoxSource for PoolBoxMock {
     }
 }
 
+#[derive(Clone)]
+pub(crate) struct RefreshBoxMock {
+    pub refresh_box: RefreshBoxWrapper,
+}
+
+impl RefreshBoxSource for RefreshBoxMock {
+    fn get_refresh_box(&self) -> std::result::Result<RefreshBoxWrapper, StageError> {
+        Ok(self.refresh_box.clone())
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct OracleBoxMock {
     pub oracle_box: OracleBoxWrapper,
@@ -69,6 +85,18 @@ impl LocalDatapointBoxSource for OracleBoxMock {
     ) -> std::result::Result<Option<OracleBoxWrapper>, StageError> {
         Ok(Some(self.oracle_box.clone()))
     }
+
+    fn get_local_oracle_datapoint_boxes(
+        &self,
+    ) -> std::result::Result<Vec<OracleBoxWrapper>, StageError> {
+        Ok(vec![self.oracle_box.clone()])
+    }
+
+    fn get_spent_local_oracle_datapoint_boxes(
+        &self,
+    ) -> std::result::Result<Vec<OracleBoxWrapper>, StageError> {
+        Ok(Vec::new())
+    }
 }
 
 #[derive(Clone)]
@@ -82,6 +110,32 @@ impl LocalBallotBoxSource for BallotBoxMock {
     }
 }
 
+pub(crate) struct DatapointStageMock {
+    pub datapoints: Vec<PostedOracleBox>,
+}
+
+impl DatapointBoxesSource for DatapointStageMock {
+    fn get_oracle_boxes(&self) -> std::result::Result<Vec<OracleBoxWrapper>, StageError> {
+        Ok(self
+            .datapoints
+            .iter()
+            .cloned()
+            .map(OracleBoxWrapper::Posted)
+            .collect())
+    }
+}
+
+/// Posted and collected oracle boxes
+pub(crate) struct OracleBoxesMock {
+    pub oracle_boxes: Vec<OracleBoxWrapper>,
+}
+
+impl DatapointBoxesSource for OracleBoxesMock {
+    fn get_oracle_boxes(&self) -> std::result::Result<Vec<OracleBoxWrapper>, StageError> {
+        Ok(self.oracle_boxes.clone())
+    }
+}
+
 pub struct BallotBoxesMock {
     pub ballot_boxes: Vec<VoteBallotBoxWrapper>,
 }
@@ -113,6 +167,35 @@ impl UpdateBoxSource for UpdateBoxMock {
     }
 }
 
+pub(crate) fn make_refresh_box(
+    value: BoxValue,
+    inputs: &RefreshBoxWrapperInputs,
+    creation_height: u32,
+) -> RefreshBoxWrapper {
+    let tokens = vec![Token::from((
+        inputs.refresh_nft_token_id.clone(),
+        1u64.try_into().unwrap(),
+    ))]
+    .try_into()
+    .unwrap();
+    RefreshBoxWrapper::new(
+        ErgoBox::new(
+            value,
+            RefreshContract::checked_load(&inputs.contract_inputs)
+                .unwrap()
+                .ergo_tree(),
+            Some(tokens),
+            NonMandatoryRegisters::empty(),
+            creation_height,
+            force_any_val::<TxId>(),
+            0,
+        )
+        .unwrap(),
+        inputs,
+    )
+    .unwrap()
+}
+
 pub(crate) fn make_pool_box(
     datapoint: i64,
     epoch_counter: i32,
@@ -178,6 +261,32 @@ pub(crate) fn make_datapoint_box(
     token_ids: &TokenIds,
     value: BoxValue,
     creation_height: u32,
+) -> ErgoBox {
+    make_oracle_box(
+        pub_key,
+        Some((epoch_counter, datapoint)),
+        token_ids,
+        value,
+        creation_height,
+    )
+}
+
+/// Oracle box of `pub_key` whose datapoint was collected by a refresh
+pub(crate) fn make_collected_datapoint_box(
+    pub_key: EcPoint,
+    token_ids: &TokenIds,
+    value: BoxValue,
+    creation_height: u32,
+) -> ErgoBox {
+    make_oracle_box(pub_key, None, token_ids, value, creation_height)
+}
+
+fn make_oracle_box(
+    pub_key: EcPoint,
+    epoch_counter_and_datapoint: Option<(i32, i64)>,
+    token_ids: &TokenIds,
+    value: BoxValue,
+    creation_height: u32,
 ) -> ErgoBox {
     let tokens = vec![
         Token::from((token_ids.oracle_token_id.clone(), 1u64.try_into().unwrap())),
@@ -191,22 +300,18 @@ pub(crate) fn make_datapoint_box(
     let parameters = OracleContractParameters::default();
     let oracle_contract_inputs =
         OracleContractInputs::build_with(parameters, token_ids.pool_nft_token_id.clone()).unwrap();
+    let mut registers = vec![(NonMandatoryRegisterId::R4, Constant::from(pub_key))];
+    if let Some((epoch_counter, datapoint)) = epoch_counter_and_datapoint {
+        registers.push((NonMandatoryRegisterId::R5, Constant::from(epoch_counter)));
+        registers.push((NonMandatoryRegisterId::R6, Constant::from(datapoint)));
+    }
     ErgoBox::new(
         value,
         OracleContract::checked_load(&oracle_contract_inputs)
             .unwrap()
             .ergo_tree(),
         Some(tokens),
-        NonMandatoryRegisters::new(
-            vec![
-                (NonMandatoryRegisterId::R4, Constant::from(pub_key)),
-                (NonMandatoryRegisterId::R5, Constant::from(epoch_counter)),
-                (NonMandatoryRegisterId::R6, Constant::from(datapoint)),
-            ]
-            .into_iter()
-            .collect(),
-        )
-        .unwrap(),
+        NonMandatoryRegisters::new(registers.into_iter().collect()).unwrap(),
         creation_height,
         force_any_val::<TxId>(),
         0,
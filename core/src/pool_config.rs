@@ -0,0 +1,194 @@
+//! Split of the config into the pool definition and the operator config. The pool definition
+//! (`pool_config.yaml`) holds the contract parameters and the token ids of the pool, the same for
+//! all the operators and safe to share. The operator config holds the node connection, the
+//! secrets and the settings of the operator, and points to the pool definition with
+//! `pool_config_file` (relative to the directory of the config file). `pool_config_hash`, the
+//! blake2b256 hash of the pool definition file, pins the pool definition the operator agreed to.
+//! The pool definition is merged into the operator config when the config is loaded. Either file
+//! can be YAML or TOML (see `config_format`).
+
+use std::path::{Path, PathBuf};
+
+use derive_more::From;
+use ergo_lib::ergo_chain_types::blake2b256_hash;
+use serde_yaml::{Mapping, Value};
+use thiserror::Error;
+
+use crate::config_format::{ConfigFormat, ConfigFormatError};
+
+/// Key of the operator config pointing to the pool definition
+pub const POOL_CONFIG_FILE_KEY: &str = "pool_config_file";
+
+/// Key of the operator config holding the hash of the pool definition
+pub const POOL_CONFIG_HASH_KEY: &str = "pool_config_hash";
+
+/// Keys of the config defining the pool
+pub const POOL_CONFIG_KEYS: [&str; 7] = [
+    "oracle_contract_parameters",
+    "pool_contract_parameters",
+    "refresh_contract_parameters",
+    "update_contract_parameters",
+    "ballot_contract_parameters",
+    "token_ids",
+    "buyback_token_id",
+];
+
+#[derive(Debug, Error, From)]
+pub enum PoolConfigError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("{0}")]
+    Format(ConfigFormatError),
+    #[error("{0} is not a YAML mapping")]
+    #[from(ignore)]
+    NotAMapping(String),
+    #[error("{0} is defined in both the operator config and the pool definition")]
+    #[from(ignore)]
+    DuplicateKey(String),
+    #[error("{0} doesn't belong in a pool definition")]
+    #[from(ignore)]
+    UnexpectedKey(String),
+    #[error("the hash of {file} is {actual}, the config expects {expected}")]
+    #[from(ignore)]
+    HashMismatch {
+        file: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("the config is split already, its pool definition is {0}")]
+    #[from(ignore)]
+    AlreadySplit(String),
+}
+
+/// Base16 blake2b256 hash of the pool definition file `bytes`
+pub fn pool_config_hash(bytes: &[u8]) -> String {
+    base16::encode_lower(&blake2b256_hash(bytes))
+}
+
+fn key(name: &str) -> Value {
+    Value::String(name.to_string())
+}
+
+/// Path of the pool definition of the operator config at `config_path`
+pub fn resolve_path(config_path: &Path, pool_config_file: &str) -> PathBuf {
+    config_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(pool_config_file)
+}
+
+/// Merge the pool definition the operator config `config` points to into it, the operator config
+/// being at `config_path`. A config without `pool_config_file` is left as it is.
+pub fn merge_pool_config(config: &mut Value, config_path: &Path) -> Result<(), PoolConfigError> {
+    let mapping = config
+        .as_mapping_mut()
+        .ok_or_else(|| PoolConfigError::NotAMapping(config_path.display().to_string()))?;
+    let pool_config_file = match mapping.remove(&key(POOL_CONFIG_FILE_KEY)) {
+        Some(Value::String(file)) => file,
+        Some(_) | None => return Ok(()),
+    };
+    let path = resolve_path(config_path, &pool_config_file);
+    let pool_config_str = std::fs::read_to_string(&path)?;
+    if let Some(Value::String(expected)) = mapping.remove(&key(POOL_CONFIG_HASH_KEY)) {
+        let actual = pool_config_hash(pool_config_str.as_bytes());
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(PoolConfigError::HashMismatch {
+                file: path.display().to_string(),
+                expected,
+                actual,
+            });
+        }
+    }
+    let pool_config = ConfigFormat::of_path(&path).parse(&pool_config_str)?;
+    let pool_mapping = match pool_config {
+        Value::Mapping(pool_mapping) => pool_mapping,
+        _ => return Err(PoolConfigError::NotAMapping(path.display().to_string())),
+    };
+    for (pool_key, value) in pool_mapping {
+        let name = pool_key.as_str().unwrap_or_default().to_string();
+        if !POOL_CONFIG_KEYS.contains(&name.as_str()) {
+            return Err(PoolConfigError::UnexpectedKey(name));
+        }
+        if mapping.contains_key(&pool_key) {
+            return Err(PoolConfigError::DuplicateKey(name));
+        }
+        mapping.insert(pool_key, value);
+    }
+    Ok(())
+}
+
+/// Split the full `config` into the operator config (without the pool keys) and the pool
+/// definition
+pub fn split(config: &Value) -> Result<(Mapping, Mapping), PoolConfigError> {
+    let mapping = config
+        .as_mapping()
+        .ok_or_else(|| PoolConfigError::NotAMapping("the config".to_string()))?;
+    if let Some(file) = mapping.get(&key(POOL_CONFIG_FILE_KEY)) {
+        return Err(PoolConfigError::AlreadySplit(
+            file.as_str().unwrap_or_default().to_string(),
+        ));
+    }
+    let mut operator_config = mapping.clone();
+    let mut pool_config = Mapping::new();
+    for name in POOL_CONFIG_KEYS {
+        if let Some(value) = operator_config.remove(&key(name)) {
+            pool_config.insert(key(name), value);
+        }
+    }
+    Ok((operator_config, pool_config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_merge() {
+        let full_config: Value = serde_yaml::from_str(
+            "node_api_key: hello\n\
+            token_ids:\n  pool_nft_token_id: abc\n\
+            oracle_contract_parameters:\n  pool_nft_index: 5\n\
+            rescan_height: 100\n",
+        )
+        .unwrap();
+        let (mut operator_config, pool_config) = split(&full_config).unwrap();
+        assert_eq!(
+            operator_config.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            vec![&key("node_api_key"), &key("rescan_height")]
+        );
+        let dir = std::env::temp_dir().join("oracle_core_test_pool_config");
+        std::fs::create_dir_all(&dir).unwrap();
+        let pool_bytes = serde_yaml::to_string(&pool_config).unwrap();
+        std::fs::write(dir.join("pool.yaml"), &pool_bytes).unwrap();
+        operator_config.insert(key(POOL_CONFIG_FILE_KEY), key("pool.yaml"));
+        operator_config.insert(
+            key(POOL_CONFIG_HASH_KEY),
+            key(&pool_config_hash(pool_bytes.as_bytes())),
+        );
+        let config_path = dir.join("oracle_config.yaml");
+
+        let mut merged = Value::Mapping(operator_config.clone());
+        merge_pool_config(&mut merged, &config_path).unwrap();
+        let merged = merged.as_mapping().unwrap();
+        assert_eq!(merged.len(), full_config.as_mapping().unwrap().len());
+        assert_eq!(merged.get(&key("token_ids")), full_config.get("token_ids"));
+
+        // Changed by someone
+        std::fs::write(dir.join("pool.yaml"), pool_bytes.replace("abc", "abd")).unwrap();
+        let mut merged = Value::Mapping(operator_config.clone());
+        assert!(matches!(
+            merge_pool_config(&mut merged, &config_path),
+            Err(PoolConfigError::HashMismatch { .. })
+        ));
+
+        // Defined in both files
+        std::fs::write(dir.join("pool.yaml"), &pool_bytes).unwrap();
+        operator_config.insert(key("token_ids"), Value::Null);
+        let mut merged = Value::Mapping(operator_config);
+        assert!(matches!(
+            merge_pool_config(&mut merged, &config_path),
+            Err(PoolConfigError::DuplicateKey(_))
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
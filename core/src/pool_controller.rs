@@ -0,0 +1,669 @@
+//! Decides what the daemon does on each main loop iteration. The `PoolController` handles the
+//! states in which the daemon must wait (pool not bootstrapped, pending txs) and leaves the live
+//! epoch decisions (publish a datapoint, refresh the pool box or stand down) to its
+//! `EpochStrategy`, selected with `epoch_strategy` in the oracle config.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use ergo_lib::ergo_chain_types::EcPoint;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::box_kind::PostedOracleBox;
+use crate::oracle_state::{DatapointBoxesSource, LocalDatapointState};
+use crate::pool_commands::PoolCommand;
+use crate::state::PoolState;
+
+/// Epoch strategies selectable in the oracle config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EpochStrategyKind {
+    /// Publish in the second half of the epoch and refresh as soon as the epoch ends
+    Standard,
+    /// Publish a quarter into the epoch to be ready to refresh as soon as the epoch ends
+    AggressiveCollector,
+    /// Publish datapoints but leave the refresh to the other oracles
+    PassivePoster,
+    /// Follow the pool without submitting any tx, `run` is then in watch-only mode (no wallet)
+    WatchOnly,
+}
+
+impl Default for EpochStrategyKind {
+    fn default() -> Self {
+        EpochStrategyKind::Standard
+    }
+}
+
+impl EpochStrategyKind {
+    pub fn strategy(self) -> Box<dyn EpochStrategy> {
+        match self {
+            EpochStrategyKind::Standard => Box::new(StandardStrategy {}),
+            EpochStrategyKind::AggressiveCollector => Box::new(AggressiveCollectorStrategy {}),
+            EpochStrategyKind::PassivePoster => Box::new(PassivePosterStrategy {}),
+            EpochStrategyKind::WatchOnly => Box::new(WatchOnlyStrategy {}),
+        }
+    }
+}
+
+/// The live epoch as seen by the strategy
+#[derive(Debug, Clone)]
+pub struct EpochView {
+    pub height: u32,
+    pub epoch_length: u32,
+    pub pool_box_epoch_id: u32,
+    pub latest_pool_box_height: u32,
+    pub local_datapoint_box_state: Option<LocalDatapointState>,
+    /// Publication delay set in the config, replaces the delay of the strategy
+    pub publish_offset_blocks: Option<u32>,
+    /// Jitter added to the publication delay in this epoch
+    pub publish_jitter_blocks: u32,
+}
+
+impl EpochView {
+    /// Blocks to wait after the pool box before publishing a datapoint after a collection,
+    /// `default_blocks` unless an offset is set in the config. The delay is kept inside the epoch.
+    pub fn publish_delay(&self, default_blocks: u32) -> u32 {
+        let delay =
+            self.publish_offset_blocks.unwrap_or(default_blocks) + self.publish_jitter_blocks;
+        delay.min(self.epoch_length.saturating_sub(1))
+    }
+
+    /// Lowest height of a datapoint box accepted by a refresh at the current height
+    fn min_start_height(&self) -> u32 {
+        self.height.saturating_sub(self.epoch_length)
+    }
+
+    /// Whether the epoch of the pool box is over, i.e. the pool box can be refreshed
+    pub fn epoch_ended(&self) -> bool {
+        self.latest_pool_box_height < self.min_start_height()
+    }
+
+    /// Whether the local datapoint is posted for the epoch of the pool box and still inside the
+    /// epoch window
+    pub fn local_datapoint_is_current(&self) -> bool {
+        match self.local_datapoint_box_state {
+            Some(LocalDatapointState::Posted { epoch_id, height }) => {
+                epoch_id == self.pool_box_epoch_id && height >= self.min_start_height()
+            }
+            Some(LocalDatapointState::Collected { .. }) | None => false,
+        }
+    }
+}
+
+/// Decides the action of the local oracle in the live epoch
+pub trait EpochStrategy: std::fmt::Debug {
+    /// Datapoint to publish, if any
+    fn publish(&self, epoch: &EpochView) -> Option<PoolCommand>;
+
+    /// Whether to refresh the pool box (collect the datapoints)
+    fn refresh(&self, epoch: &EpochView) -> bool;
+
+    /// Whether to stay idle whatever the state of the epoch
+    fn stand_down(&self, _epoch: &EpochView) -> bool {
+        false
+    }
+}
+
+/// Publish the first datapoint right away, republish a datapoint of a past epoch or out of the
+/// epoch window, and publish after a collection once `wait_blocks` passed since the pool box.
+fn publish_after(epoch: &EpochView, wait_blocks: u32) -> Option<PoolCommand> {
+    match epoch.local_datapoint_box_state {
+        None => Some(PoolCommand::PublishFirstDataPoint),
+        Some(LocalDatapointState::Collected { .. }) => {
+            if epoch.height > epoch.latest_pool_box_height + wait_blocks {
+                Some(PoolCommand::PublishSubsequentDataPoint { republish: false })
+            } else {
+                None
+            }
+        }
+        Some(LocalDatapointState::Posted { .. }) => {
+            if epoch.local_datapoint_is_current() {
+                None
+            } else {
+                Some(PoolCommand::PublishSubsequentDataPoint { republish: true })
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StandardStrategy {}
+
+impl EpochStrategy for StandardStrategy {
+    fn publish(&self, epoch: &EpochView) -> Option<PoolCommand> {
+        // publish datapoint after some blocks have passed after the pool box published
+        // to avoid some oracle box become stale on the next refresh
+        // (datapoint posted on the first block of the epoch go out of the epoch window too fast)
+        publish_after(epoch, epoch.publish_delay(epoch.epoch_length / 2))
+    }
+
+    fn refresh(&self, epoch: &EpochView) -> bool {
+        epoch.local_datapoint_is_current() && epoch.epoch_ended()
+    }
+}
+
+#[derive(Debug)]
+pub struct AggressiveCollectorStrategy {}
+
+impl EpochStrategy for AggressiveCollectorStrategy {
+    fn publish(&self, epoch: &EpochView) -> Option<PoolCommand> {
+        publish_after(epoch, epoch.publish_delay(epoch.epoch_length / 4))
+    }
+
+    fn refresh(&self, epoch: &EpochView) -> bool {
+        epoch.local_datapoint_is_current() && epoch.epoch_ended()
+    }
+}
+
+#[derive(Debug)]
+pub struct PassivePosterStrategy {}
+
+impl EpochStrategy for PassivePosterStrategy {
+    fn publish(&self, epoch: &EpochView) -> Option<PoolCommand> {
+        StandardStrategy {}.publish(epoch)
+    }
+
+    fn refresh(&self, _epoch: &EpochView) -> bool {
+        false
+    }
+}
+
+#[derive(Debug)]
+pub struct WatchOnlyStrategy {}
+
+impl EpochStrategy for WatchOnlyStrategy {
+    fn publish(&self, _epoch: &EpochView) -> Option<PoolCommand> {
+        None
+    }
+
+    fn refresh(&self, _epoch: &EpochView) -> bool {
+        false
+    }
+
+    fn stand_down(&self, _epoch: &EpochView) -> bool {
+        true
+    }
+}
+
+/// When the datapoint is published after a collection, from `publish_timing` in the config.
+/// The jitter keeps the oracles of the pool from publishing in the same block.
+#[derive(Debug, Clone, Default)]
+pub struct PublishTiming {
+    pub offset_blocks: Option<u32>,
+    /// Up to this many blocks are added to the delay
+    pub jitter_blocks: u32,
+    /// Makes the jitter differ between the oracles (the oracle address)
+    pub seed: String,
+}
+
+impl PublishTiming {
+    /// Jitter of the epoch, the same on every iteration of the epoch
+    pub fn jitter(&self, epoch_id: u32) -> u32 {
+        if self.jitter_blocks == 0 {
+            return 0;
+        }
+        let mut hasher = DefaultHasher::new();
+        (&self.seed, epoch_id).hash(&mut hasher);
+        (hasher.finish() % (self.jitter_blocks as u64 + 1)) as u32
+    }
+}
+
+/// Deterministic election of the oracle attempting the refresh, so that the oracles don't race
+/// with conflicting refresh txs
+#[derive(Debug, Clone)]
+pub struct CollectorElection {
+    pub local_public_key: EcPoint,
+    /// Blocks after the end of the epoch after which the next ranked oracle is elected
+    pub timeout_blocks: u32,
+}
+
+impl CollectorElection {
+    /// Public key of the oracle elected to refresh the pool box. The candidates are the oracles
+    /// which posted a datapoint in the epoch window, ranked by public key. The oracle at the epoch
+    /// counter modulo their number is elected, and every `timeout_blocks` blocks after the end of
+    /// the epoch without a refresh the election moves to the next ranked one.
+    pub fn elected_collector(
+        &self,
+        datapoint_boxes: &[PostedOracleBox],
+        epoch: &EpochView,
+    ) -> Option<EcPoint> {
+        let min_start_height = epoch.min_start_height();
+        let mut candidates: Vec<EcPoint> = datapoint_boxes
+            .iter()
+            .filter(|b| {
+                b.epoch_counter() == epoch.pool_box_epoch_id
+                    && b.get_box().creation_height >= min_start_height
+            })
+            .map(|b| *b.public_key().h)
+            .collect();
+        candidates.sort_by_cached_key(|pk| pk.sigma_serialize_bytes().unwrap());
+        candidates.dedup();
+        if candidates.is_empty() {
+            return None;
+        }
+        let blocks_since_epoch_end = epoch
+            .height
+            .saturating_sub(epoch.latest_pool_box_height + epoch.epoch_length + 1);
+        let fallbacks = blocks_since_epoch_end / self.timeout_blocks.max(1);
+        let index = (epoch.pool_box_epoch_id as usize + fallbacks as usize) % candidates.len();
+        Some(candidates.swap_remove(index))
+    }
+}
+
+/// State of the controller after the last iteration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerState {
+    Idle,
+    WaitingForBootstrap,
+    WaitingForPendingTxs,
+    StandingDown,
+    Publishing,
+    Refreshing,
+    /// Another oracle is elected to refresh the pool box
+    WaitingForCollector,
+    /// A tx spending the pool box was found in the mempool before submitting the refresh
+    RefreshBackedOff,
+}
+
+#[derive(Debug)]
+pub struct PoolController {
+    strategy: Box<dyn EpochStrategy>,
+    epoch_length: u32,
+    publish_timing: PublishTiming,
+    collector_election: Option<CollectorElection>,
+    /// No refresh is attempted up to this height
+    refresh_backoff_height: Option<u32>,
+    state: ControllerState,
+}
+
+impl PoolController {
+    pub fn new(
+        strategy: Box<dyn EpochStrategy>,
+        epoch_length: u32,
+        publish_timing: PublishTiming,
+        collector_election: Option<CollectorElection>,
+    ) -> Self {
+        PoolController {
+            strategy,
+            epoch_length,
+            publish_timing,
+            collector_election,
+            refresh_backoff_height: None,
+            state: ControllerState::Idle,
+        }
+    }
+
+    pub fn state(&self) -> ControllerState {
+        self.state
+    }
+
+    /// Don't attempt a refresh until the next block, the refresh is re-evaluated then
+    pub fn back_off_refresh(&mut self, height: u32) {
+        self.refresh_backoff_height = Some(height);
+        self.state = ControllerState::RefreshBackedOff;
+    }
+
+    /// Move to the state for `pool_state` at `height`, returns the command to act on. The
+    /// datapoint boxes are only read for the collector election.
+    pub fn next_command(
+        &mut self,
+        pool_state: PoolState,
+        height: u32,
+        datapoint_boxes_source: &dyn DatapointBoxesSource,
+    ) -> Option<PoolCommand> {
+        let (state, cmd) = self.decide(pool_state, height, datapoint_boxes_source);
+        if state != self.state {
+            log::debug!(
+                "Height {height}. Pool controller state {:?} -> {:?}",
+                self.state,
+                state
+            );
+            self.state = state;
+        }
+        cmd
+    }
+
+    fn decide(
+        &self,
+        pool_state: PoolState,
+        height: u32,
+        datapoint_boxes_source: &dyn DatapointBoxesSource,
+    ) -> (ControllerState, Option<PoolCommand>) {
+        let live_epoch = match pool_state {
+            PoolState::NeedsBootstrap => {
+                log::warn!(
+                    "No oracle pool found, needs bootstrap or wait for bootstrap txs to be on-chain"
+                );
+                return (ControllerState::WaitingForBootstrap, None);
+            }
+            PoolState::LiveEpoch(live_epoch) => live_epoch,
+        };
+        log::debug!("Height {height}. Live epoch state: {live_epoch:?}");
+        // Wait until our txs (or a refresh spending our oracle box) are confirmed, the boxes
+        // the next action would spend are being spent already
+        if !live_epoch.pending_txs.is_empty() {
+            log::info!(
+                "Height {height}. Waiting for pending txs: {:?}",
+                live_epoch.pending_txs
+            );
+            return (ControllerState::WaitingForPendingTxs, None);
+        }
+        let epoch = EpochView {
+            height,
+            epoch_length: self.epoch_length,
+            pool_box_epoch_id: live_epoch.pool_box_epoch_id,
+            latest_pool_box_height: live_epoch.latest_pool_box_height,
+            local_datapoint_box_state: live_epoch.local_datapoint_box_state,
+            publish_offset_blocks: self.publish_timing.offset_blocks,
+            publish_jitter_blocks: self.publish_timing.jitter(live_epoch.pool_box_epoch_id),
+        };
+        if self.strategy.stand_down(&epoch) {
+            return (ControllerState::StandingDown, None);
+        }
+        if let Some(cmd) = self.strategy.publish(&epoch) {
+            return (ControllerState::Publishing, Some(cmd));
+        }
+        if self.strategy.refresh(&epoch) {
+            if self
+                .refresh_backoff_height
+                .map_or(false, |backoff_height| height <= backoff_height)
+            {
+                return (ControllerState::RefreshBackedOff, None);
+            }
+            if let Some(election) = &self.collector_election {
+                match datapoint_boxes_source.get_oracle_datapoint_boxes() {
+                    Ok(datapoint_boxes) => match election.elected_collector(&datapoint_boxes, &epoch)
+                    {
+                        Some(elected) if elected != election.local_public_key => {
+                            log::info!(
+                                "Height {height}. Oracle {} is elected to refresh the pool box, waiting",
+                                base16::encode_lower(&elected.sigma_serialize_bytes().unwrap())
+                            );
+                            return (ControllerState::WaitingForCollector, None);
+                        }
+                        Some(_) | None => (),
+                    },
+                    // The refresh reads the datapoint boxes as well and reports the error
+                    Err(e) => log::warn!(
+                        "Height {height}. Failed to get the datapoint boxes for the collector election: {}",
+                        e
+                    ),
+                }
+            }
+            return (ControllerState::Refreshing, Some(PoolCommand::Refresh));
+        }
+        (ControllerState::Idle, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::box_kind::OracleBoxWrapperInputs;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::oracle_config::BASE_FEE;
+    use crate::oracle_state::{LiveEpochState, PendingTxs};
+    use crate::pool_commands::test_utils::{
+        generate_token_ids, make_datapoint_box, DatapointStageMock,
+    };
+
+    const EPOCH_LENGTH: u32 = 30;
+
+    fn live_epoch(
+        latest_pool_box_height: u32,
+        local_datapoint_box_state: Option<LocalDatapointState>,
+    ) -> PoolState {
+        PoolState::LiveEpoch(LiveEpochState {
+            pool_box_epoch_id: 5,
+            local_datapoint_box_state,
+            latest_pool_datapoint: 200,
+            latest_pool_box_height,
+            pending_txs: PendingTxs::default(),
+            pool_box_id: force_any_val(),
+            local_datapoint_box_id: None,
+        })
+    }
+
+    fn no_datapoints() -> DatapointStageMock {
+        DatapointStageMock {
+            datapoints: Vec::new(),
+        }
+    }
+
+    fn command(kind: EpochStrategyKind, pool_state: PoolState, height: u32) -> Option<PoolCommand> {
+        PoolController::new(
+            kind.strategy(),
+            EPOCH_LENGTH,
+            PublishTiming::default(),
+            None,
+        )
+        .next_command(pool_state, height, &no_datapoints())
+    }
+
+    #[test]
+    fn test_publish_timing() {
+        let collected = Some(LocalDatapointState::Collected { height: 100 });
+        assert!(matches!(
+            command(EpochStrategyKind::Standard, live_epoch(100, None), 101),
+            Some(PoolCommand::PublishFirstDataPoint)
+        ));
+        // Standard waits half an epoch after a collection, the aggressive collector a quarter
+        assert!(command(
+            EpochStrategyKind::Standard,
+            live_epoch(100, collected.clone()),
+            110
+        )
+        .is_none());
+        assert!(matches!(
+            command(
+                EpochStrategyKind::AggressiveCollector,
+                live_epoch(100, collected.clone()),
+                110
+            ),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: false })
+        ));
+        assert!(matches!(
+            command(EpochStrategyKind::Standard, live_epoch(100, collected), 116),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: false })
+        ));
+        // Datapoint of a past epoch
+        let stale = Some(LocalDatapointState::Posted {
+            epoch_id: 4,
+            height: 95,
+        });
+        assert!(matches!(
+            command(
+                EpochStrategyKind::PassivePoster,
+                live_epoch(100, stale),
+                120
+            ),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: true })
+        ));
+    }
+
+    #[test]
+    fn test_refresh_and_stand_down() {
+        let posted = Some(LocalDatapointState::Posted {
+            epoch_id: 5,
+            height: 115,
+        });
+        let mut controller = PoolController::new(
+            EpochStrategyKind::Standard.strategy(),
+            30,
+            PublishTiming::default(),
+            None,
+        );
+        assert!(controller
+            .next_command(live_epoch(100, posted.clone()), 120, &no_datapoints())
+            .is_none());
+        assert_eq!(controller.state(), ControllerState::Idle);
+        assert!(matches!(
+            controller.next_command(live_epoch(100, posted.clone()), 131, &no_datapoints()),
+            Some(PoolCommand::Refresh)
+        ));
+        assert_eq!(controller.state(), ControllerState::Refreshing);
+
+        assert!(command(
+            EpochStrategyKind::PassivePoster,
+            live_epoch(100, posted.clone()),
+            131
+        )
+        .is_none());
+        assert!(command(EpochStrategyKind::WatchOnly, live_epoch(100, None), 131).is_none());
+
+        let mut controller = PoolController::new(
+            EpochStrategyKind::Standard.strategy(),
+            30,
+            PublishTiming::default(),
+            None,
+        );
+        let mut pending = live_epoch(100, posted);
+        if let PoolState::LiveEpoch(ref mut live_epoch) = pending {
+            live_epoch.pending_txs.refresh_tx_id = Some("tx".to_string());
+        }
+        assert!(controller
+            .next_command(pending, 131, &no_datapoints())
+            .is_none());
+        assert_eq!(controller.state(), ControllerState::WaitingForPendingTxs);
+    }
+
+    #[test]
+    fn test_publish_timing_offset_and_jitter() {
+        let timing = PublishTiming {
+            offset_blocks: Some(5),
+            jitter_blocks: 4,
+            seed: "oracle".to_string(),
+        };
+        assert!((0..100).all(|epoch_id| timing.jitter(epoch_id) <= 4));
+        assert_eq!(timing.jitter(7), timing.jitter(7));
+        assert_eq!(PublishTiming::default().jitter(7), 0);
+
+        let collected = Some(LocalDatapointState::Collected { height: 100 });
+        let jitter = timing.jitter(5);
+        let mut controller = PoolController::new(
+            EpochStrategyKind::Standard.strategy(),
+            EPOCH_LENGTH,
+            timing.clone(),
+            None,
+        );
+        assert!(controller
+            .next_command(
+                live_epoch(100, collected.clone()),
+                105 + jitter,
+                &no_datapoints()
+            )
+            .is_none());
+        assert!(matches!(
+            controller.next_command(
+                live_epoch(100, collected.clone()),
+                106 + jitter,
+                &no_datapoints()
+            ),
+            Some(PoolCommand::PublishSubsequentDataPoint { republish: false })
+        ));
+        // The delay is kept inside the epoch
+        let mut controller = PoolController::new(
+            EpochStrategyKind::Standard.strategy(),
+            EPOCH_LENGTH,
+            PublishTiming {
+                offset_blocks: Some(100),
+                ..timing
+            },
+            None,
+        );
+        assert!(controller
+            .next_command(
+                live_epoch(100, collected),
+                100 + EPOCH_LENGTH,
+                &no_datapoints()
+            )
+            .is_some());
+    }
+
+    #[test]
+    fn test_collector_election() {
+        let token_ids = generate_token_ids();
+        let inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let public_keys: Vec<EcPoint> = (0..3)
+            .map(|_| *force_any_val::<DlogProverInput>().public_image().h)
+            .collect();
+        let datapoint_boxes = DatapointStageMock {
+            datapoints: public_keys
+                .iter()
+                .map(|pk| {
+                    PostedOracleBox::new(
+                        make_datapoint_box(pk.clone(), 200, 5, &token_ids, *BASE_FEE, 115),
+                        &inputs,
+                    )
+                    .unwrap()
+                })
+                .collect(),
+        };
+        let mut ranked = public_keys;
+        ranked.sort_by_cached_key(|pk| pk.sigma_serialize_bytes().unwrap());
+        let posted = Some(LocalDatapointState::Posted {
+            epoch_id: 5,
+            height: 115,
+        });
+        let controller = |local_public_key: &EcPoint| {
+            PoolController::new(
+                EpochStrategyKind::Standard.strategy(),
+                EPOCH_LENGTH,
+                PublishTiming::default(),
+                Some(CollectorElection {
+                    local_public_key: local_public_key.clone(),
+                    timeout_blocks: 5,
+                }),
+            )
+        };
+
+        // Epoch 5 of 3 oracles elects the third ranked one as soon as the epoch ends
+        let mut elected = controller(&ranked[2]);
+        assert!(matches!(
+            elected.next_command(live_epoch(100, posted.clone()), 131, &datapoint_boxes),
+            Some(PoolCommand::Refresh)
+        ));
+        let mut fallback = controller(&ranked[0]);
+        assert!(fallback
+            .next_command(live_epoch(100, posted.clone()), 135, &datapoint_boxes)
+            .is_none());
+        assert_eq!(fallback.state(), ControllerState::WaitingForCollector);
+        // The first ranked oracle is elected after the timeout
+        assert!(matches!(
+            fallback.next_command(live_epoch(100, posted), 136, &datapoint_boxes),
+            Some(PoolCommand::Refresh)
+        ));
+    }
+
+    #[test]
+    fn test_refresh_back_off() {
+        let posted = Some(LocalDatapointState::Posted {
+            epoch_id: 5,
+            height: 115,
+        });
+        let mut controller = PoolController::new(
+            EpochStrategyKind::Standard.strategy(),
+            EPOCH_LENGTH,
+            PublishTiming::default(),
+            None,
+        );
+        controller.back_off_refresh(131);
+        assert!(controller
+            .next_command(live_epoch(100, posted.clone()), 131, &no_datapoints())
+            .is_none());
+        assert_eq!(controller.state(), ControllerState::RefreshBackedOff);
+        assert!(matches!(
+            controller.next_command(live_epoch(100, posted), 132, &no_datapoints()),
+            Some(PoolCommand::Refresh)
+        ));
+    }
+}
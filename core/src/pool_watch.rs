@@ -0,0 +1,216 @@
+//! Watch-only mode (`run --watch-only` or the `watch_only` epoch strategy). The daemon follows
+//! the pool without a wallet: it reports the epochs and the datapoints posted by the oracles, and
+//! raises alerts (warnings in the log) for datapoints outside of the deviation range and for
+//! epochs nobody refreshed. No tx is ever built.
+
+use ergo_lib::ergotree_ir::chain::address::{Address, NetworkAddress, NetworkPrefix};
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use ergo_lib::ergotree_ir::sigma_protocol::sigma_boolean::ProveDlog;
+
+use crate::box_kind::PostedOracleBox;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchEvent {
+    NewEpoch {
+        epoch: u32,
+        rate: u64,
+        height: u32,
+    },
+    Datapoint {
+        oracle: ProveDlog,
+        epoch: u32,
+        rate: u64,
+        /// Deviation from the pool rate, `None` if the pool rate is 0
+        deviation_percent: Option<f64>,
+        outlier: bool,
+    },
+    /// The epoch ended `blocks` blocks ago and the pool box is still not refreshed
+    EpochOverdue {
+        epoch: u32,
+        blocks: u32,
+    },
+}
+
+impl WatchEvent {
+    pub fn log(&self, network_prefix: NetworkPrefix) {
+        match self {
+            WatchEvent::NewEpoch {
+                epoch,
+                rate,
+                height,
+            } => log::info!("Epoch {epoch} started at height {height}, pool rate {rate}"),
+            WatchEvent::Datapoint {
+                oracle,
+                epoch,
+                rate,
+                deviation_percent,
+                outlier,
+            } => {
+                let address =
+                    NetworkAddress::new(network_prefix, &Address::P2Pk(oracle.clone())).to_base58();
+                let deviation = deviation_percent.map_or("-".to_string(), |d| format!("{:.2}%", d));
+                if *outlier {
+                    log::warn!("Oracle {address} posted {rate} in epoch {epoch}, outside of the deviation range ({deviation})");
+                } else {
+                    log::info!("Oracle {address} posted {rate} in epoch {epoch} ({deviation})");
+                }
+            }
+            WatchEvent::EpochOverdue { epoch, blocks } => log::warn!(
+                "Epoch {epoch} ended {blocks} blocks ago and the pool box is still not refreshed"
+            ),
+        }
+    }
+}
+
+pub struct PoolWatcher {
+    epoch_length: u32,
+    max_deviation_percent: u32,
+    /// Alert when the pool box is not refreshed this many blocks after the end of the epoch
+    overdue_blocks: u32,
+    last_epoch: Option<u32>,
+    reported_box_ids: Vec<BoxId>,
+    overdue_epoch: Option<u32>,
+}
+
+impl PoolWatcher {
+    pub fn new(epoch_length: u32, max_deviation_percent: u32) -> Self {
+        PoolWatcher {
+            epoch_length,
+            max_deviation_percent,
+            overdue_blocks: epoch_length / 2,
+            last_epoch: None,
+            reported_box_ids: Vec::new(),
+            overdue_epoch: None,
+        }
+    }
+
+    /// Events since the last observation of the pool box of `epoch` (created at `pool_box_height`)
+    /// and of the datapoint boxes
+    pub fn observe(
+        &mut self,
+        epoch: u32,
+        pool_rate: u64,
+        pool_box_height: u32,
+        datapoints: &[PostedOracleBox],
+        height: u32,
+    ) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        if self.last_epoch != Some(epoch) {
+            self.last_epoch = Some(epoch);
+            self.reported_box_ids.clear();
+            events.push(WatchEvent::NewEpoch {
+                epoch,
+                rate: pool_rate,
+                height: pool_box_height,
+            });
+        }
+        for datapoint in datapoints.iter().filter(|b| b.epoch_counter() == epoch) {
+            let box_id = datapoint.get_box().box_id();
+            if self.reported_box_ids.contains(&box_id) {
+                continue;
+            }
+            self.reported_box_ids.push(box_id);
+            let rate = datapoint.rate();
+            let deviation_percent = Some(pool_rate)
+                .filter(|r| *r != 0)
+                .map(|r| (rate as f64 - r as f64) / r as f64 * 100.0);
+            events.push(WatchEvent::Datapoint {
+                oracle: datapoint.public_key(),
+                epoch,
+                rate,
+                deviation_percent,
+                outlier: deviation_percent
+                    .map_or(false, |d| d.abs() > self.max_deviation_percent as f64),
+            });
+        }
+        let epoch_end = pool_box_height + self.epoch_length;
+        if height >= epoch_end + self.overdue_blocks && self.overdue_epoch != Some(epoch) {
+            self.overdue_epoch = Some(epoch);
+            events.push(WatchEvent::EpochOverdue {
+                epoch,
+                blocks: height - epoch_end,
+            });
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::box_kind::OracleBoxWrapperInputs;
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_commands::test_utils::{generate_token_ids, make_datapoint_box};
+
+    #[test]
+    fn test_observe() {
+        let height = force_any_val::<ErgoStateContext>().pre_header.height;
+        let token_ids = generate_token_ids();
+        let inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let datapoints: Vec<PostedOracleBox> = vec![(1000, 2), (1010, 2), (1200, 2), (900, 1)]
+            .into_iter()
+            .map(|(rate, epoch)| {
+                PostedOracleBox::new(
+                    make_datapoint_box(
+                        *force_any_val::<DlogProverInput>().public_image().h,
+                        rate,
+                        epoch,
+                        &token_ids,
+                        *BASE_FEE,
+                        height - 5,
+                    ),
+                    &inputs,
+                )
+                .unwrap()
+            })
+            .collect();
+        let mut watcher = PoolWatcher::new(30, 5);
+        let pool_box_height = height - 10;
+
+        let events = watcher.observe(2, 1000, pool_box_height, &datapoints, height);
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[0], WatchEvent::NewEpoch { epoch: 2, .. }));
+        assert!(matches!(
+            events[1],
+            WatchEvent::Datapoint {
+                rate: 1000,
+                outlier: false,
+                ..
+            }
+        ));
+        assert!(matches!(
+            events[3],
+            WatchEvent::Datapoint {
+                rate: 1200,
+                outlier: true,
+                ..
+            }
+        ));
+        // Reported once
+        assert!(watcher
+            .observe(2, 1000, pool_box_height, &datapoints, height + 1)
+            .is_empty());
+
+        // The epoch ended 15 blocks ago
+        let events = watcher.observe(2, 1000, pool_box_height, &datapoints, height + 35);
+        assert_eq!(
+            events,
+            vec![WatchEvent::EpochOverdue {
+                epoch: 2,
+                blocks: 15
+            }]
+        );
+        assert!(watcher
+            .observe(2, 1000, pool_box_height, &datapoints, height + 36)
+            .is_empty());
+    }
+}
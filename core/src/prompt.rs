@@ -0,0 +1,89 @@
+//! Confirmation of the txs built by the commands. The commands ask a [`Prompt`] instead of reading
+//! the terminal, the CLI passes [`StdinPrompt`] (or [`AssumeYesPrompt`] with `--yes`), the tests
+//! and the frontends without a terminal their own.
+
+use std::io::BufRead;
+
+use crate::audit_log::PromptResult;
+use crate::cli_commands::json_output;
+
+/// Answer the operator types to confirm a tx
+pub const CONFIRMATION: &str = "YES";
+
+pub trait Prompt {
+    /// Show `message` (the preview of the tx and what it does) and return the answer to it
+    fn confirm(&self, message: &str) -> Result<PromptResult, std::io::Error>;
+}
+
+fn print_message(message: &str) {
+    // Keep stdout clean for the JSON output
+    if json_output() {
+        eprintln!(
+            "{} TYPE '{}' TO INITIATE THE TRANSACTION.",
+            message, CONFIRMATION
+        );
+    } else {
+        println!(
+            "{} TYPE '{}' TO INITIATE THE TRANSACTION.",
+            message, CONFIRMATION
+        );
+    }
+}
+
+/// Answer of the line `input`
+fn answer_of(input: &str) -> PromptResult {
+    if input.trim() == CONFIRMATION {
+        PromptResult::Confirmed
+    } else {
+        PromptResult::Declined
+    }
+}
+
+/// Asks the operator to type 'YES' on the terminal
+pub struct StdinPrompt;
+
+impl Prompt for StdinPrompt {
+    fn confirm(&self, message: &str) -> Result<PromptResult, std::io::Error> {
+        print_message(message);
+        let mut input = String::new();
+        std::io::stdin().lock().read_line(&mut input)?;
+        Ok(answer_of(&input))
+    }
+}
+
+/// Confirms every tx, the `--yes` flag
+pub struct AssumeYesPrompt;
+
+impl Prompt for AssumeYesPrompt {
+    fn confirm(&self, message: &str) -> Result<PromptResult, std::io::Error> {
+        print_message(message);
+        if !json_output() {
+            println!("{} (assumed)", CONFIRMATION);
+        }
+        Ok(PromptResult::AssumedYes)
+    }
+}
+
+/// Declines every tx without showing it
+pub struct DenyAllPrompt;
+
+impl Prompt for DenyAllPrompt {
+    fn confirm(&self, _message: &str) -> Result<PromptResult, std::io::Error> {
+        Ok(PromptResult::Declined)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli_commands::confirm_transaction;
+
+    #[test]
+    fn test_prompts() {
+        assert_eq!(answer_of("YES\n"), PromptResult::Confirmed);
+        assert_eq!(answer_of("yes\n"), PromptResult::Declined);
+        assert_eq!(answer_of(""), PromptResult::Declined);
+        assert!(confirm_transaction(&AssumeYesPrompt, "YOU WILL BE TESTING.").unwrap());
+        assert!(!confirm_transaction(&DenyAllPrompt, "YOU WILL BE TESTING.").unwrap());
+    }
+}
@@ -0,0 +1,69 @@
+//! Skipping the datapoint publication in flat markets, driven by the `skip_publish` section of the
+//! oracle config. After a collection the new datapoint is not published if it is within
+//! `min_change_percent` of the pool rate, unless `max_skipped_epochs` epochs were skipped in a row.
+
+use crate::oracle_config::SkipPublishConfig;
+use crate::EpochID;
+
+pub struct PublishSkipper {
+    config: SkipPublishConfig,
+    skipped_epochs: u32,
+    /// Epoch of the last decision and whether it was skipped
+    last_decision: Option<(EpochID, bool)>,
+}
+
+impl PublishSkipper {
+    pub fn new(config: SkipPublishConfig) -> Self {
+        PublishSkipper {
+            config,
+            skipped_epochs: 0,
+            last_decision: None,
+        }
+    }
+
+    /// Whether to skip publishing `datapoint` in `epoch`. A publication resets the count of
+    /// skipped epochs.
+    pub fn should_skip(&mut self, datapoint: i64, pool_rate: u64, epoch: EpochID) -> bool {
+        let below_threshold = pool_rate != 0
+            && ((datapoint - pool_rate as i64).abs() as f64 / pool_rate as f64 * 100.0)
+                < self.config.min_change_percent;
+        // The main loop asks again on every iteration until the epoch changes
+        let counted = self.last_decision == Some((epoch, true));
+        let published = self.last_decision == Some((epoch, false));
+        let skip = !published
+            && below_threshold
+            && (counted || self.skipped_epochs < self.config.max_skipped_epochs);
+        if !skip {
+            self.skipped_epochs = 0;
+        } else if !counted {
+            self.skipped_epochs += 1;
+        }
+        self.last_decision = Some((epoch, skip));
+        skip
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_skip() {
+        let mut skipper = PublishSkipper::new(SkipPublishConfig {
+            min_change_percent: 1.0,
+            max_skipped_epochs: 2,
+        });
+        // 0.5% change
+        assert!(skipper.should_skip(1005, 1000, 1));
+        assert!(skipper.should_skip(1005, 1000, 1));
+        assert!(skipper.should_skip(995, 1000, 2));
+        // Forced after 2 skipped epochs
+        assert!(!skipper.should_skip(1005, 1000, 3));
+        assert!(!skipper.should_skip(1005, 1000, 3));
+        assert!(skipper.should_skip(1005, 1000, 4));
+        // 2% change
+        assert!(!skipper.should_skip(1020, 1000, 4));
+        assert!(!skipper.should_skip(1020, 1000, 5));
+        assert!(!skipper.should_skip(1005, 0, 6));
+    }
+}
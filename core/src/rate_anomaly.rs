@@ -0,0 +1,155 @@
+//! Detection of implausible jumps of the finalized pool rate, an early warning for the dApps
+//! depending on the feed even when the datapoint of this oracle was posted fine. A new rate is an
+//! anomaly when it changed by more than `max_change_percent` from the previous rate, or when its
+//! z-score against the last `window` rates exceeds `max_z_score`. The anomalies are logged,
+//! pushed on the `/events` WebSocket and notified.
+
+use std::collections::VecDeque;
+
+use crate::oracle_config::RateAnomalyConfig;
+
+const DEFAULT_MAX_CHANGE_PERCENT: f64 = 10.0;
+const DEFAULT_MAX_Z_SCORE: f64 = 4.0;
+const DEFAULT_WINDOW: usize = 30;
+/// Rates needed before the z-score is checked
+const MIN_Z_SCORE_SAMPLES: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateAnomaly {
+    pub epoch: u32,
+    pub rate: u64,
+    pub previous_rate: u64,
+    pub change_percent: f64,
+    /// `None` with too few rates or constant rates
+    pub z_score: Option<f64>,
+}
+
+impl RateAnomaly {
+    pub fn message(&self) -> String {
+        format!(
+            "The pool rate of epoch {} is {}, {:+.2}% from the previous rate {}{}",
+            self.epoch,
+            self.rate,
+            self.change_percent,
+            self.previous_rate,
+            self.z_score
+                .map_or(String::new(), |z| format!(" (z-score {:.1})", z))
+        )
+    }
+}
+
+pub struct RateAnomalyDetector {
+    max_change_percent: f64,
+    max_z_score: f64,
+    window: usize,
+    epoch: Option<u32>,
+    /// Finalized rates, oldest first
+    rates: VecDeque<u64>,
+}
+
+impl RateAnomalyDetector {
+    pub fn new(config: Option<&RateAnomalyConfig>) -> Self {
+        RateAnomalyDetector {
+            max_change_percent: config
+                .and_then(|c| c.max_change_percent)
+                .unwrap_or(DEFAULT_MAX_CHANGE_PERCENT),
+            max_z_score: config
+                .and_then(|c| c.max_z_score)
+                .unwrap_or(DEFAULT_MAX_Z_SCORE),
+            window: config
+                .and_then(|c| c.window)
+                .map_or(DEFAULT_WINDOW, |w| w as usize),
+            epoch: None,
+            rates: VecDeque::new(),
+        }
+    }
+
+    /// Start from the rates of the previous epochs (e.g. of the pool archive), oldest first
+    pub fn seed(&mut self, rates: impl IntoIterator<Item = u64>) {
+        for rate in rates {
+            self.push(rate);
+        }
+    }
+
+    fn push(&mut self, rate: u64) {
+        self.rates.push_back(rate);
+        while self.rates.len() > self.window {
+            self.rates.pop_front();
+        }
+    }
+
+    /// Observe the confirmed pool box of `epoch`, returns the anomaly of a new rate
+    pub fn observe(&mut self, epoch: u32, rate: u64) -> Option<RateAnomaly> {
+        if self.epoch.map_or(false, |e| epoch <= e) {
+            return None;
+        }
+        let first_observation = self.epoch.is_none();
+        self.epoch = Some(epoch);
+        let anomaly = match (self.rates.back(), first_observation) {
+            (Some(&previous_rate), false) if previous_rate != 0 => {
+                let change_percent =
+                    (rate as f64 - previous_rate as f64) / previous_rate as f64 * 100.0;
+                let z_score = self.z_score(rate);
+                let anomalous = change_percent.abs() > self.max_change_percent
+                    || z_score.map_or(false, |z| z.abs() > self.max_z_score);
+                anomalous.then(|| RateAnomaly {
+                    epoch,
+                    rate,
+                    previous_rate,
+                    change_percent,
+                    z_score,
+                })
+            }
+            (Some(_), _) | (None, _) => None,
+        };
+        self.push(rate);
+        anomaly
+    }
+
+    fn z_score(&self, rate: u64) -> Option<f64> {
+        if self.rates.len() < MIN_Z_SCORE_SAMPLES {
+            return None;
+        }
+        let n = self.rates.len() as f64;
+        let mean = self.rates.iter().map(|r| *r as f64).sum::<f64>() / n;
+        let variance = self
+            .rates
+            .iter()
+            .map(|r| (*r as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        let std_dev = variance.sqrt();
+        (std_dev > 0.0).then(|| (rate as f64 - mean) / std_dev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_anomaly_detector() {
+        let config = RateAnomalyConfig {
+            max_change_percent: Some(20.0),
+            max_z_score: Some(3.0),
+            window: Some(10),
+        };
+        let mut detector = RateAnomalyDetector::new(Some(&config));
+        detector.seed(vec![1000, 1010, 990, 1005, 995]);
+        // The rate the daemon started with is not checked
+        assert_eq!(detector.observe(5, 2000), None);
+        let anomaly = detector.observe(6, 1000).unwrap();
+        assert_eq!(anomaly.previous_rate, 2000);
+        assert_eq!(anomaly.change_percent, -50.0);
+        assert_eq!(detector.observe(6, 1000), None);
+
+        // Small change, far from the flat rates
+        let mut detector = RateAnomalyDetector::new(Some(&config));
+        detector.seed(vec![1000, 1001, 999, 1000, 1000, 1001, 999]);
+        assert_eq!(detector.observe(1, 1000), None);
+        let anomaly = detector.observe(2, 1050).unwrap();
+        assert_eq!(anomaly.change_percent, 5.0);
+        assert!(anomaly.z_score.unwrap() > 3.0);
+        assert_eq!(detector.observe(3, 1050), None);
+    }
+}
@@ -0,0 +1,275 @@
+//! Recovery of the pending operations of a daemon that was stopped or crashed. On startup the
+//! queued and submitted actions of the action queue are reconciled with the chain before the main
+//! loop runs: the txs of the history that left the mempool while the daemon was down are settled
+//! (and resubmitted if they still apply, see `tx_history`), then every open action is resumed,
+//! completed, requeued or abandoned, and the decision is logged. A tx submitted right before a
+//! crash, before its action was updated, is adopted by the action instead of being posted again.
+
+use crate::action_queue::{self, ActionStatus, QueuedAction};
+use crate::downtime::unix_now;
+use crate::node_interface::{SubmitTransaction, TxId};
+use crate::reorg::ChainSource;
+use crate::store::{Store, TxRecord};
+use crate::tx_history::{poll_tx_statuses, TxHistoryError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecoveryDecision {
+    /// Its tx is still in the mempool, waiting for it
+    AwaitTx(TxId),
+    /// Its tx was confirmed while the daemon was stopped
+    Completed,
+    /// Its tx was submitted but not recorded in the action before the daemon stopped
+    Adopted(TxId),
+    /// Its tx left the mempool or is unknown, the tx is built again
+    Requeued(String),
+    /// Waiting for its next attempt
+    Resumed,
+    /// No longer applies
+    Abandoned(String),
+    /// Its tx left the mempool and all its attempts are used
+    Failed(String),
+}
+
+/// Reconcile the open actions of `store` with the chain, the pool being in `epoch` (`None` if
+/// unknown). Returns the decision made for each open action.
+pub fn recover_pending_operations(
+    chain: &dyn ChainSource,
+    submitter: &dyn SubmitTransaction,
+    store: &Store,
+    epoch: Option<u32>,
+) -> Result<Vec<(QueuedAction, RecoveryDecision)>, TxHistoryError> {
+    let open_actions = store.open_actions()?;
+    let settled = poll_tx_statuses(chain, submitter, store)?;
+    let settled_tx_ids: Vec<&str> = settled.iter().map(|(tx, _)| tx.tx_id.as_str()).collect();
+    let mut pending_txs = store.pending_txs()?;
+    let mut decisions = Vec::new();
+    for open_action in open_actions {
+        // Settling its tx may have updated it
+        let mut action = match store.action(open_action.id)? {
+            Some(action) => action,
+            None => continue,
+        };
+        let decision = match action.status {
+            ActionStatus::Confirmed => RecoveryDecision::Completed,
+            ActionStatus::Failed => {
+                RecoveryDecision::Failed(action.last_error.clone().unwrap_or_default())
+            }
+            ActionStatus::Abandoned => {
+                RecoveryDecision::Abandoned(action.last_error.clone().unwrap_or_default())
+            }
+            ActionStatus::Submitted => match action.tx_id.clone() {
+                Some(tx_id) if store.tx(&tx_id)?.is_some() => RecoveryDecision::AwaitTx(tx_id),
+                _ => {
+                    action.requeue("its tx is not in the tx history".to_string(), unix_now());
+                    store.update_action(&action)?;
+                    RecoveryDecision::Requeued(action.last_error.clone().unwrap_or_default())
+                }
+            },
+            ActionStatus::Queued => match take_orphan_tx(store, &action, &mut pending_txs)? {
+                Some(tx_id) => {
+                    action_queue::record_attempt(store, action.clone(), Ok(tx_id.clone()))?;
+                    RecoveryDecision::Adopted(tx_id)
+                }
+                None => match (action.epoch, epoch) {
+                    (Some(action_epoch), Some(epoch)) if action_epoch < epoch => {
+                        let reason = format!("the pool left epoch {}", action_epoch);
+                        action_queue::abandon(store, action.clone(), &reason)?;
+                        RecoveryDecision::Abandoned(reason)
+                    }
+                    _ => match &open_action.tx_id {
+                        Some(tx_id) if settled_tx_ids.contains(&tx_id.as_str()) => {
+                            RecoveryDecision::Requeued(
+                                action.last_error.clone().unwrap_or_default(),
+                            )
+                        }
+                        _ => RecoveryDecision::Resumed,
+                    },
+                },
+            },
+        };
+        let action = store.action(action.id)?.unwrap_or(action);
+        log_decision(&action, &decision);
+        decisions.push((action, decision));
+    }
+    Ok(decisions)
+}
+
+/// A tx in the mempool of the kind and epoch of the queued `action`, submitted after it was
+/// queued and not bound to another action
+fn take_orphan_tx(
+    store: &Store,
+    action: &QueuedAction,
+    pending_txs: &mut Vec<TxRecord>,
+) -> Result<Option<TxId>, TxHistoryError> {
+    for (i, tx) in pending_txs.iter().enumerate() {
+        if tx.action == action.kind.name()
+            && (action.epoch.is_none() || tx.epoch == action.epoch)
+            && tx.submitted_at >= action.created_at
+            && store.action_of_tx(&tx.tx_id)?.is_none()
+        {
+            return Ok(Some(pending_txs.remove(i).tx_id));
+        }
+    }
+    Ok(None)
+}
+
+fn log_decision(action: &QueuedAction, decision: &RecoveryDecision) {
+    let name = action.kind.name();
+    match decision {
+        RecoveryDecision::AwaitTx(tx_id) => log::info!(
+            "Recovery: the {} action {} waits for its tx {} in the mempool",
+            name,
+            action.id,
+            tx_id
+        ),
+        RecoveryDecision::Completed => log::info!(
+            "Recovery: the tx of the {} action {} was confirmed, the action is complete",
+            name,
+            action.id
+        ),
+        RecoveryDecision::Adopted(tx_id) => log::info!(
+            "Recovery: the {} action {} adopted its tx {} submitted before the restart",
+            name,
+            action.id,
+            tx_id
+        ),
+        RecoveryDecision::Requeued(reason) => log::warn!(
+            "Recovery: the {} action {} is built again ({})",
+            name,
+            action.id,
+            reason
+        ),
+        RecoveryDecision::Resumed => log::info!(
+            "Recovery: the {} action {} resumes after {} attempts",
+            name,
+            action.id,
+            action.attempts
+        ),
+        RecoveryDecision::Abandoned(reason) => log::info!(
+            "Recovery: the {} action {} is abandoned ({})",
+            name,
+            action.id,
+            reason
+        ),
+        RecoveryDecision::Failed(reason) => log::error!(
+            "Recovery: the {} action {} failed after {} attempts ({})",
+            name,
+            action.id,
+            action.attempts,
+            reason
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::action_queue::{enqueue, record_attempt, ActionKind};
+    use crate::cli_commands::bootstrap::tests::SubmitTxMock;
+    use crate::reorg::tests::ChainMock;
+
+    #[test]
+    fn test_recover_pending_operations() {
+        let store = Store::open(Path::new(":memory:")).unwrap();
+        let chain = ChainMock::default();
+        let now = unix_now();
+        let submitted_tx = |tx_id: &str, action: &str, epoch: Option<u32>| TxRecord {
+            action: action.to_string(),
+            submitted_at: now,
+            ..TxRecord::submitted(tx_id, vec![force_any_val::<BoxId>()], 1, epoch)
+        };
+
+        // Refresh in the mempool, datapoint confirmed, extraction tx spent by another tx
+        let refresh = enqueue(&store, ActionKind::Refresh, Some(7)).unwrap();
+        store
+            .record_tx(&submitted_tx("refresh_tx", "refresh", Some(7)), None)
+            .unwrap();
+        record_attempt(&store, refresh.clone(), Ok("refresh_tx".to_string())).unwrap();
+        chain
+            .unconfirmed_tx_ids
+            .borrow_mut()
+            .push("refresh_tx".to_string());
+        let datapoint = enqueue(&store, ActionKind::PublishDatapoint, Some(7)).unwrap();
+        store
+            .record_tx(
+                &submitted_tx("datapoint_tx", "publish_datapoint", Some(7)),
+                None,
+            )
+            .unwrap();
+        record_attempt(&store, datapoint.clone(), Ok("datapoint_tx".to_string())).unwrap();
+        chain
+            .inclusion_heights
+            .borrow_mut()
+            .insert("datapoint_tx".to_string(), 1001);
+        let extraction = enqueue(&store, ActionKind::RewardExtraction, None).unwrap();
+        store
+            .record_tx(
+                &submitted_tx("extraction_tx", "reward_extraction", None),
+                None,
+            )
+            .unwrap();
+        record_attempt(&store, extraction.clone(), Ok("extraction_tx".to_string())).unwrap();
+
+        let decisions =
+            recover_pending_operations(&chain, &SubmitTxMock::default(), &store, Some(7))
+                .unwrap()
+                .into_iter()
+                .map(|(action, decision)| (action.id, decision))
+                .collect::<Vec<_>>();
+        assert_eq!(
+            decisions,
+            vec![
+                (
+                    refresh.id,
+                    RecoveryDecision::AwaitTx("refresh_tx".to_string())
+                ),
+                (datapoint.id, RecoveryDecision::Completed),
+                (
+                    extraction.id,
+                    RecoveryDecision::Requeued("tx failed".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_adopt_orphan_tx_and_abandon_stale_action() {
+        let store = Store::open(Path::new(":memory:")).unwrap();
+        let chain = ChainMock::default();
+        // Crashed between the submission and the update of the action
+        let datapoint = enqueue(&store, ActionKind::PublishDatapoint, Some(8)).unwrap();
+        let tx = TxRecord {
+            action: "publish_datapoint".to_string(),
+            submitted_at: datapoint.created_at,
+            ..TxRecord::submitted("datapoint_tx", vec![force_any_val::<BoxId>()], 1, Some(8))
+        };
+        store.record_tx(&tx, None).unwrap();
+        chain
+            .unconfirmed_tx_ids
+            .borrow_mut()
+            .push("datapoint_tx".to_string());
+        let stale_refresh = enqueue(&store, ActionKind::Refresh, Some(7)).unwrap();
+
+        let decisions =
+            recover_pending_operations(&chain, &SubmitTxMock::default(), &store, Some(8)).unwrap();
+        assert_eq!(
+            decisions[0].1,
+            RecoveryDecision::Adopted("datapoint_tx".to_string())
+        );
+        assert_eq!(
+            decisions[1],
+            (
+                store.action(stale_refresh.id).unwrap().unwrap(),
+                RecoveryDecision::Abandoned("the pool left epoch 7".to_string())
+            )
+        );
+        let adopted = store.action_of_tx("datapoint_tx").unwrap().unwrap();
+        assert_eq!(adopted.id, datapoint.id);
+        assert_eq!(adopted.attempts, 1);
+    }
+}
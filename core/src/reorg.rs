@@ -0,0 +1,331 @@
+//! Detection of chain reorganizations in the main loop.
+//!
+//! The block id at the height the pool and local oracle boxes were observed is recorded, and
+//! compared with the best chain on the next iteration. After a reorg the observed boxes are
+//! re-validated against the UTXO set, the daemon doesn't act on boxes the reorg removed until the
+//! scans report the new ones. Txs submitted by the daemon are tracked until they are confirmed,
+//! a tx which disappeared (not in the mempool while its inputs are unspent) is dropped so that the
+//! action is built again. The next run waits for the submitted txs the store has in the mempool.
+
+use crate::events::{PoolEvent, EVENTS};
+use crate::latency::POSTING_LATENCY;
+use crate::node_interface::{
+    get_block_id_at_height, get_unconfirmed_transactions, get_wallet_tx_inclusion_height,
+    is_box_unspent, TxId,
+};
+use crate::store::{Store, StoreError};
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use ergo_node_interface::node_interface::NodeError;
+
+pub trait ChainSource {
+    /// Id of the block at `height` of the best chain, `None` if the chain is not that high
+    fn block_id_at_height(&self, height: u32) -> Result<Option<String>, NodeError>;
+
+    /// Whether the box is in the UTXO set
+    fn is_box_unspent(&self, box_id: &BoxId) -> Result<bool, NodeError>;
+
+    /// Ids of the txs in the mempool
+    fn unconfirmed_tx_ids(&self) -> Result<Vec<TxId>, NodeError>;
+
+    /// Height of the block including the tx, `None` if unknown
+    fn tx_inclusion_height(&self, tx_id: &TxId) -> Result<Option<u32>, NodeError>;
+}
+
+/// Chain of the node
+pub struct NodeChain {}
+
+impl ChainSource for NodeChain {
+    fn block_id_at_height(&self, height: u32) -> Result<Option<String>, NodeError> {
+        get_block_id_at_height(height)
+    }
+
+    fn is_box_unspent(&self, box_id: &BoxId) -> Result<bool, NodeError> {
+        is_box_unspent(box_id)
+    }
+
+    fn unconfirmed_tx_ids(&self) -> Result<Vec<TxId>, NodeError> {
+        Ok(get_unconfirmed_transactions()?
+            .iter()
+            .map(|tx| String::from(tx.id().0))
+            .collect())
+    }
+
+    fn tx_inclusion_height(&self, tx_id: &TxId) -> Result<Option<u32>, NodeError> {
+        get_wallet_tx_inclusion_height(tx_id)
+    }
+}
+
+/// Boxes the daemon acted on and the block at which they were observed
+#[derive(Debug, Clone)]
+struct Observation {
+    height: u32,
+    block_id: String,
+    box_ids: Vec<BoxId>,
+}
+
+#[derive(Debug, Clone)]
+struct SubmittedTx {
+    tx_id: TxId,
+    input_box_ids: Vec<BoxId>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReorgCheck {
+    Unchanged,
+    /// The block observed at `height` is no longer on the best chain. `removed_box_ids` are the
+    /// observed boxes which don't exist anymore.
+    Reorg {
+        height: u32,
+        removed_box_ids: Vec<BoxId>,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct ReorgTracker {
+    observation: Option<Observation>,
+    /// Observed boxes removed by a reorg, the scans might still report them for a while
+    removed_box_ids: Vec<BoxId>,
+    submitted_txs: Vec<SubmittedTx>,
+}
+
+impl ReorgTracker {
+    pub fn new() -> Self {
+        ReorgTracker::default()
+    }
+
+    /// Check whether the block of the last observation is still on the best chain and drop the
+    /// submitted txs which are confirmed or disappeared.
+    pub fn check(&mut self, chain: &dyn ChainSource) -> Result<ReorgCheck, NodeError> {
+        self.drop_settled_txs(chain)?;
+        let observation = match &self.observation {
+            Some(observation) => observation.clone(),
+            None => return Ok(ReorgCheck::Unchanged),
+        };
+        if chain.block_id_at_height(observation.height)?.as_ref() == Some(&observation.block_id) {
+            return Ok(ReorgCheck::Unchanged);
+        }
+        let mut removed_box_ids = Vec::new();
+        for box_id in observation.box_ids {
+            if !chain.is_box_unspent(&box_id)? {
+                removed_box_ids.push(box_id);
+            }
+        }
+        self.removed_box_ids.extend(removed_box_ids.iter().cloned());
+        self.observation = None;
+        Ok(ReorgCheck::Reorg {
+            height: observation.height,
+            removed_box_ids,
+        })
+    }
+
+    /// Record the boxes the daemon is about to act on at `height`. Returns `false` if any of them
+    /// was removed by a reorg (the scans didn't catch up yet), the daemon must not act on them.
+    pub fn observe(
+        &mut self,
+        chain: &dyn ChainSource,
+        height: u32,
+        box_ids: Vec<BoxId>,
+    ) -> Result<bool, NodeError> {
+        if box_ids.iter().any(|id| self.removed_box_ids.contains(id)) {
+            return Ok(false);
+        }
+        self.removed_box_ids.clear();
+        self.observation = chain
+            .block_id_at_height(height)?
+            .map(|block_id| Observation {
+                height,
+                block_id,
+                box_ids,
+            });
+        Ok(true)
+    }
+
+    pub fn track_submitted_tx(&mut self, tx_id: TxId, input_box_ids: Vec<BoxId>) {
+        self.submitted_txs.push(SubmittedTx {
+            tx_id,
+            input_box_ids,
+        });
+    }
+
+    /// Submitted txs not confirmed yet
+    pub fn unconfirmed_submitted_tx_ids(&self) -> Vec<TxId> {
+        self.submitted_txs
+            .iter()
+            .map(|tx| tx.tx_id.clone())
+            .collect()
+    }
+
+    /// Track the txs of the store not settled yet (e.g. submitted by the previous run), the settled
+    /// ones are dropped on the next check
+    pub fn load_pending_txs(&mut self, store: &Store) -> Result<(), StoreError> {
+        for tx in store.pending_txs()? {
+            if !self.submitted_txs.iter().any(|t| t.tx_id == tx.tx_id) {
+                self.track_submitted_tx(tx.tx_id, tx.input_box_ids);
+            }
+        }
+        Ok(())
+    }
+
+    fn drop_settled_txs(&mut self, chain: &dyn ChainSource) -> Result<(), NodeError> {
+        if self.submitted_txs.is_empty() {
+            return Ok(());
+        }
+        let unconfirmed_tx_ids = chain.unconfirmed_tx_ids()?;
+        let mut settled_tx_ids = Vec::new();
+        for tx in &self.submitted_txs {
+            if unconfirmed_tx_ids.contains(&tx.tx_id) {
+                continue;
+            }
+            let mut inputs_unspent = false;
+            for box_id in &tx.input_box_ids {
+                if chain.is_box_unspent(box_id)? {
+                    inputs_unspent = true;
+                    break;
+                }
+            }
+            if inputs_unspent {
+                log::warn!(
+                    "Submitted tx {} disappeared (not in the mempool, inputs unspent), rolling back",
+                    tx.tx_id
+                );
+                EVENTS.publish(PoolEvent::TxFailed {
+                    tx_id: tx.tx_id.clone(),
+                });
+            } else {
+                log::debug!("Submitted tx {} confirmed", tx.tx_id);
+                POSTING_LATENCY.record_confirmed(&tx.tx_id);
+                EVENTS.publish(PoolEvent::TxConfirmed {
+                    tx_id: tx.tx_id.clone(),
+                });
+            }
+            settled_tx_ids.push(tx.tx_id.clone());
+        }
+        self.submitted_txs
+            .retain(|tx| !settled_tx_ids.contains(&tx.tx_id));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::store::{TxRecord, TxStatus};
+
+    #[derive(Default)]
+    pub(crate) struct ChainMock {
+        pub block_ids: RefCell<HashMap<u32, String>>,
+        pub unspent_box_ids: RefCell<Vec<BoxId>>,
+        pub unconfirmed_tx_ids: RefCell<Vec<TxId>>,
+        pub inclusion_heights: RefCell<HashMap<TxId, u32>>,
+    }
+
+    impl ChainSource for ChainMock {
+        fn block_id_at_height(&self, height: u32) -> Result<Option<String>, NodeError> {
+            Ok(self.block_ids.borrow().get(&height).cloned())
+        }
+
+        fn is_box_unspent(&self, box_id: &BoxId) -> Result<bool, NodeError> {
+            Ok(self.unspent_box_ids.borrow().contains(box_id))
+        }
+
+        fn unconfirmed_tx_ids(&self) -> Result<Vec<TxId>, NodeError> {
+            Ok(self.unconfirmed_tx_ids.borrow().clone())
+        }
+
+        fn tx_inclusion_height(&self, tx_id: &TxId) -> Result<Option<u32>, NodeError> {
+            Ok(self.inclusion_heights.borrow().get(tx_id).copied())
+        }
+    }
+
+    #[test]
+    fn test_reorg_removes_observed_boxes() {
+        let chain = ChainMock::default();
+        let pool_box_id = force_any_val::<BoxId>();
+        let new_pool_box_id = force_any_val::<BoxId>();
+        chain.block_ids.borrow_mut().insert(100, "a".to_string());
+        chain.unspent_box_ids.borrow_mut().push(pool_box_id.clone());
+
+        let mut tracker = ReorgTracker::new();
+        assert!(tracker
+            .observe(&chain, 100, vec![pool_box_id.clone()])
+            .unwrap());
+        assert_eq!(tracker.check(&chain).unwrap(), ReorgCheck::Unchanged);
+
+        // The block at 100 is replaced and the pool box with it
+        chain.block_ids.borrow_mut().insert(100, "b".to_string());
+        chain.unspent_box_ids.borrow_mut().clear();
+        assert_eq!(
+            tracker.check(&chain).unwrap(),
+            ReorgCheck::Reorg {
+                height: 100,
+                removed_box_ids: vec![pool_box_id.clone()]
+            }
+        );
+        // The scan still reports the removed box
+        assert!(!tracker
+            .observe(&chain, 101, vec![pool_box_id.clone()])
+            .unwrap());
+        assert!(tracker.observe(&chain, 101, vec![new_pool_box_id]).unwrap());
+        assert!(tracker.observe(&chain, 101, vec![pool_box_id]).unwrap());
+    }
+
+    #[test]
+    fn test_disappeared_tx_is_dropped() {
+        let chain = ChainMock::default();
+        let input_box_id = force_any_val::<BoxId>();
+        chain
+            .unspent_box_ids
+            .borrow_mut()
+            .push(input_box_id.clone());
+        chain.unconfirmed_tx_ids.borrow_mut().push("tx".to_string());
+
+        let mut tracker = ReorgTracker::new();
+        tracker.track_submitted_tx("tx".to_string(), vec![input_box_id]);
+        tracker.check(&chain).unwrap();
+        assert_eq!(
+            tracker.unconfirmed_submitted_tx_ids(),
+            vec!["tx".to_string()]
+        );
+
+        // Dropped from the mempool, the input is still unspent
+        chain.unconfirmed_tx_ids.borrow_mut().clear();
+        tracker.check(&chain).unwrap();
+        assert!(tracker.unconfirmed_submitted_tx_ids().is_empty());
+    }
+
+    #[test]
+    fn test_load_pending_txs() {
+        let store = Store::open(std::path::Path::new(":memory:")).unwrap();
+        let input_box_id = force_any_val::<BoxId>();
+        store
+            .record_tx(
+                &TxRecord::submitted("tx", vec![input_box_id.clone()], 1_000_000, None),
+                None,
+            )
+            .unwrap();
+        store
+            .record_tx(
+                &TxRecord::submitted("settled_tx", vec![input_box_id], 1_000_000, None),
+                None,
+            )
+            .unwrap();
+        store
+            .settle_tx("settled_tx", TxStatus::Confirmed, Some(1001))
+            .unwrap();
+
+        let mut next_run_tracker = ReorgTracker::new();
+        next_run_tracker.load_pending_txs(&store).unwrap();
+        assert_eq!(
+            next_run_tracker.unconfirmed_submitted_tx_ids(),
+            vec!["tx".to_string()]
+        );
+        // Already tracked
+        next_run_tracker.load_pending_txs(&store).unwrap();
+        assert_eq!(next_run_tracker.unconfirmed_submitted_tx_ids().len(), 1);
+    }
+}
@@ -0,0 +1,304 @@
+//! Automatic extraction of the reward tokens accumulated in the local oracle box, driven by the
+//! `reward_extraction` section of the oracle config. The tokens extracted within the daily window
+//! are kept in the store, a restart of the daemon doesn't reset the `max_tokens_per_day` limit.
+
+use ergo_lib::ergotree_ir::chain::address::Address;
+use serde::{Deserialize, Serialize};
+
+use crate::box_kind::OracleBox;
+use crate::cli_commands::extract_reward_tokens::build_extract_reward_tokens_tx;
+use crate::cli_commands::extract_reward_tokens::ExtractRewardTokensActionError;
+use crate::downtime::unix_now;
+use crate::node_interface::sign_and_submit_transaction;
+use crate::node_interface::TxId;
+use crate::oracle_config::RewardExtractionConfig;
+use crate::oracle_state::LocalDatapointBoxSource;
+use crate::store;
+use crate::wallet::WalletDataSource;
+use crate::EpochID;
+
+const ONE_DAY_SECS: u64 = 24 * 60 * 60;
+
+/// Extractions within the current 24 hour window, persisted in the store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RewardExtractionWindow {
+    /// Unix time in seconds
+    pub start: u64,
+    /// Reward tokens extracted since `start`
+    pub extracted: u64,
+    pub last_extraction_epoch: Option<EpochID>,
+}
+
+impl RewardExtractionWindow {
+    fn starting_at(start: u64) -> Self {
+        RewardExtractionWindow {
+            start,
+            extracted: 0,
+            last_extraction_epoch: None,
+        }
+    }
+}
+
+pub struct RewardExtractor {
+    config: RewardExtractionConfig,
+    window: RewardExtractionWindow,
+    /// The next extraction ignores the threshold and epoch interval (requested by an admin)
+    forced: bool,
+}
+
+impl RewardExtractor {
+    /// The extractor resumes the window persisted in the store, if any
+    pub fn new(config: RewardExtractionConfig) -> Self {
+        let window = match store::get().and_then(|store| store.reward_extraction_window()) {
+            Ok(Some(window)) => window,
+            Ok(None) => RewardExtractionWindow::starting_at(unix_now()),
+            Err(e) => {
+                log::warn!("Reward extraction: failed to load the daily window: {}", e);
+                RewardExtractionWindow::starting_at(unix_now())
+            }
+        };
+        RewardExtractor {
+            config,
+            window,
+            forced: false,
+        }
+    }
+
+    /// Extract on the next call of `extract_if_due`, whatever the threshold and epoch interval.
+    /// The daily limit still applies.
+    pub fn force_next_extraction(&mut self) {
+        self.forced = true;
+    }
+
+    /// Extract reward tokens from the local oracle box to the configured payout address if the
+    /// configured threshold or epoch interval is reached. Returns the tx id of the submitted
+    /// extraction tx, if any.
+    pub fn extract_if_due(
+        &mut self,
+        local_datapoint_box_source: &dyn LocalDatapointBoxSource,
+        wallet: &dyn WalletDataSource,
+        current_epoch: EpochID,
+        height: u32,
+        change_address: Address,
+    ) -> Result<Option<TxId>, ExtractRewardTokensActionError> {
+        let oracle_box = match local_datapoint_box_source.get_local_oracle_datapoint_box()? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+        // The oracle box must always keep at least 1 reward token.
+        let available = oracle_box.reward_token().amount.as_u64().saturating_sub(1);
+        let amount = match self.amount_to_extract(available, current_epoch, unix_now()) {
+            Some(amount) => amount,
+            None => return Ok(None),
+        };
+        let payout_address = self.config.payout_address.clone();
+        // A dry run leaves the daily window as it is, it must not use up the real daily limit
+        self.forced = false;
+        if self.config.dry_run {
+            log::info!(
+                "Reward extraction (dry run): would extract {} reward tokens to {}",
+                amount,
+                payout_address.to_base58()
+            );
+            return Ok(None);
+        }
+        let (unsigned_tx, extracted) = build_extract_reward_tokens_tx(
+            local_datapoint_box_source,
+            wallet,
+            payout_address.address(),
+            Some(amount),
+            height,
+            change_address,
+        )?;
+        let tx_id = sign_and_submit_transaction(&unsigned_tx, "reward_extraction")?;
+        log::info!(
+            "Reward extraction: extracted {} reward tokens to {}, tx id: {}",
+            extracted,
+            payout_address.to_base58(),
+            tx_id
+        );
+        self.window.last_extraction_epoch = Some(current_epoch);
+        self.window.extracted += extracted;
+        if let Err(e) =
+            store::get().and_then(|store| store.set_reward_extraction_window(&self.window))
+        {
+            log::warn!("Reward extraction: failed to save the daily window: {}", e);
+        }
+        Ok(Some(tx_id))
+    }
+
+    /// Returns the number of reward tokens to extract (if extraction is due) given the number of
+    /// tokens available for extraction, the current epoch of the pool and the unix time `now`.
+    fn amount_to_extract(
+        &mut self,
+        available: u64,
+        current_epoch: EpochID,
+        now: u64,
+    ) -> Option<u64> {
+        if now.saturating_sub(self.window.start) >= ONE_DAY_SECS {
+            self.window.start = now;
+            self.window.extracted = 0;
+        }
+        if available == 0 {
+            return None;
+        }
+        let threshold_reached = self
+            .config
+            .min_reward_tokens
+            .map(|min| available >= min)
+            .unwrap_or(false);
+        let interval_elapsed = self
+            .config
+            .every_n_epochs
+            .map(|n| match self.window.last_extraction_epoch {
+                Some(last) => current_epoch.saturating_sub(last) >= n,
+                None => true,
+            })
+            .unwrap_or(false);
+        if !threshold_reached && !interval_elapsed && !self.forced {
+            return None;
+        }
+        let amount = match self.config.max_tokens_per_day {
+            Some(max) => {
+                let remaining = max.saturating_sub(self.window.extracted);
+                if remaining < available {
+                    log::warn!(
+                        "Reward extraction: daily limit of {} reward tokens reached, extracting {} of {}",
+                        max,
+                        remaining,
+                        available
+                    );
+                }
+                available.min(remaining)
+            }
+            None => available,
+        };
+        if amount == 0 {
+            None
+        } else {
+            Some(amount)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use ergo_lib::chain::ergo_state_context::ErgoStateContext;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::address::AddressEncoder;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::box_kind::{OracleBoxWrapper, OracleBoxWrapperInputs};
+    use crate::contracts::oracle::OracleContractParameters;
+    use crate::oracle_config::BASE_FEE;
+    use crate::pool_commands::test_utils::{
+        generate_token_ids, make_datapoint_box, OracleBoxMock, WalletDataMock,
+    };
+
+    fn make_config(
+        min_reward_tokens: Option<u64>,
+        every_n_epochs: Option<u32>,
+        max_tokens_per_day: Option<u64>,
+    ) -> RewardExtractionConfig {
+        RewardExtractionConfig {
+            payout_address: AddressEncoder::unchecked_parse_network_address_from_str(
+                "9iHyKxXs2ZNLMp9N9gbUT9V8gTbsV7HED1C1VhttMfBUMPDyF7r",
+            )
+            .unwrap(),
+            min_reward_tokens,
+            every_n_epochs,
+            max_tokens_per_day,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn test_extract_on_threshold() {
+        let mut extractor = RewardExtractor::new(make_config(Some(50), None, None));
+        let now = unix_now();
+        assert_eq!(extractor.amount_to_extract(49, 1, now), None);
+        assert_eq!(extractor.amount_to_extract(50, 1, now), Some(50));
+    }
+
+    #[test]
+    fn test_extract_every_n_epochs() {
+        let mut extractor = RewardExtractor::new(make_config(None, Some(10), None));
+        let now = unix_now();
+        assert_eq!(extractor.amount_to_extract(5, 1, now), Some(5));
+        extractor.window.last_extraction_epoch = Some(1);
+        assert_eq!(extractor.amount_to_extract(5, 10, now), None);
+        assert_eq!(extractor.amount_to_extract(5, 11, now), Some(5));
+        assert_eq!(extractor.amount_to_extract(0, 11, now), None);
+    }
+
+    #[test]
+    fn test_forced_extraction() {
+        let mut extractor = RewardExtractor::new(make_config(Some(50), None, None));
+        let now = unix_now();
+        assert_eq!(extractor.amount_to_extract(10, 1, now), None);
+        extractor.force_next_extraction();
+        assert_eq!(extractor.amount_to_extract(10, 1, now), Some(10));
+        assert_eq!(extractor.amount_to_extract(0, 1, now), None);
+    }
+
+    #[test]
+    fn test_extract_daily_limit() {
+        let mut extractor = RewardExtractor::new(make_config(Some(1), None, Some(100)));
+        let now = extractor.window.start;
+        extractor.window.extracted = 80;
+        assert_eq!(extractor.amount_to_extract(50, 1, now), Some(20));
+        extractor.window.extracted = 100;
+        assert_eq!(extractor.amount_to_extract(50, 1, now), None);
+        // limit is reset after 24 hours
+        assert_eq!(
+            extractor.amount_to_extract(50, 1, now + ONE_DAY_SECS),
+            Some(50)
+        );
+    }
+
+    #[test]
+    fn test_dry_run_leaves_window_unchanged() {
+        let ctx = force_any_val::<ErgoStateContext>();
+        let height = ctx.pre_header.height;
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let oracle_box_wrapper_inputs =
+            OracleBoxWrapperInputs::try_from((OracleContractParameters::default(), &token_ids))
+                .unwrap();
+        let oracle_box = OracleBoxWrapper::new(
+            make_datapoint_box(
+                *secret.public_image().h,
+                200,
+                1,
+                &token_ids,
+                BASE_FEE.checked_mul_u32(100).unwrap(),
+                height - 9,
+            ),
+            &oracle_box_wrapper_inputs,
+        )
+        .unwrap();
+        let config = RewardExtractionConfig {
+            dry_run: true,
+            ..make_config(Some(1), None, Some(100))
+        };
+        let change_address = config.payout_address.address();
+        let mut extractor = RewardExtractor::new(config);
+        let window = extractor.window;
+        let tx_id = extractor
+            .extract_if_due(
+                &OracleBoxMock { oracle_box },
+                &WalletDataMock {
+                    unspent_boxes: vec![],
+                },
+                1,
+                height,
+                change_address,
+            )
+            .unwrap();
+        assert_eq!(tx_id, None);
+        assert_eq!(extractor.window, window);
+    }
+}
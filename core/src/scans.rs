@@ -3,9 +3,13 @@ use crate::box_kind::{PoolBoxWrapperInputs, RefreshBoxWrapperInputs};
 use crate::contracts::pool::{PoolContract, PoolContractError};
 use crate::contracts::refresh::{RefreshContract, RefreshContractError};
 /// This file holds logic related to UTXO-set scans
-use crate::node_interface::{get_scan_boxes, register_scan};
+use crate::node_interface::{
+    current_block_height, deregister_scan, get_scan_boxes, get_spent_scan_boxes, list_scans,
+    register_scan,
+};
 
 use derive_more::From;
+use ergo_lib::ergo_chain_types::blake2b256_hash;
 use ergo_lib::ergotree_ir::chain::address::NetworkAddress;
 use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
 use ergo_lib::ergotree_ir::chain::token::TokenId;
@@ -14,7 +18,12 @@ use ergo_lib::ergotree_ir::mir::constant::Constant;
 use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
 use ergo_node_interface::node_interface::NodeError;
 use log::info;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
 use thiserror::Error;
 
 /// Integer which is provided by the Ergo node to reference a given scan.
@@ -25,92 +34,464 @@ pub type Result<T> = std::result::Result<T, ScanError>;
 #[derive(Debug, From, Error)]
 pub enum ScanError {
     #[error("node error: {0}")]
-    NodeError(NodeError),
+    NodeError(#[source] NodeError),
     #[error("no boxes found")]
     NoBoxesFound,
     #[error("failed to register scan")]
     FailedToRegister,
     #[error("IO error: {0}")]
-    IoError(std::io::Error),
+    IoError(#[source] std::io::Error),
     #[error("refresh contract error: {0}")]
     RefreshContract(RefreshContractError),
     #[error("pool contract error: {0}")]
     PoolContract(PoolContractError),
     #[error("address util error: {0}")]
     AddressUtilError(AddressUtilError),
+    #[error("scan state file error: {0}")]
+    StateFile(serde_json::Error),
+    #[error("scan state file is corrupted (checksum mismatch)")]
+    StateChecksumMismatch,
+    #[error("scan state file version {found}, expected {expected}")]
+    StateVersionMismatch { found: u32, expected: u32 },
+    #[error("no {0} scan registered")]
+    ScanNotRegistered(&'static str),
 }
 
-/// A `Scan` is a name + scan_id for a given scan with extra methods for acquiring boxes.
+/// A `Scan` is a box kind + scan_id for a given scan with extra methods for acquiring boxes.
 #[derive(Debug, Clone)]
 pub struct Scan {
-    name: &'static str,
+    kind: ScanKind,
     id: ScanID,
 }
 
 impl Scan {
-    /// Create a new `Scan` with provided name & scan_id
-    pub fn new(name: &'static str, scan_id: &String) -> Scan {
+    /// Create a new `Scan` with provided box kind & scan_id
+    pub fn new(kind: ScanKind, scan_id: &String) -> Scan {
         Scan {
-            name,
+            kind,
             id: scan_id.clone(),
         }
     }
 
-    /// Registers a scan in the node and returns a `Scan` as a result
-    pub fn register(name: &'static str, tracking_rule: serde_json::Value) -> Result<Scan> {
+    pub fn kind(&self) -> ScanKind {
+        self.kind
+    }
+
+    /// Returns all boxes found by the scan. The boxes are fetched from the node once per height.
+    pub fn get_boxes(&self) -> Result<Vec<ErgoBox>> {
+        let height = current_block_height()? as u32;
+        SCAN_CACHE.get_or_fetch(&self.id, height, || Ok(get_scan_boxes(&self.id)?))
+    }
+
+    /// Returns the first box found by the scan
+    pub fn get_box(&self) -> Result<Option<ErgoBox>> {
+        Ok(self.get_boxes()?.first().cloned())
+    }
+
+    /// Returns the spent boxes found by the scan (not cached)
+    pub fn get_spent_boxes(&self) -> Result<Vec<ErgoBox>> {
+        Ok(get_spent_scan_boxes(&self.id)?)
+    }
+}
+
+/// Scan boxes cached per height, shared by all the scans of the process (the main loop, the CLI
+/// commands and the REST API). The boxes found by a scan only change with a new block, so they
+/// are refetched from the node when the height advances.
+pub struct ScanCache {
+    entries: Mutex<HashMap<ScanID, CachedScanBoxes>>,
+}
+
+struct CachedScanBoxes {
+    height: u32,
+    boxes: Vec<ErgoBox>,
+}
+
+static SCAN_CACHE: Lazy<ScanCache> = Lazy::new(ScanCache::new);
+
+impl ScanCache {
+    pub fn new() -> Self {
+        ScanCache {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the boxes of the scan cached at `height`, or fetches them with `fetch`. The lock is
+    /// not held while fetching.
+    pub fn get_or_fetch(
+        &self,
+        scan_id: &ScanID,
+        height: u32,
+        fetch: impl FnOnce() -> Result<Vec<ErgoBox>>,
+    ) -> Result<Vec<ErgoBox>> {
+        if let Some(cached) = self.entries.lock().unwrap().get(scan_id) {
+            if cached.height == height {
+                return Ok(cached.boxes.clone());
+            }
+        }
+        let boxes = fetch()?;
+        self.entries.lock().unwrap().insert(
+            scan_id.clone(),
+            CachedScanBoxes {
+                height,
+                boxes: boxes.clone(),
+            },
+        );
+        Ok(boxes)
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+impl Default for ScanCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Drop the cached scan boxes, e.g. after a chain reorganization replaced the block at the
+/// current height
+pub fn clear_scan_cache() {
+    SCAN_CACHE.clear();
+}
+
+/// Version of the scan registration, part of the scan names. Bump it when the scans must be
+/// registered anew for a reason their tracking rules don't capture.
+pub const SCANS_VERSION: u32 = 1;
+
+/// Prefix of the names of the scans registered by oracle-core, followed by the pool NFT id. Only
+/// the stale scans of the pool are deregistered, the oracle-core instances of several pools share
+/// a node without deregistering each other's scans.
+const SCAN_NAME_PREFIX: &str = "oracle-core";
+
+/// Length of the pool NFT id (base16) in the scan names
+const SCAN_NAME_POOL_ID_LEN: usize = 16;
+
+/// Names of the scans registered before versioned scan names (saved in `scanIDs.json`)
+const LEGACY_SCAN_NAMES: &[&str] = &[
+    "All Datapoints Scan",
+    "Local Oracle Datapoint Scan",
+    "Local Ballot Box Scan",
+    "Ballot Box Scan",
+    "Pool Box Scan",
+    "Refresh Box Scan",
+    "Update Box Scan",
+    "Buyback Box Scan",
+];
+
+pub const LEGACY_SCAN_IDS_FILE: &str = "scanIDs.json";
+
+/// File the registered scans are saved to
+pub const SCAN_STATE_FILE: &str = "scan_state.json";
+
+/// The box kinds oracle-core tracks with a scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanKind {
+    Datapoints,
+    LocalDatapoint,
+    LocalBallot,
+    Ballots,
+    Pool,
+    Refresh,
+    Update,
+    Buyback,
+}
+
+impl ScanKind {
+    /// All the box kinds, a scan is registered for each of them the pool uses
+    pub const ALL: [ScanKind; 8] = [
+        ScanKind::Datapoints,
+        ScanKind::LocalDatapoint,
+        ScanKind::LocalBallot,
+        ScanKind::Ballots,
+        ScanKind::Pool,
+        ScanKind::Refresh,
+        ScanKind::Update,
+        ScanKind::Buyback,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScanKind::Datapoints => "all datapoints",
+            ScanKind::LocalDatapoint => "local datapoint",
+            ScanKind::LocalBallot => "local ballot box",
+            ScanKind::Ballots => "ballot boxes",
+            ScanKind::Pool => "pool box",
+            ScanKind::Refresh => "refresh box",
+            ScanKind::Update => "update box",
+            ScanKind::Buyback => "buyback box",
+        }
+    }
+}
+
+/// A scan declared by a box kind of a pool: the rule the node tracks the boxes with
+#[derive(Debug, Clone)]
+pub struct ScanSpec {
+    pub kind: ScanKind,
+    /// Pool NFT id (base16) of the pool the scan is registered for
+    pub pool_id: String,
+    pub tracking_rule: serde_json::Value,
+}
+
+impl ScanSpec {
+    pub fn new(
+        kind: ScanKind,
+        pool_nft_token_id: &TokenId,
+        tracking_rule: serde_json::Value,
+    ) -> Self {
+        ScanSpec {
+            kind,
+            pool_id: String::from(pool_nft_token_id.clone()),
+            tracking_rule,
+        }
+    }
+
+    /// Prefix of the names of the scans of the pool, e.g. `oracle-core 0fb1eca464695074 `
+    pub fn pool_name_prefix(&self) -> String {
+        format!(
+            "{} {} ",
+            SCAN_NAME_PREFIX,
+            &self.pool_id[..SCAN_NAME_POOL_ID_LEN.min(self.pool_id.len())]
+        )
+    }
+
+    /// Name of the scan in the node, e.g. `oracle-core 0fb1eca464695074 v1 pool box 5f3a9c01`.
+    /// The name holds the pool NFT id and the hash of the tracking rule, so a changed rule (e.g.
+    /// after a pool update) gets a new scan.
+    pub fn versioned_name(&self) -> String {
+        let rule_hash = base16::encode_lower(&blake2b256_hash(
+            serde_json::to_string(&self.tracking_rule)
+                .unwrap()
+                .as_bytes(),
+        ));
+        format!(
+            "{}v{} {} {}",
+            self.pool_name_prefix(),
+            SCANS_VERSION,
+            self.kind.name(),
+            &rule_hash[..8]
+        )
+    }
+}
+
+/// A scan registered in the node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeScan {
+    pub scan_id: ScanID,
+    pub name: String,
+}
+
+/// Scan registration endpoints of the node
+pub trait ScanRegistry {
+    fn list_scans(&self) -> Result<Vec<NodeScan>>;
+    fn register_scan(&self, name: &str, tracking_rule: &serde_json::Value) -> Result<ScanID>;
+    fn deregister_scan(&self, scan_id: &ScanID) -> Result<()>;
+}
+
+pub struct NodeScanRegistry {}
+
+impl ScanRegistry for NodeScanRegistry {
+    fn list_scans(&self) -> Result<Vec<NodeScan>> {
+        Ok(list_scans()?
+            .into_iter()
+            .map(|(scan_id, name)| NodeScan { scan_id, name })
+            .collect())
+    }
+
+    fn register_scan(&self, name: &str, tracking_rule: &serde_json::Value) -> Result<ScanID> {
         let scan_json = json!({
             "scanName": name,
             "trackingRule": tracking_rule,
         });
-
         info!(
             "Registering Scan:\n{}",
             serde_json::to_string_pretty(&scan_json).unwrap()
         );
-
         let scan_id = register_scan(&scan_json)?;
+        if scan_id == "null" {
+            return Err(ScanError::FailedToRegister);
+        }
         info!("Scan Successfully Set.\nID: {}", scan_id);
+        Ok(scan_id)
+    }
 
-        Ok(Scan::new(name, &scan_id))
+    fn deregister_scan(&self, scan_id: &ScanID) -> Result<()> {
+        Ok(deregister_scan(scan_id)?)
     }
+}
 
-    /// Returns all boxes found by the scan
-    pub fn get_boxes(&self) -> Result<Vec<ErgoBox>> {
-        let boxes = get_scan_boxes(&self.id)?;
-        Ok(boxes)
+/// A scan registered by oracle-core for a box kind
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegisteredScan {
+    pub kind: ScanKind,
+    pub scan_id: ScanID,
+    pub name: String,
+}
+
+/// The scans registered by oracle-core, saved in [`SCAN_STATE_FILE`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanState {
+    pub scans: Vec<RegisteredScan>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScanStateFile {
+    version: u32,
+    scans: Vec<RegisteredScan>,
+    /// Hash of the version and the scans, detects a truncated or hand-edited file
+    checksum: String,
+}
+
+impl ScanState {
+    /// Load the state, checking its version and checksum
+    pub fn load(path: &Path) -> Result<ScanState> {
+        let file: ScanStateFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if file.version != SCANS_VERSION {
+            return Err(ScanError::StateVersionMismatch {
+                found: file.version,
+                expected: SCANS_VERSION,
+            });
+        }
+        let state = ScanState { scans: file.scans };
+        if state.checksum() != file.checksum {
+            return Err(ScanError::StateChecksumMismatch);
+        }
+        Ok(state)
     }
 
-    /// Returns the first box found by the scan
-    pub fn get_box(&self) -> Result<Option<ErgoBox>> {
-        Ok(self.get_boxes()?.first().cloned())
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = ScanStateFile {
+            version: SCANS_VERSION,
+            scans: self.scans.clone(),
+            checksum: self.checksum(),
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+
+    /// The scan registered for `kind`
+    pub fn scan(&self, kind: ScanKind) -> Result<Scan> {
+        self.scans
+            .iter()
+            .find(|s| s.kind == kind)
+            .map(|s| Scan::new(kind, &s.scan_id))
+            .ok_or_else(|| ScanError::ScanNotRegistered(kind.name()))
+    }
+
+    fn checksum(&self) -> String {
+        let bytes = serde_json::to_vec(&(SCANS_VERSION, &self.scans)).unwrap();
+        base16::encode_lower(&blake2b256_hash(&bytes))
     }
 }
 
-/// Saves UTXO-set scans (specifically id) to scanIDs.json
-pub fn save_scan_ids_locally(scans: Vec<Scan>) -> Result<()> {
-    let mut id_json = json!({});
-    for scan in scans {
-        if &scan.id == "null" {
-            return Err(ScanError::FailedToRegister);
+/// Ids of the scans saved in the [`LEGACY_SCAN_IDS_FILE`] of older releases (scan name -> id)
+pub fn load_legacy_scan_ids(path: &Path) -> Result<Vec<ScanID>> {
+    let ids: HashMap<String, serde_json::Value> =
+        serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(ids
+        .into_iter()
+        .map(|(_, id)| match id {
+            serde_json::Value::String(id) => id,
+            id => id.to_string(),
+        })
+        .collect())
+}
+
+/// Outcome of [`sync_scans`]
+#[derive(Debug)]
+pub struct ScanSync {
+    pub state: ScanState,
+    /// Scans registered in the node, the wallet must be rescanned for them to find the boxes
+    pub registered: Vec<ScanKind>,
+    pub deregistered: Vec<ScanID>,
+}
+
+/// Make the scans registered in the node match `specs`. A scan of `state` is kept if the node
+/// still has it under the expected versioned name, a scan the node already has under the expected
+/// name is adopted, and the others are registered. The scans of the pool not used anymore are
+/// deregistered: the ones named with the pool NFT id of `specs`, the ones of `state` and the
+/// legacy unversioned ones of `legacy_scan_ids`. The scans of other pools are left alone.
+pub fn sync_scans(
+    registry: &dyn ScanRegistry,
+    state: Option<&ScanState>,
+    legacy_scan_ids: &[ScanID],
+    specs: &[ScanSpec],
+) -> Result<ScanSync> {
+    let node_scans = registry.list_scans()?;
+    let mut scans = Vec::new();
+    let mut registered = Vec::new();
+    for spec in specs {
+        let name = spec.versioned_name();
+        let in_node = |scan_id: &ScanID| {
+            node_scans
+                .iter()
+                .any(|s| &s.scan_id == scan_id && s.name == name)
+        };
+        let kept = state
+            .and_then(|state| state.scans.iter().find(|s| s.kind == spec.kind))
+            .filter(|s| s.name == name && in_node(&s.scan_id))
+            .map(|s| s.scan_id.clone());
+        let adopted = || {
+            node_scans
+                .iter()
+                .find(|s| s.name == name)
+                .map(|s| s.scan_id.clone())
+        };
+        let scan_id = match kept.or_else(adopted) {
+            Some(scan_id) => scan_id,
+            None => {
+                registered.push(spec.kind);
+                registry.register_scan(&name, &spec.tracking_rule)?
+            }
+        };
+        scans.push(RegisteredScan {
+            kind: spec.kind,
+            scan_id,
+            name,
+        });
+    }
+
+    let mut deregistered = Vec::new();
+    for node_scan in node_scans {
+        let of_pool = specs
+            .iter()
+            .any(|spec| node_scan.name.starts_with(&spec.pool_name_prefix()));
+        let in_state = state.map_or(false, |state| {
+            state
+                .scans
+                .iter()
+                .any(|s| s.scan_id == node_scan.scan_id && s.name == node_scan.name)
+        });
+        let legacy = legacy_scan_ids.contains(&node_scan.scan_id)
+            && LEGACY_SCAN_NAMES.contains(&node_scan.name.as_str());
+        let ours = of_pool || in_state || legacy;
+        if ours && !scans.iter().any(|s| s.scan_id == node_scan.scan_id) {
+            info!(
+                "Deregistering stale scan {} ({})",
+                node_scan.scan_id, node_scan.name
+            );
+            registry.deregister_scan(&node_scan.scan_id)?;
+            deregistered.push(node_scan.scan_id);
         }
-        id_json[scan.name] = scan.id.into();
     }
-    std::fs::write(
-        "scanIDs.json",
-        serde_json::to_string_pretty(&id_json).unwrap(),
-    )?;
-    Ok(())
+    Ok(ScanSync {
+        state: ScanState { scans },
+        registered,
+        deregistered,
+    })
 }
 
-/// This function registers scanning for the pool box
-pub fn register_pool_box_scan(inputs: PoolBoxWrapperInputs) -> Result<Scan> {
+/// Tracking rule of the pool box: pool NFT at the pool contract
+pub fn pool_box_tracking_rule(inputs: &PoolBoxWrapperInputs) -> Result<serde_json::Value> {
     // ErgoTree bytes of the P2S address/script
     let pool_box_tree_bytes = PoolContract::checked_load(&inputs.contract_inputs)?
         .ergo_tree()
         .to_scan_bytes();
 
     // Scan for NFT id + Oracle Pool Epoch address
-    let scan_json = json! ( {
+    Ok(json! ( {
         "predicate": "and",
         "args": [
         {
@@ -122,23 +503,18 @@ pub fn register_pool_box_scan(inputs: PoolBoxWrapperInputs) -> Result<Scan> {
             "value": &pool_box_tree_bytes
         }
     ]
-    } );
-
-    Scan::register("Pool Box Scan", scan_json)
+    } ))
 }
 
-/// This function registers scanning for the refresh box
-pub fn register_refresh_box_scan(
-    scan_name: &'static str,
-    inputs: RefreshBoxWrapperInputs,
-) -> Result<Scan> {
+/// Tracking rule of the refresh box: refresh NFT at the refresh contract
+pub fn refresh_box_tracking_rule(inputs: &RefreshBoxWrapperInputs) -> Result<serde_json::Value> {
     // ErgoTree bytes of the P2S address/script
     let tree_bytes = RefreshContract::checked_load(&inputs.contract_inputs)?
         .ergo_tree()
         .to_scan_bytes();
 
     // Scan for NFT id + Oracle Pool Epoch address
-    let scan_json = json! ( {
+    Ok(json! ( {
         "predicate": "and",
         "args": [
         {
@@ -150,23 +526,21 @@ pub fn register_refresh_box_scan(
             "value": tree_bytes,
         }
     ]
-    } );
-
-    Scan::register(scan_name, scan_json)
+    } ))
 }
 
-/// This function registers scanning for the oracle's personal Datapoint box
-pub fn register_local_oracle_datapoint_scan(
+/// Tracking rule of the oracle's personal Datapoint box
+pub fn local_oracle_datapoint_tracking_rule(
     oracle_pool_participant_token: &TokenId,
     datapoint_address: &ErgoTree,
     oracle_address: &NetworkAddress,
-) -> Result<Scan> {
+) -> Result<serde_json::Value> {
     // Raw EC bytes + type identifier
     let oracle_add_bytes = address_to_raw_for_register(&oracle_address.to_base58())?;
     let datapoint_bytes = datapoint_address.to_scan_bytes();
 
     // Scan for pool participant token id + datapoint contract address + oracle_address in R4
-    let scan_json = json! ( {
+    Ok(json! ( {
         "predicate": "and",
         "args": [
         {
@@ -183,19 +557,17 @@ pub fn register_local_oracle_datapoint_scan(
             "value": oracle_add_bytes.clone(),
         }
     ]
-    } );
-
-    Scan::register("Local Oracle Datapoint Scan", scan_json)
+    } ))
 }
 
-/// This function registers scanning for all of the pools oracles' Datapoint boxes for datapoint collection
-pub fn register_datapoint_scan(
+/// Tracking rule of all of the pools oracles' Datapoint boxes for datapoint collection
+pub fn datapoint_tracking_rule(
     oracle_pool_participant_token: &TokenId,
     datapoint_address: &ErgoTree,
-) -> Result<Scan> {
+) -> serde_json::Value {
     let datapoint_bytes = datapoint_address.to_scan_bytes();
-    // Scan for pool participant token id + datapoint contract address + oracle_address in R4
-    let scan_json = json! ( {
+    // Scan for pool participant token id + datapoint contract address
+    json! ( {
         "predicate": "and",
         "args": [
         {
@@ -207,22 +579,20 @@ pub fn register_datapoint_scan(
             "value": datapoint_bytes,
         }
     ]
-    } );
-
-    Scan::register("All Datapoints Scan", scan_json)
+    } )
 }
 
-/// This function registers scanning for the local ballot box
-pub fn register_local_ballot_box_scan(
+/// Tracking rule of the local ballot box
+pub fn local_ballot_box_tracking_rule(
     ballot_contract_address: &ErgoTree,
     ballot_token_id: &TokenId,
     ballot_token_owner_address: &NetworkAddress,
-) -> Result<Scan> {
+) -> Result<serde_json::Value> {
     // Raw EC bytes + type identifier
     let ballot_add_bytes = address_to_raw_for_register(&ballot_token_owner_address.to_base58())?;
     let ballot_contract_bytes = ballot_contract_address.to_scan_bytes();
-    // Scan for pool participant token id + datapoint contract address + oracle_address in R4
-    let scan_json = json! ( {
+    // Scan for ballot token id + ballot contract address + ballot token owner in R4
+    Ok(json! ( {
         "predicate": "and",
         "args": [
         {
@@ -239,17 +609,15 @@ pub fn register_local_ballot_box_scan(
             "value": ballot_add_bytes.clone(),
         }
     ]
-    } );
-
-    Scan::register("Local Ballot Box Scan", scan_json)
+    } ))
 }
 
-/// Scan for all ballot boxes matching token id of oracle pool. When updating the pool box only ballot boxes voting for the new pool will be spent
-pub fn register_ballot_box_scan(
+/// Tracking rule of all ballot boxes matching token id of oracle pool. When updating the pool box only ballot boxes voting for the new pool will be spent
+pub fn ballot_box_tracking_rule(
     ballot_contract_address: &ErgoTree,
     ballot_token_id: &TokenId,
-) -> Result<Scan> {
-    let scan_json = json! ( {
+) -> serde_json::Value {
+    json! ( {
         "predicate": "and",
         "args": [
         {
@@ -260,20 +628,29 @@ pub fn register_ballot_box_scan(
             "predicate": "equals",
             "value": ballot_contract_address.to_scan_bytes(),
         }
-        ] });
-    Scan::register("Ballot Box Scan", scan_json)
+        ] })
 }
 
-pub fn register_update_box_scan(update_nft_token_id: &TokenId) -> Result<Scan> {
-    let scan_json = json! ( {
+pub fn update_box_tracking_rule(update_nft_token_id: &TokenId) -> serde_json::Value {
+    json! ( {
         "predicate": "and",
         "args": [
         {
             "predicate": "containsAsset",
             "assetId": update_nft_token_id.clone(),
         },
-        ] });
-    Scan::register("Update Box Scan", scan_json)
+        ] })
+}
+
+pub fn buyback_box_tracking_rule(buyback_token_id: &TokenId) -> serde_json::Value {
+    json! ( {
+        "predicate": "and",
+        "args": [
+        {
+            "predicate": "containsAsset",
+            "assetId": buyback_token_id.clone(),
+        },
+        ] })
 }
 
 /// Convert a chain type to Coll[Byte] for scans
@@ -290,3 +667,183 @@ impl ToScanBytes for ErgoTree {
         )
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+    use crate::pool_commands::test_utils::generate_token_ids;
+
+    #[derive(Default)]
+    pub(crate) struct RegistryMock {
+        pub scans: RefCell<Vec<NodeScan>>,
+        pub next_id: Cell<u32>,
+    }
+
+    impl ScanRegistry for RegistryMock {
+        fn list_scans(&self) -> Result<Vec<NodeScan>> {
+            Ok(self.scans.borrow().clone())
+        }
+
+        fn register_scan(&self, name: &str, _: &serde_json::Value) -> Result<ScanID> {
+            self.next_id.set(self.next_id.get() + 1);
+            let scan_id = self.next_id.get().to_string();
+            self.scans.borrow_mut().push(NodeScan {
+                scan_id: scan_id.clone(),
+                name: name.to_string(),
+            });
+            Ok(scan_id)
+        }
+
+        fn deregister_scan(&self, scan_id: &ScanID) -> Result<()> {
+            self.scans.borrow_mut().retain(|s| &s.scan_id != scan_id);
+            Ok(())
+        }
+    }
+
+    fn pool_specs(pool_nft_token_id: &TokenId, asset_ids: [&str; 2]) -> Vec<ScanSpec> {
+        vec![
+            ScanSpec::new(
+                ScanKind::Pool,
+                pool_nft_token_id,
+                json!({"predicate": "containsAsset", "assetId": asset_ids[0]}),
+            ),
+            ScanSpec::new(
+                ScanKind::Update,
+                pool_nft_token_id,
+                json!({"predicate": "containsAsset", "assetId": asset_ids[1]}),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_sync_scans() {
+        let registry = RegistryMock::default();
+        // Registered by an older oracle-core and by another application
+        registry.register_scan("Pool Box Scan", &json!({})).unwrap();
+        registry.register_scan("my scan", &json!({})).unwrap();
+        let legacy_scan_ids = vec!["1".to_string()];
+        let pool_nft_token_id = generate_token_ids().pool_nft_token_id;
+        let specs = pool_specs(&pool_nft_token_id, ["a", "b"]);
+
+        let sync = sync_scans(&registry, None, &legacy_scan_ids, &specs).unwrap();
+        assert_eq!(sync.registered, vec![ScanKind::Pool, ScanKind::Update]);
+        assert_eq!(sync.deregistered, vec!["1".to_string()]);
+        assert_eq!(sync.state.scan(ScanKind::Update).unwrap().id, "4");
+        assert!(sync.state.scan(ScanKind::Buyback).is_err());
+
+        // Nothing to do on the next start
+        let state = sync.state;
+        let sync = sync_scans(&registry, Some(&state), &[], &specs).unwrap();
+        assert_eq!(sync.state, state);
+        assert!(sync.registered.is_empty() && sync.deregistered.is_empty());
+
+        // The pool contract changed, the old pool box scan is replaced
+        let specs = vec![
+            ScanSpec::new(
+                ScanKind::Pool,
+                &pool_nft_token_id,
+                json!({"predicate": "containsAsset", "assetId": "c"}),
+            ),
+            specs[1].clone(),
+        ];
+        let sync = sync_scans(&registry, Some(&state), &[], &specs).unwrap();
+        assert_eq!(sync.registered, vec![ScanKind::Pool]);
+        assert_eq!(sync.deregistered, vec!["3".to_string()]);
+        assert_eq!(sync.state.scan(ScanKind::Pool).unwrap().id, "5");
+
+        // The state file is lost, the scans in the node are adopted by name
+        let sync = sync_scans(&registry, None, &[], &specs).unwrap();
+        assert!(sync.registered.is_empty());
+        assert_eq!(registry.scans.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_sync_scans_of_two_pools() {
+        let registry = RegistryMock::default();
+        // An unversioned scan of an older oracle-core running next to the pools
+        registry.register_scan("Pool Box Scan", &json!({})).unwrap();
+        let pool_a = generate_token_ids().pool_nft_token_id;
+        let specs_a = pool_specs(&pool_a, ["a", "b"]);
+        let specs_b = pool_specs(&generate_token_ids().pool_nft_token_id, ["c", "d"]);
+
+        let sync_a = sync_scans(&registry, None, &[], &specs_a).unwrap();
+        let sync_b = sync_scans(&registry, None, &[], &specs_b).unwrap();
+        assert_eq!(sync_b.registered, vec![ScanKind::Pool, ScanKind::Update]);
+        assert!(sync_a.deregistered.is_empty() && sync_b.deregistered.is_empty());
+        assert_eq!(registry.scans.borrow().len(), 5);
+
+        // Restarting a pool leaves the scans of the other one alone
+        let state_a = sync_a.state;
+        let sync = sync_scans(&registry, Some(&state_a), &[], &specs_a).unwrap();
+        assert_eq!(sync.state, state_a);
+        assert!(sync.registered.is_empty() && sync.deregistered.is_empty());
+        let state_b = sync_b.state;
+        let sync = sync_scans(&registry, Some(&state_b), &[], &specs_b).unwrap();
+        assert_eq!(sync.state, state_b);
+        assert!(sync.registered.is_empty() && sync.deregistered.is_empty());
+
+        // A pool update only replaces the scans of the updated pool
+        let specs_a = pool_specs(&pool_a, ["e", "b"]);
+        let sync = sync_scans(&registry, Some(&state_a), &[], &specs_a).unwrap();
+        assert_eq!(sync.registered, vec![ScanKind::Pool]);
+        assert_eq!(sync.deregistered, vec![state_a.scans[0].scan_id.clone()]);
+        let scans = registry.scans.borrow();
+        assert!(state_b
+            .scans
+            .iter()
+            .all(|b| scans.iter().any(|s| s.scan_id == b.scan_id)));
+        assert!(scans.iter().any(|s| s.name == "Pool Box Scan"));
+    }
+
+    #[test]
+    fn test_scan_state_integrity() {
+        let path = std::env::temp_dir().join("oracle_core_test_scan_state.json");
+        let state = ScanState {
+            scans: vec![RegisteredScan {
+                kind: ScanKind::Pool,
+                scan_id: "1".to_string(),
+                name: ScanSpec::new(
+                    ScanKind::Pool,
+                    &generate_token_ids().pool_nft_token_id,
+                    json!({}),
+                )
+                .versioned_name(),
+            }],
+        };
+        state.save(&path).unwrap();
+        assert_eq!(ScanState::load(&path).unwrap(), state);
+
+        let edited = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("\"scan_id\": \"1\"", "\"scan_id\": \"2\"");
+        std::fs::write(&path, edited).unwrap();
+        assert!(matches!(
+            ScanState::load(&path),
+            Err(ScanError::StateChecksumMismatch)
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_scan_cache_refetches_on_new_height() {
+        let cache = ScanCache::new();
+        let scan_id: ScanID = "1".to_string();
+        let fetches = Cell::new(0);
+        let fetch = || {
+            fetches.set(fetches.get() + 1);
+            Ok(Vec::new())
+        };
+        cache.get_or_fetch(&scan_id, 10, fetch).unwrap();
+        cache.get_or_fetch(&scan_id, 10, fetch).unwrap();
+        assert_eq!(fetches.get(), 1);
+        cache.get_or_fetch(&"2".to_string(), 10, fetch).unwrap();
+        assert_eq!(fetches.get(), 2);
+        cache.get_or_fetch(&scan_id, 11, fetch).unwrap();
+        assert_eq!(fetches.get(), 3);
+        cache.clear();
+        cache.get_or_fetch(&scan_id, 11, fetch).unwrap();
+        assert_eq!(fetches.get(), 4);
+    }
+}
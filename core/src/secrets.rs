@@ -0,0 +1,172 @@
+//! Secrets of the config kept out of the config file in plaintext. Any string field of the config
+//! (e.g. `node_api_key`, `node_wallet_password` or the `bot_token` of a notification target) can
+//! hold instead of the secret
+//! - `encrypted:<base64>`: the secret encrypted with a passphrase (age, scrypt), written by the
+//!   `encrypt-secret` command;
+//! - `keyring:<name>`: the name of the secret stored in the OS keyring (service `oracle-core`).
+//!
+//! The secrets are resolved when the config is loaded, after the pool definition and the
+//! environment overrides are merged. The passphrase is read from the
+//! `ORACLE_CORE_SECRETS_PASSPHRASE` environment variable or asked on the terminal, once per
+//! process (the config reloads reuse it).
+
+use std::io::{Read, Write};
+
+use age::secrecy::Secret;
+use derive_more::From;
+use once_cell::sync;
+use serde_yaml::Value;
+use thiserror::Error;
+
+/// Environment variable holding the passphrase of the encrypted secrets
+pub const SECRETS_PASSPHRASE_ENV_VAR: &str = "ORACLE_CORE_SECRETS_PASSPHRASE";
+
+pub const ENCRYPTED_PREFIX: &str = "encrypted:";
+
+pub const KEYRING_PREFIX: &str = "keyring:";
+
+/// Service of the secrets in the OS keyring
+pub const KEYRING_SERVICE: &str = "oracle-core";
+
+static PASSPHRASE: sync::OnceCell<String> = sync::OnceCell::new();
+
+#[derive(Debug, Error, From)]
+pub enum SecretsError {
+    #[error("IO error: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("encryption error: {0}")]
+    Encrypt(age::EncryptError),
+    #[error("decryption error (wrong passphrase?): {0}")]
+    Decrypt(age::DecryptError),
+    #[error("the encrypted secret is not base64 encoded: {0}")]
+    Base64(base64::DecodeError),
+    #[error("the secret is not UTF-8: {0}")]
+    Utf8(std::string::FromUtf8Error),
+    #[error("keyring error: {0}")]
+    Keyring(keyring::Error),
+    #[error("the secret is not encrypted with a passphrase")]
+    NotPassphraseEncrypted,
+    #[error("empty passphrase")]
+    EmptyPassphrase,
+}
+
+/// Passphrase of the encrypted secrets, from the environment or asked on the terminal
+pub fn passphrase() -> Result<String, SecretsError> {
+    PASSPHRASE
+        .get_or_try_init(|| {
+            let passphrase = match std::env::var(SECRETS_PASSPHRASE_ENV_VAR) {
+                Ok(passphrase) => passphrase,
+                Err(_) => rpassword::prompt_password("Passphrase of the config secrets: ")?,
+            };
+            if passphrase.is_empty() {
+                Err(SecretsError::EmptyPassphrase)
+            } else {
+                Ok(passphrase)
+            }
+        })
+        .map(Clone::clone)
+}
+
+/// Passphrase of the encrypted secrets if it was read already
+pub fn cached_passphrase() -> Option<String> {
+    PASSPHRASE.get().cloned()
+}
+
+/// `encrypted:<base64>` value of the config holding `secret` encrypted with `passphrase`
+pub fn encrypt(secret: &str, passphrase: &str) -> Result<String, SecretsError> {
+    let encryptor = age::Encryptor::with_user_passphrase(Secret::new(passphrase.to_string()));
+    let mut encrypted = Vec::new();
+    let mut writer = encryptor.wrap_output(&mut encrypted)?;
+    writer.write_all(secret.as_bytes())?;
+    writer.finish()?;
+    Ok(format!("{}{}", ENCRYPTED_PREFIX, base64::encode(encrypted)))
+}
+
+/// Secret of the `encrypted` value (without its prefix)
+pub fn decrypt(encrypted: &str, passphrase: &str) -> Result<String, SecretsError> {
+    let encrypted = base64::decode(encrypted.trim())?;
+    let decryptor = match age::Decryptor::new(&encrypted[..])? {
+        age::Decryptor::Passphrase(decryptor) => decryptor,
+        _ => return Err(SecretsError::NotPassphraseEncrypted),
+    };
+    let mut reader = decryptor.decrypt(&Secret::new(passphrase.to_string()), None)?;
+    let mut secret = Vec::new();
+    reader.read_to_end(&mut secret)?;
+    Ok(String::from_utf8(secret)?)
+}
+
+/// Store `secret` in the OS keyring under `name`, returns the `keyring:<name>` value of the config
+pub fn store_in_keyring(name: &str, secret: &str) -> Result<String, SecretsError> {
+    keyring::Entry::new(KEYRING_SERVICE, name).set_password(secret)?;
+    Ok(format!("{}{}", KEYRING_PREFIX, name))
+}
+
+/// Replace the encrypted and keyring secrets of the `config` with their plaintext
+pub fn resolve_secrets(config: &mut Value) -> Result<(), SecretsError> {
+    resolve_secrets_with(config, &passphrase, &|name| {
+        Ok(keyring::Entry::new(KEYRING_SERVICE, name).get_password()?)
+    })
+}
+
+fn resolve_secrets_with(
+    value: &mut Value,
+    passphrase: &dyn Fn() -> Result<String, SecretsError>,
+    keyring: &dyn Fn(&str) -> Result<String, SecretsError>,
+) -> Result<(), SecretsError> {
+    match value {
+        Value::String(s) => {
+            if let Some(encrypted) = s.strip_prefix(ENCRYPTED_PREFIX) {
+                *s = decrypt(encrypted, &passphrase()?)?;
+            } else if let Some(name) = s.strip_prefix(KEYRING_PREFIX) {
+                *s = keyring(name)?;
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (_, v) in mapping.iter_mut() {
+                resolve_secrets_with(v, passphrase, keyring)?;
+            }
+        }
+        Value::Sequence(sequence) => {
+            for v in sequence.iter_mut() {
+                resolve_secrets_with(v, passphrase, keyring)?;
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => (),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_secrets() {
+        let encrypted = encrypt("hello", "correct horse").unwrap();
+        assert!(encrypted.starts_with(ENCRYPTED_PREFIX));
+        assert!(matches!(
+            decrypt(encrypted.strip_prefix(ENCRYPTED_PREFIX).unwrap(), "wrong"),
+            Err(SecretsError::Decrypt(_))
+        ));
+        let mut config: Value = serde_yaml::from_str(&format!(
+            "node_api_key: {}\nnode_port: 9053\n\
+            notifications:\n  targets:\n    - type: telegram\n      bot_token: keyring:bot\n      \
+            chat_id: '42'\n",
+            encrypted
+        ))
+        .unwrap();
+        resolve_secrets_with(&mut config, &|| Ok("correct horse".to_string()), &|name| {
+            Ok(format!("{} token", name))
+        })
+        .unwrap();
+        assert_eq!(config["node_api_key"], Value::from("hello"));
+        assert_eq!(
+            config["notifications"]["targets"][0]["bot_token"],
+            Value::from("bot token")
+        );
+        assert_eq!(
+            config["notifications"]["targets"][0]["chat_id"],
+            Value::from("42")
+        );
+    }
+}
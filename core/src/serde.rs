@@ -1,14 +1,21 @@
 //! Types to allow oracle configuration to convert to and from Serde.
 
 use std::convert::{TryFrom, TryInto};
+use std::net::IpAddr;
+use std::path::PathBuf;
 
 use derive_more::From;
-use ergo_lib::ergotree_ir::chain::{
-    address::{AddressEncoder, AddressEncoderError},
-    ergo_box::box_value::BoxValueError,
-    token::TokenId,
+use ergo_lib::{
+    ergo_chain_types::{Digest32, DigestNError},
+    ergotree_ir::chain::{
+        address::{Address, AddressEncoder, AddressEncoderError},
+        ergo_box::box_value::{BoxValue, BoxValueError},
+        token::TokenId,
+    },
+    wallet::tx_builder::SUGGESTED_TX_FEE,
 };
 use log::LevelFilter;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -19,7 +26,9 @@ use crate::{
     },
     cli_commands::{
         bootstrap::{BootstrapConfig, TokensToMint},
+        import_pool::PoolDefinition,
         prepare_update::{UpdateBootstrapConfig, UpdateTokensToMint},
+        propose_update::UpdateProposal,
     },
     contracts::{
         ballot::{BallotContractParameters, BallotContractParametersError},
@@ -32,19 +41,49 @@ use crate::{
         update::{UpdateContractParameters, UpdateContractParametersError},
     },
     datapoint_source::PredefinedDataPointSource,
-    oracle_config::{OracleConfig, OracleConfigError, TokenIds},
+    logging::LogFormat,
+    oracle_config::{
+        CollectorElectionConfig, FeeEstimationConfig, GrpcConfig, LowBalanceAlertConfig,
+        NotificationsConfig, OracleConfig, OracleConfigError, PeerMonitorConfig,
+        PublishTimingConfig, RateAnomalyConfig, RewardExtractionConfig, SkipPublishConfig,
+        StorageRentConfig, TokenIds, TxFeeAction, TxFeesConfig,
+    },
+    pool_controller::EpochStrategyKind,
 };
 
 /// Used to (de)serialize `OracleConfig` instance.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub(crate) struct OracleConfigSerde {
     node_ip: String,
     node_port: u16,
     node_api_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    node_wallet_password: Option<String>,
+    #[serde(default = "default_base_fee")]
     base_fee: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tx_fees: Option<TxFeesConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fee_estimation: Option<FeeEstimationConfig>,
+    #[schemars(with = "Option<String>")]
     log_level: Option<LevelFilter>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    log_filter: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    log_format: Option<LogFormat>,
     core_api_port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    core_api_bind_address: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    core_api_admin_token_file: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    core_api_admin_allowed_scripts: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    core_api_cors_allowed_origins: Option<Vec<String>>,
     oracle_address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    change_address: Option<String>,
     data_point_source: Option<PredefinedDataPointSource>,
     data_point_source_custom_script: Option<String>,
     oracle_contract_parameters: OracleContractParametersSerde,
@@ -54,12 +93,44 @@ pub(crate) struct OracleConfigSerde {
     ballot_contract_parameters: BallotContractParametersSerde,
     token_ids: TokenIds,
     rescan_height: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<RewardExtractionConfigSerde>")]
+    reward_extraction: Option<RewardExtractionConfig>,
+    /// Base64 encoded buyback NFT id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    buyback_token_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    epoch_strategy: Option<EpochStrategyKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    publish_timing: Option<PublishTimingConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    skip_publish: Option<SkipPublishConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    collector_election: Option<CollectorElectionConfig>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    chain_transactions: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    grpc: Option<GrpcConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    epoch_stats_window: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notifications: Option<NotificationsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    missed_epochs_alert_threshold: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    low_balance_alert: Option<LowBalanceAlertConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    peer_monitor: Option<PeerMonitorConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rate_anomaly: Option<RateAnomalyConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    storage_rent: Option<StorageRentConfig>,
 }
 
 #[derive(Debug, Error, From)]
 pub enum SerdeConversionError {
     #[error("Serde conversion error: AddressEncoder {0}")]
-    AddressEncoder(AddressEncoderError),
+    AddressEncoder(#[source] AddressEncoderError),
     #[error("Oracle config error: {0}")]
     OracleConfigError(OracleConfigError),
     #[error("Base16 decode error: {0}")]
@@ -76,6 +147,14 @@ pub enum SerdeConversionError {
     UpdateContractParameters(UpdateContractParametersError),
     #[error("BoxValueError: {0}")]
     BoxValueError(BoxValueError),
+    #[error("Digest32 error: {0}")]
+    Digest(DigestNError),
+    #[error("change address {0} is not on the network of the oracle address")]
+    #[from(ignore)]
+    ChangeAddressNetwork(String),
+    #[error("reward extraction payout address {0} is not a P2PK address")]
+    #[from(ignore)]
+    PayoutAddressNotP2Pk(String),
 }
 
 impl From<OracleConfig> for OracleConfigSerde {
@@ -115,10 +194,20 @@ impl From<OracleConfig> for OracleConfigSerde {
             node_ip: c.node_ip,
             node_port: c.node_port,
             node_api_key: c.node_api_key,
+            node_wallet_password: c.node_wallet_password,
             base_fee: c.base_fee,
+            tx_fees: c.tx_fees,
+            fee_estimation: c.fee_estimation,
             log_level: c.log_level,
+            log_filter: c.log_filter,
+            log_format: c.log_format,
             core_api_port: c.core_api_port,
+            core_api_bind_address: c.core_api_bind_address,
+            core_api_admin_token_file: c.core_api_admin_token_file,
+            core_api_admin_allowed_scripts: c.core_api_admin_allowed_scripts,
+            core_api_cors_allowed_origins: c.core_api_cors_allowed_origins,
             oracle_address: c.oracle_address.to_base58(),
+            change_address: c.change_address.map(|a| a.to_base58()),
             data_point_source: c.data_point_source,
             data_point_source_custom_script: c.data_point_source_custom_script,
             oracle_contract_parameters,
@@ -128,13 +217,51 @@ impl From<OracleConfig> for OracleConfigSerde {
             update_contract_parameters,
             token_ids: c.token_ids,
             rescan_height: c.rescan_height,
+            reward_extraction: c.reward_extraction,
+            buyback_token_id: c.buyback_token_id.map(|id| {
+                let bytes: Vec<u8> = id.into();
+                base64::encode(bytes)
+            }),
+            epoch_strategy: c.epoch_strategy,
+            publish_timing: c.publish_timing,
+            skip_publish: c.skip_publish,
+            collector_election: c.collector_election,
+            chain_transactions: c.chain_transactions,
+            grpc: c.grpc,
+            epoch_stats_window: c.epoch_stats_window,
+            notifications: c.notifications,
+            missed_epochs_alert_threshold: c.missed_epochs_alert_threshold,
+            low_balance_alert: c.low_balance_alert,
+            peer_monitor: c.peer_monitor,
+            rate_anomaly: c.rate_anomaly,
+            storage_rent: c.storage_rent,
         }
     }
 }
 
+fn default_base_fee() -> u64 {
+    *SUGGESTED_TX_FEE().as_u64()
+}
+
 impl TryFrom<OracleConfigSerde> for OracleConfig {
     type Error = SerdeConversionError;
     fn try_from(c: OracleConfigSerde) -> Result<Self, Self::Error> {
+        BoxValue::try_from(c.base_fee)?;
+        if let Some(tx_fees) = &c.tx_fees {
+            for action in TxFeeAction::ALL {
+                if let Some(fee) = tx_fees.fee(action) {
+                    BoxValue::try_from(fee)?;
+                }
+            }
+        }
+        if let Some(fee_estimation) = &c.fee_estimation {
+            for fee in [fee_estimation.min_fee, fee_estimation.max_fee]
+                .iter()
+                .flatten()
+            {
+                BoxValue::try_from(*fee)?;
+            }
+        }
         let oracle_contract_parameters = OracleContractParameters::checked_load(
             base16::decode(c.oracle_contract_parameters.ergo_tree_bytes.as_str())?,
             c.oracle_contract_parameters.pool_nft_index,
@@ -156,24 +283,13 @@ impl TryFrom<OracleConfigSerde> for OracleConfig {
             c.pool_contract_parameters.update_nft_index,
         )?;
 
-        let refresh_contract_parameters =
-            RefreshContractParameters::checked_load(RefreshContractParametersInputs {
-                ergo_tree_bytes: base16::decode(
-                    c.refresh_contract_parameters.ergo_tree_bytes.as_str(),
-                )?,
-                pool_nft_index: c.refresh_contract_parameters.pool_nft_index,
-                oracle_token_id_index: c.refresh_contract_parameters.oracle_token_id_index,
-                min_data_points_index: c.refresh_contract_parameters.min_data_points_index,
-                min_data_points: c.refresh_contract_parameters.min_data_points,
-                buffer_length_index: c.refresh_contract_parameters.buffer_length_index,
-                buffer_length: c.refresh_contract_parameters.buffer_length,
-                max_deviation_percent_index: c
-                    .refresh_contract_parameters
-                    .max_deviation_percent_index,
-                max_deviation_percent: c.refresh_contract_parameters.max_deviation_percent,
-                epoch_length_index: c.refresh_contract_parameters.epoch_length_index,
-                epoch_length: c.refresh_contract_parameters.epoch_length,
-            })?;
+        let refresh_contract_parameters = if c.refresh_contract_parameters.uses_default_ergo_tree()
+        {
+            // Only the values are given, build the EIP-23 contract with them
+            RefreshContractParameters::build_with(c.refresh_contract_parameters.to_inputs()?)?
+        } else {
+            RefreshContractParameters::checked_load(c.refresh_contract_parameters.to_inputs()?)?
+        };
 
         let update_contract_parameters = UpdateContractParameters::checked_load(
             base16::decode(c.update_contract_parameters.ergo_tree_bytes.as_str())?,
@@ -192,6 +308,17 @@ impl TryFrom<OracleConfigSerde> for OracleConfig {
 
         let oracle_address =
             AddressEncoder::unchecked_parse_network_address_from_str(&c.oracle_address)?;
+        let change_address = c
+            .change_address
+            .map(|a| AddressEncoder::unchecked_parse_network_address_from_str(&a))
+            .transpose()?;
+        if let Some(change_address) = &change_address {
+            if change_address.network() != oracle_address.network() {
+                return Err(SerdeConversionError::ChangeAddressNetwork(
+                    change_address.to_base58(),
+                ));
+            }
+        }
 
         let refresh_box_wrapper_inputs = RefreshBoxWrapperInputs::checked_load(
             refresh_contract_parameters.clone(),
@@ -229,10 +356,20 @@ impl TryFrom<OracleConfigSerde> for OracleConfig {
             node_ip: c.node_ip,
             node_port: c.node_port,
             node_api_key: c.node_api_key,
+            node_wallet_password: c.node_wallet_password,
             base_fee: c.base_fee,
+            tx_fees: c.tx_fees,
+            fee_estimation: c.fee_estimation,
             log_level: c.log_level,
+            log_filter: c.log_filter,
+            log_format: c.log_format,
             core_api_port: c.core_api_port,
+            core_api_bind_address: c.core_api_bind_address,
+            core_api_admin_token_file: c.core_api_admin_token_file,
+            core_api_admin_allowed_scripts: c.core_api_admin_allowed_scripts,
+            core_api_cors_allowed_origins: c.core_api_cors_allowed_origins,
             oracle_address,
+            change_address,
             data_point_source: c.data_point_source,
             data_point_source_custom_script: c.data_point_source_custom_script,
             oracle_box_wrapper_inputs,
@@ -242,6 +379,65 @@ impl TryFrom<OracleConfigSerde> for OracleConfig {
             ballot_box_wrapper_inputs,
             token_ids: c.token_ids,
             rescan_height: c.rescan_height,
+            reward_extraction: c.reward_extraction,
+            buyback_token_id: c
+                .buyback_token_id
+                .map(|id| TokenId::from_base64(&id))
+                .transpose()?,
+            epoch_strategy: c.epoch_strategy,
+            publish_timing: c.publish_timing,
+            skip_publish: c.skip_publish,
+            collector_election: c.collector_election,
+            chain_transactions: c.chain_transactions,
+            grpc: c.grpc,
+            epoch_stats_window: c.epoch_stats_window,
+            notifications: c.notifications,
+            missed_epochs_alert_threshold: c.missed_epochs_alert_threshold,
+            low_balance_alert: c.low_balance_alert,
+            peer_monitor: c.peer_monitor,
+            rate_anomaly: c.rate_anomaly,
+            storage_rent: c.storage_rent,
+        })
+    }
+}
+
+/// Used to (de)serialize `RewardExtractionConfig` instance.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct RewardExtractionConfigSerde {
+    payout_address: String,
+    min_reward_tokens: Option<u64>,
+    every_n_epochs: Option<u32>,
+    max_tokens_per_day: Option<u64>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+impl From<RewardExtractionConfig> for RewardExtractionConfigSerde {
+    fn from(c: RewardExtractionConfig) -> Self {
+        RewardExtractionConfigSerde {
+            payout_address: c.payout_address.to_base58(),
+            min_reward_tokens: c.min_reward_tokens,
+            every_n_epochs: c.every_n_epochs,
+            max_tokens_per_day: c.max_tokens_per_day,
+            dry_run: c.dry_run,
+        }
+    }
+}
+
+impl TryFrom<RewardExtractionConfigSerde> for RewardExtractionConfig {
+    type Error = SerdeConversionError;
+    fn try_from(c: RewardExtractionConfigSerde) -> Result<Self, Self::Error> {
+        let payout_address =
+            AddressEncoder::unchecked_parse_network_address_from_str(&c.payout_address)?;
+        if !matches!(payout_address.address(), Address::P2Pk(_)) {
+            return Err(SerdeConversionError::PayoutAddressNotP2Pk(c.payout_address));
+        }
+        Ok(RewardExtractionConfig {
+            payout_address,
+            min_reward_tokens: c.min_reward_tokens,
+            every_n_epochs: c.every_n_epochs,
+            max_tokens_per_day: c.max_tokens_per_day,
+            dry_run: c.dry_run,
         })
     }
 }
@@ -302,23 +498,7 @@ impl TryFrom<BootstrapConfigSerde> for BootstrapConfig {
             c.pool_contract_parameters.update_nft_index,
         )?;
         let refresh_contract_parameters =
-            RefreshContractParameters::build_with(RefreshContractParametersInputs {
-                ergo_tree_bytes: base16::decode(
-                    c.refresh_contract_parameters.ergo_tree_bytes.as_str(),
-                )?,
-                pool_nft_index: c.refresh_contract_parameters.pool_nft_index,
-                oracle_token_id_index: c.refresh_contract_parameters.oracle_token_id_index,
-                min_data_points_index: c.refresh_contract_parameters.min_data_points_index,
-                min_data_points: c.refresh_contract_parameters.min_data_points,
-                buffer_length_index: c.refresh_contract_parameters.buffer_length_index,
-                buffer_length: c.refresh_contract_parameters.buffer_length,
-                max_deviation_percent_index: c
-                    .refresh_contract_parameters
-                    .max_deviation_percent_index,
-                max_deviation_percent: c.refresh_contract_parameters.max_deviation_percent,
-                epoch_length_index: c.refresh_contract_parameters.epoch_length_index,
-                epoch_length: c.refresh_contract_parameters.epoch_length,
-            })?;
+            RefreshContractParameters::build_with(c.refresh_contract_parameters.to_inputs()?)?;
         let update_contract_parameters = UpdateContractParameters::build_with(
             base16::decode(c.update_contract_parameters.ergo_tree_bytes.as_str())?,
             c.update_contract_parameters.pool_nft_index,
@@ -360,7 +540,31 @@ impl TryFrom<BootstrapConfigSerde> for BootstrapConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Used to serialize the pool definition (see `pool_config`) of an imported pool.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PoolDefinitionSerde {
+    oracle_contract_parameters: OracleContractParametersSerde,
+    pool_contract_parameters: PoolContractParametersSerde,
+    refresh_contract_parameters: RefreshContractParametersSerde,
+    update_contract_parameters: UpdateContractParametersSerde,
+    ballot_contract_parameters: BallotContractParametersSerde,
+    token_ids: TokenIds,
+}
+
+impl From<PoolDefinition> for PoolDefinitionSerde {
+    fn from(d: PoolDefinition) -> Self {
+        PoolDefinitionSerde {
+            oracle_contract_parameters: d.oracle_contract_parameters.into(),
+            pool_contract_parameters: d.pool_contract_parameters.into(),
+            refresh_contract_parameters: d.refresh_contract_parameters.into(),
+            update_contract_parameters: d.update_contract_parameters.into(),
+            ballot_contract_parameters: d.ballot_contract_parameters.into(),
+            token_ids: d.token_ids,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct OracleContractParametersSerde {
     ergo_tree_bytes: String,
     pool_nft_index: usize,
@@ -379,7 +583,7 @@ impl From<OracleContractParameters> for OracleContractParametersSerde {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 struct PoolContractParametersSerde {
     ergo_tree_bytes: String,
     refresh_nft_index: usize,
@@ -396,40 +600,81 @@ impl From<PoolContractParameters> for PoolContractParametersSerde {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The ergo tree and the constant indices can be omitted to use the EIP-23 refresh contract, so
+/// that only the epoch length, min data points, buffer length and max deviation percent have to be
+/// set for a custom pool.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 struct RefreshContractParametersSerde {
-    ergo_tree_bytes: String,
-    pool_nft_index: usize,
-    oracle_token_id_index: usize,
-    min_data_points_index: usize,
+    ergo_tree_bytes: Option<String>,
+    pool_nft_index: Option<usize>,
+    oracle_token_id_index: Option<usize>,
+    min_data_points_index: Option<usize>,
     min_data_points: i32,
-    buffer_length_index: usize,
+    buffer_length_index: Option<usize>,
     buffer_length: i32,
-    max_deviation_percent_index: usize,
+    max_deviation_percent_index: Option<usize>,
     max_deviation_percent: i32,
-    epoch_length_index: usize,
+    epoch_length_index: Option<usize>,
     epoch_length: i32,
 }
 
+impl RefreshContractParametersSerde {
+    fn uses_default_ergo_tree(&self) -> bool {
+        self.ergo_tree_bytes.is_none()
+    }
+
+    fn to_inputs(&self) -> Result<RefreshContractParametersInputs, SerdeConversionError> {
+        let default = RefreshContractParameters::default();
+        Ok(RefreshContractParametersInputs {
+            ergo_tree_bytes: match &self.ergo_tree_bytes {
+                Some(bytes) => base16::decode(bytes.as_str())?,
+                None => default.ergo_tree_bytes(),
+            },
+            pool_nft_index: self
+                .pool_nft_index
+                .unwrap_or_else(|| default.pool_nft_index()),
+            oracle_token_id_index: self
+                .oracle_token_id_index
+                .unwrap_or_else(|| default.oracle_token_id_index()),
+            min_data_points_index: self
+                .min_data_points_index
+                .unwrap_or_else(|| default.min_data_points_index()),
+            min_data_points: self.min_data_points,
+            buffer_length_index: self
+                .buffer_length_index
+                .unwrap_or_else(|| default.buffer_length_index()),
+            buffer_length: self.buffer_length,
+            max_deviation_percent_index: self
+                .max_deviation_percent_index
+                .unwrap_or_else(|| default.max_deviation_percent_index()),
+            max_deviation_percent: self.max_deviation_percent,
+            epoch_length_index: self
+                .epoch_length_index
+                .unwrap_or_else(|| default.epoch_length_index()),
+            epoch_length: self.epoch_length,
+        })
+    }
+}
+
 impl From<RefreshContractParameters> for RefreshContractParametersSerde {
     fn from(p: RefreshContractParameters) -> Self {
         RefreshContractParametersSerde {
-            ergo_tree_bytes: base16::encode_lower(p.ergo_tree_bytes().as_slice()),
-            pool_nft_index: p.pool_nft_index(),
-            oracle_token_id_index: p.oracle_token_id_index(),
-            min_data_points_index: p.min_data_points_index(),
+            ergo_tree_bytes: Some(base16::encode_lower(p.ergo_tree_bytes().as_slice())),
+            pool_nft_index: Some(p.pool_nft_index()),
+            oracle_token_id_index: Some(p.oracle_token_id_index()),
+            min_data_points_index: Some(p.min_data_points_index()),
             min_data_points: p.min_data_points(),
-            buffer_length_index: p.buffer_length_index(),
+            buffer_length_index: Some(p.buffer_length_index()),
             buffer_length: p.buffer_length(),
-            max_deviation_percent_index: p.max_deviation_percent_index(),
+            max_deviation_percent_index: Some(p.max_deviation_percent_index()),
             max_deviation_percent: p.max_deviation_percent(),
-            epoch_length_index: p.epoch_length_index(),
+            epoch_length_index: Some(p.epoch_length_index()),
             epoch_length: p.epoch_length(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 struct BallotContractParametersSerde {
     ergo_tree_bytes: String,
     min_storage_rent_index: usize,
@@ -449,7 +694,7 @@ impl From<BallotContractParameters> for BallotContractParametersSerde {
 }
 
 /// Used to (de)serialize `OracleContractParameters` instance.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 struct UpdateContractParametersSerde {
     ergo_tree_bytes: String,
     pool_nft_index: usize,
@@ -495,21 +740,12 @@ impl TryFrom<UpdateBootstrapConfigSerde> for UpdateBootstrapConfig {
 
         let refresh_contract_parameters = if let Some(c) = config_serde.refresh_contract_parameters
         {
-            Some(RefreshContractParameters::checked_load(
-                RefreshContractParametersInputs {
-                    ergo_tree_bytes: base16::decode(c.ergo_tree_bytes.as_str())?,
-                    pool_nft_index: c.pool_nft_index,
-                    oracle_token_id_index: c.oracle_token_id_index,
-                    min_data_points_index: c.min_data_points_index,
-                    min_data_points: c.min_data_points,
-                    buffer_length_index: c.buffer_length_index,
-                    buffer_length: c.buffer_length,
-                    max_deviation_percent_index: c.max_deviation_percent_index,
-                    max_deviation_percent: c.max_deviation_percent,
-                    epoch_length_index: c.epoch_length_index,
-                    epoch_length: c.epoch_length,
-                },
-            )?)
+            // Only the values are given, build the EIP-23 contract with them
+            if c.uses_default_ergo_tree() {
+                Some(RefreshContractParameters::build_with(c.to_inputs()?)?)
+            } else {
+                Some(RefreshContractParameters::checked_load(c.to_inputs()?)?)
+            }
         } else {
             None
         };
@@ -556,3 +792,49 @@ where
     let s: String = serde::de::Deserialize::deserialize(deserializer)?;
     TokenId::from_base64(&s).map_err(serde::de::Error::custom)
 }
+
+/// Used to (de)serialize `UpdateProposal` instance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct UpdateProposalSerde {
+    pool_contract_parameters: PoolContractParametersSerde,
+    refresh_nft_token_id: TokenId,
+    update_nft_token_id: TokenId,
+    new_pool_box_address_hash: String,
+    reward_token_id: TokenId,
+    reward_token_amount: u64,
+    update_box_creation_height: u32,
+}
+
+impl From<UpdateProposal> for UpdateProposalSerde {
+    fn from(p: UpdateProposal) -> Self {
+        UpdateProposalSerde {
+            pool_contract_parameters: PoolContractParametersSerde::from(p.pool_contract_parameters),
+            refresh_nft_token_id: p.refresh_nft_token_id,
+            update_nft_token_id: p.update_nft_token_id,
+            new_pool_box_address_hash: String::from(p.new_pool_box_address_hash),
+            reward_token_id: p.reward_token_id,
+            reward_token_amount: p.reward_token_amount,
+            update_box_creation_height: p.update_box_creation_height,
+        }
+    }
+}
+
+impl TryFrom<UpdateProposalSerde> for UpdateProposal {
+    type Error = SerdeConversionError;
+    fn try_from(p: UpdateProposalSerde) -> Result<Self, Self::Error> {
+        let pool_contract_parameters = PoolContractParameters::checked_load(
+            base16::decode(p.pool_contract_parameters.ergo_tree_bytes.as_str())?,
+            p.pool_contract_parameters.refresh_nft_index,
+            p.pool_contract_parameters.update_nft_index,
+        )?;
+        Ok(UpdateProposal {
+            pool_contract_parameters,
+            refresh_nft_token_id: p.refresh_nft_token_id,
+            update_nft_token_id: p.update_nft_token_id,
+            new_pool_box_address_hash: Digest32::try_from(p.new_pool_box_address_hash)?,
+            reward_token_id: p.reward_token_id,
+            reward_token_amount: p.reward_token_amount,
+            update_box_creation_height: p.update_box_creation_height,
+        })
+    }
+}
@@ -0,0 +1,80 @@
+//! Graceful shutdown of the daemon. SIGTERM and SIGINT only set a flag the main loop checks, so
+//! the iteration in progress is finished (an action built but not submitted yet is dropped) before
+//! the daemon saves its state and exits. A second signal exits right away.
+//!
+//! When run as a `Type=notify` systemd unit the daemon reports when it is ready and stopping.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Listen for SIGTERM and SIGINT on a separate thread
+pub fn listen_for_shutdown_signals() {
+    thread::spawn(|| {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            loop {
+                wait_for_signal().await;
+                if SHUTDOWN_REQUESTED.swap(true, Ordering::SeqCst) {
+                    log::warn!("Shutdown signal received again, exiting right away");
+                    log::logger().flush();
+                    std::process::exit(exitcode::SOFTWARE);
+                }
+                log::info!("Shutdown requested, finishing the current iteration of the main loop");
+            }
+        });
+    });
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut sigterm = signal(SignalKind::terminate()).unwrap();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => (),
+        _ = sigterm.recv() => (),
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    tokio::signal::ctrl_c().await.unwrap();
+}
+
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Sleep for `duration`, returning early if a shutdown is requested
+pub fn sleep_unless_shutdown(duration: Duration) {
+    let start = Instant::now();
+    while !shutdown_requested() && start.elapsed() < duration {
+        thread::sleep(Duration::from_millis(200));
+    }
+}
+
+/// Send `state` (e.g. `READY=1`) to systemd, if the daemon is run by a `Type=notify` unit
+#[cfg(unix)]
+pub fn notify_systemd(state: &str) {
+    use std::os::unix::net::UnixDatagram;
+    let socket_path = match std::env::var("NOTIFY_SOCKET") {
+        Ok(path) => path,
+        Err(_) => return,
+    };
+    if socket_path.starts_with('@') {
+        log::debug!("Abstract NOTIFY_SOCKET {} is not supported", socket_path);
+        return;
+    }
+    if let Err(e) = UnixDatagram::unbound().and_then(|s| s.send_to(state.as_bytes(), &socket_path))
+    {
+        log::warn!("Failed to notify systemd ({}): {}", state, e);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify_systemd(_state: &str) {}
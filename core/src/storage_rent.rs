@@ -0,0 +1,189 @@
+//! Storage rent of the pool, refresh and update boxes. A box left unspent for [`STORAGE_PERIOD`]
+//! blocks (4 years) can be spent by a miner collecting the storage fee: the box is re-created with
+//! its value lowered by the fee, or destroyed with its tokens (the NFTs of the pool) if its value
+//! doesn't cover the fee. The pool and refresh boxes are re-created by every refresh tx, the update
+//! box only by an update of the pool. The daemon alerts when one of them comes within the renewal
+//! window of its rent height and `renew-storage-rent` re-creates the update box with an update of
+//! the pool to its current contract.
+
+use ergo_lib::ergotree_ir::chain::ergo_box::ErgoBox;
+use ergo_lib::ergotree_ir::serialization::SigmaSerializable;
+use serde::Serialize;
+
+use crate::box_kind::{PoolBox, RefreshBox};
+use crate::oracle_config::StorageRentConfig;
+use crate::oracle_state::{OraclePool, StageError};
+
+/// Blocks after which the storage rent of a box can be collected (4 years of 2 minute blocks)
+pub const STORAGE_PERIOD: u32 = 1_051_200;
+
+/// Storage fee in nanoERGs per byte of the box (`storageFeeFactor` of the network parameters)
+pub const STORAGE_FEE_FACTOR: u64 = 1_250_000;
+
+/// Default `renewal_window_blocks`, 90 days
+pub const DEFAULT_RENEWAL_WINDOW: u32 = 64_800;
+
+/// Blocks between two checks of the daemon, a day
+pub const CHECK_INTERVAL_BLOCKS: u32 = 720;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtocolBox {
+    Pool,
+    Refresh,
+    Update,
+}
+
+impl ProtocolBox {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProtocolBox::Pool => "pool box",
+            ProtocolBox::Refresh => "refresh box",
+            ProtocolBox::Update => "update box",
+        }
+    }
+}
+
+/// Storage rent of a protocol box at a height
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RentStatus {
+    pub protocol_box: ProtocolBox,
+    pub box_id: String,
+    pub creation_height: u32,
+    /// Height from which the storage rent can be collected
+    pub rent_height: u32,
+    /// Negative once the rent can be collected
+    pub blocks_left: i64,
+    pub value: u64,
+    /// Fee collected from the box
+    pub storage_fee: u64,
+}
+
+impl RentStatus {
+    pub fn new(protocol_box: ProtocolBox, b: &ErgoBox, height: u32) -> Self {
+        let rent_height = b.creation_height.saturating_add(STORAGE_PERIOD);
+        let size = b.sigma_serialize_bytes().map_or(0, |bytes| bytes.len()) as u64;
+        RentStatus {
+            protocol_box,
+            box_id: String::from(b.box_id()),
+            creation_height: b.creation_height,
+            rent_height,
+            blocks_left: rent_height as i64 - height as i64,
+            value: *b.value.as_u64(),
+            storage_fee: size * STORAGE_FEE_FACTOR,
+        }
+    }
+
+    /// Whether the rent height is within `window` blocks
+    pub fn due(&self, window: u32) -> bool {
+        self.blocks_left <= window as i64
+    }
+
+    /// Whether collecting the rent destroys the box with its tokens
+    pub fn destroyed_on_collection(&self) -> bool {
+        self.value < self.storage_fee
+    }
+
+    pub fn message(&self) -> String {
+        let outcome = if self.destroyed_on_collection() {
+            "the box and its NFT would be destroyed"
+        } else {
+            "its value would be lowered by the fee"
+        };
+        let when = if self.blocks_left > 0 {
+            format!("in {} blocks", self.blocks_left)
+        } else {
+            "now".to_string()
+        };
+        format!(
+            "The storage rent of the {} {} (created at height {}) can be collected {} (height {}), {}",
+            self.protocol_box.name(),
+            self.box_id,
+            self.creation_height,
+            when,
+            self.rent_height,
+            outcome
+        )
+    }
+}
+
+/// Blocks before the rent height from which the boxes are renewed
+pub fn renewal_window(config: Option<&StorageRentConfig>) -> u32 {
+    config
+        .and_then(|c| c.renewal_window_blocks)
+        .unwrap_or(DEFAULT_RENEWAL_WINDOW)
+}
+
+/// Storage rent of the pool, refresh and update boxes of the pool at `height`
+pub fn protocol_boxes_rent(op: &OraclePool, height: u32) -> Result<Vec<RentStatus>, StageError> {
+    Ok(vec![
+        RentStatus::new(
+            ProtocolBox::Pool,
+            op.get_pool_box_source().get_pool_box()?.get_box(),
+            height,
+        ),
+        RentStatus::new(
+            ProtocolBox::Refresh,
+            op.get_refresh_box_source().get_refresh_box()?.get_box(),
+            height,
+        ),
+        RentStatus::new(
+            ProtocolBox::Update,
+            op.get_update_box_source().get_update_box()?.get_box(),
+            height,
+        ),
+    ])
+}
+
+/// Checks of the storage rent by the daemon, once every [`CHECK_INTERVAL_BLOCKS`]
+#[derive(Debug, Default)]
+pub struct StorageRentMonitor {
+    last_check_height: Option<u32>,
+}
+
+impl StorageRentMonitor {
+    /// Whether the storage rent is checked again at `height`
+    pub fn should_check(&mut self, height: u32) -> bool {
+        let check = self
+            .last_check_height
+            .map_or(true, |last| height >= last + CHECK_INTERVAL_BLOCKS);
+        if check {
+            self.last_check_height = Some(height);
+        }
+        check
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool_commands::test_utils::make_wallet_unspent_box;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+    use sigma_test_util::force_any_val;
+
+    #[test]
+    fn test_rent_status() {
+        // Created at height 1
+        let b = make_wallet_unspent_box(
+            force_any_val::<DlogProverInput>().public_image(),
+            BoxValue::SAFE_USER_MIN,
+            None,
+        );
+        let status = RentStatus::new(ProtocolBox::Update, &b, 1_000_000);
+        assert_eq!(status.rent_height, STORAGE_PERIOD + 1);
+        assert_eq!(status.blocks_left, 51_201);
+        assert!(status.due(DEFAULT_RENEWAL_WINDOW));
+        assert!(!status.due(50_000));
+        assert!(status.destroyed_on_collection());
+        assert!(status.message().contains("in 51201 blocks"));
+        let status = RentStatus::new(ProtocolBox::Update, &b, STORAGE_PERIOD + 10);
+        assert_eq!(status.blocks_left, -9);
+        assert!(status.message().contains("can be collected now"));
+
+        let mut monitor = StorageRentMonitor::default();
+        assert!(monitor.should_check(100));
+        assert!(!monitor.should_check(101));
+        assert!(monitor.should_check(100 + CHECK_INTERVAL_BLOCKS));
+    }
+}
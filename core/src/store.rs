@@ -0,0 +1,793 @@
+//! Embedded SQLite database (`oracle_core.sqlite`) holding the state and the history of the oracle:
+//! the txs submitted by the daemon and the commands with their fees, the datapoints posted, the pool
+//! boxes observed and the alerts raised. The daemon and the commands open the same database, SQLite
+//! serializes their writes. The schema is migrated on open, `user_version` holds its version.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use derive_more::From;
+use ergo_lib::chain::transaction::Transaction;
+use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+use once_cell::sync::OnceCell;
+use rusqlite::{params, Connection, OptionalExtension, Params, Row};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::action_queue::{ActionKind, ActionStatus, QueuedAction};
+use crate::downtime::unix_now;
+use crate::fees::FeeLedger;
+use crate::node_interface::TxId;
+use crate::notifications::NotificationKind;
+use crate::reward_extraction::RewardExtractionWindow;
+
+pub const STORE_FILE: &str = "oracle_core.sqlite";
+
+static STORE: OnceCell<Store> = OnceCell::new();
+
+/// Schema migrations, `MIGRATIONS[i]` migrates the schema from version `i` to `i + 1`
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE meta (
+        key TEXT PRIMARY KEY,
+        value
+    );
+    CREATE TABLE txs (
+        tx_id TEXT PRIMARY KEY,
+        action TEXT NOT NULL,
+        input_box_ids TEXT NOT NULL,
+        fee INTEGER NOT NULL,
+        day TEXT NOT NULL,
+        epoch INTEGER,
+        submitted_at INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        settled_at INTEGER
+    );
+    CREATE INDEX txs_status ON txs (status);
+    CREATE TABLE datapoints (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        epoch INTEGER NOT NULL,
+        datapoint INTEGER NOT NULL,
+        tx_id TEXT NOT NULL,
+        posted_at INTEGER NOT NULL
+    );
+    CREATE TABLE pool_states (
+        epoch INTEGER PRIMARY KEY,
+        rate INTEGER NOT NULL,
+        box_id TEXT NOT NULL,
+        height INTEGER NOT NULL,
+        observed_at INTEGER NOT NULL
+    );
+    CREATE TABLE alerts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        time INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        message TEXT NOT NULL,
+        sent INTEGER NOT NULL
+    );
+",
+    "
+    ALTER TABLE txs ADD COLUMN outputs TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE txs ADD COLUMN height INTEGER;
+    ALTER TABLE txs ADD COLUMN confirmed_height INTEGER;
+",
+    "
+    ALTER TABLE txs ADD COLUMN signed_tx TEXT;
+    ALTER TABLE txs ADD COLUMN resubmissions INTEGER NOT NULL DEFAULT 0;
+",
+    "
+    CREATE TABLE actions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,
+        epoch INTEGER,
+        status TEXT NOT NULL,
+        attempts INTEGER NOT NULL,
+        tx_id TEXT,
+        last_error TEXT,
+        next_attempt_at INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE INDEX actions_status ON actions (status);
+",
+];
+
+const TX_COLUMNS: &str = "tx_id, action, input_box_ids, outputs, fee, day, epoch, height, \
+    submitted_at, status, confirmed_height, settled_at, resubmissions";
+
+const ACTION_COLUMNS: &str = "id, kind, epoch, status, attempts, tx_id, last_error, \
+    next_attempt_at, created_at, updated_at";
+
+/// Milliseconds a write waits for the write of another process
+const BUSY_TIMEOUT_MS: u64 = 5000;
+
+#[derive(Debug, From, Error)]
+pub enum StoreError {
+    #[error("database error: {0}")]
+    Sqlite(rusqlite::Error),
+    #[error("stored value error: {0}")]
+    Json(serde_json::Error),
+    #[error("the database is not open")]
+    NotOpen,
+    #[error("database schema version {0} is newer than this oracle-core")]
+    UnknownSchemaVersion(usize),
+    #[error("unknown tx status {0}")]
+    UnknownTxStatus(String),
+    #[error("unknown stored value {0}")]
+    #[from(ignore)]
+    UnknownValue(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TxStatus {
+    /// Accepted by the node, in the mempool
+    Submitted,
+    Confirmed,
+    /// Left the mempool without being confirmed, its inputs are unspent
+    Evicted,
+    /// Its inputs were spent by another tx
+    Failed,
+}
+
+impl TxStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TxStatus::Submitted => "submitted",
+            TxStatus::Confirmed => "confirmed",
+            TxStatus::Evicted => "evicted",
+            TxStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for TxStatus {
+    type Err = StoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            TxStatus::Submitted,
+            TxStatus::Confirmed,
+            TxStatus::Evicted,
+            TxStatus::Failed,
+        ]
+        .iter()
+        .copied()
+        .find(|status| status.name() == s)
+        .ok_or_else(|| StoreError::UnknownTxStatus(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TxOutput {
+    #[schema(value_type = String)]
+    pub box_id: BoxId,
+    pub value: u64,
+}
+
+/// A tx signed by the node wallet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct TxRecord {
+    pub tx_id: TxId,
+    /// What the tx was built for, e.g. `refresh` or `transfer_oracle_token`
+    pub action: String,
+    #[schema(value_type = Vec<String>)]
+    pub input_box_ids: Vec<BoxId>,
+    pub outputs: Vec<TxOutput>,
+    /// Miner fee in nanoERG
+    pub fee: u64,
+    /// UTC day (`YYYY-MM-DD`) of the submission
+    pub day: String,
+    /// Epoch of the pool last observed by the daemon
+    pub epoch: Option<u32>,
+    /// Height of the chain at the submission
+    pub height: Option<u32>,
+    /// Unix time in seconds
+    pub submitted_at: u64,
+    pub status: TxStatus,
+    /// Height of the block including the tx, if known
+    pub confirmed_height: Option<u32>,
+    /// Unix time in seconds the status was settled
+    pub settled_at: Option<u64>,
+    /// Times the tx was resubmitted after an eviction
+    pub resubmissions: u32,
+}
+
+impl TxRecord {
+    fn from_row(row: &Row) -> Result<Self, StoreError> {
+        Ok(TxRecord {
+            tx_id: row.get(0)?,
+            action: row.get(1)?,
+            input_box_ids: serde_json::from_str(&row.get::<_, String>(2)?)?,
+            outputs: serde_json::from_str(&row.get::<_, String>(3)?)?,
+            fee: row.get::<_, i64>(4)? as u64,
+            day: row.get(5)?,
+            epoch: row.get(6)?,
+            height: row.get(7)?,
+            submitted_at: row.get::<_, i64>(8)? as u64,
+            status: row.get::<_, String>(9)?.parse()?,
+            confirmed_height: row.get(10)?,
+            settled_at: row.get::<_, Option<i64>>(11)?.map(|t| t as u64),
+            resubmissions: row.get(12)?,
+        })
+    }
+
+    /// A tx submitted in `epoch` without outputs
+    #[cfg(test)]
+    pub fn submitted(tx_id: &str, input_box_ids: Vec<BoxId>, fee: u64, epoch: Option<u32>) -> Self {
+        TxRecord {
+            tx_id: tx_id.to_string(),
+            action: "refresh".to_string(),
+            input_box_ids,
+            outputs: Vec::new(),
+            fee,
+            day: "2023-11-14".to_string(),
+            epoch,
+            height: Some(1000),
+            submitted_at: 1_700_000_000,
+            status: TxStatus::Submitted,
+            confirmed_height: None,
+            settled_at: None,
+            resubmissions: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PostedDatapoint {
+    pub epoch: u32,
+    pub datapoint: i64,
+    pub tx_id: TxId,
+    /// Unix time in seconds
+    pub posted_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ObservedPoolState {
+    pub epoch: u32,
+    pub rate: u64,
+    pub box_id: String,
+    /// Creation height of the pool box
+    pub height: u32,
+    /// Unix time in seconds of the first observation
+    pub observed_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Alert {
+    /// Unix time in seconds
+    pub time: u64,
+    pub kind: NotificationKind,
+    pub message: String,
+    /// Whether it was sent to the notification targets
+    pub sent: bool,
+}
+
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+impl Store {
+    /// Open the database at `path`, creating it if it doesn't exist
+    pub fn open(path: &Path) -> Result<Self, StoreError> {
+        let mut conn = Connection::open(path)?;
+        conn.busy_timeout(std::time::Duration::from_millis(BUSY_TIMEOUT_MS))?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        migrate(&mut conn)?;
+        Ok(Store {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Write a consistent copy of the database to `path`, which must not exist. Safe while the
+    /// daemon writes to the database.
+    pub fn backup_to(&self, path: &Path) -> Result<(), StoreError> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// Epoch of the pool last observed by the daemon
+    pub fn epoch(&self) -> Result<Option<u32>, StoreError> {
+        Ok(self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT value FROM meta WHERE key = 'epoch'", [], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    pub fn set_epoch(&self, epoch: u32) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('epoch', ?1)",
+            params![epoch],
+        )?;
+        Ok(())
+    }
+
+    /// Reward extraction window of the daemon, kept across restarts for `max_tokens_per_day`
+    pub fn reward_extraction_window(&self) -> Result<Option<RewardExtractionWindow>, StoreError> {
+        let value: Option<String> = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'reward_extraction_window'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value.map(|v| serde_json::from_str(&v)).transpose()?)
+    }
+
+    pub fn set_reward_extraction_window(
+        &self,
+        window: &RewardExtractionWindow,
+    ) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('reward_extraction_window', ?1)",
+            params![serde_json::to_string(window)?],
+        )?;
+        Ok(())
+    }
+
+    /// Record `tx`, with the `signed_tx` to resubmit it if it is evicted
+    pub fn record_tx(
+        &self,
+        tx: &TxRecord,
+        signed_tx: Option<&Transaction>,
+    ) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute(
+            &format!(
+                "INSERT OR IGNORE INTO txs ({}, signed_tx)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                TX_COLUMNS
+            ),
+            params![
+                tx.tx_id,
+                tx.action,
+                serde_json::to_string(&tx.input_box_ids)?,
+                serde_json::to_string(&tx.outputs)?,
+                tx.fee as i64,
+                tx.day,
+                tx.epoch,
+                tx.height,
+                tx.submitted_at as i64,
+                tx.status.name(),
+                tx.confirmed_height,
+                tx.settled_at.map(|t| t as i64),
+                tx.resubmissions,
+                signed_tx.map(serde_json::to_string).transpose()?
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn settle_tx(
+        &self,
+        tx_id: &str,
+        status: TxStatus,
+        confirmed_height: Option<u32>,
+    ) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE txs SET status = ?2, confirmed_height = ?3, settled_at = ?4 WHERE tx_id = ?1",
+            params![tx_id, status.name(), confirmed_height, unix_now() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// The signed tx recorded with `tx_id`
+    pub fn signed_tx(&self, tx_id: &str) -> Result<Option<Transaction>, StoreError> {
+        let signed_tx: Option<String> = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT signed_tx FROM txs WHERE tx_id = ?1",
+                params![tx_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(signed_tx.map(|tx| serde_json::from_str(&tx)).transpose()?)
+    }
+
+    /// The evicted tx was resubmitted, it is in the mempool again
+    pub fn mark_resubmitted(&self, tx_id: &str) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE txs SET status = ?2, resubmissions = resubmissions + 1, settled_at = NULL
+            WHERE tx_id = ?1",
+            params![tx_id, TxStatus::Submitted.name()],
+        )?;
+        Ok(())
+    }
+
+    /// The submitted txs still in the mempool, oldest first
+    pub fn pending_txs(&self) -> Result<Vec<TxRecord>, StoreError> {
+        self.select_txs(
+            "WHERE status = ?1 ORDER BY submitted_at, rowid",
+            params![TxStatus::Submitted.name()],
+        )
+    }
+
+    /// The tx recorded with `tx_id`
+    pub fn tx(&self, tx_id: &str) -> Result<Option<TxRecord>, StoreError> {
+        Ok(self.select_txs("WHERE tx_id = ?1", params![tx_id])?.pop())
+    }
+
+    /// The last `limit` txs, only the ones with `status` if given, newest first
+    pub fn txs(&self, status: Option<TxStatus>, limit: u32) -> Result<Vec<TxRecord>, StoreError> {
+        self.select_txs(
+            "WHERE ?1 IS NULL OR status = ?1 ORDER BY submitted_at DESC, rowid DESC LIMIT ?2",
+            params![status.map(|s| s.name()), limit],
+        )
+    }
+
+    fn select_txs(&self, clauses: &str, params: impl Params) -> Result<Vec<TxRecord>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM txs {}", TX_COLUMNS, clauses))?;
+        let mut rows = stmt.query(params)?;
+        let mut txs = Vec::new();
+        while let Some(row) = rows.next()? {
+            txs.push(TxRecord::from_row(row)?);
+        }
+        Ok(txs)
+    }
+
+    /// The fees of all the recorded txs
+    pub fn fee_ledger(&self) -> Result<FeeLedger, StoreError> {
+        let mut ledger = FeeLedger::default();
+        {
+            let conn = self.conn.lock().unwrap();
+            let mut stmt =
+                conn.prepare("SELECT action, fee, day, epoch FROM txs ORDER BY rowid")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<u32>>(3)?,
+                ))
+            })?;
+            for row in rows {
+                let (action, fee, day, epoch) = row?;
+                ledger.record_in_epoch(&action, fee as u64, day, epoch);
+            }
+        }
+        ledger.epoch = self.epoch()?;
+        Ok(ledger)
+    }
+
+    pub fn record_datapoint(
+        &self,
+        epoch: u32,
+        datapoint: i64,
+        tx_id: &str,
+    ) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO datapoints (epoch, datapoint, tx_id, posted_at) VALUES (?1, ?2, ?3, ?4)",
+            params![epoch, datapoint, tx_id, unix_now() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// The last `limit` posted datapoints, newest first
+    pub fn datapoints(&self, limit: u32) -> Result<Vec<PostedDatapoint>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT epoch, datapoint, tx_id, posted_at FROM datapoints ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok(PostedDatapoint {
+                epoch: row.get(0)?,
+                datapoint: row.get(1)?,
+                tx_id: row.get(2)?,
+                posted_at: row.get::<_, i64>(3)? as u64,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Record the pool box of `epoch`, only its first observation is kept
+    pub fn record_pool_state(
+        &self,
+        epoch: u32,
+        rate: u64,
+        box_id: &BoxId,
+        height: u32,
+    ) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO pool_states (epoch, rate, box_id, height, observed_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                epoch,
+                rate as i64,
+                String::from(box_id.clone()),
+                height,
+                unix_now() as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The pool boxes of the last `limit` observed epochs, newest first
+    pub fn pool_states(&self, limit: u32) -> Result<Vec<ObservedPoolState>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT epoch, rate, box_id, height, observed_at FROM pool_states
+            ORDER BY epoch DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok(ObservedPoolState {
+                epoch: row.get(0)?,
+                rate: row.get::<_, i64>(1)? as u64,
+                box_id: row.get(2)?,
+                height: row.get(3)?,
+                observed_at: row.get::<_, i64>(4)? as u64,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    pub fn record_alert(
+        &self,
+        kind: NotificationKind,
+        message: &str,
+        sent: bool,
+    ) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO alerts (time, kind, message, sent) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                unix_now() as i64,
+                serde_json::to_string(&kind)?,
+                message,
+                sent
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The last `limit` alerts, newest first
+    pub fn alerts(&self, limit: u32) -> Result<Vec<Alert>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT time, kind, message, sent FROM alerts ORDER BY id DESC LIMIT ?1")?;
+        let rows = stmt.query_map(params![limit], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, bool>(3)?,
+            ))
+        })?;
+        let mut alerts = Vec::new();
+        for row in rows {
+            let (time, kind, message, sent) = row?;
+            alerts.push(Alert {
+                time: time as u64,
+                kind: serde_json::from_str(&kind)?,
+                message,
+                sent,
+            });
+        }
+        Ok(alerts)
+    }
+
+    /// Queue an action of `kind` in `epoch`, due now
+    pub fn insert_action(
+        &self,
+        kind: ActionKind,
+        epoch: Option<u32>,
+        now: u64,
+    ) -> Result<QueuedAction, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO actions (kind, epoch, status, attempts, next_attempt_at, created_at,
+            updated_at) VALUES (?1, ?2, ?3, 0, ?4, ?4, ?4)",
+            params![kind.name(), epoch, ActionStatus::Queued.name(), now as i64],
+        )?;
+        Ok(QueuedAction {
+            id: conn.last_insert_rowid(),
+            kind,
+            epoch,
+            status: ActionStatus::Queued,
+            attempts: 0,
+            tx_id: None,
+            last_error: None,
+            next_attempt_at: now,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Write the status, attempts, tx, error and times of `action`
+    pub fn update_action(&self, action: &QueuedAction) -> Result<(), StoreError> {
+        self.conn.lock().unwrap().execute(
+            "UPDATE actions SET status = ?2, attempts = ?3, tx_id = ?4, last_error = ?5,
+            next_attempt_at = ?6, updated_at = ?7 WHERE id = ?1",
+            params![
+                action.id,
+                action.status.name(),
+                action.attempts,
+                action.tx_id,
+                action.last_error,
+                action.next_attempt_at as i64,
+                action.updated_at as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The queued or submitted action of `kind` in `epoch`
+    pub fn open_action(
+        &self,
+        kind: ActionKind,
+        epoch: Option<u32>,
+    ) -> Result<Option<QueuedAction>, StoreError> {
+        Ok(self
+            .select_actions(
+                "WHERE kind = ?1 AND epoch IS ?2 AND status IN ('queued', 'submitted')
+                ORDER BY id DESC LIMIT 1",
+                params![kind.name(), epoch],
+            )?
+            .pop())
+    }
+
+    /// The action with `id`
+    pub fn action(&self, id: i64) -> Result<Option<QueuedAction>, StoreError> {
+        Ok(self.select_actions("WHERE id = ?1", params![id])?.pop())
+    }
+
+    /// The submitted action of the tx `tx_id`
+    pub fn action_of_tx(&self, tx_id: &str) -> Result<Option<QueuedAction>, StoreError> {
+        Ok(self
+            .select_actions("WHERE tx_id = ?1 AND status = 'submitted'", params![tx_id])?
+            .pop())
+    }
+
+    /// The queued and submitted actions, oldest first
+    pub fn open_actions(&self) -> Result<Vec<QueuedAction>, StoreError> {
+        self.select_actions("WHERE status IN ('queued', 'submitted') ORDER BY id", [])
+    }
+
+    fn select_actions(
+        &self,
+        clauses: &str,
+        params: impl Params,
+    ) -> Result<Vec<QueuedAction>, StoreError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM actions {}",
+            ACTION_COLUMNS, clauses
+        ))?;
+        let mut rows = stmt.query(params)?;
+        let mut actions = Vec::new();
+        while let Some(row) = rows.next()? {
+            actions.push(action_from_row(row)?);
+        }
+        Ok(actions)
+    }
+}
+
+fn action_from_row(row: &Row) -> Result<QueuedAction, StoreError> {
+    Ok(QueuedAction {
+        id: row.get(0)?,
+        kind: ActionKind::from_name(&row.get::<_, String>(1)?)?,
+        epoch: row.get(2)?,
+        status: ActionStatus::from_name(&row.get::<_, String>(3)?)?,
+        attempts: row.get(4)?,
+        tx_id: row.get(5)?,
+        last_error: row.get(6)?,
+        next_attempt_at: row.get::<_, i64>(7)? as u64,
+        created_at: row.get::<_, i64>(8)? as u64,
+        updated_at: row.get::<_, i64>(9)? as u64,
+    })
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), StoreError> {
+    let version = conn.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))? as usize;
+    if version > MIGRATIONS.len() {
+        return Err(StoreError::UnknownSchemaVersion(version));
+    }
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", (i + 1) as i64)?;
+        tx.commit()?;
+        log::info!("Migrated the database schema to version {}", i + 1);
+    }
+    Ok(())
+}
+
+/// Open the database at `path` for the rest of the process
+pub fn init(path: &Path) -> Result<(), StoreError> {
+    let store = Store::open(path)?;
+    let _ = STORE.set(store);
+    Ok(())
+}
+
+/// The database opened by [`init`]
+pub fn get() -> Result<&'static Store, StoreError> {
+    STORE.get().ok_or(StoreError::NotOpen)
+}
+
+/// Record an alert if the database is open, failures are only logged
+pub fn record_alert(kind: NotificationKind, message: &str, sent: bool) {
+    if let Ok(store) = get() {
+        if let Err(e) = store.record_alert(kind, message, sent) {
+            log::warn!("Failed to record the {} alert: {}", kind, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+
+    #[test]
+    fn test_store() {
+        let store = Store::open(Path::new(":memory:")).unwrap();
+        let box_id = force_any_val::<BoxId>();
+        store
+            .record_tx(
+                &TxRecord::submitted("tx0", vec![box_id.clone()], 1_000_000, None),
+                None,
+            )
+            .unwrap();
+        store.set_epoch(7).unwrap();
+        assert_eq!(store.reward_extraction_window().unwrap(), None);
+        let window = RewardExtractionWindow {
+            start: 1000,
+            extracted: 50,
+            last_extraction_epoch: Some(6),
+        };
+        store.set_reward_extraction_window(&window).unwrap();
+        assert_eq!(store.reward_extraction_window().unwrap(), Some(window));
+        let tx1 = TxRecord {
+            outputs: vec![TxOutput {
+                box_id: box_id.clone(),
+                value: 1_000_000,
+            }],
+            ..TxRecord::submitted("tx1", vec![box_id.clone()], 2_000_000, Some(7))
+        };
+        store.record_tx(&tx1, None).unwrap();
+        store
+            .settle_tx("tx0", TxStatus::Confirmed, Some(1001))
+            .unwrap();
+        assert_eq!(store.pending_txs().unwrap(), vec![tx1.clone()]);
+        let confirmed = store.txs(Some(TxStatus::Confirmed), 10).unwrap();
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(confirmed[0].confirmed_height, Some(1001));
+        assert_eq!(store.txs(None, 1).unwrap(), vec![tx1]);
+        let ledger = store.fee_ledger().unwrap();
+        assert_eq!(ledger.epoch, Some(7));
+        assert_eq!(ledger.total.nanoergs, 3_000_000);
+        assert_eq!(ledger.current_epoch().txs, 1);
+
+        store.record_datapoint(7, 100, "tx2").unwrap();
+        store.record_datapoint(8, 101, "tx3").unwrap();
+        let datapoints = store.datapoints(1).unwrap();
+        assert_eq!(datapoints.len(), 1);
+        assert_eq!(datapoints[0].datapoint, 101);
+
+        store.record_pool_state(7, 100, &box_id, 1000).unwrap();
+        store.record_pool_state(7, 200, &box_id, 1001).unwrap();
+        let pool_states = store.pool_states(10).unwrap();
+        assert_eq!(pool_states.len(), 1);
+        assert_eq!(pool_states[0].rate, 100);
+
+        store
+            .record_alert(NotificationKind::EpochMissed, "missed", true)
+            .unwrap();
+        let alerts = store.alerts(10).unwrap();
+        assert_eq!(alerts[0].kind, NotificationKind::EpochMissed);
+        assert!(alerts[0].sent);
+    }
+}
@@ -0,0 +1,318 @@
+//! History of the txs signed by the node wallet, by the daemon or by a command. Every submitted tx
+//! is recorded in the store with its action, inputs, outputs and the height it was submitted at.
+//! The daemon polls the node for the txs in the mempool and settles them as confirmed (with the
+//! height of their block), evicted (left the mempool with their inputs unspent) or failed (their
+//! inputs were spent by another tx). `tx-history` prints the history, `/txs` of the REST API
+//! serves it.
+//!
+//! An evicted tx of the daemon is resubmitted as it was signed if all its inputs are unspent and
+//! its action still applies: a datapoint only in the epoch it was posted in, a refresh as long as
+//! the pool box is unspent. A tx is resubmitted at most `MAX_RESUBMISSIONS` times.
+
+use derive_more::From;
+use ergo_lib::chain::transaction::Transaction;
+use ergo_node_interface::node_interface::NodeError;
+use thiserror::Error;
+
+use crate::action_queue;
+use crate::downtime::unix_now;
+use crate::fees::{today, tx_fee};
+use crate::metrics::METRICS;
+use crate::node_interface::{current_block_height, SubmitTransaction, TxId};
+use crate::reorg::ChainSource;
+use crate::store::{self, Store, StoreError, TxOutput, TxRecord, TxStatus};
+
+/// Times an evicted tx is resubmitted
+const MAX_RESUBMISSIONS: u32 = 3;
+
+/// Actions of the daemon resubmitted when evicted
+const RESUBMITTED_ACTIONS: [&str; 3] = ["publish_datapoint", "refresh", "reward_extraction"];
+
+#[derive(Debug, From, Error)]
+pub enum TxHistoryError {
+    #[error("store error: {0}")]
+    Store(StoreError),
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+}
+
+/// Record the tx submitted for `action` in the store. Failures are only logged, the tx is submitted
+/// already.
+pub fn record_submitted_tx(action: &str, tx: &Transaction) {
+    let fee = tx_fee(tx);
+    let res = store::get().and_then(|store| {
+        let record = TxRecord {
+            tx_id: String::from(tx.id().0),
+            action: action.to_string(),
+            input_box_ids: tx.inputs.iter().map(|i| i.box_id.clone()).collect(),
+            outputs: tx
+                .outputs
+                .iter()
+                .map(|b| TxOutput {
+                    box_id: b.box_id(),
+                    value: *b.value.as_u64(),
+                })
+                .collect(),
+            fee,
+            day: today(),
+            epoch: store.epoch()?,
+            height: current_block_height().ok().map(|h| h as u32),
+            submitted_at: unix_now(),
+            status: TxStatus::Submitted,
+            confirmed_height: None,
+            settled_at: None,
+            resubmissions: 0,
+        };
+        store.record_tx(&record, Some(tx))?;
+        store.fee_ledger()
+    });
+    match res {
+        Ok(ledger) => {
+            log::debug!("Paid a fee of {} nanoERG for {}", fee, action);
+            METRICS.set_fee_totals(ledger.by_action);
+        }
+        Err(e) => log::warn!("Failed to record the {} tx: {}", action, e),
+    }
+}
+
+/// Status of the submitted `tx` and the height of its block if confirmed
+pub fn tx_status(
+    chain: &dyn ChainSource,
+    tx: &TxRecord,
+    unconfirmed_tx_ids: &[TxId],
+) -> Result<(TxStatus, Option<u32>), NodeError> {
+    if unconfirmed_tx_ids.contains(&tx.tx_id) {
+        return Ok((TxStatus::Submitted, None));
+    }
+    if let Some(height) = chain.tx_inclusion_height(&tx.tx_id)? {
+        return Ok((TxStatus::Confirmed, Some(height)));
+    }
+    // Evicted only if all its inputs are unspent, a single spent input means that another tx
+    // consumed it
+    let mut all_inputs_unspent = !tx.input_box_ids.is_empty();
+    for box_id in &tx.input_box_ids {
+        if !chain.is_box_unspent(box_id)? {
+            all_inputs_unspent = false;
+            break;
+        }
+    }
+    if all_inputs_unspent {
+        return Ok((TxStatus::Evicted, None));
+    }
+    // Not known to the wallet, confirmed if one of its outputs exists
+    for output in &tx.outputs {
+        if chain.is_box_unspent(&output.box_id)? {
+            return Ok((TxStatus::Confirmed, None));
+        }
+    }
+    Ok((TxStatus::Failed, None))
+}
+
+/// Whether the evicted `tx` still applies with the pool in `epoch`
+fn should_resubmit(
+    chain: &dyn ChainSource,
+    tx: &TxRecord,
+    epoch: Option<u32>,
+) -> Result<bool, NodeError> {
+    if !RESUBMITTED_ACTIONS.contains(&tx.action.as_str()) || tx.resubmissions >= MAX_RESUBMISSIONS {
+        return Ok(false);
+    }
+    // The oracle box of a datapoint tx is unspent in the next epoch too
+    if tx.action == "publish_datapoint" && (tx.epoch.is_none() || tx.epoch != epoch) {
+        return Ok(false);
+    }
+    for box_id in &tx.input_box_ids {
+        if !chain.is_box_unspent(box_id)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Resubmit the evicted `tx` if it still applies, returns whether it was resubmitted
+fn resubmit_evicted(
+    chain: &dyn ChainSource,
+    submitter: &dyn SubmitTransaction,
+    store: &Store,
+    tx: &TxRecord,
+) -> Result<bool, TxHistoryError> {
+    if !should_resubmit(chain, tx, store.epoch()?)? {
+        return Ok(false);
+    }
+    let signed_tx = match store.signed_tx(&tx.tx_id)? {
+        Some(signed_tx) => signed_tx,
+        None => return Ok(false),
+    };
+    match submitter.submit_transaction(&signed_tx) {
+        Ok(_) => {
+            store.mark_resubmitted(&tx.tx_id)?;
+            log::info!(
+                "The {} tx {} was evicted from the mempool, resubmitted it ({} of {})",
+                tx.action,
+                tx.tx_id,
+                tx.resubmissions + 1,
+                MAX_RESUBMISSIONS
+            );
+            Ok(true)
+        }
+        Err(e) => {
+            log::warn!(
+                "Failed to resubmit the evicted {} tx {}: {}",
+                tx.action,
+                tx.tx_id,
+                e
+            );
+            Ok(false)
+        }
+    }
+}
+
+/// Settle the txs of the store which left the mempool, the evicted txs of the daemon are
+/// resubmitted if they still apply. Returns the settled txs and their status.
+pub fn poll_tx_statuses(
+    chain: &dyn ChainSource,
+    submitter: &dyn SubmitTransaction,
+    store: &Store,
+) -> Result<Vec<(TxRecord, TxStatus)>, TxHistoryError> {
+    let pending_txs = store.pending_txs()?;
+    if pending_txs.is_empty() {
+        return Ok(Vec::new());
+    }
+    let unconfirmed_tx_ids = chain.unconfirmed_tx_ids()?;
+    let mut settled = Vec::new();
+    for tx in pending_txs {
+        let (status, confirmed_height) = tx_status(chain, &tx, &unconfirmed_tx_ids)?;
+        match status {
+            TxStatus::Submitted => continue,
+            TxStatus::Confirmed => log::info!(
+                "The {} tx {} was confirmed at height {}",
+                tx.action,
+                tx.tx_id,
+                confirmed_height.map_or("?".to_string(), |h| h.to_string())
+            ),
+            TxStatus::Evicted => {
+                if resubmit_evicted(chain, submitter, store, &tx)? {
+                    continue;
+                }
+                log::warn!(
+                    "The {} tx {} was evicted from the mempool, its inputs are unspent",
+                    tx.action,
+                    tx.tx_id
+                )
+            }
+            TxStatus::Failed => log::warn!(
+                "The {} tx {} failed, its inputs were spent by another tx",
+                tx.action,
+                tx.tx_id
+            ),
+        }
+        store.settle_tx(&tx.tx_id, status, confirmed_height)?;
+        action_queue::tx_settled(store, &tx.tx_id, status)?;
+        settled.push((tx, status));
+    }
+    Ok(settled)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use ergo_lib::ergotree_ir::chain::ergo_box::BoxId;
+    use sigma_test_util::force_any_val;
+
+    use super::*;
+    use crate::cli_commands::bootstrap::tests::SubmitTxMock;
+    use crate::reorg::tests::ChainMock;
+
+    #[test]
+    fn test_poll_tx_statuses() {
+        let store = Store::open(Path::new(":memory:")).unwrap();
+        let chain = ChainMock::default();
+        let input_box_ids: Vec<BoxId> = (0..4).map(|_| force_any_val::<BoxId>()).collect();
+        let output_box_id = force_any_val::<BoxId>();
+        for (i, input_box_id) in input_box_ids.iter().enumerate() {
+            let mut tx =
+                TxRecord::submitted(&format!("tx{}", i), vec![input_box_id.clone()], 1, None);
+            if i == 3 {
+                tx.outputs.push(TxOutput {
+                    box_id: output_box_id.clone(),
+                    value: 1_000_000,
+                });
+            }
+            store.record_tx(&tx, None).unwrap();
+        }
+        let partly_spent_box_ids: Vec<BoxId> = (0..2).map(|_| force_any_val::<BoxId>()).collect();
+        store
+            .record_tx(
+                &TxRecord::submitted("tx4", partly_spent_box_ids.clone(), 1, None),
+                None,
+            )
+            .unwrap();
+        // tx0 in the mempool, tx1 evicted, tx2 spent by another tx, tx3 confirmed unknown to the
+        // wallet, one of the inputs of tx4 spent by another tx
+        chain
+            .unconfirmed_tx_ids
+            .borrow_mut()
+            .push("tx0".to_string());
+        chain.unspent_box_ids.borrow_mut().extend(vec![
+            input_box_ids[1].clone(),
+            output_box_id,
+            partly_spent_box_ids[0].clone(),
+        ]);
+        let settled = poll_tx_statuses(&chain, &SubmitTxMock::default(), &store).unwrap();
+        let statuses: Vec<(&str, TxStatus)> = settled
+            .iter()
+            .map(|(tx, status)| (tx.tx_id.as_str(), *status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("tx1", TxStatus::Evicted),
+                ("tx2", TxStatus::Failed),
+                ("tx3", TxStatus::Confirmed),
+                ("tx4", TxStatus::Failed)
+            ]
+        );
+
+        chain.unconfirmed_tx_ids.borrow_mut().clear();
+        chain
+            .inclusion_heights
+            .borrow_mut()
+            .insert("tx0".to_string(), 1001);
+        poll_tx_statuses(&chain, &SubmitTxMock::default(), &store).unwrap();
+        let confirmed = store.txs(Some(TxStatus::Confirmed), 10).unwrap();
+        assert_eq!(confirmed.len(), 2);
+        assert!(confirmed
+            .iter()
+            .any(|tx| tx.tx_id == "tx0" && tx.confirmed_height == Some(1001)));
+        assert!(store.pending_txs().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_resubmit_evicted_datapoint_tx() {
+        let store = Store::open(Path::new(":memory:")).unwrap();
+        let chain = ChainMock::default();
+        let submitter = SubmitTxMock::default();
+        let input_box_id = force_any_val::<BoxId>();
+        let tx = TxRecord {
+            action: "publish_datapoint".to_string(),
+            ..TxRecord::submitted("tx", vec![input_box_id.clone()], 1, Some(7))
+        };
+        store
+            .record_tx(&tx, Some(&force_any_val::<Transaction>()))
+            .unwrap();
+        store.set_epoch(7).unwrap();
+        chain.unspent_box_ids.borrow_mut().push(input_box_id);
+
+        assert!(poll_tx_statuses(&chain, &submitter, &store)
+            .unwrap()
+            .is_empty());
+        let pending_txs = store.pending_txs().unwrap();
+        assert_eq!(pending_txs[0].resubmissions, 1);
+
+        // The datapoint doesn't apply in the next epoch
+        store.set_epoch(8).unwrap();
+        let settled = poll_tx_statuses(&chain, &submitter, &store).unwrap();
+        assert_eq!(settled[0].1, TxStatus::Evicted);
+    }
+}
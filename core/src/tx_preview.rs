@@ -0,0 +1,308 @@
+//! Human readable preview of a tx, printed by the commands before asking for the confirmation. The
+//! inputs and outputs are listed with their address, value, tokens (named when they are tokens of
+//! the pool) and registers, followed by the fee. The contracts are shown by the hash of their
+//! ergo tree, the P2S addresses of the pool contracts being too long to read.
+
+use std::fmt::Write;
+
+use ergo_lib::{
+    chain::transaction::unsigned::UnsignedTransaction,
+    ergo_chain_types::{blake2b256_hash, EcPoint},
+    ergotree_ir::{
+        chain::{
+            address::{Address, NetworkAddress, NetworkPrefix},
+            ergo_box::{ErgoBox, NonMandatoryRegisterId},
+            token::TokenId,
+        },
+        ergo_tree::ErgoTree,
+        mir::constant::{Constant, TryExtractInto},
+        serialization::SigmaSerializable,
+        sigma_protocol::sigma_boolean::ProveDlog,
+    },
+    wallet::miner_fee::MINERS_FEE_ADDRESS,
+};
+
+use crate::fees::unsigned_tx_fee;
+use crate::node_interface::get_box_with_pool;
+use crate::oracle_config::{TokenIds, MAYBE_ORACLE_CONFIG};
+
+const REGISTERS: [NonMandatoryRegisterId; 6] = [
+    NonMandatoryRegisterId::R4,
+    NonMandatoryRegisterId::R5,
+    NonMandatoryRegisterId::R6,
+    NonMandatoryRegisterId::R7,
+    NonMandatoryRegisterId::R8,
+    NonMandatoryRegisterId::R9,
+];
+
+/// `nanoergs` as ERGs with the 9 decimals
+pub fn format_erg(nanoergs: u64) -> String {
+    format!(
+        "{}.{:09} ERG",
+        nanoergs / 1_000_000_000,
+        nanoergs % 1_000_000_000
+    )
+}
+
+/// Preview of `tx`, the input boxes fetched from the node (unspent or in the mempool)
+pub fn tx_preview(tx: &UnsignedTransaction, network: NetworkPrefix) -> String {
+    let input_boxes = tx
+        .inputs
+        .iter()
+        .map(|input| {
+            get_box_with_pool(&String::from(input.box_id.clone()))
+                .ok()
+                .flatten()
+        })
+        .collect::<Vec<_>>();
+    let token_ids = MAYBE_ORACLE_CONFIG
+        .as_ref()
+        .ok()
+        .map(|config| &config.token_ids);
+    render_tx_preview(tx, &input_boxes, network, token_ids)
+}
+
+/// Preview of `tx` spending the `input_boxes` (`None` for the ones unknown to the node), the
+/// tokens of the pool with `token_ids` being named
+pub fn render_tx_preview(
+    tx: &UnsignedTransaction,
+    input_boxes: &[Option<ErgoBox>],
+    network: NetworkPrefix,
+    token_ids: Option<&TokenIds>,
+) -> String {
+    let mut preview = String::new();
+    let input_total: Option<u64> = input_boxes
+        .iter()
+        .map(|b| b.as_ref().map(|b| *b.value.as_u64()))
+        .sum();
+    writeln!(preview, "INPUTS").unwrap();
+    for (index, (input, input_box)) in tx.inputs.iter().zip(input_boxes).enumerate() {
+        match input_box {
+            Some(input_box) => write_box(&mut preview, index, input_box, network, token_ids),
+            None => writeln!(
+                preview,
+                "  #{:<2} {} (box unknown to the node)",
+                index,
+                String::from(input.box_id.clone())
+            )
+            .unwrap(),
+        }
+    }
+    if let Some(data_inputs) = &tx.data_inputs {
+        writeln!(preview, "DATA INPUTS").unwrap();
+        for (index, data_input) in data_inputs.iter().enumerate() {
+            writeln!(
+                preview,
+                "  #{:<2} {}",
+                index,
+                String::from(data_input.box_id.clone())
+            )
+            .unwrap();
+        }
+    }
+    writeln!(preview, "OUTPUTS").unwrap();
+    let tx_id = tx.id();
+    for (index, candidate) in tx.output_candidates.iter().enumerate() {
+        if let Ok(output_box) = ErgoBox::from_box_candidate(candidate, tx_id.clone(), index as u16)
+        {
+            write_box(&mut preview, index, &output_box, network, token_ids);
+        }
+    }
+    if let Some(input_total) = input_total {
+        writeln!(preview, "TOTAL IN  {}", format_erg(input_total)).unwrap();
+    }
+    write!(preview, "FEE       {}", format_erg(unsigned_tx_fee(tx))).unwrap();
+    preview
+}
+
+fn write_box(
+    preview: &mut String,
+    index: usize,
+    b: &ErgoBox,
+    network: NetworkPrefix,
+    token_ids: Option<&TokenIds>,
+) {
+    writeln!(
+        preview,
+        "  #{:<2} {:>24}  {}",
+        index,
+        format_erg(*b.value.as_u64()),
+        describe_tree(&b.ergo_tree, network)
+    )
+    .unwrap();
+    if let Some(tokens) = &b.tokens {
+        for token in tokens.iter() {
+            let name = token_ids
+                .and_then(|token_ids| token_name(&token.token_id, token_ids))
+                .map(|name| format!(" ({})", name))
+                .unwrap_or_default();
+            writeln!(
+                preview,
+                "       token {} x {}{}",
+                String::from(token.token_id.clone()),
+                token.amount.as_u64(),
+                name
+            )
+            .unwrap();
+        }
+    }
+    for register in REGISTERS {
+        if let Some(value) = b.get_register(register.into()) {
+            writeln!(
+                preview,
+                "       {:?} {}",
+                register,
+                describe_constant(&value, network)
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Address of the P2PK trees, otherwise the hash of the contract
+fn describe_tree(tree: &ErgoTree, network: NetworkPrefix) -> String {
+    if MINERS_FEE_ADDRESS
+        .script()
+        .map_or(false, |fee_tree| &fee_tree == tree)
+    {
+        return "miner fee".to_string();
+    }
+    match Address::recreate_from_ergo_tree(tree) {
+        Ok(address @ Address::P2Pk(_)) => NetworkAddress::new(network, &address).to_base58(),
+        _ => match tree.sigma_serialize_bytes() {
+            Ok(bytes) => format!(
+                "contract {}",
+                base16::encode_lower(&blake2b256_hash(&bytes))
+            ),
+            Err(_) => "contract (unparsable ergo tree)".to_string(),
+        },
+    }
+}
+
+fn token_name(token_id: &TokenId, token_ids: &TokenIds) -> Option<&'static str> {
+    [
+        (&token_ids.pool_nft_token_id, "pool NFT"),
+        (&token_ids.refresh_nft_token_id, "refresh NFT"),
+        (&token_ids.update_nft_token_id, "update NFT"),
+        (&token_ids.oracle_token_id, "oracle token"),
+        (&token_ids.reward_token_id, "reward token"),
+        (&token_ids.ballot_token_id, "ballot token"),
+    ]
+    .iter()
+    .find(|entry| entry.0 == token_id)
+    .map(|entry| entry.1)
+}
+
+/// Value of a register, the public keys as their address
+fn describe_constant(value: &Constant, network: NetworkPrefix) -> String {
+    if let Ok(v) = value.clone().try_extract_into::<i32>() {
+        return format!("Int {}", v);
+    }
+    if let Ok(v) = value.clone().try_extract_into::<i64>() {
+        return format!("Long {}", v);
+    }
+    if let Ok(pk) = value.clone().try_extract_into::<EcPoint>() {
+        let address = Address::P2Pk(ProveDlog::from(pk));
+        return format!(
+            "public key of {}",
+            NetworkAddress::new(network, &address).to_base58()
+        );
+    }
+    if let Ok(bytes) = value.clone().try_extract_into::<Vec<u8>>() {
+        return format!("bytes {}", base16::encode_lower(&bytes));
+    }
+    format!("{:?}", value.tpe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pool_commands::test_utils::{
+        generate_token_ids, make_datapoint_box, make_wallet_unspent_box,
+    };
+    use ergo_lib::chain::ergo_box::box_builder::ErgoBoxCandidateBuilder;
+    use ergo_lib::ergotree_interpreter::sigma_protocol::private_input::DlogProverInput;
+    use ergo_lib::ergotree_ir::chain::ergo_box::box_value::BoxValue;
+    use ergo_lib::wallet::box_selector::{BoxSelector, SimpleBoxSelector};
+    use ergo_lib::wallet::tx_builder::{TxBuilder, SUGGESTED_TX_FEE};
+    use sigma_test_util::force_any_val;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_tx_preview() {
+        assert_eq!(format_erg(1_100_000), "0.001100000 ERG");
+        assert_eq!(format_erg(12_000_000_001), "12.000000001 ERG");
+
+        let token_ids = generate_token_ids();
+        let secret = force_any_val::<DlogProverInput>();
+        let address = Address::P2Pk(secret.public_image());
+        let address_str = NetworkAddress::new(NetworkPrefix::Mainnet, &address).to_base58();
+        let oracle_box = make_datapoint_box(
+            *secret.public_image().h,
+            200,
+            3,
+            &token_ids,
+            BoxValue::SAFE_USER_MIN,
+            1,
+        );
+        let wallet_box = make_wallet_unspent_box(
+            secret.public_image(),
+            BoxValue::try_from(100_000_000u64).unwrap(),
+            None,
+        );
+        let mut output =
+            ErgoBoxCandidateBuilder::new(BoxValue::SAFE_USER_MIN, oracle_box.ergo_tree.clone(), 1);
+        for token in oracle_box.tokens.clone().unwrap().iter() {
+            output.add_token(token.clone());
+        }
+        let target_balance = BoxValue::SAFE_USER_MIN
+            .checked_add(&SUGGESTED_TX_FEE())
+            .unwrap();
+        let selection = SimpleBoxSelector::new()
+            .select(
+                vec![oracle_box.clone(), wallet_box.clone()],
+                target_balance,
+                &oracle_box.tokens.clone().unwrap().as_vec().clone(),
+            )
+            .unwrap();
+        let tx = TxBuilder::new(
+            selection,
+            vec![output.build().unwrap()],
+            1,
+            SUGGESTED_TX_FEE(),
+            address.clone(),
+        )
+        .build()
+        .unwrap();
+        let input_boxes = tx
+            .inputs
+            .iter()
+            .map(|input| {
+                vec![oracle_box.clone(), wallet_box.clone()]
+                    .into_iter()
+                    .find(|b| b.box_id() == input.box_id)
+            })
+            .collect::<Vec<_>>();
+        let preview =
+            render_tx_preview(&tx, &input_boxes, NetworkPrefix::Mainnet, Some(&token_ids));
+        assert!(preview.starts_with("INPUTS\n"));
+        assert!(preview.contains(&format!(
+            "token {} x 1 (oracle token)",
+            String::from(token_ids.oracle_token_id.clone())
+        )));
+        assert!(preview.contains("R5 Int 3"));
+        assert!(preview.contains("R6 Long 200"));
+        assert!(preview.contains(&format!("R4 public key of {}", address_str)));
+        assert!(preview.contains(&format!("{}\n", address_str)));
+        assert!(preview.contains("miner fee"));
+        assert!(preview.ends_with(&format!(
+            "FEE       {}",
+            format_erg(*SUGGESTED_TX_FEE().as_u64())
+        )));
+
+        // The boxes unknown to the node are listed by id
+        let preview = render_tx_preview(&tx, &[None, None], NetworkPrefix::Mainnet, None);
+        assert!(preview.contains("(box unknown to the node)"));
+        assert!(!preview.contains("TOTAL IN"));
+    }
+}
@@ -0,0 +1,448 @@
+//! Validation of the txs before they are signed and submitted. The contract of every input is
+//! reduced locally against the current state of the chain (the last 10 headers of the node) and
+//! the inputs reducing to false are reported, with the clauses of the pool contracts (pool,
+//! refresh and oracle) the tx violates, instead of the node's bare "Script reduced to false". The
+//! inputs reducing to a signature (the wallet boxes, the oracle box of a published datapoint) are
+//! left to the node signing the tx.
+//!
+//! A tx failing the validation isn't submitted. The tx is submitted without validation when its
+//! input boxes or the headers can't be fetched from the node.
+
+use std::convert::TryInto;
+use std::fmt;
+use std::rc::Rc;
+
+use derive_more::From;
+use ergo_lib::{
+    chain::{ergo_state_context::ErgoStateContext, transaction::unsigned::UnsignedTransaction},
+    ergo_chain_types::{Header, PreHeader},
+    ergotree_interpreter::eval::{env::Env, reduce_to_crypto},
+    ergotree_ir::{
+        chain::{
+            ergo_box::{box_value::BoxValue, ErgoBox, NonMandatoryRegisterId},
+            token::TokenId,
+        },
+        mir::constant::TryExtractInto,
+        sigma_protocol::sigma_boolean::SigmaBoolean,
+    },
+    wallet::signing::{make_context, TransactionContext, TransactionContextError},
+};
+use ergo_node_interface::node_interface::NodeError;
+use thiserror::Error;
+
+use crate::{
+    contracts::refresh::RefreshContractParameters,
+    node_interface::{get_box_with_pool, get_last_headers},
+    oracle_config::{OracleConfig, TokenIds, MAYBE_ORACLE_CONFIG},
+};
+
+/// Headers of the state context
+const STATE_CONTEXT_HEADERS: usize = 10;
+
+#[derive(Debug, Error, From)]
+pub enum TxValidationError {
+    #[error("node error: {0}")]
+    Node(#[source] NodeError),
+    #[error("transaction context error: {0}")]
+    TransactionContext(TransactionContextError),
+    #[error("box {0} is neither unspent nor in the mempool")]
+    #[from(ignore)]
+    UnknownBox(String),
+    #[error("the node returned {0} headers, expected 10")]
+    #[from(ignore)]
+    MissingHeaders(usize),
+    #[error(
+        "the tx fails the local validation, it was not submitted:\n{}",
+        failures_report(.0)
+    )]
+    #[from(ignore)]
+    FailedInputs(Vec<InputFailure>),
+}
+
+/// Parameters of the pool the contract clauses are checked against
+#[derive(Debug, Clone)]
+pub struct PoolParameters {
+    pub token_ids: TokenIds,
+    pub refresh_contract_parameters: RefreshContractParameters,
+    pub oracle_min_storage_rent: BoxValue,
+}
+
+impl From<&OracleConfig> for PoolParameters {
+    fn from(config: &OracleConfig) -> Self {
+        PoolParameters {
+            token_ids: config.token_ids.clone(),
+            refresh_contract_parameters: config
+                .refresh_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .clone(),
+            oracle_min_storage_rent: config
+                .oracle_box_wrapper_inputs
+                .contract_inputs
+                .contract_parameters()
+                .min_storage_rent,
+        }
+    }
+}
+
+/// Kind of an input box, from its first token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    Pool,
+    Refresh,
+    Oracle,
+    Ballot,
+    Update,
+    Other,
+}
+
+impl InputKind {
+    fn of_box(b: &ErgoBox, token_ids: &TokenIds) -> Self {
+        match first_token_id(b) {
+            Some(id) if id == token_ids.pool_nft_token_id => InputKind::Pool,
+            Some(id) if id == token_ids.refresh_nft_token_id => InputKind::Refresh,
+            Some(id) if id == token_ids.oracle_token_id => InputKind::Oracle,
+            Some(id) if id == token_ids.ballot_token_id => InputKind::Ballot,
+            Some(id) if id == token_ids.update_nft_token_id => InputKind::Update,
+            _ => InputKind::Other,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputKind::Pool => "pool box",
+            InputKind::Refresh => "refresh box",
+            InputKind::Oracle => "oracle box",
+            InputKind::Ballot => "ballot box",
+            InputKind::Update => "update box",
+            InputKind::Other => "box",
+        }
+    }
+}
+
+/// Input whose contract doesn't hold in the tx
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputFailure {
+    pub index: usize,
+    pub box_id: String,
+    pub kind: InputKind,
+    /// Evaluation error of the contract, `None` if it reduced to false
+    pub error: Option<String>,
+    /// Clauses of the contract the tx violates
+    pub violated_clauses: Vec<String>,
+}
+
+impl fmt::Display for InputFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "input {} ({} {}) ",
+            self.index,
+            self.kind.name(),
+            self.box_id
+        )?;
+        match &self.error {
+            Some(error) => write!(f, "failed to evaluate: {}", error)?,
+            None => write!(f, "reduced to false")?,
+        }
+        for clause in &self.violated_clauses {
+            write!(f, "\n  violated: {}", clause)?;
+        }
+        Ok(())
+    }
+}
+
+fn failures_report(failures: &[InputFailure]) -> String {
+    failures
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// State context of the next block, from the last 10 headers of the node
+pub fn current_state_context() -> Result<ErgoStateContext, TxValidationError> {
+    let mut headers = get_last_headers(STATE_CONTEXT_HEADERS as u32)?;
+    // The node returns the oldest header first, the state context holds the newest first
+    headers.reverse();
+    let last_header = headers
+        .first()
+        .cloned()
+        .ok_or(TxValidationError::MissingHeaders(0))?;
+    let mut pre_header = PreHeader::from(last_header.clone());
+    pre_header.parent_id = last_header.id;
+    pre_header.height = last_header.height + 1;
+    let num_headers = headers.len();
+    let headers: [Header; STATE_CONTEXT_HEADERS] = headers
+        .try_into()
+        .map_err(|_| TxValidationError::MissingHeaders(num_headers))?;
+    Ok(ErgoStateContext::new(pre_header, headers))
+}
+
+/// Reduce the inputs of `tx` spending `input_boxes` (with the `data_boxes`) in the
+/// `state_context`, returns the inputs reducing to false
+pub fn validate_tx(
+    tx: &UnsignedTransaction,
+    input_boxes: &[ErgoBox],
+    data_boxes: &[ErgoBox],
+    state_context: &ErgoStateContext,
+    pool_parameters: Option<&PoolParameters>,
+) -> Result<Vec<InputFailure>, TxValidationError> {
+    let tx_context =
+        TransactionContext::new(tx.clone(), input_boxes.to_vec(), data_boxes.to_vec())?;
+    let mut failures = Vec::new();
+    for (index, input_box) in input_boxes.iter().enumerate() {
+        let error = match reduce_input(&tx_context, state_context, index, input_box) {
+            Ok(SigmaBoolean::TrivialProp(false)) => None,
+            Ok(_) => continue,
+            Err(e) => Some(e),
+        };
+        let kind = pool_parameters.map_or(InputKind::Other, |p| {
+            InputKind::of_box(input_box, &p.token_ids)
+        });
+        let violated_clauses = pool_parameters.map_or_else(Vec::new, |p| {
+            violated_clauses(kind, index, tx, input_boxes, state_context, p)
+        });
+        failures.push(InputFailure {
+            index,
+            box_id: String::from(input_box.box_id()),
+            kind,
+            error,
+            violated_clauses,
+        });
+    }
+    Ok(failures)
+}
+
+fn reduce_input(
+    tx_context: &TransactionContext<UnsignedTransaction>,
+    state_context: &ErgoStateContext,
+    index: usize,
+    input_box: &ErgoBox,
+) -> Result<SigmaBoolean, String> {
+    let ctx = make_context(state_context, tx_context, index).map_err(|e| e.to_string())?;
+    let proposition = input_box
+        .ergo_tree
+        .proposition()
+        .map_err(|e| format!("{:?}", e))?;
+    reduce_to_crypto(&proposition, &Env::empty(), Rc::new(ctx))
+        .map(|res| res.sigma_prop)
+        .map_err(|e| e.to_string())
+}
+
+/// Validate `tx` against the current state of the chain before it is signed. The inputs failing
+/// the validation are logged and make the tx rejected, the tx is let through if it can't be
+/// validated.
+pub fn check_tx_before_submit(tx: &UnsignedTransaction) -> Result<(), TxValidationError> {
+    let pool_parameters = MAYBE_ORACLE_CONFIG.as_ref().ok().map(PoolParameters::from);
+    let res = fetch_boxes(tx).and_then(|(input_boxes, data_boxes)| {
+        validate_tx(
+            tx,
+            &input_boxes,
+            &data_boxes,
+            &current_state_context()?,
+            pool_parameters.as_ref(),
+        )
+    });
+    match res {
+        Ok(failures) if failures.is_empty() => Ok(()),
+        Ok(failures) => {
+            log::error!(
+                "The tx fails the local validation:\n{}",
+                failures_report(&failures)
+            );
+            Err(TxValidationError::FailedInputs(failures))
+        }
+        Err(e) => {
+            log::warn!("Submitting the tx without local validation: {}", e);
+            Ok(())
+        }
+    }
+}
+
+/// Input and data boxes of `tx`, unspent or created by a tx in the mempool
+fn fetch_boxes(
+    tx: &UnsignedTransaction,
+) -> Result<(Vec<ErgoBox>, Vec<ErgoBox>), TxValidationError> {
+    let fetch =
+        |box_id: String| get_box_with_pool(&box_id)?.ok_or(TxValidationError::UnknownBox(box_id));
+    let input_boxes = tx
+        .inputs
+        .iter()
+        .map(|input| fetch(String::from(input.box_id.clone())))
+        .collect::<Result<Vec<_>, _>>()?;
+    let data_boxes = tx
+        .data_inputs
+        .iter()
+        .flat_map(|data_inputs| data_inputs.iter())
+        .map(|data_input| fetch(String::from(data_input.box_id.clone())))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((input_boxes, data_boxes))
+}
+
+fn first_token_id(b: &ErgoBox) -> Option<TokenId> {
+    b.tokens
+        .as_ref()
+        .map(|tokens| tokens.first().token_id.clone())
+}
+
+fn register_i32(b: &ErgoBox, register: NonMandatoryRegisterId) -> Option<i32> {
+    b.get_register(register.into())
+        .and_then(|r| r.try_extract_into::<i32>().ok())
+}
+
+fn register_i64(b: &ErgoBox, register: NonMandatoryRegisterId) -> Option<i64> {
+    b.get_register(register.into())
+        .and_then(|r| r.try_extract_into::<i64>().ok())
+}
+
+/// Clauses of the contract of the input `index` (of `kind`) the tx violates
+fn violated_clauses(
+    kind: InputKind,
+    index: usize,
+    tx: &UnsignedTransaction,
+    input_boxes: &[ErgoBox],
+    state_context: &ErgoStateContext,
+    pool_parameters: &PoolParameters,
+) -> Vec<String> {
+    let token_ids = &pool_parameters.token_ids;
+    let outputs = tx.output_candidates.as_vec();
+    let output_token_id = |i: usize| {
+        outputs
+            .get(i)
+            .and_then(|b| b.tokens.as_ref())
+            .map(|tokens| tokens.first().token_id.clone())
+    };
+    let input_token_id = |i: usize| input_boxes.get(i).and_then(first_token_id);
+    let mut clauses = Vec::new();
+    match kind {
+        InputKind::Pool => {
+            let second_input = input_token_id(1);
+            if second_input != Some(token_ids.refresh_nft_token_id.clone())
+                && second_input != Some(token_ids.update_nft_token_id.clone())
+            {
+                clauses.push("the refresh box or the update box is the second input".to_string());
+            }
+            if index != 0 || output_token_id(0) != Some(token_ids.pool_nft_token_id.clone()) {
+                clauses.push("the pool box is the first input and the first output".to_string());
+            }
+        }
+        InputKind::Refresh => {
+            let params = &pool_parameters.refresh_contract_parameters;
+            let height = state_context.pre_header.height;
+            let pool_box = input_boxes
+                .first()
+                .filter(|b| first_token_id(b) == Some(token_ids.pool_nft_token_id.clone()));
+            let pool_box = match pool_box {
+                Some(pool_box) => pool_box,
+                None => {
+                    clauses.push("the pool box is the first input".to_string());
+                    return clauses;
+                }
+            };
+            let epoch_length = params.epoch_length().max(0) as u32;
+            if pool_box.creation_height >= height.saturating_sub(epoch_length) {
+                clauses.push(format!(
+                    "the epoch is over: the pool box (height {}) is older than {} blocks at height {}",
+                    pool_box.creation_height, epoch_length, height
+                ));
+            }
+            let epoch_counter = register_i32(pool_box, NonMandatoryRegisterId::R5);
+            let datapoints = input_boxes
+                .iter()
+                .filter(|b| first_token_id(b) == Some(token_ids.oracle_token_id.clone()))
+                .collect::<Vec<_>>();
+            if (datapoints.len() as i32) < params.min_data_points() {
+                clauses.push(format!(
+                    "at least {} datapoints are collected, the tx spends {}",
+                    params.min_data_points(),
+                    datapoints.len()
+                ));
+            }
+            if datapoints
+                .iter()
+                .any(|b| register_i32(b, NonMandatoryRegisterId::R5) != epoch_counter)
+            {
+                clauses.push(
+                    "the datapoints are posted in the epoch of the pool box (R5 of the oracle boxes)"
+                        .to_string(),
+                );
+            }
+            let rates = datapoints
+                .iter()
+                .map(|b| register_i64(b, NonMandatoryRegisterId::R6).unwrap_or_default())
+                .collect::<Vec<_>>();
+            if rates.windows(2).any(|w| w[0] > w[1]) {
+                clauses.push("the datapoint inputs are sorted by rate".to_string());
+            }
+            if let (Some(min), Some(max)) = (rates.iter().min(), rates.iter().max()) {
+                let max_deviation_percent = params.max_deviation_percent() as i128;
+                if *min as i128 * (100 + max_deviation_percent) < *max as i128 * 100 {
+                    clauses.push(format!(
+                        "the datapoints deviate by at most {}% (from {} to {})",
+                        max_deviation_percent, min, max
+                    ));
+                }
+            }
+            if output_token_id(0) != Some(token_ids.pool_nft_token_id.clone()) {
+                clauses.push("the pool box is the first output".to_string());
+            }
+            let refresh_out = outputs.get(1).filter(|b| {
+                b.tokens.as_ref().map(|t| t.first().token_id.clone())
+                    == Some(token_ids.refresh_nft_token_id.clone())
+            });
+            if refresh_out.map_or(true, |b| b.ergo_tree != input_boxes[index].ergo_tree) {
+                clauses.push(
+                    "the refresh box is the second output, with the refresh contract".to_string(),
+                );
+            }
+        }
+        InputKind::Oracle => {
+            // Spent by the refresh tx
+            if input_token_id(0) == Some(token_ids.pool_nft_token_id.clone()) {
+                return clauses;
+            }
+            let oracle_box = &input_boxes[index];
+            let out_index = tx
+                .inputs
+                .get(index)
+                .and_then(|input| input.extension.values.get(&0).cloned())
+                .and_then(|v| v.try_extract_into::<i32>().ok());
+            let out_index = match out_index {
+                Some(out_index) => out_index,
+                None => {
+                    clauses.push(
+                        "context variable 0 holds the index of the output of the oracle box"
+                            .to_string(),
+                    );
+                    return clauses;
+                }
+            };
+            let is_copy = outputs.get(out_index as usize).map_or(false, |out| {
+                let in_tokens = oracle_box.tokens.as_ref().map(|t| t.as_vec().clone());
+                let out_tokens = out.tokens.as_ref().map(|t| t.as_vec().clone());
+                match (in_tokens, out_tokens) {
+                    (Some(in_tokens), Some(out_tokens)) => {
+                        out_tokens.len() == 2
+                            && in_tokens.len() >= 2
+                            && out_tokens[0] == in_tokens[0]
+                            && out_tokens[1].token_id == in_tokens[1].token_id
+                            && out.ergo_tree == oracle_box.ergo_tree
+                            && out.value >= pool_parameters.oracle_min_storage_rent
+                    }
+                    _ => false,
+                }
+            });
+            if !is_copy {
+                clauses.push(format!(
+                    "output {} (context variable 0) is a copy of the oracle box: oracle token, \
+                    reward token id, contract and at least {} nanoERGs",
+                    out_index,
+                    pool_parameters.oracle_min_storage_rent.as_u64()
+                ));
+            }
+        }
+        InputKind::Ballot | InputKind::Update | InputKind::Other => (),
+    }
+    clauses
+}
@@ -4,11 +4,13 @@ use ergo_node_interface::node_interface::NodeError;
 use thiserror::Error;
 
 use crate::node_interface;
+use crate::oracle_config::ORACLE_CONFIG;
+use crate::pending_outputs::PENDING_OUTPUTS;
 
 #[derive(Debug, Error, From)]
 pub enum WalletDataError {
     #[error("node error: {0}")]
-    NodeError(NodeError),
+    NodeError(#[source] NodeError),
 }
 
 pub trait WalletDataSource {
@@ -25,6 +27,12 @@ impl WalletData {
 
 impl WalletDataSource for WalletData {
     fn get_unspent_wallet_boxes(&self) -> Result<Vec<ErgoBox>, WalletDataError> {
-        node_interface::get_unspent_wallet_boxes().map_err(Into::into)
+        let oracle_address_tree = ORACLE_CONFIG.oracle_address.address().script().ok();
+        // Change outputs of the pending txs of the daemon are spendable (chained txs)
+        Ok(
+            PENDING_OUTPUTS.apply(node_interface::get_unspent_wallet_boxes()?, |b| {
+                oracle_address_tree.as_ref() == Some(&b.ergo_tree)
+            }),
+        )
     }
 }
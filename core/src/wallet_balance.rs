@@ -0,0 +1,117 @@
+//! Monitoring of the nanoERG balance of the node wallet paying the tx fees, so the automated
+//! posting never silently stops on an empty wallet. The fee burn is measured from the balance at
+//! the start of the last epochs, the operator is alerted when the balance or the epochs it still
+//! covers go below the `low_balance_alert` thresholds.
+
+use std::collections::VecDeque;
+
+/// Epochs covered by the balance below which the operator is alerted without `low_balance_alert`
+/// in the config
+pub const DEFAULT_MIN_EPOCHS: u64 = 50;
+
+/// Balances kept to measure the fee burn
+const BURN_WINDOW: usize = 10;
+
+pub struct BalanceMonitor {
+    min_nanoergs: Option<u64>,
+    min_epochs: Option<u64>,
+    /// Burn per epoch assumed until two epochs were observed
+    fallback_burn: u64,
+    /// Balance at the first observation of each epoch, oldest first
+    samples: VecDeque<(u32, u64)>,
+    alerted: bool,
+}
+
+impl BalanceMonitor {
+    pub fn new(min_nanoergs: Option<u64>, min_epochs: Option<u64>, fallback_burn: u64) -> Self {
+        BalanceMonitor {
+            min_nanoergs,
+            min_epochs,
+            fallback_burn,
+            samples: VecDeque::new(),
+            alerted: false,
+        }
+    }
+
+    /// Average nanoERGs spent per epoch over the last epochs, the fallback before two epochs were
+    /// observed
+    pub fn burn_per_epoch(&self) -> u64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some((first_epoch, first_balance)), Some((last_epoch, last_balance)))
+                if last_epoch > first_epoch =>
+            {
+                first_balance.saturating_sub(*last_balance) / (last_epoch - first_epoch) as u64
+            }
+            (Some(_), Some(_)) | (None, _) | (_, None) => self.fallback_burn,
+        }
+    }
+
+    /// Epochs the balance still covers at the current burn, `None` if nothing is burnt
+    pub fn epochs_remaining(&self, balance: u64) -> Option<u64> {
+        let burn = self.burn_per_epoch();
+        (burn > 0).then(|| balance / burn)
+    }
+
+    /// Observe the balance of the wallet in `epoch`. Returns the alert message when the balance
+    /// goes below a threshold, again only after it went back above (top-up).
+    pub fn observe(&mut self, epoch: u32, balance: u64) -> Option<String> {
+        match self.samples.back() {
+            Some((last_epoch, _)) if *last_epoch >= epoch => (),
+            Some((_, last_balance)) if balance > *last_balance => {
+                // Top-up, the burn is measured again from here
+                self.samples.clear();
+                self.samples.push_back((epoch, balance));
+            }
+            Some(_) | None => {
+                self.samples.push_back((epoch, balance));
+                while self.samples.len() > BURN_WINDOW {
+                    self.samples.pop_front();
+                }
+            }
+        }
+        let epochs_remaining = self.epochs_remaining(balance);
+        let low = self.min_nanoergs.map_or(false, |min| balance < min)
+            || self
+                .min_epochs
+                .zip(epochs_remaining)
+                .map_or(false, |(min, remaining)| remaining < min);
+        let alert = low && !self.alerted;
+        self.alerted = low;
+        alert.then(|| {
+            format!(
+                "Low wallet balance: {} nanoERG left, about {} epochs at {} nanoERG per epoch. Top up the wallet to keep posting.",
+                balance,
+                epochs_remaining.map_or("unlimited".to_string(), |e| e.to_string()),
+                self.burn_per_epoch()
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance_monitor() {
+        let mut monitor = BalanceMonitor::new(None, Some(10), 1_000_000);
+        assert_eq!(monitor.observe(1, 100_000_000), None);
+        assert_eq!(monitor.burn_per_epoch(), 1_000_000);
+        assert_eq!(monitor.observe(2, 95_000_000), None);
+        assert_eq!(monitor.burn_per_epoch(), 5_000_000);
+        assert_eq!(monitor.epochs_remaining(95_000_000), Some(19));
+        // Same epoch, not sampled
+        assert_eq!(monitor.observe(2, 90_000_000), None);
+        assert_eq!(monitor.burn_per_epoch(), 5_000_000);
+        assert!(monitor.observe(3, 45_000_000).is_some());
+        // Alerted once
+        assert_eq!(monitor.observe(4, 40_000_000), None);
+        // Top-up
+        assert_eq!(monitor.observe(5, 1_000_000_000), None);
+        assert_eq!(monitor.burn_per_epoch(), 1_000_000);
+
+        let mut monitor = BalanceMonitor::new(Some(5_000_000), None, 0);
+        assert_eq!(monitor.epochs_remaining(1_000), None);
+        assert!(monitor.observe(1, 1_000).is_some());
+    }
+}
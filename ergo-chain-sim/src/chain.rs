@@ -53,14 +53,12 @@ impl ChainSim {
         // TODO: verify tx signatures
         // TODO: verify tx using all the checks from https://github.com/ergoplatform/ergo/blob/1935c95560a30b19cdb52c1a291e8a389ba63c97/src/main/scala/org/ergoplatform/modifiers/mempool/ErgoTransaction.scala#L80-L384
         //
-        dbg!("removing spent boxes from UTXO: {:?}", &boxes_to_spend);
         self.unspent_boxes = self
             .unspent_boxes
             .clone()
             .into_iter()
             .filter(|b| !boxes_to_spend.as_vec().contains(b))
             .collect();
-        dbg!("adding tx outputs to UTXO: {:?}", &tx.outputs);
         self.unspent_boxes.append(tx.outputs.to_vec().as_mut());
         self.all_boxes.append(tx.outputs.to_vec().as_mut());
     }
@@ -106,6 +104,11 @@ impl ChainSim {
         self.all_boxes.push(b);
     }
 
+    /// Returns all unspent boxes (UTXO set)
+    pub fn unspent_boxes(&self) -> &[ErgoBox] {
+        &self.unspent_boxes
+    }
+
     /// Returns unspent boxes guarder by the given ErgoTree
     pub fn get_unspent_boxes(&self, ergo_tree: &ErgoTree) -> Vec<ErgoBox> {
         self.unspent_boxes